@@ -0,0 +1,178 @@
+/// bouncing_ball: a ball bouncing on the floor, a hybrid system with events
+///
+/// Usage: cargo run --release --example bouncing_ball -- [--restitution 0.9] [--plot ball.svg]
+///
+/// The ball falls freely until its height turns negative, the guard of the only
+/// mode (see `runge_kutta::hybrid`), where the bounce reverses its velocity and
+/// scales it by the coefficient of restitution e. The apexes of the flights are
+/// recorded along the way as events (velocity going from positive to
+/// non-positive). Dropped from rest at h, the ball lands at t_1 = sqrt(2 h / g),
+/// its k-th bounce comes at t_1 (1 + 2 e + ... + 2 e^(k-1)) and its k-th apex
+/// reaches h e^(2k), so the located switches and events are checked against
+/// these. The bounces pile up before t_1 (1 + e) / (1 - e), where the ball comes
+/// to rest after infinitely many of them (Zeno behaviour): integrating past it
+/// runs into the limit on the number of switches, reported as an error.
+///
+/// The coefficient of restitution is carried as a constant third component of the
+/// state, since the dynamics and reset maps of the modes are plain functions.
+///
+// === Begin Imports ===
+// third party imports
+use clap::{Parser, ValueEnum};
+use integration_station::prelude::*;
+use integration_station::runge_kutta::hybrid::{HybridSystem, Mode, Transition};
+use integration_station::runge_kutta::stopping::{Direction, Event};
+
+// local imports
+mod common;
+use common::{series_plot, timed};
+
+// Standard library imports
+use std::path::PathBuf;
+use std::process;
+
+// === End Imports ===
+
+// Gravitational acceleration (m/s^2)
+const GRAVITY: f64 = 9.81;
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum Method {
+    Rkf45,
+    CashKarp45,
+    Dopri78,
+}
+
+#[derive(Debug, Parser)]
+#[command(about = "Integrates a ball bouncing on the floor")]
+struct Args {
+    /// Integrator
+    #[arg(long, value_enum, default_value_t = Method::Dopri78)]
+    method: Method,
+    /// Height the ball is dropped from (m)
+    #[arg(long, default_value_t = 10.0)]
+    height: f64,
+    /// Coefficient of restitution of the bounces
+    #[arg(long, default_value_t = 0.8)]
+    restitution: f64,
+    /// Final time (s)
+    #[arg(long, default_value_t = 10.0)]
+    t_end: f64,
+    /// Number of bounces after which the integration errors
+    #[arg(long, default_value_t = 100)]
+    max_bounces: usize,
+    /// Longest step (s). The flights are exact parabolas, which the integrators
+    /// would cross in a step or two, so this sets the resolution of the plot
+    #[arg(long, default_value_t = 0.02)]
+    max_step: f64,
+    /// Absolute tolerance of the integrator
+    #[arg(long, default_value_t = 1e-10)]
+    atol: f64,
+    /// Relative tolerance of the integrator
+    #[arg(long, default_value_t = 1e-10)]
+    rtol: f64,
+    /// Plot of the height against time to write (.png or .svg)
+    #[arg(long)]
+    plot: Option<PathBuf>,
+}
+
+fn main() {
+    let args = Args::parse();
+    if let Err(e) = bouncing_ball(&args) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+// Free fall of the state [height, velocity, restitution]
+fn flight(_t: f64, y: &Vector3<f64>) -> Vector3<f64> {
+    Vector3::new(y[1], -GRAVITY, 0.0)
+}
+
+// Bounce off the floor
+fn bounce(_t: f64, y: &Vector3<f64>) -> Vector3<f64> {
+    Vector3::new(0.0, -y[2] * y[1], y[2])
+}
+
+fn bouncing_ball(args: &Args) -> Result<(), &'static str> {
+    if !(0.0..1.0).contains(&args.restitution) || args.height <= 0.0 {
+        return Err("[BALL] The restitution must be in [0, 1) and the height positive");
+    }
+    let ball = HybridSystem::new(vec![Mode {
+        name: "flight".to_string(),
+        dynamics: flight,
+        transitions: vec![Transition {
+            guard: |_t, y| y[0],
+            target: 0,
+            reset: bounce,
+        }],
+    }])?
+    .with_max_switches(args.max_bounces);
+    let apex = Event {
+        direction: Direction::Falling,
+        ..Event::new(|_t, y: &Vector3<f64>| y[1])
+    };
+    let options = IntegOptions {
+        atol: Some(Vector3::repeat(args.atol)),
+        rtol: Some(args.rtol),
+        max_step: Some(args.max_step),
+        events: Some(vec![apex]),
+        ..IntegOptions::default()
+    };
+    let y_0 = Vector3::new(args.height, 0.0, args.restitution);
+
+    let (ans, ms) = timed(|| match args.method {
+        Method::Rkf45 => ball.integrate(&*RKF45, 0, 0.0, y_0, args.t_end, options),
+        Method::CashKarp45 => ball.integrate(&*CASH_KARP45, 0, 0.0, y_0, args.t_end, options),
+        Method::Dopri78 => ball.integrate(&*DOPRI78, 0, 0.0, y_0, args.t_end, options),
+    });
+    let ans = ans?;
+
+    let e = args.restitution;
+    let landing = (2.0 * args.height / GRAVITY).sqrt();
+    let bounce_error = ans
+        .switches
+        .iter()
+        .scan((landing, landing), |(t_k, flight), switch| {
+            let error = (switch.t - *t_k).abs();
+            *flight *= e;
+            *t_k += 2.0 * *flight;
+            Some(error)
+        })
+        .fold(0.0, f64::max);
+    let apex_error = ans
+        .results
+        .events
+        .iter()
+        .enumerate()
+        .map(|(k, hit)| (hit.state[0] - args.height * e.powi(2 * (k as i32 + 1))).abs())
+        .fold(0.0, f64::max);
+    println!("method:       {:?}", args.method);
+    println!("steps:        {}", ans.results.times.len() - 1);
+    println!("time:         {:.1} ms", ms);
+    println!(
+        "bounces:      {} (max time error {:.2e} s)",
+        ans.switches.len(),
+        bounce_error
+    );
+    println!(
+        "apexes:       {} (max height error {:.2e} m)",
+        ans.results.events.len(),
+        apex_error
+    );
+    println!("rest time:    {:.6} s", landing * (1.0 + e) / (1.0 - e));
+    println!(
+        "final height: {:.6} m at t = {}",
+        ans.results.last_y()[0],
+        ans.results.t
+    );
+
+    let title = format!("Bouncing ball (e = {})", e);
+    series_plot(
+        &ans.results,
+        &[0],
+        &["height"],
+        &title,
+        args.plot.as_deref(),
+    )
+}
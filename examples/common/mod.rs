@@ -0,0 +1,135 @@
+// not every example uses every helper
+#![allow(dead_code)]
+
+/// Shared helpers of the examples
+///
+/// Plots are drawn with `utils::plot` when the examples are built with the
+/// `plotters` feature (`cargo run --example lorenz --features plotters -- --plot
+/// lorenz.png`). Without it, asking for a plot is an error rather than silently
+/// ignored.
+///
+// === Begin Imports ===
+// third party imports
+use integration_station::prelude::*;
+
+// Standard library imports
+use std::path::Path;
+use std::time::Instant;
+
+// === End Imports ===
+
+// Plots component j against component i of the solution into `path`, if given
+pub fn phase_plot<N: Dim + DimName>(
+    results: &IntegResult<N>,
+    (i, j): (usize, usize),
+    labels: &[&str],
+    title: &str,
+    path: Option<&Path>,
+) -> Result<(), &'static str>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    match path {
+        Some(path) => draw::phase_plane(results, (i, j), labels, title, path),
+        None => Ok(()),
+    }
+}
+
+// Plots the components of the solution against time into `path`, if given
+pub fn series_plot<N: Dim + DimName>(
+    results: &IntegResult<N>,
+    components: &[usize],
+    labels: &[&str],
+    title: &str,
+    path: Option<&Path>,
+) -> Result<(), &'static str>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    match path {
+        Some(path) => draw::time_series(results, components, labels, title, path),
+        None => Ok(()),
+    }
+}
+
+// Runs `f`, returning its result with the wall clock time it took in milliseconds
+pub fn timed<R, F: FnOnce() -> R>(f: F) -> (R, f64) {
+    let start = Instant::now();
+    let res = f();
+    (res, start.elapsed().as_secs_f64() * 1e3)
+}
+
+#[cfg(feature = "plotters")]
+mod draw {
+    use integration_station::prelude::*;
+    use integration_station::utils::plot::{plot_phase_plane, plot_time_series, PlotOptions};
+    use std::path::Path;
+
+    fn options(labels: &[&str], title: &str) -> PlotOptions {
+        PlotOptions {
+            title: title.to_string(),
+            labels: labels.iter().map(|label| label.to_string()).collect(),
+            ..PlotOptions::default()
+        }
+    }
+
+    pub fn phase_plane<N: Dim + DimName>(
+        results: &IntegResult<N>,
+        components: (usize, usize),
+        labels: &[&str],
+        title: &str,
+        path: &Path,
+    ) -> Result<(), &'static str>
+    where
+        DefaultAllocator: Allocator<N>,
+    {
+        plot_phase_plane(results, components, path, &options(labels, title))
+    }
+
+    pub fn time_series<N: Dim + DimName>(
+        results: &IntegResult<N>,
+        components: &[usize],
+        labels: &[&str],
+        title: &str,
+        path: &Path,
+    ) -> Result<(), &'static str>
+    where
+        DefaultAllocator: Allocator<N>,
+    {
+        plot_time_series(results, components, path, &options(labels, title))
+    }
+}
+
+#[cfg(not(feature = "plotters"))]
+mod draw {
+    use integration_station::prelude::*;
+    use std::path::Path;
+
+    const NO_PLOTTERS: &str = "[EXAMPLE] Plots need the `plotters` feature";
+
+    pub fn phase_plane<N: Dim + DimName>(
+        _results: &IntegResult<N>,
+        _components: (usize, usize),
+        _labels: &[&str],
+        _title: &str,
+        _path: &Path,
+    ) -> Result<(), &'static str>
+    where
+        DefaultAllocator: Allocator<N>,
+    {
+        Err(NO_PLOTTERS)
+    }
+
+    pub fn time_series<N: Dim + DimName>(
+        _results: &IntegResult<N>,
+        _components: &[usize],
+        _labels: &[&str],
+        _title: &str,
+        _path: &Path,
+    ) -> Result<(), &'static str>
+    where
+        DefaultAllocator: Allocator<N>,
+    {
+        Err(NO_PLOTTERS)
+    }
+}
@@ -0,0 +1,198 @@
+/// ensemble: a parallel Monte Carlo ensemble over uncertain parameters
+///
+/// Usage: cargo run --release --example ensemble -- [--sampling sobol] [--samples 1024] [--threads 4]
+///
+/// Propagates uncertain rates of the Lotka-Volterra predator-prey model
+/// dx = a x - b x y, dy = d x y - c y through to the populations at the final
+/// time, with one integration per sampled parameter set spread over threads (see
+/// `analysis::ensemble`). Reports the mean and spread of the populations and the
+/// wall clock time, and with `--target` keeps adding runs until the standard error
+/// of the mean prey population falls below it. Running with other sampling
+/// options or seeds shows how much the structured samples improve on random ones:
+/// the means of different seeds agree more closely for the same number of runs.
+///
+// === Begin Imports ===
+// third party imports
+use clap::{Parser, ValueEnum};
+use integration_station::analysis::collocation::Uncertain;
+use integration_station::analysis::ensemble::{
+    ensemble, ensemble_adaptive, AdaptiveStopping, EnsembleResult, Sampling,
+};
+use integration_station::prelude::*;
+
+// local imports
+mod common;
+use common::timed;
+
+// Standard library imports
+use std::process;
+
+// === End Imports ===
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum Method {
+    Rk4,
+    Rkf45,
+    Dopri78,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum SamplingArg {
+    Random,
+    Antithetic,
+    LatinHypercube,
+    Sobol,
+}
+
+#[derive(Debug, Parser)]
+#[command(about = "Propagates uncertain rates of the Lotka-Volterra model")]
+struct Args {
+    /// Integrator of each run
+    #[arg(long, value_enum, default_value_t = Method::Dopri78)]
+    method: Method,
+    /// Sampling of the parameters
+    #[arg(long, value_enum, default_value_t = SamplingArg::Sobol)]
+    sampling: SamplingArg,
+    /// Number of runs
+    #[arg(long, default_value_t = 1024)]
+    samples: usize,
+    /// Seed of the samples
+    #[arg(long, default_value_t = 42)]
+    seed: u64,
+    /// Worker threads (all available cores when not given)
+    #[arg(long)]
+    threads: Option<usize>,
+    /// Standard error of the mean prey population to add runs until, in batches
+    /// of `samples` (instead of a fixed number of runs)
+    #[arg(long)]
+    target: Option<f64>,
+    /// Final time
+    #[arg(long, default_value_t = 15.0)]
+    t_end: f64,
+    /// Step of the fixed step method
+    #[arg(long, default_value_t = 0.01)]
+    dt: f64,
+    /// Absolute tolerance of the adaptive methods
+    #[arg(long, default_value_t = 1e-8)]
+    atol: f64,
+    /// Relative tolerance of the adaptive methods
+    #[arg(long, default_value_t = 1e-8)]
+    rtol: f64,
+}
+
+fn main() {
+    let args = Args::parse();
+    if let Err(e) = run_ensemble(&args) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+// Populations at the final time for the rates [a, b, c, d]
+fn populations(args: &Args, rates: &[f64]) -> Result<Vector2<f64>, &'static str> {
+    let (a, b, c, d) = (rates[0], rates[1], rates[2], rates[3]);
+    let system = move |_t: f64, y: &Vector2<f64>| {
+        Vector2::new(a * y[0] - b * y[0] * y[1], d * y[0] * y[1] - c * y[1])
+    };
+    let y_0 = Vector2::new(10.0, 5.0);
+    let options = IntegOptions {
+        atol: Some(Vector2::repeat(args.atol)),
+        rtol: Some(args.rtol),
+        ..IntegOptions::default()
+    };
+    let ans = match args.method {
+        Method::Rk4 => FixedStep::integrate(&*RK4, system, 0.0, y_0, args.t_end, args.dt, options),
+        Method::Rkf45 => AdaptiveStep::integrate(&*RKF45, system, 0.0, y_0, args.t_end, options),
+        Method::Dopri78 => {
+            AdaptiveStep::integrate(&*DOPRI78, system, 0.0, y_0, args.t_end, options)
+        }
+    }?;
+    Ok(*ans.last_y())
+}
+
+fn run_ensemble(args: &Args) -> Result<(), &'static str> {
+    let params = [
+        Uncertain::Normal {
+            mean: 1.1,
+            std: 0.05,
+        },
+        Uncertain::Uniform {
+            lower: 0.35,
+            upper: 0.45,
+        },
+        Uncertain::Normal {
+            mean: 0.4,
+            std: 0.02,
+        },
+        Uncertain::Uniform {
+            lower: 0.08,
+            upper: 0.12,
+        },
+    ];
+    let sampling = match args.sampling {
+        SamplingArg::Random => Sampling::Random,
+        SamplingArg::Antithetic => Sampling::Antithetic,
+        SamplingArg::LatinHypercube => Sampling::LatinHypercube,
+        SamplingArg::Sobol => Sampling::Sobol,
+    };
+    let model = |rates: &[f64]| populations(args, rates);
+
+    let (ans, ms) = timed(
+        || -> Result<(EnsembleResult<U2>, Option<f64>), &'static str> {
+            match args.target {
+                Some(target) => {
+                    let stopping = AdaptiveStopping {
+                        target,
+                        batch: args.samples,
+                        max_samples: 64 * args.samples,
+                    };
+                    let statistic = |y: &Vector2<f64>| y[0];
+                    let adaptive = ensemble_adaptive(
+                        &params,
+                        sampling,
+                        args.seed,
+                        args.threads,
+                        &stopping,
+                        statistic,
+                        model,
+                    )?;
+                    if !adaptive.converged {
+                        return Err("[ENSEMBLE] The target was not reached within 64 batches");
+                    }
+                    Ok((adaptive.ensemble, Some(adaptive.standard_error)))
+                }
+                None => {
+                    let ens = ensemble(
+                        &params,
+                        args.samples,
+                        sampling,
+                        args.seed,
+                        args.threads,
+                        model,
+                    )?;
+                    Ok((ens, None))
+                }
+            }
+        },
+    );
+    let (ens, standard_error) = ans?;
+
+    let n = ens.outputs.len() as f64;
+    println!("method:    {:?}", args.method);
+    println!("sampling:  {:?}", args.sampling);
+    println!("runs:      {}", ens.outputs.len());
+    println!("time:      {:.1} ms", ms);
+    for (i, name) in ["prey", "predator"].iter().enumerate() {
+        println!(
+            "{:<10} {:.6} +- {:.6} (standard error {:.2e})",
+            format!("{}:", name),
+            ens.mean[i],
+            ens.variance[i].sqrt(),
+            (ens.variance[i] / n).sqrt()
+        );
+    }
+    if let Some(standard_error) = standard_error {
+        println!("reached:   {:.2e} on the prey mean", standard_error);
+    }
+    Ok(())
+}
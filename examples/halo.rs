@@ -0,0 +1,188 @@
+/// halo: a halo orbit about the Earth-Moon L1 point
+///
+/// Usage: cargo run --release --example halo -- [--z0 0.05] [--method rkf45] [--plot halo.png]
+///
+/// Corrects an initial guess into a periodic (northern) halo orbit of the circular
+/// restricted three body problem (see `systems::astro`) by single shooting: the
+/// orbit starts on the x-z plane with velocity along y only and, by the symmetry of
+/// the problem about that plane, is periodic when it crosses the plane again
+/// perpendicularly (vx = vz = 0). Each iteration integrates to the crossing, a
+/// stopping condition, and updates x and vy with the state transition matrix
+/// (`analysis::stm`), keeping the amplitude z fixed. The corrected orbit is then
+/// flown for a few revolutions, reporting how far it strays from periodicity and
+/// how well the Jacobi constant is kept. Halo orbits are unstable, so the error
+/// after each revolution grows by the largest eigenvalue of the monodromy matrix
+/// (in the hundreds to thousands for orbits about L1) whatever the tolerance.
+///
+// === Begin Imports ===
+// third party imports
+use clap::{Parser, ValueEnum};
+use integration_station::analysis::stm::propagate_stm;
+use integration_station::prelude::*;
+use integration_station::runge_kutta::stopping::{Direction, Event};
+use integration_station::systems::astro::Cr3bp;
+
+// local imports
+mod common;
+use common::{phase_plot, timed};
+
+// Standard library imports
+use std::path::PathBuf;
+use std::process;
+
+// === End Imports ===
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum Method {
+    Rkf45,
+    CashKarp45,
+    Dopri78,
+}
+
+#[derive(Debug, Parser)]
+#[command(about = "Corrects and propagates a halo orbit of the Earth-Moon system")]
+struct Args {
+    /// Integrator
+    #[arg(long, value_enum, default_value_t = Method::Dopri78)]
+    method: Method,
+    /// Out of plane amplitude (held fixed by the correction)
+    #[arg(long, default_value_t = 0.05)]
+    z0: f64,
+    /// Initial guess of x on the x-z plane
+    #[arg(long, default_value_t = 0.823)]
+    x0: f64,
+    /// Initial guess of vy on the x-z plane
+    #[arg(long, default_value_t = 0.168)]
+    vy0: f64,
+    /// Largest vx and vz at the half period crossing of a corrected orbit
+    #[arg(long, default_value_t = 1e-10)]
+    tol: f64,
+    /// Most correction iterations
+    #[arg(long, default_value_t = 20)]
+    max_iters: usize,
+    /// Revolutions flown with the corrected orbit
+    #[arg(long, default_value_t = 2)]
+    revolutions: usize,
+    /// Absolute tolerance of the integrator
+    #[arg(long, default_value_t = 1e-12)]
+    atol: f64,
+    /// Relative tolerance of the integrator
+    #[arg(long, default_value_t = 1e-12)]
+    rtol: f64,
+    /// Plot of the orbit (y, z) to write (.png or .svg)
+    #[arg(long)]
+    plot: Option<PathBuf>,
+}
+
+fn main() {
+    let args = Args::parse();
+    if let Err(e) = halo(&args) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+// Integrates `span` from `y_0` with the chosen method
+fn fly(
+    args: &Args,
+    y_0: Vector6<f64>,
+    span: f64,
+    options: IntegOptions<U6>,
+) -> Result<IntegResult<U6>, IntegError> {
+    let system = Cr3bp::EARTH_MOON;
+    let options = IntegOptions {
+        atol: Some(Vector6::repeat(args.atol)),
+        rtol: Some(args.rtol),
+        ..options
+    };
+    match args.method {
+        Method::Rkf45 => AdaptiveStep::integrate(&*RKF45, system, 0.0, y_0, span, options),
+        Method::CashKarp45 => {
+            AdaptiveStep::integrate(&*CASH_KARP45, system, 0.0, y_0, span, options)
+        }
+        Method::Dopri78 => AdaptiveStep::integrate(&*DOPRI78, system, 0.0, y_0, span, options),
+    }
+}
+
+// Time and state where the orbit next crosses the x-z plane, y going from
+// positive to non-positive
+fn half_period(args: &Args, y_0: Vector6<f64>) -> Result<(f64, Vector6<f64>), &'static str> {
+    let crossing = Event {
+        direction: Direction::Falling,
+        ..Event::new(|_t, y: &Vector6<f64>| y[1])
+    };
+    let options = IntegOptions {
+        stop_conditions: Some(vec![StopCondition::Crossing(crossing)]),
+        ..IntegOptions::default()
+    };
+    let ans = fly(args, y_0, 10.0, options)?;
+    match ans.stopped {
+        Some(_) => Ok((ans.t, *ans.last_y())),
+        None => Err("[HALO] The orbit did not return to the x-z plane"),
+    }
+}
+
+// Corrects x and vy of `y_0` until the half period crossing is perpendicular.
+// Returns the initial state, the period and the iterations taken
+fn correct(args: &Args, mut y_0: Vector6<f64>) -> Result<(Vector6<f64>, f64, usize), &'static str> {
+    let system = Cr3bp::EARTH_MOON;
+    for iter in 0..args.max_iters {
+        let (t_half, y_half) = half_period(args, y_0)?;
+        if y_half[3].abs().max(y_half[5].abs()) < args.tol {
+            return Ok((y_0, 2.0 * t_half, iter));
+        }
+        // the crossing moves with the initial state: dt = -(dy / dy_0) dy_0 / vy
+        let (_, phi) = propagate_stm(&RK4, &system, 0.0, &y_0, t_half, t_half / 2000.0);
+        let f = system.dynamics(t_half, &y_half);
+        let partial = |row: usize, col: usize| phi[(row, col)] - f[row] * phi[(1, col)] / f[1];
+        let jac = Matrix2::new(partial(3, 0), partial(3, 4), partial(5, 0), partial(5, 4));
+        let update = jac
+            .try_inverse()
+            .ok_or("[HALO] Singular correction, the guess is too far off")?
+            * Vector2::new(y_half[3], y_half[5]);
+        y_0[0] -= update[0];
+        y_0[4] -= update[1];
+    }
+    Err("[HALO] The correction did not converge, try another guess")
+}
+
+fn halo(args: &Args) -> Result<(), &'static str> {
+    let system = Cr3bp::EARTH_MOON;
+    let guess = Vector6::new(args.x0, 0.0, args.z0, 0.0, args.vy0, 0.0);
+    let (corrected, correct_ms) = timed(|| correct(args, guess));
+    let (y_0, period, iters) = corrected?;
+
+    let span = period * args.revolutions as f64;
+    let (ans, fly_ms) = timed(|| fly(args, y_0, span, IntegOptions::default()));
+    let ans = ans?;
+
+    let jacobi = system.jacobi_constant(&y_0);
+    let drift = ans
+        .states
+        .iter()
+        .map(|y| (system.jacobi_constant(y) - jacobi).abs())
+        .fold(0.0, f64::max);
+    println!("method:          {:?}", args.method);
+    println!("iterations:      {} ({:.1} ms)", iters, correct_ms);
+    println!(
+        "initial state:   x = {:.12}, z = {:.12}, vy = {:.12}",
+        y_0[0], y_0[2], y_0[4]
+    );
+    println!("period:          {:.12}", period);
+    println!("jacobi constant: {:.12}", jacobi);
+    println!(
+        "steps:           {} ({:.1} ms)",
+        ans.times.len() - 1,
+        fly_ms
+    );
+    println!("jacobi drift:    {:.2e}", drift);
+    println!(
+        "return error:    {:.2e} after {} revolutions",
+        (ans.last_y() - y_0).amax(),
+        args.revolutions
+    );
+
+    let title = format!("L1 halo orbit (z0 = {})", args.z0);
+    let labels = ["x", "y", "z", "vx", "vy", "vz"];
+    phase_plot(&ans, (1, 2), &labels, &title, args.plot.as_deref())
+}
@@ -0,0 +1,132 @@
+/// lorenz: the Lorenz attractor
+///
+/// Usage: cargo run --release --example lorenz -- [--method dopri78] [--rtol 1e-10]
+///
+/// Integrates dx = sigma (y - x), dy = x (rho - z) - y, dz = x y - beta z from
+/// (1, 1, 1) with the chosen method, and reports the cost, the final state and how
+/// often the trajectory switched between the two lobes of the attractor (sign
+/// changes of x). The flow is chaotic: runs with different methods or tolerances
+/// agree for a while and then part ways, the later the tighter the tolerance.
+///
+// === Begin Imports ===
+// third party imports
+use clap::{Parser, ValueEnum};
+use integration_station::prelude::*;
+
+// local imports
+mod common;
+use common::{phase_plot, timed};
+
+// Standard library imports
+use std::path::PathBuf;
+use std::process;
+
+// === End Imports ===
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum Method {
+    Rk4,
+    Rkf45,
+    Dopri78,
+    RidcRk4,
+    RidcDopri78,
+}
+
+#[derive(Debug, Parser)]
+#[command(about = "Integrates the Lorenz system")]
+struct Args {
+    /// Integrator
+    #[arg(long, value_enum, default_value_t = Method::Dopri78)]
+    method: Method,
+    /// Prandtl number
+    #[arg(long, default_value_t = 10.0)]
+    sigma: f64,
+    /// Rayleigh number
+    #[arg(long, default_value_t = 28.0)]
+    rho: f64,
+    /// Geometric factor
+    #[arg(long, default_value_t = 8.0 / 3.0)]
+    beta: f64,
+    /// Final time
+    #[arg(long, default_value_t = 50.0)]
+    t_end: f64,
+    /// Step of the fixed step methods
+    #[arg(long, default_value_t = 0.005)]
+    dt: f64,
+    /// Absolute tolerance of the adaptive methods
+    #[arg(long, default_value_t = 1e-10)]
+    atol: f64,
+    /// Relative tolerance of the adaptive methods
+    #[arg(long, default_value_t = 1e-10)]
+    rtol: f64,
+    /// Correction levels of the RIDC methods
+    #[arg(long, default_value_t = 3)]
+    corrector_order: usize,
+    /// Plot of the attractor (x, z) to write (.png or .svg)
+    #[arg(long)]
+    plot: Option<PathBuf>,
+}
+
+fn main() {
+    let args = Args::parse();
+    if let Err(e) = lorenz(&args) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+fn lorenz(args: &Args) -> Result<(), &'static str> {
+    let (sigma, rho, beta) = (args.sigma, args.rho, args.beta);
+    let system = move |_t: f64, y: &Vector3<f64>| {
+        Vector3::new(
+            sigma * (y[1] - y[0]),
+            y[0] * (rho - y[2]) - y[1],
+            y[0] * y[1] - beta * y[2],
+        )
+    };
+    let y_0 = Vector3::new(1.0, 1.0, 1.0);
+    let options = IntegOptions {
+        atol: Some(Vector3::repeat(args.atol)),
+        rtol: Some(args.rtol),
+        ..IntegOptions::default()
+    };
+    let parallel = IntegOptionsParallel {
+        atol: Some(Vector3::repeat(args.atol)),
+        rtol: Some(args.rtol),
+        corrector_order: Some(args.corrector_order),
+        ..IntegOptionsParallel::default()
+    };
+
+    let (ans, ms) = timed(|| match args.method {
+        Method::Rk4 => FixedStep::integrate(&*RK4, system, 0.0, y_0, args.t_end, args.dt, options),
+        Method::Rkf45 => AdaptiveStep::integrate(&*RKF45, system, 0.0, y_0, args.t_end, options),
+        Method::Dopri78 => {
+            AdaptiveStep::integrate(&*DOPRI78, system, 0.0, y_0, args.t_end, options)
+        }
+        Method::RidcRk4 => RIDCIntegratorFixed::parallel_integrator(
+            &*RK4, system, 0.0, &y_0, args.t_end, args.dt, parallel,
+        ),
+        Method::RidcDopri78 => RIDCIntegratorAdaptive::parallel_integrator(
+            &*DOPRI78, system, 0.0, &y_0, args.t_end, parallel,
+        ),
+    });
+    let ans = ans?;
+
+    let switches = ans
+        .states
+        .windows(2)
+        .filter(|pair| pair[0][0] * pair[1][0] < 0.0)
+        .count();
+    let y = ans.last_y();
+    println!("method:        {:?}", args.method);
+    println!("steps:         {}", ans.times.len() - 1);
+    println!("time:          {:.1} ms", ms);
+    println!(
+        "final state:   ({:.6}, {:.6}, {:.6}) at t = {}",
+        y[0], y[1], y[2], ans.t
+    );
+    println!("lobe switches: {}", switches);
+
+    let title = format!("Lorenz attractor (rho = {})", rho);
+    phase_plot(&ans, (0, 2), &["x", "y", "z"], &title, args.plot.as_deref())
+}
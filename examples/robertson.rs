@@ -0,0 +1,123 @@
+/// robertson: the stiff Robertson chemical kinetics
+///
+/// Usage: cargo run --release --example robertson -- [--method fixed-cost] [--dt 0.001]
+///
+/// Integrates the Robertson reactions (see `systems::stiff`) to t = 40 and
+/// compares the result with the RADAU5 reference of the test set. The fast
+/// reaction makes the problem stiff: the explicit adaptive methods are held to
+/// small steps by stability rather than accuracy, whatever the tolerance, while
+/// the implicit fixed cost deferred correction (`ridc::fixed_cost`) reaches a far
+/// smaller error at a known cost per step. Its steps are bounded instead by the
+/// fixed number of simplified newton iterations, which stop converging through the
+/// fast initial transient when the step is too long, and the solution blows up
+/// (reported as an error rather than printed).
+///
+// === Begin Imports ===
+// third party imports
+use clap::{Parser, ValueEnum};
+use integration_station::analysis::benchmark::Benchmark;
+use integration_station::prelude::*;
+use integration_station::ridc::fixed_cost::FixedCost;
+use integration_station::systems::counted::CountedSystem;
+
+// local imports
+mod common;
+use common::timed;
+
+// Standard library imports
+use std::process;
+
+// === End Imports ===
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum Method {
+    FixedCost,
+    Rkf45,
+    Dopri78,
+}
+
+#[derive(Debug, Parser)]
+#[command(about = "Integrates the stiff Robertson problem")]
+struct Args {
+    /// Integrator
+    #[arg(long, value_enum, default_value_t = Method::FixedCost)]
+    method: Method,
+    /// Step of the fixed cost integrator
+    #[arg(long, default_value_t = 0.002)]
+    dt: f64,
+    /// Substeps (nodes) of each fixed cost step
+    #[arg(long, default_value_t = 3)]
+    nodes: usize,
+    /// Correction sweeps of each fixed cost step
+    #[arg(long, default_value_t = 3)]
+    sweeps: usize,
+    /// Newton iterations of each implicit solve of the fixed cost integrator
+    #[arg(long, default_value_t = 2)]
+    newton_iters: usize,
+    /// Absolute tolerance of the adaptive methods
+    #[arg(long, default_value_t = 1e-8)]
+    atol: f64,
+    /// Relative tolerance of the adaptive methods
+    #[arg(long, default_value_t = 1e-6)]
+    rtol: f64,
+}
+
+fn main() {
+    let args = Args::parse();
+    if let Err(e) = robertson(&args) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+fn robertson(args: &Args) -> Result<(), &'static str> {
+    let problem = Benchmark::robertson();
+    let system = CountedSystem::new(problem.system);
+    let span = problem.t_end - problem.t_0;
+    let options = IntegOptions {
+        atol: Some(Vector3::repeat(args.atol)),
+        rtol: Some(args.rtol),
+        ..IntegOptions::default()
+    };
+
+    let (ans, ms) =
+        timed(|| match args.method {
+            Method::FixedCost => FixedCost::new(args.nodes, args.sweeps, args.newton_iters)?
+                .integrate(system.clone(), problem.t_0, problem.y_0, span, args.dt),
+            Method::Rkf45 => AdaptiveStep::integrate(
+                &*RKF45,
+                system.clone(),
+                problem.t_0,
+                problem.y_0,
+                span,
+                options,
+            ),
+            Method::Dopri78 => AdaptiveStep::integrate(
+                &*DOPRI78,
+                system.clone(),
+                problem.t_0,
+                problem.y_0,
+                span,
+                options,
+            ),
+        });
+    let ans = ans?;
+
+    let y = ans.last_y();
+    if y.iter().any(|y_i| !y_i.is_finite()) {
+        return Err("[ROBERTSON] The solution blew up, try a shorter step");
+    }
+    println!("method:      {:?}", args.method);
+    println!("steps:       {}", ans.times.len() - 1);
+    println!("evaluations: {}", system.rhs_calls());
+    println!("time:        {:.1} ms", ms);
+    println!("final state: ({:.6e}, {:.6e}, {:.6e})", y[0], y[1], y[2]);
+    println!(
+        "error:       {:.2e} (relative, against RADAU5)",
+        problem.error(y)
+    );
+    if let Some(defect) = ans.defects.iter().cloned().reduce(f64::max) {
+        println!("max defect:  {:.2e}", defect);
+    }
+    Ok(())
+}
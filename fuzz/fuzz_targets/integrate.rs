@@ -0,0 +1,74 @@
+#![no_main]
+//! Fuzz Target for the Integrators
+//!
+//! A forced nonlinear oscillator y'' = c_0 y + c_1 y'^3 + c_2 sin(c_3 t) with
+//! arbitrary coefficients (stiff, blowing up in finite time, infinite or NaN),
+//! integrated from arbitrary initial states and times with arbitrary step sizes,
+//! spans and tolerances (zero, negative, subnormal, infinite, NaN) by the fixed
+//! step, adaptive and fixed cost integrators. Bad problems must end in an error
+//! (or a result), never a panic or a hang.
+//!
+//! The integrators do not bound the number of steps they take, so problems asking
+//! for more than `MAX_STEPS` fixed steps are skipped: a service integrating user
+//! models bounds the work it accepts the same way.
+//!
+
+// === Begin Imports ===
+// third party imports
+use libfuzzer_sys::arbitrary::{self, Arbitrary};
+use libfuzzer_sys::fuzz_target;
+use nalgebra::Vector2;
+
+// local imports
+use integration_station::ridc::fixed_cost::FixedCost;
+use integration_station::runge_kutta::adaptive::AdaptiveStep;
+use integration_station::runge_kutta::common::IntegOptions;
+use integration_station::runge_kutta::fixed::FixedStep;
+use integration_station::runge_kutta::rk_embed::{DOPRI78, RK32};
+use integration_station::runge_kutta::rk_simp::RK4;
+
+// === End Imports ===
+
+// Largest number of fixed steps integrated
+const MAX_STEPS: f64 = 1e4;
+
+#[derive(Debug, Arbitrary)]
+struct Problem {
+    coeffs: [f64; 4],
+    t_0: f64,
+    y_0: [f64; 2],
+    span: f64,
+    dt: f64,
+    atol: [f64; 2],
+    rtol: f64,
+    first_step: Option<f64>,
+    min_step: Option<f64>,
+    max_step: Option<f64>,
+    max_rejections: Option<u8>,
+}
+
+fuzz_target!(|problem: Problem| {
+    let c = problem.coeffs;
+    let fxn = move |t: f64, y: &Vector2<f64>| {
+        Vector2::new(y[1], c[0] * y[0] + c[1] * y[1].powi(3) + c[2] * (c[3] * t).sin())
+    };
+    let y_0 = Vector2::from(problem.y_0);
+    let opts = IntegOptions {
+        atol: Some(Vector2::from(problem.atol)),
+        rtol: Some(problem.rtol),
+        first_step: problem.first_step,
+        min_step: problem.min_step,
+        max_step: problem.max_step,
+        max_rejections: problem.max_rejections.map(usize::from),
+        ..IntegOptions::default()
+    };
+
+    if (problem.span / problem.dt).abs() <= MAX_STEPS {
+        let _ = RK4.integrate(fxn, problem.t_0, y_0, problem.span, problem.dt, opts.clone());
+        if let Ok(stepper) = FixedCost::new(2, 1, 1) {
+            let _ = stepper.integrate(fxn, problem.t_0, y_0, problem.span, problem.dt);
+        }
+    }
+    let _ = DOPRI78.integrate(fxn, problem.t_0, y_0, problem.span, opts.clone());
+    let _ = RK32.integrate(fxn, problem.t_0, y_0, problem.span, opts);
+});
@@ -0,0 +1,82 @@
+#![no_main]
+//! Fuzz Target for the Newton Solvers
+//!
+//! Residuals f(x) = A x + c x^3 + g(x) - b with arbitrary coefficients (singular,
+//! ill conditioned, infinite or NaN), a term g blowing up or leaving its domain
+//! (exp, tan, 1 / x, sqrt, ln), arbitrary initial guesses and accuracies. The
+//! solvers may fail on any of them, but only by returning an error: a panic, a
+//! hang or an Ok which is not a root to the accuracy is a bug.
+//!
+
+// === Begin Imports ===
+// third party imports
+use libfuzzer_sys::arbitrary::{self, Arbitrary};
+use libfuzzer_sys::fuzz_target;
+use nalgebra::{Matrix3, Vector3};
+
+// local imports
+use integration_station::utils::newton_raphson::{
+    deflated_roots, newton_raphson_broyden, newton_raphson_broyden_warm, newton_raphson_fdiff,
+    newton_raphson_linsrch,
+};
+
+// === End Imports ===
+
+#[derive(Debug, Arbitrary)]
+struct Problem {
+    // Linear part, row major
+    a: [f64; 9],
+    // Cubic coefficients
+    cubic: [f64; 3],
+    // Selects the nonlinear term of each component
+    terms: [u8; 3],
+    b: [f64; 3],
+    x_0: [f64; 3],
+    acc: f64,
+    // Jacobian to warm start Broyden from, row major
+    warm: Option<[f64; 9]>,
+}
+
+fn term(kind: u8, x: f64) -> f64 {
+    match kind % 6 {
+        0 => 0.0,
+        1 => x.exp(),
+        2 => x.tan(),
+        3 => 1.0 / x,
+        4 => x.sqrt(),
+        _ => x.ln(),
+    }
+}
+
+fuzz_target!(|problem: Problem| {
+    let a = Matrix3::from_row_slice(&problem.a);
+    let b = Vector3::from(problem.b);
+    let fxn = |x: &Vector3<f64>| {
+        a * x + Vector3::from_fn(|i, _| {
+            problem.cubic[i] * x[i].powi(3) + term(problem.terms[i], x[i])
+        }) - b
+    };
+    let x_0 = Vector3::from(problem.x_0);
+    let acc = problem.acc;
+    // a returned root is a root to the accuracy
+    let check = |x: &Vector3<f64>| assert!(fxn(x).amax() < acc, "{:?} is no root {:?}", x, problem);
+
+    for found in [
+        newton_raphson_fdiff(fxn, x_0, acc),
+        newton_raphson_linsrch(fxn, x_0, acc),
+        newton_raphson_broyden(fxn, x_0, acc),
+    ]
+    .iter()
+    {
+        if let Ok(x) = found {
+            check(x);
+        }
+    }
+    let warm = problem.warm.map(|jac| Matrix3::from_row_slice(&jac));
+    if let Ok(sol) = newton_raphson_broyden_warm(fxn, x_0, acc, warm) {
+        check(&sol.x);
+    }
+    for x in deflated_roots(fxn, x_0, acc, 3) {
+        check(&x);
+    }
+});
@@ -8,7 +8,7 @@
 // third party imports
 extern crate nalgebra as na;
 use na::allocator::Allocator;
-use na::{DefaultAllocator, Dim, DimName, VectorN};
+use na::{DefaultAllocator, Dim, DimName, OVector};
 
 // local imports
 use super::primer::MultiStepPrimer;
@@ -29,7 +29,7 @@ pub enum AdamState {
 #[derive(Clone)]
 pub struct AdamsData<N: Dim + DimName>
 where
-    DefaultAllocator: Allocator<f64, N>,
+    DefaultAllocator: Allocator<N>,
 {
     // Order of polynomial fit to use for integration
     order: usize,
@@ -44,31 +44,31 @@ where
     //
     alphas: VecDeque<f64>,
     // Divided differences
-    phis: VecDeque<VectorN<f64, N>>,
+    phis: VecDeque<OVector<f64, N>>,
     // Recursively defined quadrature coefficients
     gs: VecDeque<f64>,
     // Error divided difference
-    phi_e: VecDeque<VectorN<f64, N>>,
+    phi_e: VecDeque<OVector<f64, N>>,
     // Intermediate divided diffs
-    phi_stars: VecDeque<VectorN<f64, N>>,
+    phi_stars: VecDeque<OVector<f64, N>>,
     // Dynamics function to integrate
-    fxn: fn(f64, &VectorN<f64, N>) -> VectorN<f64, N>,
+    fxn: fn(f64, &OVector<f64, N>) -> OVector<f64, N>,
     // Times of polynomial fit
     times: VecDeque<f64>,
     // Evaluations of the dynamics function at the times above
-    dyn_evals: VecDeque<VectorN<f64, N>>,
+    dyn_evals: VecDeque<OVector<f64, N>>,
     // current state estimate
-    state: VectorN<f64, N>,
+    state: OVector<f64, N>,
     // Initial state
     init: AdamState,
 }
 impl<N: Dim + DimName> AdamsData<N>
 where
-    DefaultAllocator: Allocator<f64, N>,
+    DefaultAllocator: Allocator<N>,
 {
     pub fn from_primer(primer: MultiStepPrimer<N>) -> Self {
         let order = primer.len() - 1;
-        let mut phi_vec = VecDeque::<VectorN<f64, N>>::with_capacity(order + 1);
+        let mut phi_vec = VecDeque::<OVector<f64, N>>::with_capacity(order + 1);
         phi_vec.push_back(primer.states.first().unwrap().clone());
 
         AdamsData {
@@ -80,8 +80,8 @@ where
             alphas: VecDeque::<f64>::with_capacity(order),
             phis: phi_vec,
             gs: VecDeque::<f64>::with_capacity(order + 1),
-            phi_e: VecDeque::<VectorN<f64, N>>::with_capacity(order + 1),
-            phi_stars: VecDeque::<VectorN<f64, N>>::with_capacity(order + 1),
+            phi_e: VecDeque::<OVector<f64, N>>::with_capacity(order + 1),
+            phi_stars: VecDeque::<OVector<f64, N>>::with_capacity(order + 1),
             fxn: primer.fxn.unwrap(),
             times: VecDeque::from(primer.times),
             dyn_evals: VecDeque::from(primer.dyn_evals),
@@ -93,7 +93,7 @@ where
 
 impl<N: Dim + DimName> AdamsInit for AdamsData<N>
 where
-    DefaultAllocator: Allocator<f64, N>,
+    DefaultAllocator: Allocator<N>,
 {
     // computes all the delta t's for the data
     fn init_dtks(&mut self, t_kp1: f64) {
@@ -140,7 +140,7 @@ where
 
     fn init_phis(&mut self) {
         let divided_diff_vec = divided_diff(&self.dyn_evals, &self.times);
-        let mut mult_res = VectorN::<f64, N>::repeat(1.0);
+        let mut mult_res = OVector::<f64, N>::repeat(1.0);
         for (idx, psi) in self.psis_n.iter().enumerate() {
             mult_res *= *psi;
             self.phis
@@ -161,9 +161,9 @@ where
 
 impl<N: Dim + DimName> AdamsPredictor for AdamsData<N>
 where
-    DefaultAllocator: Allocator<f64, N>,
+    DefaultAllocator: Allocator<N>,
 {
-    type OutVec = VectorN<f64, N>;
+    type OutVec = OVector<f64, N>;
 
     fn get_g1s(&mut self) {
         self.gs.clear();
@@ -181,18 +181,18 @@ where
 
     fn get_phi_e(&mut self) {
         self.phi_e.clear();
-        self.phi_e.push_back(VectorN::<f64, N>::zeros());
+        self.phi_e.push_back(OVector::<f64, N>::zeros());
         for idx in 1..self.order {
             self.phi_e
                 .push_back(&self.phi_e[idx - 1] + &self.phi_stars[self.order + 1 - idx]);
         }
     }
 
-    fn predict(&self, m: usize, step: f64) -> VectorN<f64, N>
+    fn predict(&self, m: usize, step: f64) -> OVector<f64, N>
     where
-        DefaultAllocator: Allocator<f64, N>,
+        DefaultAllocator: Allocator<N>,
     {
-        let mut sum = VectorN::<f64, N>::zeros();
+        let mut sum = OVector::<f64, N>::zeros();
         for i in 0..=m {
             sum += self.gs[i] * &self.phi_stars[i];
         }
@@ -202,24 +202,20 @@ where
 
 impl<N: Dim + DimName> AdamsCorrector for AdamsData<N>
 where
-    DefaultAllocator: Allocator<f64, N>,
+    DefaultAllocator: Allocator<N>,
 {
-    type OutVec = VectorN<f64, N>;
+    type OutVec = OVector<f64, N>;
 
-    fn correct(
-        &self,
-        step: f64,
-        t_nxt: f64,
-        fxn: fn(f64, &VectorN<f64, N>) -> VectorN<f64, N>,
-        prediction: &VectorN<f64, N>,
-    ) -> VectorN<f64, N> {
+    fn correct(&self, step: f64, t_nxt: f64, prediction: &OVector<f64, N>) -> OVector<f64, N> {
         prediction
-            + step * self.gs.back().unwrap() * (fxn(t_nxt, prediction) - self.phi_e.back().unwrap())
+            + step
+                * self.gs.back().unwrap()
+                * ((self.fxn)(t_nxt, prediction) - self.phi_e.back().unwrap())
     }
 }
 impl<N: Dim + DimName> AdamsUpdate for AdamsData<N>
 where
-    DefaultAllocator: Allocator<f64, N>,
+    DefaultAllocator: Allocator<N>,
 {
     fn update_phis(&mut self) {
         // clear out space for the new phi in the buffer. This prevents re-allocation of the ring buffer
@@ -235,16 +231,11 @@ where
 
 impl<N: Dim + DimName> AdamsStepper for AdamsData<N>
 where
-    DefaultAllocator: Allocator<f64, N>,
+    DefaultAllocator: Allocator<N>,
 {
-    type OutVec = VectorN<f64, N>;
+    type OutVec = OVector<f64, N>;
 
-    fn step(
-        &mut self,
-        order: usize,
-        fxn: fn(f64, &VectorN<f64, N>) -> VectorN<f64, N>,
-        t_nxt: f64,
-    ) -> Result<VectorN<f64, N>, &'static str> {
+    fn step(&mut self, order: usize, t_nxt: f64) -> Result<OVector<f64, N>, &'static str> {
         if order > self.order {
             return Err("Order requested is larger than integrator order");
         }
@@ -269,9 +260,9 @@ where
         let corrected = &prediction
             + step
                 * self.gs.back().unwrap()
-                * (fxn(t_nxt, &prediction) - self.phi_e.back().unwrap());
+                * ((self.fxn)(t_nxt, &prediction) - self.phi_e.back().unwrap());
         self.dyn_evals.pop_front();
-        self.dyn_evals.push_back(fxn(t_nxt, &corrected));
+        self.dyn_evals.push_back((self.fxn)(t_nxt, &corrected));
         self.state = corrected.clone();
         self.times.pop_front();
         self.times.push_back(t_nxt);
@@ -306,7 +297,7 @@ mod tests {
         println!("STEP | {:?}", step);
         let t_nxt = primer.times.last().unwrap() + step;
         let mut adams_stepper = AdamsData::from_primer(primer);
-        let ans = adams_stepper.step(m, two_d_dynamics, t_nxt);
+        let ans = adams_stepper.step(m, t_nxt);
         println!("EST   | {:?}", ans);
         println!("TRUTH | {:?}", two_d_solution(t_nxt));
     }
@@ -1,6 +1,6 @@
 extern crate nalgebra as na;
 use na::allocator::Allocator;
-use na::{DefaultAllocator, Dim, DimName, VectorN};
+use na::{DefaultAllocator, Dim, DimName, OVector};
 
 use crate::runge_kutta::adaptive::{AdaptiveStep, StepValid};
 use crate::runge_kutta::common::{StepResult, StepWithError, Tolerances};
@@ -10,15 +10,15 @@ use crate::runge_kutta::common::{IntegOptions}
 // Divided diff from:
 // https://www.math.usm.edu/lambers/mat460/fall09/lecture17.pdf
 pub fn divided_diff<N: Dim + DimName>(
-    points: &[VectorN<f64, N>],
+    points: &[OVector<f64, N>],
     times: &[f64],
-) -> Vec<VectorN<f64, N>>
+) -> Vec<OVector<f64, N>>
 where
-    DefaultAllocator: Allocator<f64, N>,
+    DefaultAllocator: Allocator<N>,
 {
     let n = times.len();
     // initialize empty finite difference table
-    let mut diffs = vec![vec![VectorN::<f64, N>::zeros(); n]; n];
+    let mut diffs = vec![vec![OVector::<f64, N>::zeros(); n]; n];
     let mut times = times.clone().to_vec();
     times.reverse();
     for i in (0..n).rev() {
@@ -42,16 +42,16 @@ where
 // In other words, K is the order of the polynommial fit
 pub struct AdamsPredictor<N: Dim + DimName, K: Dim + DimName>
 where
-    DefaultAllocator: Allocator<f64, N> + Allocator<f64, K>,
+    DefaultAllocator: Allocator<N> + Allocator<K>,
 {
-    pub xs: Vec<VectorN<f64, N>>,
-    pub hs: VectorN<f64, K>,
+    pub xs: Vec<OVector<f64, N>>,
+    pub hs: OVector<f64, K>,
     pub psis: Vec<f64>,
 }
 
 impl<N: Dim + DimName, K: Dim + DimName> AdamsPredictor<N, K>
 where
-    DefaultAllocator: Allocator<f64, K> + Allocator<f64, N>,
+    DefaultAllocator: Allocator<K> + Allocator<N>,
 {
     // t_kp1 is the first time step
 }
@@ -128,15 +128,15 @@ pub fn get_alphas(psis_np1: &Vec<f64>, step: f64) -> Vec<f64> {
 }
 
 pub fn get_phis<N: Dim + DimName>(
-    fn_eval_n: &VectorN<f64, N>,
-    divided_diff_vec: &Vec<VectorN<f64, N>>,
+    fn_eval_n: &OVector<f64, N>,
+    divided_diff_vec: &Vec<OVector<f64, N>>,
     psis_last: &Vec<f64>,
-) -> Vec<VectorN<f64, N>>
+) -> Vec<OVector<f64, N>>
 where
-    DefaultAllocator: Allocator<f64, N>,
+    DefaultAllocator: Allocator<N>,
 {
-    let mut phis: Vec<VectorN<f64, N>> = Vec::new();
-    let mut mult_res = VectorN::<f64, N>::repeat(1.0);
+    let mut phis: Vec<OVector<f64, N>> = Vec::new();
+    let mut mult_res = OVector::<f64, N>::repeat(1.0);
     phis.push(fn_eval_n.clone());
     for (idx, psi) in psis_last.iter().enumerate() {
         mult_res *= *psi;
@@ -148,12 +148,12 @@ where
 pub fn get_phi_star<N: Dim + DimName>(
     m: usize,
     betas: &Vec<f64>,
-    phis: &Vec<VectorN<f64, N>>,
-) -> Vec<VectorN<f64, N>>
+    phis: &Vec<OVector<f64, N>>,
+) -> Vec<OVector<f64, N>>
 where
-    DefaultAllocator: Allocator<f64, N>,
+    DefaultAllocator: Allocator<N>,
 {
-    let mut phi_stars: Vec<VectorN<f64, N>> = Vec::new();
+    let mut phi_stars: Vec<OVector<f64, N>> = Vec::new();
     for idx in 0..m + 1 {
         phi_stars.push(betas[idx] * phis[idx].clone())
     }
@@ -162,15 +162,15 @@ where
 
 pub fn get_prediction<N: Dim + DimName>(
     m: usize,
-    phi_star: &Vec<VectorN<f64, N>>,
+    phi_star: &Vec<OVector<f64, N>>,
     gs: &Vec<f64>,
     step: f64,
-    y_n: &VectorN<f64, N>,
-) -> VectorN<f64, N>
+    y_n: &OVector<f64, N>,
+) -> OVector<f64, N>
 where
-    DefaultAllocator: Allocator<f64, N>,
+    DefaultAllocator: Allocator<N>,
 {
-    let mut sum = VectorN::<f64, N>::zeros();
+    let mut sum = OVector::<f64, N>::zeros();
     // TODO: Validate that it should be M+1, not M
     for i in 0..m {
         sum += gs[i] * phi_star[i].clone()
@@ -180,13 +180,13 @@ where
 
 pub fn get_phi_e<N: Dim + DimName>(
     m: usize,
-    phi_star: &Vec<VectorN<f64, N>>,
-) -> Vec<VectorN<f64, N>>
+    phi_star: &Vec<OVector<f64, N>>,
+) -> Vec<OVector<f64, N>>
 where
-    DefaultAllocator: Allocator<f64, N>,
+    DefaultAllocator: Allocator<N>,
 {
-    let mut phi_e: Vec<VectorN<f64, N>> = Vec::new();
-    phi_e.push(VectorN::<f64, N>::zeros());
+    let mut phi_e: Vec<OVector<f64, N>> = Vec::new();
+    phi_e.push(OVector::<f64, N>::zeros());
     // TODO: Validate whether this should be M+1 or M
     for idx in 1..m {
         phi_e.push(phi_e[idx - 1].clone() + phi_star[phi_star.len() - idx].clone());
@@ -196,13 +196,13 @@ where
 
 fn update_phis<N: Dim + DimName>(
     m: usize,
-    corr_fxn_eval: VectorN<f64, N>,
-    phi_e: Vec<VectorN<f64, N>>,
-) -> Vec<VectorN<f64, N>>
+    corr_fxn_eval: OVector<f64, N>,
+    phi_e: Vec<OVector<f64, N>>,
+) -> Vec<OVector<f64, N>>
 where
-    DefaultAllocator: Allocator<f64, N>,
+    DefaultAllocator: Allocator<N>,
 {
-    let mut phis_new: Vec<VectorN<f64, N>> = Vec::new();
+    let mut phis_new: Vec<OVector<f64, N>> = Vec::new();
     // start with k+1
     phis_new.push(corr_fxn_eval.clone() - phi_e.last().unwrap().clone());
     for idx in 1..m {
@@ -472,9 +472,9 @@ pub fn predictor<N: Dim + DimName>(
     order: usize,
     step: f64,
     data: &MultiStepData<N>,
-) -> Result<(VectorN<f64, N>, Vec<VectorN<f64, N>>, Vec<f64>), String>
+) -> Result<(OVector<f64, N>, Vec<OVector<f64, N>>, Vec<f64>), String>
 where
-    DefaultAllocator: Allocator<f64, N>,
+    DefaultAllocator: Allocator<N>,
 {
     if data.times.len() < order + 1 {
         return Err(format!(
@@ -517,15 +517,15 @@ where
 }
 
 pub fn corrector<N: Dim + DimName>(
-    prediction: &VectorN<f64, N>,
-    fxn: fn(f64, &VectorN<f64, N>) -> VectorN<f64, N>,
+    prediction: &OVector<f64, N>,
+    fxn: fn(f64, &OVector<f64, N>) -> OVector<f64, N>,
     t_nxt: f64,
     step: f64,
     gs: &Vec<f64>,
-    phi_e: &Vec<VectorN<f64, N>>,
-) -> VectorN<f64, N>
+    phi_e: &Vec<OVector<f64, N>>,
+) -> OVector<f64, N>
 where
-    DefaultAllocator: Allocator<f64, N>,
+    DefaultAllocator: Allocator<N>,
 {
     prediction + step * gs.last().unwrap() * (fxn(t_nxt, &prediction) - phi_e.last().unwrap())
 }
@@ -533,13 +533,13 @@ where
 // Checks the gamma for a given prediction
 pub fn check_gamma<N: Dim + DimName>(
     order: usize,
-    abs_tol: &VectorN<f64, N>,
+    abs_tol: &OVector<f64, N>,
     rel_tol: f64,
-    pred_lower: &VectorN<f64, N>,
-    pred_higher: &VectorN<f64, N>,
+    pred_lower: &OVector<f64, N>,
+    pred_higher: &OVector<f64, N>,
 ) -> f64
 where
-    DefaultAllocator: Allocator<f64, N>,
+    DefaultAllocator: Allocator<N>,
 {
     let numer = abs_tol + (rel_tol * pred_higher).abs();
     let denom = 2.0 * (pred_higher - pred_lower).abs();
@@ -551,19 +551,19 @@ where
 
 fn initialize_multistep<N: Dim + DimName>(
     order: usize,
-    atol: &VectorN<f64, N>,
+    atol: &OVector<f64, N>,
     rtol: f64,
-    fxn: fn(f64, &VectorN<f64, N>) -> VectorN<f64, N>,
+    fxn: fn(f64, &OVector<f64, N>) -> OVector<f64, N>,
     step: f64,
     t_0: f64,
-    y_0: &VectorN<f64, N>,
+    y_0: &OVector<f64, N>,
 ) -> Result<MultiStepData<N>, &'static str>
 where
-    DefaultAllocator: Allocator<f64, N>,
+    DefaultAllocator: Allocator<N>,
 {
-    let mut fxn_evals: Vec<VectorN<f64, N>> = Vec::new();
+    let mut fxn_evals: Vec<OVector<f64, N>> = Vec::new();
     let mut times: Vec<f64> = vec![t_0];
-    let mut states: Vec<VectorN<f64, N>> = Vec::new();
+    let mut states: Vec<OVector<f64, N>> = Vec::new();
     states.push(y_0.clone());
 
     let mut t_last = t_0;
@@ -9,7 +9,7 @@
 // third party imports
 extern crate nalgebra as na;
 use na::allocator::Allocator;
-use na::{DefaultAllocator, Dim, DimName, VectorN};
+use na::{DefaultAllocator, Dim, DimName, OVector};
 
 // local imports
 use crate::runge_kutta::adaptive::{AdaptiveStep, StepValid};
@@ -24,20 +24,20 @@ use super::traits::AdamsPrimer;
 #[derive(Clone)]
 pub struct MultiStepPrimer<N: Dim + DimName>
 where
-    DefaultAllocator: Allocator<f64, N>,
+    DefaultAllocator: Allocator<N>,
 {
     pub times: Vec<f64>,
-    pub states: Vec<VectorN<f64, N>>,
-    pub dyn_evals: Vec<VectorN<f64, N>>,
+    pub states: Vec<OVector<f64, N>>,
+    pub dyn_evals: Vec<OVector<f64, N>>,
     pub first_step: Option<f64>,
-    pub fxn: Option<fn(f64, &VectorN<f64, N>) -> VectorN<f64, N>>,
+    pub fxn: Option<fn(f64, &OVector<f64, N>) -> OVector<f64, N>>,
 }
 
 impl<N: DimName + Dim> MultiStepPrimer<N>
 where
-    DefaultAllocator: Allocator<f64, N>,
+    DefaultAllocator: Allocator<N>,
 {
-    fn new(t_0: f64, y_0: &VectorN<f64, N>, dy_0: &VectorN<f64, N>) -> Self {
+    fn new(t_0: f64, y_0: &OVector<f64, N>, dy_0: &OVector<f64, N>) -> Self {
         MultiStepPrimer {
             times: vec![t_0],
             states: vec![y_0.clone()],
@@ -46,7 +46,7 @@ where
             fxn: None,
         }
     }
-    fn push(&mut self, t_new: f64, y_new: &VectorN<f64, N>, dy_new: VectorN<f64, N>) {
+    fn push(&mut self, t_new: f64, y_new: &OVector<f64, N>, dy_new: OVector<f64, N>) {
         self.times.push(t_new);
         self.states.push(y_new.clone());
         self.dyn_evals.push(dy_new);
@@ -54,9 +54,9 @@ where
 }
 impl<N: DimName + Dim> AdamsPrimer for MultiStepPrimer<N>
 where
-    DefaultAllocator: Allocator<f64, N>,
+    DefaultAllocator: Allocator<N>,
 {
-    type OutVec = VectorN<f64, N>;
+    type OutVec = OVector<f64, N>;
     type TolGen = Tolerances<N>;
     type Primer = Self;
 
@@ -72,7 +72,7 @@ where
         let abs_tol = tol
             .abs
             .clone()
-            .unwrap_or(VectorN::<f64, N>::repeat(1e-6_f64));
+            .unwrap_or(OVector::<f64, N>::repeat(1e-6_f64));
         let rel_tol = tol.rel.unwrap_or(1e-9_f64);
 
         // initialize an empty results object
@@ -91,7 +91,7 @@ where
             if t_last >= t_0 + dt_max {
                 return Err("Initializer over-stepped maximum step");
             }
-            step_res = RK32.step(fxn, t_last, &y_last, sub_step, &abs_tol, rel_tol);
+            step_res = RK32.step(&fxn, t_last, &y_last, sub_step, &abs_tol, rel_tol);
             step_revision = RK32.revise_step(step_res.error, sub_step);
 
             match step_revision {
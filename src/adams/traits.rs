@@ -57,7 +57,6 @@ pub trait AdamsCorrector {
         &self,
         step: f64,
         t_nxt: f64,
-        fxn: fn(f64, &<Self as AdamsCorrector>::OutVec) -> <Self as AdamsCorrector>::OutVec,
         prediction: &<Self as AdamsCorrector>::OutVec,
     ) -> <Self as AdamsCorrector>::OutVec;
 }
@@ -72,7 +71,6 @@ pub trait AdamsStepper: AdamsUpdate + AdamsPredictor + AdamsCorrector + AdamsIni
     fn step(
         &mut self,
         order: usize,
-        fxn: fn(f64, &<Self as AdamsStepper>::OutVec) -> <Self as AdamsStepper>::OutVec,
         t_nxt: f64,
     ) -> Result<<Self as AdamsStepper>::OutVec, &'static str>;
 }
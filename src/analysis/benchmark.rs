@@ -0,0 +1,293 @@
+/// Solver Benchmarks (analysis/benchmark)
+///
+/// Side by side accuracy and cost numbers of integrators on the standard test
+/// problems (see `systems::stiff` and `systems::nonstiff`), in the manner of the
+/// work precision diagrams of Hairer & Wanner and of the "Test Set for IVP Solvers".
+/// A `Benchmark` is a problem with its reference solution at the end of the
+/// interval, and a `Solver` anything which integrates a problem to a tolerance:
+/// the adaptive steppers through `Adaptive`, or an external code wrapped the same
+/// way. `compare` runs every solver at every tolerance and reports, for each run,
+/// the error against the reference, the significant correct digits (-log10 of the
+/// error), the number of dynamics evaluations (counted with `CountedSystem`) and
+/// the wall time. `report` lays the runs out as a table.
+///
+/// The error is the largest over the components of |y - y_ref| / max(|y_ref|, floor),
+/// relative for components of the size of the floor of the problem or more and
+/// absolute for smaller ones (the test set measures relative errors only, which
+/// breaks down on components whose reference vanishes, like those of a periodic
+/// orbit returning to an axis).
+///
+/// CVODE (SUNDIALS) plugs in as another `Solver` with the `sundials` feature (see
+/// `analysis::cvode`). The bindings build the C library, so they are optional.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DefaultAllocator, Dim, DimName, OVector, U3, U4, U8};
+
+// local imports
+use crate::runge_kutta::adaptive::AdaptiveStep;
+use crate::runge_kutta::common::IntegOptions;
+use crate::systems::counted::CountedSystem;
+use crate::systems::nonstiff::{Arenstorf, DetestD, ARENSTORF_END, ARENSTORF_INIT, DETEST_D_END};
+use crate::systems::stiff::{
+    Hires, Robertson, HIRES_END, HIRES_INIT, HIRES_REF, ROBERTSON_END, ROBERTSON_INIT,
+    ROBERTSON_REF,
+};
+use crate::systems::OdeSystem;
+
+// Standard library imports
+use std::time::{Duration, Instant};
+
+// === End Imports ===
+
+// Dynamics handed to a solver
+pub type Dynamics<'a, N> = &'a dyn Fn(f64, &OVector<f64, N>) -> OVector<f64, N>;
+
+// Problem with a reference solution at the end of its interval
+#[derive(Debug, Clone)]
+pub struct Benchmark<N: Dim + DimName, S>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    pub name: String,
+    pub system: S,
+    pub t_0: f64,
+    pub y_0: OVector<f64, N>,
+    pub t_end: f64,
+    // Solution at t_end
+    pub reference: OVector<f64, N>,
+    // Magnitude below which components are compared absolutely
+    pub floor: f64,
+}
+
+// Integrator run by the benchmarks
+pub trait Solver<N: Dim + DimName>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    fn name(&self) -> String;
+
+    // Solution at t_end of y' = fxn(t, y) from y_0 at t_0, to the tolerance tol
+    fn solve(
+        &self,
+        fxn: Dynamics<N>,
+        t_0: f64,
+        y_0: &OVector<f64, N>,
+        t_end: f64,
+        tol: f64,
+    ) -> Result<OVector<f64, N>, &'static str>;
+}
+
+// Adaptive stepper as a solver, with the tolerance as both atol and rtol
+pub struct Adaptive<'a, A: AdaptiveStep> {
+    name: String,
+    stepper: &'a A,
+}
+
+impl<'a, A: AdaptiveStep> Adaptive<'a, A> {
+    pub fn new(name: &str, stepper: &'a A) -> Self {
+        Adaptive {
+            name: name.to_string(),
+            stepper,
+        }
+    }
+}
+
+impl<'a, A: AdaptiveStep, N: Dim + DimName> Solver<N> for Adaptive<'a, A>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn solve(
+        &self,
+        fxn: Dynamics<N>,
+        t_0: f64,
+        y_0: &OVector<f64, N>,
+        t_end: f64,
+        tol: f64,
+    ) -> Result<OVector<f64, N>, &'static str> {
+        let opts = IntegOptions {
+            atol: Some(OVector::<f64, N>::repeat(tol)),
+            rtol: Some(tol),
+            ..IntegOptions::default()
+        };
+        let ans = self
+            .stepper
+            .integrate(fxn, t_0, y_0.clone(), t_end - t_0, opts)?;
+        Ok(ans.last_y().clone())
+    }
+}
+
+// Outcome of a solver on a benchmark at a tolerance
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub solver: String,
+    pub problem: String,
+    pub tol: f64,
+    // Error against the reference
+    pub error: f64,
+    // Significant correct digits, -log10(error)
+    pub digits: f64,
+    // Dynamics evaluations
+    pub evals: u64,
+    // Wall time of the run
+    pub elapsed: Duration,
+}
+
+impl<N: Dim + DimName, S: OdeSystem<N>> Benchmark<N, S>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    // Error of a solution at t_end against the reference
+    pub fn error(&self, y: &OVector<f64, N>) -> f64 {
+        y.iter()
+            .zip(self.reference.iter())
+            .map(|(a, b)| (a - b).abs() / b.abs().max(self.floor))
+            .fold(0.0, f64::max)
+    }
+
+    // Runs every solver at every tolerance
+    pub fn compare(
+        &self,
+        solvers: &[&dyn Solver<N>],
+        tols: &[f64],
+    ) -> Result<Vec<Comparison>, &'static str> {
+        let counted = CountedSystem::new(|t: f64, y: &OVector<f64, N>| self.system.dynamics(t, y));
+        let fxn = |t: f64, y: &OVector<f64, N>| counted.dynamics(t, y);
+        let mut rows = Vec::new();
+        for solver in solvers {
+            for &tol in tols {
+                counted.reset();
+                let start = Instant::now();
+                let y = solver.solve(&fxn, self.t_0, &self.y_0, self.t_end, tol)?;
+                let elapsed = start.elapsed();
+                let error = self.error(&y);
+                rows.push(Comparison {
+                    solver: solver.name(),
+                    problem: self.name.clone(),
+                    tol,
+                    error,
+                    digits: -error.log10(),
+                    evals: counted.rhs_calls(),
+                    elapsed,
+                });
+            }
+        }
+        Ok(rows)
+    }
+}
+
+impl Benchmark<U4, Arenstorf> {
+    // One period of the Arenstorf orbit
+    pub fn arenstorf() -> Self {
+        Benchmark {
+            name: "arenstorf".to_string(),
+            system: Arenstorf::EARTH_MOON,
+            t_0: 0.0,
+            y_0: *ARENSTORF_INIT,
+            t_end: ARENSTORF_END,
+            reference: *ARENSTORF_INIT,
+            floor: 1.0,
+        }
+    }
+}
+
+impl Benchmark<U4, DetestD> {
+    // DETEST class D problem of the given eccentricity
+    pub fn detest_d(problem: DetestD) -> Self {
+        Benchmark {
+            name: format!("detest d e = {}", problem.e),
+            system: problem,
+            t_0: 0.0,
+            y_0: problem.init(),
+            t_end: DETEST_D_END,
+            reference: problem.exact(DETEST_D_END),
+            floor: 1.0,
+        }
+    }
+}
+
+impl Benchmark<U3, Robertson> {
+    pub fn robertson() -> Self {
+        Benchmark {
+            name: "robertson".to_string(),
+            system: Robertson,
+            t_0: 0.0,
+            y_0: *ROBERTSON_INIT,
+            t_end: ROBERTSON_END,
+            reference: *ROBERTSON_REF,
+            floor: 0.0,
+        }
+    }
+}
+
+impl Benchmark<U8, Hires> {
+    pub fn hires() -> Self {
+        Benchmark {
+            name: "hires".to_string(),
+            system: Hires,
+            t_0: 0.0,
+            y_0: *HIRES_INIT,
+            t_end: HIRES_END,
+            reference: *HIRES_REF,
+            floor: 0.0,
+        }
+    }
+}
+
+// Table of the runs, one per line
+pub fn report(rows: &[Comparison]) -> String {
+    let mut table = format!(
+        "{:<20} {:<20} {:>8} {:>10} {:>7} {:>9} {:>12}\n",
+        "solver", "problem", "tol", "error", "digits", "evals", "time [us]"
+    );
+    for row in rows {
+        table.push_str(&format!(
+            "{:<20} {:<20} {:>8.0e} {:>10.3e} {:>7.2} {:>9} {:>12}\n",
+            row.solver,
+            row.problem,
+            row.tol,
+            row.error,
+            row.digits,
+            row.evals,
+            row.elapsed.as_micros()
+        ));
+    }
+    table
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runge_kutta::rk_embed::{DOPRI78, RKF45};
+
+    #[test]
+    fn test_compare() {
+        // on the kepler problem at e = 0.5 the eighth order pair buys more digits
+        // for fewer evaluations than the fifth order one, and both gain digits as
+        // the tolerance tightens
+        let benchmark = Benchmark::detest_d(DetestD::D3);
+        let dopri = Adaptive::new("dopri78", &*DOPRI78);
+        let rkf = Adaptive::new("rkf45", &*RKF45);
+        let rows = benchmark.compare(&[&dopri, &rkf], &[1e-6, 1e-10]).unwrap();
+        assert_eq!(rows.len(), 4);
+        assert!(rows.iter().all(|row| row.evals > 0 && row.digits > 2.5));
+        assert!(rows[1].digits > rows[0].digits + 2.0);
+        assert!(rows[3].digits > rows[2].digits + 2.0);
+        assert!(rows[1].evals < rows[3].evals);
+        assert_eq!(
+            (rows[0].solver.as_str(), rows[2].solver.as_str()),
+            ("dopri78", "rkf45")
+        );
+        assert_eq!(report(&rows).lines().count(), 5);
+        // the exact solution is exact
+        let y = benchmark.system.exact(DETEST_D_END);
+        assert_eq!(benchmark.error(&y), 0.0);
+    }
+}
@@ -0,0 +1,346 @@
+/// Stochastic Collocation (analysis/collocation)
+///
+/// Propagates uncertain parameters through a model (typically an integration to a
+/// final time) by evaluating it at the nodes of a tensor product gauss rule over
+/// the parameter distributions: gauss-legendre for uniform parameters and
+/// gauss-hermite for normal ones. The model runs are independent and are spread
+/// over threads with `sweep`.
+///
+/// From the runs the statistics of every output component follow as quadrature
+/// sums:
+/// - the mean and variance
+/// - the coefficients of the polynomial chaos expansion (PCE) in the orthonormal
+///   basis of the distributions (legendre / probabilists' hermite polynomials) for
+///   every multi-index of total degree below the number of nodes per parameter
+///
+/// With n nodes per parameter the moments are exact for outputs that are
+/// polynomials of degree up to 2n - 1 in each parameter, and converge
+/// exponentially for smooth outputs. The number of runs grows as n^d with the
+/// number d of parameters, so this suits a handful of parameters (sampled
+/// ensembles, see `ensemble`, scale to more).
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DefaultAllocator, Dim, DimName, OVector};
+
+// local imports
+use crate::lagrange::quadrature::{gauss_hermite, gauss_legendre};
+use crate::utils::sweep::sweep;
+
+// === End Imports ===
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Uncertain {
+    // Uniformly distributed on [lower, upper]
+    Uniform { lower: f64, upper: f64 },
+    // Normally distributed
+    Normal { mean: f64, std: f64 },
+}
+
+impl Uncertain {
+    // Quadrature nodes (in parameter space) and probability weights
+    fn rule(&self, n: usize) -> (Vec<f64>, Vec<f64>) {
+        match *self {
+            Uncertain::Uniform { lower, upper } => {
+                let (nodes, weights) = gauss_legendre(n);
+                let mid = 0.5 * (lower + upper);
+                let half = 0.5 * (upper - lower);
+                (nodes.iter().map(|x| mid + half * x).collect(), weights)
+            }
+            Uncertain::Normal { mean, std } => {
+                let (nodes, weights) = gauss_hermite(n);
+                (nodes.iter().map(|x| mean + std * x).collect(), weights)
+            }
+        }
+    }
+
+    // Parameter value with cumulative probability u in (0, 1), mapping uniform
+    // samples onto the distribution (see `ensemble`)
+    pub fn quantile(&self, u: f64) -> f64 {
+        match *self {
+            Uncertain::Uniform { lower, upper } => lower + u * (upper - lower),
+            Uncertain::Normal { mean, std } => mean + std * normal_quantile(u),
+        }
+    }
+
+    // Orthonormal polynomials of degrees 0..=degree at the parameter value p
+    fn basis(&self, p: f64, degree: usize) -> Vec<f64> {
+        // three term recurrences of the monic-free legendre P_k on [-1, 1] and the
+        // probabilists' hermite He_k, normalized afterwards
+        let (x, legendre) = match *self {
+            Uncertain::Uniform { lower, upper } => {
+                ((2.0 * p - lower - upper) / (upper - lower), true)
+            }
+            Uncertain::Normal { mean, std } => ((p - mean) / std, false),
+        };
+        let mut polys = vec![1.0, x];
+        for k in 1..degree {
+            let k_f = k as f64;
+            let next = if legendre {
+                ((2.0 * k_f + 1.0) * x * polys[k] - k_f * polys[k - 1]) / (k_f + 1.0)
+            } else {
+                x * polys[k] - k_f * polys[k - 1]
+            };
+            polys.push(next);
+        }
+        polys.truncate(degree + 1);
+        let mut factorial = 1.0;
+        for (k, val) in polys.iter_mut().enumerate() {
+            if k > 0 {
+                factorial *= k as f64;
+            }
+            *val *= if legendre {
+                (2.0 * k as f64 + 1.0).sqrt()
+            } else {
+                1.0 / factorial.sqrt()
+            };
+        }
+        polys
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CollocationResult<M: Dim + DimName>
+where
+    DefaultAllocator: Allocator<M>,
+{
+    // Parameter values of each model run
+    pub nodes: Vec<Vec<f64>>,
+    // Probability weight of each model run
+    pub weights: Vec<f64>,
+    // Mean of each output component
+    pub mean: OVector<f64, M>,
+    // Variance of each output component
+    pub variance: OVector<f64, M>,
+    // PCE coefficients, by multi-index (polynomial degree in each parameter)
+    pub coefficients: Vec<(Vec<usize>, OVector<f64, M>)>,
+}
+
+// Propagates the uncertain parameters through the model with `nodes` quadrature
+// nodes per parameter, on up to `threads` threads (all available cores when None)
+pub fn stochastic_collocation<F, E, M: Dim + DimName>(
+    params: &[Uncertain],
+    nodes: usize,
+    threads: Option<usize>,
+    model: F,
+) -> Result<CollocationResult<M>, &'static str>
+where
+    F: Fn(&[f64]) -> Result<OVector<f64, M>, E> + Sync,
+    E: Into<&'static str>,
+    DefaultAllocator: Allocator<M>,
+    OVector<f64, M>: Send,
+{
+    if params.is_empty() || nodes == 0 {
+        return Err("[COLLOCATION] At least one parameter and one node are needed");
+    }
+    if params.iter().any(|p| match *p {
+        Uncertain::Uniform { lower, upper } => upper <= lower || upper.is_nan() || lower.is_nan(),
+        Uncertain::Normal { std, .. } => std <= 0.0 || std.is_nan(),
+    }) {
+        return Err("[COLLOCATION] Parameter distributions must have a positive width");
+    }
+
+    // tensor product grid, the last parameter varying fastest
+    let rules: Vec<(Vec<f64>, Vec<f64>)> = params.iter().map(|p| p.rule(nodes)).collect();
+    let mut grid: Vec<(Vec<f64>, f64)> = vec![(Vec::new(), 1.0)];
+    for (points, weights) in rules.iter() {
+        grid = grid
+            .iter()
+            .flat_map(|(node, weight)| {
+                points.iter().zip(weights.iter()).map(move |(p, w)| {
+                    let mut node = node.clone();
+                    node.push(*p);
+                    (node, weight * w)
+                })
+            })
+            .collect();
+    }
+    let (grid_nodes, grid_weights): (Vec<Vec<f64>>, Vec<f64>) = grid.into_iter().unzip();
+
+    let outputs = sweep(&grid_nodes, threads, |node| model(node).map_err(Into::into))
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut mean = OVector::<f64, M>::zeros();
+    for (w, y) in grid_weights.iter().zip(outputs.iter()) {
+        mean += y * *w;
+    }
+    let mut variance = OVector::<f64, M>::zeros();
+    for (w, y) in grid_weights.iter().zip(outputs.iter()) {
+        variance += (y - &mean).map(|dev| dev * dev) * *w;
+    }
+
+    // projections onto the orthonormal basis for every total degree below `nodes`
+    let degree = nodes - 1;
+    let bases: Vec<Vec<Vec<f64>>> = grid_nodes
+        .iter()
+        .map(|node| {
+            params
+                .iter()
+                .zip(node.iter())
+                .map(|(param, p)| param.basis(*p, degree))
+                .collect()
+        })
+        .collect();
+    let coefficients = multi_indices(params.len(), degree)
+        .into_iter()
+        .map(|index| {
+            let mut coeff = OVector::<f64, M>::zeros();
+            for ((w, y), basis) in grid_weights.iter().zip(outputs.iter()).zip(bases.iter()) {
+                let psi: f64 = index.iter().zip(basis.iter()).map(|(k, b)| b[*k]).product();
+                coeff += y * (w * psi);
+            }
+            (index, coeff)
+        })
+        .collect();
+
+    Ok(CollocationResult {
+        nodes: grid_nodes,
+        weights: grid_weights,
+        mean,
+        variance,
+        coefficients,
+    })
+}
+
+// Inverse of the standard normal cumulative distribution, to a relative accuracy
+// of about 1e-9 (Acklam's rational approximations). Odd about u = 1/2
+fn normal_quantile(u: f64) -> f64 {
+    const A: [f64; 6] = [
+        -3.969_683_028_665_376e1,
+        2.209_460_984_245_205e2,
+        -2.759_285_104_469_687e2,
+        1.383_577_518_672_69e2,
+        -3.066_479_806_614_716e1,
+        2.506_628_277_459_239,
+    ];
+    const B: [f64; 5] = [
+        -5.447_609_879_822_406e1,
+        1.615_858_368_580_409e2,
+        -1.556_989_798_598_866e2,
+        6.680_131_188_771_972e1,
+        -1.328_068_155_288_572e1,
+    ];
+    const C: [f64; 6] = [
+        -7.784_894_002_430_293e-3,
+        -3.223_964_580_411_365e-1,
+        -2.400_758_277_161_838,
+        -2.549_732_539_343_734,
+        4.374_664_141_464_968,
+        2.938_163_982_698_783,
+    ];
+    const D: [f64; 4] = [
+        7.784_695_709_041_462e-3,
+        3.224_671_290_700_398e-1,
+        2.445_134_137_142_996,
+        3.754_408_661_907_416,
+    ];
+    // boundary of the central region
+    const P_LOW: f64 = 0.02425;
+
+    // tail of the distribution at probability p < P_LOW, negative
+    let tail = |p: f64| {
+        let q = (-2.0 * p.ln()).sqrt();
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5])
+            / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0)
+    };
+    if u < P_LOW {
+        tail(u)
+    } else if u > 1.0 - P_LOW {
+        -tail(1.0 - u)
+    } else {
+        let q = u - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q
+            / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.0)
+    }
+}
+
+// Multi-indices of `dims` entries with total degree up to `degree`, by increasing
+// total degree
+fn multi_indices(dims: usize, degree: usize) -> Vec<Vec<usize>> {
+    let mut indices: Vec<Vec<usize>> = vec![Vec::new()];
+    for _ in 0..dims {
+        indices = indices
+            .iter()
+            .flat_map(|index| {
+                let used: usize = index.iter().sum();
+                (0..=degree - used).map(move |k| {
+                    let mut index = index.clone();
+                    index.push(k);
+                    index
+                })
+            })
+            .collect();
+    }
+    indices.sort_by_key(|index| index.iter().sum::<usize>());
+    indices
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runge_kutta::common::IntegOptions;
+    use crate::runge_kutta::fixed::FixedStep;
+    use crate::runge_kutta::rk_simp::RK4;
+    use na::Vector2;
+
+    #[test]
+    fn test_stochastic_collocation() {
+        // y_0' = -k y_0 and y_1' = c y_1 to t = 1: the outputs are e^-k and e^c
+        let params = [
+            Uncertain::Uniform {
+                lower: 0.5,
+                upper: 1.5,
+            },
+            Uncertain::Normal {
+                mean: 0.0,
+                std: 0.1,
+            },
+        ];
+        let model = |p: &[f64]| {
+            let (k, c) = (p[0], p[1]);
+            let fxn = move |_t: f64, y: &Vector2<f64>| Vector2::new(-k * y[0], c * y[1]);
+            RK4.integrate(
+                fxn,
+                0.0,
+                Vector2::new(1.0, 1.0),
+                1.0,
+                0.01,
+                IntegOptions::default(),
+            )
+            .map(|ans| *ans.last_y())
+        };
+        let ans = stochastic_collocation(&params, 6, None, model).unwrap();
+        assert_eq!(ans.nodes.len(), 36);
+        assert!((ans.weights.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+
+        let mean_0 = (-0.5_f64).exp() - (-1.5_f64).exp();
+        let var_0 = 0.5 * ((-1.0_f64).exp() - (-3.0_f64).exp()) - mean_0 * mean_0;
+        let mean_1 = 0.005_f64.exp();
+        let var_1 = 0.02_f64.exp() - 0.01_f64.exp();
+        assert!((ans.mean[0] - mean_0).abs() < 1e-8);
+        assert!((ans.mean[1] - mean_1).abs() < 1e-8);
+        assert!((ans.variance[0] - var_0).abs() < 1e-8);
+        assert!((ans.variance[1] - var_1).abs() < 1e-8);
+
+        // the PCE constant term is the mean and the rest carries the variance
+        assert_eq!(ans.coefficients.len(), 21);
+        let (index, coeff) = &ans.coefficients[0];
+        assert_eq!(index, &vec![0, 0]);
+        assert!((coeff - ans.mean).norm() < 1e-12);
+        let pce_var = ans.coefficients[1..]
+            .iter()
+            .fold(Vector2::zeros(), |acc, (_, c)| acc + c.component_mul(c));
+        assert!((pce_var - ans.variance).norm() < 1e-8);
+
+        let bad = [Uncertain::Normal {
+            mean: 0.0,
+            std: 0.0,
+        }];
+        assert!(stochastic_collocation(&bad, 3, None, model).is_err());
+    }
+}
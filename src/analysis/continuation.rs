@@ -0,0 +1,296 @@
+/// Continuation of Equilibria (analysis/continuation)
+///
+/// Tracks a branch of steady states f(y; lambda) = 0 of a system as the parameter
+/// lambda is varied, e.g. to find where an equilibrium appears, disappears or
+/// changes stability as a design or physical parameter changes.
+///
+/// Two methods are provided:
+/// - `natural_continuation` steps lambda through a list of values and corrects the
+///   state at each with a newton solve. Simple, but fails at a fold (turning point)
+///   of the branch since no equilibrium exists past it for the next lambda
+/// - `arclength_continuation` (pseudo-arclength) steps along the branch itself by
+///   solving for (y, lambda) together, with the step measured along the tangent of
+///   the branch. It follows the branch around folds and reports where they occur
+///
+/// Both correct with the matrix free `newton_krylov` solver.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DMatrix, DVector, DefaultAllocator, Dim, DimName, Dyn, OVector};
+
+// local imports
+use crate::utils::linalg::Gmres;
+use crate::utils::newton_raphson::newton_krylov;
+
+// === End Imports ===
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContinuationOptions {
+    // Arclength step. A negative step starts in the direction of decreasing lambda
+    pub step: Option<f64>,
+    // Maximum number of continuation steps
+    pub max_steps: Option<usize>,
+    // Continuation stops once lambda leaves [lambda_min, lambda_max]
+    pub lambda_min: Option<f64>,
+    pub lambda_max: Option<f64>,
+    // Residual tolerance of the newton corrector
+    pub tol: Option<f64>,
+}
+
+impl ContinuationOptions {
+    pub fn default() -> Self {
+        ContinuationOptions {
+            step: Some(0.01),
+            max_steps: Some(1000),
+            lambda_min: None,
+            lambda_max: None,
+            tol: Some(1e-10),
+        }
+    }
+}
+
+// Turning point of a branch, where it reverses direction in lambda
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fold<N: Dim + DimName>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    // Estimated state at the fold
+    pub y: OVector<f64, N>,
+    // Estimated parameter value at the fold
+    pub lambda: f64,
+    // Index of the last branch point before the fold
+    pub index: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Branch<N: Dim + DimName>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    // Equilibrium states along the branch
+    pub states: Vec<OVector<f64, N>>,
+    // Parameter value of each state
+    pub params: Vec<f64>,
+    // Folds passed along the branch
+    pub folds: Vec<Fold<N>>,
+}
+
+// Natural parameter continuation over the given parameter values
+//
+// Each equilibrium is predicted by linear extrapolation of the previous two and
+// corrected with newton iterations at fixed lambda. Errors when a correction fails,
+// typically because the branch folded back before reaching the next lambda.
+pub fn natural_continuation<F, N: Dim + DimName>(
+    fxn: F,
+    y_0: OVector<f64, N>,
+    lambdas: &[f64],
+    tol: f64,
+) -> Result<Branch<N>, &'static str>
+where
+    F: Fn(&OVector<f64, N>, f64) -> OVector<f64, N>,
+    DefaultAllocator: Allocator<N>,
+{
+    let solver = Gmres::default();
+    let mut states: Vec<OVector<f64, N>> = Vec::with_capacity(lambdas.len());
+    for (k, &lambda) in lambdas.iter().enumerate() {
+        let guess = match k {
+            0 => y_0.clone(),
+            1 => states[0].clone(),
+            _ => {
+                let ratio = (lambda - lambdas[k - 1]) / (lambdas[k - 1] - lambdas[k - 2]);
+                &states[k - 1] + (&states[k - 1] - &states[k - 2]) * ratio
+            }
+        };
+        let y = newton_krylov(|y| fxn(y, lambda), guess, tol, &solver, None)?;
+        states.push(y);
+    }
+    Ok(Branch {
+        states,
+        params: lambdas.to_vec(),
+        folds: Vec::new(),
+    })
+}
+
+// Pseudo-arclength continuation from an (approximate) equilibrium y_0 at lambda_0
+//
+// Unknowns are z = (y, lambda). From a point z_k with unit tangent t_k the next
+// point is predicted at z_k + ds t_k and corrected onto the branch by solving
+//   f(y, lambda) = 0,  t_k . (z - z_k) = ds
+// The tangent t_{k+1} solves [df/dz; t_k^T] t = (0, ..., 0, 1), which keeps the
+// orientation along the branch. A fold lies between two points where the lambda
+// component of the tangent changes sign. The step is halved when a correction fails.
+pub fn arclength_continuation<F, N: Dim + DimName>(
+    fxn: F,
+    y_0: OVector<f64, N>,
+    lambda_0: f64,
+    opts: ContinuationOptions,
+) -> Result<Branch<N>, &'static str>
+where
+    F: Fn(&OVector<f64, N>, f64) -> OVector<f64, N>,
+    DefaultAllocator: Allocator<N>,
+{
+    const MAX_HALVINGS: usize = 8;
+
+    let defaults = ContinuationOptions::default();
+    let step = opts.step.or(defaults.step).unwrap();
+    let max_steps = opts.max_steps.or(defaults.max_steps).unwrap();
+    let lambda_min = opts.lambda_min.unwrap_or(f64::NEG_INFINITY);
+    let lambda_max = opts.lambda_max.unwrap_or(f64::INFINITY);
+    let tol = opts.tol.or(defaults.tol).unwrap();
+    if step == 0.0 {
+        return Err("[CONTINUATION] Step must be non-zero");
+    }
+
+    let n = N::dim();
+    let solver = Gmres::default();
+    let to_state = |z: &DVector<f64>| OVector::<f64, N>::from_iterator(z.iter().take(n).cloned());
+    let residual = |z: &DVector<f64>| {
+        DVector::<f64>::from_iterator(n, fxn(&to_state(z), z[n]).iter().cloned())
+    };
+
+    // start from a converged equilibrium
+    let y_0 = newton_krylov(|y| fxn(y, lambda_0), y_0, tol, &solver, None)?;
+    let mut z = DVector::<f64>::from_iterator(n + 1, y_0.iter().cloned().chain(Some(lambda_0)));
+
+    // the initial tangent points in the direction of the sign of the step
+    let mut t_prev = DVector::<f64>::zeros(n + 1);
+    t_prev[n] = step.signum();
+    let mut tangent = branch_tangent(&residual, &z, &t_prev)?;
+    let mut ds = step.abs();
+
+    let mut branch = Branch {
+        states: vec![y_0],
+        params: vec![lambda_0],
+        folds: Vec::new(),
+    };
+    for _ in 0..max_steps {
+        // predict along the tangent and correct onto the branch
+        let mut halvings = 0;
+        let z_new = loop {
+            let z_pred = &z + &tangent * ds;
+            let extended = |w: &DVector<f64>| {
+                let f_w = residual(w);
+                let arc = tangent.dot(&(w - &z)) - ds;
+                DVector::<f64>::from_iterator(n + 1, f_w.iter().cloned().chain(Some(arc)))
+            };
+            match newton_krylov::<_, _, Dyn>(extended, z_pred, tol, &solver, None) {
+                Ok(z_new) => break z_new,
+                Err(msg) if halvings == MAX_HALVINGS => return Err(msg),
+                Err(_) => {
+                    halvings += 1;
+                    ds *= 0.5;
+                }
+            }
+        };
+        let tangent_new = branch_tangent(&residual, &z_new, &tangent)?;
+
+        // locate a fold by linear interpolation of the lambda component of the tangent
+        if tangent[n] * tangent_new[n] < 0.0 {
+            let frac = tangent[n] / (tangent[n] - tangent_new[n]);
+            let z_fold = &z + (&z_new - &z) * frac;
+            branch.folds.push(Fold {
+                y: to_state(&z_fold),
+                lambda: z_fold[n],
+                index: branch.states.len() - 1,
+            });
+        }
+
+        branch.states.push(to_state(&z_new));
+        branch.params.push(z_new[n]);
+        z = z_new;
+        tangent = tangent_new;
+        if z[n] < lambda_min || z[n] > lambda_max {
+            break;
+        }
+    }
+    Ok(branch)
+}
+
+// Unit tangent of the branch at z, oriented to continue in the direction of t_prev
+fn branch_tangent<G>(
+    residual: &G,
+    z: &DVector<f64>,
+    t_prev: &DVector<f64>,
+) -> Result<DVector<f64>, &'static str>
+where
+    G: Fn(&DVector<f64>) -> DVector<f64>,
+{
+    // Approximately cube root of ULP precision
+    const H_FACTOR: f64 = 6.055_454_452_393_343e-6_f64;
+
+    // central difference jacobian of f with respect to (y, lambda), bordered by t_prev
+    let n = z.len() - 1;
+    let mut mat = DMatrix::<f64>::zeros(n + 1, n + 1);
+    for j in 0..=n {
+        let h = H_FACTOR * z[j].abs().max(1.0);
+        let mut shift = DVector::<f64>::zeros(n + 1);
+        shift[j] = h;
+        let column = (residual(&(z + &shift)) - residual(&(z - &shift))) / (2.0 * h);
+        mat.view_mut((0, j), (n, 1)).copy_from(&column);
+        mat[(n, j)] = t_prev[j];
+    }
+    let mut rhs = DVector::<f64>::zeros(n + 1);
+    rhs[n] = 1.0;
+    let tangent = mat
+        .lu()
+        .solve(&rhs)
+        .ok_or("[CONTINUATION] Singular jacobian, the branch may bifurcate")?;
+    Ok(tangent.normalize())
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use na::{Vector1, Vector2};
+
+    // saddle-node: y0 = +-sqrt(lambda) meet at a fold at lambda = 0
+    fn saddle_node(y: &Vector2<f64>, lambda: f64) -> Vector2<f64> {
+        Vector2::new(lambda - y[0] * y[0], y[1] - 2.0 * y[0])
+    }
+
+    #[test]
+    fn test_arclength_fold() {
+        let opts = ContinuationOptions {
+            step: Some(-0.05),
+            lambda_max: Some(1.5),
+            ..ContinuationOptions::default()
+        };
+        let branch =
+            arclength_continuation(saddle_node, Vector2::new(1.1, 2.0), 1.0, opts).unwrap();
+        assert_eq!(branch.states.len(), branch.params.len());
+        for (y, lambda) in branch.states.iter().zip(branch.params.iter()) {
+            assert!(saddle_node(y, *lambda).norm() < 1e-8);
+        }
+        assert!((branch.states[0][0] - 1.0).abs() < 1e-10);
+
+        // the branch turns around the fold onto the lower half and stops past lambda_max
+        assert_eq!(branch.folds.len(), 1);
+        let fold = &branch.folds[0];
+        assert!(fold.lambda.abs() < 5e-3 && fold.y[0].abs() < 1e-2);
+        assert!(branch.params[fold.index] > 0.0 && branch.states[fold.index][0] > 0.0);
+        assert!(branch.states[fold.index + 1][0] < 0.0);
+        assert!(*branch.params.last().unwrap() > 1.5);
+        assert!(branch.states.last().unwrap()[0] < -1.0);
+    }
+
+    #[test]
+    fn test_natural_continuation() {
+        let fxn = |y: &Vector1<f64>, lambda: f64| Vector1::new(lambda - y[0].powi(3) - y[0]);
+        let lambdas: Vec<f64> = (0..=10).map(|k| k as f64 * 0.2).collect();
+        let branch = natural_continuation(fxn, Vector1::new(0.0), &lambdas, 1e-12).unwrap();
+        for (y, lambda) in branch.states.iter().zip(lambdas.iter()) {
+            assert!((y[0].powi(3) + y[0] - lambda).abs() < 1e-12);
+        }
+        assert!(branch.folds.is_empty());
+
+        // natural continuation cannot pass the fold of the saddle-node
+        let lambdas = [1.0, 0.5, 0.0, -0.5];
+        assert!(
+            natural_continuation(saddle_node, Vector2::new(1.0, 2.0), &lambdas, 1e-10).is_err()
+        );
+    }
+}
@@ -0,0 +1,262 @@
+/// CVODE Solver (analysis/cvode)
+///
+/// CVODE from SUNDIALS (through the `sundials-sys` bindings, `sundials` feature) as
+/// a benchmark `Solver`, for side by side accuracy and cost numbers against the
+/// integrators of this crate on the problems of `benchmark`. The variable order BDF
+/// methods with a dense newton solve are the reference for the stiff problems, the
+/// Adams-Moulton methods with fixed point iteration for the nonstiff ones.
+///
+/// CVode is called once to t_end (CV_NORMAL) with the tolerance as both the scalar
+/// reltol and abstol, as in `Adaptive`. The dynamics reach the C right hand side
+/// through the user data pointer. A panic in them must not unwind into C, so it is
+/// caught and reported to CVODE as an unrecoverable failure, and a non finite
+/// derivative as a recoverable one (CVODE then retries with a smaller step).
+///
+/// The bindings build SUNDIALS from the sources they vendor, which needs cmake and
+/// libclang.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DefaultAllocator, Dim, DimName, OVector};
+use sundials_sys::*;
+
+// local imports
+use super::benchmark::{Dynamics, Solver};
+
+// Standard library imports
+use std::ffi::{c_int, c_void};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+// === End Imports ===
+
+// Linear multistep family used by CVODE
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CvodeMethod {
+    // Variable order BDF (1 - 5) with a dense newton solve, for stiff problems
+    Bdf,
+    // Variable order Adams-Moulton (1 - 12) with fixed point iteration
+    Adams,
+}
+
+// CVODE as a benchmark solver
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cvode {
+    pub method: CvodeMethod,
+    // Limit on the internal steps to t_end (CVODE defaults to 500)
+    pub max_steps: i64,
+}
+
+impl Cvode {
+    pub fn new(method: CvodeMethod) -> Self {
+        Cvode {
+            method,
+            max_steps: 100_000,
+        }
+    }
+}
+
+// Right hand side handed to CVODE, evaluating the dynamics in the user data
+unsafe extern "C" fn rhs<N: Dim + DimName>(
+    t: realtype,
+    y: N_Vector,
+    dy: N_Vector,
+    user_data: *mut c_void,
+) -> c_int
+where
+    DefaultAllocator: Allocator<N>,
+{
+    let fxn = &*(user_data as *const Dynamics<N>);
+    let y = std::slice::from_raw_parts(N_VGetArrayPointer(y), N::dim());
+    let dy = std::slice::from_raw_parts_mut(N_VGetArrayPointer(dy), N::dim());
+    match catch_unwind(AssertUnwindSafe(|| {
+        fxn(t, &OVector::<f64, N>::from_column_slice(y))
+    })) {
+        Ok(val) if val.iter().all(|v| v.is_finite()) => {
+            dy.copy_from_slice(val.as_slice());
+            0
+        }
+        Ok(_) => 1,
+        Err(_) => -1,
+    }
+}
+
+// SUNDIALS objects of one solve, freed however the solve ends
+struct CvodeMem {
+    ctx: SUNContext,
+    mem: *mut c_void,
+    y: N_Vector,
+    matrix: SUNMatrix,
+    solver: SUNLinearSolver,
+    fixed_point: SUNNonlinearSolver,
+}
+
+impl Drop for CvodeMem {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.mem.is_null() {
+                CVodeFree(&mut self.mem);
+            }
+            if !self.solver.is_null() {
+                SUNLinSolFree(self.solver);
+            }
+            if !self.fixed_point.is_null() {
+                SUNNonlinSolFree(self.fixed_point);
+            }
+            if !self.matrix.is_null() {
+                SUNMatDestroy(self.matrix);
+            }
+            if !self.y.is_null() {
+                N_VDestroy(self.y);
+            }
+            if !self.ctx.is_null() {
+                SUNContext_Free(&mut self.ctx);
+            }
+        }
+    }
+}
+
+impl<N: Dim + DimName> Solver<N> for Cvode
+where
+    DefaultAllocator: Allocator<N>,
+{
+    fn name(&self) -> String {
+        match self.method {
+            CvodeMethod::Bdf => "cvode bdf".to_string(),
+            CvodeMethod::Adams => "cvode adams".to_string(),
+        }
+    }
+
+    fn solve(
+        &self,
+        fxn: Dynamics<N>,
+        t_0: f64,
+        y_0: &OVector<f64, N>,
+        t_end: f64,
+        tol: f64,
+    ) -> Result<OVector<f64, N>, &'static str> {
+        let dim = N::dim() as sunindextype;
+        let lmm = match self.method {
+            CvodeMethod::Bdf => CV_BDF,
+            CvodeMethod::Adams => CV_ADAMS,
+        };
+        let mut data: Dynamics<N> = fxn;
+        let mut cvode = CvodeMem {
+            ctx: ptr::null_mut(),
+            mem: ptr::null_mut(),
+            y: ptr::null_mut(),
+            matrix: ptr::null_mut(),
+            solver: ptr::null_mut(),
+            fixed_point: ptr::null_mut(),
+        };
+
+        unsafe {
+            if SUNContext_Create(comm_no_mpi(), &mut cvode.ctx) < 0 {
+                return Err("[CVODE] Could not create a SUNDIALS context");
+            }
+            cvode.y = N_VNew_Serial(dim, cvode.ctx);
+            cvode.mem = CVodeCreate(lmm, cvode.ctx);
+            if cvode.y.is_null() || cvode.mem.is_null() {
+                return Err("[CVODE] Could not allocate the solver");
+            }
+            std::slice::from_raw_parts_mut(N_VGetArrayPointer(cvode.y), N::dim())
+                .copy_from_slice(y_0.as_slice());
+
+            if CVodeInit(cvode.mem, Some(rhs::<N>), t_0, cvode.y) < 0
+                || CVodeSStolerances(cvode.mem, tol, tol) < 0
+                || CVodeSetMaxNumSteps(cvode.mem, self.max_steps as _) < 0
+                || CVodeSetUserData(cvode.mem, &mut data as *mut Dynamics<N> as *mut c_void) < 0
+            {
+                return Err("[CVODE] Could not initialize the solver");
+            }
+            if self.method == CvodeMethod::Bdf {
+                cvode.matrix = SUNDenseMatrix(dim, dim, cvode.ctx);
+                cvode.solver = SUNLinSol_Dense(cvode.y, cvode.matrix, cvode.ctx);
+                if cvode.matrix.is_null()
+                    || cvode.solver.is_null()
+                    || CVodeSetLinearSolver(cvode.mem, cvode.solver, cvode.matrix) < 0
+                {
+                    return Err("[CVODE] Could not attach the dense linear solver");
+                }
+            } else {
+                cvode.fixed_point = SUNNonlinSol_FixedPoint(cvode.y, 0, cvode.ctx);
+                if cvode.fixed_point.is_null()
+                    || CVodeSetNonlinearSolver(cvode.mem, cvode.fixed_point) < 0
+                {
+                    return Err("[CVODE] Could not attach the fixed point iteration");
+                }
+            }
+
+            let mut t = t_0;
+            match CVode(cvode.mem, t_end, cvode.y, &mut t, CV_NORMAL) {
+                CV_TOO_MUCH_WORK => Err("[CVODE] Maximum number of steps reached"),
+                CV_TOO_MUCH_ACC => Err("[CVODE] Tolerance below machine precision"),
+                CV_RHSFUNC_FAIL | CV_FIRST_RHSFUNC_ERR | CV_UNREC_RHSFUNC_ERR
+                | CV_REPTD_RHSFUNC_ERR => Err("[CVODE] Dynamics failed to evaluate"),
+                flag if flag < 0 => Err("[CVODE] Integration failed"),
+                _ => Ok(OVector::<f64, N>::from_column_slice(
+                    std::slice::from_raw_parts(N_VGetArrayPointer(cvode.y), N::dim()),
+                )),
+            }
+        }
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::benchmark::{report, Adaptive, Benchmark};
+    use crate::runge_kutta::rk_embed::DOPRI78;
+    use crate::systems::nonstiff::DetestD;
+
+    #[test]
+    fn test_cvode_stiff() {
+        // BDF on the stiff test set gains digits as the tolerance tightens
+        let cvode = Cvode::new(CvodeMethod::Bdf);
+        let rows = Benchmark::robertson()
+            .compare(&[&cvode], &[1e-8, 1e-11])
+            .unwrap();
+        println!("{}", report(&rows));
+        assert!(rows[0].digits > 2.0);
+        assert!(rows[1].digits > rows[0].digits);
+        let rows = Benchmark::hires().compare(&[&cvode], &[1e-9]).unwrap();
+        assert!(rows[0].digits > 3.0);
+    }
+
+    #[test]
+    fn test_cvode_side_by_side() {
+        // the adams methods and dopri78 on the kepler problem, through the same
+        // evaluation counting
+        let benchmark = Benchmark::detest_d(DetestD::D3);
+        let adams = Cvode::new(CvodeMethod::Adams);
+        let dopri = Adaptive::new("dopri78", &*DOPRI78);
+        let rows = benchmark.compare(&[&adams, &dopri], &[1e-10]).unwrap();
+        println!("{}", report(&rows));
+        assert_eq!(rows[0].solver, "cvode adams");
+        assert!(rows.iter().all(|row| row.evals > 0 && row.digits > 5.0));
+    }
+
+    #[test]
+    fn test_cvode_failure() {
+        // the step limit and a panic in the dynamics come back as errors
+        let benchmark = Benchmark::robertson();
+        let starved = Cvode {
+            max_steps: 5,
+            ..Cvode::new(CvodeMethod::Bdf)
+        };
+        let fxn = |t: f64, y: &OVector<f64, na::U3>| {
+            crate::systems::OdeSystem::dynamics(&benchmark.system, t, y)
+        };
+        let ans = starved.solve(&fxn, 0.0, &benchmark.y_0, benchmark.t_end, 1e-8);
+        assert_eq!(ans.unwrap_err(), "[CVODE] Maximum number of steps reached");
+
+        let panics = |_t: f64, _y: &OVector<f64, na::U3>| -> OVector<f64, na::U3> {
+            panic!("dynamics failed")
+        };
+        let ans = Cvode::new(CvodeMethod::Bdf).solve(&panics, 0.0, &benchmark.y_0, 1.0, 1e-8);
+        assert_eq!(ans.unwrap_err(), "[CVODE] Dynamics failed to evaluate");
+    }
+}
@@ -0,0 +1,582 @@
+/// Sampled Ensembles (analysis/ensemble)
+///
+/// Propagates uncertain parameters through a model by running it at sampled
+/// parameter values and averaging, the Monte Carlo counterpart of
+/// `stochastic_collocation`. Its cost does not grow with the number of parameters,
+/// but plain random sampling converges slowly: the error of the mean falls as
+/// n^(-1/2) with the number n of runs. The `Sampling` options trade that for
+/// structured samples which reach the same accuracy with far fewer runs:
+/// - `Random`: independent draws, the reference
+/// - `Antithetic`: draws in pairs u, 1 - u (mirrored through the median of every
+///   parameter). Cancels the odd part of the output, the whole error for outputs
+///   linear in the parameters
+/// - `LatinHypercube`: the range of every parameter is cut into n strata of equal
+///   probability, each holding exactly one run (McKay, Beckman & Conover, 1979).
+///   Removes the error of the additive part of the output
+/// - `Sobol`: the low discrepancy sequence of Sobol with the direction numbers of
+///   Joe & Kuo (2008), up to `SOBOL_DIMS` parameters. The error falls as nearly
+///   1 / n for smooth outputs; use powers of two for n
+///
+/// Samples are drawn in the unit cube and mapped onto the parameter distributions
+/// through their quantile functions (`Uncertain::quantile`). All of them come from
+/// the generator seeded with `seed` (see `rng`): the Sobol points are randomized
+/// with a digital shift, which keeps their structure, so every option gives an
+/// unbiased mean and repeating a run with other seeds estimates its error. The
+/// model runs are spread over threads with `sweep`, and the results do not depend
+/// on the thread count.
+///
+/// Rather than fixing the number of runs up front, `ensemble_adaptive` keeps adding
+/// runs until the standard error of the mean of a scalar statistic of the output
+/// falls below a target. Random, antithetic and Sobol samples extend their own
+/// sequence (the first n of a larger draw are the draw of n), so no run is wasted;
+/// latin hypercubes do not and are refused. The standard error is that of
+/// independent runs (of the pair averages for antithetic sampling), which
+/// overstates the error of the Sobol mean, so those stop late rather than early.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DefaultAllocator, Dim, DimName, OVector};
+
+// local imports
+use super::collocation::Uncertain;
+use crate::utils::rng::SplitRng;
+use crate::utils::sweep::sweep;
+
+// === End Imports ===
+
+// Number of parameters the Sobol sequence supports
+pub const SOBOL_DIMS: usize = 13;
+
+// Degree s, inner polynomial coefficients a and initial direction numbers m of
+// the primitive polynomials of the Sobol dimensions after the first (Joe & Kuo)
+const SOBOL_POLYS: [(usize, u32, [u32; 5]); SOBOL_DIMS - 1] = [
+    (1, 0, [1, 0, 0, 0, 0]),
+    (2, 1, [1, 3, 0, 0, 0]),
+    (3, 1, [1, 3, 1, 0, 0]),
+    (3, 2, [1, 1, 1, 0, 0]),
+    (4, 1, [1, 1, 3, 3, 0]),
+    (4, 4, [1, 3, 5, 13, 0]),
+    (5, 2, [1, 1, 5, 5, 17]),
+    (5, 4, [1, 1, 5, 5, 5]),
+    (5, 7, [1, 1, 7, 11, 19]),
+    (5, 11, [1, 1, 5, 1, 1]),
+    (5, 13, [1, 1, 1, 3, 11]),
+    (5, 14, [1, 3, 5, 5, 31]),
+];
+
+// Bits of the Sobol points
+const SOBOL_BITS: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sampling {
+    // Independent draws
+    Random,
+    // Pairs of draws mirrored through the median
+    Antithetic,
+    // One draw in each of n equal probability strata of every parameter
+    LatinHypercube,
+    // Digitally shifted Sobol sequence
+    Sobol,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnsembleResult<M: Dim + DimName>
+where
+    DefaultAllocator: Allocator<M>,
+{
+    // Parameter values of each model run
+    pub samples: Vec<Vec<f64>>,
+    // Output of each model run
+    pub outputs: Vec<OVector<f64, M>>,
+    // Sample mean of each output component
+    pub mean: OVector<f64, M>,
+    // Sample variance of each output component
+    pub variance: OVector<f64, M>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdaptiveStopping {
+    // Standard error of the mean of the statistic to stop at
+    pub target: f64,
+    // Runs added at a time (even for antithetic sampling, a power of two suits
+    // Sobol samples)
+    pub batch: usize,
+    // Most runs made before giving up on the target
+    pub max_samples: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdaptiveEnsemble<M: Dim + DimName>
+where
+    DefaultAllocator: Allocator<M>,
+{
+    // Runs made, with their mean and variance
+    pub ensemble: EnsembleResult<M>,
+    // Mean of the statistic over the runs
+    pub statistic: f64,
+    // Standard error of that mean
+    pub standard_error: f64,
+    // Whether the target was reached (rather than the largest number of runs)
+    pub converged: bool,
+}
+
+// Uniform on (0, 1), at the centers of the 2^-53 cells, so quantiles stay finite
+fn open_uniform(rng: &mut SplitRng) -> f64 {
+    rng.uniform() + 0.5 / (1u64 << 53) as f64
+}
+
+// Direction numbers of every Sobol dimension, as SOBOL_BITS bit fractions
+fn sobol_directions(dims: usize) -> Vec<[u32; SOBOL_BITS]> {
+    let mut directions = Vec::with_capacity(dims);
+    // the first dimension is the van der corput sequence in base 2
+    let mut first = [0; SOBOL_BITS];
+    for (k, v) in first.iter_mut().enumerate() {
+        *v = 1 << (SOBOL_BITS - 1 - k);
+    }
+    directions.push(first);
+    for (s, a, m) in SOBOL_POLYS.iter().take(dims.saturating_sub(1)) {
+        let s = *s;
+        let mut v = [0u32; SOBOL_BITS];
+        for k in 0..SOBOL_BITS {
+            v[k] = if k < s {
+                m[k] << (SOBOL_BITS - 1 - k)
+            } else {
+                let mut next = v[k - s] ^ (v[k - s] >> s);
+                for j in 1..s {
+                    if (a >> (s - 1 - j)) & 1 == 1 {
+                        next ^= v[k - j];
+                    }
+                }
+                next
+            };
+        }
+        directions.push(v);
+    }
+    directions
+}
+
+// `samples` points in the unit cube of `dims` dimensions, drawn with `sampling`
+// from the generator seeded with `seed`
+pub fn unit_samples(
+    sampling: Sampling,
+    samples: usize,
+    dims: usize,
+    seed: u64,
+) -> Result<Vec<Vec<f64>>, &'static str> {
+    if samples == 0 || dims == 0 {
+        return Err("[ENSEMBLE] At least one sample and one dimension are needed");
+    }
+    let mut rng = SplitRng::new(seed);
+    let points = match sampling {
+        Sampling::Random => (0..samples)
+            .map(|_| (0..dims).map(|_| open_uniform(&mut rng)).collect())
+            .collect(),
+        Sampling::Antithetic => {
+            if !samples.is_multiple_of(2) {
+                return Err("[ENSEMBLE] Antithetic sampling needs an even number of samples");
+            }
+            (0..samples / 2)
+                .flat_map(|_| {
+                    let u: Vec<f64> = (0..dims).map(|_| open_uniform(&mut rng)).collect();
+                    let mirror = u.iter().map(|u_j| 1.0 - u_j).collect();
+                    [u, mirror]
+                })
+                .collect()
+        }
+        Sampling::LatinHypercube => {
+            let mut points = vec![Vec::with_capacity(dims); samples];
+            let mut strata: Vec<usize> = (0..samples).collect();
+            for _ in 0..dims {
+                // fisher-yates shuffle of the strata over the samples
+                for i in (1..samples).rev() {
+                    let j = (rng.next_u64() % (i as u64 + 1)) as usize;
+                    strata.swap(i, j);
+                }
+                for (point, stratum) in points.iter_mut().zip(strata.iter()) {
+                    point.push((*stratum as f64 + open_uniform(&mut rng)) / samples as f64);
+                }
+            }
+            points
+        }
+        Sampling::Sobol => {
+            if dims > SOBOL_DIMS {
+                return Err("[ENSEMBLE] Too many dimensions for the Sobol sequence");
+            }
+            if samples as u64 > u32::MAX as u64 {
+                return Err("[ENSEMBLE] Too many samples for the Sobol sequence");
+            }
+            let directions = sobol_directions(dims);
+            let shift: Vec<u32> = (0..dims).map(|_| (rng.next_u64() >> 32) as u32).collect();
+            let scale = 1.0 / (1u64 << SOBOL_BITS) as f64;
+            // gray code order: point i differs from point i - 1 in the direction
+            // numbers of the lowest zero bit of i - 1
+            let mut x = vec![0u32; dims];
+            let mut points = Vec::with_capacity(samples);
+            for i in 0..samples {
+                if i > 0 {
+                    let bit = (i - 1).trailing_ones() as usize;
+                    for (x_j, v) in x.iter_mut().zip(directions.iter()) {
+                        *x_j ^= v[bit];
+                    }
+                }
+                points.push(
+                    x.iter()
+                        .zip(shift.iter())
+                        .map(|(x_j, s_j)| ((x_j ^ s_j) as f64 + 0.5) * scale)
+                        .collect(),
+                );
+            }
+            points
+        }
+    };
+    Ok(points)
+}
+
+// Propagates the uncertain parameters through the model with `samples` runs drawn
+// with `sampling`, on up to `threads` threads (all available cores when None)
+pub fn ensemble<F, E, M: Dim + DimName>(
+    params: &[Uncertain],
+    samples: usize,
+    sampling: Sampling,
+    seed: u64,
+    threads: Option<usize>,
+    model: F,
+) -> Result<EnsembleResult<M>, &'static str>
+where
+    F: Fn(&[f64]) -> Result<OVector<f64, M>, E> + Sync,
+    E: Into<&'static str>,
+    DefaultAllocator: Allocator<M>,
+    OVector<f64, M>: Send,
+{
+    let samples = parameter_samples(params, sampling, samples, seed)?;
+    let outputs = sweep(&samples, threads, |p| model(p).map_err(Into::into))
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(summarize(samples, outputs))
+}
+
+// Propagates the uncertain parameters through the model, adding runs drawn with
+// `sampling` (in batches of `stopping.batch`) until the standard error of the mean
+// of `statistic` of the output falls below `stopping.target`, or
+// `stopping.max_samples` runs are made
+pub fn ensemble_adaptive<F, E, S, M: Dim + DimName>(
+    params: &[Uncertain],
+    sampling: Sampling,
+    seed: u64,
+    threads: Option<usize>,
+    stopping: &AdaptiveStopping,
+    statistic: S,
+    model: F,
+) -> Result<AdaptiveEnsemble<M>, &'static str>
+where
+    F: Fn(&[f64]) -> Result<OVector<f64, M>, E> + Sync,
+    E: Into<&'static str>,
+    S: Fn(&OVector<f64, M>) -> f64,
+    DefaultAllocator: Allocator<M>,
+    OVector<f64, M>: Send,
+{
+    if sampling == Sampling::LatinHypercube {
+        return Err("[ENSEMBLE] Latin hypercube samples can not be extended");
+    }
+    if stopping.batch < 2 || stopping.max_samples < stopping.batch {
+        return Err(
+            "[ENSEMBLE] Batches of at least two runs, within the largest count, are needed",
+        );
+    }
+    if sampling == Sampling::Antithetic && !stopping.batch.is_multiple_of(2) {
+        return Err("[ENSEMBLE] Antithetic sampling needs batches of an even number of runs");
+    }
+    if stopping.target.is_nan() || stopping.target <= 0.0 {
+        return Err("[ENSEMBLE] The target standard error must be positive");
+    }
+
+    let mut outputs: Vec<OVector<f64, M>> = Vec::new();
+    let mut values: Vec<f64> = Vec::new();
+    let mut count = stopping.batch;
+    loop {
+        // the first runs of the larger draw are the runs already made
+        let samples = parameter_samples(params, sampling, count, seed)?;
+        let added = sweep(&samples[outputs.len()..], threads, |p| {
+            model(p).map_err(Into::into)
+        })
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?;
+        values.extend(added.iter().map(&statistic));
+        outputs.extend(added);
+
+        // antithetic pairs are independent of each other, their members are not
+        let units: Vec<f64> = match sampling {
+            Sampling::Antithetic => values
+                .chunks(2)
+                .map(|pair| 0.5 * (pair[0] + pair[1]))
+                .collect(),
+            _ => values.clone(),
+        };
+        let n = units.len() as f64;
+        let mean = units.iter().sum::<f64>() / n;
+        let variance = units.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0);
+        let standard_error = (variance / n).sqrt();
+
+        let converged = standard_error <= stopping.target;
+        if converged || count >= stopping.max_samples {
+            return Ok(AdaptiveEnsemble {
+                ensemble: summarize(samples, outputs),
+                statistic: mean,
+                standard_error,
+                converged,
+            });
+        }
+        // the error falls as n^(-1/2): jump to the count predicted to reach the
+        // target, in whole batches
+        let predicted = count as f64 * (standard_error / stopping.target).powi(2);
+        let batches = (predicted / stopping.batch as f64).ceil() as usize;
+        count = (batches * stopping.batch)
+            .max(count + stopping.batch)
+            .min(stopping.max_samples);
+    }
+}
+
+// `samples` parameter values of the uncertain parameters drawn with `sampling`
+fn parameter_samples(
+    params: &[Uncertain],
+    sampling: Sampling,
+    samples: usize,
+    seed: u64,
+) -> Result<Vec<Vec<f64>>, &'static str> {
+    if params.iter().any(|p| match *p {
+        Uncertain::Uniform { lower, upper } => upper <= lower || upper.is_nan() || lower.is_nan(),
+        Uncertain::Normal { std, .. } => std <= 0.0 || std.is_nan(),
+    }) {
+        return Err("[ENSEMBLE] Parameter distributions must have a positive width");
+    }
+    Ok(unit_samples(sampling, samples, params.len(), seed)?
+        .into_iter()
+        .map(|u| {
+            params
+                .iter()
+                .zip(u.iter())
+                .map(|(param, u_j)| param.quantile(*u_j))
+                .collect()
+        })
+        .collect())
+}
+
+// Sample mean and variance of the outputs of the runs
+fn summarize<M: Dim + DimName>(
+    samples: Vec<Vec<f64>>,
+    outputs: Vec<OVector<f64, M>>,
+) -> EnsembleResult<M>
+where
+    DefaultAllocator: Allocator<M>,
+{
+    let n = outputs.len() as f64;
+    let mut mean = OVector::<f64, M>::zeros();
+    for y in outputs.iter() {
+        mean += y;
+    }
+    mean /= n;
+    let mut variance = OVector::<f64, M>::zeros();
+    if outputs.len() > 1 {
+        for y in outputs.iter() {
+            variance += (y - &mean).map(|dev| dev * dev);
+        }
+        variance /= n - 1.0;
+    }
+
+    EnsembleResult {
+        samples,
+        outputs,
+        mean,
+        variance,
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runge_kutta::common::IntegOptions;
+    use crate::runge_kutta::fixed::FixedStep;
+    use crate::runge_kutta::rk_simp::RK4;
+    use na::Vector1;
+
+    #[test]
+    fn test_ensemble_sampling() {
+        // every dimension of the stratified designs has one point per stratum
+        let stratified = |points: &[Vec<f64>], dims: usize| {
+            let n = points.len();
+            (0..dims).all(|j| {
+                let mut hits = vec![0; n];
+                for point in points {
+                    hits[(point[j] * n as f64) as usize] += 1;
+                }
+                hits.iter().all(|h| *h == 1)
+            })
+        };
+        let sobol = unit_samples(Sampling::Sobol, 64, SOBOL_DIMS, 3).unwrap();
+        assert!(stratified(&sobol, SOBOL_DIMS));
+        // and the first two sobol dimensions form a (0, 6, 2) net: one point in
+        // every dyadic box of area 1 / 64
+        for (wide, tall) in [(1, 64), (8, 8), (2, 32)] {
+            let mut hits = vec![0; 64];
+            for point in &sobol {
+                let col = (point[0] * wide as f64) as usize;
+                let row = (point[1] * tall as f64) as usize;
+                hits[col * tall + row] += 1;
+            }
+            assert!(hits.iter().all(|h| *h == 1));
+        }
+        let lhs = unit_samples(Sampling::LatinHypercube, 50, 3, 3).unwrap();
+        assert!(stratified(&lhs, 3));
+        let anti = unit_samples(Sampling::Antithetic, 10, 2, 3).unwrap();
+        assert!(anti
+            .chunks(2)
+            .all(|pair| (pair[0][0] + pair[1][0] - 1.0).abs() < 1e-15));
+        assert!(unit_samples(Sampling::Antithetic, 9, 2, 3).is_err());
+        assert!(unit_samples(Sampling::Sobol, 8, SOBOL_DIMS + 1, 3).is_err());
+        assert!(unit_samples(Sampling::Random, 0, 2, 3).is_err());
+
+        // the quantiles of the normal distribution
+        let normal = Uncertain::Normal {
+            mean: 0.0,
+            std: 1.0,
+        };
+        assert!((normal.quantile(0.975) - 1.959_963_984_540_054).abs() < 1e-8);
+        assert!((normal.quantile(1e-6) + 4.753_424_308_822_899).abs() < 1e-8);
+
+        // y' = -k y from y_0 to t = 1, k ~ U[0.5, 1.5] and y_0 ~ N(1, 0.1):
+        // E[y(1)] = e^-0.5 - e^-1.5
+        let params = [
+            Uncertain::Uniform {
+                lower: 0.5,
+                upper: 1.5,
+            },
+            Uncertain::Normal {
+                mean: 1.0,
+                std: 0.1,
+            },
+        ];
+        let model = |p: &[f64]| {
+            let k = p[0];
+            let fxn = move |_t: f64, y: &Vector1<f64>| -k * y;
+            RK4.integrate(
+                fxn,
+                0.0,
+                Vector1::new(p[1]),
+                1.0,
+                0.05,
+                IntegOptions::default(),
+            )
+            .map(|ans| *ans.last_y())
+        };
+        let exact = (-0.5_f64).exp() - (-1.5_f64).exp();
+        // rms error of the mean over independent seeds
+        let rms = |sampling: Sampling| {
+            let sq: f64 = (0..16)
+                .map(|seed| {
+                    let ans = ensemble(&params, 256, sampling, seed, None, model).unwrap();
+                    (ans.mean[0] - exact).powi(2)
+                })
+                .sum();
+            (sq / 16.0).sqrt()
+        };
+        let errs: Vec<f64> = [
+            Sampling::Random,
+            Sampling::Antithetic,
+            Sampling::LatinHypercube,
+            Sampling::Sobol,
+        ]
+        .iter()
+        .map(|sampling| rms(*sampling))
+        .collect();
+        println!("RANDOM, ANTITHETIC, LATIN HYPERCUBE, SOBOL | {:?}", errs);
+        assert!(errs[0] < 0.02);
+        assert!(errs[1] < 0.5 * errs[0]);
+        assert!(errs[2] < 0.3 * errs[0]);
+        assert!(errs[3] < 0.2 * errs[0]);
+
+        // the ensemble is reproducible whatever the thread count
+        let ans = ensemble(&params, 32, Sampling::Sobol, 5, Some(4), model).unwrap();
+        assert_eq!(
+            ans,
+            ensemble(&params, 32, Sampling::Sobol, 5, Some(1), model).unwrap()
+        );
+        assert!((ans.variance[0] - 0.0136).abs() < 0.003);
+    }
+
+    #[test]
+    fn test_ensemble_adaptive() {
+        // y(1) = y_0 e^-k with k ~ U[0.5, 1.5] and y_0 ~ N(1, 0.1), whose variance
+        // 0.0136 takes some 550 independent runs to a standard error of 0.005
+        let params = [
+            Uncertain::Uniform {
+                lower: 0.5,
+                upper: 1.5,
+            },
+            Uncertain::Normal {
+                mean: 1.0,
+                std: 0.1,
+            },
+        ];
+        let model = |p: &[f64]| Ok::<_, &'static str>(Vector1::new(p[1] * (-p[0]).exp()));
+        let exact = (-0.5_f64).exp() - (-1.5_f64).exp();
+        let stopping = AdaptiveStopping {
+            target: 0.005,
+            batch: 64,
+            max_samples: 4096,
+        };
+        let statistic = |y: &Vector1<f64>| y[0];
+        let ans = ensemble_adaptive(
+            &params,
+            Sampling::Random,
+            3,
+            None,
+            &stopping,
+            statistic,
+            model,
+        )
+        .unwrap();
+        let runs = ans.ensemble.outputs.len();
+        assert!(ans.converged && ans.standard_error <= 0.005);
+        assert!(runs % 64 == 0 && runs > 300 && runs < 1200);
+        assert!((ans.statistic - exact).abs() < 4.0 * 0.005);
+        assert_eq!(ans.statistic, ans.ensemble.mean[0]);
+        // the same runs as an ensemble of that size
+        let fixed = ensemble(&params, runs, Sampling::Random, 3, Some(2), model).unwrap();
+        assert_eq!(fixed, ans.ensemble);
+
+        // antithetic pairs cancel most of the (nearly linear) output
+        let anti = ensemble_adaptive(
+            &params,
+            Sampling::Antithetic,
+            3,
+            None,
+            &stopping,
+            statistic,
+            model,
+        )
+        .unwrap();
+        assert!(anti.converged && anti.ensemble.outputs.len() < runs);
+
+        // out of runs before the target
+        let tight = AdaptiveStopping {
+            target: 1e-6,
+            ..stopping
+        };
+        let ans =
+            ensemble_adaptive(&params, Sampling::Sobol, 3, None, &tight, statistic, model).unwrap();
+        assert!(!ans.converged && ans.ensemble.outputs.len() == 4096);
+        let lhs = ensemble_adaptive(
+            &params,
+            Sampling::LatinHypercube,
+            3,
+            None,
+            &stopping,
+            statistic,
+            model,
+        );
+        assert!(lhs.is_err());
+    }
+}
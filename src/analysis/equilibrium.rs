@@ -0,0 +1,160 @@
+/// Equilibria and Linear Stability (analysis/equilibrium)
+///
+/// Finds fixed points f(y) = 0 of a system from the same `OdeSystem` used for
+/// integration and characterizes them from the eigenvalues of the jacobian there.
+/// An equilibrium is asymptotically stable when every eigenvalue has a negative real
+/// part and unstable when any has a positive one. Eigenvalues on the imaginary axis
+/// (within a small tolerance) leave the linearization inconclusive.
+///
+/// The dynamics are evaluated at t = 0, so non-autonomous systems are analysed as
+/// frozen at that time.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{Complex, DMatrix, DefaultAllocator, Dim, DimName, OMatrix, OVector};
+
+// local imports
+use crate::systems::OdeSystem;
+use crate::utils::finite_diff::fdiff_jacobian;
+use crate::utils::linalg::eigenvalues;
+use crate::utils::newton_raphson::newton_raphson_fdiff_jac;
+use crate::utils::solver_dim::{SolverAllocator, SolverDim};
+
+// === End Imports ===
+
+// Residual tolerance of the newton solve for the equilibrium
+const TOL: f64 = 1e-10;
+// Real parts smaller than this (relative to the spectral scale) count as zero
+const MARGINAL_TOL: f64 = 1e-8;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stability {
+    // Every eigenvalue has a negative real part
+    Stable,
+    // At least one eigenvalue has a positive real part
+    Unstable,
+    // No eigenvalue has a positive real part but some lie on the imaginary axis
+    Marginal,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Equilibrium<N: Dim + DimName>
+where
+    DefaultAllocator: Allocator<N> + Allocator<N, N>,
+{
+    // Fixed point of the dynamics
+    pub y: OVector<f64, N>,
+    // Jacobian of the dynamics at the fixed point
+    pub jacobian: OMatrix<f64, N, N>,
+    // Eigenvalues of the jacobian, by decreasing real part
+    pub eigenvalues: Vec<Complex<f64>>,
+    // Stability of the linearization
+    pub stability: Stability,
+}
+
+// Solves for an equilibrium of the system near guess and reports its linear
+// stability, from the jacobian the newton solve ended on
+pub fn find_equilibrium<S, N: SolverDim>(
+    system: &S,
+    guess: OVector<f64, N>,
+) -> Result<Equilibrium<N>, &'static str>
+where
+    S: OdeSystem<N>,
+    DefaultAllocator: SolverAllocator<N>,
+{
+    let fxn = |y: &OVector<f64, N>| system.dynamics(0.0, y);
+    let root = newton_raphson_fdiff_jac(fxn, guess, TOL)?;
+    linearization(root.x, root.jacobian)
+}
+
+// Linearization of the system about the state y (assumed to be an equilibrium)
+pub fn linear_stability<S, N: Dim + DimName>(
+    system: &S,
+    y: OVector<f64, N>,
+) -> Result<Equilibrium<N>, &'static str>
+where
+    S: OdeSystem<N>,
+    DefaultAllocator: Allocator<N> + Allocator<N, N>,
+{
+    let fxn = |y: &OVector<f64, N>| system.dynamics(0.0, y);
+    let jacobian = fdiff_jacobian(&fxn, &fxn(&y), &y);
+    linearization(y, jacobian)
+}
+
+// Stability of the linearization with the given jacobian about the state y
+fn linearization<N: Dim + DimName>(
+    y: OVector<f64, N>,
+    jacobian: OMatrix<f64, N, N>,
+) -> Result<Equilibrium<N>, &'static str>
+where
+    DefaultAllocator: Allocator<N> + Allocator<N, N>,
+{
+    let n = N::dim();
+    let mat = DMatrix::<f64>::from_iterator(n, n, jacobian.iter().cloned());
+    let mut eigenvalues = eigenvalues(mat)?;
+    eigenvalues.sort_by(|a, b| b.re.partial_cmp(&a.re).unwrap());
+
+    let scale = eigenvalues
+        .iter()
+        .fold(1.0_f64, |m, val| m.max(val.re.hypot(val.im)));
+    let leading = eigenvalues.first().map_or(0.0, |val| val.re);
+    let stability = if leading > MARGINAL_TOL * scale {
+        Stability::Unstable
+    } else if leading < -MARGINAL_TOL * scale {
+        Stability::Stable
+    } else {
+        Stability::Marginal
+    };
+
+    Ok(Equilibrium {
+        y,
+        jacobian,
+        eigenvalues,
+        stability,
+    })
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use na::Vector2;
+    use std::f64::consts::PI;
+
+    fn pendulum(damping: f64) -> impl Fn(f64, &Vector2<f64>) -> Vector2<f64> {
+        move |_t: f64, y: &Vector2<f64>| Vector2::new(y[1], -y[0].sin() - damping * y[1])
+    }
+
+    #[test]
+    fn test_pendulum_equilibria() {
+        // hanging down: a stable focus with eigenvalues -1/4 +- i sqrt(15) / 4
+        let down = find_equilibrium(&pendulum(0.5), Vector2::new(0.3, -0.2)).unwrap();
+        assert!(down.y.norm() < 1e-10);
+        assert_eq!(down.stability, Stability::Stable);
+        assert_eq!(down.eigenvalues.len(), 2);
+        for val in down.eigenvalues.iter() {
+            assert!((val.re + 0.25).abs() < 1e-8);
+            assert!((val.im.abs() - 15.0_f64.sqrt() / 4.0).abs() < 1e-8);
+        }
+
+        // inverted: a saddle
+        let up = find_equilibrium(&pendulum(0.5), Vector2::new(3.0, 0.0)).unwrap();
+        assert!((up.y[0] - PI).abs() < 1e-10);
+        assert_eq!(up.stability, Stability::Unstable);
+        assert!(up.eigenvalues[0].re > 0.0 && up.eigenvalues[1].re < 0.0);
+
+        // without damping the linearization is a center
+        let center = linear_stability(&pendulum(0.0), Vector2::zeros()).unwrap();
+        assert_eq!(center.stability, Stability::Marginal);
+    }
+
+    #[test]
+    fn test_no_equilibrium() {
+        // y0 drifts at a constant rate, so there is no fixed point, though the
+        // newton steps vanish once y1 has settled
+        let drift = |_t: f64, y: &Vector2<f64>| Vector2::new(1.0, -y[1]);
+        assert!(find_equilibrium(&drift, Vector2::new(0.5, 0.5)).is_err());
+    }
+}
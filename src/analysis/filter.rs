@@ -0,0 +1,336 @@
+/// Extended Kalman Filter (analysis/filter)
+///
+/// Building blocks for sequential estimation around the propagator. The time
+/// update (`predict`) integrates the state estimate and its state transition matrix
+/// to the next measurement time and propagates the covariance as
+///
+/// P = Phi P Phi^T + Q
+///
+/// where Q is the process noise accumulated over the interval, supplied by the user
+/// through `ProcessNoise` (any `Fn(t, dt, y) -> Q` closure works). The measurement
+/// update (`update`) takes the measurement, its predicted value and the measurement
+/// jacobian H from the user, who owns the measurement model, and applies the
+/// standard gain with the Joseph form covariance update, which keeps P symmetric
+/// positive semi-definite in the presence of round off.
+///
+/// Over long arcs with a large dynamic range (e.g. position known to meters and
+/// velocity to mm/s) even the Joseph form can lose positive definiteness. The filter
+/// can then be switched to square-root form (`square_root`), which propagates a
+/// factor S of P = S S^T instead. Both updates are computed by QR factorization of
+/// a pre-array whose triangular factor is the updated S, so P can never become
+/// indefinite and the factor needs only half the dynamic range of P.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DMatrix, DefaultAllocator, Dim, DimName, OMatrix, OVector};
+
+// local imports
+use super::stm::propagate_stm;
+use crate::runge_kutta::base::RKStepper;
+use crate::systems::OdeSystem;
+
+// === End Imports ===
+
+pub trait ProcessNoise<N: Dim + DimName>
+where
+    DefaultAllocator: Allocator<N> + Allocator<N, N>,
+{
+    // Process noise covariance accumulated over [t, t + dt] from the state y at t
+    fn noise(&self, t: f64, dt: f64, y: &OVector<f64, N>) -> OMatrix<f64, N, N>;
+}
+
+impl<F, N: Dim + DimName> ProcessNoise<N> for F
+where
+    F: Fn(f64, f64, &OVector<f64, N>) -> OMatrix<f64, N, N>,
+    DefaultAllocator: Allocator<N> + Allocator<N, N>,
+{
+    fn noise(&self, t: f64, dt: f64, y: &OVector<f64, N>) -> OMatrix<f64, N, N> {
+        self(t, dt, y)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ExtendedKalmanFilter<S, Q, N: Dim + DimName>
+where
+    DefaultAllocator: Allocator<N> + Allocator<N, N>,
+{
+    // Dynamics of the state
+    pub system: S,
+    // Process noise model
+    pub process_noise: Q,
+    // Fixed integration step of the time update
+    pub step: f64,
+    // Time of the estimate
+    pub t: f64,
+    // State estimate
+    pub y: OVector<f64, N>,
+    // Covariance of the estimate
+    pub covariance: OMatrix<f64, N, N>,
+    // Lower triangular factor S of the covariance P = S S^T, when the filter runs in
+    // square-root form (the covariance is then kept as S S^T for reading)
+    pub sqrt_covariance: Option<OMatrix<f64, N, N>>,
+}
+
+impl<S, Q, N: Dim + DimName> ExtendedKalmanFilter<S, Q, N>
+where
+    S: OdeSystem<N>,
+    Q: ProcessNoise<N>,
+    DefaultAllocator: Allocator<N> + Allocator<N, N>,
+{
+    pub fn new(
+        system: S,
+        process_noise: Q,
+        t_0: f64,
+        y_0: OVector<f64, N>,
+        p_0: OMatrix<f64, N, N>,
+        step: f64,
+    ) -> Self {
+        ExtendedKalmanFilter {
+            system,
+            process_noise,
+            step,
+            t: t_0,
+            y: y_0,
+            covariance: p_0,
+            sqrt_covariance: None,
+        }
+    }
+
+    // Switches the filter to square-root form. Errors if the covariance is not
+    // positive definite
+    pub fn square_root(mut self) -> Result<Self, &'static str> {
+        match to_dynamic(&self.covariance).cholesky() {
+            Some(chol) => {
+                self.sqrt_covariance = Some(to_static(&chol.unpack()));
+                Ok(self)
+            }
+            None => Err("[EKF] Covariance is not positive definite"),
+        }
+    }
+
+    // Time update of the estimate and covariance to time t. Returns the state
+    // transition matrix over the interval
+    pub fn predict<D: Dim + DimName>(
+        &mut self,
+        stepper: &RKStepper<D>,
+        t: f64,
+    ) -> OMatrix<f64, N, N>
+    where
+        DefaultAllocator: Allocator<D> + Allocator<D, D>,
+    {
+        let dt = t - self.t;
+        let (y, phi) = propagate_stm(stepper, &self.system, self.t, &self.y, dt, self.step);
+        let noise = self.process_noise.noise(self.t, dt, &self.y);
+        match &self.sqrt_covariance {
+            // S = tria([Phi S, sqrt(Q)])
+            Some(sqrt_p) => {
+                let n = N::dim();
+                let mut pre = DMatrix::<f64>::zeros(n, 2 * n);
+                pre.view_mut((0, 0), (n, n))
+                    .copy_from(&to_dynamic(&(&phi * sqrt_p)));
+                pre.view_mut((0, n), (n, n))
+                    .copy_from(&psd_sqrt(to_dynamic(&noise)));
+                self.set_sqrt_covariance(to_static(&tria(pre)));
+            }
+            None => self.covariance = &phi * &self.covariance * phi.transpose() + noise,
+        }
+        self.y = y;
+        self.t = t;
+        phi
+    }
+
+    // Measurement update from the measurement z, its value z_pred predicted from the
+    // current estimate, the measurement jacobian h_mat and the measurement noise
+    // covariance r. Returns the innovation z - z_pred
+    pub fn update<M: Dim + DimName>(
+        &mut self,
+        z: &OVector<f64, M>,
+        z_pred: &OVector<f64, M>,
+        h_mat: &OMatrix<f64, M, N>,
+        r: &OMatrix<f64, M, M>,
+    ) -> Result<OVector<f64, M>, &'static str>
+    where
+        DefaultAllocator: Allocator<M> + Allocator<M, M> + Allocator<M, N> + Allocator<N, M>,
+    {
+        let innovation = z - z_pred;
+        if let Some(sqrt_p) = &self.sqrt_covariance {
+            let sqrt_p = to_dynamic(sqrt_p);
+            return self.update_sqrt(innovation, &to_dynamic(h_mat), &to_dynamic(r), &sqrt_p);
+        }
+        let ph_t = &self.covariance * h_mat.transpose();
+        let s_mat = h_mat * &ph_t + r;
+
+        // K = P H^T S^-1, from S K^T = H P since P and S are symmetric
+        let gain = match s_mat.cholesky() {
+            Some(chol) => chol.solve(&ph_t.transpose()).transpose(),
+            None => return Err("[EKF] Innovation covariance is not positive definite"),
+        };
+        self.y += &gain * &innovation;
+        let i_kh = OMatrix::<f64, N, N>::identity() - &gain * h_mat;
+        self.covariance =
+            &i_kh * &self.covariance * i_kh.transpose() + &gain * r * gain.transpose();
+        Ok(innovation)
+    }
+
+    // Square-root measurement update. The lower triangular factor of the pre-array
+    //   [sqrt(R)  H S]
+    //   [0        S  ]
+    // is [X 0; Y S_new] with X X^T the innovation covariance and gain K = Y X^-1
+    fn update_sqrt<M: Dim + DimName>(
+        &mut self,
+        innovation: OVector<f64, M>,
+        h_mat: &DMatrix<f64>,
+        r: &DMatrix<f64>,
+        sqrt_p: &DMatrix<f64>,
+    ) -> Result<OVector<f64, M>, &'static str>
+    where
+        DefaultAllocator: Allocator<M>,
+    {
+        let (n, m) = (N::dim(), M::dim());
+        let sqrt_r = match r.clone().cholesky() {
+            Some(chol) => chol.unpack(),
+            None => return Err("[EKF] Measurement noise is not positive definite"),
+        };
+        let mut pre = DMatrix::<f64>::zeros(m + n, m + n);
+        pre.view_mut((0, 0), (m, m)).copy_from(&sqrt_r);
+        pre.view_mut((0, m), (m, n)).copy_from(&(h_mat * sqrt_p));
+        pre.view_mut((m, m), (n, n)).copy_from(sqrt_p);
+        let post = tria(pre);
+
+        // K^T = X^-T Y^T
+        let x_mat = post.view((0, 0), (m, m)).clone_owned();
+        let y_mat = post.view((m, 0), (n, m)).clone_owned();
+        let gain_t = match x_mat.transpose().solve_upper_triangular(&y_mat.transpose()) {
+            Some(gain_t) => gain_t,
+            None => return Err("[EKF] Innovation covariance is not positive definite"),
+        };
+        let correction =
+            gain_t.transpose() * DMatrix::from_column_slice(m, 1, innovation.as_slice());
+        self.y += OVector::<f64, N>::from_column_slice(correction.as_slice());
+        self.set_sqrt_covariance(to_static(&post.view((m, m), (n, n)).clone_owned()));
+        Ok(innovation)
+    }
+
+    fn set_sqrt_covariance(&mut self, sqrt_p: OMatrix<f64, N, N>) {
+        self.covariance = &sqrt_p * sqrt_p.transpose();
+        self.sqrt_covariance = Some(sqrt_p);
+    }
+}
+
+// Lower triangular L with L L^T = A A^T, from the QR factorization of A^T
+fn tria(pre: DMatrix<f64>) -> DMatrix<f64> {
+    let rows = pre.nrows();
+    let r = pre.transpose().qr().r();
+    r.view((0, 0), (r.nrows(), rows)).transpose()
+}
+
+// Square root factor L with L L^T = A of a symmetric positive semi-definite A.
+// Unlike a cholesky factor it exists for singular A (e.g. noise on only some states)
+fn psd_sqrt(mat: DMatrix<f64>) -> DMatrix<f64> {
+    let eigen = mat.symmetric_eigen();
+    let roots = eigen.eigenvalues.map(|val| val.max(0.0).sqrt());
+    eigen.eigenvectors * DMatrix::from_diagonal(&roots)
+}
+
+fn to_dynamic<R: Dim + DimName, C: Dim + DimName>(mat: &OMatrix<f64, R, C>) -> DMatrix<f64>
+where
+    DefaultAllocator: Allocator<R, C>,
+{
+    DMatrix::from_column_slice(R::dim(), C::dim(), mat.as_slice())
+}
+
+fn to_static<N: Dim + DimName>(mat: &DMatrix<f64>) -> OMatrix<f64, N, N>
+where
+    DefaultAllocator: Allocator<N, N>,
+{
+    OMatrix::<f64, N, N>::from_column_slice(mat.as_slice())
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runge_kutta::rk_simp::RK4;
+    use na::{Matrix1, Matrix1x2, Matrix2, Vector1, Vector2};
+
+    #[test]
+    fn test_ekf() {
+        // constant velocity target with white acceleration noise of intensity q
+        let q = 0.01;
+        let cv = |_t: f64, y: &Vector2<f64>| Vector2::new(y[1], 0.0);
+        let noise = move |_t: f64, dt: f64, _y: &Vector2<f64>| {
+            Matrix2::new(dt.powi(3) / 3.0, dt.powi(2) / 2.0, dt.powi(2) / 2.0, dt) * q
+        };
+        let p_0 = Matrix2::new(4.0, 0.0, 0.0, 1.0);
+        let mut ekf = ExtendedKalmanFilter::new(cv, noise, 0.0, Vector2::new(0.0, 1.0), p_0, 0.1);
+
+        // the linear time update is exact
+        let phi = ekf.predict(&RK4, 2.0);
+        let phi_exact = Matrix2::new(1.0, 2.0, 0.0, 1.0);
+        assert!((phi - phi_exact).norm() < 1e-12);
+        assert!((ekf.y - Vector2::new(2.0, 1.0)).norm() < 1e-12);
+        let p_pred = phi_exact * p_0 * phi_exact.transpose() + noise(0.0, 2.0, &ekf.y);
+        assert!((ekf.covariance - p_pred).norm() < 1e-10);
+
+        // position measurement
+        let h_mat = Matrix1x2::new(1.0, 0.0);
+        let r = Matrix1::new(0.25);
+        let innovation = ekf
+            .update(&Vector1::new(2.5), &(h_mat * ekf.y), &h_mat, &r)
+            .unwrap();
+        assert!((innovation[0] - 0.5).abs() < 1e-12);
+        let gain = p_pred * h_mat.transpose() / (p_pred[(0, 0)] + 0.25);
+        assert!((ekf.y - (Vector2::new(2.0, 1.0) + gain * 0.5)).norm() < 1e-12);
+        let p_upd = (Matrix2::identity() - gain * h_mat) * p_pred;
+        assert!((ekf.covariance - p_upd).norm() < 1e-10);
+
+        // tracking a target moving at 3 units per time unit
+        for k in 1..=20 {
+            let t = 2.0 + k as f64;
+            ekf.predict(&RK4, t);
+            let z_pred = h_mat * ekf.y;
+            ekf.update(&Vector1::new(3.0 * t), &z_pred, &h_mat, &r)
+                .unwrap();
+        }
+        assert!((ekf.y[1] - 3.0).abs() < 0.1);
+        assert!(ekf.covariance[(0, 0)] < 0.25);
+    }
+
+    #[test]
+    fn test_square_root_ekf() {
+        // pendulum with noise on the rate only (a singular process noise matrix)
+        let pendulum = |_t: f64, y: &Vector2<f64>| Vector2::new(y[1], -y[0].sin());
+        let noise = |_t: f64, dt: f64, _y: &Vector2<f64>| Matrix2::new(0.0, 0.0, 0.0, 1e-4 * dt);
+        let p_0 = Matrix2::new(0.1, 0.01, 0.01, 0.2);
+        let y_0 = Vector2::new(0.5, 0.0);
+        let mut full = ExtendedKalmanFilter::new(pendulum, noise, 0.0, y_0, p_0, 0.01);
+        let mut sqrt = full.clone().square_root().unwrap();
+        assert!((sqrt.covariance - p_0).norm() < 1e-15);
+
+        // angle measurements of a pendulum released from 0.6 rad
+        let h_mat = Matrix1x2::new(1.0, 0.0);
+        let r = Matrix1::new(1e-4);
+        let (_, truth) = RK4.integrate_state(&pendulum, 0.0, Vector2::new(0.6, 0.0), 10.0, 0.01);
+        for k in 1..=20 {
+            let t = 0.5 * k as f64;
+            let z = Vector1::new(truth[50 * k][0]);
+            for ekf in [&mut full, &mut sqrt].iter_mut() {
+                ekf.predict(&RK4, t);
+                let z_pred = h_mat * ekf.y;
+                ekf.update(&z, &z_pred, &h_mat, &r).unwrap();
+            }
+            // both forms agree, and the factor reproduces the covariance
+            assert!((full.y - sqrt.y).norm() < 1e-9);
+            assert!((full.covariance - sqrt.covariance).norm() < 1e-9 * full.covariance.norm());
+            let factor = sqrt.sqrt_covariance.unwrap();
+            assert_eq!(factor[(0, 1)], 0.0);
+            assert!((factor * factor.transpose() - sqrt.covariance).norm() < 1e-15);
+        }
+        assert!((sqrt.y - truth[1000]).norm() < 1e-2);
+
+        let indefinite = ExtendedKalmanFilter::new(pendulum, noise, 0.0, y_0, -p_0, 0.01);
+        assert!(indefinite.square_root().is_err());
+    }
+}
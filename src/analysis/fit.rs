@@ -0,0 +1,232 @@
+/// Parameter Estimation (analysis/fit)
+///
+/// Estimates the parameters p of a model dy/dt = f(t, y, p) from observations of
+/// the state at known times by minimizing the weighted least squares cost
+///
+/// sum_k sum_i w_i (y_i(t_k; p) - d_ki)^2
+///
+/// with `levenberg_marquardt`. The gradient comes from forward sensitivities: the
+/// state is integrated together with S = dy/dp, which follows
+/// dS/dt = J_y S + J_p (S(t_0) = 0), with the jacobians J_y = df/dy and
+/// J_p = df/dp found by finite differences. One integration per iteration then
+/// gives both the residuals and their jacobian, where differencing the whole
+/// trajectory would need one integration per parameter and loses accuracy to the
+/// integration error.
+///
+/// The model and sensitivities are stepped from observation to observation with a
+/// fixed step `RKStepper` (through `integrate_state`), so every observation time is
+/// hit exactly. Weights are typically 1 / sigma_i^2 of the measurement noise, in
+/// which case `covariance` is the covariance of the estimated parameters.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DMatrix, DVector, DefaultAllocator, Dim, DimName, OVector};
+
+// local imports
+use crate::runge_kutta::base::RKStepper;
+use crate::utils::finite_diff::fdiff_jacobian;
+use crate::utils::least_squares::{levenberg_marquardt, LevenbergMarquardt};
+
+// === End Imports ===
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FitOptions<N: Dim + DimName>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    // Fixed integration step of the model and sensitivities
+    pub step: Option<f64>,
+    // Weight of each state component in the cost (defaults to 1)
+    pub weights: Option<OVector<f64, N>>,
+    // Settings of the least squares solver
+    pub solver: Option<LevenbergMarquardt>,
+}
+
+impl<N: Dim + DimName> FitOptions<N>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    pub fn default() -> Self {
+        FitOptions {
+            step: Some(0.01),
+            weights: None,
+            solver: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FitResult {
+    // Estimated parameters
+    pub params: Vec<f64>,
+    // Weighted least squares cost at the estimate
+    pub cost: f64,
+    // Inverse of the (weighted) normal matrix at the estimate, None when singular
+    pub covariance: Option<DMatrix<f64>>,
+    // Number of model integrations
+    pub iterations: usize,
+}
+
+// Estimates the parameters of the model from observations (t_k, d_k) of the state
+// started from y_0 at t_0, with p_0 the initial guess
+pub fn fit<F, N: Dim + DimName, D: Dim + DimName>(
+    stepper: &RKStepper<D>,
+    model: F,
+    t_0: f64,
+    y_0: &OVector<f64, N>,
+    data: &[(f64, OVector<f64, N>)],
+    p_0: &[f64],
+    opts: FitOptions<N>,
+) -> Result<FitResult, &'static str>
+where
+    F: Fn(f64, &OVector<f64, N>, &[f64]) -> OVector<f64, N>,
+    DefaultAllocator: Allocator<N> + Allocator<N, N> + Allocator<D> + Allocator<D, D>,
+{
+    // Approximately cube root of ULP precision
+    const H_FACTOR: f64 = 6.055_454_452_393_343e-6_f64;
+
+    let step = opts.step.or(FitOptions::<N>::default().step).unwrap();
+    let weights = opts
+        .weights
+        .unwrap_or_else(|| OVector::<f64, N>::repeat(1.0));
+    let solver = opts.solver.unwrap_or_else(LevenbergMarquardt::default);
+    if data.is_empty() || p_0.is_empty() {
+        return Err("[FIT] Observations and parameters must not be empty");
+    }
+    if data[0].0 < t_0 || data.windows(2).any(|w| w[1].0 <= w[0].0) {
+        return Err("[FIT] Observation times must be increasing from t_0");
+    }
+    if weights.iter().any(|w| *w < 0.0) {
+        return Err("[FIT] Weights must not be negative");
+    }
+    let sqrt_w = weights.map(|w| w.sqrt());
+    let (n, m) = (N::dim(), p_0.len());
+
+    // weighted residuals and their jacobian from one integration of the model and
+    // its sensitivities, stored as x = [y, dy/dp_1, ..., dy/dp_m]
+    let residuals = |p: &DVector<f64>| {
+        let params = p.as_slice();
+        let sensitivities = |t: f64, x: &Vec<f64>| {
+            let y = OVector::<f64, N>::from_column_slice(&x[..n]);
+            let fxn = |y: &OVector<f64, N>| model(t, y, params);
+            let f_y = fxn(&y);
+            let jac_y = fdiff_jacobian(&fxn, &f_y, &y);
+            let mut dx: Vec<f64> = f_y.iter().cloned().collect();
+            for (j, s_j) in x[n..].chunks(n).enumerate() {
+                let h = H_FACTOR * params[j].abs().max(1.0);
+                let mut shifted = params.to_vec();
+                shifted[j] = params[j] + h;
+                let f_p = model(t, &y, &shifted);
+                shifted[j] = params[j] - h;
+                let jac_p = (f_p - model(t, &y, &shifted)) / (2.0 * h);
+                let ds = &jac_y * OVector::<f64, N>::from_column_slice(s_j) + jac_p;
+                dx.extend(ds.iter());
+            }
+            dx
+        };
+
+        let mut res = DVector::<f64>::zeros(n * data.len());
+        let mut jac = DMatrix::<f64>::zeros(n * data.len(), m);
+        let mut x: Vec<f64> = y_0.iter().cloned().chain(vec![0.0; n * m]).collect();
+        let mut t = t_0;
+        for (k, (t_k, d_k)) in data.iter().enumerate() {
+            if *t_k > t {
+                let (_, states) = stepper.integrate_state(&sensitivities, t, x, t_k - t, step);
+                x = states.last().unwrap().clone();
+                t = *t_k;
+            }
+            if x.iter().any(|val| !val.is_finite()) {
+                return Err("[FIT] Model integration diverged");
+            }
+            for i in 0..n {
+                res[k * n + i] = sqrt_w[i] * (x[i] - d_k[i]);
+                for j in 0..m {
+                    jac[(k * n + i, j)] = sqrt_w[i] * x[n + j * n + i];
+                }
+            }
+        }
+        Ok((res, jac))
+    };
+
+    let ans = levenberg_marquardt(residuals, DVector::from_column_slice(p_0), &solver)?;
+    let covariance = (ans.jacobian.transpose() * &ans.jacobian).try_inverse();
+    Ok(FitResult {
+        params: ans.x.iter().cloned().collect(),
+        cost: ans.cost,
+        covariance,
+        iterations: ans.iterations,
+    })
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runge_kutta::rk_simp::RK4;
+    use na::{Vector1, Vector2};
+
+    #[test]
+    fn test_fit_logistic() {
+        // logistic growth with rate r and capacity k
+        let logistic =
+            |_t: f64, y: &Vector1<f64>, p: &[f64]| Vector1::new(p[0] * y[0] * (1.0 - y[0] / p[1]));
+        let exact = |t: f64| 10.0 / (1.0 + 9.0 * (-0.8 * t).exp());
+        let data: Vec<(f64, Vector1<f64>)> = (1..=20)
+            .map(|k| {
+                let t = 0.5 * k as f64;
+                (t, Vector1::new(exact(t)))
+            })
+            .collect();
+
+        let ans = fit(
+            &RK4,
+            logistic,
+            0.0,
+            &Vector1::new(1.0),
+            &data,
+            &[0.5, 7.0],
+            FitOptions::default(),
+        )
+        .unwrap();
+        assert!((ans.params[0] - 0.8).abs() < 1e-6);
+        assert!((ans.params[1] - 10.0).abs() < 1e-6);
+        assert!(ans.cost < 1e-12);
+        assert!(ans.covariance.is_some());
+
+        // weighted fit of a damped oscillator where only positions are observed
+        let oscillator =
+            |_t: f64, y: &Vector2<f64>, p: &[f64]| Vector2::new(y[1], -p[0] * y[0] - p[1] * y[1]);
+        let truth = RK4.integrate_state(
+            &|t: f64, y: &Vector2<f64>| oscillator(t, y, &[4.0, 0.3]),
+            0.0,
+            Vector2::new(1.0, 0.0),
+            5.0,
+            0.01,
+        );
+        let data: Vec<(f64, Vector2<f64>)> = truth
+            .0
+            .iter()
+            .zip(truth.1.iter())
+            .step_by(25)
+            .skip(1)
+            .map(|(t, y)| (*t, Vector2::new(y[0], 123.0)))
+            .collect();
+        let opts = FitOptions {
+            weights: Some(Vector2::new(1.0, 0.0)),
+            ..FitOptions::default()
+        };
+        let ans = fit(
+            &RK4,
+            oscillator,
+            0.0,
+            &Vector2::new(1.0, 0.0),
+            &data,
+            &[3.0, 0.1],
+            opts,
+        )
+        .unwrap();
+        assert!((ans.params[0] - 4.0).abs() < 1e-6 && (ans.params[1] - 0.3).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,179 @@
+/// Lyapunov Exponents (analysis/lyapunov)
+///
+/// Estimates the leading Lyapunov exponents of a trajectory, the average
+/// exponential rates at which nearby trajectories separate. A positive leading
+/// exponent is the usual numerical signature of chaos.
+///
+/// The state is integrated together with k tangent vectors following the
+/// variational equations dv/dt = J(t, y) v, with J the finite difference jacobian
+/// of the dynamics. Tangent vectors grow and align with the most unstable
+/// direction, so every renormalization interval they are re-orthonormalized by a QR
+/// factorization, V = Q R, and the logs of the diagonal of R accumulated. The i-th
+/// exponent is the time average of ln |R_ii| (Benettin et al. 1980).
+///
+/// The augmented state is stepped with a fixed step `RKStepper` through
+/// `integrate_state`, so any explicit tableau can be used.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DMatrix, DefaultAllocator, Dim, DimName, OVector};
+
+// local imports
+use crate::runge_kutta::base::RKStepper;
+use crate::systems::OdeSystem;
+use crate::utils::finite_diff::fdiff_jacobian;
+
+// === End Imports ===
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LyapunovOptions {
+    // Number of exponents to estimate (defaults to the full spectrum)
+    pub count: Option<usize>,
+    // Fixed integration step
+    pub step: Option<f64>,
+    // Time between re-orthonormalizations of the tangent vectors
+    pub renorm_interval: Option<f64>,
+    // Time integrated before averaging so the state settles onto the attractor
+    pub transient: Option<f64>,
+}
+
+impl LyapunovOptions {
+    pub fn default() -> Self {
+        LyapunovOptions {
+            count: None,
+            step: Some(0.01),
+            renorm_interval: Some(1.0),
+            transient: Some(0.0),
+        }
+    }
+}
+
+// Estimates the leading Lyapunov exponents (largest first) of the trajectory from
+// y_0 by averaging over the time span `span` (after the transient)
+pub fn lyapunov_exponents<S, N: Dim + DimName, D: Dim + DimName>(
+    stepper: &RKStepper<D>,
+    system: &S,
+    y_0: OVector<f64, N>,
+    span: f64,
+    opts: LyapunovOptions,
+) -> Result<Vec<f64>, &'static str>
+where
+    S: OdeSystem<N>,
+    DefaultAllocator: Allocator<N> + Allocator<N, N> + Allocator<D> + Allocator<D, D>,
+{
+    let defaults = LyapunovOptions::default();
+    let n = N::dim();
+    let count = opts.count.unwrap_or(n);
+    let step = opts.step.or(defaults.step).unwrap();
+    let renorm_interval = opts.renorm_interval.or(defaults.renorm_interval).unwrap();
+    let transient = opts.transient.or(defaults.transient).unwrap();
+    if count == 0 || count > n {
+        return Err("[LYAPUNOV] Number of exponents must be between 1 and the state dimension");
+    }
+    if !(span > 0.0 && step > 0.0 && renorm_interval > 0.0 && transient >= 0.0) {
+        return Err("[LYAPUNOV] Time span, step and renormalization interval must be positive");
+    }
+
+    // augmented state [y, v_1, ..., v_count] as a flat vector
+    let to_state = |x: &[f64]| OVector::<f64, N>::from_column_slice(&x[..n]);
+    let variational = |t: f64, x: &Vec<f64>| {
+        let y = to_state(x);
+        let fxn = |y: &OVector<f64, N>| system.dynamics(t, y);
+        let f_y = fxn(&y);
+        let jac = fdiff_jacobian(&fxn, &f_y, &y);
+        let mut dx: Vec<f64> = f_y.iter().cloned().collect();
+        for v in x[n..].chunks(n) {
+            dx.extend((&jac * OVector::<f64, N>::from_column_slice(v)).iter());
+        }
+        dx
+    };
+
+    // settle onto the attractor
+    let mut t = 0.0;
+    let mut y: Vec<f64> = y_0.iter().cloned().collect();
+    if transient > 0.0 {
+        let dynamics = |t: f64, y: &Vec<f64>| -> Vec<f64> {
+            system.dynamics(t, &to_state(y)).iter().cloned().collect()
+        };
+        let (_, states) = stepper.integrate_state(&dynamics, t, y, transient, step);
+        y = states.last().unwrap().clone();
+        t = transient;
+    }
+
+    // tangent vectors start as the first coordinate directions
+    let mut basis = DMatrix::<f64>::identity(n, count);
+    let mut log_growth = vec![0.0; count];
+    let t_end = t + span;
+    while t < t_end {
+        let interval = renorm_interval.min(t_end - t);
+        let x_0: Vec<f64> = y.iter().chain(basis.iter()).cloned().collect();
+        let (_, states) = stepper.integrate_state(&variational, t, x_0, interval, step);
+        let x = states.last().unwrap();
+        t += interval;
+
+        // re-orthonormalize the tangent vectors and accumulate their growth
+        let qr = DMatrix::<f64>::from_column_slice(n, count, &x[n..]).qr();
+        let r = qr.r();
+        for (i, growth) in log_growth.iter_mut().enumerate() {
+            if r[(i, i)] == 0.0 {
+                return Err("[LYAPUNOV] Tangent vectors collapsed");
+            }
+            *growth += r[(i, i)].abs().ln();
+        }
+        basis = qr.q();
+        y = x[..n].to_vec();
+    }
+    Ok(log_growth.iter().map(|growth| growth / span).collect())
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runge_kutta::rk_simp::RK4;
+    use na::{Vector2, Vector3};
+
+    #[test]
+    fn test_lyapunov_exponents() {
+        // linear decay: the exponents are the eigenvalues
+        let decay = |_t: f64, y: &Vector2<f64>| Vector2::new(-y[0] + y[1], -2.0 * y[1]);
+        let exps = lyapunov_exponents(
+            &RK4,
+            &decay,
+            Vector2::new(1.0, 1.0),
+            20.0,
+            LyapunovOptions::default(),
+        )
+        .unwrap();
+        assert!((exps[0] + 1.0).abs() < 1e-2 && (exps[1] + 2.0).abs() < 1e-2);
+
+        // lorenz attractor: about (0.906, 0, -14.57)
+        let (sigma, rho, beta) = (10.0, 28.0, 8.0 / 3.0);
+        let lorenz = move |_t: f64, y: &Vector3<f64>| {
+            Vector3::new(
+                sigma * (y[1] - y[0]),
+                y[0] * (rho - y[2]) - y[1],
+                y[0] * y[1] - beta * y[2],
+            )
+        };
+        let opts = LyapunovOptions {
+            transient: Some(10.0),
+            ..LyapunovOptions::default()
+        };
+        let exps =
+            lyapunov_exponents(&RK4, &lorenz, Vector3::new(1.0, 1.0, 1.0), 200.0, opts).unwrap();
+        assert!(exps[0] > 0.75 && exps[0] < 1.05);
+        assert!(exps[1].abs() < 0.1);
+        // the sum is the (constant) divergence of the flow
+        let sum: f64 = exps.iter().sum();
+        assert!((sum + sigma + 1.0 + beta).abs() < 1e-2);
+
+        let opts = LyapunovOptions {
+            count: Some(4),
+            ..LyapunovOptions::default()
+        };
+        assert!(lyapunov_exponents(&RK4, &lorenz, Vector3::zeros(), 1.0, opts).is_err());
+    }
+}
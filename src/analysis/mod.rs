@@ -0,0 +1,24 @@
+/// Analysis Tools
+/// Tools for studying a system beyond a single trajectory: how its steady states
+/// move as a parameter is varied, their stability, the stability regions of the
+/// integrators, estimation of its parameters and state from measurements,
+/// guaranteed enclosures of the solution (with the `validated` feature), accuracy
+/// and cost of the integrators on standard problems (against CVODE with the
+/// `sundials` feature), ...
+pub mod benchmark;
+pub mod collocation;
+pub mod continuation;
+#[cfg(feature = "sundials")]
+pub mod cvode;
+pub mod ensemble;
+pub mod equilibrium;
+pub mod filter;
+pub mod fit;
+pub mod lyapunov;
+#[cfg(feature = "rustfft")]
+pub mod spectrum;
+pub mod stability;
+pub mod stm;
+pub mod unscented;
+#[cfg(feature = "validated")]
+pub mod validated;
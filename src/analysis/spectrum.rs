@@ -0,0 +1,163 @@
+/// Frequency Analysis (analysis/spectrum)
+///
+/// Only available with the `rustfft` feature.
+///
+/// Computes the amplitude spectra of selected components of a solution, e.g. to
+/// identify orbital or oscillation frequencies. Integrator steps are generally not
+/// uniform, so the solution is first resampled on a uniform grid with the dense
+/// output, optionally windowed, then transformed with an FFT.
+///
+/// Amplitudes are one-sided and normalized by the window sum, so a sinusoid of
+/// amplitude A whose frequency falls on a bin shows a peak of height A. The
+/// frequency resolution is 1 / (time span), in cycles per unit time.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DefaultAllocator, Dim, DimName};
+use rustfft::num_complex::Complex;
+use rustfft::FftPlanner;
+
+// local imports
+use crate::runge_kutta::common::IntegResult;
+use crate::systems::OdeSystem;
+use crate::utils::dense::DenseOutput;
+
+// Standard library imports
+use std::f64::consts::PI;
+
+// === End Imports ===
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Window {
+    // No windowing. Exact for periodic signals sampled over whole periods
+    Rectangular,
+    // Hann window. Reduces the leakage of frequencies that fall between bins
+    Hann,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spectrum {
+    // Frequency of each bin (cycles per unit time), from 0 to the nyquist frequency
+    pub frequencies: Vec<f64>,
+    // Amplitude of each bin, one list per selected component
+    pub amplitudes: Vec<Vec<f64>>,
+}
+
+impl Spectrum {
+    // Frequency of the largest non-zero frequency peak of the i-th selected component
+    pub fn dominant_frequency(&self, i: usize) -> f64 {
+        let (k, _) = self.amplitudes[i].iter().enumerate().skip(1).fold(
+            (0, f64::NEG_INFINITY),
+            |(k_max, a_max), (k, a)| {
+                if *a > a_max {
+                    (k, *a)
+                } else {
+                    (k_max, a_max)
+                }
+            },
+        );
+        self.frequencies[k]
+    }
+}
+
+// Amplitude spectra of the given components of a solution of the system, from
+// `samples` uniformly spaced samples over the whole solution
+pub fn spectrum<S, N: Dim + DimName>(
+    system: &S,
+    results: &IntegResult<N>,
+    components: &[usize],
+    samples: usize,
+    window: Window,
+) -> Result<Spectrum, &'static str>
+where
+    S: OdeSystem<N>,
+    DefaultAllocator: Allocator<N>,
+{
+    if components.iter().any(|c| *c >= N::dim()) {
+        return Err("[SPECTRUM] Component index out of range");
+    }
+    let dense = DenseOutput::new(system, results);
+    let (t_0, t_end) = dense.span();
+    if t_end == t_0 {
+        return Err("[SPECTRUM] Solution has no time span");
+    }
+    let (times, states) = dense.resample(t_0, t_end, samples)?;
+    let dt = (times[1] - times[0]).abs();
+
+    let weights: Vec<f64> = match window {
+        Window::Rectangular => vec![1.0; samples],
+        Window::Hann => (0..samples)
+            .map(|k| 0.5 - 0.5 * (2.0 * PI * k as f64 / (samples - 1) as f64).cos())
+            .collect(),
+    };
+    let weight_sum: f64 = weights.iter().sum();
+
+    let bins = samples / 2 + 1;
+    let frequencies = (0..bins)
+        .map(|k| k as f64 / (samples as f64 * dt))
+        .collect();
+    let fft = FftPlanner::<f64>::new().plan_fft_forward(samples);
+    let amplitudes = components
+        .iter()
+        .map(|c| {
+            let mut buffer: Vec<Complex<f64>> = states
+                .iter()
+                .zip(weights.iter())
+                .map(|(y, w)| Complex::new(y[*c] * w, 0.0))
+                .collect();
+            fft.process(&mut buffer);
+            buffer[..bins]
+                .iter()
+                .enumerate()
+                .map(|(k, val)| {
+                    // one-sided: fold the negative frequencies onto the positive ones
+                    let fold = if k == 0 || 2 * k == samples { 1.0 } else { 2.0 };
+                    fold * val.norm() / weight_sum
+                })
+                .collect()
+        })
+        .collect();
+
+    Ok(Spectrum {
+        frequencies,
+        amplitudes,
+    })
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runge_kutta::adaptive::AdaptiveStep;
+    use crate::runge_kutta::common::IntegOptions;
+    use crate::runge_kutta::rk_embed::RKF45;
+    use na::Vector4;
+
+    #[test]
+    fn test_spectrum() {
+        // two uncoupled oscillators at 2 and 5 cycles per unit time
+        let (w_1, w_2) = (2.0 * PI * 2.0, 2.0 * PI * 5.0);
+        let oscillators = move |_t: f64, y: &Vector4<f64>| {
+            Vector4::new(y[1], -w_1 * w_1 * y[0], y[3], -w_2 * w_2 * y[2])
+        };
+        let opts = IntegOptions {
+            rtol: Some(1e-10),
+            atol: Some(Vector4::repeat(1e-10)),
+            ..IntegOptions::default()
+        };
+        let y_0 = Vector4::new(1.5, 0.0, 0.5, 0.0);
+        let ans = RKF45.integrate(oscillators, 0.0, y_0, 10.0, opts).unwrap();
+
+        for &window in [Window::Rectangular, Window::Hann].iter() {
+            let spec = spectrum(&oscillators, &ans, &[0, 2], 2001, window).unwrap();
+            assert_eq!(spec.amplitudes.len(), 2);
+            assert!((spec.dominant_frequency(0) - 2.0).abs() < 0.1);
+            assert!((spec.dominant_frequency(1) - 5.0).abs() < 0.1);
+            let peak = spec.amplitudes[0].iter().cloned().fold(0.0, f64::max);
+            assert!((peak - 1.5).abs() < 0.1);
+        }
+        assert!(spectrum(&oscillators, &ans, &[4], 100, Window::Hann).is_err());
+    }
+}
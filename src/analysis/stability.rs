@@ -0,0 +1,375 @@
+/// Linear Stability Regions (analysis/stability)
+///
+/// Computes the linear stability region of a configured integrator: the set of
+/// z = h lambda for which its solution of the test equation y' = lambda y does not
+/// grow. An integration is (linearly) stable when h lambda lies in the region for
+/// every eigenvalue lambda of the jacobian of the problem, so comparing the region
+/// with the spectrum of a problem (e.g. from `equilibrium`) checks whether an
+/// integrator configuration can take the intended step on it.
+///
+/// The region is found empirically, by running the integrator itself on the test
+/// equation for every z of a grid over the complex plane, so it covers any method
+/// and configuration: Runge-Kutta tableaus, RIDC with any number of correctors and
+/// restart length, ... The complex test equation is integrated as the real system
+/// `Dahlquist` (y' = z y for y = y_re + i y_im) with unit steps, and the
+/// amplification per step |R(z)| = |y_n|^(1/n) is measured over n steps (several
+/// steps capture methods like RIDC whose amplification is only defined over a
+/// restart window).
+///
+/// The result holds the amplification over the grid and the boundary |R(z)| = 1
+/// as line segments (marching squares) for plotting.
+///
+/// For method development, the same runs give the amplification factor R(z) of a
+/// configured integrator, its order star (where |R(z) e^-z| > 1, the same grid
+/// with the amplification taken relative to the exact solution) and its observed
+/// order of accuracy on the test equation. The stability function and order of a
+/// Runge-Kutta tableau are also available exactly from the tableau itself (see
+/// `runge_kutta::tableaus`).
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::{Complex, Vector2, U2};
+
+// local imports
+use crate::systems::OdeSystem;
+use crate::utils::sweep::sweep;
+
+// === End Imports ===
+
+// Test equation y' = z y for complex y = y[0] + i y[1]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Dahlquist {
+    // Step times eigenvalue
+    pub z: Complex<f64>,
+}
+
+impl OdeSystem<U2> for Dahlquist {
+    fn dynamics(&self, _t: f64, y: &Vector2<f64>) -> Vector2<f64> {
+        Vector2::new(
+            self.z.re * y[0] - self.z.im * y[1],
+            self.z.im * y[0] + self.z.re * y[1],
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StabilityRegion {
+    // Real parts of the grid points
+    pub re: Vec<f64>,
+    // Imaginary parts of the grid points
+    pub im: Vec<f64>,
+    // Amplification per step |R(z)| at each grid point, indexed [im][re]. Infinite
+    // where the integration failed or overflowed
+    pub amplification: Vec<Vec<f64>>,
+    // Segments of the boundary |R(z)| = 1 as pairs of points (re, im)
+    pub boundary: Vec<[(f64, f64); 2]>,
+}
+
+impl StabilityRegion {
+    // Amplification per step at z, interpolated bilinearly from the grid. None
+    // outside of the grid
+    pub fn amplification_at(&self, z: Complex<f64>) -> Option<f64> {
+        let (i, s) = cell(&self.re, z.re)?;
+        let (j, u) = cell(&self.im, z.im)?;
+        let amp = &self.amplification;
+        Some(
+            (1.0 - u) * ((1.0 - s) * amp[j][i] + s * amp[j][i + 1])
+                + u * ((1.0 - s) * amp[j + 1][i] + s * amp[j + 1][i + 1]),
+        )
+    }
+
+    // Whether h lambda is inside the region (within the grid) for every eigenvalue
+    pub fn covers(&self, eigenvalues: &[Complex<f64>], h: f64) -> bool {
+        eigenvalues.iter().all(|lambda| {
+            self.amplification_at(lambda * h)
+                .is_some_and(|amp| amp <= 1.0)
+        })
+    }
+}
+
+// Computes the stability region over the grid of `resolution` (re, im) points
+// spanning `re` and `im`, using up to `threads` worker threads (all cores when
+// None). `integrate(system, y_0, span)` runs the integrator under test on the
+// system from y_0 over `span` with unit steps and returns the final state. The
+// amplification is measured over `steps` steps
+pub fn stability_region<F, E>(
+    integrate: F,
+    re: (f64, f64),
+    im: (f64, f64),
+    resolution: (usize, usize),
+    steps: usize,
+    threads: Option<usize>,
+) -> Result<StabilityRegion, &'static str>
+where
+    F: Fn(Dahlquist, Vector2<f64>, f64) -> Result<Vector2<f64>, E> + Sync,
+{
+    if steps == 0 {
+        return Err("[STABILITY] The grid needs at least 2 points a side and 1 step");
+    }
+    let integrate = &integrate;
+    grid_region(re, im, resolution, threads, |z| {
+        match integrate(Dahlquist { z }, Vector2::new(1.0, 0.0), steps as f64) {
+            Ok(y) if y.norm().is_finite() => y.norm().powf(1.0 / steps as f64),
+            _ => f64::INFINITY,
+        }
+    })
+}
+
+// Order star of an integrator over a grid (as for `stability_region`): the
+// amplification per step relative to the exact solution, |R(z) e^-z|, with its
+// level 1 boundary. Near the origin the star has p + 1 sectors on each side of
+// the boundary for a method of order p
+pub fn order_star<F, E>(
+    integrate: F,
+    re: (f64, f64),
+    im: (f64, f64),
+    resolution: (usize, usize),
+    steps: usize,
+    threads: Option<usize>,
+) -> Result<StabilityRegion, &'static str>
+where
+    F: Fn(Dahlquist, Vector2<f64>, f64) -> Result<Vector2<f64>, E> + Sync,
+{
+    if steps == 0 {
+        return Err("[STABILITY] The grid needs at least 2 points a side and 1 step");
+    }
+    let integrate = &integrate;
+    grid_region(re, im, resolution, threads, |z| {
+        match integrate(Dahlquist { z }, Vector2::new(1.0, 0.0), steps as f64) {
+            Ok(y) if y.norm().is_finite() => y.norm().powf(1.0 / steps as f64) * (-z.re).exp(),
+            _ => f64::INFINITY,
+        }
+    })
+}
+
+// Amplification factor R(z) of one unit step of an integrator (see
+// `stability_region` for `integrate`)
+pub fn amplification_factor<F, E>(
+    integrate: F,
+    z: Complex<f64>,
+) -> Result<Complex<f64>, &'static str>
+where
+    F: Fn(Dahlquist, Vector2<f64>, f64) -> Result<Vector2<f64>, E>,
+    E: Into<&'static str>,
+{
+    let y = integrate(Dahlquist { z }, Vector2::new(1.0, 0.0), 1.0).map_err(Into::into)?;
+    Ok(Complex::new(y[0], y[1]))
+}
+
+// Observed order of accuracy of an integrator on y' = lambda y over a time T with
+// lambda T = `lambda_t`: the rate at which the error at T falls between `steps`
+// and 2 `steps` steps. (With unit steps, n steps of y' = (lambda T / n) y are the
+// same as n steps of length T / n on y' = lambda y.) `lambda_t` should be small
+// enough for both step counts to be stable and the errors above round-off
+pub fn observed_order<F, E>(
+    integrate: F,
+    lambda_t: Complex<f64>,
+    steps: usize,
+) -> Result<f64, &'static str>
+where
+    F: Fn(Dahlquist, Vector2<f64>, f64) -> Result<Vector2<f64>, E>,
+    E: Into<&'static str>,
+{
+    if steps == 0 {
+        return Err("[STABILITY] Observed order needs at least 1 step");
+    }
+    let exact = Complex::new(lambda_t.im.cos(), lambda_t.im.sin()) * lambda_t.re.exp();
+    let error = |n: usize| -> Result<f64, &'static str> {
+        let z = lambda_t / n as f64;
+        let y = integrate(Dahlquist { z }, Vector2::new(1.0, 0.0), n as f64).map_err(Into::into)?;
+        Ok((Complex::new(y[0], y[1]) - exact).norm_sqr().sqrt())
+    };
+    let (coarse, fine) = (error(steps)?, error(2 * steps)?);
+    if fine == 0.0 || !coarse.is_finite() {
+        return Err("[STABILITY] Errors at round-off or unstable: adjust lambda_t or steps");
+    }
+    Ok((coarse / fine).log2())
+}
+
+// Evaluates `amplification` over the grid of `resolution` points spanning re and
+// im, with the boundary at level 1
+fn grid_region<A>(
+    re: (f64, f64),
+    im: (f64, f64),
+    resolution: (usize, usize),
+    threads: Option<usize>,
+    amplification: A,
+) -> Result<StabilityRegion, &'static str>
+where
+    A: Fn(Complex<f64>) -> f64 + Sync,
+{
+    if resolution.0 < 2 || resolution.1 < 2 {
+        return Err("[STABILITY] The grid needs at least 2 points a side and 1 step");
+    }
+    if !(re.0 < re.1 && im.0 < im.1) {
+        return Err("[STABILITY] Grid ranges must be increasing");
+    }
+    let linspace = |(lo, hi): (f64, f64), n: usize| -> Vec<f64> {
+        (0..n)
+            .map(|k| lo + (hi - lo) * k as f64 / (n - 1) as f64)
+            .collect()
+    };
+    let re = linspace(re, resolution.0);
+    let im = linspace(im, resolution.1);
+
+    let points: Vec<Complex<f64>> = im
+        .iter()
+        .flat_map(|y| re.iter().map(move |x| Complex::new(*x, *y)))
+        .collect();
+    let amps = sweep(&points, threads, |z| amplification(*z));
+    let amplification: Vec<Vec<f64>> = amps.chunks(re.len()).map(|row| row.to_vec()).collect();
+    let boundary = contour(&re, &im, &amplification, 1.0);
+
+    Ok(StabilityRegion {
+        re,
+        im,
+        amplification,
+        boundary,
+    })
+}
+
+// Index of the grid cell containing x and the fraction of the way across it
+fn cell(grid: &[f64], x: f64) -> Option<(usize, f64)> {
+    let last = grid.len() - 1;
+    if x < grid[0] || x > grid[last] {
+        return None;
+    }
+    let idx = grid.windows(2).position(|w| x <= w[1]).unwrap_or(last - 1);
+    Some((idx, (x - grid[idx]) / (grid[idx + 1] - grid[idx])))
+}
+
+// Segments of the level set vals = level over the grid (marching squares, with the
+// crossing on each cell edge found by linear interpolation)
+fn contour(re: &[f64], im: &[f64], vals: &[Vec<f64>], level: f64) -> Vec<[(f64, f64); 2]> {
+    let mut segments = Vec::new();
+    for j in 0..im.len() - 1 {
+        for i in 0..re.len() - 1 {
+            // corners counter-clockwise from the lower left
+            let corners = [
+                (re[i], im[j], vals[j][i]),
+                (re[i + 1], im[j], vals[j][i + 1]),
+                (re[i + 1], im[j + 1], vals[j + 1][i + 1]),
+                (re[i], im[j + 1], vals[j + 1][i]),
+            ];
+            let mut crossings = Vec::with_capacity(4);
+            for k in 0..4 {
+                let (x_a, y_a, v_a) = corners[k];
+                let (x_b, y_b, v_b) = corners[(k + 1) % 4];
+                if (v_a <= level) == (v_b <= level) {
+                    continue;
+                }
+                // infinite values put the crossing at the finite end
+                let frac = if v_a.is_finite() && v_b.is_finite() {
+                    (level - v_a) / (v_b - v_a)
+                } else if v_a.is_finite() {
+                    0.0
+                } else {
+                    1.0
+                };
+                crossings.push((x_a + frac * (x_b - x_a), y_a + frac * (y_b - y_a)));
+            }
+            // two crossings make a segment, four (a saddle) make two
+            for pair in crossings.chunks(2) {
+                if pair.len() == 2 {
+                    segments.push([pair[0], pair[1]]);
+                }
+            }
+        }
+    }
+    segments
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ridc::base::RIDCIntegratorFixed;
+    use crate::ridc::common::IntegOptionsParallel;
+    use crate::runge_kutta::common::IntegOptions;
+    use crate::runge_kutta::fixed::FixedStep;
+    use crate::runge_kutta::rk_embed::{DOPRI78, RKF45};
+    use crate::runge_kutta::rk_simp::RK4;
+
+    #[test]
+    fn test_rk4_stability_region() {
+        let rk4 = |system: Dahlquist, y_0: Vector2<f64>, span: f64| {
+            RK4.integrate(system, 0.0, y_0, span, 1.0, IntegOptions::default())
+                .map(|ans| *ans.last_y())
+        };
+        let region = stability_region(rk4, (-3.5, 0.5), (-3.5, 3.5), (81, 141), 4, None).unwrap();
+
+        // the amplification of RK4 is |1 + z + z^2/2 + z^3/6 + z^4/24|
+        let z = Complex::new(-1.0_f64, 1.5);
+        let exact =
+            (Complex::new(1.0, 0.0) + z + z * z / 2.0 + z * z * z / 6.0 + z * z * z * z / 24.0)
+                .norm_sqr()
+                .sqrt();
+        assert!((region.amplification[100][50] - exact).abs() < 1e-12);
+
+        // the region reaches -2.785 on the real axis and +- 2.828 on the imaginary one
+        let on_axis = |seg: &[(f64, f64); 2]| seg[0].1.abs() < 0.03 && seg[0].0 < -2.0;
+        let crossing = region.boundary.iter().find(|seg| on_axis(seg)).unwrap();
+        assert!((crossing[0].0 + 2.785).abs() < 0.02);
+        assert!(region.covers(&[Complex::new(-2.7, 0.0), Complex::new(0.0, 2.7)], 1.0));
+        assert!(!region.covers(&[Complex::new(-2.9, 0.0)], 1.0));
+        assert!(!region.covers(&[Complex::new(0.0, -3.0)], 1.0));
+        // halving the step brings a stiffer eigenvalue inside
+        assert!(region.covers(&[Complex::new(-5.0, 0.0)], 0.5));
+        assert!(!region.covers(&[Complex::new(-10.0, 0.0)], 1.0));
+
+        // RK4 with a deferred correction over restart windows of 4 steps
+        let ridc = |system: Dahlquist, y_0: Vector2<f64>, span: f64| {
+            let opts = IntegOptionsParallel {
+                restart_length: Some(4),
+                ..IntegOptionsParallel::default()
+            };
+            RK4.parallel_integrator(system, 0.0, &y_0, span, 1.0, opts)
+                .map(|ans| *ans.last_y())
+        };
+        let region = stability_region(ridc, (-4.0, 1.0), (-3.0, 3.0), (11, 13), 8, None).unwrap();
+        assert!(region.covers(&[Complex::new(-1.0, 0.5), Complex::new(-2.0, 0.0)], 1.0));
+        assert!(!region.covers(&[Complex::new(0.5, 0.0), Complex::new(-10.0, 0.0)], 1.0));
+        assert!(!region.boundary.is_empty());
+    }
+
+    #[test]
+    fn test_amplification_and_order() {
+        let rk4 = |system: Dahlquist, y_0: Vector2<f64>, span: f64| {
+            RK4.integrate(system, 0.0, y_0, span, 1.0, IntegOptions::default())
+                .map(|ans| *ans.last_y())
+        };
+        let z = Complex::new(-0.8_f64, 1.2);
+        let r = amplification_factor(rk4, z).unwrap();
+        assert!((r - RK4.tableau().stability_function(z)).norm_sqr() < 1e-28);
+        assert_eq!(RK4.tableau().order(), 4);
+        // the order conditions of the prebuilt embedded pairs (b, b_hat)
+        assert_eq!(RKF45.tableau().orders(), (5, 4));
+        assert_eq!(DOPRI78.tableau().orders(), (8, 7));
+
+        let order = observed_order(rk4, Complex::new(-1.0, 1.0), 20).unwrap();
+        assert!((order - 4.0).abs() < 0.1);
+
+        // RK4 falls behind the exact solution on the positive real axis and ahead of
+        // it on the negative one
+        let star = order_star(rk4, (-0.5, 0.5), (-0.5, 0.5), (21, 21), 1, Some(2)).unwrap();
+        assert!(star.amplification_at(Complex::new(0.4, 0.0)).unwrap() < 1.0);
+        assert!(star.amplification_at(Complex::new(-0.4, 0.0)).unwrap() > 1.0);
+        assert!(!star.boundary.is_empty());
+
+        let ridc = |system: Dahlquist, y_0: Vector2<f64>, span: f64| {
+            RK4.parallel_integrator(
+                system,
+                0.0,
+                &y_0,
+                span,
+                1.0,
+                IntegOptionsParallel::default(),
+            )
+            .map(|ans| *ans.last_y())
+        };
+        // a single correction keeps the order of the RK4 predictor
+        let order = observed_order(ridc, Complex::new(-1.0, 1.0), 40).unwrap();
+        assert!((order - 4.0).abs() < 0.1);
+    }
+}
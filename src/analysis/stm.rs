@@ -0,0 +1,319 @@
+/// State Transition Matrix (analysis/stm)
+///
+/// Propagates a state together with its state transition matrix
+/// Phi(t, t_0) = dy(t) / dy(t_0), the linear map taking a perturbation of the
+/// initial state to the perturbation at time t. Phi follows the variational
+/// equations dPhi/dt = J(t, y) Phi with Phi(t_0, t_0) = I, where J is the finite
+/// difference jacobian of the dynamics.
+///
+/// The state and the columns of Phi are stepped together as one augmented state
+/// with a fixed step `RKStepper` (through `integrate_state`).
+///
+/// Across a switch of a hybrid system (see `runge_kutta::hybrid`), where the guard
+/// g(t, y) fires and the reset y+ = R(t, y-) is applied, Phi jumps by the saltation
+/// matrix S = R_y + (f+ - R_y f- - R_t) g_y^T / (g_y f- + g_t), with f- and f+ the dynamics of the modes left and entered. Besides the
+/// perturbation of the state carried through the reset, S accounts for the
+/// perturbation shifting the time of the switch, so the sensitivities through an
+/// impact or an impulsive maneuver are exact rather than finite differenced across
+/// the discontinuity. `hybrid_stm` composes the legs of a hybrid trajectory with
+/// the saltation matrices of its switches. A switch grazing the guard surface
+/// (g_y f- + g_t = 0) has no saltation matrix: its time is not differentiable.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DefaultAllocator, Dim, DimName, OMatrix, OVector};
+
+// local imports
+use crate::runge_kutta::base::RKStepper;
+use crate::runge_kutta::hybrid::{HybridResult, HybridSystem, Mode, Transition};
+use crate::systems::OdeSystem;
+use crate::utils::finite_diff::{fdiff_jacobian, fdiff_jacobian_richardson};
+
+// Standard library imports
+use std::fmt;
+
+// === End Imports ===
+
+// Propagates y_0 from t_0 over the time span `span` with the fixed step `step`.
+// Returns the final state and the state transition matrix over the span
+pub fn propagate_stm<S, N: Dim + DimName, D: Dim + DimName>(
+    stepper: &RKStepper<D>,
+    system: &S,
+    t_0: f64,
+    y_0: &OVector<f64, N>,
+    span: f64,
+    step: f64,
+) -> (OVector<f64, N>, OMatrix<f64, N, N>)
+where
+    S: OdeSystem<N>,
+    DefaultAllocator: Allocator<N> + Allocator<N, N> + Allocator<D> + Allocator<D, D>,
+{
+    let n = N::dim();
+    if span == 0.0 {
+        return (y_0.clone(), OMatrix::<f64, N, N>::identity());
+    }
+
+    // augmented state [y, Phi] with Phi stored column major
+    let variational = |t: f64, x: &Vec<f64>| {
+        let y = OVector::<f64, N>::from_column_slice(&x[..n]);
+        let fxn = |y: &OVector<f64, N>| system.dynamics(t, y);
+        let f_y = fxn(&y);
+        let jac = fdiff_jacobian(&fxn, &f_y, &y);
+        let phi = OMatrix::<f64, N, N>::from_column_slice(&x[n..]);
+        f_y.iter().chain((jac * phi).iter()).cloned().collect()
+    };
+    let x_0: Vec<f64> = y_0
+        .iter()
+        .chain(OMatrix::<f64, N, N>::identity().iter())
+        .cloned()
+        .collect();
+    let (_, states) = stepper.integrate_state(&variational, t_0, x_0, span, step);
+    let x = states.last().unwrap();
+    (
+        OVector::<f64, N>::from_column_slice(&x[..n]),
+        OMatrix::<f64, N, N>::from_column_slice(&x[n..]),
+    )
+}
+
+// Error of a saltation matrix or of a hybrid state transition matrix
+#[derive(Debug, Clone, PartialEq)]
+pub enum SaltationError {
+    // The switch from mode `from` to mode `to` at time t grazes its guard
+    Grazing { from: String, to: String, t: f64 },
+    // The trajectory has a different number of switches than resets
+    Mismatch,
+}
+
+impl fmt::Display for SaltationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaltationError::Grazing { from, to, t } => write!(
+                f,
+                "[SALTATION] Switch from mode {} to mode {} at t = {} grazes its guard",
+                from, to, t
+            ),
+            SaltationError::Mismatch => {
+                f.write_str("[SALTATION] Trajectory does not match its switches")
+            }
+        }
+    }
+}
+
+// Saltation matrix of the transition `transition` from mode `from` to mode `to`,
+// taken at time t from the state y_minus on the guard surface
+pub fn saltation_matrix<N: Dim + DimName>(
+    from: &Mode<N>,
+    to: &Mode<N>,
+    transition: &Transition<N>,
+    t: f64,
+    y_minus: &OVector<f64, N>,
+) -> Result<OMatrix<f64, N, N>, SaltationError>
+where
+    DefaultAllocator: Allocator<N> + Allocator<N, N>,
+{
+    // Approximately cube root of ULP precision, for central differences
+    const H_FACTOR: f64 = 6.055_454_452_393_343e-6_f64;
+    let h_t = H_FACTOR * t.abs().max(1.0);
+    let (guard, reset) = (transition.guard, transition.reset);
+
+    let y_plus = reset(t, y_minus);
+    let f_minus = (from.dynamics)(t, y_minus);
+    let f_plus = (to.dynamics)(t, &y_plus);
+    let reset_y = fdiff_jacobian_richardson(&|y: &OVector<f64, N>| reset(t, y), &y_plus, y_minus);
+    let reset_t = (reset(t + h_t, y_minus) - reset(t - h_t, y_minus)) / (2.0 * h_t);
+    let guard_t = (guard(t + h_t, y_minus) - guard(t - h_t, y_minus)) / (2.0 * h_t);
+    let mut guard_y = OVector::<f64, N>::zeros();
+    for j in 0..N::dim() {
+        let h = H_FACTOR * y_minus[j].abs().max(1.0);
+        let (mut y_p, mut y_m) = (y_minus.clone(), y_minus.clone());
+        y_p[j] += h;
+        y_m[j] -= h;
+        guard_y[j] = (guard(t, &y_p) - guard(t, &y_m)) / (2.0 * h);
+    }
+
+    let rate = guard_y.dot(&f_minus) + guard_t;
+    let scale = guard_y.norm() * f_minus.norm() + guard_t.abs();
+    if rate.abs() <= f64::EPSILON.sqrt() * scale || scale == 0.0 {
+        return Err(SaltationError::Grazing {
+            from: from.name.clone(),
+            to: to.name.clone(),
+            t,
+        });
+    }
+    let jump = f_plus - &reset_y * f_minus - reset_t;
+    Ok(reset_y + OMatrix::<f64, N, N>::from_fn(|i, j| jump[i] * guard_y[j] / rate))
+}
+
+// State transition matrix over a trajectory of a hybrid system: the variational
+// equations are stepped along each leg between switches with the fixed step `step`,
+// and the legs are joined by the saltation matrices of the switches
+pub fn hybrid_stm<N: Dim + DimName, D: Dim + DimName>(
+    stepper: &RKStepper<D>,
+    system: &HybridSystem<N>,
+    trajectory: &HybridResult<N>,
+    step: f64,
+) -> Result<OMatrix<f64, N, N>, SaltationError>
+where
+    DefaultAllocator: Allocator<N> + Allocator<N, N> + Allocator<D> + Allocator<D, D>,
+{
+    let (times, states) = (&trajectory.results.times, &trajectory.results.states);
+    // the time of each switch appears twice, before and after the reset
+    let ends: Vec<usize> = (0..times.len().saturating_sub(1))
+        .filter(|j| times[*j] == times[j + 1])
+        .collect();
+    if ends.len() != trajectory.switches.len() {
+        return Err(SaltationError::Mismatch);
+    }
+
+    let modes = system.modes();
+    let mut phi = OMatrix::<f64, N, N>::identity();
+    let mut start = 0;
+    let mut current = trajectory.initial_mode;
+    for (end, switch) in ends.iter().zip(trajectory.switches.iter()) {
+        let span = times[*end] - times[start];
+        let (_, leg) = propagate_stm(
+            stepper,
+            &modes[current].dynamics,
+            times[start],
+            &states[start],
+            span,
+            step,
+        );
+        let from = &modes[switch.from];
+        let jump = saltation_matrix(
+            from,
+            &modes[switch.to],
+            &from.transitions[switch.transition],
+            switch.t,
+            &states[*end],
+        )?;
+        phi = jump * leg * phi;
+        start = end + 1;
+        current = switch.to;
+    }
+    let last = times.len() - 1;
+    let (_, leg) = propagate_stm(
+        stepper,
+        &modes[current].dynamics,
+        times[start],
+        &states[start],
+        times[last] - times[start],
+        step,
+    );
+    Ok(leg * phi)
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runge_kutta::common::IntegOptions;
+    use crate::runge_kutta::rk_embed::DOPRI78;
+    use crate::runge_kutta::rk_simp::RK4;
+    use na::{Matrix2, Vector2};
+
+    #[test]
+    fn test_propagate_stm() {
+        // harmonic oscillator: Phi is the rotation [cos t, sin t; -sin t, cos t]
+        let spring = |_t: f64, y: &Vector2<f64>| Vector2::new(y[1], -y[0]);
+        let (y, phi) = propagate_stm(&RK4, &spring, 0.0, &Vector2::new(1.0, 0.0), 2.0, 0.01);
+        let (c, s) = (2.0_f64.cos(), 2.0_f64.sin());
+        assert!((y - Vector2::new(c, -s)).norm() < 1e-9);
+        assert!((phi - Matrix2::new(c, s, -s, c)).norm() < 1e-8);
+
+        // nonlinear: compare against differenced trajectories
+        let pendulum = |_t: f64, y: &Vector2<f64>| Vector2::new(y[1], -y[0].sin());
+        let y_0 = Vector2::new(1.0, 0.5);
+        let (_, phi) = propagate_stm(&RK4, &pendulum, 0.0, &y_0, 3.0, 0.01);
+        let h = 1e-6;
+        for j in 0..2 {
+            let mut shift = Vector2::zeros();
+            shift[j] = h;
+            let (y_p, _) = propagate_stm(&RK4, &pendulum, 0.0, &(y_0 + shift), 3.0, 0.01);
+            let (y_m, _) = propagate_stm(&RK4, &pendulum, 0.0, &(y_0 - shift), 3.0, 0.01);
+            assert!(((y_p - y_m) / (2.0 * h) - phi.column(j)).norm() < 1e-6);
+        }
+    }
+
+    fn fall(_t: f64, y: &Vector2<f64>) -> Vector2<f64> {
+        Vector2::new(y[1], -9.8)
+    }
+
+    fn bounce(_t: f64, y: &Vector2<f64>) -> Vector2<f64> {
+        Vector2::new(y[0], -0.8 * y[1])
+    }
+
+    #[test]
+    fn test_hybrid_stm() {
+        // bouncing ball: at an impact with speed v the saltation matrix is
+        // [-e, 0; -(1 + e) g / v, -e]
+        let ball = HybridSystem::new(vec![Mode {
+            name: "flight".to_string(),
+            dynamics: fall,
+            transitions: vec![Transition {
+                guard: |_t, y| y[0],
+                target: 0,
+                reset: bounce,
+            }],
+        }])
+        .unwrap();
+        let flight = &ball.modes()[0];
+        let v = -7.0;
+        let jump = saltation_matrix(
+            flight,
+            flight,
+            &flight.transitions[0],
+            1.0,
+            &Vector2::new(0.0, v),
+        )
+        .unwrap();
+        assert!((jump - Matrix2::new(-0.8, 0.0, -1.8 * 9.8 / v, -0.8)).norm() < 1e-8);
+        let grazing = saltation_matrix(
+            flight,
+            flight,
+            &flight.transitions[0],
+            1.0,
+            &Vector2::new(0.0, 0.0),
+        )
+        .unwrap_err();
+        assert_eq!(
+            grazing,
+            SaltationError::Grazing {
+                from: "flight".to_string(),
+                to: "flight".to_string(),
+                t: 1.0
+            }
+        );
+        assert_eq!(
+            grazing.to_string(),
+            "[SALTATION] Switch from mode flight to mode flight at t = 1 grazes its guard"
+        );
+
+        // through two bounces: compare against differenced hybrid trajectories
+        let options = || IntegOptions {
+            atol: Some(Vector2::repeat(1e-13)),
+            rtol: Some(1e-13),
+            // the flights are exact quadratics: keep the steps from spanning one
+            max_step: Some(0.1),
+            ..IntegOptions::default()
+        };
+        let run = |y_0: Vector2<f64>| {
+            ball.integrate(&*DOPRI78, 0, 0.0, y_0, 4.0, options())
+                .unwrap()
+        };
+        let y_0 = Vector2::new(10.0, 1.0);
+        let trajectory = run(y_0);
+        assert_eq!(trajectory.switches.len(), 2);
+        let phi = hybrid_stm(&RK4, &ball, &trajectory, 0.01).unwrap();
+        let h = 1e-5;
+        for j in 0..2 {
+            let mut shift = Vector2::zeros();
+            shift[j] = h;
+            let y_p = *run(y_0 + shift).results.last_y();
+            let y_m = *run(y_0 - shift).results.last_y();
+            assert!(((y_p - y_m) / (2.0 * h) - phi.column(j)).norm() < 1e-5);
+        }
+    }
+}
@@ -0,0 +1,198 @@
+/// Unscented Transform (analysis/unscented)
+///
+/// Propagates a mean and covariance through a nonlinear map (typically an
+/// integration over some time span) without variational equations. 2n + 1 sigma
+/// points are placed around the mean along the columns of the scaled matrix square
+/// root of the covariance, each is propagated independently (in parallel with
+/// `sweep`), and the mean and covariance of the result are rebuilt as weighted
+/// sums over the propagated points. The result is exact for linear maps and
+/// captures the mean to second order for nonlinear ones.
+///
+/// The spread of the points is set by alpha, kappa and beta following the scaled
+/// transform of Julier (2002) and Wan & van der Merwe (2000):
+/// lambda = alpha^2 (n + kappa) - n, with points at mean +- sqrt(n + lambda) L_i
+/// for the columns L_i of the cholesky factor of the covariance. beta = 2 is optimal
+/// for gaussian inputs.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DefaultAllocator, Dim, DimName, OMatrix, OVector, U1};
+
+// local imports
+use crate::utils::sweep::sweep;
+
+// === End Imports ===
+
+// Mean and covariance of a distribution
+pub type Moments<N> = (OVector<f64, N>, OMatrix<f64, N, N>);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UnscentedOptions {
+    // Spread of the sigma points around the mean
+    pub alpha: Option<f64>,
+    // Weight correction of the central point for the covariance (prior knowledge of
+    // the distribution, 2 for gaussians)
+    pub beta: Option<f64>,
+    // Secondary scaling of the spread
+    pub kappa: Option<f64>,
+    // Number of threads propagating the sigma points (all available cores when None)
+    pub threads: Option<usize>,
+}
+
+impl UnscentedOptions {
+    pub fn default() -> Self {
+        UnscentedOptions {
+            alpha: Some(1.0),
+            beta: Some(2.0),
+            kappa: Some(0.0),
+            threads: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SigmaPoints<N: Dim + DimName>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    // Sigma points, the mean first
+    pub points: Vec<OVector<f64, N>>,
+    // Weight of each point in the mean
+    pub mean_weights: Vec<f64>,
+    // Weight of each point in the covariance
+    pub cov_weights: Vec<f64>,
+}
+
+impl<N: Dim + DimName> SigmaPoints<N>
+where
+    DefaultAllocator: Allocator<N> + Allocator<N, N>,
+{
+    // Sigma points of a mean and covariance. Errors if the covariance is not
+    // positive definite
+    pub fn new(
+        mean: &OVector<f64, N>,
+        covariance: &OMatrix<f64, N, N>,
+        opts: &UnscentedOptions,
+    ) -> Result<Self, &'static str> {
+        let defaults = UnscentedOptions::default();
+        let alpha = opts.alpha.or(defaults.alpha).unwrap();
+        let beta = opts.beta.or(defaults.beta).unwrap();
+        let kappa = opts.kappa.or(defaults.kappa).unwrap();
+        let n = N::dim() as f64;
+        let lambda = alpha * alpha * (n + kappa) - n;
+        if n + lambda <= 0.0 {
+            return Err("[UNSCENTED] alpha and kappa must give a positive spread");
+        }
+        let factor = match covariance.clone().cholesky() {
+            Some(chol) => chol.unpack() * (n + lambda).sqrt(),
+            None => return Err("[UNSCENTED] Covariance is not positive definite"),
+        };
+
+        let mut points = vec![mean.clone()];
+        for col in factor.column_iter() {
+            points.push(mean + col);
+        }
+        for col in factor.column_iter() {
+            points.push(mean - col);
+        }
+        let weight = 0.5 / (n + lambda);
+        let mut mean_weights = vec![weight; points.len()];
+        mean_weights[0] = lambda / (n + lambda);
+        let mut cov_weights = mean_weights.clone();
+        cov_weights[0] += 1.0 - alpha * alpha + beta;
+
+        Ok(SigmaPoints {
+            points,
+            mean_weights,
+            cov_weights,
+        })
+    }
+
+    // Mean and covariance of the transformed sigma points (in the same order)
+    pub fn reconstruct<M: Dim + DimName>(&self, transformed: &[OVector<f64, M>]) -> Moments<M>
+    where
+        DefaultAllocator: Allocator<M> + Allocator<M, M> + Allocator<U1, M>,
+    {
+        let mut mean = OVector::<f64, M>::zeros();
+        for (w, y) in self.mean_weights.iter().zip(transformed.iter()) {
+            mean += y * *w;
+        }
+        let mut covariance = OMatrix::<f64, M, M>::zeros();
+        for (w, y) in self.cov_weights.iter().zip(transformed.iter()) {
+            let dev = y - &mean;
+            covariance += &dev * dev.transpose() * *w;
+        }
+        (mean, covariance)
+    }
+}
+
+// Propagates a mean and covariance through `propagate` (e.g. an integration of
+// each sigma point to a final time) with the unscented transform
+pub fn unscented_transform<F, E, N: Dim + DimName>(
+    mean: &OVector<f64, N>,
+    covariance: &OMatrix<f64, N, N>,
+    opts: UnscentedOptions,
+    propagate: F,
+) -> Result<Moments<N>, &'static str>
+where
+    F: Fn(&OVector<f64, N>) -> Result<OVector<f64, N>, E> + Sync,
+    E: Into<&'static str>,
+    DefaultAllocator: Allocator<N> + Allocator<N, N> + Allocator<U1, N>,
+    OVector<f64, N>: Send + Sync,
+{
+    let sigma = SigmaPoints::new(mean, covariance, &opts)?;
+    let transformed = sweep(&sigma.points, opts.threads, |y| {
+        propagate(y).map_err(Into::into)
+    })
+    .into_iter()
+    .collect::<Result<Vec<_>, _>>()?;
+    Ok(sigma.reconstruct(&transformed))
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::stm::propagate_stm;
+    use crate::runge_kutta::common::IntegOptions;
+    use crate::runge_kutta::fixed::FixedStep;
+    use crate::runge_kutta::rk_simp::RK4;
+    use na::{Matrix2, Vector2};
+
+    #[test]
+    fn test_unscented_transform() {
+        let damped = |_t: f64, y: &Vector2<f64>| Vector2::new(y[1], -2.0 * y[0] - 0.3 * y[1]);
+        let propagate = |y: &Vector2<f64>| {
+            RK4.integrate(damped, 0.0, *y, 3.0, 0.01, IntegOptions::default())
+                .map(|ans| *ans.last_y())
+        };
+        let mean = Vector2::new(1.0, -0.5);
+        let cov = Matrix2::new(0.04, 0.01, 0.01, 0.09);
+
+        // sigma points reproduce the input statistics
+        let sigma = SigmaPoints::new(&mean, &cov, &UnscentedOptions::default()).unwrap();
+        assert_eq!(sigma.points.len(), 5);
+        let (m, p) = sigma.reconstruct(&sigma.points);
+        assert!((m - mean).norm() < 1e-14 && (p - cov).norm() < 1e-14);
+
+        // exact for a linear system: the mean and covariance follow the STM
+        let (y, phi) = propagate_stm(&RK4, &damped, 0.0, &mean, 3.0, 0.01);
+        let (m, p) =
+            unscented_transform(&mean, &cov, UnscentedOptions::default(), propagate).unwrap();
+        assert!((m - y).norm() < 1e-10);
+        // (up to the finite difference jacobian of the STM)
+        assert!((p - phi * cov * phi.transpose()).norm() < 1e-8);
+
+        // y -> y^2 of a gaussian: the mean picks up the variance
+        let opts = UnscentedOptions {
+            threads: Some(1),
+            ..UnscentedOptions::default()
+        };
+        let square = |y: &Vector2<f64>| Ok::<_, &'static str>(y.component_mul(y));
+        let (m, _) = unscented_transform(&mean, &cov, opts, square).unwrap();
+        assert!((m - Vector2::new(1.04, 0.34)).norm() < 1e-12);
+        assert!(unscented_transform(&mean, &-cov, opts, square).is_err());
+    }
+}
@@ -0,0 +1,288 @@
+/// Validated Integration (analysis/validated)
+///
+/// Computes guaranteed enclosures of the solution of an initial value problem:
+/// boxes of intervals which provably contain the exact solution (of every initial
+/// state in the initial box) at each step, with all rounding errors accounted for
+/// through outward rounded interval arithmetic (see `utils::interval`). The
+/// dynamics are written against intervals, `Fn(Interval, &[Interval]) ->
+/// Vec<Interval>` for (t, y), so that they can be evaluated over whole boxes.
+///
+/// Each step over [t_k, t_k + h] first finds an a priori enclosure B of the
+/// solution over the whole step, validated by the Picard-Lindelof condition
+///
+/// Y_k + [0, h] F([t_k, t_k + h], B) is contained in B
+///
+/// (B is grown from a first order guess until the condition holds), then tightens
+/// it with a few Picard iterations and encloses the state at the end of the step
+/// with Y_{k+1} = Y_k + h F([t_k, t_k + h], B). Steps which cannot be validated
+/// (too long for the local lipschitz constant, or a solution which blows up) are
+/// halved down to a minimum step, after which the integration errors.
+///
+/// The method is first order, so enclosures widen by O(h) over a fixed time span on
+/// top of the growth of the initial uncertainty, and interval arithmetic
+/// overestimates expressions where a variable appears more than once. Small steps
+/// and dynamics written with each variable appearing once where possible keep the
+/// enclosures tight.
+///
+// === Begin Imports ===
+// local imports
+use crate::utils::interval::Interval;
+
+// === End Imports ===
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ValidatedOptions {
+    // Nominal step
+    pub step: Option<f64>,
+    // Smallest step tried before giving up on validating a step
+    pub min_step: Option<f64>,
+    // Relative growth of the a priori enclosure per validation attempt
+    pub inflation: Option<f64>,
+    // Number of growth attempts per step size
+    pub max_inflations: Option<usize>,
+}
+
+impl ValidatedOptions {
+    pub fn default() -> Self {
+        ValidatedOptions {
+            step: Some(0.01),
+            min_step: Some(1e-10),
+            inflation: Some(0.1),
+            max_inflations: Some(10),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Enclosure {
+    // Step times, starting at t_0
+    pub times: Vec<f64>,
+    // Enclosure of the solution at each step time
+    pub states: Vec<Vec<Interval>>,
+    // Enclosure of the solution over each step [t_k, t_{k+1}]
+    pub step_bounds: Vec<Vec<Interval>>,
+}
+
+impl Enclosure {
+    // Largest interval width of the final enclosure
+    pub fn final_width(&self) -> f64 {
+        self.states
+            .last()
+            .unwrap()
+            .iter()
+            .map(|y| y.width())
+            .fold(0.0, f64::max)
+    }
+
+    // Enclosure of the solution at any time in the integrated span
+    pub fn bounds_at(&self, t: f64) -> Option<&Vec<Interval>> {
+        let (first, last) = (self.times[0], *self.times.last().unwrap());
+        if t < first.min(last) || t > first.max(last) {
+            return None;
+        }
+        if let Some(idx) = self.times.iter().position(|t_k| *t_k == t) {
+            return Some(&self.states[idx]);
+        }
+        self.times
+            .windows(2)
+            .position(|w| (w[0] - t) * (w[1] - t) <= 0.0)
+            .map(|idx| &self.step_bounds[idx])
+    }
+}
+
+// Encloses the solution from the initial box y_0 at t_0 over the time span `span`
+// (which can be negative)
+pub fn validated_integrate<F>(
+    fxn: F,
+    t_0: f64,
+    y_0: &[Interval],
+    span: f64,
+    opts: ValidatedOptions,
+) -> Result<Enclosure, &'static str>
+where
+    F: Fn(Interval, &[Interval]) -> Vec<Interval>,
+{
+    let defaults = ValidatedOptions::default();
+    let step = opts.step.or(defaults.step).unwrap().abs();
+    let min_step = opts.min_step.or(defaults.min_step).unwrap().abs();
+    let inflation = opts.inflation.or(defaults.inflation).unwrap();
+    let max_inflations = opts.max_inflations.or(defaults.max_inflations).unwrap();
+    if step == 0.0 || !span.is_finite() {
+        return Err("[VALIDATED] Step must be nonzero and the span finite");
+    }
+    if y_0.iter().any(|y| !y.is_finite()) {
+        return Err("[VALIDATED] Initial enclosure must be finite");
+    }
+
+    let t_end = t_0 + span;
+    let dir = span.signum();
+    let mut times = vec![t_0];
+    let mut states = vec![y_0.to_vec()];
+    let mut step_bounds = Vec::new();
+    let mut t = t_0;
+    let mut h = step;
+    while (t_end - t) * dir > 0.0 {
+        let y = states.last().unwrap().clone();
+        let t_next = if h >= (t_end - t).abs() {
+            t_end
+        } else {
+            t + dir * h
+        };
+        let span_t = Interval::new(t, t_next);
+        // the exact step and the range of partial steps within it
+        let h_int = Interval::point(t_next) - Interval::point(t);
+        let partial = h_int.hull(&Interval::point(0.0));
+
+        let bound = match a_priori(&fxn, span_t, &y, partial, inflation, max_inflations) {
+            Some(bound) => bound,
+            None => {
+                h *= 0.5;
+                if h < min_step {
+                    return Err("[VALIDATED] Could not validate a step above the minimum step");
+                }
+                continue;
+            }
+        };
+        let f_b = fxn(span_t, &bound);
+        let y_next: Vec<Interval> = y
+            .iter()
+            .zip(f_b.iter())
+            .zip(bound.iter())
+            .map(|((y_k, f_k), b_k)| {
+                let next = *y_k + h_int * *f_k;
+                next.intersect(b_k).unwrap_or(next)
+            })
+            .collect();
+        if y_next.iter().any(|y| !y.is_finite()) {
+            return Err("[VALIDATED] Enclosure is no longer finite");
+        }
+
+        t = t_next;
+        times.push(t);
+        states.push(y_next);
+        step_bounds.push(bound);
+        h = (2.0 * h).min(step);
+    }
+
+    Ok(Enclosure {
+        times,
+        states,
+        step_bounds,
+    })
+}
+
+// Finds and tightens an enclosure B with y + partial * F(t, B) within B, None if
+// the growth attempts run out
+fn a_priori<F>(
+    fxn: &F,
+    t: Interval,
+    y: &[Interval],
+    partial: Interval,
+    inflation: f64,
+    max_inflations: usize,
+) -> Option<Vec<Interval>>
+where
+    F: Fn(Interval, &[Interval]) -> Vec<Interval>,
+{
+    let picard = |bound: &[Interval]| -> Vec<Interval> {
+        y.iter()
+            .zip(fxn(t, bound).iter())
+            .map(|(y_k, f_k)| *y_k + partial * *f_k)
+            .collect()
+    };
+    let inflate = |bound: &[Interval]| -> Vec<Interval> {
+        bound
+            .iter()
+            .map(|b| {
+                let grow = inflation * b.width() + 1e-15 * (1.0 + b.lo.abs().max(b.hi.abs()));
+                Interval::new(b.lo - grow, b.hi + grow)
+            })
+            .collect()
+    };
+
+    let mut bound = inflate(&picard(y));
+    for _ in 0..max_inflations {
+        let image = picard(&bound);
+        if image.iter().any(|b| !b.is_finite()) {
+            return None;
+        }
+        if image.iter().zip(bound.iter()).all(|(i, b)| i.subset(b)) {
+            // validated: further Picard iterates still enclose the solution
+            let mut tight = image;
+            for _ in 0..3 {
+                tight = picard(&tight);
+            }
+            return Some(tight);
+        }
+        let grown: Vec<Interval> = image
+            .iter()
+            .zip(bound.iter())
+            .map(|(i, b)| i.hull(b))
+            .collect();
+        bound = inflate(&grown);
+    }
+    None
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validated_integrate() {
+        // exponential decay encloses e^-t from an exact and an uncertain start
+        let decay = |_t: Interval, y: &[Interval]| vec![-y[0]];
+        let ans = validated_integrate(
+            decay,
+            0.0,
+            &[Interval::point(1.0)],
+            1.0,
+            ValidatedOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(*ans.times.last().unwrap(), 1.0);
+        for (t, y) in ans.times.iter().zip(ans.states.iter()) {
+            assert!(y[0].contains((-t).exp()));
+        }
+        assert!(ans.final_width() < 0.05);
+        assert!(ans.bounds_at(0.505).unwrap()[0].contains((-0.505_f64).exp()));
+        assert!(ans.bounds_at(1.5).is_none());
+
+        // pendulum from a box of initial angles, checked against sampled RK4
+        // trajectories
+        let pendulum = |_t: Interval, y: &[Interval]| vec![y[1], -y[0].sin()];
+        let y_0 = [Interval::new(0.99, 1.01), Interval::point(0.0)];
+        let opts = ValidatedOptions {
+            step: Some(0.001),
+            ..ValidatedOptions::default()
+        };
+        let ans = validated_integrate(pendulum, 0.0, &y_0, 2.0, opts).unwrap();
+        assert!(ans.final_width() < 0.2);
+        for theta in [0.99_f64, 1.0, 1.01].iter() {
+            let (mut a, mut w) = (*theta, 0.0_f64);
+            let h = 1e-4;
+            for _ in 0..20_000 {
+                let k1 = (w, -a.sin());
+                let k2 = (w + 0.5 * h * k1.1, -(a + 0.5 * h * k1.0).sin());
+                let k3 = (w + 0.5 * h * k2.1, -(a + 0.5 * h * k2.0).sin());
+                let k4 = (w + h * k3.1, -(a + h * k3.0).sin());
+                a += h / 6.0 * (k1.0 + 2.0 * k2.0 + 2.0 * k3.0 + k4.0);
+                w += h / 6.0 * (k1.1 + 2.0 * k2.1 + 2.0 * k3.1 + k4.1);
+            }
+            let y = ans.states.last().unwrap();
+            assert!(y[0].contains(a) && y[1].contains(w));
+        }
+
+        // y' = y^2 from 1 blows up at t = 1
+        let blowup = |_t: Interval, y: &[Interval]| vec![y[0].sqr()];
+        let ans = validated_integrate(
+            blowup,
+            0.0,
+            &[Interval::point(1.0)],
+            2.0,
+            ValidatedOptions::default(),
+        );
+        assert!(ans.is_err());
+    }
+}
@@ -0,0 +1,50 @@
+/// ridc: integrates a problem described in a TOML or JSON file
+///
+/// Usage: ridc <problem.toml | problem.json>
+///
+/// Writes the solution as CSV to the `path` of the `[output]` section, or to the
+/// standard output. See `cli` for the layout of the problem files.
+///
+// === Begin Imports ===
+// third party imports
+use integration_station::cli::{run, Problem};
+
+// Standard library imports
+use std::env;
+use std::fs;
+use std::process;
+
+// === End Imports ===
+
+fn main() {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("Usage: ridc <problem.toml | problem.json>");
+            process::exit(2);
+        }
+    };
+    if let Err(e) = ridc(&path) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+fn ridc(path: &str) -> Result<(), String> {
+    let text =
+        fs::read_to_string(path).map_err(|e| format!("[CLI] Cannot read {}: {}", path, e))?;
+    let problem = match path.ends_with(".json") {
+        true => Problem::from_json(&text),
+        false => Problem::from_toml(&text),
+    }?;
+    let out = run(&problem).map_err(|e| e.to_string())?;
+    match &problem.output {
+        Some(output) => {
+            fs::write(output, out).map_err(|e| format!("[CLI] Cannot write {}: {}", output, e))
+        }
+        None => {
+            print!("{}", out);
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,597 @@
+/// Command Line Problems (cli)
+///
+/// Problem descriptions for the `ridc` binary (built with the `cli` feature), for
+/// quick experiments and for reproducing bug reports without writing a program. A
+/// problem is read from TOML (or from JSON with the same layout, for files ending
+/// in `.json`), integrated, and its solution written as CSV, one row per output
+/// step with the columns `t, y0, y1, ...`:
+///
+/// [system]
+/// name = "van_der_pol"   # built-in system
+/// mu = 5.0               # parameters of the system, defaults otherwise
+/// y0 = [2.0, 0.0]        # initial state, the system's default otherwise
+///
+/// [integrator]
+/// method = "ridc_rk4"    # stepper, prefixed with ridc_ for the RIDC integrators
+/// t0 = 0.0               # initial time (default 0)
+/// t_end = 10.0           # final time
+/// dt = 0.01              # step of the fixed step methods
+/// atol = 1e-8            # tolerances of the adaptive methods
+/// rtol = 1e-8
+/// corrector_order = 3    # correction levels of the RIDC integrators
+///
+/// [output]
+/// path = "vdp.csv"       # standard output when absent
+/// every = 10             # keeps every n-th step (and the last)
+///
+/// Instead of a `name`, the system can be given as equations (see `systems::expr`),
+/// with the other keys of the section as its parameters and `y0` required:
+///
+/// [system]
+/// rhs = "dy0 = y1; dy1 = -sin(y0) - c * y1"
+/// c = 0.1
+/// y0 = [1.0, 0.0]
+///
+/// Built-in systems, their parameters and defaults:
+/// - `van_der_pol`: mu = 1, y0 = (2, 0)
+/// - `robertson`: y0 = (1, 0, 0)
+/// - `hires`: y0 from the test set
+/// - `cr3bp`: mu of the Arenstorf orbit, y0 its initial state
+/// - `two_body_j2`: mu, j2, r_eq of the Earth, no default y0
+///
+/// Fixed step methods are `rk2`, `heun` and `rk4`, adaptive ones `rk32`, `rkf45`,
+/// `cash_karp45` and `dopri78`.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::{DefaultAllocator, OVector, U1, U10, U11, U12, U2, U3, U4, U5, U6, U7, U8, U9};
+use serde_json::{Map, Value};
+
+// local imports
+use crate::ridc::base::{RIDCIntegratorAdaptive, RIDCIntegratorFixed};
+use crate::ridc::common::IntegOptionsParallel;
+use crate::runge_kutta::adaptive::AdaptiveStep;
+use crate::runge_kutta::common::{IntegError, IntegOptions, IntegResult};
+use crate::runge_kutta::fixed::FixedStep;
+use crate::runge_kutta::rk_embed::{CASH_KARP45, DOPRI78, RK32, RKF45};
+use crate::runge_kutta::rk_simp::{HEUN, RK2, RK4};
+use crate::systems::astro::{Cr3bp, TwoBodyJ2, ARENSTORF_INIT};
+use crate::systems::expr::ExprSystem;
+use crate::systems::stiff::{
+    Hires, Robertson, VanDerPol, HIRES_INIT, ROBERTSON_INIT, VAN_DER_POL_INIT,
+};
+use crate::systems::OdeSystem;
+use crate::utils::solver_dim::{SolverAllocator, SolverDim};
+
+// Standard library imports
+use std::fmt;
+use std::fmt::Write;
+
+// === End Imports ===
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    // Name of the built-in system
+    pub system: String,
+    // Equations of the system, instead of a built-in one
+    pub rhs: Option<String>,
+    // Parameters of the system overriding its defaults
+    pub params: Vec<(String, f64)>,
+    // Initial state, the default of the system when None
+    pub y_0: Option<Vec<f64>>,
+    // Initial time
+    pub t_0: f64,
+    // Final time
+    pub t_end: f64,
+    // Name of the stepper
+    pub method: String,
+    // Step of the fixed step methods
+    pub dt: Option<f64>,
+    // Absolute tolerance of the adaptive methods
+    pub atol: Option<f64>,
+    // Relative tolerance of the adaptive methods
+    pub rtol: Option<f64>,
+    // Correction levels of the RIDC integrators
+    pub corrector_order: Option<usize>,
+    // CSV file to write, standard output when None
+    pub output: Option<String>,
+    // Every how many steps a row is written
+    pub every: usize,
+}
+
+// Error of a problem. Mistakes in the system name what was wrong, as do failures
+// of the integration (see `IntegError`)
+#[derive(Debug, Clone, PartialEq)]
+pub enum CliError {
+    // No built-in system has this name
+    UnknownSystem(String),
+    // The system has no parameter `key`
+    UnknownParam {
+        system: String,
+        key: String,
+    },
+    // `y0` has `given` states where the system has `states`
+    Dimension {
+        system: String,
+        states: usize,
+        given: usize,
+    },
+    // The integration failed
+    Integ(IntegError),
+    // Any other mistake in the problem
+    Invalid(&'static str),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CliError::UnknownSystem(name) => write!(f, "[CLI] Unknown system `{}`", name),
+            CliError::UnknownParam { system, key } => {
+                write!(f, "[CLI] System `{}` has no parameter `{}`", system, key)
+            }
+            CliError::Dimension {
+                system,
+                states,
+                given,
+            } => write!(
+                f,
+                "[CLI] System `{}` has {} states, `y0` has {}",
+                system, states, given
+            ),
+            CliError::Integ(err) => err.fmt(f),
+            CliError::Invalid(err) => f.write_str(err),
+        }
+    }
+}
+
+impl From<&'static str> for CliError {
+    fn from(err: &'static str) -> Self {
+        CliError::Invalid(err)
+    }
+}
+
+impl From<IntegError> for CliError {
+    fn from(err: IntegError) -> Self {
+        CliError::Integ(err)
+    }
+}
+
+impl Problem {
+    pub fn from_toml(text: &str) -> Result<Self, &'static str> {
+        let table: toml::Table = text.parse().map_err(|_| "[CLI] Invalid TOML")?;
+        let value = serde_json::to_value(table).map_err(|_| "[CLI] Invalid TOML")?;
+        Problem::from_value(&value)
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, &'static str> {
+        let value: Value = serde_json::from_str(text).map_err(|_| "[CLI] Invalid JSON")?;
+        Problem::from_value(&value)
+    }
+
+    fn from_value(value: &Value) -> Result<Self, &'static str> {
+        let empty = Map::new();
+        let root = value
+            .as_object()
+            .ok_or("[CLI] A problem must be a table of sections")?;
+        let section = |name: &str| match root.get(name) {
+            None => Ok(&empty),
+            Some(Value::Object(table)) => Ok(table),
+            Some(_) => Err("[CLI] Sections must be tables"),
+        };
+        let (system, integrator, output) = (
+            section("system")?,
+            section("integrator")?,
+            section("output")?,
+        );
+
+        let mut params = Vec::new();
+        let mut y_0 = None;
+        for (key, val) in system.iter() {
+            match key.as_str() {
+                "name" | "rhs" => (),
+                "y0" => {
+                    let state = val.as_array().ok_or("[CLI] `y0` must be an array")?;
+                    y_0 = Some(
+                        state
+                            .iter()
+                            .map(|y| y.as_f64().ok_or("[CLI] `y0` must hold numbers"))
+                            .collect::<Result<Vec<_>, _>>()?,
+                    );
+                }
+                _ => params.push((key.clone(), as_number(val)?)),
+            }
+        }
+
+        let rhs = text(system, "rhs")?;
+        let name = match (text(system, "name")?, &rhs) {
+            (Some(name), None) => name,
+            (None, Some(_)) => String::from("rhs"),
+            _ => return Err("[CLI] The system needs either a `name` or an `rhs`"),
+        };
+        Ok(Problem {
+            system: name,
+            rhs,
+            params,
+            y_0,
+            t_0: number(integrator, "t0")?.unwrap_or(0.0),
+            t_end: number(integrator, "t_end")?.ok_or("[CLI] The integrator needs a `t_end`")?,
+            method: text(integrator, "method")?.ok_or("[CLI] The integrator needs a `method`")?,
+            dt: number(integrator, "dt")?,
+            atol: number(integrator, "atol")?,
+            rtol: number(integrator, "rtol")?,
+            corrector_order: count(integrator, "corrector_order")?,
+            output: text(output, "path")?,
+            every: count(output, "every")?.unwrap_or(1).max(1),
+        })
+    }
+
+    // Value of a parameter, the default when the problem does not set it
+    fn param(&self, name: &str, default: f64) -> f64 {
+        self.params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map_or(default, |(_, val)| *val)
+    }
+
+    // Errors on parameters the system does not have
+    fn check_params(&self, known: &[&str]) -> Result<(), CliError> {
+        match self
+            .params
+            .iter()
+            .find(|(key, _)| !known.contains(&key.as_str()))
+        {
+            Some((key, _)) => Err(CliError::UnknownParam {
+                system: self.system.clone(),
+                key: key.clone(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    // Initial state of the problem, or the default of the system
+    fn initial<N: SolverDim>(
+        &self,
+        default: Option<&OVector<f64, N>>,
+    ) -> Result<OVector<f64, N>, CliError>
+    where
+        DefaultAllocator: SolverAllocator<N>,
+    {
+        match (&self.y_0, default) {
+            (Some(y_0), _) if y_0.len() == N::dim() => {
+                Ok(OVector::<f64, N>::from_column_slice(y_0))
+            }
+            (Some(y_0), _) => Err(CliError::Dimension {
+                system: self.system.clone(),
+                states: N::dim(),
+                given: y_0.len(),
+            }),
+            (None, Some(y_0)) => Ok(y_0.clone()),
+            (None, None) => Err("[CLI] The system needs a `y0`".into()),
+        }
+    }
+}
+
+fn as_number(val: &Value) -> Result<f64, &'static str> {
+    val.as_f64().ok_or("[CLI] Expected a number")
+}
+
+fn number(table: &Map<String, Value>, key: &str) -> Result<Option<f64>, &'static str> {
+    table.get(key).map(as_number).transpose()
+}
+
+fn count(table: &Map<String, Value>, key: &str) -> Result<Option<usize>, &'static str> {
+    table
+        .get(key)
+        .map(|val| {
+            val.as_u64()
+                .map(|n| n as usize)
+                .ok_or("[CLI] Expected a non-negative integer")
+        })
+        .transpose()
+}
+
+fn text(table: &Map<String, Value>, key: &str) -> Result<Option<String>, &'static str> {
+    table
+        .get(key)
+        .map(|val| {
+            val.as_str()
+                .map(String::from)
+                .ok_or("[CLI] Expected a string")
+        })
+        .transpose()
+}
+
+// Integrates the problem and returns its solution as CSV
+pub fn run(problem: &Problem) -> Result<String, CliError> {
+    if let Some(rhs) = &problem.rhs {
+        return run_expr(problem, rhs);
+    }
+    match problem.system.as_str() {
+        "van_der_pol" => {
+            problem.check_params(&["mu"])?;
+            let system = VanDerPol::new(problem.param("mu", 1.0));
+            solve(problem, system, Some(&*VAN_DER_POL_INIT))
+        }
+        "robertson" => {
+            problem.check_params(&[])?;
+            solve(problem, Robertson, Some(&*ROBERTSON_INIT))
+        }
+        "hires" => {
+            problem.check_params(&[])?;
+            solve(problem, Hires, Some(&*HIRES_INIT))
+        }
+        "cr3bp" => {
+            problem.check_params(&["mu"])?;
+            let system = Cr3bp::new(problem.param("mu", Cr3bp::ARENSTORF.mu));
+            solve(problem, system, Some(&*ARENSTORF_INIT))
+        }
+        "two_body_j2" => {
+            problem.check_params(&["mu", "j2", "r_eq"])?;
+            let earth = TwoBodyJ2::EARTH;
+            let system = TwoBodyJ2::new(
+                problem.param("mu", earth.mu),
+                problem.param("j2", earth.j2),
+                problem.param("r_eq", earth.r_eq),
+            );
+            solve(problem, system, None)
+        }
+        name => Err(CliError::UnknownSystem(name.to_string())),
+    }
+}
+
+// Integrates a system given as equations, of as many states as `y0`
+fn run_expr(problem: &Problem, rhs: &str) -> Result<String, CliError> {
+    let params: Vec<(&str, f64)> = problem
+        .params
+        .iter()
+        .map(|(name, val)| (name.as_str(), *val))
+        .collect();
+    let dim = problem
+        .y_0
+        .as_ref()
+        .ok_or("[CLI] Systems given as equations need a `y0`")?
+        .len();
+    match dim {
+        1 => solve(problem, ExprSystem::<U1>::parse(rhs, &params)?, None),
+        2 => solve(problem, ExprSystem::<U2>::parse(rhs, &params)?, None),
+        3 => solve(problem, ExprSystem::<U3>::parse(rhs, &params)?, None),
+        4 => solve(problem, ExprSystem::<U4>::parse(rhs, &params)?, None),
+        5 => solve(problem, ExprSystem::<U5>::parse(rhs, &params)?, None),
+        6 => solve(problem, ExprSystem::<U6>::parse(rhs, &params)?, None),
+        7 => solve(problem, ExprSystem::<U7>::parse(rhs, &params)?, None),
+        8 => solve(problem, ExprSystem::<U8>::parse(rhs, &params)?, None),
+        9 => solve(problem, ExprSystem::<U9>::parse(rhs, &params)?, None),
+        10 => solve(problem, ExprSystem::<U10>::parse(rhs, &params)?, None),
+        11 => solve(problem, ExprSystem::<U11>::parse(rhs, &params)?, None),
+        12 => solve(problem, ExprSystem::<U12>::parse(rhs, &params)?, None),
+        _ => Err("[CLI] Systems given as equations have 1 to 12 states".into()),
+    }
+}
+
+// Integrates a system with the stepper of the problem and writes the solution
+fn solve<N: SolverDim, S: OdeSystem<N> + Clone + Send + 'static>(
+    problem: &Problem,
+    system: S,
+    default: Option<&OVector<f64, N>>,
+) -> Result<String, CliError>
+where
+    DefaultAllocator: SolverAllocator<N>,
+{
+    let y_0 = problem.initial(default)?;
+    let (ridc, name) = match problem.method.strip_prefix("ridc_") {
+        Some(name) => (true, name),
+        None => (false, problem.method.as_str()),
+    };
+    let ans = match name {
+        "rk2" => fixed(&*RK2, ridc, problem, system, y_0),
+        "heun" => fixed(&*HEUN, ridc, problem, system, y_0),
+        "rk4" => fixed(&*RK4, ridc, problem, system, y_0),
+        "rk32" => adaptive(&*RK32, ridc, problem, system, y_0),
+        "rkf45" => adaptive(&*RKF45, ridc, problem, system, y_0),
+        "cash_karp45" => adaptive(&*CASH_KARP45, ridc, problem, system, y_0),
+        "dopri78" => adaptive(&*DOPRI78, ridc, problem, system, y_0),
+        _ => Err("[CLI] Unknown method".into()),
+    }?;
+    Ok(csv(&ans, problem.every))
+}
+
+fn fixed<
+    N: SolverDim,
+    S: OdeSystem<N> + Clone + Send + 'static,
+    P: FixedStep + RIDCIntegratorFixed,
+>(
+    stepper: &P,
+    ridc: bool,
+    problem: &Problem,
+    system: S,
+    y_0: OVector<f64, N>,
+) -> Result<IntegResult<N>, IntegError>
+where
+    DefaultAllocator: SolverAllocator<N>,
+{
+    let dt = problem.dt.ok_or("[CLI] Fixed step methods need a `dt`")?;
+    let span = problem.t_end - problem.t_0;
+    match ridc {
+        true => {
+            let options = IntegOptionsParallel {
+                corrector_order: problem.corrector_order,
+                ..IntegOptionsParallel::default()
+            };
+            stepper.parallel_integrator(system, problem.t_0, &y_0, span, dt, options)
+        }
+        false => FixedStep::integrate(
+            stepper,
+            system,
+            problem.t_0,
+            y_0,
+            span,
+            dt,
+            IntegOptions::default(),
+        ),
+    }
+}
+
+fn adaptive<
+    N: SolverDim,
+    S: OdeSystem<N> + Clone + Send + 'static,
+    P: AdaptiveStep + RIDCIntegratorAdaptive,
+>(
+    stepper: &P,
+    ridc: bool,
+    problem: &Problem,
+    system: S,
+    y_0: OVector<f64, N>,
+) -> Result<IntegResult<N>, IntegError>
+where
+    DefaultAllocator: SolverAllocator<N>,
+{
+    let atol = problem.atol.map(OVector::<f64, N>::repeat);
+    let span = problem.t_end - problem.t_0;
+    match ridc {
+        true => {
+            let options = IntegOptionsParallel {
+                atol,
+                rtol: problem.rtol,
+                corrector_order: problem.corrector_order,
+                ..IntegOptionsParallel::default()
+            };
+            stepper.parallel_integrator(system, problem.t_0, &y_0, span, options)
+        }
+        false => {
+            let options = IntegOptions {
+                atol,
+                rtol: problem.rtol,
+                ..IntegOptions::default()
+            };
+            AdaptiveStep::integrate(stepper, system, problem.t_0, y_0, span, options)
+        }
+    }
+}
+
+// Solution as CSV with a header row, keeping every n-th step and the last
+pub fn csv<N: SolverDim>(results: &IntegResult<N>, every: usize) -> String
+where
+    DefaultAllocator: SolverAllocator<N>,
+{
+    let mut out = String::from("t");
+    for i in 0..N::dim() {
+        let _ = write!(out, ",y{}", i);
+    }
+    out.push('\n');
+    let last = results.times.len().saturating_sub(1);
+    for (k, (t, y)) in results.times.iter().zip(&results.states).enumerate() {
+        if k % every.max(1) != 0 && k != last {
+            continue;
+        }
+        let _ = write!(out, "{:e}", t);
+        for val in y.iter() {
+            let _ = write!(out, ",{:e}", val);
+        }
+        out.push('\n');
+    }
+    out
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use na::Vector2;
+
+    #[test]
+    fn test_cli_problem() {
+        let config = r#"
+            [system]
+            name = "van_der_pol"
+            mu = 0.5
+            y0 = [1.0, 0]
+
+            [integrator]
+            method = "dopri78"
+            t_end = 2
+            atol = 1e-10
+            rtol = 1e-10
+
+            [output]
+            every = 3
+        "#;
+        let problem = Problem::from_toml(config).unwrap();
+        assert_eq!(problem.params, vec![(String::from("mu"), 0.5)]);
+        assert_eq!(problem.t_0, 0.0);
+        assert!(problem.output.is_none());
+
+        // the last row is the solution of the integration at t_end
+        let out = run(&problem).unwrap();
+        let rows: Vec<&str> = out.lines().collect();
+        assert_eq!(rows[0], "t,y0,y1");
+        let last: Vec<f64> = rows[rows.len() - 1]
+            .split(',')
+            .map(|val| val.parse().unwrap())
+            .collect();
+        let options = IntegOptions {
+            atol: Some(Vector2::repeat(1e-10)),
+            rtol: Some(1e-10),
+            ..IntegOptions::default()
+        };
+        let ans = DOPRI78
+            .integrate(
+                VanDerPol::new(0.5),
+                0.0,
+                Vector2::new(1.0, 0.0),
+                2.0,
+                options,
+            )
+            .unwrap();
+        assert_eq!(last, vec![2.0, ans.last_y()[0], ans.last_y()[1]]);
+        assert_eq!(rows.len(), 2 + (ans.times.len() - 1).div_ceil(3));
+
+        // the same problem as JSON, through a RIDC integrator
+        let json = r#"{
+            "system": {"name": "van_der_pol", "mu": 0.5, "y0": [1.0, 0.0]},
+            "integrator": {"method": "ridc_rk4", "t_end": 2.0, "dt": 0.01, "corrector_order": 3}
+        }"#;
+        let out = run(&Problem::from_json(json).unwrap()).unwrap();
+        let last: Vec<f64> = out
+            .lines()
+            .last()
+            .unwrap()
+            .split(',')
+            .map(|val| val.parse().unwrap())
+            .collect();
+        assert!((last[1] - ans.last_y()[0]).abs() < 1e-8);
+
+        // mistakes are reported
+        let broken =
+            |edit: (&str, &str)| run(&Problem::from_toml(&config.replace(edit.0, edit.1))?);
+        assert_eq!(
+            broken(("mu = 0.5", "nu = 0.5")).unwrap_err().to_string(),
+            "[CLI] System `van_der_pol` has no parameter `nu`"
+        );
+        assert_eq!(
+            broken(("[1.0, 0]", "[1.0]")).unwrap_err().to_string(),
+            "[CLI] System `van_der_pol` has 2 states, `y0` has 1"
+        );
+        assert_eq!(
+            broken(("dopri78", "rk4")).unwrap_err().to_string(),
+            "[CLI] Fixed step methods need a `dt`"
+        );
+        assert_eq!(
+            broken(("\"van_der_pol\"", "\"lorenz\"")).unwrap_err(),
+            CliError::UnknownSystem(String::from("lorenz"))
+        );
+
+        // the same system as equations
+        let rhs = "rhs = \"dy0 = y1; dy1 = mu * ((1 - y0^2) * y1 - y0)\"";
+        let out = run(&Problem::from_toml(&config.replace("name = \"van_der_pol\"", rhs)).unwrap())
+            .unwrap();
+        let last = out.lines().last().unwrap().split(',').nth(1).unwrap();
+        assert!((last.parse::<f64>().unwrap() - ans.last_y()[0]).abs() < 1e-9);
+        assert_eq!(
+            broken(("name = \"van_der_pol\"", "")).unwrap_err(),
+            CliError::Invalid("[CLI] The system needs either a `name` or an `rhs`")
+        );
+        assert!(broken(("t_end = 2", "")).is_err());
+        assert!(Problem::from_toml("[system").is_err());
+    }
+}
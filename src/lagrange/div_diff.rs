@@ -72,7 +72,7 @@
 // third party imports
 extern crate nalgebra as na;
 use na::allocator::Allocator;
-use na::{DefaultAllocator, Dim, DimName, VectorN};
+use na::{DefaultAllocator, Dim, DimName, OVector};
 
 // standard library
 use std::collections::VecDeque;
@@ -83,15 +83,15 @@ use std::collections::VecDeque;
 /// This is a vector function where all weights are functions
 ///
 pub fn divided_diff<N: Dim + DimName>(
-    points: &VecDeque<VectorN<f64, N>>,
+    points: &VecDeque<OVector<f64, N>>,
     times: &VecDeque<f64>,
-) -> Vec<VectorN<f64, N>>
+) -> Vec<OVector<f64, N>>
 where
-    DefaultAllocator: Allocator<f64, N>,
+    DefaultAllocator: Allocator<N>,
 {
     let n = times.len();
     // initialize empty finite difference table
-    let mut diffs = vec![vec![VectorN::<f64, N>::zeros(); n]; n];
+    let mut diffs = vec![vec![OVector::<f64, N>::zeros(); n]; n];
     // insert first column
     for i in 0..n {
         diffs[i][0] = points[i].clone();
@@ -111,16 +111,16 @@ where
 /// timestep
 ///
 pub fn update_diff<N: Dim + DimName>(
-    old_diffs: Vec<VectorN<f64, N>>,
+    old_diffs: Vec<OVector<f64, N>>,
     times: &VecDeque<f64>,
-    nxt_point: &VectorN<f64, N>,
+    nxt_point: &OVector<f64, N>,
     nxt_time: f64,
-) -> Vec<VectorN<f64, N>>
+) -> Vec<OVector<f64, N>>
 where
-    DefaultAllocator: Allocator<f64, N>,
+    DefaultAllocator: Allocator<N>,
 {
     let n = old_diffs.len();
-    let mut new_diffs = vec![VectorN::<f64, N>::zeros(); n];
+    let mut new_diffs = vec![OVector::<f64, N>::zeros(); n];
     new_diffs[0] = nxt_point.clone();
 
     for i in 1..n {
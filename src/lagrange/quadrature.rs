@@ -9,11 +9,14 @@
 /// Currently only weights up to order 4 are provided. Higher order
 /// templates can be derived using a symbolic solver such as wolfram alpha
 ///
+/// Gauss nodes (legendre and probabilists' hermite) are found as the eigenvalues
+/// of the jacobi matrix of their three term recurrence (Golub & Welsch 1969)
+///
 // === Begin Imports ===
 // third party imports
 extern crate nalgebra as na;
 use na::allocator::Allocator;
-use na::{DefaultAllocator, Dim, DimName, VectorN};
+use na::{DMatrix, DefaultAllocator, Dim, DimName, OVector};
 
 // standard library
 use std::collections::VecDeque;
@@ -106,14 +109,85 @@ pub fn specific_weights(x_pows: Vec<f64>, weights: &Vec<Vec<f64>>) -> Vec<f64> {
         .collect()
 }
 
+// Weights for the integral from x_0 to x of the Lagrange polynomial through any
+// number of stencil `times`. The stencil is shifted so x_0 is the origin, otherwise
+// the powers of large absolute times cancel and the weights lose most of their
+// precision late in an integration
+pub fn interval_weights(times: &VecDeque<f64>, x_0: f64, x: f64) -> Vec<f64> {
+    let shifted: Vec<f64> = times.iter().map(|t| t - x_0).collect();
+    let h = x - x_0;
+    (0..shifted.len())
+        .map(|i| {
+            // coefficients (ascending powers) of prod_{j != i} (t - t_j) / (t_i - t_j)
+            let mut coeffs = vec![1.0];
+            for (j, t_j) in shifted.iter().enumerate() {
+                if j == i {
+                    continue;
+                }
+                let denom = shifted[i] - t_j;
+                let mut next = vec![0.0; coeffs.len() + 1];
+                for (k, c) in coeffs.iter().enumerate() {
+                    next[k + 1] += c / denom;
+                    next[k] -= c * t_j / denom;
+                }
+                coeffs = next;
+            }
+            coeffs
+                .iter()
+                .enumerate()
+                .map(|(k, c)| c * h.powi(k as i32 + 1) / (k as f64 + 1.0))
+                .sum()
+        })
+        .collect()
+}
+
+// ------------------------------- Gauss Nodes -----------------------------------------
+// Nodes and weights of the n point gauss-legendre rule for the uniform probability
+// density on [-1, 1] (weights sum to one)
+pub fn gauss_legendre(n: usize) -> (Vec<f64>, Vec<f64>) {
+    let (nodes, _) = golub_welsch(n, |k| k / (4.0 * k * k - 1.0).sqrt());
+    let stencil: VecDeque<f64> = nodes.iter().cloned().collect();
+    let weights = interval_weights(&stencil, -1.0, 1.0)
+        .iter()
+        .map(|w| 0.5 * w)
+        .collect();
+    (nodes, weights)
+}
+
+// Nodes and weights of the n point gauss-hermite rule for the standard normal
+// probability density (weights sum to one)
+pub fn gauss_hermite(n: usize) -> (Vec<f64>, Vec<f64>) {
+    golub_welsch(n, f64::sqrt)
+}
+
+// Eigen decomposition of the symmetric tridiagonal jacobi matrix with zero diagonal
+// and off diagonal entries off_diag(k), k = 1..n-1. The nodes are its eigenvalues
+// and the weights the squared first components of its (unit) eigenvectors
+fn golub_welsch<F: Fn(f64) -> f64>(n: usize, off_diag: F) -> (Vec<f64>, Vec<f64>) {
+    let mut jacobi = DMatrix::<f64>::zeros(n, n);
+    for k in 1..n {
+        let beta = off_diag(k as f64);
+        jacobi[(k - 1, k)] = beta;
+        jacobi[(k, k - 1)] = beta;
+    }
+    let eigen = jacobi.symmetric_eigen();
+    let mut pairs: Vec<(f64, f64)> = (0..n)
+        .map(|i| (eigen.eigenvalues[i], eigen.eigenvectors[(0, i)].powi(2)))
+        .collect();
+    pairs.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    pairs.into_iter().unzip()
+}
+
+// -------------------------------------------------------------------------------------
+
 pub fn lagrange_quad_third_order<N: Dim + DimName>(
     x_0: f64,
     x: f64,
     weights: &Vec<Vec<f64>>,
-    vals: &VecDeque<VectorN<f64, N>>,
-) -> VectorN<f64, N>
+    vals: &VecDeque<OVector<f64, N>>,
+) -> OVector<f64, N>
 where
-    DefaultAllocator: Allocator<f64, N>,
+    DefaultAllocator: Allocator<N>,
 {
     // Compute the specific weights for the integral bounds
     let x_pows = get_x_pow(x_0, x, 3);
@@ -131,10 +205,10 @@ pub fn lagrange_quad_fourth_order<N: Dim + DimName>(
     x_0: f64,
     x: f64,
     times: &VecDeque<f64>,
-    div_diffs: &Vec<VectorN<f64, N>>,
-) -> VectorN<f64, N>
+    div_diffs: &Vec<OVector<f64, N>>,
+) -> OVector<f64, N>
 where
-    DefaultAllocator: Allocator<f64, N>,
+    DefaultAllocator: Allocator<N>,
 {
     let x_pows = get_x_pow(x_0, x, 4);
     let weights = vec![
@@ -321,4 +395,55 @@ mod tests {
         const TOL: f64 = 1.0e-4;
         assert!((true_cubic_area[0] - est_cubic_area[0]).abs() < TOL);
     }
+
+    #[test]
+    fn test_interval_weights() {
+        // matches the classic third order stencil
+        let times = VecDeque::from(vec![5.0, 4.0, 2.0, 1.0]);
+        let classic = specific_weights(get_x_pow(2.0, 4.5, 3), &get_weights(&times));
+        let shifted = interval_weights(&times, 2.0, 4.5);
+        for (a, b) in classic.iter().zip(shifted.iter()) {
+            assert!((a - b).abs() < 1e-12);
+        }
+
+        // smaller stencils integrate polynomials of their degree exactly, even far
+        // from the origin
+        let poly = |t: f64| 3.0 * (t - 1.0e4) - 2.0;
+        let times = VecDeque::from(vec![1.0e4 + 0.2, 1.0e4 + 0.1]);
+        let quad: f64 = interval_weights(&times, 1.0e4 + 0.1, 1.0e4 + 0.2)
+            .iter()
+            .zip(times.iter())
+            .map(|(w, t)| w * poly(*t))
+            .sum();
+        assert!((quad - (1.5 * (0.04 - 0.01) - 0.2)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_gauss_nodes() {
+        // n point rules integrate polynomials up to degree 2n - 1 exactly
+        let (nodes, weights) = gauss_legendre(4);
+        let moment = |k: i32| -> f64 {
+            nodes
+                .iter()
+                .zip(weights.iter())
+                .map(|(x, w)| w * x.powi(k))
+                .sum()
+        };
+        assert!((moment(0) - 1.0).abs() < 1e-14);
+        assert!((moment(6) - 1.0 / 7.0).abs() < 1e-14);
+        assert!((nodes[3] - 0.861_136_311_594_052_6).abs() < 1e-14);
+
+        // moments of the standard normal: 1, 3, 15 for degrees 2, 4, 6
+        let (nodes, weights) = gauss_hermite(4);
+        let moment = |k: i32| -> f64 {
+            nodes
+                .iter()
+                .zip(weights.iter())
+                .map(|(x, w)| w * x.powi(k))
+                .sum()
+        };
+        assert!((moment(0) - 1.0).abs() < 1e-14);
+        assert!(moment(3).abs() < 1e-14);
+        assert!((moment(6) - 15.0).abs() < 1e-12);
+    }
 }
@@ -3,7 +3,16 @@
 //! ### Full Examples
 //!
 //! Full examples, detailing and explaining usage of the basic functionality of the
-//! library, can be found in the [`examples`] directory.
+//! library, can be found in the [`examples`] directory. Each takes its tolerances
+//! and method as flags (`cargo run --release --example lorenz -- --help`):
+//! - `lorenz`: the Lorenz attractor with the fixed step, adaptive and RIDC methods
+//! - `robertson`: the stiff Robertson kinetics with the fixed cost integrator
+//! - `halo`: a halo orbit of the Earth-Moon system corrected by single shooting
+//! - `bouncing_ball`: a hybrid system with guards, resets and events
+//! - `ensemble`: a Monte Carlo ensemble over uncertain parameters on threads
+//!
+//! With the `plotters` feature, the examples taking a `--plot` path draw the
+//! solution there.
 //!
 //! # Installation
 //!
@@ -12,10 +21,19 @@
 #[macro_use]
 extern crate lazy_static;
 
+// The nalgebra version the crate is built against. Vectors and matrices passed to
+// the integrators are of this version's types
+pub use nalgebra;
+
 pub mod adams;
+pub mod analysis;
+#[cfg(feature = "cli")]
+pub mod cli;
 pub mod lagrange;
+pub mod prelude;
 pub mod ridc;
 pub mod runge_kutta;
+pub mod systems;
 pub mod test_fxns;
 pub mod test_scripts;
 pub mod utils;
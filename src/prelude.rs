@@ -0,0 +1,76 @@
+/// Prelude (prelude)
+///
+/// Everything needed to set up and solve a typical problem with a single import
+/// (`use integration_station::prelude::*`). Brings in the pre-built steppers and
+/// the integrator traits providing their `integrate` / `parallel_integrator`
+/// methods, the option and result structs, the root finders and euler steps, and
+/// the nalgebra types (vectors, matrices and dimension names) the crate's
+/// signatures use. The nalgebra types are re-exported
+/// from the version the crate is built against, so user code does not have to
+/// depend on that exact nalgebra version or know its import paths.
+///
+// === Begin Imports ===
+// third party imports
+pub use nalgebra::allocator::Allocator;
+pub use nalgebra::{
+    DMatrix, DVector, DefaultAllocator, Dim, DimName, Dyn, Matrix1, Matrix2, Matrix3, Matrix4,
+    Matrix5, Matrix6, OMatrix, OVector, SMatrix, SVector, Vector1, Vector2, Vector3, Vector4,
+    Vector5, Vector6, U1, U2, U3, U4, U5, U6,
+};
+
+// local imports
+pub use crate::ridc::base::{RIDCIntegratorAdaptive, RIDCIntegratorFixed};
+pub use crate::ridc::common::{IntegOptionsParallel, Schedule};
+pub use crate::ridc::corrector::{Corrector, ExplicitEuler, ImplicitEuler, Picard};
+pub use crate::ridc::predictor::Predictor;
+pub use crate::runge_kutta::adaptive::AdaptiveStep;
+pub use crate::runge_kutta::base::RKStepper;
+pub use crate::runge_kutta::common::{
+    AdaptiveError, IntegError, IntegOptions, IntegResult, StepResult, StepSimple,
+};
+pub use crate::runge_kutta::doubling::StepDoubling;
+pub use crate::runge_kutta::embedded::EmbeddedRKStepper;
+pub use crate::runge_kutta::fixed::FixedStep;
+pub use crate::runge_kutta::rk_embed::{CASH_KARP45, DOPRI78, RK32, RKF45};
+pub use crate::runge_kutta::rk_simp::{HEUN, RK2, RK4};
+pub use crate::runge_kutta::stopping::StopCondition;
+pub use crate::runge_kutta::tableaus::{EmbeddedTableau, Tableau};
+pub use crate::systems::state::State;
+pub use crate::systems::OdeSystem;
+pub use crate::utils::euler::{bwd_euler, fwd_euler};
+pub use crate::utils::newton_raphson::{
+    newton_krylov, newton_raphson_banded, newton_raphson_broyden, newton_raphson_fdiff,
+    newton_raphson_linsrch, NewtonDiagnostics, NewtonError,
+};
+pub use crate::utils::solver_dim::{SolverAllocator, SolverDim};
+
+// === End Imports ===
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    #[test]
+    fn test_prelude() {
+        let fxn = |_t: f64, y: &Vector2<f64>| Vector2::new(y[1], -y[0]);
+        let y_0 = Vector2::new(1.0, 0.0);
+        let truth = Vector2::new(2.0_f64.cos(), -(2.0_f64.sin()));
+
+        let fixed = RK4
+            .integrate(fxn, 0.0, y_0, 2.0, 0.01, IntegOptions::default())
+            .unwrap();
+        let adaptive = RK32
+            .integrate(fxn, 0.0, y_0, 2.0, IntegOptions::default())
+            .unwrap();
+        let ridc = RK4
+            .parallel_integrator(fxn, 0.0, &y_0, 2.0, 0.01, IntegOptionsParallel::default())
+            .unwrap();
+        assert!((fixed.last_y() - truth).amax() < 1e-8);
+        assert!((adaptive.last_y() - truth).amax() < 1e-2);
+        assert!((ridc.last_y() - truth).amax() < 1e-8);
+
+        let root = newton_raphson_broyden(|x: &Vector1<f64>| x * 2.0, Vector1::new(1.0), 1e-10);
+        assert!(root.unwrap()[0].abs() < 1e-10);
+    }
+}
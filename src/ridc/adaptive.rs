@@ -9,172 +9,189 @@
 // third party imports
 extern crate nalgebra as na;
 use na::allocator::Allocator;
-use na::{DefaultAllocator, Dim, DimMin, DimName, DimSub, VectorN, U1};
+use na::{DefaultAllocator, Dim, DimName, OVector};
 
 // local imports
 use super::base::{RIDCIntegratorAdaptive, RIDCIntegratorBase};
-use super::common::{IVPSolData, IVPSolMsg, IntegOptionsParallel};
-use crate::lagrange::quadrature::{get_weights, get_x_pow, specific_weights};
+use super::common::{IVPSolData, IVPSolMsg, IntegOptionsParallel, LevelSetup};
+use super::predictor::Predictor;
+use super::slab::SlabControl;
 use crate::runge_kutta::adaptive::{AdaptiveStep, StepValid};
-use crate::runge_kutta::common::{IntegResult, StepResult, StepWithError};
+use crate::runge_kutta::common::{
+    approach_end, AdaptiveError, Breakpoints, IntegError, IntegResult, RejectedStep, StepBounds,
+};
+use crate::runge_kutta::domain::shrink_step;
+use crate::runge_kutta::doubling::StepDoubling;
 use crate::runge_kutta::embedded::EmbeddedRKStepper;
+use crate::systems::fallible::{check_failure, step_failed};
+use crate::systems::OdeSystem;
+use crate::utils::solver_dim::{SolverAllocator, SolverDim};
 
 // Standard library imports
-use std::collections::VecDeque;
 use std::marker::Send;
+use std::mem;
 
 // === End Imports ===
 
 impl<D: DimName + Dim> RIDCIntegratorBase for EmbeddedRKStepper<D> where
-    DefaultAllocator: Allocator<f64, D> + Allocator<f64, D, D>
+    DefaultAllocator: Allocator<D> + Allocator<D, D>
 {
 }
 
-impl<D: DimName + Dim> RIDCIntegratorAdaptive for EmbeddedRKStepper<D>
-where
-    DefaultAllocator: Allocator<f64, D> + Allocator<f64, D, D>,
-{
-    fn parallel_integrator<N: Dim + DimName + DimMin<N> + DimSub<U1>>(
+impl<P: Predictor> RIDCIntegratorBase for StepDoubling<P> {}
+
+impl<T: AdaptiveStep + RIDCIntegratorBase> RIDCIntegratorAdaptive for T {
+    fn parallel_integrator<N: SolverDim, S: OdeSystem<N> + Clone + Send + 'static>(
         &self,
-        fxn: fn(f64, &VectorN<f64, N>) -> VectorN<f64, N>,
+        fxn: S,
         t_0: f64,
-        y_0: &VectorN<f64, N>,
+        y_0: &OVector<f64, N>,
         step: f64,
         integ_opts: IntegOptionsParallel<N>,
-    ) -> Result<IntegResult<N>, &'static str>
+    ) -> Result<IntegResult<N>, IntegError>
     where
-        DefaultAllocator: Allocator<f64, N>
-            + Allocator<f64, U1, N>
-            + Allocator<f64, N, N>
-            + Allocator<f64, <N as DimMin<N>>::Output, N>
-            + Allocator<f64, <N as DimMin<N>>::Output>
-            + Allocator<f64, N, <N as DimMin<N>>::Output>
-            + Allocator<f64, <<N as DimMin<N>>::Output as DimSub<U1>>::Output>,
-        <N as DimMin<N>>::Output: DimName,
-        <N as DimMin<N>>::Output: DimSub<U1>,
-        <DefaultAllocator as Allocator<f64, N>>::Buffer: Send + Sync,
+        DefaultAllocator: SolverAllocator<N>,
     {
         // Unwrap Options to defaults
+        let first_dyn_eval = &fxn.dynamics(t_0, y_0);
+        check_failure(&fxn)?;
+        let setup = LevelSetup::new(&integ_opts, 1.0e-8_f64, t_0, y_0, first_dyn_eval)?;
+        let corrector_order = setup.corrector_order;
         let atol = integ_opts
             .atol
-            .unwrap_or(VectorN::<f64, N>::repeat(1e-9_f64));
+            .unwrap_or(OVector::<f64, N>::repeat(1e-9_f64));
         let rtol = integ_opts.rtol.unwrap_or(1e-6_f64);
         let min_step_size = integ_opts.min_step.unwrap_or(1e-10_f64);
-        let poly_order = integ_opts.poly_order.unwrap_or(3); // ONLY 3 is currently supported
-        let corrector_order = integ_opts.corrector_order.unwrap_or(1);
+        let record_rejections = integ_opts.record_rejections.unwrap_or(false);
         let restart_length = integ_opts.restart_length.unwrap_or(100);
-        let corr_conv_tol = integ_opts.convergence_tol.unwrap_or(1.0e-8_f64);
+        let diagnostics = integ_opts.diagnostics.unwrap_or_default();
+        let correction_tol = integ_opts.correction_tol;
+        let mut breakpoint_times = integ_opts.breakpoints.unwrap_or_default();
+        breakpoint_times.extend(fxn.breakpoints());
 
         // Initialize results struct and other integration variables
         let mut results = IntegResult::new(t_0, y_0.clone());
         let t_end = t_0 + step;
-        let mut sub_step = step;
-        let mut step_res: StepResult<N>;
-        let mut step_revision: StepValid;
-        let backward: bool = step < 0.0;
+        let mut breakpoints = Breakpoints::new(&breakpoint_times, t_0, t_end);
 
         // initialize vals
         let mut y_last = y_0.clone();
-        let first_dyn_eval = &fxn(t_0, y_0);
-        let mut counter = 1;
-
-        // spawn threads
-        let (root_tx, root_rx) = self.spawn_correctors(
-            corrector_order,
-            poly_order,
-            fxn,
+        let mut bounds = StepBounds::new(
+            min_step_size,
+            integ_opts.max_step,
+            integ_opts.max_rejections,
+        );
+        let mut sub_step = bounds.accept(match integ_opts.first_step {
+            Some(h) => h.abs().min(step.abs()).copysign(step),
+            None => self.initial_step(&fxn, t_0, y_0, first_dyn_eval, step, &atol, rtol),
+        });
+        check_failure(&fxn)?;
+        // number of correction levels applied in the current restart window
+        let mut levels = integ_opts
+            .first_levels
+            .map_or(corrector_order, |first| first.min(corrector_order));
+        let mut slab = SlabControl::new(
+            restart_length,
+            integ_opts.slab_length,
+            integ_opts.roughness_tol,
+            setup.poly_order,
             t_0,
-            y_0,
             first_dyn_eval,
-            corr_conv_tol,
         );
 
-        // flag to prevent infinite looping while collecting results
-        let mut just_restarted = false;
-
-        // Used to generate the weights for the quadrature. Quadrature uses a backwards
-        // form of the lagrange polynomial interpolation
-        let mut times_rev: VecDeque<f64> = VecDeque::from(vec![t_0]);
-        times_rev.reserve_exact(poly_order);
-
-        // start the RK integrator
-        while results.t != t_end {
-            // Ensures integrator does not over-step the goal
-            if (backward && sub_step.abs() > (t_end - results.t).abs())
-                || (!backward && sub_step > (t_end - results.t))
-            {
-                sub_step = t_end - results.t;
-            } else if sub_step.abs() < min_step_size {
-                return Err("Step size is below minimum allowable step size");
-            };
-
-            step_res = self.step(fxn, results.t, &y_last, sub_step, &atol, rtol);
-            step_revision = self.revise_step(step_res.error, sub_step);
-
-            match step_revision {
-                StepValid::Accept(nxt_step) => {
-                    if counter < poly_order + 1 {
-                        just_restarted = false;
-                        // Update all times
-                        results.t += sub_step;
-                        results.times.push(results.t);
-                        times_rev.push_front(results.t);
+        // spawn threads
+        let (root_tx, mut root_rx, idle) = self.spawn_correctors(&fxn, &setup);
 
-                        // send initialization point to corrector
-                        root_tx
-                            .send(IVPSolMsg::PROCESS(IVPSolData {
-                                y_nxt: step_res.value.clone(),
-                                dy_nxt: step_res.dyn_eval.clone(),
-                                t_nxt: results.t.clone(),
-                                weights: None,
-                            }))
-                            .expect("Could not send Message from [ROOT]");
+        // the levels are shut down on errors as well, so none is left sending
+        let outcome = (|| -> Result<(), IntegError> {
+            // start the RK integrator
+            while results.t != t_end {
+                if slab.restart_due(results.t) {
+                    // stop and wait for other threads to catch up
+                    let corrections =
+                        self.restart(&fxn, &root_tx, &mut root_rx, &mut results, &mut slab)?;
+                    // adapt the number of correction levels for the next window
+                    if let Some(tol) = correction_tol {
+                        levels = self.select_levels(&corrections, levels, corrector_order, tol);
+                    }
+                    y_last = results.states[results.states.len() - 1].clone();
+                }
 
-                        y_last = step_res.value;
-                        sub_step = nxt_step;
-                        counter += 1;
-                    } else if (counter % restart_length == 0) && !(just_restarted) {
-                        // stop and wait for other threads to catch up
-                        self.collect_results(&root_rx, &mut results)?;
-                        y_last = results.states[results.states.len() - 1].clone();
-                        just_restarted = true;
-                    } else {
-                        just_restarted = false;
-                        results.t += sub_step;
-
-                        let x_pows = get_x_pow(
-                            results.times[results.times.len() - 1],
-                            results.t,
-                            poly_order,
-                        );
-                        results.times.push(results.t);
+                // Ensures integrator does not over-step the goal
+                let (h_end, last) = approach_end(results.t, t_end, sub_step);
+                let (h, landing) = breakpoints.limit(results.t, h_end);
+                let step_res = self.step(&fxn, results.t, &y_last, h, &atol, rtol);
+                if step_failed(&fxn)? {
+                    // retake the step with half the step size
+                    sub_step = shrink_step(results.t, h, min_step_size)?;
+                    continue;
+                }
+                let step_revision = self.revise_step(step_res.error, h);
+
+                match step_revision {
+                    StepValid::Accept(nxt_step) => {
+                        if slab.check_roughness(results.t + h, &step_res.dyn_eval) {
+                            // retake the step after restarting
+                            continue;
+                        }
 
-                        // rotate times into times vector
-                        times_rev.pop_back().expect("Could not pop old time");
-                        times_rev.push_front(results.t);
+                        results.advance(h);
+                        results.times.push(results.t);
+                        if last && landing.is_none() {
+                            results.land_on(t_end);
+                        }
+                        // correctors work at the actual end of the step (just short of a breakpoint)
+                        let t_nxt = results.t;
+                        let weights = slab.push(t_nxt, &step_res.dyn_eval);
+                        if let Some(t_bp) = landing {
+                            results.land_on(t_bp);
+                            breakpoints.passed();
+                            slab.end_slab();
+                        }
 
                         // send estimate to the corrector
                         root_tx
                             .send(IVPSolMsg::PROCESS(IVPSolData {
                                 y_nxt: step_res.value.clone(),
                                 dy_nxt: step_res.dyn_eval.clone(),
-                                t_nxt: results.t.clone(),
-                                weights: Some(specific_weights(x_pows, &get_weights(&times_rev))),
+                                t_nxt,
+                                weights,
+                                levels: if slab.isolated() { 0 } else { levels },
+                                corrections: Vec::new(),
                             }))
                             .expect("Could not send Message from [ROOT]");
 
                         y_last = step_res.value;
-                        sub_step = nxt_step;
-                        counter += 1;
+                        sub_step = bounds.accept(nxt_step);
+                        results.next_step = Some(sub_step);
+                    }
+                    StepValid::Refine(nxt_step) => {
+                        if record_rejections {
+                            results.rejected.push(RejectedStep {
+                                t: results.t,
+                                step: h,
+                                error: step_res.error,
+                            });
+                        }
+                        // the record of the rejections goes with the error
+                        sub_step =
+                            bounds
+                                .reject(results.t, nxt_step, step_res.error)
+                                .map_err(|err| AdaptiveError {
+                                    rejected: mem::take(&mut results.rejected),
+                                    ..err
+                                })?;
                     }
-                }
-                StepValid::Refine(nxt_step) => {
-                    sub_step = nxt_step;
                 }
             }
-        }
-        self.collect_results(&root_rx, &mut results)?;
-        self.poison(root_tx, root_rx)?;
+            self.collect_results(&root_tx, &mut root_rx, &mut results)?;
+            Ok(())
+        })();
+        let shutdown = self.poison(root_tx, root_rx);
+        outcome?;
+        shutdown?;
+        results.level_idle = idle.lock().unwrap().clone();
+        results.update_diagnostics(&diagnostics);
         Ok(results)
     }
 }
@@ -224,4 +241,59 @@ mod tests {
         println!("DIFF 2d | {:?}", diff);
         assert!(diff < tol_val);
     }
+
+    #[test]
+    fn test_ridc_diagnostics() {
+        fn position(_t: f64, y: &Vector2<f64>) -> f64 {
+            y[0]
+        }
+        let time_end = 2.0;
+        let options = IntegOptionsParallel {
+            diagnostics: Some(vec![position]),
+            ..IntegOptionsParallel::default()
+        };
+        let ans = RK32
+            .parallel_integrator(two_d_dynamics, IT_2_D, &IV_2_D, time_end - IT_2_D, options)
+            .unwrap();
+        assert_eq!(ans.diagnostics.len(), ans.states.len());
+        for (vals, y) in ans.diagnostics.iter().zip(ans.states.iter()) {
+            assert_eq!(vals[0], y[0]);
+        }
+    }
+
+    #[test]
+    fn test_ridc_warm_start() {
+        // a second run of a similar problem seeded with the step and correction
+        // levels the first one ended on
+        let options = IntegOptionsParallel {
+            corrector_order: Some(3),
+            correction_tol: Some(1e-10),
+            restart_length: Some(20),
+            ..IntegOptionsParallel::default()
+        };
+        let ans = RK32
+            .parallel_integrator(two_d_dynamics, IT_2_D, &IV_2_D, 2.0, options.clone())
+            .unwrap();
+        let next_step = ans.next_step.unwrap();
+        let levels = *ans.correction_levels.last().unwrap();
+
+        let warm = IntegOptionsParallel {
+            first_step: Some(next_step),
+            first_levels: Some(levels),
+            record_rejections: Some(true),
+            ..options
+        };
+        let ans = RK32
+            .parallel_integrator(two_d_dynamics, IT_2_D, &IV_2_D, 2.0, warm)
+            .unwrap();
+        // the first step tried is the seed (it may be rejected at the start)
+        let first_try = match ans.rejected.first() {
+            Some(rejected) if rejected.t == IT_2_D => rejected.step,
+            _ => ans.times[1] - ans.times[0],
+        };
+        assert_eq!(first_try, next_step);
+        assert_eq!(ans.correction_levels[0], levels);
+        let diff = (two_d_solution(IT_2_D + 2.0) - ans.last_y()).abs();
+        assert!(diff < Vector2::repeat(1e-3));
+    }
 }
@@ -8,78 +8,67 @@
 // === Begin Imports ===
 // third party imports
 extern crate nalgebra as na;
-use na::allocator::Allocator;
-use na::{DefaultAllocator, Dim, DimMin, DimName, DimSub, VectorN, U1};
+use na::{DefaultAllocator, OVector};
 
 // local imports
-use super::common::{IVPSolMsg, IntegOptionsParallel};
-use super::corrector::Corrector;
+use super::common::{
+    block_on, IVPSolData, IVPSolMsg, IdleTimes, IntegOptionsParallel, LevelReceiver, LevelSender,
+    LevelSetup, Schedule,
+};
+use super::corrector::{CorrectorThread, PollResult};
+use super::predictor::Predictor;
+use super::slab::SlabControl;
 use crate::runge_kutta::adaptive::AdaptiveStep;
-use crate::runge_kutta::common::IntegResult;
-use crate::runge_kutta::fixed::FixedStep;
+use crate::runge_kutta::common::{IntegError, IntegResult};
+use crate::systems::fallible::check_failure;
+use crate::systems::OdeSystem;
+use crate::utils::solver_dim::{SolverAllocator, SolverDim};
 
 // Standard library imports
 use std::marker::Send;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc;
-use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, Mutex, TryLockError};
 use std::thread;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 // === End Imports ===
 pub trait RIDCIntegratorAdaptive: AdaptiveStep + RIDCIntegratorBase {
-    fn parallel_integrator<N: Dim + DimName + DimMin<N> + DimSub<U1>>(
+    fn parallel_integrator<N: SolverDim, S: OdeSystem<N> + Clone + Send + 'static>(
         &self,
         // Dynamics function to integrate
-        fxn: fn(f64, &VectorN<f64, N>) -> VectorN<f64, N>,
+        fxn: S,
         // Initial time
         t_0: f64,
         // Initial state
-        y_0: &VectorN<f64, N>,
+        y_0: &OVector<f64, N>,
         // Time to step to. IE duration of integration
         step: f64,
         // Integration options for solving IVP. See common.rs
         integ_opts: IntegOptionsParallel<N>,
-    ) -> Result<IntegResult<N>, &'static str>
+    ) -> Result<IntegResult<N>, IntegError>
     where
-        DefaultAllocator: Allocator<f64, N>
-            + Allocator<f64, U1, N>
-            + Allocator<f64, N, N>
-            + Allocator<f64, <N as DimMin<N>>::Output, N>
-            + Allocator<f64, <N as DimMin<N>>::Output>
-            + Allocator<f64, N, <N as DimMin<N>>::Output>
-            + Allocator<f64, <<N as DimMin<N>>::Output as DimSub<U1>>::Output>,
-        <N as DimMin<N>>::Output: DimName,
-        <N as DimMin<N>>::Output: DimSub<U1>,
-        <DefaultAllocator as Allocator<f64, N>>::Buffer: Send + Sync;
+        DefaultAllocator: SolverAllocator<N>;
 }
 
-pub trait RIDCIntegratorFixed: FixedStep + RIDCIntegratorBase {
-    fn parallel_integrator<N: Dim + DimName + DimMin<N> + DimSub<U1>>(
+pub trait RIDCIntegratorFixed: Predictor + RIDCIntegratorBase {
+    fn parallel_integrator<N: SolverDim, S: OdeSystem<N> + Clone + Send + 'static>(
         &self,
         // Dynamics function to integrate
-        fxn: fn(f64, &VectorN<f64, N>) -> VectorN<f64, N>,
+        fxn: S,
         // Initial time
         t_0: f64,
         // Initial state
-        y_0: &VectorN<f64, N>,
+        y_0: &OVector<f64, N>,
         // Time to step to. IE duration of integration
         step: f64,
         // Time step to use for fixed step integration
         dt: f64,
         // Integration options for solving IVP. See common.rs
         integ_opts: IntegOptionsParallel<N>,
-    ) -> Result<IntegResult<N>, &'static str>
+    ) -> Result<IntegResult<N>, IntegError>
     where
-        DefaultAllocator: Allocator<f64, N>
-            + Allocator<f64, U1, N>
-            + Allocator<f64, N, N>
-            + Allocator<f64, <N as DimMin<N>>::Output, N>
-            + Allocator<f64, <N as DimMin<N>>::Output>
-            + Allocator<f64, N, <N as DimMin<N>>::Output>
-            + Allocator<f64, <<N as DimMin<N>>::Output as DimSub<U1>>::Output>,
-        <N as DimMin<N>>::Output: DimName,
-        <N as DimMin<N>>::Output: DimSub<U1>,
-        <DefaultAllocator as Allocator<f64, N>>::Buffer: Send + Sync;
+        DefaultAllocator: SolverAllocator<N>;
 }
 
 pub trait RIDCIntegratorBase {
@@ -87,150 +76,310 @@ pub trait RIDCIntegratorBase {
     const SHUTDOWN_TIMEOUT_SEC: u128 = 100;
 
     // Generates all corrector threads for RIDC
-    fn spawn_correctors<N: Dim + DimName + DimMin<N> + DimSub<U1>>(
+    fn spawn_correctors<N: SolverDim, S: OdeSystem<N> + Clone + Send + 'static>(
         &self,
-        // Number of corrector threads to spawn
-        corrector_order: usize,
-        // Size of Polynomial fit to use for Stencil (stencil size = Poly Order + 1)
-        poly_order: usize,
         // Dynamics function to use for integration problem
-        dyn_fxn: fn(f64, &VectorN<f64, N>) -> VectorN<f64, N>,
-        // Initial time to start all correctors at
-        itime: f64,
-        // Initial state to initialize all correctors with
-        istate: &VectorN<f64, N>,
-        // First dynamics evaluation using the initial state (istate) and initial time (itime)
-        idyn: &VectorN<f64, N>,
-        // Convergence tolerance to use for newton solver in corrector
-        corr_conv_tol: f64,
-    ) -> (Sender<IVPSolMsg<N>>, Receiver<IVPSolMsg<N>>)
+        dyn_fxn: &S,
+        // Correction levels to spawn and the point they start from
+        setup: &LevelSetup<N>,
+    ) -> (LevelSender<N>, LevelReceiver<N>, IdleTimes)
     where
-        DefaultAllocator: Allocator<f64, N>
-            + Allocator<f64, U1, N>
-            + Allocator<f64, N, N>
-            + Allocator<f64, <N as DimMin<N>>::Output, N>
-            + Allocator<f64, <N as DimMin<N>>::Output>
-            + Allocator<f64, N, <N as DimMin<N>>::Output>
-            + Allocator<f64, <<N as DimMin<N>>::Output as DimSub<U1>>::Output>,
-        <N as DimMin<N>>::Output: DimName,
-        <N as DimMin<N>>::Output: DimSub<U1>,
-        <DefaultAllocator as Allocator<f64, N>>::Buffer: Send + Sync,
+        DefaultAllocator: SolverAllocator<N>,
     {
-        // Spawn all channels
-        let mut channels: Vec<(Sender<IVPSolMsg<N>>, Receiver<IVPSolMsg<N>>)> = Vec::new();
-        channels.push(mpsc::channel());
-        for _ in 0..corrector_order {
-            channels.push(mpsc::channel());
-        }
+        let (corrector_order, schedule) = (setup.corrector_order, setup.schedule);
+        let idle = Arc::new(Mutex::new(vec![0.0; corrector_order]));
 
-        // set up root channels used by the predictor thread
-        let channels_root = channels.pop().unwrap();
-        let root_tx = channels_root.0;
-        let mut last_rx = channels_root.1;
+        // Spawn all channels. Bounded channels never lead back to the predictor,
+        // which only reads its results at the end of a slab
+        let channel = |last: bool| match schedule {
+            Schedule::BoundedLag(lag) if !last => {
+                let (tx, rx) = mpsc::sync_channel(lag);
+                (LevelSender::Bounded(tx), LevelReceiver::Blocking(rx))
+            }
+            _ => {
+                let (tx, rx) = mpsc::channel();
+                (LevelSender::Unbounded(tx), LevelReceiver::Blocking(rx))
+            }
+        };
+        let (root_tx, mut last_rx) = channel(corrector_order == 0);
 
-        // generate corrector threads
-        let mut thread_handles: Vec<thread::JoinHandle<Result<(), &'static str>>> = Vec::new();
+        // generate correctors
+        let mut levels = Vec::new();
         for i in 0..corrector_order {
-            let chan = channels.pop().unwrap();
-            let mut corrector = Corrector::new(
-                poly_order,
-                dyn_fxn,
-                istate,
-                idyn,
-                itime,
+            let chan = channel(i + 1 == corrector_order);
+            levels.push(CorrectorThread::new(
+                dyn_fxn.clone(),
+                setup,
                 last_rx,
                 chan.0,
                 i as u32,
-                corr_conv_tol,
-            );
+                idle.clone(),
+            ));
             last_rx = chan.1;
-            let handler = thread::Builder::new()
-                .name(format!("THREAD {}", i))
-                .spawn(move || corrector.run())
-                .unwrap();
-            thread_handles.push(handler);
         }
         let root_rx = last_rx;
-        (root_tx, root_rx)
+
+        match schedule {
+            Schedule::PerLevel | Schedule::BoundedLag(_) => {
+                for (i, mut level) in levels.into_iter().enumerate() {
+                    thread::Builder::new()
+                        .name(format!("THREAD {}", i))
+                        .spawn(move || level.run())
+                        .unwrap();
+                }
+            }
+            Schedule::Pooled(workers) => {
+                let levels: Arc<Vec<Mutex<CorrectorThread<N, S>>>> =
+                    Arc::new(levels.into_iter().map(Mutex::new).collect());
+                let failed = Arc::new(AtomicBool::new(false));
+                for w in 0..workers {
+                    let (levels, failed) = (levels.clone(), failed.clone());
+                    thread::Builder::new()
+                        .name(format!("WORKER {}", w))
+                        .spawn(move || work_levels(&levels, w, &failed))
+                        .unwrap();
+                }
+            }
+        }
+        (root_tx, root_rx, idle)
     }
 
     // Initiates the deployment of poison pill, starting shutdown of threads
-    fn poison<N: Dim + DimName + DimMin<N> + DimSub<U1>>(
+    fn poison<N: SolverDim>(
         &self,
         // Transmit channel for main process
-        root_tx: Sender<IVPSolMsg<N>>,
+        root_tx: LevelSender<N>,
         // Receiver channel for main process
-        root_rx: Receiver<IVPSolMsg<N>>,
+        root_rx: LevelReceiver<N>,
     ) -> Result<(), &'static str>
     where
-        DefaultAllocator: Allocator<f64, N>
-            + Allocator<f64, U1, N>
-            + Allocator<f64, N, N>
-            + Allocator<f64, <N as DimMin<N>>::Output, N>
-            + Allocator<f64, <N as DimMin<N>>::Output>
-            + Allocator<f64, N, <N as DimMin<N>>::Output>
-            + Allocator<f64, <<N as DimMin<N>>::Output as DimSub<U1>>::Output>,
-        <N as DimMin<N>>::Output: DimName,
-        <N as DimMin<N>>::Output: DimSub<U1>,
-        <DefaultAllocator as Allocator<f64, N>>::Buffer: Send + Sync,
+        DefaultAllocator: SolverAllocator<N>,
     {
-        root_tx
-            .send(IVPSolMsg::TERMINATE)
-            .expect("Could not send poison pill msg from [ROOT]");
-
-        let time = Instant::now();
-        while time.elapsed().as_micros() < Self::SHUTDOWN_TIMEOUT_SEC {
-            match root_rx.recv() {
-                Ok(msg) => match msg {
-                    IVPSolMsg::PROCESS(_) => continue,
-                    IVPSolMsg::TERMINATE => return Ok(()),
-                },
-                Err(_) => {
-                    return Err(
-                        "Something went wrong while attempting to receive messages on root rx",
-                    );
-                }
-            };
-        }
-        Err("Shutdown Process Timed out")
+        block_on(poison_levels(root_tx, root_rx, Self::SHUTDOWN_TIMEOUT_SEC))
     }
 
-    fn collect_results<N: Dim + DimName + DimMin<N> + DimSub<U1>>(
+    // Collects corrected solutions for every time step taken so far. Returns the
+    // correction sizes reported by each level for the collected solutions
+    fn collect_results<N: SolverDim>(
         &self,
+        // Root transmit channel used to flush the correctors
+        root_tx: &LevelSender<N>,
         // Root receiver channel to listen for results on
-        root_rx: &Receiver<IVPSolMsg<N>>,
+        root_rx: &mut LevelReceiver<N>,
         // Results object to add results to
         results: &mut IntegResult<N>,
-    ) -> Result<(), &'static str>
+    ) -> Result<Vec<Vec<f64>>, &'static str>
+    where
+        DefaultAllocator: SolverAllocator<N>,
+    {
+        block_on(collect_levels(root_tx, root_rx, results))
+    }
+
+    // Ends the current slab. Collects its corrected solutions and restarts the
+    // correction history (and the slab) from the last of them. Returns the
+    // correction sizes reported for the slab
+    fn restart<N: SolverDim, S: OdeSystem<N>>(
+        &self,
+        // Dynamics function used for the integration problem
+        fxn: &S,
+        // Root transmit channel
+        root_tx: &LevelSender<N>,
+        // Root receiver channel to listen for results on
+        root_rx: &mut LevelReceiver<N>,
+        // Results object to add results to
+        results: &mut IntegResult<N>,
+        // Slab controller of the predictor
+        slab: &mut SlabControl<N>,
+    ) -> Result<Vec<Vec<f64>>, &'static str>
     where
-        DefaultAllocator: Allocator<f64, N>
-            + Allocator<f64, U1, N>
-            + Allocator<f64, N, N>
-            + Allocator<f64, <N as DimMin<N>>::Output, N>
-            + Allocator<f64, <N as DimMin<N>>::Output>
-            + Allocator<f64, N, <N as DimMin<N>>::Output>
-            + Allocator<f64, <<N as DimMin<N>>::Output as DimSub<U1>>::Output>,
-        <N as DimMin<N>>::Output: DimName,
-        <N as DimMin<N>>::Output: DimSub<U1>,
-        <DefaultAllocator as Allocator<f64, N>>::Buffer: Send + Sync,
+        DefaultAllocator: SolverAllocator<N>,
     {
-        while results.states.len() < results.times.len() {
-            match root_rx.recv() {
-                Ok(msg) => match msg {
-                    IVPSolMsg::PROCESS(data) => {
-                        results.states.push(data.y_nxt);
-                    }
-                    IVPSolMsg::TERMINATE => {
-                        return Err(
-                            "The root thread recieved a terminate command without `poison()`.",
-                        );
-                    }
-                },
-                Err(_) => {
-                    return Err("Something went terribly wrong while collecting results");
+        block_on(restart_levels(fxn, root_tx, root_rx, results, slab))
+    }
+
+    // Chooses the number of correction levels for the next restart window from the
+    // corrections measured over the last one. The levels are cut back to the first
+    // level whose correction fell below `correction_tol` (that level confirms the
+    // solution has converged). If no level converged another level is added, as
+    // long as the corrections were still contracting
+    fn select_levels(
+        &self,
+        // Correction sizes reported by each level for the solutions of the window
+        corrections: &[Vec<f64>],
+        // Number of levels used in the last window
+        levels: usize,
+        // Maximum number of levels (number of corrector threads)
+        max_levels: usize,
+        // Correction size below which a level is considered converged
+        correction_tol: f64,
+    ) -> usize {
+        // largest correction made by each level over the window
+        let mut sizes = vec![0.0_f64; levels];
+        for corr in corrections {
+            for (size, val) in sizes.iter_mut().zip(corr) {
+                *size = size.max(*val);
+            }
+        }
+
+        match sizes.iter().position(|size| *size <= correction_tol) {
+            Some(k) => k + 1,
+            None => {
+                let contracting = levels < 2 || sizes[levels - 1] < sizes[levels - 2];
+                if levels < max_levels && contracting {
+                    levels + 1
+                } else {
+                    levels
+                }
+            }
+        }
+    }
+}
+
+// Sends the poison pill down the levels and waits for it to come back out of the
+// last one. Shared by the threaded (see `RIDCIntegratorBase::poison`) and async
+// backends, as are `collect_levels` and `restart_levels`
+pub(crate) async fn poison_levels<N: SolverDim>(
+    root_tx: LevelSender<N>,
+    mut root_rx: LevelReceiver<N>,
+    // Timeout for the shutdown in us
+    timeout: u128,
+) -> Result<(), &'static str>
+where
+    DefaultAllocator: SolverAllocator<N>,
+{
+    root_tx
+        .send(IVPSolMsg::TERMINATE)
+        .expect("Could not send poison pill msg from [ROOT]");
+
+    let time = Instant::now();
+    while time.elapsed().as_micros() < timeout {
+        match root_rx.recv_async().await {
+            Some(msg) => match msg {
+                IVPSolMsg::TERMINATE => return Ok(()),
+                _ => continue,
+            },
+            None => {
+                return Err("Something went wrong while attempting to receive messages on root rx");
+            }
+        };
+    }
+    Err("Shutdown Process Timed out")
+}
+
+// Flushes the levels and collects the corrected solutions of every step sent so
+// far into the results
+pub(crate) async fn collect_levels<N: SolverDim>(
+    root_tx: &LevelSender<N>,
+    root_rx: &mut LevelReceiver<N>,
+    results: &mut IntegResult<N>,
+) -> Result<Vec<Vec<f64>>, &'static str>
+where
+    DefaultAllocator: SolverAllocator<N>,
+{
+    // the flush comes back once every estimate sent before it is corrected
+    root_tx
+        .send(IVPSolMsg::FLUSH)
+        .expect("Could not send flush msg from [ROOT]");
+
+    let mut corrections = Vec::new();
+    loop {
+        match root_rx.recv_async().await {
+            Some(msg) => match msg {
+                IVPSolMsg::PROCESS(data) => {
+                    results.states.push(data.y_nxt);
+                    results.correction_levels.push(data.corrections.len());
+                    corrections.push(data.corrections);
+                }
+                IVPSolMsg::FLUSH => break,
+                IVPSolMsg::RESTART(_) => continue,
+                IVPSolMsg::FAILED(err) => return Err(err),
+                IVPSolMsg::TERMINATE => {
+                    return Err("The root thread recieved a terminate command without `poison()`.");
+                }
+            },
+            None => {
+                return Err("Something went terribly wrong while collecting results");
+            }
+        };
+    }
+    if results.states.len() != results.times.len() {
+        return Err("Correctors did not return a solution for every step");
+    }
+    Ok(corrections)
+}
+
+// Collects the corrected solutions of the slab and restarts the levels (and the
+// slab) from the last of them
+pub(crate) async fn restart_levels<N: SolverDim, S: OdeSystem<N>>(
+    fxn: &S,
+    root_tx: &LevelSender<N>,
+    root_rx: &mut LevelReceiver<N>,
+    results: &mut IntegResult<N>,
+    slab: &mut SlabControl<N>,
+) -> Result<Vec<Vec<f64>>, &'static str>
+where
+    DefaultAllocator: SolverAllocator<N>,
+{
+    let corrections = collect_levels(root_tx, root_rx, results).await?;
+    let y_nxt = results.states[results.states.len() - 1].clone();
+    let dy_nxt = fxn.dynamics(results.t, &y_nxt);
+    check_failure(fxn)?;
+    slab.restart(results.t, &dy_nxt);
+    results.restarts.push(results.t);
+    root_tx
+        .send(IVPSolMsg::RESTART(IVPSolData {
+            y_nxt,
+            dy_nxt,
+            t_nxt: results.t,
+            weights: None,
+            levels: 0,
+            corrections: Vec::new(),
+        }))
+        .expect("Could not send restart msg from [ROOT]");
+    Ok(corrections)
+}
+
+// Worker of a pooled schedule. Polls the levels round robin (starting from its own
+// offset) and processes the input of any level not held by another worker, until
+// every level has terminated or a level failed
+fn work_levels<N: SolverDim, S: OdeSystem<N>>(
+    levels: &[Mutex<CorrectorThread<N, S>>],
+    offset: usize,
+    failed: &AtomicBool,
+) -> Result<(), &'static str>
+where
+    DefaultAllocator: SolverAllocator<N>,
+{
+    let n = levels.len();
+    while !failed.load(Ordering::SeqCst) {
+        let (mut worked, mut done) = (false, 0);
+        for k in 0..n {
+            let mut level = match levels[(offset + k) % n].try_lock() {
+                Ok(level) => level,
+                Err(TryLockError::WouldBlock) => continue,
+                Err(TryLockError::Poisoned(_)) => {
+                    failed.store(true, Ordering::SeqCst);
+                    return Err("A correction level panicked");
                 }
             };
+            match level.poll() {
+                Ok(PollResult::Worked) => worked = true,
+                Ok(PollResult::Idle) => (),
+                Ok(PollResult::Done) => done += 1,
+                Err(err) => {
+                    // the other workers stop too, which drops the channels of
+                    // every level so the predictor sees the failure
+                    failed.store(true, Ordering::SeqCst);
+                    return Err(err);
+                }
+            }
+        }
+        if done == n {
+            break;
+        }
+        if !worked {
+            thread::sleep(Duration::from_micros(20));
         }
-        Ok(())
     }
+    Ok(())
 }
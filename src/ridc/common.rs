@@ -7,7 +7,22 @@
 // third party imports
 extern crate nalgebra as na;
 use na::allocator::Allocator;
-use na::{DefaultAllocator, Dim, DimName, VectorN};
+use na::{DefaultAllocator, Dim, DimName, OVector};
+
+// local imports
+use super::corrector::{Corrector, ImplicitEuler};
+use crate::runge_kutta::common::Diagnostic;
+use crate::utils::solver_dim::{SolverAllocator, SolverDim};
+
+#[cfg(feature = "tokio")]
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+
+// Standard library imports
+use std::future::{poll_fn, Future};
+use std::pin::pin;
+use std::sync::mpsc::{Receiver, SendError, Sender, SyncSender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
 
 // === End Imports ===
 
@@ -15,59 +30,322 @@ use na::{DefaultAllocator, Dim, DimName, VectorN};
 #[derive(Debug, Clone, PartialEq)]
 pub struct IntegOptionsParallel<N: Dim + DimName>
 where
-    DefaultAllocator: Allocator<f64, N>,
+    DefaultAllocator: Allocator<N>,
 {
     // Absolute tolerance to use for RK predictor
-    pub atol: Option<VectorN<f64, N>>,
+    pub atol: Option<OVector<f64, N>>,
     // Relative tolerance to use for RK predictor
     pub rtol: Option<f64>,
     // Minimum step allowed for RK predictor
     pub min_step: Option<f64>,
+    // Maximum step allowed for the adaptive RK predictor
+    pub max_step: Option<f64>,
+    // Consecutive rejections of a predictor step after which the adaptive RK
+    // predictor errors (default 50)
+    pub max_rejections: Option<usize>,
+    // Whether the adaptive RK predictor records its rejected steps in the result
+    pub record_rejections: Option<bool>,
+    // Size of the first step of the adaptive RK predictor. Chosen automatically from
+    // the dynamics at the initial state when None
+    pub first_step: Option<f64>,
     // Order of polynomial fit for correctors to use
     pub poly_order: Option<usize>,
     // Number of corrections to apply. Corresponds to number of additional threads spawned
     pub corrector_order: Option<usize>,
     // Number of steps to take before restarting the integrator
     pub restart_length: Option<usize>,
+    // Maximum length of time covered by a restart window (slab), independent of the
+    // step size. A slab ends after `restart_length` steps or `slab_length` time,
+    // whichever comes first
+    pub slab_length: Option<f64>,
+    // Relative deviation of a dynamics evaluation from the extrapolation of the
+    // slab's previous evaluations above which the dynamics are considered rough
+    // (e.g. a discontinuity). The slab is then ended before the rough step and that
+    // step is kept out of every correction stencil. Disabled by default
+    pub roughness_tol: Option<f64>,
+    // Known discontinuity times of the dynamics (e.g. thrust on/off or table
+    // breakpoints). Steps land exactly on each of them and the correction history
+    // is restarted afterward
+    pub breakpoints: Option<Vec<f64>>,
     // Tolerance to use for the convergence of the Corrector Newton Solver
     pub convergence_tol: Option<f64>,
+    // Update formula applied by every correction level (see ridc/corrector). The
+    // implicit (backward euler) correction by default
+    pub corrector: Option<Arc<dyn Corrector<N>>>,
+    // How the correction levels are scheduled on threads. One thread per level by
+    // default
+    pub schedule: Option<Schedule>,
+    // Diagnostic functionals to record at every corrected solution
+    pub diagnostics: Option<Vec<Diagnostic<N>>>,
+    // Size of correction below which further correction levels are considered
+    // converged. When set, the number of correction levels applied is adapted per
+    // restart window (with `corrector_order` as the maximum)
+    pub correction_tol: Option<f64>,
+    // Number of correction levels applied in the first restart window when they are
+    // adapted (`correction_tol`), `corrector_order` by default. Seeding it with the
+    // last of the `correction_levels` of a similar run warm starts the adaptation
+    pub first_levels: Option<usize>,
+    // Carry the correction integrals of every level in double-double (see
+    // utils/double_double), removing the round-off they build up over long runs.
+    // States stay in f64. Off by default
+    #[cfg(feature = "double_double")]
+    pub double_double: Option<bool>,
 }
 impl<N: Dim + DimName> IntegOptionsParallel<N>
 where
-    DefaultAllocator: Allocator<f64, N>,
+    DefaultAllocator: Allocator<N>,
 {
     pub fn default() -> Self {
         Self {
             atol: None,
             rtol: None,
             min_step: None,
+            max_step: None,
+            max_rejections: None,
+            record_rejections: None,
+            first_step: None,
             poly_order: None,
             corrector_order: None,
             restart_length: None,
+            slab_length: None,
+            roughness_tol: None,
+            breakpoints: None,
             convergence_tol: None,
+            corrector: None,
+            schedule: None,
+            diagnostics: None,
+            correction_tol: None,
+            first_levels: None,
+            #[cfg(feature = "double_double")]
+            double_double: None,
         }
     }
+
+    // Whether the correction integrals are carried in double-double
+    #[cfg(feature = "double_double")]
+    pub(crate) fn double_double(&self) -> bool {
+        self.double_double.unwrap_or(false)
+    }
+
+    // Double-double accumulation needs the `double_double` feature
+    #[cfg(not(feature = "double_double"))]
+    pub(crate) fn double_double(&self) -> bool {
+        false
+    }
+}
+
+// Settings shared by the correction levels of a run, and the point they all start
+// from
+pub struct LevelSetup<N: Dim + DimName>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    // Number of correction levels (one corrector each)
+    pub corrector_order: usize,
+    // Size of Polynomial fit to use for Stencil (stencil size = Poly Order + 1)
+    pub poly_order: usize,
+    // Update formula applied by every correction level
+    pub corrector: Arc<dyn Corrector<N>>,
+    // Convergence tolerance to use for newton solver in corrector
+    pub convergence_tol: f64,
+    // Whether the levels carry their correction integrals in double-double
+    pub double_double: bool,
+    // How the correction levels are scheduled on threads
+    pub schedule: Schedule,
+    // Initial time
+    pub t_0: f64,
+    // Initial state
+    pub y_0: OVector<f64, N>,
+    // Dynamics evaluated at the initial time and state
+    pub dy_0: OVector<f64, N>,
+}
+
+impl<N: SolverDim> LevelSetup<N>
+where
+    DefaultAllocator: SolverAllocator<N>,
+{
+    // Levels of the integration options, starting from (t_0, y_0). The newton solves
+    // of the corrections converge to `convergence_tol` unless the options set one
+    pub fn new(
+        integ_opts: &IntegOptionsParallel<N>,
+        convergence_tol: f64,
+        t_0: f64,
+        y_0: &OVector<f64, N>,
+        dy_0: &OVector<f64, N>,
+    ) -> Result<Self, &'static str> {
+        let schedule = integ_opts.schedule.unwrap_or(Schedule::PerLevel);
+        if schedule == Schedule::Pooled(0) {
+            return Err("A pooled schedule needs at least one worker");
+        }
+        Ok(LevelSetup {
+            corrector_order: integ_opts.corrector_order.unwrap_or(1),
+            poly_order: integ_opts.poly_order.unwrap_or(3), // ONLY 3 is currently supported
+            corrector: integ_opts
+                .corrector
+                .clone()
+                .unwrap_or_else(|| Arc::new(ImplicitEuler)),
+            convergence_tol: integ_opts.convergence_tol.unwrap_or(convergence_tol),
+            double_double: integ_opts.double_double(),
+            schedule,
+            t_0,
+            y_0: y_0.clone(),
+            dy_0: dy_0.clone(),
+        })
+    }
+}
+
+// Scheduling policy of the correction pipeline. Which one saturates the hardware
+// best depends on the cost of the dynamics, the number of levels and the cores
+// available. The time each level spends waiting for input is reported in the
+// `level_idle` of the result
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Schedule {
+    // One thread per correction level, each blocking until its input arrives
+    PerLevel,
+    // A pool of worker threads sharing the correction levels. Each worker takes
+    // whichever level has input waiting (levels still process their steps in
+    // order), so fewer threads than levels can be used
+    Pooled(usize),
+    // One thread per correction level, with at most the given number of estimates
+    // queued between consecutive levels. A level that runs ahead blocks until the
+    // next one catches up, which bounds the memory of long runs
+    BoundedLag(usize),
+}
+
+// Time in seconds each correction level spent waiting for input, shared by the
+// levels which report into it
+pub type IdleTimes = Arc<Mutex<Vec<f64>>>;
+
+// Sending half of a channel between pipeline stages
+pub enum LevelSender<N: Dim + DimName>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    Unbounded(Sender<IVPSolMsg<N>>),
+    Bounded(SyncSender<IVPSolMsg<N>>),
+    // Channel of the async backend (see ridc/tasks)
+    #[cfg(feature = "tokio")]
+    Async(UnboundedSender<IVPSolMsg<N>>),
+}
+
+impl<N: Dim + DimName> LevelSender<N>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    pub fn send(&self, msg: IVPSolMsg<N>) -> Result<(), SendError<IVPSolMsg<N>>> {
+        match self {
+            LevelSender::Unbounded(tx) => tx.send(msg),
+            LevelSender::Bounded(tx) => tx.send(msg),
+            #[cfg(feature = "tokio")]
+            LevelSender::Async(tx) => tx.send(msg).map_err(|err| SendError(err.0)),
+        }
+    }
+}
+
+// Receiving half of a channel between pipeline stages
+pub enum LevelReceiver<N: Dim + DimName>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    Blocking(Receiver<IVPSolMsg<N>>),
+    // Channel of the async backend (see ridc/tasks)
+    #[cfg(feature = "tokio")]
+    Async(UnboundedReceiver<IVPSolMsg<N>>),
+}
+
+impl<N: Dim + DimName> LevelReceiver<N>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    // Blocks until a message arrives. None once every sender is gone
+    pub fn recv(&mut self) -> Option<IVPSolMsg<N>> {
+        match self {
+            LevelReceiver::Blocking(rx) => rx.recv().ok(),
+            #[cfg(feature = "tokio")]
+            LevelReceiver::Async(rx) => rx.blocking_recv(),
+        }
+    }
+
+    // Next message if one is waiting
+    pub fn try_recv(&mut self) -> Result<IVPSolMsg<N>, TryRecvError> {
+        match self {
+            LevelReceiver::Blocking(rx) => rx.try_recv(),
+            #[cfg(feature = "tokio")]
+            LevelReceiver::Async(rx) => rx.try_recv().map_err(|err| match err {
+                tokio::sync::mpsc::error::TryRecvError::Empty => TryRecvError::Empty,
+                tokio::sync::mpsc::error::TryRecvError::Disconnected => TryRecvError::Disconnected,
+            }),
+        }
+    }
+
+    // Waits for a message without blocking the executor (blocking channels block)
+    pub async fn recv_async(&mut self) -> Option<IVPSolMsg<N>> {
+        match self {
+            LevelReceiver::Blocking(rx) => rx.recv().ok(),
+            #[cfg(feature = "tokio")]
+            LevelReceiver::Async(rx) => rx.recv().await,
+        }
+    }
+}
+
+// Runs the predictor loop shared by the threaded and async backends on the
+// calling thread. The blocking channels of the threaded backend never leave a
+// future pending, so the only re-polls are those of `yield_now`
+pub(crate) fn block_on<F: Future>(fut: F) -> F::Output {
+    let mut fut = pin!(fut);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(out) = fut.as_mut().poll(&mut cx) {
+            return out;
+        }
+    }
+}
+
+// Gives the executor a turn, so the levels of the async backend make progress
+// even on a single threaded runtime
+pub(crate) async fn yield_now() {
+    let mut yielded = false;
+    poll_fn(|cx| {
+        if yielded {
+            return Poll::Ready(());
+        }
+        yielded = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    })
+    .await
 }
 
 pub enum IVPSolMsg<N: Dim + DimName>
 where
-    DefaultAllocator: Allocator<f64, N>,
+    DefaultAllocator: Allocator<N>,
 {
     PROCESS(IVPSolData<N>),
+    // Correct any buffered estimates and echo back once all prior estimates are out
+    FLUSH,
+    // Restart the correction history from the initial point of a new slab
+    RESTART(IVPSolData<N>),
+    // A level failed with this error. It and the levels after it drop any further
+    // work, but still pass the TERMINATE on
+    FAILED(&'static str),
     TERMINATE,
 }
 
 #[derive(Debug)]
 pub struct IVPSolData<N: Dim + DimName>
 where
-    DefaultAllocator: Allocator<f64, N>,
+    DefaultAllocator: Allocator<N>,
 {
     // Next solution estimate to correct
-    pub y_nxt: VectorN<f64, N>,
+    pub y_nxt: OVector<f64, N>,
     // Dynamics Function eval f(t_nxt, y_nxt)
-    pub dy_nxt: VectorN<f64, N>,
+    pub dy_nxt: OVector<f64, N>,
     // Time at which solution estimate and were evaluated
     pub t_nxt: f64,
     // Pre-computed quadrature weights for integrating from time t0 to t_nxt
     pub weights: Option<Vec<f64>>,
+    // Number of correction levels to apply. Later levels pass the estimate through
+    pub levels: usize,
+    // Size (max abs) of the correction applied by each level so far
+    pub corrections: Vec<f64>,
 }
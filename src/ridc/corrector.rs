@@ -9,6 +9,20 @@
 /// to either a predictor (for the final correction level in an RIDC integrator) or
 /// to the next level of correction
 ///
+/// At the end of each restart window (slab) the predictor sends a FLUSH, which makes
+/// a corrector still filling its stencil correct what it has buffered, followed by a
+/// RESTART that resets the correction history to the corrected initial point of the
+/// next slab
+///
+/// A level whose correction fails (a newton solve which does not converge, or
+/// dynamics failing to evaluate, see `systems::fallible`) sends the error on as a
+/// FAILED message and drops its further work. The levels after it pass the FAILED
+/// on to the predictor, which ends the integration with the error.
+///
+/// How a level updates the provisional solution from its quadrature is set by a
+/// `Corrector`: the implicit (backward euler) update used by default, an explicit
+/// pointwise update, a Picard update, or any custom formula implementing the trait.
+///
 /// Note: Please look at either the adaptive step or fixed step RIDC predictors for usage
 /// information
 ///
@@ -16,201 +30,548 @@
 // third party imports
 extern crate nalgebra as na;
 use na::allocator::Allocator;
-use na::{DefaultAllocator, Dim, DimMin, DimName, DimSub, VectorN, U1};
+use na::{DefaultAllocator, Dim, DimName, OVector};
 
 // local imports
-use super::common::{IVPSolData, IVPSolMsg};
-use crate::lagrange::quadrature::{get_weights, get_x_pow, specific_weights};
+use super::common::{IVPSolData, IVPSolMsg, IdleTimes, LevelReceiver, LevelSender, LevelSetup};
+use crate::lagrange::quadrature::interval_weights;
+use crate::systems::fallible::check_failure;
+use crate::systems::OdeSystem;
+#[cfg(feature = "double_double")]
+use crate::utils::double_double::{self, DoubleDouble};
+use crate::utils::kahan::weighted_sum;
 use crate::utils::newton_raphson::{
     newton_raphson_broyden, newton_raphson_fdiff, newton_raphson_linsrch,
 };
+use crate::utils::solver_dim::{SolverAllocator, SolverDim};
 
 // Standard library imports
 use std::collections::VecDeque;
+use std::fmt;
 use std::marker::Send;
-use std::sync::mpsc::{Receiver, Sender};
+use std::sync::mpsc::TryRecvError;
+use std::sync::Arc;
+use std::time::Instant;
 
 // === End Imports ===
 
-pub struct Corrector<N: Dim + DimName + DimMin<N> + DimSub<U1>>
+// One step of a correction sweep, from t_prev to t_n
+pub struct CorrectionStep<'a, N: Dim + DimName>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    // Start time of the step
+    pub t_prev: f64,
+    // End time of the step
+    pub t_n: f64,
+    // Solution at t_prev, already corrected by this level
+    pub y_prev: &'a OVector<f64, N>,
+    // Provisional solution at t_n from the previous level
+    pub y_est: &'a OVector<f64, N>,
+    // Dynamics evaluated on the previous level at t_prev
+    pub dy_prev: &'a OVector<f64, N>,
+    // Dynamics evaluated on the previous level at t_n
+    pub dy_est: &'a OVector<f64, N>,
+    // Quadrature of the previous level's dynamics over the step
+    pub quadrature: &'a OVector<f64, N>,
+}
+
+// Update formula applied by each correction level. Correctors are shared by all of
+// the corrector threads
+pub trait Corrector<N: Dim + DimName>: Send + Sync
+where
+    DefaultAllocator: Allocator<N>,
+{
+    // Name of the correction formula, used for display and comparison of options
+    fn name(&self) -> &'static str;
+
+    // Corrected solution at the end of the step. `convergence_tol` is the tolerance
+    // for any nonlinear solve the formula needs
+    fn correct(
+        &self,
+        dynamics: &dyn OdeSystem<N>,
+        step: &CorrectionStep<N>,
+        convergence_tol: f64,
+    ) -> Result<OVector<f64, N>, &'static str>;
+
+    // Change of the solution over the step (the corrected solution less y_prev),
+    // which lets the level add it to y_prev in higher precision (see
+    // utils/double_double). Taken from `correct` by default; formulas computing it
+    // directly keep its low order bits
+    fn increment(
+        &self,
+        dynamics: &dyn OdeSystem<N>,
+        step: &CorrectionStep<N>,
+        convergence_tol: f64,
+    ) -> Result<OVector<f64, N>, &'static str> {
+        Ok(self.correct(dynamics, step, convergence_tol)? - step.y_prev)
+    }
+}
+
+impl<N: Dim + DimName> fmt::Debug for dyn Corrector<N>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Corrector({})", self.name())
+    }
+}
+
+impl<N: Dim + DimName> PartialEq for dyn Corrector<N>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.name() == other.name()
+    }
+}
+
+// Implicit (backward euler) correction, solved with a newton iteration. Stable for
+// stiff dynamics. The default
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImplicitEuler;
+
+impl<N: SolverDim> Corrector<N> for ImplicitEuler
+where
+    DefaultAllocator: SolverAllocator<N>,
+{
+    fn name(&self) -> &'static str {
+        "IMPLICIT EULER"
+    }
+
+    fn correct(
+        &self,
+        dynamics: &dyn OdeSystem<N>,
+        step: &CorrectionStep<N>,
+        convergence_tol: f64,
+    ) -> Result<OVector<f64, N>, &'static str> {
+        let dt = step.t_n - step.t_prev;
+        let root_problem = |y_n: &OVector<f64, N>| {
+            y_n - (step.y_prev + dt * dynamics.dynamics(step.t_n, y_n) - dt * step.dy_est
+                + step.quadrature)
+        };
+        newton_raphson_broyden(root_problem, step.y_est.clone(), convergence_tol)
+    }
+
+    fn increment(
+        &self,
+        dynamics: &dyn OdeSystem<N>,
+        step: &CorrectionStep<N>,
+        convergence_tol: f64,
+    ) -> Result<OVector<f64, N>, &'static str> {
+        let dt = step.t_n - step.t_prev;
+        let root_problem = |del: &OVector<f64, N>| {
+            del - (dt * dynamics.dynamics(step.t_n, &(step.y_prev + del)) - dt * step.dy_est
+                + step.quadrature)
+        };
+        newton_raphson_broyden(root_problem, step.y_est - step.y_prev, convergence_tol)
+    }
+}
+
+// Explicit (forward euler) correction, computed pointwise without a solve
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExplicitEuler;
+
+impl<N: Dim + DimName> Corrector<N> for ExplicitEuler
+where
+    DefaultAllocator: Allocator<N>,
+{
+    fn name(&self) -> &'static str {
+        "EXPLICIT EULER"
+    }
+
+    fn correct(
+        &self,
+        dynamics: &dyn OdeSystem<N>,
+        step: &CorrectionStep<N>,
+        _convergence_tol: f64,
+    ) -> Result<OVector<f64, N>, &'static str> {
+        let dt = step.t_n - step.t_prev;
+        Ok(step.y_prev
+            + dt * (dynamics.dynamics(step.t_prev, step.y_prev) - step.dy_prev)
+            + step.quadrature)
+    }
+
+    fn increment(
+        &self,
+        dynamics: &dyn OdeSystem<N>,
+        step: &CorrectionStep<N>,
+        _convergence_tol: f64,
+    ) -> Result<OVector<f64, N>, &'static str> {
+        let dt = step.t_n - step.t_prev;
+        Ok(dt * (dynamics.dynamics(step.t_prev, step.y_prev) - step.dy_prev) + step.quadrature)
+    }
+}
+
+// Picard correction: the corrected solution is the quadrature of the previous
+// level's dynamics. Gains at most one order per level and needs no evaluations
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Picard;
+
+impl<N: Dim + DimName> Corrector<N> for Picard
+where
+    DefaultAllocator: Allocator<N>,
+{
+    fn name(&self) -> &'static str {
+        "PICARD"
+    }
+
+    fn correct(
+        &self,
+        _dynamics: &dyn OdeSystem<N>,
+        step: &CorrectionStep<N>,
+        _convergence_tol: f64,
+    ) -> Result<OVector<f64, N>, &'static str> {
+        Ok(step.y_prev + step.quadrature)
+    }
+
+    fn increment(
+        &self,
+        _dynamics: &dyn OdeSystem<N>,
+        step: &CorrectionStep<N>,
+        _convergence_tol: f64,
+    ) -> Result<OVector<f64, N>, &'static str> {
+        Ok(step.quadrature.clone())
+    }
+}
+
+pub struct CorrectorThread<N: SolverDim, S: OdeSystem<N>>
 where
-    DefaultAllocator: Allocator<f64, N>
-        + Allocator<f64, U1, N>
-        + Allocator<f64, N, N>
-        + Allocator<f64, <N as DimMin<N>>::Output, N>
-        + Allocator<f64, <N as DimMin<N>>::Output>
-        + Allocator<f64, N, <N as DimMin<N>>::Output>
-        + Allocator<f64, <<N as DimMin<N>>::Output as DimSub<U1>>::Output>,
-    <N as DimMin<N>>::Output: DimName,
-    <N as DimMin<N>>::Output: DimSub<U1>,
-    <DefaultAllocator as Allocator<f64, N>>::Buffer: Send + Sync,
+    DefaultAllocator: SolverAllocator<N>,
 {
     // Order, M, of the polynomial fit to use for quadrature. Requires M+1 points
     pub poly_order: usize,
     // Dynamics function used for the initial value problem
-    dynamics: fn(f64, &VectorN<f64, N>) -> VectorN<f64, N>,
+    dynamics: S,
+    // Update formula applied to each step
+    corrector: Arc<dyn Corrector<N>>,
     // Corrected Estimates of the IVP solutions
-    y_ests: VecDeque<VectorN<f64, N>>,
+    y_ests: VecDeque<OVector<f64, N>>,
     // Evaluations of the Dynamics function at the final corrected estimate
-    fxn_evals: VecDeque<VectorN<f64, N>>,
+    fxn_evals: VecDeque<OVector<f64, N>>,
     // Times at which function evals occur
     times: VecDeque<f64>,
     // Handle for recieving messages from channel
-    pub rx: Receiver<IVPSolMsg<N>>,
+    pub rx: LevelReceiver<N>,
     // Handle for sending messages on channel
-    pub tx: Sender<IVPSolMsg<N>>,
+    pub tx: LevelSender<N>,
     // Thread Number. An ID for helping with debugging
     pub id: u32,
-    // Convergence tolerance for Newton solver used in the backward euler step
+    // Convergence tolerance for any Newton solve of the update formula
     convergence_tol: f64,
+    // Requested levels and prior corrections of the points buffered during initialization
+    init_info: VecDeque<(usize, Vec<f64>)>,
+    // Whether the stencil has been filled since the last (re)start
+    initialized: bool,
+    // Time in seconds spent waiting for input
+    idle: f64,
+    // Start of the current wait for input when polled
+    waiting_since: Option<Instant>,
+    // Whether the level has terminated
+    finished: bool,
+    // Whether the level (or one before it) failed
+    failed: bool,
+    // Idle times of all levels, where this level reports its own on termination
+    idle_out: IdleTimes,
+    // Low order parts of the corrected estimates (aligned with y_ests) when the
+    // correction integrals are carried in double-double
+    #[cfg(feature = "double_double")]
+    y_lo: Option<VecDeque<OVector<f64, N>>>,
+}
+
+// Outcome of polling a correction level for input
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PollResult {
+    // A message was processed
+    Worked,
+    // No message was waiting
+    Idle,
+    // The level has terminated
+    Done,
 }
 
-impl<N: Dim + DimName + DimMin<N> + DimSub<U1>> Corrector<N>
+impl<N: SolverDim, S: OdeSystem<N>> CorrectorThread<N, S>
 where
-    DefaultAllocator: Allocator<f64, N>
-        + Allocator<f64, U1, N>
-        + Allocator<f64, N, N>
-        + Allocator<f64, <N as DimMin<N>>::Output, N>
-        + Allocator<f64, <N as DimMin<N>>::Output>
-        + Allocator<f64, N, <N as DimMin<N>>::Output>
-        + Allocator<f64, <<N as DimMin<N>>::Output as DimSub<U1>>::Output>,
-    <N as DimMin<N>>::Output: DimName,
-    <N as DimMin<N>>::Output: DimSub<U1>,
-    <DefaultAllocator as Allocator<f64, N>>::Buffer: Send + Sync,
+    DefaultAllocator: SolverAllocator<N>,
 {
+    // Level `id` of the setup, reading its input from rx and sending its output on tx
     pub fn new(
-        poly_order: usize,
-        dynamics: fn(f64, &VectorN<f64, N>) -> VectorN<f64, N>,
-        y_0: &VectorN<f64, N>,
-        dy_0: &VectorN<f64, N>,
-        t_0: f64,
-        rx: Receiver<IVPSolMsg<N>>,
-        tx: Sender<IVPSolMsg<N>>,
+        dynamics: S,
+        setup: &LevelSetup<N>,
+        rx: LevelReceiver<N>,
+        tx: LevelSender<N>,
         id: u32,
-        convergence_tol: f64,
+        idle_out: IdleTimes,
     ) -> Self {
-        let mut y_ests: VecDeque<VectorN<f64, N>> = VecDeque::from(vec![y_0.clone()]);
+        let poly_order = setup.poly_order;
+        let mut y_ests: VecDeque<OVector<f64, N>> = VecDeque::from(vec![setup.y_0.clone()]);
         y_ests.reserve_exact(poly_order);
-        let mut fxn_evals: VecDeque<VectorN<f64, N>> = VecDeque::from(vec![dy_0.clone()]);
+        let mut fxn_evals: VecDeque<OVector<f64, N>> = VecDeque::from(vec![setup.dy_0.clone()]);
         fxn_evals.reserve_exact(poly_order);
-        let mut times: VecDeque<f64> = VecDeque::from(vec![t_0]);
+        let mut times: VecDeque<f64> = VecDeque::from(vec![setup.t_0]);
         times.reserve_exact(poly_order);
 
-        Corrector {
+        CorrectorThread {
             poly_order,
             dynamics,
+            corrector: setup.corrector.clone(),
             y_ests,
             fxn_evals,
             times,
             rx,
             tx,
             id,
-            convergence_tol,
+            convergence_tol: setup.convergence_tol,
+            init_info: VecDeque::new(),
+            initialized: false,
+            idle: 0.0,
+            waiting_since: None,
+            finished: false,
+            failed: false,
+            idle_out,
+            #[cfg(feature = "double_double")]
+            y_lo: None,
         }
+        .with_double_double(setup.double_double)
     }
 
+    // Carries the correction integrals of the level in double-double when enabled
+    #[cfg(feature = "double_double")]
+    pub fn with_double_double(mut self, enabled: bool) -> Self {
+        self.y_lo = match enabled {
+            true => Some(
+                self.y_ests
+                    .iter()
+                    .map(|_| OVector::<f64, N>::zeros())
+                    .collect(),
+            ),
+            false => None,
+        };
+        self
+    }
+
+    // Double-double accumulation needs the `double_double` feature
+    #[cfg(not(feature = "double_double"))]
+    pub fn with_double_double(self, _enabled: bool) -> Self {
+        self
+    }
+
+    // Whether this corrector should correct (rather than pass through) an estimate
+    // for which `levels` correction levels were requested
+    fn active(&self, levels: usize) -> bool {
+        (self.id as usize) < levels
+    }
+
+    // Processes messages, blocking until each arrives, until terminated
     pub fn run(&mut self) -> Result<(), &'static str> {
-        // initialization loop
         loop {
-            let data = match self.rx.recv() {
-                Ok(msg) => match msg {
-                    IVPSolMsg::PROCESS(data) => data,
-                    IVPSolMsg::TERMINATE => {
-                        break;
-                    }
-                },
-                Err(_) => {
+            let wait = Instant::now();
+            let msg = match self.rx.recv() {
+                Some(msg) => msg,
+                None => {
                     break;
                 }
             };
-            match self.initialize(data) {
-                Ok(i) => match i {
-                    0 => continue,
-                    1 => break,
-                    _ => break,
-                },
-                Err(_) => break,
+            self.idle += wait.elapsed().as_secs_f64();
+            if self.handle(msg)? {
+                break;
             }
         }
-        // Main processing loop
+        Ok(())
+    }
+
+    // Processes messages as they arrive until terminated, yielding to the executor
+    // while waiting
+    #[cfg(feature = "tokio")]
+    pub async fn run_async(&mut self) -> Result<(), &'static str> {
         loop {
-            let data = match self.rx.recv() {
-                Ok(msg) => match msg {
-                    IVPSolMsg::PROCESS(data) => data,
-                    IVPSolMsg::TERMINATE => {
-                        self.tx
-                            .send(IVPSolMsg::TERMINATE)
-                            .expect("Failure to send TERMINATE message to downstream threads");
-                        break;
-                    }
-                },
-                Err(_) => {
+            let wait = Instant::now();
+            let msg = match self.rx.recv_async().await {
+                Some(msg) => msg,
+                None => {
                     break;
                 }
             };
-            self.correct(data)?;
+            self.idle += wait.elapsed().as_secs_f64();
+            if self.handle(msg)? {
+                break;
+            }
         }
         Ok(())
     }
 
-    fn initialize(&mut self, data: IVPSolData<N>) -> Result<u32, &'static str> {
+    // Processes the next message if one is waiting, without blocking
+    pub fn poll(&mut self) -> Result<PollResult, &'static str> {
+        if self.finished {
+            return Ok(PollResult::Done);
+        }
+        match self.rx.try_recv() {
+            Ok(msg) => {
+                if let Some(wait) = self.waiting_since.take() {
+                    self.idle += wait.elapsed().as_secs_f64();
+                }
+                self.finished = self.handle(msg)?;
+                Ok(PollResult::Worked)
+            }
+            Err(TryRecvError::Empty) => {
+                self.waiting_since.get_or_insert_with(Instant::now);
+                Ok(PollResult::Idle)
+            }
+            Err(TryRecvError::Disconnected) => {
+                self.finished = true;
+                Ok(PollResult::Done)
+            }
+        }
+    }
+
+    // Processes a message. Returns whether it terminated the level. A failure
+    // of the level is passed on as a FAILED message
+    fn handle(&mut self, msg: IVPSolMsg<N>) -> Result<bool, &'static str> {
+        match msg {
+            IVPSolMsg::FAILED(err) => self.fail(err)?,
+            IVPSolMsg::TERMINATE => {
+                if !self.failed {
+                    if let Err(err) = self.flush() {
+                        self.fail(err)?;
+                    }
+                }
+                // reported before passing the terminate on, so every level has
+                // reported once it reaches the predictor
+                self.idle_out.lock().unwrap()[self.id as usize] = self.idle;
+                self.send(IVPSolMsg::TERMINATE)?;
+                return Ok(true);
+            }
+            _ if self.failed => (),
+            msg => {
+                if let Err(err) = self.process(msg) {
+                    self.fail(err)?;
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    // Passes the first failure of the level (or one before it) on
+    fn fail(&mut self, err: &'static str) -> Result<(), &'static str> {
+        if self.failed {
+            return Ok(());
+        }
+        self.failed = true;
+        self.send(IVPSolMsg::FAILED(err))
+    }
+
+    // Sends a message downstream. Errors once the receiver is gone, which ends the
+    // level
+    fn send(&self, msg: IVPSolMsg<N>) -> Result<(), &'static str> {
+        self.tx
+            .send(msg)
+            .map_err(|_| "[RIDC] The next level or the predictor stopped listening")
+    }
+
+    // Processes a message carrying work
+    fn process(&mut self, msg: IVPSolMsg<N>) -> Result<(), &'static str> {
+        match msg {
+            IVPSolMsg::PROCESS(data) => {
+                if self.initialized {
+                    self.correct(data)?;
+                } else {
+                    self.initialize(data)?;
+                }
+            }
+            IVPSolMsg::FLUSH => {
+                self.flush()?;
+                self.send(IVPSolMsg::FLUSH)?;
+            }
+            IVPSolMsg::RESTART(data) => {
+                self.restart(&data);
+                self.send(IVPSolMsg::RESTART(data))?;
+            }
+            IVPSolMsg::FAILED(_) | IVPSolMsg::TERMINATE => (),
+        }
+        Ok(())
+    }
+
+    fn initialize(&mut self, data: IVPSolData<N>) -> Result<(), &'static str> {
         self.y_ests.push_front(data.y_nxt);
         self.fxn_evals.push_front(data.dy_nxt);
         self.times.push_front(data.t_nxt);
+        self.init_info.push_front((data.levels, data.corrections));
+        #[cfg(feature = "double_double")]
+        if let Some(y_lo) = &mut self.y_lo {
+            y_lo.push_front(OVector::<f64, N>::zeros());
+        }
 
         if self.y_ests.len() == self.poly_order + 1 {
             self.first_correction()?;
-            Ok(1)
-        } else {
-            Ok(0)
         }
+        Ok(())
     }
 
-    fn first_correction(&mut self) -> Result<(), &'static str> {
-        let gen_weights = get_weights(&self.times);
+    // Corrects the estimates buffered while initializing (with a smaller stencil)
+    // so that a slab shorter than the stencil still produces all of its solutions
+    fn flush(&mut self) -> Result<(), &'static str> {
+        if !self.initialized && self.y_ests.len() > 1 {
+            self.first_correction()?;
+        }
+        Ok(())
+    }
+
+    // Restarts the correction history from the (fully corrected) initial point of
+    // a new slab
+    fn restart(&mut self, data: &IVPSolData<N>) {
+        // a level restarted from its own solution keeps the low order part of it
+        #[cfg(feature = "double_double")]
+        if let Some(y_lo) = &mut self.y_lo {
+            let lo = match data.y_nxt == self.y_ests[0] {
+                true => y_lo[0].clone(),
+                false => OVector::<f64, N>::zeros(),
+            };
+            y_lo.clear();
+            y_lo.push_front(lo);
+        }
+        self.y_ests.clear();
+        self.y_ests.push_front(data.y_nxt.clone());
+        self.fxn_evals.clear();
+        self.fxn_evals.push_front(data.dy_nxt.clone());
+        self.times.clear();
+        self.times.push_front(data.t_nxt);
+        self.init_info.clear();
+        self.initialized = false;
+    }
 
-        let l = self.poly_order + 1;
+    fn first_correction(&mut self) -> Result<(), &'static str> {
+        let l = self.y_ests.len();
         for i in 1..l {
             // set correction time interval
             let t_0 = self.times[l - i];
             let t_n = self.times[l - i - 1];
-            let dt = t_n - t_0;
-
-            // Generate quadrature solution over the selected interval
-            let x_pows = get_x_pow(t_0, t_n, self.poly_order);
-            let spec_weights = specific_weights(x_pows, &gen_weights);
-            let quadrature: VectorN<f64, N> = spec_weights
-                .iter()
-                .zip(self.fxn_evals.iter())
-                .map(|(w, y)| *w * y)
-                .sum();
-
-            // set up and solve implicit solution
-            let root_problem = |y_n: &VectorN<f64, N>| {
-                y_n - (&self.y_ests[l - i] + dt * (self.dynamics)(t_n, y_n)
-                    - dt * &self.fxn_evals[l - i - 1]
-                    + &quadrature)
-            };
 
-            let root_sol = newton_raphson_broyden(
-                root_problem,
-                self.y_ests[l - i - 1].clone(),
-                self.convergence_tol,
-            )?;
+            let (levels, mut corrections) = self.init_info[l - i - 1].clone();
+            let mut dy_nxt = self.fxn_evals[l - i - 1].clone();
+            if self.active(levels) {
+                // Generate quadrature solution over the selected interval
+                let spec_weights = interval_weights(&self.times, t_0, t_n);
+                let root_sol = self.update(&spec_weights, l - i)?;
 
-            self.y_ests[l - i - 1] = root_sol;
-            self.fxn_evals[l - i - 1] = (self.dynamics)(t_n, &self.y_ests[l - i - 1]);
+                corrections.push((&root_sol - &self.y_ests[l - i - 1]).amax());
+                self.y_ests[l - i - 1] = root_sol;
+                // the stored evaluations stay at the previous level for the quadrature
+                dy_nxt = self.dynamics.dynamics(t_n, &self.y_ests[l - i - 1]);
+                check_failure(&self.dynamics)?;
+            }
 
             let data_new = IVPSolMsg::PROCESS(IVPSolData {
                 y_nxt: self.y_ests[l - i - 1].clone(),
-                dy_nxt: self.fxn_evals[l - i - 1].clone(),
+                dy_nxt,
                 t_nxt: t_n,
                 weights: None,
+                levels,
+                corrections,
             });
-            self.tx
-                .send(data_new)
-                .expect("Could Not Send message from thread!");
+            self.send(data_new)?;
         }
+        self.init_info.clear();
+        self.initialized = true;
         Ok(())
     }
 
@@ -226,42 +587,146 @@ where
         self.y_ests.push_front(data.y_nxt);
         self.fxn_evals.push_front(data.dy_nxt);
         self.times.push_front(data.t_nxt);
+        #[cfg(feature = "double_double")]
+        if let Some(y_lo) = &mut self.y_lo {
+            y_lo.pop_back();
+            y_lo.push_front(OVector::<f64, N>::zeros());
+        }
 
-        // compute correction
-        let quadrature: VectorN<f64, N> = data
-            .weights
-            .clone()
-            .unwrap()
-            .iter()
-            .zip(self.fxn_evals.iter())
-            .map(|(w, y)| *w * y)
-            .sum();
-
-        let dt = self.times[0] - self.times[1];
-
-        let root_problem = |y_n: &VectorN<f64, N>| {
-            y_n - (&self.y_ests[1] + dt * (self.dynamics)(self.times[0], y_n)
-                - dt * &self.fxn_evals[0]
-                + &quadrature)
-        };
-
-        self.y_ests[0] =
-            newton_raphson_broyden(root_problem, self.y_ests[0].clone(), self.convergence_tol)
-                .expect("Couldn't converge to solution");
+        let mut corrections = data.corrections;
+        let mut dy_nxt = self.fxn_evals[0].clone();
+        if self.active(data.levels) {
+            // compute correction
+            let root_sol = self.update(data.weights.as_ref().unwrap(), 1)?;
+            corrections.push((&root_sol - &self.y_ests[0]).amax());
+            self.y_ests[0] = root_sol;
 
-        // re-evaluate the dynamics function
-        self.fxn_evals[0] = (self.dynamics)(self.times[0], &self.y_ests[0]);
+            // re-evaluate the dynamics function. The stored evaluations stay at the
+            // previous level for the quadrature
+            dy_nxt = self.dynamics.dynamics(self.times[0], &self.y_ests[0]);
+            check_failure(&self.dynamics)?;
+        }
 
         let data_new = IVPSolMsg::PROCESS(IVPSolData {
             y_nxt: self.y_ests[0].clone(),
-            dy_nxt: self.fxn_evals[0].clone(),
+            dy_nxt,
             t_nxt: self.times[0].clone(),
             weights: data.weights,
+            levels: data.levels,
+            corrections,
         });
-        self.tx
-            .send(data_new)
-            .expect("Could not send message from thread");
+        self.send(data_new)
+    }
 
-        Ok(())
+    // Corrected solution at the estimate following `prev` (index prev - 1), from the
+    // quadrature of the stored evaluations with the given weights
+    fn update(&mut self, weights: &[f64], prev: usize) -> Result<OVector<f64, N>, &'static str> {
+        let step = |quadrature| CorrectionStep {
+            t_prev: self.times[prev],
+            t_n: self.times[prev - 1],
+            y_prev: &self.y_ests[prev],
+            y_est: &self.y_ests[prev - 1],
+            dy_prev: &self.fxn_evals[prev],
+            dy_est: &self.fxn_evals[prev - 1],
+            quadrature,
+        };
+
+        #[cfg(feature = "double_double")]
+        if let Some(y_lo) = &self.y_lo {
+            let quadrature = double_double::weighted_sum(weights, self.fxn_evals.iter());
+            let increment = self.corrector.increment(
+                &self.dynamics,
+                &step(&quadrature.hi),
+                self.convergence_tol,
+            )?;
+            let mut sol = DoubleDouble::new(self.y_ests[prev].clone(), y_lo[prev].clone());
+            sol.add(&increment);
+            sol.add(&quadrature.lo);
+            self.y_lo.as_mut().unwrap()[prev - 1] = sol.lo;
+            return Ok(sol.hi);
+        }
+
+        let quadrature: OVector<f64, N> = weighted_sum(weights, self.fxn_evals.iter());
+        self.corrector
+            .correct(&self.dynamics, &step(&quadrature), self.convergence_tol)
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ridc::base::RIDCIntegratorFixed;
+    use crate::ridc::common::IntegOptionsParallel;
+    use crate::ridc::predictor::Predictor;
+    use crate::runge_kutta::rk_simp::RK2;
+    use na::{Vector1, U1};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // Explicit correction counting its applications
+    struct Counted(AtomicUsize);
+
+    impl<N: Dim + DimName> Corrector<N> for Counted
+    where
+        DefaultAllocator: Allocator<N>,
+    {
+        fn name(&self) -> &'static str {
+            "COUNTED"
+        }
+
+        fn correct(
+            &self,
+            dynamics: &dyn OdeSystem<N>,
+            step: &CorrectionStep<N>,
+            convergence_tol: f64,
+        ) -> Result<OVector<f64, N>, &'static str> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            ExplicitEuler.correct(dynamics, step, convergence_tol)
+        }
+    }
+
+    #[test]
+    fn test_correction_schemes() {
+        // y(t) = exp(sin(t))
+        let fxn = |t: f64, y: &Vector1<f64>| y * t.cos();
+        let (t_end, dt) = (2.0_f64, 0.05);
+        let truth = Vector1::new(t_end.sin().exp());
+        let mut y = Vector1::new(1.0);
+        for i in 0..40 {
+            y = RK2.predict(&fxn, i as f64 * dt, &y, dt).value;
+        }
+        let predicted = (y - truth).amax();
+
+        let run = |corrector: Arc<dyn Corrector<U1>>| {
+            let options = IntegOptionsParallel {
+                corrector_order: Some(2),
+                corrector: Some(corrector),
+                ..IntegOptionsParallel::default()
+            };
+            let ans = RK2
+                .parallel_integrator(fxn, 0.0, &Vector1::new(1.0), t_end, dt, options)
+                .unwrap();
+            (ans.last_y() - truth).amax()
+        };
+        let implicit = run(Arc::new(ImplicitEuler));
+        let explicit = run(Arc::new(ExplicitEuler));
+        let picard = run(Arc::new(Picard));
+        println!(
+            "PREDICTED {:e} | IMPLICIT {:e} | EXPLICIT {:e} | PICARD {:e}",
+            predicted, implicit, explicit, picard
+        );
+        assert!(implicit < 1e-2 * predicted);
+        assert!(explicit < 1e-2 * predicted);
+        assert!(picard < predicted);
+
+        // a custom formula is applied by every level to every step
+        let counted = Arc::new(Counted(AtomicUsize::new(0)));
+        assert_eq!(run(counted.clone()), explicit);
+        assert_eq!(counted.0.load(Ordering::SeqCst), 2 * 40);
+
+        // the default is the implicit correction
+        assert_eq!(run(Arc::new(ImplicitEuler)), implicit);
+        let default = IntegOptionsParallel::<U1>::default();
+        assert!(default.corrector.is_none());
     }
 }
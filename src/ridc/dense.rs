@@ -0,0 +1,229 @@
+/// Dense Output of RIDC Solutions (ridc/dense)
+///
+/// The correction levels raise the order of the solution at the steps above that
+/// of the predictor, so interpolating between the steps with a low order
+/// method (linear, or the cubic hermite of `runge_kutta::defect`) throws most of it
+/// away on long steps. `HermiteBirkhoff` interpolates the corrected solution with
+/// a polynomial matching the states and the dynamics at the m steps nearest to the
+/// requested time, which is of degree 2 m - 1 and accurate to O(h^(2 m)). m is
+/// chosen from the order of the corrected solution, so the dense output keeps that
+/// order.
+///
+/// The windows of steps stay inside a restart window (see `IntegResult::restarts`),
+/// as the correction history does: the dynamics may jump at a restart (breakpoints
+/// and rough steps end the restart window), so the interpolant on the left of one
+/// uses only the value of the state there and not the dynamics, which belong to
+/// the right side. Matching values at some nodes and derivatives at others is what
+/// makes it Hermite-Birkhoff rather than plain Hermite interpolation. The
+/// polynomial is evaluated in newton form with confluent divided differences.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DefaultAllocator, Dim, DimName, OVector};
+
+// local imports
+use crate::runge_kutta::common::IntegResult;
+use crate::systems::OdeSystem;
+
+// === End Imports ===
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HermiteBirkhoff<N: Dim + DimName>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    // Times of the solution
+    times: Vec<f64>,
+    // Corrected states
+    states: Vec<OVector<f64, N>>,
+    // Dynamics at the corrected states
+    derivs: Vec<OVector<f64, N>>,
+    // Indices of the first step of every restart window
+    windows: Vec<usize>,
+    // Number of steps the interpolant is built on
+    nodes: usize,
+}
+
+impl<N: Dim + DimName> HermiteBirkhoff<N>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    // Interpolant of a RIDC solution accurate to (at least) `order`. Evaluates the
+    // dynamics once at every step
+    pub fn new<S: OdeSystem<N> + ?Sized>(
+        fxn: &S,
+        results: &IntegResult<N>,
+        order: usize,
+    ) -> Result<Self, &'static str> {
+        if order == 0 {
+            return Err("[DENSE] Order of the interpolant must be at least one");
+        }
+        if results.times.len() < 2 {
+            return Err("[DENSE] Interpolation needs a solution of at least one step");
+        }
+        let mut windows = vec![0];
+        for t_r in &results.restarts {
+            if let Some(k) = results.times.iter().position(|t| t == t_r) {
+                if k > 0 && k + 1 < results.times.len() {
+                    windows.push(k);
+                }
+            }
+        }
+        windows.dedup();
+        Ok(HermiteBirkhoff {
+            derivs: results
+                .times
+                .iter()
+                .zip(&results.states)
+                .map(|(t, y)| fxn.dynamics(*t, y))
+                .collect(),
+            times: results.times.clone(),
+            states: results.states.clone(),
+            windows,
+            nodes: order.div_ceil(2).max(2),
+        })
+    }
+
+    // Solution at t. None outside of the integrated interval
+    pub fn eval(&self, t: f64) -> Option<OVector<f64, N>> {
+        let times = &self.times;
+        let forward = times[times.len() - 1] >= times[0];
+        let ahead = |t_k: f64| match forward {
+            true => t_k >= t,
+            false => t_k <= t,
+        };
+        // end of the step containing t
+        let k = match times.iter().position(|t_k| ahead(*t_k))? {
+            0 if times[0] == t => return Some(self.states[0].clone()),
+            0 => return None,
+            k => k,
+        };
+
+        // steps of the restart window containing the step, and the nodes around it
+        let w = self
+            .windows
+            .iter()
+            .rposition(|start| *start < k)
+            .unwrap_or(0);
+        let first = self.windows[w];
+        let last = match self.windows.get(w + 1) {
+            Some(next) => *next,
+            None => times.len() - 1,
+        };
+        let m = self.nodes.min(last - first + 1);
+        let lo = (k - 1)
+            .saturating_sub((m - 2) / 2)
+            .max(first)
+            .min(last + 1 - m);
+        // the dynamics at a restart belong to the window on its right
+        let closed = self.windows.get(w + 1).is_some();
+        let mut conditions = Vec::with_capacity(2 * m);
+        for (i, t_i) in times.iter().enumerate().skip(lo).take(m) {
+            conditions.push((*t_i, i, false));
+            if !(closed && i == last) {
+                conditions.push((*t_i, i, true));
+            }
+        }
+        Some(self.newton(&conditions, t))
+    }
+
+    // Evaluates at t the polynomial through the conditions (time, step, whether
+    // the condition is on the derivative) with confluent divided differences. A
+    // derivative condition follows the value condition of its step
+    fn newton(&self, conditions: &[(f64, usize, bool)], t: f64) -> OVector<f64, N> {
+        let n = conditions.len();
+        let mut table: Vec<OVector<f64, N>> = conditions
+            .iter()
+            .map(|(_, i, _)| self.states[*i].clone())
+            .collect();
+        let mut coeffs = vec![table[0].clone()];
+        for level in 1..n {
+            for j in (level..n).rev() {
+                let (z_j, i_j, deriv) = conditions[j];
+                let z_lo = conditions[j - level].0;
+                table[j] = match level == 1 && deriv {
+                    true => self.derivs[i_j].clone(),
+                    false => (&table[j] - &table[j - 1]) / (z_j - z_lo),
+                };
+            }
+            coeffs.push(table[level].clone());
+        }
+        let mut value = coeffs[n - 1].clone();
+        for j in (0..n - 1).rev() {
+            value = value * (t - conditions[j].0) + &coeffs[j];
+        }
+        value
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ridc::base::RIDCIntegratorFixed;
+    use crate::ridc::common::IntegOptionsParallel;
+    use crate::runge_kutta::common::IntegOptions;
+    use crate::runge_kutta::fixed::FixedStep;
+    use crate::runge_kutta::rk_simp::RK4;
+    use na::{Vector2, U2};
+
+    #[test]
+    fn test_hermite_birkhoff() {
+        // harmonic oscillator, y = (cos t, -sin t)
+        let sho = |_t: f64, y: &Vector2<f64>| Vector2::new(y[1], -y[0]);
+        let truth = |t: f64| Vector2::new(t.cos(), -t.sin());
+        let y_0 = Vector2::new(1.0, 0.0);
+        let worst = |dense: &HermiteBirkhoff<U2>| {
+            (0..400)
+                .map(|i| {
+                    let t = 0.01 * i as f64 + 0.005;
+                    (dense.eval(t).unwrap() - truth(t)).amax()
+                })
+                .fold(0.0, f64::max)
+        };
+
+        // exact states: the error is that of the interpolation alone, O(h^(2 m))
+        let mut exact = RK4
+            .integrate(sho, 0.0, y_0, 4.0, 0.2, IntegOptions::default())
+            .unwrap();
+        exact.states = exact.times.iter().map(|t| truth(*t)).collect();
+        let errs: Vec<f64> = [4, 6, 8]
+            .iter()
+            .map(|order| worst(&HermiteBirkhoff::new(&sho, &exact, *order).unwrap()))
+            .collect();
+        println!("INTERPOLATION ORDER 4, 6, 8 | {:?}", errs);
+        assert!(errs[0] > 30.0 * errs[1] && errs[1] > 30.0 * errs[2]);
+        let dense = HermiteBirkhoff::new(&sho, &exact, 6).unwrap();
+        assert_eq!(dense.eval(0.2).unwrap(), exact.states[1]);
+        assert!(dense.eval(4.1).is_none() && dense.eval(-0.1).is_none());
+        assert!(HermiteBirkhoff::new(&sho, &exact, 0).is_err());
+
+        // on a corrected solution the dense output is as accurate as the steps, also
+        // across restarts, where the cubic adds its own error
+        let options = IntegOptionsParallel {
+            corrector_order: Some(3),
+            restart_length: Some(7),
+            ..IntegOptionsParallel::default()
+        };
+        let ans = RK4
+            .parallel_integrator(sho, 0.0, &y_0, 4.0, 0.2, options)
+            .unwrap();
+        assert!(!ans.restarts.is_empty());
+        let at_steps = ans
+            .times
+            .iter()
+            .zip(&ans.states)
+            .map(|(t, y)| (y - truth(*t)).amax())
+            .fold(0.0, f64::max);
+        let cubic = worst(&HermiteBirkhoff::new(&sho, &ans, 4).unwrap());
+        let high = worst(&HermiteBirkhoff::new(&sho, &ans, 8).unwrap());
+        println!(
+            "STEPS {:e} | CUBIC {:e} | ORDER 8 {:e}",
+            at_steps, cubic, high
+        );
+        assert!(high < 1.1 * at_steps);
+        assert!(cubic > 1.3 * high);
+    }
+}
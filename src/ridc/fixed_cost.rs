@@ -0,0 +1,235 @@
+/// Fixed Cost Deferred Correction (ridc/fixed_cost)
+///
+/// Integrator with a bounded, input independent amount of work per step, for
+/// control loops and flight software where the latency of a step must be known
+/// ahead of time. The adaptive integrators retry rejected steps, the newton solves
+/// of the implicit correctors iterate until converged and the RIDC pipeline runs on
+/// threads, all of which make the cost of a step depend on the state. `FixedCost`
+/// instead takes each step as a serial deferred correction: the step is split into
+/// `nodes` equal substeps, an implicit (backward euler) prediction is followed by
+/// exactly `sweeps` implicit correction sweeps, and every implicit solve makes
+/// exactly `newton_iters` simplified newton iterations with the iteration matrix
+/// factored once per step (from a finite difference jacobian at the start of the
+/// step). Nothing is retried or checked for convergence, so the number of dynamics
+/// evaluations per step is fixed (`evals_per_step`).
+///
+/// The accuracy is whatever the work buys: up to order min(sweeps + 1, nodes + 1)
+/// when the newton iterations converge. What was achieved is reported in the
+/// `defects` of the result, the residual of the collocation equations left at each
+/// step (the largest component, over the nodes, of the difference between the
+/// solution and the start of the step plus the quadrature of the dynamics), which
+/// the converged deferred correction drives to zero.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::{DefaultAllocator, OMatrix, OVector};
+
+// local imports
+use crate::lagrange::quadrature::interval_weights;
+use crate::runge_kutta::common::{approach_end, IntegError, IntegResult};
+use crate::systems::fallible::check_failure;
+use crate::systems::OdeSystem;
+use crate::utils::finite_diff::fdiff_jacobian;
+use crate::utils::solver_dim::{SolverAllocator, SolverDim};
+
+// Standard library imports
+use std::collections::VecDeque;
+
+// === End Imports ===
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FixedCost {
+    // Number of substeps each step is split into
+    nodes: usize,
+    // Number of correction sweeps after the prediction
+    sweeps: usize,
+    // Number of newton iterations of each implicit solve
+    newton_iters: usize,
+    // Quadrature weights over each substep of the polynomial through the nodes, on
+    // a step of unit length
+    weights: Vec<Vec<f64>>,
+}
+
+impl FixedCost {
+    pub fn new(nodes: usize, sweeps: usize, newton_iters: usize) -> Result<Self, &'static str> {
+        if nodes == 0 || nodes > 8 {
+            return Err("[FIXED COST] Between 1 and 8 substeps (equispaced nodes)");
+        }
+        if newton_iters == 0 {
+            return Err("[FIXED COST] The implicit solves need a newton iteration");
+        }
+        let theta: VecDeque<f64> = (0..=nodes).map(|m| m as f64 / nodes as f64).collect();
+        let weights = (0..nodes)
+            .map(|m| interval_weights(&theta, theta[m], theta[m + 1]))
+            .collect();
+        Ok(FixedCost {
+            nodes,
+            sweeps,
+            newton_iters,
+            weights,
+        })
+    }
+
+    // Dynamics evaluations of every step of a system of dimension `dim`: the
+    // jacobian, the prediction, the dynamics at the nodes and the sweeps
+    pub fn evals_per_step(&self, dim: usize) -> usize {
+        let solves = self.nodes * self.newton_iters;
+        2 * dim + solves + self.nodes + 1 + self.sweeps * (solves + self.nodes)
+    }
+
+    // Takes a step of length h from y_0 at t, returning the solution and the defect
+    pub fn step<N: SolverDim, S: OdeSystem<N>>(
+        &self,
+        fxn: &S,
+        t: f64,
+        y_0: &OVector<f64, N>,
+        h: f64,
+    ) -> Result<(OVector<f64, N>, f64), &'static str>
+    where
+        DefaultAllocator: SolverAllocator<N>,
+    {
+        let dt = h / self.nodes as f64;
+        let times: Vec<f64> = (0..=self.nodes).map(|m| t + m as f64 * dt).collect();
+        let jac = fdiff_jacobian(&|y: &OVector<f64, N>| fxn.dynamics(t, y), y_0, y_0);
+        let iteration = (OMatrix::<f64, N, N>::identity() - jac * dt).lu();
+        // y = base + dt f(t_new, y) with a fixed number of simplified newton iterations
+        let solve = |t_new: f64, base: &OVector<f64, N>, guess: OVector<f64, N>| {
+            let mut y = guess;
+            for _ in 0..self.newton_iters {
+                let residual = &y - base - fxn.dynamics(t_new, &y) * dt;
+                y -= iteration
+                    .solve(&residual)
+                    .ok_or("[FIXED COST] Singular iteration matrix")?;
+            }
+            Ok::<_, &'static str>(y)
+        };
+
+        // prediction with backward euler over the substeps
+        let mut ys = vec![y_0.clone()];
+        for m in 0..self.nodes {
+            let next = solve(times[m + 1], &ys[m], ys[m].clone())?;
+            ys.push(next);
+        }
+        let mut fs: Vec<OVector<f64, N>> = (0..=self.nodes)
+            .map(|m| fxn.dynamics(times[m], &ys[m]))
+            .collect();
+
+        // implicit correction sweeps
+        for _ in 0..self.sweeps {
+            let mut corrected = vec![y_0.clone()];
+            for m in 0..self.nodes {
+                let base = self.quadrature(m, h, &fs) + &corrected[m] - &fs[m + 1] * dt;
+                let guess = &ys[m + 1] + &corrected[m] - &ys[m];
+                let next = solve(times[m + 1], &base, guess)?;
+                corrected.push(next);
+            }
+            ys = corrected;
+            for m in 1..=self.nodes {
+                fs[m] = fxn.dynamics(times[m], &ys[m]);
+            }
+        }
+
+        // residual of the collocation equations at the nodes
+        let mut integral = y_0.clone();
+        let mut defect: f64 = 0.0;
+        for m in 0..self.nodes {
+            integral += self.quadrature(m, h, &fs);
+            defect = defect.max((&integral - &ys[m + 1]).amax());
+        }
+        // the step is never retried, so any failure of the dynamics ends it
+        check_failure(fxn)?;
+        Ok((ys.pop().unwrap(), defect))
+    }
+
+    // Quadrature over substep m of a step of length h of the polynomial through fs
+    fn quadrature<N: SolverDim>(&self, m: usize, h: f64, fs: &[OVector<f64, N>]) -> OVector<f64, N>
+    where
+        DefaultAllocator: SolverAllocator<N>,
+    {
+        let mut sum = OVector::<f64, N>::zeros();
+        for (w, f) in self.weights[m].iter().zip(fs) {
+            sum += f * (w * h);
+        }
+        sum
+    }
+
+    // Integrates over span with steps of dt (the last one shortened to land on the
+    // end), recording the defect of every step
+    pub fn integrate<N: SolverDim, S: OdeSystem<N>>(
+        &self,
+        fxn: S,
+        t_0: f64,
+        y_0: OVector<f64, N>,
+        span: f64,
+        dt: f64,
+    ) -> Result<IntegResult<N>, IntegError>
+    where
+        DefaultAllocator: SolverAllocator<N>,
+    {
+        if dt == 0.0 || dt.is_nan() {
+            return Err("[FIXED COST] Step size must be nonzero".into());
+        }
+        let t_end = t_0 + span;
+        let mut results = IntegResult::new(t_0, y_0);
+        let mut last = span == 0.0;
+        while !last {
+            let h;
+            (h, last) = approach_end(results.t, t_end, dt);
+            let (y, defect) = self.step(&fxn, results.t, results.last_y(), h)?;
+            results.add_val(h, y);
+            results.defects.push(defect);
+        }
+        results.land_on(t_end);
+        Ok(results)
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::systems::fallible::{EvalFailure, Fallible};
+    use na::Vector1;
+    use std::cell::Cell;
+
+    #[test]
+    fn test_fixed_cost() {
+        // prothero-robinson y' = -100 (y - cos t) - sin t, stiff with the smooth
+        // solution cos t: every step costs the same number of evaluations, and
+        // each sweep buys accuracy and a smaller defect
+        let evals = Cell::new(0);
+        let fxn = |t: f64, y: &Vector1<f64>| {
+            evals.set(evals.get() + 1);
+            Vector1::new(-100.0 * (y[0] - t.cos()) - t.sin())
+        };
+        let run = |sweeps: usize| {
+            let stepper = FixedCost::new(3, sweeps, 2).unwrap();
+            evals.set(0);
+            let ans = stepper
+                .integrate(fxn, 0.0, Vector1::new(1.0), 2.0, 0.1)
+                .unwrap();
+            assert_eq!(ans.defects.len(), 20);
+            assert_eq!(evals.get(), 20 * stepper.evals_per_step(1));
+            let error = (ans.last_y()[0] - 2.0_f64.cos()).abs();
+            let defect = ans.defects.iter().cloned().fold(0.0, f64::max);
+            (error, defect)
+        };
+        let (error_0, defect_0) = run(0);
+        let (error_3, defect_3) = run(3);
+        assert!(error_3 < 1e-5 && error_3 < 0.1 * error_0);
+        assert!(defect_3 < 1e-4 && defect_3 < 0.1 * defect_0);
+        assert!(FixedCost::new(0, 1, 1).is_err() && FixedCost::new(3, 1, 0).is_err());
+
+        // steps are never retried, so any failure of the dynamics ends the run
+        let table = |t: f64, y: &Vector1<f64>| match t > 1.0 {
+            true => Err(EvalFailure::Retry),
+            false => Ok(Vector1::new(-y[0])),
+        };
+        let err = FixedCost::new(3, 2, 2)
+            .unwrap()
+            .integrate(Fallible::new(table), 0.0, Vector1::new(1.0), 2.0, 0.1)
+            .unwrap_err();
+        assert_eq!(err, IntegError::Failed(EvalFailure::Retry.error()));
+    }
+}
@@ -1,176 +1,199 @@
 /// Fixed time-step Revisionist Iterated Differed Corrector (RIDC) [adaptive]
 ///
 /// Performs parallel integration of IVP using a multiple order correction
-/// method with a fixed time step
+/// method with a fixed time step. The predictor loop is an async function so the
+/// async backend (ridc/tasks) runs the same loop on its executor, while the
+/// threaded integrator drives it to completion on the calling thread
 ///
 // === Begin Imports ===
 // third party imports
 extern crate nalgebra as na;
-use na::allocator::Allocator;
-use na::{DefaultAllocator, Dim, DimMin, DimName, DimSub, VectorN, U1};
+use na::{DefaultAllocator, OVector};
 
 // local imports
-use super::base::{RIDCIntegratorBase, RIDCIntegratorFixed};
-use super::common::{IVPSolData, IVPSolMsg, IntegOptionsParallel};
-use crate::lagrange::quadrature::{get_weights, get_x_pow, specific_weights};
-use crate::runge_kutta::base::RKStepper;
-use crate::runge_kutta::common::{IntegResult, StepSimple};
+use super::base::{
+    collect_levels, poison_levels, restart_levels, RIDCIntegratorBase, RIDCIntegratorFixed,
+};
+use super::common::{
+    block_on, yield_now, IVPSolData, IVPSolMsg, IdleTimes, IntegOptionsParallel, LevelReceiver,
+    LevelSender, LevelSetup,
+};
+use super::predictor::Predictor;
+use super::slab::SlabControl;
+use crate::runge_kutta::common::{approach_end, Breakpoints, IntegError, IntegResult};
+use crate::runge_kutta::domain::shrink_step;
+use crate::systems::fallible::{check_failure, step_failed};
+use crate::systems::OdeSystem;
+use crate::utils::solver_dim::{SolverAllocator, SolverDim};
 
 // Standard library imports
-use std::collections::VecDeque;
 use std::marker::Send;
 
 // === End Imports ===
 
-impl<D: DimName + Dim> RIDCIntegratorBase for RKStepper<D> where
-    DefaultAllocator: Allocator<f64, D> + Allocator<f64, D, D>
-{
-}
+impl<P: Predictor> RIDCIntegratorBase for P {}
 
-impl<D: DimName + Dim> RIDCIntegratorFixed for RKStepper<D>
-where
-    DefaultAllocator: Allocator<f64, D> + Allocator<f64, D, D>,
-{
-    fn parallel_integrator<N: Dim + DimName + DimMin<N> + DimSub<U1>>(
+impl<P: Predictor> RIDCIntegratorFixed for P {
+    fn parallel_integrator<N: SolverDim, S: OdeSystem<N> + Clone + Send + 'static>(
         &self,
-        fxn: fn(f64, &VectorN<f64, N>) -> VectorN<f64, N>,
+        fxn: S,
         t_0: f64,
-        y_0: &VectorN<f64, N>,
+        y_0: &OVector<f64, N>,
         step: f64,
-        mut dt: f64,
+        dt: f64,
         integ_opts: IntegOptionsParallel<N>,
-    ) -> Result<IntegResult<N>, &'static str>
+    ) -> Result<IntegResult<N>, IntegError>
     where
-        DefaultAllocator: Allocator<f64, N>
-            + Allocator<f64, U1, N>
-            + Allocator<f64, N, N>
-            + Allocator<f64, <N as DimMin<N>>::Output, N>
-            + Allocator<f64, <N as DimMin<N>>::Output>
-            + Allocator<f64, N, <N as DimMin<N>>::Output>
-            + Allocator<f64, <<N as DimMin<N>>::Output as DimSub<U1>>::Output>,
-        <N as DimMin<N>>::Output: DimName,
-        <N as DimMin<N>>::Output: DimSub<U1>,
-        <DefaultAllocator as Allocator<f64, N>>::Buffer: Send + Sync,
+        DefaultAllocator: SolverAllocator<N>,
     {
-        // Unwrap Options to defaults
-        let min_step_size = integ_opts.min_step.unwrap_or(1e-10_f64);
-        let poly_order = integ_opts.poly_order.unwrap_or(3); // ONLY 3 is currently supported
-        let corrector_order = integ_opts.corrector_order.unwrap_or(1);
-        let restart_length = integ_opts.restart_length.unwrap_or(100);
-        let corr_conv_tol = integ_opts.convergence_tol.unwrap_or(1.0e-10_f64);
-
-        if dt.abs() < min_step_size {
-            return Err("Requested Step size is smaller than minimum step size");
-        }
+        // spawn threads
+        let spawn = |setup: &LevelSetup<N>| self.spawn_correctors(&fxn, setup);
+        block_on(integrate_fixed(
+            self, &fxn, t_0, y_0, step, dt, integ_opts, spawn,
+        ))
+    }
+}
 
-        // Initialize results struct and other integration variables
-        let mut results = IntegResult::new(t_0, y_0.clone());
-        let t_end = t_0 + step;
-        let backward: bool = step < 0.0;
-        if backward {
-            dt = -dt;
-        }
+// Predictor loop of fixed step RIDC, shared by the threaded integrator above and
+// the async backend (see ridc/tasks). `spawn` starts the correction levels and
+// returns the ends of their pipeline
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn integrate_fixed<P, N, S, L>(
+    predictor: &P,
+    fxn: &S,
+    t_0: f64,
+    y_0: &OVector<f64, N>,
+    step: f64,
+    mut dt: f64,
+    integ_opts: IntegOptionsParallel<N>,
+    spawn: L,
+) -> Result<IntegResult<N>, IntegError>
+where
+    P: Predictor,
+    N: SolverDim,
+    S: OdeSystem<N>,
+    L: FnOnce(&LevelSetup<N>) -> (LevelSender<N>, LevelReceiver<N>, IdleTimes),
+    DefaultAllocator: SolverAllocator<N>,
+{
+    // Unwrap Options to defaults
+    let first_dyn_eval = &fxn.dynamics(t_0, y_0);
+    check_failure(fxn)?;
+    let setup = LevelSetup::new(&integ_opts, 1.0e-10_f64, t_0, y_0, first_dyn_eval)?;
+    let corrector_order = setup.corrector_order;
+    let min_step_size = integ_opts.min_step.unwrap_or(1e-10_f64);
+    let restart_length = integ_opts.restart_length.unwrap_or(100);
+    let diagnostics = integ_opts.diagnostics.unwrap_or_default();
+    let correction_tol = integ_opts.correction_tol;
+    let mut breakpoint_times = integ_opts.breakpoints.unwrap_or_default();
+    breakpoint_times.extend(fxn.breakpoints());
 
-        // initialize vals
-        let mut y_last = y_0.clone();
-        let first_dyn_eval = &fxn(t_0, y_0);
-        let mut counter = 1;
+    if dt.abs() < min_step_size {
+        return Err("Requested Step size is smaller than minimum step size".into());
+    }
 
-        // spawn threads
-        let (root_tx, root_rx) = self.spawn_correctors(
-            corrector_order,
-            poly_order,
-            fxn,
-            t_0,
-            y_0,
-            first_dyn_eval,
-            corr_conv_tol,
-        );
+    // Initialize results struct and other integration variables
+    let mut results = IntegResult::new(t_0, y_0.clone());
+    let t_end = t_0 + step;
+    let backward: bool = step < 0.0;
+    if backward {
+        dt = -dt;
+    }
+    let mut breakpoints = Breakpoints::new(&breakpoint_times, t_0, t_end);
 
-        // flag to prevent infinite looping while collecting results
-        let mut just_restarted = false;
+    // initialize vals
+    let mut y_last = y_0.clone();
+    // number of correction levels applied in the current restart window
+    let mut levels = corrector_order;
+    let mut slab = SlabControl::new(
+        restart_length,
+        integ_opts.slab_length,
+        integ_opts.roughness_tol,
+        setup.poly_order,
+        t_0,
+        first_dyn_eval,
+    );
 
-        // Used to generate the weights for the quadrature. Quadrature uses a backwards
-        // form of the lagrange polynomial interpolation
-        let mut times_rev: VecDeque<f64> = VecDeque::from(vec![t_0]);
-        times_rev.reserve_exact(poly_order);
+    let (root_tx, mut root_rx, idle) = spawn(&setup);
 
+    // the levels are shut down on errors as well, so none is left sending
+    let outcome: Result<(), IntegError> = async {
+        // shortened step after a recoverable failure, grown back over the next steps
+        let mut shrunk: Option<f64> = None;
         while results.t != t_end {
+            if slab.restart_due(results.t) {
+                // stop and wait for other threads to catch up
+                let corrections =
+                    restart_levels(fxn, &root_tx, &mut root_rx, &mut results, &mut slab).await?;
+                // adapt the number of correction levels for the next window
+                if let Some(tol) = correction_tol {
+                    levels = predictor.select_levels(&corrections, levels, corrector_order, tol);
+                }
+                y_last = results.states[results.states.len() - 1].clone();
+            }
+
             // Ensures integrator does not over-step the goal
-            if (backward && dt.abs() > (t_end - results.t).abs())
-                || (!backward && dt > (t_end - results.t))
-            {
-                dt = t_end - results.t;
+            let (h_end, last) = approach_end(results.t, t_end, shrunk.unwrap_or(dt));
+            let (h, landing) = breakpoints.limit(results.t, h_end);
+            let step_res = predictor.predict(fxn, results.t, &y_last, h);
+            if step_failed(fxn)? {
+                // retake the step with half the step size
+                shrunk = Some(shrink_step(results.t, h, min_step_size)?);
+                continue;
+            }
+            if slab.check_roughness(results.t + h, &step_res.dyn_eval) {
+                // retake the step after restarting
+                continue;
             }
+            shrunk = shrunk.map(|h| 2.0 * h).filter(|h| h.abs() < dt.abs());
 
-            if counter < poly_order + 1 {
-                just_restarted = false;
-                // evaluate function
-                let step_res = self.step(fxn, results.t, &y_last, dt);
-
-                // Update all times
-                results.t += dt;
-                results.times.push(results.t);
-                times_rev.push_front(results.t);
-
-                // send initialization point to corrector
-                root_tx
-                    .send(IVPSolMsg::PROCESS(IVPSolData {
-                        y_nxt: step_res.value.clone(),
-                        dy_nxt: step_res.dyn_eval.clone(),
-                        t_nxt: results.t.clone(),
-                        weights: None,
-                    }))
-                    .expect("Could not send Message from [ROOT]");
-
-                y_last = step_res.value;
-                counter += 1;
-            } else if (counter % restart_length == 0) && !(just_restarted) {
-                // stop and wait for other threads to catch up
-                self.collect_results(&root_rx, &mut results)?;
-                y_last = results.states[results.states.len() - 1].clone();
-                just_restarted = true;
-            } else {
-                just_restarted = false;
-                let step_res = self.step(fxn, results.t, &y_last, dt);
-
-                results.t += dt;
-
-                let x_pows = get_x_pow(
-                    results.times[results.times.len() - 1],
-                    results.t,
-                    poly_order,
-                );
-                results.times.push(results.t);
-
-                // rotate times into times vector
-                times_rev.pop_back().expect("Could not pop old time");
-                times_rev.push_front(results.t);
-
-                // send estimate to the corrector
-                root_tx
-                    .send(IVPSolMsg::PROCESS(IVPSolData {
-                        y_nxt: step_res.value.clone(),
-                        dy_nxt: step_res.dyn_eval.clone(),
-                        t_nxt: results.t.clone(),
-                        weights: Some(specific_weights(x_pows, &get_weights(&times_rev))),
-                    }))
-                    .expect("Could not send Message from [ROOT]");
-
-                y_last = step_res.value;
-                counter += 1;
+            results.advance(h);
+            results.times.push(results.t);
+            if last && landing.is_none() {
+                results.land_on(t_end);
             }
+            // correctors work at the actual end of the step (just short of a breakpoint)
+            let t_nxt = results.t;
+            let weights = slab.push(t_nxt, &step_res.dyn_eval);
+            if let Some(t_bp) = landing {
+                results.land_on(t_bp);
+                breakpoints.passed();
+                slab.end_slab();
+            }
+
+            // send estimate to the corrector
+            root_tx
+                .send(IVPSolMsg::PROCESS(IVPSolData {
+                    y_nxt: step_res.value.clone(),
+                    dy_nxt: step_res.dyn_eval.clone(),
+                    t_nxt,
+                    weights,
+                    levels: if slab.isolated() { 0 } else { levels },
+                    corrections: Vec::new(),
+                }))
+                .expect("Could not send Message from [ROOT]");
+
+            y_last = step_res.value;
+            yield_now().await;
         }
-        self.collect_results(&root_rx, &mut results)?;
-        self.poison(root_tx, root_rx)?;
-        Ok(results)
+        collect_levels(&root_tx, &mut root_rx, &mut results).await?;
+        Ok(())
     }
+    .await;
+    let shutdown = poison_levels(root_tx, root_rx, P::SHUTDOWN_TIMEOUT_SEC).await;
+    outcome?;
+    shutdown?;
+    results.level_idle = idle.lock().unwrap().clone();
+    results.update_diagnostics(&diagnostics);
+    Ok(results)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ridc::common::Schedule;
     use crate::runge_kutta::rk_simp::RK4;
+    use crate::systems::fallible::{EvalFailure, Fallible};
+    use crate::systems::fp_policy::{FpChecked, FpPolicy};
     use crate::test_fxns::one_d::{
         one_d_dynamics, one_d_solution, ONE_D_INIT_TIME, ONE_D_INIT_VAL,
     };
@@ -182,7 +205,7 @@ mod tests {
         println!("STARTING FIXED STEP RIDC TEST");
         let time_end = 10.0;
         let dt = time_end - ONE_D_INIT_TIME;
-        let step_size = 0.5;
+        let step_size = 0.1;
         let options = IntegOptionsParallel::default();
         let ans = RK4
             .parallel_integrator(
@@ -215,4 +238,207 @@ mod tests {
         println!("DIFF 2d | {:?}", diff);
         assert!(diff < tol_val);
     }
+
+    #[test]
+    fn test_ridc_adaptive_levels() {
+        let time_end = 6.0;
+        let dt = time_end - ONE_D_INIT_TIME;
+        let run = |correction_tol: Option<f64>| {
+            let options = IntegOptionsParallel {
+                corrector_order: Some(3),
+                restart_length: Some(10),
+                correction_tol,
+                ..IntegOptionsParallel::default()
+            };
+            RK4.parallel_integrator(
+                one_d_dynamics,
+                ONE_D_INIT_TIME,
+                &ONE_D_INIT_VAL,
+                dt,
+                0.1,
+                options,
+            )
+            .unwrap()
+        };
+
+        // without a tolerance every solution gets every correction
+        let fixed = run(None);
+        assert_eq!(fixed.correction_levels.len(), fixed.states.len() - 1);
+        assert!(fixed.correction_levels.iter().all(|levels| *levels == 3));
+
+        // a loose tolerance is met by the first level so later windows drop the rest
+        let loose = run(Some(1e-3));
+        assert_eq!(loose.correction_levels[0], 3);
+        assert_eq!(*loose.correction_levels.last().unwrap(), 1);
+
+        // an unreachable tolerance keeps all of the levels
+        let tight = run(Some(1e-15));
+        assert!(tight.correction_levels.iter().all(|levels| *levels == 3));
+        assert_eq!(tight.last_y(), fixed.last_y());
+    }
+
+    #[test]
+    fn test_ridc_slabs() {
+        let run = |t_jump: f64, slab_length: Option<f64>, roughness_tol: Option<f64>| {
+            // forcing switched on part way through a step
+            let fxn = move |t: f64, y: &Vector1<f64>| {
+                Vector1::new(-y[0] + if t >= t_jump { 1.0 } else { 0.0 })
+            };
+            let options = IntegOptionsParallel {
+                corrector_order: Some(2),
+                slab_length,
+                roughness_tol,
+                ..IntegOptionsParallel::default()
+            };
+            let ans = RK4
+                .parallel_integrator(fxn, 0.0, &Vector1::new(1.0), 3.0, 0.1, options)
+                .unwrap();
+            let truth = (-3.0_f64).exp() + 1.0 - (t_jump - 3.0).exp();
+            (ans.last_y()[0] - truth).abs()
+        };
+
+        // slabs end on their time length rather than the (default 100) step count
+        let options = IntegOptionsParallel {
+            slab_length: Some(0.5),
+            ..IntegOptionsParallel::default()
+        };
+        let ans = RK4
+            .parallel_integrator(one_d_dynamics, 1.0, &ONE_D_INIT_VAL, 3.0, 0.1, options)
+            .unwrap();
+        assert_eq!(ans.restarts.len(), 5);
+        for (i, t) in ans.restarts.iter().enumerate() {
+            assert!((t - 1.5 - 0.5 * i as f64).abs() < 1e-12);
+        }
+
+        // stencils straddling the jump smear it, isolating the rough step avoids that
+        for t_jump in &[1.01, 1.03, 1.07, 1.09] {
+            let smeared = run(*t_jump, None, None);
+            let isolated = run(*t_jump, None, Some(1e-2));
+            assert!(isolated < 0.7 * smeared);
+        }
+    }
+
+    #[test]
+    fn test_ridc_breakpoints() {
+        let fxn =
+            |t: f64, y: &Vector1<f64>| Vector1::new(-y[0] + if t >= 1.05 { 1.0 } else { 0.0 });
+        let truth = (-3.0_f64).exp() + 1.0 - (1.05 - 3.0_f64).exp();
+        let options = IntegOptionsParallel {
+            corrector_order: Some(2),
+            breakpoints: Some(vec![1.05]),
+            ..IntegOptionsParallel::default()
+        };
+        let ans = RK4
+            .parallel_integrator(fxn, 0.0, &Vector1::new(1.0), 3.0, 0.1, options)
+            .unwrap();
+
+        // the step lands on the breakpoint and the correction history restarts there
+        assert!(ans.times.contains(&1.05));
+        assert_eq!(ans.restarts, vec![1.05]);
+        assert!((ans.last_y()[0] - truth).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_ridc_schedules() {
+        let run = |schedule: Option<Schedule>| {
+            let options = IntegOptionsParallel {
+                corrector_order: Some(3),
+                restart_length: Some(20),
+                schedule,
+                ..IntegOptionsParallel::default()
+            };
+            RK4.parallel_integrator(
+                one_d_dynamics,
+                ONE_D_INIT_TIME,
+                &ONE_D_INIT_VAL,
+                5.0,
+                0.1,
+                options,
+            )
+        };
+        let per_level = run(None).unwrap();
+        assert_eq!(per_level.level_idle.len(), 3);
+        assert!(per_level.level_idle.iter().all(|idle| *idle > 0.0));
+
+        // the schedule only changes which thread does the work
+        for schedule in &[
+            Schedule::PerLevel,
+            Schedule::Pooled(1),
+            Schedule::Pooled(2),
+            Schedule::BoundedLag(0),
+            Schedule::BoundedLag(2),
+        ] {
+            let ans = run(Some(*schedule)).unwrap();
+            assert_eq!(ans.states, per_level.states);
+            assert_eq!(ans.level_idle.len(), 3);
+        }
+        assert!(run(Some(Schedule::Pooled(0))).is_err());
+    }
+
+    #[test]
+    fn test_ridc_failures() {
+        // the strict floating point policy ends the integration on the NaN
+        let nan = |t: f64, y: &Vector1<f64>| Vector1::new(if t > 1.0 { f64::NAN } else { -y[0] });
+        let system = FpChecked::new(nan, FpPolicy::strict());
+        let options = IntegOptionsParallel::default();
+        let err = RK4
+            .parallel_integrator(system, 0.0, &Vector1::new(1.0), 2.0, 0.1, options)
+            .unwrap_err();
+        assert_eq!(
+            err,
+            IntegError::Failed("[FP] Invalid value (NaN) in the dynamics")
+        );
+
+        // failures in the correction levels (on their own threads) end it as well,
+        // whatever the schedule
+        let in_levels = |_t: f64, y: &Vector1<f64>| {
+            let name = std::thread::current().name().unwrap_or("").to_string();
+            if name.starts_with("THREAD") || name.starts_with("WORKER") {
+                Err(EvalFailure::Fatal("[TABLE] Level failed"))
+            } else {
+                Ok(Vector1::new(-y[0]))
+            }
+        };
+        for schedule in &[
+            Schedule::PerLevel,
+            Schedule::Pooled(2),
+            Schedule::BoundedLag(1),
+        ] {
+            let options = IntegOptionsParallel {
+                corrector_order: Some(3),
+                restart_length: Some(10),
+                schedule: Some(*schedule),
+                ..IntegOptionsParallel::default()
+            };
+            let ans = RK4.parallel_integrator(
+                Fallible::new(in_levels),
+                0.0,
+                &Vector1::new(1.0),
+                5.0,
+                0.1,
+                options,
+            );
+            assert_eq!(ans.unwrap_err(), IntegError::Failed("[TABLE] Level failed"));
+        }
+
+        // steps of the predictor which fail recoverably (stages overshooting below
+        // zero) are retried with half the step
+        let decay = |_t: f64, y: &Vector1<f64>| match y[0] < 0.0 {
+            true => Err(EvalFailure::Retry),
+            false => Ok(Vector1::new(-3.0 * y[0])),
+        };
+        let ans = RK4
+            .parallel_integrator(
+                Fallible::new(decay),
+                0.0,
+                &Vector1::new(1.0),
+                3.0,
+                1.0,
+                IntegOptionsParallel::default(),
+            )
+            .unwrap();
+        assert_eq!(ans.t, 3.0);
+        assert!(ans.times.windows(2).any(|w| w[1] - w[0] < 1.0));
+        assert!(ans.states.iter().all(|y| y[0] > 0.0));
+    }
 }
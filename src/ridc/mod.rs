@@ -2,4 +2,10 @@ pub mod adaptive;
 pub mod base;
 pub mod common;
 pub mod corrector;
+pub mod dense;
+pub mod fixed_cost;
 pub mod fixedstep;
+pub mod predictor;
+pub mod slab;
+#[cfg(feature = "tokio")]
+pub mod tasks;
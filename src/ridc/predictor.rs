@@ -0,0 +1,117 @@
+/// RIDC Predictors (predictor)
+///
+/// The predictor advances the uncorrected solution one step at a time and hands
+/// each estimate (with the dynamics evaluated there) to the corrector threads.
+/// Any type implementing `Predictor` can drive the fixed step RIDC pipeline, so
+/// schemes suited to a particular problem (exponential integrators for stiff linear
+/// parts, symplectic or geometric steppers, ...) can be plugged in without touching
+/// the correctors. Every simple Runge Kutta stepper is a predictor already.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DefaultAllocator, Dim, DimName, OVector};
+
+// local imports
+use crate::runge_kutta::common::{StepResult, StepSimple};
+use crate::systems::OdeSystem;
+
+// === End Imports ===
+
+pub trait Predictor {
+    // Advances y_0 from t_0 by step. The result holds the new state in value and
+    // the dynamics evaluated at (t_0 + step, value) in dyn_eval, which the
+    // correctors integrate. The error field is not used.
+    fn predict<N: DimName + Dim, S: OdeSystem<N> + ?Sized>(
+        &self,
+        fxn: &S,
+        t_0: f64,
+        y_0: &OVector<f64, N>,
+        step: f64,
+    ) -> StepResult<N>
+    where
+        DefaultAllocator: Allocator<N>;
+}
+
+impl<T: StepSimple> Predictor for T {
+    fn predict<N: DimName + Dim, S: OdeSystem<N> + ?Sized>(
+        &self,
+        fxn: &S,
+        t_0: f64,
+        y_0: &OVector<f64, N>,
+        step: f64,
+    ) -> StepResult<N>
+    where
+        DefaultAllocator: Allocator<N>,
+    {
+        self.step(fxn, t_0, y_0, step)
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ridc::base::RIDCIntegratorFixed;
+    use crate::ridc::common::IntegOptionsParallel;
+    use na::Vector1;
+
+    // Exponential euler for y' = lambda y + g(t, y), exact on the linear part
+    struct ExpEuler {
+        lambda: f64,
+    }
+
+    impl Predictor for ExpEuler {
+        fn predict<N: DimName + Dim, S: OdeSystem<N> + ?Sized>(
+            &self,
+            fxn: &S,
+            t_0: f64,
+            y_0: &OVector<f64, N>,
+            step: f64,
+        ) -> StepResult<N>
+        where
+            DefaultAllocator: Allocator<N>,
+        {
+            let growth = (self.lambda * step).exp();
+            let forcing = fxn.dynamics(t_0, y_0) - self.lambda * y_0;
+            let value = growth * y_0 + (growth - 1.0) / self.lambda * forcing;
+            StepResult {
+                error: 0.0,
+                dyn_eval: fxn.dynamics(t_0 + step, &value),
+                value,
+            }
+        }
+    }
+
+    #[test]
+    fn test_custom_predictor() {
+        let fxn = |t: f64, y: &Vector1<f64>| Vector1::new(-20.0 * y[0] + t.cos());
+        // y(0) = 1
+        let truth = |t: f64| {
+            let particular = (20.0 * t.cos() + t.sin()) / 401.0;
+            particular + (1.0 - 20.0 / 401.0) * (-20.0 * t).exp()
+        };
+        let predictor = ExpEuler { lambda: -20.0 };
+        let (t_end, dt) = (2.0, 0.05);
+
+        // the predictor alone
+        let mut y = Vector1::new(1.0);
+        for i in 0..40 {
+            y = predictor.predict(&fxn, i as f64 * dt, &y, dt).value;
+        }
+        let predicted = (y[0] - truth(t_end)).abs();
+
+        let options = IntegOptionsParallel {
+            corrector_order: Some(2),
+            ..IntegOptionsParallel::default()
+        };
+        let ans = predictor
+            .parallel_integrator(fxn, 0.0, &Vector1::new(1.0), t_end, dt, options)
+            .unwrap();
+        let corrected = (ans.last_y()[0] - truth(t_end)).abs();
+        println!("PREDICTED {:e} | CORRECTED {:e}", predicted, corrected);
+        assert_eq!(ans.states.len(), 41);
+        assert!(corrected < 0.1 * predicted);
+    }
+}
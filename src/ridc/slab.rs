@@ -0,0 +1,216 @@
+/// RIDC Time Slabs (slab)
+///
+/// Controls the restart windows (slabs) of the RIDC predictors separately from the
+/// step size. A slab ends after a maximum number of steps or a maximum length of
+/// time, whichever comes first, at which point the correction history is restarted
+/// from the corrected solution.
+///
+/// The high order quadrature stencil of the correctors smears any roughness in the
+/// dynamics (discontinuities, kinks) over the steps around it. When a roughness
+/// tolerance is set every predictor evaluation is compared against the polynomial
+/// extrapolation of the previous evaluations in the slab. If it deviates by more
+/// than the tolerance the slab is ended before that step and the step is given a
+/// slab of its own, so no stencil straddles the rough point. The isolated step is
+/// left as predicted.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DefaultAllocator, Dim, DimName, OVector};
+
+// local imports
+use crate::lagrange::quadrature::interval_weights;
+
+// Standard library imports
+use std::collections::VecDeque;
+
+// === End Imports ===
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlabControl<N: Dim + DimName>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    // Maximum number of steps in a slab
+    pub max_steps: usize,
+    // Maximum length of time covered by a slab (unlimited when None)
+    pub max_length: Option<f64>,
+    // Relative deviation from the extrapolated dynamics which ends the slab early
+    pub roughness_tol: Option<f64>,
+    // Number of points in the quadrature stencil (poly_order + 1)
+    stencil: usize,
+    // Time at which the current slab started
+    start: f64,
+    // Number of steps taken in the current slab
+    steps: usize,
+    // Most recent times of the slab (newest first), at most `stencil` of them
+    times: VecDeque<f64>,
+    // Predictor dynamics evaluations at `times`
+    evals: VecDeque<OVector<f64, N>>,
+    // Whether the slab should end before the next step
+    end_pending: bool,
+    // Whether the current slab ends after a single step
+    isolate: bool,
+    // Whether the next slab ends after a single step
+    isolate_next: bool,
+}
+
+impl<N: Dim + DimName> SlabControl<N>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    pub fn new(
+        max_steps: usize,
+        max_length: Option<f64>,
+        roughness_tol: Option<f64>,
+        poly_order: usize,
+        t_0: f64,
+        dy_0: &OVector<f64, N>,
+    ) -> Self {
+        let mut slab = SlabControl {
+            max_steps,
+            max_length,
+            roughness_tol,
+            stencil: poly_order + 1,
+            start: t_0,
+            steps: 0,
+            times: VecDeque::with_capacity(poly_order + 1),
+            evals: VecDeque::with_capacity(poly_order + 1),
+            end_pending: false,
+            isolate: false,
+            isolate_next: false,
+        };
+        slab.restart(t_0, dy_0);
+        slab
+    }
+
+    // Number of steps taken in the current slab
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    // Whether the current slab holds a single rough step. Corrections assume smooth
+    // dynamics so such a step is left as predicted
+    pub fn isolated(&self) -> bool {
+        self.isolate
+    }
+
+    // Whether the current slab is over and the correctors should be restarted
+    pub fn restart_due(&self, t: f64) -> bool {
+        if self.steps == 0 {
+            return false;
+        }
+        let too_long = match self.max_length {
+            Some(length) => (t - self.start).abs() >= length * (1.0 - 1e-12),
+            None => false,
+        };
+        self.end_pending || self.isolate || self.steps >= self.max_steps || too_long
+    }
+
+    // Ends the current slab before the next step
+    pub fn end_slab(&mut self) {
+        self.end_pending = true;
+    }
+
+    // Starts a new slab at time t with the dynamics evaluated at the corrected state
+    pub fn restart(&mut self, t: f64, dy: &OVector<f64, N>) {
+        self.end_pending = false;
+        self.isolate = self.isolate_next;
+        self.isolate_next = false;
+        self.start = t;
+        self.steps = 0;
+        self.times.clear();
+        self.times.push_front(t);
+        self.evals.clear();
+        self.evals.push_front(dy.clone());
+    }
+
+    // Checks a predictor evaluation at time t against the extrapolation of the slab.
+    // Returns true (and schedules the end of the slab) if the dynamics are rough
+    pub fn check_roughness(&mut self, t: f64, dy: &OVector<f64, N>) -> bool {
+        let tol = match self.roughness_tol {
+            Some(tol) => tol,
+            None => return false,
+        };
+        if self.isolate || self.times.len() < self.stencil {
+            return false;
+        }
+
+        // value of each Lagrange basis polynomial of the stencil at t
+        let basis = self.times.iter().enumerate().map(|(i, t_i)| {
+            self.times
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, t_j)| (t - t_j) / (t_i - t_j))
+                .product::<f64>()
+        });
+        let extrap: OVector<f64, N> = basis.zip(self.evals.iter()).map(|(l, f)| l * f).sum();
+        let scale = self
+            .evals
+            .iter()
+            .fold(dy.amax(), |acc, f| acc.max(f.amax()));
+
+        if (dy - extrap).amax() > tol * scale {
+            self.end_pending = true;
+            self.isolate_next = true;
+            true
+        } else {
+            false
+        }
+    }
+
+    // Records an accepted step and returns the quadrature weights over it once the
+    // stencil is full (None while the correctors are still initializing)
+    pub fn push(&mut self, t: f64, dy: &OVector<f64, N>) -> Option<Vec<f64>> {
+        let t_prev = self.times[0];
+        self.steps += 1;
+        if self.times.len() == self.stencil {
+            self.times.pop_back();
+            self.evals.pop_back();
+        }
+        self.times.push_front(t);
+        self.evals.push_front(dy.clone());
+
+        if self.steps >= self.stencil {
+            Some(interval_weights(&self.times, t_prev, t))
+        } else {
+            None
+        }
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use na::Vector1;
+
+    #[test]
+    fn test_slab_roughness() {
+        let f = |t: f64| Vector1::new(if t < 1.0 { t.sin() } else { t.sin() + 0.5 });
+        let mut slab = SlabControl::new(100, Some(10.0), Some(1e-3), 3, 0.0, &f(0.0));
+        let dt = 0.1;
+        let mut t = 0.0;
+        while t + dt < 1.0 {
+            t += dt;
+            assert!(!slab.check_roughness(t, &f(t)));
+            slab.push(t, &f(t));
+        }
+
+        // the jump is caught before the step is accepted
+        assert!(slab.check_roughness(t + dt, &f(t + dt)));
+        assert!(slab.restart_due(t));
+
+        // the rough step gets a slab of its own
+        slab.restart(t, &f(t));
+        assert!(!slab.restart_due(t));
+        assert!(!slab.check_roughness(t + dt, &f(t + dt)));
+        slab.push(t + dt, &f(t + dt));
+        assert!(slab.restart_due(t + dt));
+        slab.restart(t + dt, &f(t + dt));
+        slab.push(t + 2.0 * dt, &f(t + 2.0 * dt));
+        assert!(!slab.restart_due(t + 2.0 * dt));
+    }
+}
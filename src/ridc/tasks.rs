@@ -0,0 +1,128 @@
+/// Async Revisionist Iterated Differed Corrector (RIDC) [tasks]
+///
+/// Fixed time-step RIDC run on an async executor (the `tokio` feature). Each
+/// correction level is a tokio task and the levels communicate over async
+/// channels, so the integrator can run inside a tokio based service without
+/// dedicating an OS thread to every level. Even a single threaded runtime makes
+/// progress: the predictor yields to the executor after every step.
+///
+/// Runs the predictor loop of the threaded fixed step integrator
+/// (`fixedstep::integrate_fixed`), so it produces the same solutions. Must be
+/// awaited within a tokio runtime, since the levels are spawned on it.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::{DefaultAllocator, OVector};
+use tokio::sync::mpsc::unbounded_channel;
+
+// local imports
+use super::common::{IntegOptionsParallel, LevelReceiver, LevelSender, LevelSetup, Schedule};
+use super::corrector::CorrectorThread;
+use super::fixedstep::integrate_fixed;
+use super::predictor::Predictor;
+use crate::runge_kutta::common::{IntegError, IntegResult};
+use crate::systems::OdeSystem;
+use crate::utils::solver_dim::{SolverAllocator, SolverDim};
+
+// Standard library imports
+use std::marker::Send;
+use std::sync::{Arc, Mutex};
+
+// === End Imports ===
+
+// Integrates from t_0 over `step` with fixed steps of size dt, like the threaded
+// `RIDCIntegratorFixed::parallel_integrator` (both run `integrate_fixed`)
+pub async fn parallel_integrator_async<
+    P: Predictor,
+    N: SolverDim,
+    S: OdeSystem<N> + Clone + Send + 'static,
+>(
+    predictor: &P,
+    fxn: S,
+    t_0: f64,
+    y_0: &OVector<f64, N>,
+    step: f64,
+    dt: f64,
+    integ_opts: IntegOptionsParallel<N>,
+) -> Result<IntegResult<N>, IntegError>
+where
+    DefaultAllocator: SolverAllocator<N>,
+{
+    if let Some(schedule) = integ_opts.schedule {
+        if schedule != Schedule::PerLevel {
+            return Err("The async backend schedules the levels as tasks of the runtime".into());
+        }
+    }
+
+    // spawn a task per correction level
+    let spawn = |setup: &LevelSetup<N>| {
+        let idle = Arc::new(Mutex::new(vec![0.0; setup.corrector_order]));
+        let (root_tx, rx) = unbounded_channel();
+        let mut last_rx = LevelReceiver::Async(rx);
+        for i in 0..setup.corrector_order {
+            let (tx, rx) = unbounded_channel();
+            let mut level = CorrectorThread::new(
+                fxn.clone(),
+                setup,
+                last_rx,
+                LevelSender::Async(tx),
+                i as u32,
+                idle.clone(),
+            );
+            last_rx = LevelReceiver::Async(rx);
+            tokio::spawn(async move { level.run_async().await });
+        }
+        (LevelSender::Async(root_tx), last_rx, idle)
+    };
+    integrate_fixed(predictor, &fxn, t_0, y_0, step, dt, integ_opts, spawn).await
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ridc::base::RIDCIntegratorFixed;
+    use crate::runge_kutta::rk_simp::RK4;
+    use crate::test_fxns::one_d::{one_d_dynamics, ONE_D_INIT_TIME, ONE_D_INIT_VAL};
+
+    #[test]
+    fn test_ridc_async() {
+        let options = IntegOptionsParallel {
+            corrector_order: Some(3),
+            restart_length: Some(20),
+            breakpoints: Some(vec![2.05]),
+            ..IntegOptionsParallel::default()
+        };
+        let threaded = RK4
+            .parallel_integrator(
+                one_d_dynamics,
+                ONE_D_INIT_TIME,
+                &ONE_D_INIT_VAL,
+                5.0,
+                0.1,
+                options.clone(),
+            )
+            .unwrap();
+
+        // every level is a task on a single thread
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let ans = runtime
+            .block_on(parallel_integrator_async(
+                &*RK4,
+                one_d_dynamics,
+                ONE_D_INIT_TIME,
+                &ONE_D_INIT_VAL,
+                5.0,
+                0.1,
+                options,
+            ))
+            .unwrap();
+        assert_eq!(ans.times, threaded.times);
+        assert_eq!(ans.states, threaded.states);
+        assert_eq!(ans.restarts, threaded.restarts);
+        assert_eq!(ans.level_idle.len(), 3);
+    }
+}
@@ -2,9 +2,23 @@
 ///
 ///
 extern crate nalgebra as na;
-use super::common::{IntegOptions, IntegResult, RkOrder, StepResult, StepWithError};
+use super::common::{
+    approach_end, AdaptiveError, Breakpoints, IntegError, IntegOptions, IntegResult, RejectedStep,
+    RkOrder, StepBounds, StepResult, StepWithError,
+};
+use super::defect::defect_error;
+use super::domain::{shrink_step, DomainGuard};
+use super::extrema::Extremum;
+use super::stopping::StopMonitor;
 use na::allocator::Allocator;
-use na::{DefaultAllocator, Dim, DimName, VectorN};
+use na::{DefaultAllocator, Dim, DimName, OVector};
+
+// local imports
+use crate::systems::OdeSystem;
+use crate::utils::norms::rms_norm;
+
+// Standard library imports
+use std::mem;
 
 pub enum StepValid {
     Accept(f64),
@@ -26,64 +40,287 @@ pub trait AdaptiveStep: StepWithError + RkOrder {
         }
     }
 
-    fn integrate<N: DimName + Dim>(
+    /// Starting step size following the algorithm of Hairer, Norsett & Wanner
+    /// ("Solving Ordinary Differential Equations I", section II.4). A first guess
+    /// from the size of y_0 and f(t_0, y_0) is refined with the second derivative
+    /// estimated by a trial euler step, using the same order as `revise_step`.
+    /// Costs one dynamics evaluation. Has the sign of `step` and is at most |step|
+    fn initial_step<N: DimName + Dim, S: OdeSystem<N> + ?Sized>(
+        &self,
+        fxn: &S,
+        t_0: f64,
+        y_0: &OVector<f64, N>,
+        f_0: &OVector<f64, N>,
+        step: f64,
+        atol: &OVector<f64, N>,
+        rtol: f64,
+    ) -> f64
+    where
+        DefaultAllocator: Allocator<N>,
+    {
+        let scale = atol.zip_map(y_0, |a, y| a + rtol * y.abs());
+        let d_0 = rms_norm(y_0, &scale);
+        let d_1 = rms_norm(f_0, &scale);
+        let h_0 = if d_0 < 1e-5 || d_1 < 1e-5 {
+            1e-6
+        } else {
+            0.01 * d_0 / d_1
+        }
+        .min(step.abs());
+
+        // second derivative estimate from an explicit euler step
+        let h_trial = h_0.copysign(step);
+        let f_1 = fxn.dynamics(t_0 + h_trial, &(y_0 + h_trial * f_0));
+        let d_2 = rms_norm(&(f_1 - f_0), &scale) / h_0;
+
+        let d_max = d_1.max(d_2);
+        let h_1 = if d_max <= 1e-15 {
+            (h_0 * 1e-3).max(1e-6)
+        } else {
+            (0.01 / d_max).powf(1.0 / (self.order() as f64 - 1.0))
+        };
+        (100.0 * h_0).min(h_1).min(step.abs()).copysign(step)
+    }
+
+    fn integrate<N: DimName + Dim, S: OdeSystem<N>>(
         &self,
-        fxn: fn(f64, &VectorN<f64, N>) -> VectorN<f64, N>,
+        fxn: S,
         t_0: f64,
-        y_0: VectorN<f64, N>,
+        y_0: OVector<f64, N>,
         step: f64,
         integ_opts: IntegOptions<N>,
-    ) -> Result<IntegResult<N>, &'static str>
+    ) -> Result<IntegResult<N>, IntegError>
     where
-        DefaultAllocator: Allocator<f64, N>,
+        DefaultAllocator: Allocator<N>,
     {
         // Unwrap Options to defaults
         let atol = integ_opts
             .atol
-            .unwrap_or(VectorN::<f64, N>::repeat(1e-3_f64));
+            .unwrap_or(OVector::<f64, N>::repeat(1e-3_f64));
         let rtol = integ_opts.rtol.unwrap_or(1e-6_f64);
         let min_step_size = integ_opts.min_step.unwrap_or(1e-10_f64);
+        let record_rejections = integ_opts.record_rejections.unwrap_or(false);
+        let diagnostics = integ_opts.diagnostics.unwrap_or_default();
+        let integrands = integ_opts.integrands.unwrap_or_default();
+        let mut breakpoint_times = integ_opts.breakpoints.unwrap_or_default();
+        breakpoint_times.extend(fxn.breakpoints());
+        let defect_control = integ_opts.defect_control.unwrap_or(false);
+        let mut extrema = Extremum::start(&integ_opts.extrema.unwrap_or_default(), t_0, &y_0)?;
+        // dynamics at the start of the step, for the interpolant under defect control
+        // or tracking extrema
+        let mut f_last = match defect_control || !extrema.is_empty() {
+            true => Some(fxn.dynamics(t_0, &y_0)),
+            false => None,
+        };
 
-        let mut results = IntegResult::new(t_0, y_0);
+        let mut results = IntegResult::new(t_0, y_0.clone());
+        results.update_diagnostics(&diagnostics);
+        results.add_integrals(&vec![0.0; integrands.len()]);
         let t_end = t_0 + step;
-        let mut sub_step = step;
+        let mut breakpoints = Breakpoints::new(&breakpoint_times, t_0, t_end);
+        let stop_conditions = integ_opts.stop_conditions.unwrap_or_default();
+        let events = integ_opts.events.unwrap_or_default();
+        let mut monitor = StopMonitor::new(&stop_conditions, t_0, &y_0)
+            .with_chattering_guard(integ_opts.event_dead_band, integ_opts.event_rearm)
+            .with_events(&events, t_0, &y_0);
+        let constraints = integ_opts.domain.unwrap_or_default();
+        let guarded = DomainGuard::new(&fxn, &constraints);
+        let mut bounds = StepBounds::new(
+            min_step_size,
+            integ_opts.max_step,
+            integ_opts.max_rejections,
+        );
+        let mut sub_step = bounds.accept(match integ_opts.first_step {
+            Some(h) => h.abs().min(step.abs()).copysign(step),
+            None => {
+                let f_0 = fxn.dynamics(t_0, &y_0);
+                self.initial_step(&fxn, t_0, &y_0, &f_0, step, &atol, rtol)
+            }
+        });
         let mut step_res: StepResult<N>;
+        let mut step_integrals: Vec<f64>;
         let mut step_revision: StepValid;
-        let backward: bool = step < 0.0;
 
         while results.t != t_end {
             // Ensures integrator does not over-step the goal
-            if (backward && sub_step.abs() > (t_end - results.t).abs())
-                || (!backward && sub_step > (t_end - results.t))
-            {
-                sub_step = t_end - results.t;
-            } else if sub_step.abs() < min_step_size {
-                return Err("Step size is below minimum allowable step size");
-            };
-
-            step_res = self.step(fxn, results.t, results.last_y(), sub_step, &atol, rtol);
-            step_revision = self.revise_step(step_res.error, sub_step);
+            let (h_end, last) = approach_end(results.t, t_end, sub_step);
+            let (h, landing) = breakpoints.limit(results.t, h_end);
+            let (t, y) = (results.t, results.last_y());
+            (step_res, step_integrals) =
+                self.step_integrating(&guarded, t, y, h, &atol, rtol, &integrands);
+            if let (true, Some(f_0)) = (defect_control, &f_last) {
+                let (t, y) = (results.t, results.last_y());
+                let (y_1, f_1) = (&step_res.value, &step_res.dyn_eval);
+                let defect = defect_error(&guarded, t, y, f_0, h, y_1, f_1, &atol, rtol);
+                step_res.error = step_res.error.max(defect);
+            }
+            if guarded.rejected()? {
+                match shrink_step(results.t, h, min_step_size) {
+                    Ok(h) => sub_step = h,
+                    Err(err) => {
+                        results.stopped = Some(monitor.exit_domain(results.t, err)?);
+                        break;
+                    }
+                }
+                continue;
+            }
+            step_revision = self.revise_step(step_res.error, h);
 
             match step_revision {
                 StepValid::Accept(nxt_step) => {
-                    results.add_val(sub_step, step_res.value);
-                    sub_step = nxt_step;
+                    if !monitor.is_empty() {
+                        let (t, y) = (results.t, results.last_y());
+                        let stop = monitor.check(t, h, &step_res.value, |s| {
+                            self.step(&guarded, t, y, s, &atol, rtol).value
+                        });
+                        if let Some((stop, s, y_s)) = stop {
+                            if !integrands.is_empty() {
+                                let rule = self.step_integrating(
+                                    &guarded,
+                                    t,
+                                    y,
+                                    s,
+                                    &atol,
+                                    rtol,
+                                    &integrands,
+                                );
+                                step_integrals = rule.1;
+                            }
+                            if let Some(f_0) = &f_last {
+                                let f_s = fxn.dynamics(stop.t, &y_s);
+                                for ext in extrema.iter_mut() {
+                                    ext.update_step(t, s, (y, f_0), (&y_s, &f_s));
+                                }
+                            }
+                            results.events.extend(monitor.take_hits());
+                            results.add_val(s, y_s);
+                            results.add_integrals(&step_integrals);
+                            results.land_on(stop.t);
+                            results.stopped = Some(stop);
+                            results.update_diagnostics(&diagnostics);
+                            break;
+                        }
+                    }
+                    if !guarded.contains(results.t + h, &step_res.value) {
+                        match shrink_step(results.t, h, min_step_size) {
+                            Ok(h) => sub_step = h,
+                            Err(err) => {
+                                results.stopped = Some(monitor.exit_domain(results.t, err)?);
+                                break;
+                            }
+                        }
+                        continue;
+                    }
+                    if let Some(f_0) = &f_last {
+                        let (y_0, y_1) = (results.last_y(), &step_res.value);
+                        for ext in extrema.iter_mut() {
+                            ext.update_step(results.t, h, (y_0, f_0), (y_1, &step_res.dyn_eval));
+                        }
+                    }
+                    results.events.extend(monitor.take_hits());
+                    results.add_val(h, step_res.value);
+                    results.add_integrals(&step_integrals);
+                    if let Some(t_bp) = landing {
+                        results.land_on(t_bp);
+                        breakpoints.passed();
+                    } else if last {
+                        results.land_on(t_end);
+                    }
+                    if f_last.is_some() {
+                        // the dynamics may jump at a breakpoint
+                        f_last = Some(match landing {
+                            Some(_) => fxn.dynamics(results.t, results.last_y()),
+                            None => step_res.dyn_eval,
+                        });
+                    }
+                    results.update_diagnostics(&diagnostics);
+                    sub_step = bounds.accept(nxt_step);
+                    results.next_step = Some(sub_step);
                 }
                 StepValid::Refine(nxt_step) => {
-                    sub_step = nxt_step;
+                    if record_rejections {
+                        results.rejected.push(RejectedStep {
+                            t: results.t,
+                            step: h,
+                            error: step_res.error,
+                        });
+                    }
+                    // the record of the rejections goes with the error
+                    sub_step =
+                        bounds
+                            .reject(results.t, nxt_step, step_res.error)
+                            .map_err(|err| AdaptiveError {
+                                rejected: mem::take(&mut results.rejected),
+                                ..err
+                            })?;
                 }
             }
         }
+        results.extrema = extrema;
         Ok(results)
     }
+
+    // States at each of the target times (sorted in the direction of integration),
+    // integrating from one target to the next. The step size reached carries over
+    // to the next leg, so the targets cost no step size selection of their own.
+    // Fewer states than targets are returned when a stop condition ends the
+    // integration
+    fn propagate_to_each<N: DimName + Dim, S: OdeSystem<N> + Clone>(
+        &self,
+        fxn: S,
+        t_0: f64,
+        y_0: OVector<f64, N>,
+        targets: &[f64],
+        integ_opts: IntegOptions<N>,
+    ) -> Result<Vec<OVector<f64, N>>, &'static str>
+    where
+        DefaultAllocator: Allocator<N>,
+    {
+        let forward = targets.last().is_none_or(|t_last| *t_last >= t_0);
+        let mut prev = t_0;
+        for target in targets {
+            if (*target < prev && forward) || (*target > prev && !forward) || target.is_nan() {
+                return Err("[PROPAGATE] Targets must be sorted in the direction of integration");
+            }
+            prev = *target;
+        }
+
+        let mut states = Vec::with_capacity(targets.len());
+        let (mut t, mut y) = (t_0, y_0);
+        let mut first_step = integ_opts.first_step;
+        for target in targets {
+            if *target != t {
+                let options = IntegOptions {
+                    first_step,
+                    ..integ_opts.clone()
+                };
+                let ans = self.integrate(fxn.clone(), t, y.clone(), target - t, options)?;
+                if ans.stopped.is_some() {
+                    break;
+                }
+                // the step reaching the target is cut short, so the one before it
+                // tells more about the steps the problem allows
+                let n = ans.times.len();
+                let full = match n > 2 {
+                    true => (ans.times[n - 2] - ans.times[n - 3]).abs(),
+                    false => 0.0,
+                };
+                first_step = ans.next_step.map(|h| h.abs().max(full));
+                t = *target;
+                y = ans.last_y().clone();
+            }
+            states.push(y.clone());
+        }
+        Ok(states)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::runge_kutta::common::IntegOptions;
+    use crate::runge_kutta::common::{AdaptiveErrorKind, IntegOptions};
     use crate::runge_kutta::embedded::EmbeddedRKStepper;
-    use crate::runge_kutta::rk_embed::DOPRI78;
+    use crate::runge_kutta::rk_embed::{DOPRI78, RKF45};
     use crate::runge_kutta::tableaus::EmbeddedTableau;
     use nalgebra::{Matrix4, Vector1, Vector4};
 
@@ -140,8 +377,12 @@ mod tests {
         println!("PYTHON | t: {:?}, y: {:?}", python_t, python_y);
         println!("t: {:?} | y: {:?}", ans.t, ans.last_y());
         println!("DIFF: {:?}", (python_y - ans.last_y()[0]).abs());
+        // both solutions depend on the step sequence (scipy limits step growth and picks
+        // its own first step), so each is only checked against the analytic solution
+        let exact_y = 5.0 / 3.0 * python_t - 5.0 / 9.0 + 5.0 / 9.0 * (-3.0 * python_t).exp();
         const TOL_VAL: f64 = 1e-3;
-        assert!((python_y - ans.last_y()[0]).abs() < TOL_VAL);
+        assert!((python_y - exact_y).abs() < TOL_VAL);
+        assert!((exact_y - ans.last_y()[0]).abs() < TOL_VAL);
     }
 
     #[test]
@@ -159,4 +400,214 @@ mod tests {
         println!("{:?}", diff);
         assert!(diff < tol_val);
     }
+
+    #[test]
+    fn test_diagnostics_energy() {
+        // Simple harmonic oscillator conserves 0.5 * (x^2 + v^2)
+        fn sho(_t: f64, y: &Vector2<f64>) -> Vector2<f64> {
+            Vector2::new(y[1], -y[0])
+        }
+        fn energy(_t: f64, y: &Vector2<f64>) -> f64 {
+            0.5 * (y[0].powi(2) + y[1].powi(2))
+        }
+        let options = IntegOptions {
+            atol: Some(Vector2::repeat(1e-10)),
+            rtol: Some(1e-8),
+            diagnostics: Some(vec![energy]),
+            ..IntegOptions::default()
+        };
+        let ans = RKF45
+            .integrate(sho, 0.0, Vector2::new(1.0, 0.0), 20.0, options)
+            .unwrap();
+
+        assert_eq!(ans.diagnostics.len(), ans.states.len());
+        assert_eq!(ans.diagnostics[0], vec![0.5]);
+        let max_drift = ans
+            .diagnostic_drift(0)
+            .iter()
+            .fold(0.0_f64, |acc, d| acc.max(d.abs()));
+        println!("MAX ENERGY DRIFT | {:?}", max_drift);
+        assert!(max_drift < 1e-6);
+    }
+
+    #[test]
+    fn test_initial_step() {
+        let atol = Vector2::repeat(1e-6);
+        let rtol = 1e-6;
+        let f_0 = two_d_dynamics(IT_2_D, &IV_2_D);
+        let h = RKF45.initial_step(&two_d_dynamics, IT_2_D, &IV_2_D, &f_0, 1.0, &atol, rtol);
+
+        // the chosen step is accepted without being a uselessly small start
+        let step_res = RKF45.step(&two_d_dynamics, IT_2_D, &IV_2_D, h, &atol, rtol);
+        assert!(step_res.error <= 1.0);
+        assert!(h > 1e-3);
+
+        // integrating backward gives a negative first step, bounded by the interval
+        let h_bwd = RKF45.initial_step(&two_d_dynamics, IT_2_D, &IV_2_D, &f_0, -1e-4, &atol, rtol);
+        assert_eq!(h_bwd, -1e-4);
+
+        // a user supplied first step is taken as is
+        let options = IntegOptions {
+            atol: Some(atol),
+            rtol: Some(rtol),
+            first_step: Some(1e-3),
+            ..IntegOptions::default()
+        };
+        let ans = RKF45
+            .integrate(two_d_dynamics, IT_2_D, *IV_2_D, 1.0, options)
+            .unwrap();
+        assert!((ans.times[1] - ans.times[0] - 1e-3).abs() < 1e-15);
+    }
+
+    #[test]
+    fn test_step_bounds() {
+        // y' = y^2 from y(0) = 1 blows up at t = 1
+        let blow_up = |_t: f64, y: &Vector1<f64>| Vector1::new(y[0] * y[0]);
+        let options = IntegOptions {
+            record_rejections: Some(true),
+            ..IntegOptions::default()
+        };
+        let err = match RKF45.integrate(blow_up, 0.0, Vector1::new(1.0), 2.0, options) {
+            Err(IntegError::Step(err)) => err,
+            ans => panic!("{:?}", ans.map(|ans| ans.t)),
+        };
+        assert_eq!(err.kind, AdaptiveErrorKind::Underflow);
+        assert!((err.t - 1.0).abs() < 1e-3);
+        assert!(err.h.abs() < 1e-10 && err.err_est > 1.0);
+        let prefix = format!("[ADAPTIVE] Step size underflow at t = {}: ", err.t);
+        assert!(err.to_string().starts_with(&prefix));
+        assert!(err
+            .to_string()
+            .contains(&format!("error estimate {:e}", err.err_est)));
+        // the record of the rejections ends with the failing step
+        let last = err.rejected.last().unwrap();
+        assert_eq!((last.t, last.error), (err.t, err.err_est));
+        // short of the blow up the steps are fine
+        assert!(RKF45
+            .integrate(
+                blow_up,
+                0.0,
+                Vector1::new(1.0),
+                0.999,
+                IntegOptions::default(),
+            )
+            .is_ok());
+
+        // an oversized first step is rejected, which is a stall when none are allowed
+        let options = IntegOptions {
+            first_step: Some(1.0),
+            max_rejections: Some(0),
+            ..IntegOptions::default()
+        };
+        let err = match RKF45.integrate(blow_up, 0.0, Vector1::new(1.0), 0.9, options) {
+            Err(IntegError::Step(err)) => err,
+            ans => panic!("{:?}", ans.map(|ans| ans.t)),
+        };
+        assert_eq!((err.kind, err.t), (AdaptiveErrorKind::Stalled, 0.0));
+        assert!(err.err_est > 1.0 && err.rejected.is_empty());
+        assert!(err
+            .to_string()
+            .starts_with("[ADAPTIVE] Step stalled at t = 0: "));
+
+        // steps never exceed the maximum step
+        let options = IntegOptions {
+            max_step: Some(0.05),
+            ..IntegOptions::default()
+        };
+        let ans = RKF45
+            .integrate(two_d_dynamics, IT_2_D, *IV_2_D, 1.0, options)
+            .unwrap();
+        assert!(ans
+            .times
+            .windows(2)
+            .all(|pair| pair[1] - pair[0] <= 0.05 + 1e-15));
+    }
+
+    #[test]
+    fn test_rejection_history() {
+        let run = |record_rejections: Option<bool>| {
+            let options = IntegOptions {
+                atol: Some(Vector2::repeat(1e-10)),
+                rtol: Some(1e-10),
+                first_step: Some(1.0),
+                record_rejections,
+                ..IntegOptions::default()
+            };
+            let sho = |_t: f64, y: &Vector2<f64>| Vector2::new(y[1], -y[0]);
+            RKF45
+                .integrate(sho, 0.0, Vector2::new(1.0, 0.0), 1.0, options)
+                .unwrap()
+        };
+
+        // recording is opt-in
+        assert!(run(None).rejected.is_empty());
+
+        // the oversized first step is rejected and retried with a smaller step
+        let ans = run(Some(true));
+        let first = ans.rejected[0];
+        assert_eq!((first.t, first.step), (0.0, 1.0));
+        assert!(first.error > 1.0);
+        assert!(ans.times[1] - ans.times[0] < first.step);
+        assert!(ans.rejected.iter().all(|r| r.error > 1.0));
+    }
+
+    #[test]
+    fn test_propagate_to_each() {
+        // harmonic oscillator observed at irregular epochs
+        let evals = std::sync::atomic::AtomicUsize::new(0);
+        let sho = |_t: f64, y: &Vector2<f64>| {
+            evals.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            Vector2::new(y[1], -y[0])
+        };
+        let truth = |t: f64| Vector2::new(t.cos(), -t.sin());
+        let y_0 = Vector2::new(1.0, 0.0);
+        let options = IntegOptions {
+            atol: Some(Vector2::repeat(1e-10)),
+            rtol: Some(1e-10),
+            ..IntegOptions::default()
+        };
+        let targets: Vec<f64> = (0..60)
+            .map(|k| 0.37 * k as f64 + 0.01 * (k % 7) as f64)
+            .collect();
+
+        let states = DOPRI78
+            .propagate_to_each(sho, 0.0, y_0, &targets, options.clone())
+            .unwrap();
+        let warm = evals.swap(0, std::sync::atomic::Ordering::Relaxed);
+        assert_eq!(states.len(), targets.len());
+        assert_eq!(states[0], y_0);
+        let worst = targets
+            .iter()
+            .zip(states.iter())
+            .map(|(t, y)| (y - truth(*t)).amax())
+            .fold(0.0, f64::max);
+        assert!(worst < 1e-8);
+
+        // restarting the step size selection at every target costs more
+        let mut t = 0.0;
+        let mut y = y_0;
+        for target in targets.iter().skip(1) {
+            y = *DOPRI78
+                .integrate(sho, t, y, target - t, options.clone())
+                .unwrap()
+                .last_y();
+            t = *target;
+        }
+        let cold = evals.load(std::sync::atomic::Ordering::Relaxed);
+        println!(
+            "PROPAGATE TO EACH {} evals | COLD RESTARTS {} evals",
+            warm, cold
+        );
+        assert!(warm < cold);
+        assert!((y - states[states.len() - 1]).amax() < 1e-8);
+
+        // backwards, and out of order
+        let back = DOPRI78
+            .propagate_to_each(sho, 2.0, truth(2.0), &[1.0, 0.0], options.clone())
+            .unwrap();
+        assert!((back[1] - y_0).amax() < 1e-8);
+        assert!(DOPRI78
+            .propagate_to_each(sho, 0.0, y_0, &[1.0, 0.5, 2.0], options)
+            .is_err());
+    }
 }
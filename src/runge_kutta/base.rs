@@ -18,19 +18,25 @@
 // third party imports
 extern crate nalgebra as na;
 use na::allocator::Allocator;
-use na::{DefaultAllocator, Dim, DimName, VectorN};
+use na::{DefaultAllocator, Dim, DimName, OVector};
 
 // Local imports
-use super::common::{StepResult, StepSimple};
+use super::common::{approach_end, Diagnostic, StepResult, StepSimple};
 use super::fixed::FixedStep;
 use super::tableaus::{RkType, Tableau};
+use super::zero_alloc::{stages, MAX_STAGES};
+use crate::systems::batch::BatchSystem;
+use crate::systems::state::State;
+use crate::systems::OdeSystem;
+use crate::utils::arena::Arena;
+use crate::utils::kahan::CompensatedSum;
 
 // === End Imports ===
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct RKStepper<D: DimName + Dim>
 where
-    DefaultAllocator: Allocator<f64, D> + Allocator<f64, D, D>,
+    DefaultAllocator: Allocator<D> + Allocator<D, D>,
 {
     // Name of integrator (usually based on butcher table choice)
     name: &'static str,
@@ -44,9 +50,12 @@ where
 
 impl<D: DimName + Dim> RKStepper<D>
 where
-    DefaultAllocator: Allocator<f64, D> + Allocator<f64, D, D>,
+    DefaultAllocator: Allocator<D> + Allocator<D, D>,
 {
     pub fn new(s: &'static str, t: Tableau<D>) -> Result<Self, ()> {
+        if t.c_vals.len() > MAX_STAGES {
+            return Err(());
+        }
         let mut ut = t.a_vals.upper_triangle();
         ut.fill_diagonal(0.0);
         match (t.a_vals.trace() == 0.0, ut.iter().sum::<f64>() == 0.0) {
@@ -60,51 +69,243 @@ where
             _ => unimplemented!("No Implicit Embedded integration provided yet"),
         }
     }
+
+    // Butcher tableau of the method (e.g. for its order or stability function)
+    pub fn tableau(&self) -> &Tableau<D> {
+        &self.tableau
+    }
+
+    // Explicit step on any `State` container (see systems::state)
+    pub fn step_state<X: State, F: Fn(f64, &X) -> X>(
+        &self,
+        fxn: &F,
+        t_0: f64,
+        y_0: &X,
+        step: f64,
+    ) -> X {
+        let mut ks: Vec<X> = Vec::with_capacity(self.stages);
+        for i in 0..self.stages {
+            let mut y_i = y_0.clone();
+            for (j, k) in ks.iter().enumerate() {
+                y_i.axpy(step * self.tableau.a_vals[(i, j)], k);
+            }
+            ks.push(fxn(t_0 + step * self.tableau.c_vals[i], &y_i));
+        }
+        let mut val = y_0.clone();
+        for (b, k) in self.tableau.b_vals.iter().zip(ks.iter()) {
+            val.axpy(step * b, k);
+        }
+        val
+    }
+
+    // Fixed step integration on any `State` container. Returns the times and states
+    // of every step
+    pub fn integrate_state<X: State, F: Fn(f64, &X) -> X>(
+        &self,
+        fxn: &F,
+        t_0: f64,
+        y_0: X,
+        step: f64,
+        dt: f64,
+    ) -> (Vec<f64>, Vec<X>) {
+        let t_end = t_0 + step;
+        let dt = dt.abs().copysign(step);
+        let mut times = vec![t_0];
+        let mut states = vec![y_0];
+        let mut t = t_0;
+        let mut clock = CompensatedSum::new(t_0);
+        while t != t_end {
+            let (h, last) = approach_end(t, t_end, dt);
+            let y_nxt = self.step_state(fxn, t, &states[states.len() - 1], h);
+            clock.add(h);
+            t = if last { t_end } else { clock.value() };
+            times.push(t);
+            states.push(y_nxt);
+        }
+        (times, states)
+    }
+
+    // Explicit step in place on any `State` container, for dynamics which write the
+    // derivative into their last argument. The stage temporaries are taken from the
+    // arena, so once it is warm the step does not allocate
+    pub fn step_state_in_place<X: State, F: Fn(f64, &X, &mut X)>(
+        &self,
+        fxn: &F,
+        t_0: f64,
+        y: &mut X,
+        step: f64,
+        arena: &mut Arena<X>,
+    ) {
+        let mut ks = arena.list();
+        let mut y_i = arena.copy_of(y);
+        for i in 0..self.stages {
+            y_i.copy_from(y);
+            for (j, k) in ks.iter().enumerate() {
+                y_i.axpy(step * self.tableau.a_vals[(i, j)], k);
+            }
+            // overwritten by the dynamics
+            let mut k_i = arena.copy_of(y);
+            fxn(t_0 + step * self.tableau.c_vals[i], &y_i, &mut k_i);
+            ks.push(k_i);
+        }
+        for (b, k) in self.tableau.b_vals.iter().zip(ks.iter()) {
+            y.axpy(step * b, k);
+        }
+        arena.give(y_i);
+        arena.give_list(ks);
+    }
+
+    // Fixed step integration in place (see `step_state_in_place`). Returns the final
+    // state only, so the whole run is allocation free once the arena is warm
+    pub fn integrate_state_in_place<X: State, F: Fn(f64, &X, &mut X)>(
+        &self,
+        fxn: &F,
+        t_0: f64,
+        mut y: X,
+        step: f64,
+        dt: f64,
+        arena: &mut Arena<X>,
+    ) -> X {
+        let t_end = t_0 + step;
+        let dt = dt.abs().copysign(step);
+        let mut t = t_0;
+        let mut clock = CompensatedSum::new(t_0);
+        while t != t_end {
+            let (h, last) = approach_end(t, t_end, dt);
+            self.step_state_in_place(fxn, t, &mut y, h, arena);
+            clock.add(h);
+            t = if last { t_end } else { clock.value() };
+        }
+        y
+    }
+
+    // Explicit step of a whole ensemble with batched dynamics (see systems::batch).
+    // `states` holds the members one after another. The dynamics are evaluated
+    // once per stage for every member at once
+    pub fn step_batch<B: BatchSystem>(
+        &self,
+        system: &B,
+        t_0: f64,
+        states: &[f64],
+        step: f64,
+    ) -> Result<Vec<f64>, &'static str> {
+        let dim = system.dim();
+        if dim == 0 || !states.len().is_multiple_of(dim) {
+            return Err("[BATCH] The states must hold a whole number of members");
+        }
+        let mut ks: Vec<Vec<f64>> = Vec::with_capacity(self.stages);
+        let mut y_i = vec![0.0; states.len()];
+        for i in 0..self.stages {
+            y_i.copy_from_slice(states);
+            for (j, k) in ks.iter().enumerate() {
+                let a = step * self.tableau.a_vals[(i, j)];
+                for (y, k) in y_i.iter_mut().zip(k) {
+                    *y += a * k;
+                }
+            }
+            let mut k_i = vec![0.0; states.len()];
+            system.dynamics_batch(t_0 + step * self.tableau.c_vals[i], &y_i, &mut k_i);
+            ks.push(k_i);
+        }
+        let mut val = states.to_vec();
+        for (b, k) in self.tableau.b_vals.iter().zip(ks.iter()) {
+            for (y, k) in val.iter_mut().zip(k) {
+                *y += step * b * k;
+            }
+        }
+        Ok(val)
+    }
+
+    // Fixed step integration of a whole ensemble with batched dynamics. Returns the
+    // times and the (flat) states of the ensemble at every step
+    pub fn integrate_batch<B: BatchSystem>(
+        &self,
+        system: &B,
+        t_0: f64,
+        states: Vec<f64>,
+        step: f64,
+        dt: f64,
+    ) -> Result<(Vec<f64>, Vec<Vec<f64>>), &'static str> {
+        let t_end = t_0 + step;
+        let dt = dt.abs().copysign(step);
+        let mut times = vec![t_0];
+        let mut history = vec![states];
+        let mut t = t_0;
+        let mut clock = CompensatedSum::new(t_0);
+        while t != t_end {
+            let (h, last) = approach_end(t, t_end, dt);
+            let y_nxt = self.step_batch(system, t, &history[history.len() - 1], h)?;
+            clock.add(h);
+            t = if last { t_end } else { clock.value() };
+            times.push(t);
+            history.push(y_nxt);
+        }
+        Ok((times, history))
+    }
 }
 
 impl<D: DimName + Dim> StepSimple for RKStepper<D>
 where
-    DefaultAllocator: Allocator<f64, D> + Allocator<f64, D, D>,
+    DefaultAllocator: Allocator<D> + Allocator<D, D>,
 {
-    fn step<N: DimName + Dim>(
+    fn step<N: DimName + Dim, S: OdeSystem<N> + ?Sized>(
         &self,
-        fxn: fn(f64, &VectorN<f64, N>) -> VectorN<f64, N>,
+        fxn: &S,
         t_0: f64,
-        y_0: &VectorN<f64, N>,
+        y_0: &OVector<f64, N>,
         step: f64,
     ) -> StepResult<N>
     where
-        DefaultAllocator: Allocator<f64, N>,
+        DefaultAllocator: Allocator<N>,
+    {
+        self.step_integrating(fxn, t_0, y_0, step, &[]).0
+    }
+
+    // The integrands are weighted at the stages like the derivatives
+    fn step_integrating<N: DimName + Dim, S: OdeSystem<N> + ?Sized>(
+        &self,
+        fxn: &S,
+        t_0: f64,
+        y_0: &OVector<f64, N>,
+        step: f64,
+        integrands: &[Diagnostic<N>],
+    ) -> (StepResult<N>, Vec<f64>)
+    where
+        DefaultAllocator: Allocator<N>,
     {
         match self.rktype {
             RkType::Explicit => {
-                let mut ks = Vec::new();
+                // on the stack, so steps of fixed size states do not allocate
+                let mut ks = stages::<N>();
+                let mut integrals = vec![0.0; integrands.len()];
                 for i in 0..self.stages {
-                    let ka_sum: VectorN<f64, N> = ks
+                    let ka_sum: OVector<f64, N> = ks[..i]
                         .iter()
                         .enumerate()
                         .map(|(j, k)| self.tableau.a_vals[(i, j)] * k)
-                        .fold(VectorN::<f64, N>::zeros(), |sum, val| sum + val);
-                    ks.push(fxn(
-                        t_0 + step * self.tableau.c_vals[i],
-                        &(y_0 + step * ka_sum),
-                    ));
+                        .fold(OVector::<f64, N>::zeros(), |sum, val| sum + val);
+                    let (t_i, y_i) = (t_0 + step * self.tableau.c_vals[i], y_0 + step * ka_sum);
+                    for (integral, g) in integrals.iter_mut().zip(integrands.iter()) {
+                        *integral += step * self.tableau.b_vals[i] * g(t_i, &y_i);
+                    }
+                    ks[i] = fxn.dynamics(t_i, &y_i);
                 }
-                let sum_bi_ki: VectorN<f64, N> = self
+                let sum_bi_ki: OVector<f64, N> = self
                     .tableau
                     .b_vals
                     .iter()
                     .enumerate()
                     .map(|(i, b)| *b * &ks[i])
-                    .fold(VectorN::<f64, N>::zeros(), |sum, val| sum + val);
+                    .fold(OVector::<f64, N>::zeros(), |sum, val| sum + val);
 
                 let val = y_0 + step * sum_bi_ki;
-                let dyn_eval = fxn(t_0 + step, &val);
-                StepResult {
+                let dyn_eval = fxn.dynamics(t_0 + step, &val);
+                let res = StepResult {
                     error: 0.0,
                     value: val,
                     dyn_eval: dyn_eval,
-                }
+                };
+                (res, integrals)
             }
             _ => unimplemented!("Only Explicit Embedded methods currently supported"),
         }
@@ -112,7 +313,7 @@ where
 }
 
 impl<D: DimName + Dim> FixedStep for RKStepper<D> where
-    DefaultAllocator: Allocator<f64, D> + Allocator<f64, D, D>
+    DefaultAllocator: Allocator<D> + Allocator<D, D>
 {
 }
 
@@ -144,7 +345,7 @@ mod tests {
             b_vals,
         };
         let r = RKStepper::new("RK4", b).unwrap();
-        let ans = r.step(test_dyn, 0.0, &Vector1::new(0.0), 1.0);
+        let ans = r.step(&test_dyn, 0.0, &Vector1::new(0.0), 1.0);
 
         assert!((ans.value[0] + 0.5).abs() < 1e-7);
     }
@@ -162,7 +363,7 @@ mod tests {
             b_vals,
         };
         let r = RKStepper::new("RK4", b).unwrap();
-        let ans = r.step(test_dyn_2, 0.0, &Vector1::new(0.0), 1.0);
+        let ans = r.step(&test_dyn_2, 0.0, &Vector1::new(0.0), 1.0);
         assert_eq!(ans.value[0], 1.875);
     }
 
@@ -177,7 +378,7 @@ mod tests {
             b_vals,
         };
         let r = RKStepper::new("RK3", b).unwrap();
-        let ans = r.step(test_dyn_2, 0.0, &Vector1::new(0.0), 0.5);
+        let ans = r.step(&test_dyn_2, 0.0, &Vector1::new(0.0), 0.5);
         assert_eq!(ans.value[0], 0.3125)
     }
 }
@@ -5,83 +5,282 @@
 // third party imports
 extern crate nalgebra as na;
 use na::allocator::Allocator;
-use na::{DefaultAllocator, Dim, DimName, VectorN};
+use na::{DefaultAllocator, Dim, DimName, OVector};
+
+// local imports
+use super::extrema::Extremum;
+use super::stopping::{Event, EventHit, Stop, StopCondition};
+use crate::systems::OdeSystem;
+use crate::utils::kahan::CompensatedSum;
+
+// Standard library imports
+use std::fmt;
 
 // === End Imports ===
 
+// Diagnostic functional g(t, y) evaluated on every accepted step (e.g. energy,
+// mass or momentum) so that drift of conserved quantities can be monitored
+pub type Diagnostic<N> = fn(f64, &OVector<f64, N>) -> f64;
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct IntegResult<N: DimName + Dim>
 where
-    DefaultAllocator: Allocator<f64, N>,
+    DefaultAllocator: Allocator<N>,
 {
     // List of times of solutions
     pub times: Vec<f64>,
     // List of solutions associated with times
-    pub states: Vec<VectorN<f64, N>>,
+    pub states: Vec<OVector<f64, N>>,
     // Current time of integrator
     pub t: f64,
+    // Values of each registered diagnostic functional at every solution
+    pub diagnostics: Vec<Vec<f64>>,
+    // Integrals from the start of each registered integrand at every solution
+    pub integrals: Vec<Vec<f64>>,
+    // Number of correction levels applied to each solution (RIDC integrators only)
+    pub correction_levels: Vec<usize>,
+    // Times at which the correction history was restarted (RIDC integrators only)
+    pub restarts: Vec<f64>,
+    // Time in seconds each correction level spent waiting for input (RIDC
+    // integrators only)
+    pub level_idle: Vec<f64>,
+    // Defect (residual of the collocation equations) left at each step by the
+    // fixed cost deferred correction integrator
+    pub defects: Vec<f64>,
+    // Steps rejected by the step size controller (adaptive integrators only, when
+    // recording is enabled)
+    pub rejected: Vec<RejectedStep>,
+    // Stopping condition which ended the integration early, if any
+    pub stopped: Option<Stop>,
+    // Crossings of the registered events, in chronological order
+    pub events: Vec<EventHit<N>>,
+    // Running extrema of the tracked components (see `extrema`)
+    pub extrema: Vec<Extremum>,
+    // Step the controller proposed after the last accepted step (adaptive
+    // integrators only). Passed as `first_step`, it warm starts the integration of
+    // a similar problem without the initial step selection
+    pub next_step: Option<f64>,
+    // Compensated sum of the steps giving t
+    clock: CompensatedSum,
+}
+
+// A step rejected by the step size controller of an adaptive integrator
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RejectedStep {
+    // Time the step was attempted from
+    pub t: f64,
+    // Attempted step size
+    pub step: f64,
+    // Error estimate of the attempt (weighted rms norm, rejected when above 1)
+    pub error: f64,
 }
 
 impl<N: DimName + Dim> IntegResult<N>
 where
-    DefaultAllocator: Allocator<f64, N>,
+    DefaultAllocator: Allocator<N>,
 {
-    pub fn new(t_0: f64, y_0: VectorN<f64, N>) -> Self {
+    pub fn new(t_0: f64, y_0: OVector<f64, N>) -> Self {
         IntegResult {
             times: vec![t_0],
             states: vec![y_0],
             t: t_0,
+            diagnostics: Vec::new(),
+            integrals: Vec::new(),
+            correction_levels: Vec::new(),
+            restarts: Vec::new(),
+            level_idle: Vec::new(),
+            defects: Vec::new(),
+            rejected: Vec::new(),
+            stopped: None,
+            events: Vec::new(),
+            extrema: Vec::new(),
+            next_step: None,
+            clock: CompensatedSum::new(t_0),
         }
     }
 
-    pub fn last_y(&self) -> &VectorN<f64, N> {
+    pub fn last_y(&self) -> &OVector<f64, N> {
         &self.states[self.states.len() - 1]
     }
 
-    pub fn add_val(&mut self, step: f64, new_state: VectorN<f64, N>) {
-        self.t += step;
+    // Advances the current time by a step with compensated summation, so that long
+    // runs of small steps do not accumulate round-off in t
+    pub fn advance(&mut self, step: f64) -> f64 {
+        self.clock.add(step);
+        self.t = self.clock.value();
+        self.t
+    }
+
+    pub fn add_val(&mut self, step: f64, new_state: OVector<f64, N>) {
+        self.advance(step);
         self.times.push(self.t);
         self.states.push(new_state);
     }
+
+    // Snaps the time of the last solution onto t (a step aimed at t can miss it by
+    // round-off)
+    pub fn land_on(&mut self, t: f64) {
+        self.t = t;
+        self.clock = CompensatedSum::new(t);
+        if let Some(last) = self.times.last_mut() {
+            *last = t;
+        }
+    }
+
+    // Evaluates the diagnostic functionals at any solutions that have not been
+    // evaluated yet. Does nothing if no diagnostics are registered
+    pub fn update_diagnostics(&mut self, diagnostics: &[Diagnostic<N>]) {
+        if diagnostics.is_empty() {
+            return;
+        }
+        for idx in self.diagnostics.len()..self.states.len() {
+            let (t, y) = (self.times[idx], &self.states[idx]);
+            self.diagnostics
+                .push(diagnostics.iter().map(|g| g(t, y)).collect());
+        }
+    }
+
+    // Adds the integrals over the last step to the running integrals (starting them
+    // at zero on the first solution)
+    pub fn add_integrals(&mut self, increments: &[f64]) {
+        if increments.is_empty() {
+            return;
+        }
+        let mut totals = match self.integrals.last() {
+            Some(last) => last.clone(),
+            None => vec![0.0; increments.len()],
+        };
+        for (total, inc) in totals.iter_mut().zip(increments.iter()) {
+            *total += inc;
+        }
+        self.integrals.push(totals);
+    }
+
+    // Drift of a diagnostic from its initial value at every solution
+    pub fn diagnostic_drift(&self, idx: usize) -> Vec<f64> {
+        match self.diagnostics.first() {
+            Some(first) => self
+                .diagnostics
+                .iter()
+                .map(|vals| vals[idx] - first[idx])
+                .collect(),
+            None => Vec::new(),
+        }
+    }
 }
 
 // Stepper Traits
 pub trait StepSimple {
-    fn step<N: DimName + Dim>(
+    fn step<N: DimName + Dim, S: OdeSystem<N> + ?Sized>(
         &self,
-        fxn: fn(f64, &VectorN<f64, N>) -> VectorN<f64, N>,
+        fxn: &S,
         t_0: f64,
-        y_0: &VectorN<f64, N>,
+        y_0: &OVector<f64, N>,
         step: f64,
     ) -> StepResult<N>
     where
-        DefaultAllocator: Allocator<f64, N>;
+        DefaultAllocator: Allocator<N>;
+
+    // Step which also integrates the integrands g(t, y) over it. Steppers with
+    // stages use their weights at the stages. The default uses Simpson's rule with
+    // the midpoint from a half step
+    fn step_integrating<N: DimName + Dim, S: OdeSystem<N> + ?Sized>(
+        &self,
+        fxn: &S,
+        t_0: f64,
+        y_0: &OVector<f64, N>,
+        step: f64,
+        integrands: &[Diagnostic<N>],
+    ) -> (StepResult<N>, Vec<f64>)
+    where
+        DefaultAllocator: Allocator<N>,
+    {
+        let res = self.step(fxn, t_0, y_0, step);
+        let integrals = match integrands.is_empty() {
+            true => Vec::new(),
+            false => {
+                let mid = self.step(fxn, t_0, y_0, 0.5 * step).value;
+                simpson(integrands, t_0, step, y_0, &mid, &res.value)
+            }
+        };
+        (res, integrals)
+    }
+}
+
+// Simpson's rule for the integrands over a step from the states at its start,
+// midpoint and end
+pub fn simpson<N: DimName + Dim>(
+    integrands: &[Diagnostic<N>],
+    t_0: f64,
+    step: f64,
+    y_0: &OVector<f64, N>,
+    y_mid: &OVector<f64, N>,
+    y_1: &OVector<f64, N>,
+) -> Vec<f64>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    integrands
+        .iter()
+        .map(|g| {
+            let sum = g(t_0, y_0) + 4.0 * g(t_0 + 0.5 * step, y_mid) + g(t_0 + step, y_1);
+            step * sum / 6.0
+        })
+        .collect()
 }
 #[derive(Debug, Clone, PartialEq)]
 pub struct StepResult<N: DimName + Dim>
 where
-    DefaultAllocator: Allocator<f64, N>,
+    DefaultAllocator: Allocator<N>,
 {
     // Error norm of current step
     pub error: f64,
     // Solution estimate
-    pub value: VectorN<f64, N>,
+    pub value: OVector<f64, N>,
     // Derivative evaluation
-    pub dyn_eval: VectorN<f64, N>,
+    pub dyn_eval: OVector<f64, N>,
 }
 
 pub trait StepWithError {
-    fn step<N: DimName + Dim>(
+    fn step<N: DimName + Dim, S: OdeSystem<N> + ?Sized>(
         &self,
-        fxn: fn(f64, &VectorN<f64, N>) -> VectorN<f64, N>,
+        fxn: &S,
         t_0: f64,
-        y_0: &VectorN<f64, N>,
+        y_0: &OVector<f64, N>,
         step: f64,
-        atol: &VectorN<f64, N>,
+        atol: &OVector<f64, N>,
         rtol: f64,
     ) -> StepResult<N>
     where
-        DefaultAllocator: Allocator<f64, N>;
+        DefaultAllocator: Allocator<N>;
+
+    // Step which also integrates the integrands g(t, y) over it. Steppers with
+    // stages use the weights of the propagated solution at the stages. The default
+    // uses Simpson's rule with the midpoint from a half step
+    #[allow(clippy::too_many_arguments)]
+    fn step_integrating<N: DimName + Dim, S: OdeSystem<N> + ?Sized>(
+        &self,
+        fxn: &S,
+        t_0: f64,
+        y_0: &OVector<f64, N>,
+        step: f64,
+        atol: &OVector<f64, N>,
+        rtol: f64,
+        integrands: &[Diagnostic<N>],
+    ) -> (StepResult<N>, Vec<f64>)
+    where
+        DefaultAllocator: Allocator<N>,
+    {
+        let res = self.step(fxn, t_0, y_0, step, atol, rtol);
+        let integrals = match integrands.is_empty() {
+            true => Vec::new(),
+            false => {
+                let mid = self.step(fxn, t_0, y_0, 0.5 * step, atol, rtol).value;
+                simpson(integrands, t_0, step, y_0, &mid, &res.value)
+            }
+        };
+        (res, integrals)
+    }
 }
 
 pub trait RkOrder {
@@ -91,10 +290,10 @@ pub trait RkOrder {
 // Tolerance object for RIDC integrator
 pub struct Tolerances<N: Dim + DimName>
 where
-    DefaultAllocator: Allocator<f64, N>,
+    DefaultAllocator: Allocator<N>,
 {
     // Absolute tolerance
-    pub abs: Option<VectorN<f64, N>>,
+    pub abs: Option<OVector<f64, N>>,
     // Relative tolerance
     pub rel: Option<f64>,
 }
@@ -103,24 +302,311 @@ where
 #[derive(Debug, Clone, PartialEq)]
 pub struct IntegOptions<N: DimName + Dim>
 where
-    DefaultAllocator: Allocator<f64, N>,
+    DefaultAllocator: Allocator<N>,
 {
     // Absolute tolerance
-    pub atol: Option<VectorN<f64, N>>,
+    pub atol: Option<OVector<f64, N>>,
     // Relative Tolerance
     pub rtol: Option<f64>,
     // Minimum step. Errors if step goes below this threshold
     pub min_step: Option<f64>,
+    // Maximum step of adaptive integrators
+    pub max_step: Option<f64>,
+    // Consecutive rejections of a step after which adaptive integrators error
+    // (default 50)
+    pub max_rejections: Option<usize>,
+    // Whether adaptive integrators record their rejected steps in the result
+    pub record_rejections: Option<bool>,
+    // Size of the first step of adaptive integrators. Chosen automatically from the
+    // dynamics at the initial state when None
+    pub first_step: Option<f64>,
+    // Diagnostic functionals to record at every accepted step
+    pub diagnostics: Option<Vec<Diagnostic<N>>>,
+    // Known discontinuity times of the dynamics (e.g. thrust on/off or table
+    // breakpoints). Steps land exactly on each of them
+    pub breakpoints: Option<Vec<f64>>,
+    // Conditions ending the integration early, in order of priority (see
+    // `stopping`)
+    pub stop_conditions: Option<Vec<StopCondition<N>>>,
+    // Events recorded without ending the integration (see `stopping`)
+    pub events: Option<Vec<Event<N>>>,
+    // Integrands g(t, y) integrated along the solution by the Runge Kutta
+    // integrators (e.g. fuel used or a cost functional), reported in
+    // `IntegResult::integrals`
+    pub integrands: Option<Vec<Diagnostic<N>>>,
+    // Components of the state whose minimum and maximum over the integration are
+    // tracked by the Runge Kutta integrators (see `extrema`)
+    pub extrema: Option<Vec<usize>>,
+    // Validity constraints g(t, y) >= 0 of the dynamics. Steps are shrunk to keep
+    // the dynamics from being evaluated outside of them (see `domain`)
+    pub domain: Option<Vec<Diagnostic<N>>>,
+    // Whether adaptive integrators also control the error of the cubic hermite
+    // interpolant between the steps (see `defect`)
+    pub defect_control: Option<bool>,
+    // Distance past zero events and guards must reach to fire, against chattering
+    // (see `stopping`)
+    pub event_dead_band: Option<f64>,
+    // Time after the start during which events and guards are disarmed, against
+    // chattering (see `stopping`)
+    pub event_rearm: Option<f64>,
 }
 impl<N: DimName + Dim> IntegOptions<N>
 where
-    DefaultAllocator: Allocator<f64, N>,
+    DefaultAllocator: Allocator<N>,
 {
     pub fn default() -> Self {
         Self {
             atol: None,
             rtol: None,
             min_step: None,
+            max_step: None,
+            max_rejections: None,
+            record_rejections: None,
+            first_step: None,
+            diagnostics: None,
+            breakpoints: None,
+            stop_conditions: None,
+            events: None,
+            integrands: None,
+            extrema: None,
+            domain: None,
+            defect_control: None,
+            event_dead_band: None,
+            event_rearm: None,
+        }
+    }
+}
+
+// Step from t toward t_end which finishes without a sliver step: the remainder
+// when it is within 1% of the step (the step then lands exactly on t_end), half
+// the remainder when it is less than two steps, and the step otherwise. The flag
+// marks the last step
+pub fn approach_end(t: f64, t_end: f64, step: f64) -> (f64, bool) {
+    let rest = t_end - t;
+    if rest.abs() <= 1.01 * step.abs() {
+        (rest, true)
+    } else if rest.abs() < 2.0 * step.abs() {
+        (0.5 * rest, false)
+    } else {
+        (step.abs().copysign(rest), false)
+    }
+}
+
+// Breakpoints strictly inside an integration interval, in the order the
+// integration reaches them
+#[derive(Debug, Clone, PartialEq)]
+pub struct Breakpoints {
+    // Breakpoint times in integration order
+    times: Vec<f64>,
+    // Index of the next breakpoint to land on
+    next: usize,
+}
+
+impl Breakpoints {
+    pub fn new(times: &[f64], t_0: f64, t_end: f64) -> Self {
+        let mut inside: Vec<f64> = times
+            .iter()
+            .cloned()
+            .filter(|t| (t - t_0) * (t_end - t) > 0.0)
+            .collect();
+        inside.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        inside.dedup();
+        if t_end < t_0 {
+            inside.reverse();
+        }
+        Breakpoints {
+            times: inside,
+            next: 0,
+        }
+    }
+
+    // Limits a step from t so that it ends on the next breakpoint instead of passing
+    // it. A step falling just short of the breakpoint is stretched onto it so no
+    // sliver step is left behind. Returns the step and the breakpoint it lands on.
+    // The step stops a few units in the last place short of the breakpoint so every
+    // dynamics evaluation of the step is on the near side of the discontinuity,
+    // the integrator then snaps the time onto the breakpoint
+    pub fn limit(&self, t: f64, step: f64) -> (f64, Option<f64>) {
+        match self.times.get(self.next) {
+            Some(t_bp) if (t_bp - t).abs() <= 1.01 * step.abs() => {
+                let margin = 4.0 * f64::EPSILON * t_bp.abs().max(t.abs());
+                let t_near = if t < *t_bp {
+                    t_bp - margin
+                } else {
+                    t_bp + margin
+                };
+                (t_near - t, Some(*t_bp))
+            }
+            _ => (step, None),
+        }
+    }
+
+    // Records that the integration landed on the next breakpoint
+    pub fn passed(&mut self) {
+        self.next += 1;
+    }
+}
+
+// Bounds on the step size proposed by an adaptive step controller, with detection
+// of steps which keep getting rejected
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepBounds {
+    // Smallest step the controller may propose after a rejection
+    pub min_step: f64,
+    // Largest step the controller may propose
+    pub max_step: f64,
+    // Number of consecutive rejections after which the step is considered stalled
+    pub max_rejections: usize,
+    // Consecutive rejections of the current step
+    rejections: usize,
+}
+
+impl StepBounds {
+    pub fn new(min_step: f64, max_step: Option<f64>, max_rejections: Option<usize>) -> Self {
+        StepBounds {
+            min_step,
+            max_step: max_step.unwrap_or(f64::INFINITY),
+            max_rejections: max_rejections.unwrap_or(50),
+            rejections: 0,
+        }
+    }
+
+    // Bounds the step proposed for the next step after an accepted one
+    pub fn accept(&mut self, step: f64) -> f64 {
+        self.rejections = 0;
+        step.abs()
+            .max(self.min_step)
+            .min(self.max_step)
+            .copysign(step)
+    }
+
+    // Bounds the step proposed to retry a step rejected at time t with the given
+    // error estimate. Errors if the step underflows the minimum step or the step
+    // has stalled
+    pub fn reject(&mut self, t: f64, step: f64, error: f64) -> Result<f64, AdaptiveError> {
+        self.rejections += 1;
+        let kind = if step.abs() < self.min_step {
+            AdaptiveErrorKind::Underflow
+        } else if self.rejections > self.max_rejections {
+            AdaptiveErrorKind::Stalled
+        } else {
+            return Ok(step.abs().min(self.max_step).copysign(step));
+        };
+        Err(AdaptiveError {
+            t,
+            h: step,
+            err_est: error,
+            kind,
+            rejected: Vec::new(),
+        })
+    }
+}
+
+// Reason an adaptive step could not be taken
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AdaptiveErrorKind {
+    // The step proposed after a rejection is below the minimum step
+    Underflow,
+    // The step was rejected more than the allowed number of times in a row
+    Stalled,
+}
+
+impl AdaptiveErrorKind {
+    // Message of the failure, without the step it happened on
+    pub fn message(self) -> &'static str {
+        match self {
+            AdaptiveErrorKind::Underflow => {
+                "[ADAPTIVE] Step size underflow, below the minimum step"
+            }
+            AdaptiveErrorKind::Stalled => {
+                "[ADAPTIVE] Step stalled, too many consecutive rejections"
+            }
+        }
+    }
+}
+
+// Step of an adaptive integrator which could not be taken
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdaptiveError {
+    // Time the step was attempted from
+    pub t: f64,
+    // Step proposed to retry it
+    pub h: f64,
+    // Error estimate of the last attempt (weighted rms norm)
+    pub err_est: f64,
+    // Why the step could not be taken
+    pub kind: AdaptiveErrorKind,
+    // Steps rejected up to the failure, with `record_rejections`
+    pub rejected: Vec<RejectedStep>,
+}
+
+impl fmt::Display for AdaptiveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            AdaptiveErrorKind::Underflow => write!(
+                f,
+                "[ADAPTIVE] Step size underflow at t = {}: h = {:e} is below the minimum step \
+                 (error estimate {:e})",
+                self.t, self.h, self.err_est
+            ),
+            AdaptiveErrorKind::Stalled => write!(
+                f,
+                "[ADAPTIVE] Step stalled at t = {}: too many consecutive rejections \
+                 (h = {:e}, error estimate {:e})",
+                self.t, self.h, self.err_est
+            ),
+        }
+    }
+}
+
+// Error of an integrator. Failures which know where they happened report it,
+// any other error is a static message
+#[derive(Debug, Clone, PartialEq)]
+pub enum IntegError {
+    // An adaptive step could not be taken
+    Step(AdaptiveError),
+    // Steps from t leave the domain or fail to evaluate down to the minimum step
+    Domain { t: f64, min_step: f64 },
+    // Any other failure
+    Failed(&'static str),
+}
+
+impl fmt::Display for IntegError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IntegError::Step(err) => err.fmt(f),
+            IntegError::Domain { t, min_step } => write!(
+                f,
+                "[DOMAIN] Steps from t = {} leave the domain or fail to evaluate down to the \
+                 minimum step {:e}",
+                t, min_step
+            ),
+            IntegError::Failed(err) => f.write_str(err),
+        }
+    }
+}
+
+impl From<&'static str> for IntegError {
+    fn from(err: &'static str) -> Self {
+        IntegError::Failed(err)
+    }
+}
+
+impl From<AdaptiveError> for IntegError {
+    fn from(err: AdaptiveError) -> Self {
+        IntegError::Step(err)
+    }
+}
+
+// The static message of the error, for callers which report static errors
+impl From<IntegError> for &'static str {
+    fn from(err: IntegError) -> Self {
+        match err {
+            IntegError::Step(err) => err.kind.message(),
+            IntegError::Domain { .. } => {
+                "[DOMAIN] Steps leave the domain or fail to evaluate down to the minimum step"
+            }
+            IntegError::Failed(err) => err,
         }
     }
 }
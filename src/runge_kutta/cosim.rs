@@ -0,0 +1,398 @@
+/// Co-Simulation (runge_kutta/cosim)
+///
+/// Step and exchange interface in the manner of FMI for co-simulation, so a model
+/// integrated here can be one slave among the tools (Simulink, Modelica models,
+/// flight software, other FMUs) a master algorithm advances together. The master
+/// owns the communication points: between them it sets the inputs of each slave,
+/// calls `do_step(dt)`, and reads the outputs to pass on. `CoSim` wraps a
+/// `ControlledSystem` x' = f(t, x, u) and an output map y = h(t, x, u), and
+/// integrates across each communication step with its own adaptive stepper, as
+/// finely as its tolerances need, with the inputs held constant (zero order hold).
+/// The step the controller proposed last carries over to the next communication
+/// step.
+///
+/// A communication step ended early by a stopping condition of the options (an
+/// event the master must handle) returns the time reached, like a discarded step
+/// in FMI. The state of the slave can be saved and restored (`save`, `restore`),
+/// for masters which iterate a communication step or roll it back.
+///
+/// For hardware in the loop the slave can be paced to the wall clock
+/// (`with_real_time`): the simulation time advances `rate` seconds per wall clock
+/// second, measured from the first communication step. With `Pacing::Sleep` a step
+/// which finishes ahead of the wall clock sleeps until its end is due, so the slave
+/// tracks real time; with `Pacing::Deadline` it never waits (the master paces
+/// itself) and only the deadlines are checked. Either way a step finishing after
+/// its end was due is an overrun, logged with how late it was (`overruns`): the
+/// model or its tolerances are too heavy for real time. The pacing is soft, only as
+/// precise as the sleeps of the operating system. Restoring a saved state moves the
+/// simulation time, so the pacing restarts from the next step.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DefaultAllocator, Dim, DimName, OVector};
+
+// local imports
+use super::adaptive::AdaptiveStep;
+use super::common::IntegOptions;
+use crate::systems::control::ControlledSystem;
+
+// Standard library imports
+use std::thread;
+use std::time::{Duration, Instant};
+
+// === End Imports ===
+
+// Output map y = h(t, x, u)
+pub type OutputMap<N, M, P> = fn(f64, &OVector<f64, N>, &OVector<f64, M>) -> OVector<f64, P>;
+
+// Saved state of a co-simulation slave
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoSimState<N: Dim + DimName, M: Dim + DimName>
+where
+    DefaultAllocator: Allocator<N> + Allocator<M>,
+{
+    // Time of the slave
+    pub t: f64,
+    // State of the dynamics
+    pub state: OVector<f64, N>,
+    // Inputs held
+    pub input: OVector<f64, M>,
+    // Step proposed for the next communication step
+    pub next_step: Option<f64>,
+}
+
+// How a slave paced to the wall clock keeps up with it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pacing {
+    // Sleeps until the end of each step is due
+    Sleep,
+    // Never waits, only checks the deadlines
+    Deadline,
+}
+
+// A communication step which finished after its end was due
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Overrun {
+    // Simulation time at the end of the step
+    pub t: f64,
+    // Wall clock time by which the step was late
+    pub late: Duration,
+}
+
+#[derive(Debug, Clone)]
+struct RealTime {
+    // Simulation seconds per wall clock second
+    rate: f64,
+    pacing: Pacing,
+    // Wall clock and simulation time the pacing is measured from
+    anchor: Option<(Instant, f64)>,
+    // Steps which finished late, in order
+    overruns: Vec<Overrun>,
+}
+
+pub struct CoSim<'a, A, S, N: Dim + DimName, M: Dim + DimName, P: Dim + DimName>
+where
+    DefaultAllocator: Allocator<N> + Allocator<M> + Allocator<P>,
+{
+    // Stepper integrating across the communication steps
+    stepper: &'a A,
+    // Dynamics x' = f(t, x, u)
+    system: S,
+    // Outputs y = h(t, x, u)
+    output: OutputMap<N, M, P>,
+    // Options of the integration over each communication step
+    options: IntegOptions<N>,
+    // Time, state, inputs and proposed step of the slave
+    current: CoSimState<N, M>,
+    // Pacing to the wall clock, if any
+    real_time: Option<RealTime>,
+}
+
+impl<'a, A, S, N: Dim + DimName, M: Dim + DimName, P: Dim + DimName> CoSim<'a, A, S, N, M, P>
+where
+    A: AdaptiveStep,
+    S: ControlledSystem<N, M>,
+    DefaultAllocator: Allocator<N> + Allocator<M> + Allocator<P>,
+{
+    pub fn new(
+        stepper: &'a A,
+        system: S,
+        output: OutputMap<N, M, P>,
+        t_0: f64,
+        x_0: OVector<f64, N>,
+        u_0: OVector<f64, M>,
+    ) -> Self {
+        CoSim {
+            stepper,
+            system,
+            output,
+            options: IntegOptions::default(),
+            current: CoSimState {
+                t: t_0,
+                state: x_0,
+                input: u_0,
+                next_step: None,
+            },
+            real_time: None,
+        }
+    }
+
+    // Sets the options of the integration over each communication step
+    pub fn with_options(mut self, options: IntegOptions<N>) -> Self {
+        self.options = options;
+        self
+    }
+
+    // Paces the slave to the wall clock, advancing `rate` simulation seconds per
+    // wall clock second
+    pub fn with_real_time(mut self, rate: f64, pacing: Pacing) -> Result<Self, &'static str> {
+        if rate <= 0.0 || !rate.is_finite() {
+            return Err("[COSIM] Real time rate must be positive");
+        }
+        self.real_time = Some(RealTime {
+            rate,
+            pacing,
+            anchor: None,
+            overruns: Vec::new(),
+        });
+        Ok(self)
+    }
+
+    // Steps which finished after their end was due (empty when not paced)
+    pub fn overruns(&self) -> &[Overrun] {
+        self.real_time
+            .as_ref()
+            .map_or(&[], |real_time| &real_time.overruns)
+    }
+
+    pub fn time(&self) -> f64 {
+        self.current.t
+    }
+
+    pub fn state(&self) -> &OVector<f64, N> {
+        &self.current.state
+    }
+
+    // Overwrites the state between steps (e.g. a reset decided by the master)
+    pub fn set_state(&mut self, state: OVector<f64, N>) {
+        self.current.state = state;
+    }
+
+    pub fn input(&self) -> &OVector<f64, M> {
+        &self.current.input
+    }
+
+    // Sets the inputs held over the next communication steps
+    pub fn set_input(&mut self, input: OVector<f64, M>) {
+        self.current.input = input;
+    }
+
+    // Sets a single input
+    pub fn set_input_value(&mut self, index: usize, value: f64) -> Result<(), &'static str> {
+        match self.current.input.get_mut(index) {
+            Some(u) => {
+                *u = value;
+                Ok(())
+            }
+            None => Err("[COSIM] Input outside of the inputs"),
+        }
+    }
+
+    // Outputs at the current time
+    pub fn outputs(&self) -> OVector<f64, P> {
+        (self.output)(self.current.t, &self.current.state, &self.current.input)
+    }
+
+    pub fn save(&self) -> CoSimState<N, M> {
+        self.current.clone()
+    }
+
+    pub fn restore(&mut self, saved: &CoSimState<N, M>) {
+        self.current = saved.clone();
+        if let Some(real_time) = self.real_time.as_mut() {
+            real_time.anchor = None;
+        }
+    }
+
+    // Advances the slave over the communication step dt with the inputs held,
+    // returning the time reached (before t + dt if a stopping condition fired)
+    pub fn do_step(&mut self, dt: f64) -> Result<f64, &'static str> {
+        if dt <= 0.0 || dt.is_nan() {
+            return Err("[COSIM] Communication step must be positive");
+        }
+        if let Some(real_time) = self.real_time.as_mut() {
+            real_time
+                .anchor
+                .get_or_insert((Instant::now(), self.current.t));
+        }
+        let input = &self.current.input;
+        let system = &self.system;
+        let dynamics = |t: f64, x: &OVector<f64, N>| system.controlled_dynamics(t, x, input);
+        let options = IntegOptions {
+            first_step: self.current.next_step.or(self.options.first_step),
+            ..self.options.clone()
+        };
+        let ans = self.stepper.integrate(
+            dynamics,
+            self.current.t,
+            self.current.state.clone(),
+            dt,
+            options,
+        )?;
+        self.current.t = ans.t;
+        self.current.state = ans.last_y().clone();
+        self.current.next_step = ans.next_step.or(self.current.next_step);
+        if let Some(real_time) = self.real_time.as_mut() {
+            real_time.pace(ans.t);
+        }
+        Ok(ans.t)
+    }
+}
+
+impl RealTime {
+    // Waits for or checks the deadline of a step ending at simulation time t
+    fn pace(&mut self, t: f64) {
+        if let Some((wall, t_0)) = self.anchor {
+            let due = wall + Duration::from_secs_f64(((t - t_0) / self.rate).max(0.0));
+            let now = Instant::now();
+            if now > due {
+                self.overruns.push(Overrun { t, late: now - due });
+            } else if self.pacing == Pacing::Sleep {
+                thread::sleep(due - now);
+            }
+        }
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runge_kutta::rk_embed::DOPRI78;
+    use crate::runge_kutta::stopping::StopCondition;
+    use na::{Vector1, Vector2};
+
+    // first order lag x' = -x + u
+    fn lag(_t: f64, x: &Vector1<f64>, u: &Vector1<f64>) -> Vector1<f64> {
+        Vector1::new(-x[0] + u[0])
+    }
+
+    // outputs the state and its rate
+    fn measure(_t: f64, x: &Vector1<f64>, u: &Vector1<f64>) -> Vector2<f64> {
+        Vector2::new(x[0], -x[0] + u[0])
+    }
+
+    #[test]
+    fn test_cosim() {
+        // a proportional controller stepping at 0.1 closes the loop around the
+        // lag: with the control held between communication points the state
+        // follows the discrete recursion of the zero order hold
+        let opts = IntegOptions {
+            atol: Some(Vector1::new(1e-13)),
+            rtol: Some(1e-13),
+            ..IntegOptions::default()
+        };
+        let mut slave = CoSim::new(
+            &*DOPRI78,
+            lag,
+            measure,
+            0.0,
+            Vector1::new(0.0),
+            Vector1::new(0.0),
+        )
+        .with_options(opts.clone());
+        let (dt, gain, target): (f64, f64, f64) = (0.1, 2.0, 1.0);
+        let decay = (-dt).exp();
+        let mut x = 0.0;
+        for _ in 0..30 {
+            let u = gain * (target - slave.outputs()[0]);
+            slave.set_input_value(0, u).unwrap();
+            assert_eq!(slave.do_step(dt).unwrap(), slave.time());
+            x = decay * x + (1.0 - decay) * u;
+            assert!((slave.state()[0] - x).abs() < 1e-10);
+        }
+        assert!((slave.time() - 3.0).abs() < 1e-12);
+        assert!(slave.set_input_value(1, 0.0).is_err());
+        assert!(slave.do_step(0.0).is_err());
+
+        // rolling back a step and redoing it with another input
+        let saved = slave.save();
+        slave.do_step(dt).unwrap();
+        slave.restore(&saved);
+        slave.set_input(Vector1::new(0.0));
+        slave.do_step(dt).unwrap();
+        assert!((slave.state()[0] - decay * x).abs() < 1e-10);
+
+        // a stopping condition ends the communication step early, at the time the
+        // state falls to half its value
+        let stops = IntegOptions {
+            stop_conditions: Some(vec![StopCondition::Guard(|_t, x| x[0] - 0.25)]),
+            ..opts
+        };
+        let mut slave = CoSim::new(
+            &*DOPRI78,
+            lag,
+            measure,
+            0.0,
+            Vector1::new(0.5),
+            Vector1::new(0.0),
+        )
+        .with_options(stops);
+        let reached = slave.do_step(1.0).unwrap();
+        assert!((reached - 2.0_f64.ln()).abs() < 1e-9);
+        assert_eq!(slave.time(), reached);
+    }
+
+    #[test]
+    fn test_real_time() {
+        // ten simulation seconds per wall clock second: half a simulated second
+        // takes at least 50 ms of sleeping, with the lag far lighter than that
+        let start = Instant::now();
+        let mut slave = CoSim::new(
+            &*DOPRI78,
+            lag,
+            measure,
+            0.0,
+            Vector1::new(0.0),
+            Vector1::new(1.0),
+        )
+        .with_real_time(10.0, Pacing::Sleep)
+        .unwrap();
+        for _ in 0..5 {
+            slave.do_step(0.1).unwrap();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert!(slave.overruns().is_empty());
+
+        // a million simulation seconds per second is beyond any model: every
+        // step is late, and reported without waiting
+        let mut slave = CoSim::new(
+            &*DOPRI78,
+            lag,
+            measure,
+            0.0,
+            Vector1::new(0.0),
+            Vector1::new(1.0),
+        )
+        .with_real_time(1e6, Pacing::Deadline)
+        .unwrap();
+        for _ in 0..5 {
+            slave.do_step(0.1).unwrap();
+        }
+        let overruns = slave.overruns();
+        assert_eq!(overruns.len(), 5);
+        assert!((overruns[4].t - 0.5).abs() < 1e-12 && overruns[4].late > Duration::ZERO);
+        assert!(CoSim::new(
+            &*DOPRI78,
+            lag,
+            measure,
+            0.0,
+            Vector1::new(0.0),
+            Vector1::new(1.0)
+        )
+        .with_real_time(0.0, Pacing::Sleep)
+        .is_err());
+    }
+}
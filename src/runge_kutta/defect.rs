@@ -0,0 +1,174 @@
+/// Defect Control (runge_kutta/defect)
+///
+/// The error estimate of an embedded pair only measures the error at the end of a
+/// step. Solutions between the steps come from an interpolant, which can be much
+/// less accurate than the step endpoints when the steps are long. With
+/// `IntegOptions::defect_control` set, the adaptive integrators also control the
+/// error of the interpolant: the cubic hermite through the states and dynamics at
+/// both ends of a step,
+///
+/// u(t_0 + theta h), theta in [0, 1]
+///
+/// is checked against the dynamics at off-node points. Its defect (residual)
+/// u'(t) - f(t, u(t)) there, times h, is a local error measure of the interpolant,
+/// and the step is rejected when its weighted rms norm exceeds one, the same as the
+/// endpoint error. The defect of a cubic hermite peaks at theta = 1/2 -+ sqrt(3)/6,
+/// where it is sampled, costing two dynamics evaluations per step (Enright,
+/// "A new error-control for initial value solvers", 1989).
+///
+/// The interpolant is third order, so defect control limits the steps of high
+/// order methods to what the cubic can follow: the solution is accurate to the
+/// tolerance at every output time rather than only at the step endpoints.
+/// `dense_output` evaluates the interpolant on the result of an integration.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DefaultAllocator, Dim, DimName, OVector};
+
+// local imports
+use super::common::IntegResult;
+use crate::systems::OdeSystem;
+use crate::utils::norms::weighted_rms_norm;
+
+// === End Imports ===
+
+// Fractions of the step where the defect of the cubic hermite is largest
+const DEFECT_THETAS: [f64; 2] = [0.211_324_865_405_187_1, 0.788_675_134_594_812_9];
+
+// Value and time derivative of the cubic hermite through (y_0, f_0) at t_0 and
+// (y_1, f_1) at t_0 + h, at t_0 + theta h
+pub fn hermite_cubic<N: Dim + DimName>(
+    y_0: &OVector<f64, N>,
+    f_0: &OVector<f64, N>,
+    y_1: &OVector<f64, N>,
+    f_1: &OVector<f64, N>,
+    h: f64,
+    theta: f64,
+) -> (OVector<f64, N>, OVector<f64, N>)
+where
+    DefaultAllocator: Allocator<N>,
+{
+    let (s, s2, s3) = (theta, theta * theta, theta * theta * theta);
+    let value = (2.0 * s3 - 3.0 * s2 + 1.0) * y_0
+        + (h * (s3 - 2.0 * s2 + s)) * f_0
+        + (3.0 * s2 - 2.0 * s3) * y_1
+        + (h * (s3 - s2)) * f_1;
+    let deriv = (6.0 * (s2 - s) / h) * (y_0 - y_1)
+        + (3.0 * s2 - 4.0 * s + 1.0) * f_0
+        + (3.0 * s2 - 2.0 * s) * f_1;
+    (value, deriv)
+}
+
+// Error norm of the interpolant over a step: the largest weighted rms norm of h
+// times its defect at the sample points
+pub(crate) fn defect_error<N: Dim + DimName, S: OdeSystem<N> + ?Sized>(
+    fxn: &S,
+    t_0: f64,
+    y_0: &OVector<f64, N>,
+    f_0: &OVector<f64, N>,
+    h: f64,
+    y_1: &OVector<f64, N>,
+    f_1: &OVector<f64, N>,
+    atol: &OVector<f64, N>,
+    rtol: f64,
+) -> f64
+where
+    DefaultAllocator: Allocator<N>,
+{
+    DEFECT_THETAS
+        .iter()
+        .map(|theta| {
+            let (u, du) = hermite_cubic(y_0, f_0, y_1, f_1, h, *theta);
+            let defect = (du - fxn.dynamics(t_0 + theta * h, &u)) * h.abs();
+            weighted_rms_norm(&defect, y_0, y_1, atol, rtol)
+        })
+        .fold(0.0, f64::max)
+}
+
+// Solution at t between the stored states of an integration, from the cubic
+// hermite on the step containing t. None outside of the integrated interval
+pub fn dense_output<N: Dim + DimName, S: OdeSystem<N> + ?Sized>(
+    fxn: &S,
+    results: &IntegResult<N>,
+    t: f64,
+) -> Option<OVector<f64, N>>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    let times = &results.times;
+    let forward = times[times.len() - 1] >= times[0];
+    // index of the step whose end is the first node at or past t
+    let k = times.iter().position(|t_k| match forward {
+        true => *t_k >= t,
+        false => *t_k <= t,
+    })?;
+    if k == 0 {
+        return match times[0] == t {
+            true => Some(results.states[0].clone()),
+            false => None,
+        };
+    }
+    let (t_0, t_1) = (times[k - 1], times[k]);
+    let (y_0, y_1) = (&results.states[k - 1], &results.states[k]);
+    let h = t_1 - t_0;
+    let f_0 = fxn.dynamics(t_0, y_0);
+    let f_1 = fxn.dynamics(t_1, y_1);
+    Some(hermite_cubic(y_0, &f_0, y_1, &f_1, h, (t - t_0) / h).0)
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runge_kutta::adaptive::AdaptiveStep;
+    use crate::runge_kutta::common::IntegOptions;
+    use crate::runge_kutta::rk_embed::DOPRI78;
+    use na::Vector2;
+
+    #[test]
+    fn test_defect_control() {
+        // harmonic oscillator, y = (cos t, -sin t)
+        let sho = |_t: f64, y: &Vector2<f64>| Vector2::new(y[1], -y[0]);
+        let truth = |t: f64| Vector2::new(t.cos(), -t.sin());
+        let tol = 1e-8;
+        let run = |defect_control| {
+            let options = IntegOptions {
+                atol: Some(Vector2::repeat(tol)),
+                rtol: Some(tol),
+                defect_control: Some(defect_control),
+                ..IntegOptions::default()
+            };
+            let ans = DOPRI78
+                .integrate(sho, 0.0, Vector2::new(1.0, 0.0), 10.0, options)
+                .unwrap();
+            // worst error of the interpolant between the steps
+            let worst = (0..1000)
+                .map(|i| {
+                    let t = 0.01 * i as f64 + 0.005;
+                    (dense_output(&sho, &ans, t).unwrap() - truth(t)).amax()
+                })
+                .fold(0.0, f64::max);
+            (ans.times.len(), worst, ans)
+        };
+        let (steps, worst, plain) = run(false);
+        let (steps_dc, worst_dc, _) = run(true);
+        println!(
+            "PLAIN {} steps {:e} | DEFECT CONTROL {} steps {:e}",
+            steps, worst, steps_dc, worst_dc
+        );
+        assert!(worst > 100.0 * tol);
+        assert!(worst_dc < 10.0 * tol);
+        assert!(steps_dc > steps);
+
+        // the interpolant hits the nodes and stays inside the integrated interval
+        assert_eq!(
+            dense_output(&sho, &plain, plain.times[3]).unwrap(),
+            plain.states[3]
+        );
+        assert_eq!(dense_output(&sho, &plain, 0.0).unwrap(), plain.states[0]);
+        assert!(dense_output(&sho, &plain, 10.5).is_none());
+        assert!(dense_output(&sho, &plain, -0.5).is_none());
+    }
+}
@@ -0,0 +1,177 @@
+/// Domain Guard (runge_kutta/domain)
+///
+/// Dynamics are often only defined on part of the state space: an atmosphere model
+/// below zero altitude, a rate law with a negative concentration under a square
+/// root. Validity constraints g(t, y) >= 0 registered through
+/// `IntegOptions::domain` keep the Runge-Kutta integrators from evaluating the dynamics
+/// outside of that domain, where they may return NaN or panic.
+///
+/// The dynamics are wrapped in a `DomainGuard` which checks every state the
+/// stepper evaluates (the stages of a Runge-Kutta step) against the constraints
+/// and skips the evaluation of any state outside, flagging the step instead. A
+/// flagged step, or one which ends outside of the domain, is retried with half the
+/// step until it stays inside, and the integration errors if that takes the step
+/// below the minimum step.
+///
+/// Steps on which fallible dynamics (see `systems::fallible`) fail recoverably are
+/// retried the same way, and unrecoverable failures end the integration with their
+/// error.
+///
+/// A solution which really leaves the domain (a trajectory reaching the ground)
+/// can end there instead: with a `StopCondition::DomainExit` registered, the
+/// integration stops on the boundary (to within the minimum step) and reports it
+/// in `IntegResult::stopped`.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DefaultAllocator, Dim, DimName, OVector};
+
+// local imports
+use super::common::{Diagnostic, IntegError};
+use crate::systems::fallible::{step_failed, EvalFailure};
+use crate::systems::OdeSystem;
+
+// Standard library imports
+use std::cell::Cell;
+
+// === End Imports ===
+
+pub struct DomainGuard<'a, S, N: Dim + DimName>
+where
+    S: OdeSystem<N>,
+    DefaultAllocator: Allocator<N>,
+{
+    // Guarded dynamics
+    system: &'a S,
+    // Validity constraints g(t, y) >= 0
+    constraints: &'a [Diagnostic<N>],
+    // Whether a state outside of the domain was evaluated since the last reset
+    violated: Cell<bool>,
+}
+
+impl<'a, S, N: Dim + DimName> DomainGuard<'a, S, N>
+where
+    S: OdeSystem<N>,
+    DefaultAllocator: Allocator<N>,
+{
+    pub fn new(system: &'a S, constraints: &'a [Diagnostic<N>]) -> Self {
+        DomainGuard {
+            system,
+            constraints,
+            violated: Cell::new(false),
+        }
+    }
+
+    pub fn contains(&self, t: f64, y: &OVector<f64, N>) -> bool {
+        self.constraints.iter().all(|g| g(t, y) >= 0.0)
+    }
+
+    // Whether a step evaluated the dynamics outside of the domain, clearing the
+    // flag for the next step
+    pub fn reset(&self) -> bool {
+        self.violated.replace(false)
+    }
+
+    // Whether a step has to be retried with a smaller step, because it left the
+    // domain or the dynamics failed recoverably (see `systems::fallible`). Errors on
+    // unrecoverable failures of the dynamics
+    pub fn rejected(&self) -> Result<bool, &'static str> {
+        let left = self.reset();
+        Ok(step_failed(self.system)? || left)
+    }
+}
+
+impl<'a, S, N: Dim + DimName> OdeSystem<N> for DomainGuard<'a, S, N>
+where
+    S: OdeSystem<N>,
+    DefaultAllocator: Allocator<N>,
+{
+    fn dynamics(&self, t: f64, y: &OVector<f64, N>) -> OVector<f64, N> {
+        if self.violated.get() || !self.contains(t, y) {
+            // the step is retried: its value does not matter
+            self.violated.set(true);
+            return OVector::<f64, N>::zeros();
+        }
+        self.system.dynamics(t, y)
+    }
+
+    fn breakpoints(&self) -> Vec<f64> {
+        self.system.breakpoints()
+    }
+
+    fn take_failure(&self) -> Option<EvalFailure> {
+        self.system.take_failure()
+    }
+}
+
+// Halves a step from t which left the domain or failed to evaluate. Errors once it
+// is below the minimum step
+pub fn shrink_step(t: f64, h: f64, min_step: f64) -> Result<f64, IntegError> {
+    if (0.5 * h).abs() < min_step {
+        return Err(IntegError::Domain { t, min_step });
+    }
+    Ok(0.5 * h)
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runge_kutta::adaptive::AdaptiveStep;
+    use crate::runge_kutta::common::IntegOptions;
+    use crate::runge_kutta::fixed::FixedStep;
+    use crate::runge_kutta::rk_embed::RKF45;
+    use crate::runge_kutta::rk_simp::RK4;
+    use crate::runge_kutta::stopping::StopCondition;
+    use na::Vector1;
+
+    #[test]
+    fn test_domain_guard() {
+        // y' = -sqrt(y) reaches 0 at t = 2 from y = 1, and is undefined below
+        let fxn = |_t: f64, y: &Vector1<f64>| {
+            assert!(y[0] >= 0.0, "evaluated outside of the domain");
+            Vector1::new(-y[0].sqrt())
+        };
+        let positive = |_t: f64, y: &Vector1<f64>| y[0];
+        let opts = IntegOptions {
+            domain: Some(vec![positive]),
+            ..IntegOptions::default()
+        };
+        // steps shrink near the boundary, which is only approached
+        let ans = RK4
+            .integrate(fxn, 0.0, Vector1::new(1.0), 1.9, 0.25, opts.clone())
+            .unwrap();
+        assert!((ans.last_y()[0] - 0.0025).abs() < 2e-4);
+        assert!(ans.times.windows(2).any(|w| w[1] - w[0] < 0.15));
+
+        // past the boundary the steps run into the minimum step, where the solution
+        // reaches it
+        let err = RK4
+            .integrate(fxn, 0.0, Vector1::new(1.0), 3.0, 0.25, opts.clone())
+            .unwrap_err();
+        match err {
+            IntegError::Domain { t, .. } => assert!((t - 2.0).abs() < 0.01),
+            err => panic!("{}", err),
+        }
+        assert!(err.to_string().starts_with("[DOMAIN] Steps from t = 2.00"));
+
+        // unless the boundary ends the integration
+        let opts = IntegOptions {
+            stop_conditions: Some(vec![
+                StopCondition::Event(|t, _y| t - 2.5),
+                StopCondition::DomainExit,
+            ]),
+            atol: Some(Vector1::new(1e-10)),
+            ..opts
+        };
+        let ans = RKF45
+            .integrate(fxn, 0.0, Vector1::new(1.0), 3.0, opts)
+            .unwrap();
+        let stop = ans.stopped.unwrap();
+        assert_eq!(stop.condition, 1);
+        assert!((stop.t - 2.0).abs() < 1e-3);
+        assert!(ans.last_y()[0] >= 0.0 && ans.last_y()[0] < 1e-6);
+    }
+}
@@ -0,0 +1,199 @@
+/// Step Doubling Error Estimation (doubling)
+///
+/// Adaptive stepping for steppers without an embedded error estimate. Each step is
+/// taken once with h and again as two steps of h / 2; for a method of order p the
+/// difference of the two results is (2^p - 1) times the local error of the pair of
+/// half steps, up to higher order terms (Richardson extrapolation, see Hairer,
+/// Norsett & Wanner "Solving Ordinary Differential Equations I", section II.4).
+///
+/// `StepDoubling` wraps any `Predictor` (so every simple Runge Kutta stepper and
+/// any custom predictor) into an `AdaptiveStep`, usable with the adaptive
+/// integrators and the adaptive RIDC pipeline like an embedded pair. The accepted
+/// value is the result of the two half steps (without extrapolation, so the
+/// predictor's own properties are kept). A step costs three steps of the wrapped
+/// predictor. `RKStepper::step_doubling` builds one with the order read off the
+/// tableau.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DefaultAllocator, Dim, DimName, OVector};
+
+// local imports
+use super::adaptive::AdaptiveStep;
+use super::base::RKStepper;
+use super::common::{simpson, Diagnostic, RkOrder, StepResult, StepWithError};
+use crate::ridc::predictor::Predictor;
+use crate::systems::OdeSystem;
+use crate::utils::norms::weighted_rms_norm;
+
+// === End Imports ===
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepDoubling<P> {
+    // Stepper without an error estimate of its own
+    predictor: P,
+    // Order of accuracy p of the predictor
+    order: usize,
+}
+
+impl<P: Predictor> StepDoubling<P> {
+    pub fn new(predictor: P, order: usize) -> Result<Self, &'static str> {
+        if order == 0 {
+            return Err("[STEP DOUBLING] Predictor order must be at least one");
+        }
+        Ok(StepDoubling { predictor, order })
+    }
+
+    // Wrapped stepper
+    pub fn predictor(&self) -> &P {
+        &self.predictor
+    }
+}
+
+impl<D: DimName + Dim> RKStepper<D>
+where
+    DefaultAllocator: Allocator<D> + Allocator<D, D>,
+{
+    // Adaptive version of the stepper, with the error estimated by step doubling
+    pub fn step_doubling(&self) -> StepDoubling<RKStepper<D>> {
+        StepDoubling {
+            predictor: self.clone(),
+            order: self.tableau().order().max(1),
+        }
+    }
+}
+
+impl<P: Predictor> StepWithError for StepDoubling<P> {
+    fn step<N: DimName + Dim, S: OdeSystem<N> + ?Sized>(
+        &self,
+        fxn: &S,
+        t_0: f64,
+        y_0: &OVector<f64, N>,
+        step: f64,
+        atol: &OVector<f64, N>,
+        rtol: f64,
+    ) -> StepResult<N>
+    where
+        DefaultAllocator: Allocator<N>,
+    {
+        self.step_integrating(fxn, t_0, y_0, step, atol, rtol, &[])
+            .0
+    }
+
+    // Simpson's rule on the midpoint the half steps pass through
+    fn step_integrating<N: DimName + Dim, S: OdeSystem<N> + ?Sized>(
+        &self,
+        fxn: &S,
+        t_0: f64,
+        y_0: &OVector<f64, N>,
+        step: f64,
+        atol: &OVector<f64, N>,
+        rtol: f64,
+        integrands: &[Diagnostic<N>],
+    ) -> (StepResult<N>, Vec<f64>)
+    where
+        DefaultAllocator: Allocator<N>,
+    {
+        let full = self.predictor.predict(fxn, t_0, y_0, step);
+        let half = 0.5 * step;
+        let first = self.predictor.predict(fxn, t_0, y_0, half);
+        let second = self.predictor.predict(fxn, t_0 + half, &first.value, half);
+
+        let scale = 2.0_f64.powi(self.order as i32) - 1.0;
+        let local_err = (&second.value - &full.value) / scale;
+        let integrals = simpson(integrands, t_0, step, y_0, &first.value, &second.value);
+        let res = StepResult {
+            error: weighted_rms_norm(&local_err, y_0, &second.value, atol, rtol),
+            value: second.value,
+            dyn_eval: second.dyn_eval,
+        };
+        (res, integrals)
+    }
+}
+
+// revise_step and initial_step take the exponent of the step in the error estimate
+// as order() - 1, and the doubling estimate is of the local error, O(h^(p + 1))
+impl<P: Predictor> RkOrder for StepDoubling<P> {
+    fn order(&self) -> usize {
+        self.order + 2
+    }
+}
+
+impl<P: Predictor> AdaptiveStep for StepDoubling<P> {}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ridc::base::RIDCIntegratorAdaptive;
+    use crate::ridc::common::IntegOptionsParallel;
+    use crate::runge_kutta::common::IntegError;
+    use crate::runge_kutta::common::IntegOptions;
+    use crate::runge_kutta::rk_simp::{HEUN, RK4};
+    use crate::systems::fallible::{EvalFailure, Fallible};
+    use na::Vector2;
+
+    #[test]
+    fn test_step_doubling() {
+        let rk4 = RK4.step_doubling();
+        assert_eq!(rk4.order(), 6);
+        assert!(StepDoubling::new(RK4.clone(), 0).is_err());
+
+        // harmonic oscillator, y = (cos t, -sin t)
+        let sho = |_t: f64, y: &Vector2<f64>| Vector2::new(y[1], -y[0]);
+        let truth = |t: f64| Vector2::new(t.cos(), -t.sin());
+        let y_0 = Vector2::new(1.0, 0.0);
+
+        // the estimate tracks the true local error of the two half steps
+        let (atol, rtol) = (Vector2::repeat(1.0), 0.0);
+        let res = rk4.step(&sho, 0.0, &y_0, 0.2, &atol, rtol);
+        let true_err = (res.value - truth(0.2)).norm() / 2.0_f64.sqrt();
+        assert!((res.error / true_err - 1.0).abs() < 0.2);
+
+        // tighter tolerances buy proportionally smaller errors
+        let run = |tol: f64| {
+            let options = IntegOptions {
+                atol: Some(Vector2::repeat(tol)),
+                rtol: Some(tol),
+                ..IntegOptions::default()
+            };
+            let ans = rk4.integrate(sho, 0.0, y_0, 10.0, options).unwrap();
+            (truth(10.0) - ans.last_y()).amax()
+        };
+        let (loose, tight) = (run(1e-6), run(1e-10));
+        println!("LOOSE {:e} | TIGHT {:e}", loose, tight);
+        assert!(loose < 1e-4);
+        assert!(tight < 5e-8);
+        assert!(loose > 500.0 * tight);
+
+        // a second order predictor drives the adaptive RIDC pipeline
+        let options = IntegOptionsParallel {
+            corrector_order: Some(2),
+            ..IntegOptionsParallel::default()
+        };
+        let ans = HEUN
+            .step_doubling()
+            .parallel_integrator(sho, 0.0, &y_0, 10.0, options)
+            .unwrap();
+        let diff = (truth(10.0) - ans.last_y()).amax();
+        println!("RIDC HEUN {:e}", diff);
+        assert!(diff < 1e-4);
+
+        // failures of the dynamics end the adaptive pipeline too
+        let table = |t: f64, y: &Vector2<f64>| match t > 5.0 {
+            true => Err(EvalFailure::Fatal("[TABLE] Time out of range")),
+            false => Ok(Vector2::new(y[1], -y[0])),
+        };
+        let options = IntegOptionsParallel {
+            corrector_order: Some(2),
+            ..IntegOptionsParallel::default()
+        };
+        let err = HEUN
+            .step_doubling()
+            .parallel_integrator(Fallible::new(table), 0.0, &y_0, 10.0, options)
+            .unwrap_err();
+        assert_eq!(err, IntegError::Failed("[TABLE] Time out of range"));
+    }
+}
@@ -19,12 +19,15 @@
 // third party imports
 extern crate nalgebra as na;
 use na::allocator::Allocator;
-use na::{DefaultAllocator, Dim, DimName, VectorN};
+use na::{DefaultAllocator, Dim, DimName, OVector};
 
 // local imports
 use super::adaptive::AdaptiveStep;
-use super::common::{RkOrder, StepResult, StepWithError};
+use super::common::{Diagnostic, RkOrder, StepResult, StepWithError};
 use super::tableaus::{EmbeddedTableau, RkType};
+use super::zero_alloc::{stages, MAX_STAGES};
+use crate::systems::OdeSystem;
+use crate::utils::norms::weighted_rms_norm;
 
 // === End Imports ===
 
@@ -32,7 +35,7 @@ use super::tableaus::{EmbeddedTableau, RkType};
 #[derive(Debug, Clone, PartialEq)]
 pub struct EmbeddedRKStepper<D: DimName + Dim>
 where
-    DefaultAllocator: Allocator<f64, D> + Allocator<f64, D, D>,
+    DefaultAllocator: Allocator<D> + Allocator<D, D>,
 {
     // Name of integrator (usually based on butcher table choice)
     name: &'static str,
@@ -46,9 +49,12 @@ where
 
 impl<D: DimName + Dim> EmbeddedRKStepper<D>
 where
-    DefaultAllocator: Allocator<f64, D> + Allocator<f64, D, D>,
+    DefaultAllocator: Allocator<D> + Allocator<D, D>,
 {
     pub fn new(s: &'static str, t: EmbeddedTableau<D>) -> Result<Self, ()> {
+        if t.c_vals.len() > MAX_STAGES {
+            return Err(());
+        }
         let mut ut = t.a_vals.upper_triangle();
         ut.fill_diagonal(0.0);
         match (t.a_vals.trace() == 0.0, ut.iter().sum::<f64>() == 0.0) {
@@ -62,68 +68,91 @@ where
             _ => unimplemented!("No Implicit Embedded integration provided yet"),
         }
     }
+
+    // Butcher tableau of the method (e.g. for its order or stability function)
+    pub fn tableau(&self) -> &EmbeddedTableau<D> {
+        &self.tableau
+    }
 }
 
 impl<D: DimName + Dim> StepWithError for EmbeddedRKStepper<D>
 where
-    DefaultAllocator: Allocator<f64, D> + Allocator<f64, D, D>,
+    DefaultAllocator: Allocator<D> + Allocator<D, D>,
 {
     // defaults are atol = 1e-3, rtol = 1e-6 (copied from scipy defaults)
-    fn step<N: DimName + Dim>(
+    fn step<N: DimName + Dim, S: OdeSystem<N> + ?Sized>(
         &self,
-        fxn: fn(f64, &VectorN<f64, N>) -> VectorN<f64, N>,
+        fxn: &S,
         t_0: f64,
-        y_0: &VectorN<f64, N>,
+        y_0: &OVector<f64, N>,
         step: f64,
-        atol: &VectorN<f64, N>,
+        atol: &OVector<f64, N>,
         rtol: f64,
     ) -> StepResult<N>
     where
-        DefaultAllocator: Allocator<f64, N>,
+        DefaultAllocator: Allocator<N>,
+    {
+        self.step_integrating(fxn, t_0, y_0, step, atol, rtol, &[])
+            .0
+    }
+
+    // The integrands are weighted at the stages like the derivatives of the
+    // propagated (higher order) solution
+    fn step_integrating<N: DimName + Dim, S: OdeSystem<N> + ?Sized>(
+        &self,
+        fxn: &S,
+        t_0: f64,
+        y_0: &OVector<f64, N>,
+        step: f64,
+        atol: &OVector<f64, N>,
+        rtol: f64,
+        integrands: &[Diagnostic<N>],
+    ) -> (StepResult<N>, Vec<f64>)
+    where
+        DefaultAllocator: Allocator<N>,
     {
         match self.rktype {
             RkType::Explicit => {
-                let mut ks = Vec::new();
+                // on the stack, so steps of fixed size states do not allocate
+                let mut ks = stages::<N>();
+                let mut integrals = vec![0.0; integrands.len()];
                 for i in 0..self.stages {
-                    let ka_sum: VectorN<f64, N> = ks
+                    let ka_sum: OVector<f64, N> = ks[..i]
                         .iter()
                         .enumerate()
                         .map(|(j, k)| self.tableau.a_vals[(i, j)] * k)
-                        .fold(VectorN::<f64, N>::zeros(), |sum, val| sum + val);
-                    ks.push(fxn(
-                        t_0 + step * self.tableau.c_vals[i],
-                        &(y_0 + step * ka_sum),
-                    ));
+                        .fold(OVector::<f64, N>::zeros(), |sum, val| sum + val);
+                    let (t_i, y_i) = (t_0 + step * self.tableau.c_vals[i], y_0 + step * ka_sum);
+                    for (integral, g) in integrals.iter_mut().zip(integrands.iter()) {
+                        *integral += step * self.tableau.b_hat_vals[i] * g(t_i, &y_i);
+                    }
+                    ks[i] = fxn.dynamics(t_i, &y_i);
                 }
-                let sum_bi_ki: VectorN<f64, N> = self
+                let sum_bi_ki: OVector<f64, N> = self
                     .tableau
                     .b_vals
                     .iter()
                     .enumerate()
                     .map(|(i, b)| *b * &ks[i])
-                    .fold(VectorN::<f64, N>::zeros(), |sum, val| sum + val);
-                let sum_b_hat_i_ki: VectorN<f64, N> = self
+                    .fold(OVector::<f64, N>::zeros(), |sum, val| sum + val);
+                let sum_b_hat_i_ki: OVector<f64, N> = self
                     .tableau
                     .b_hat_vals
                     .iter()
                     .enumerate()
                     .map(|(i, b)| *b * &ks[i])
-                    .fold(VectorN::<f64, N>::zeros(), |sum, val| sum + val);
+                    .fold(OVector::<f64, N>::zeros(), |sum, val| sum + val);
 
                 let y_n = y_0 + step * sum_bi_ki;
                 let y_hat_n = y_0 + step * sum_b_hat_i_ki;
-                // Pulled from pg 913 of Numerical Recipes (eq 17.2.7-9)
-                let error = VectorN::<f64, N>::from_iterator(
-                    (&y_hat_n - &y_n).iter().enumerate().map(|(idx, delta)| {
-                        delta / (atol[idx] + rtol * y_n[idx].abs().max(y_hat_n[idx].abs()))
-                    }),
-                )
-                .norm();
-                StepResult {
-                    dyn_eval: fxn(t_0 + step, &y_hat_n),
+                // Hairer weighted rms norm (see utils::norms)
+                let error = weighted_rms_norm(&(&y_hat_n - &y_n), y_0, &y_hat_n, atol, rtol);
+                let res = StepResult {
+                    dyn_eval: fxn.dynamics(t_0 + step, &y_hat_n),
                     value: y_hat_n,
                     error,
-                }
+                };
+                (res, integrals)
             }
             _ => unimplemented!("Only Explicit Embedded methods currently supported"),
         }
@@ -131,7 +160,7 @@ where
 }
 impl<D: DimName + Dim> RkOrder for EmbeddedRKStepper<D>
 where
-    DefaultAllocator: Allocator<f64, D> + Allocator<f64, D, D>,
+    DefaultAllocator: Allocator<D> + Allocator<D, D>,
 {
     fn order(&self) -> usize {
         self.stages
@@ -139,7 +168,7 @@ where
 }
 
 impl<D: DimName + Dim> AdaptiveStep for EmbeddedRKStepper<D> where
-    DefaultAllocator: Allocator<f64, D> + Allocator<f64, D, D>
+    DefaultAllocator: Allocator<D> + Allocator<D, D>
 {
 }
 
@@ -235,7 +264,7 @@ mod tests {
         let act_val: f64 = 0.022674519230769238;
         let atol = Vector1::new(1e-6_f64);
         let rtol = 1e-3_f64;
-        let step_val = integ.step(test_dyn, t_0, &y_0, h, &atol, rtol);
+        let step_val = integ.step(&test_dyn, t_0, &y_0, h, &atol, rtol);
         const TOL_VAL: f64 = 1e-8;
         assert!((act_val - step_val.value[0]).abs() < TOL_VAL);
     }
@@ -285,13 +314,13 @@ mod tests {
         // test against value computed from Scipy's RK23 method
         let python_step = 0.0176027714722467136521100;
         let python_val = 0.00076101;
-        let step_val_2 = integ.step(test_dyn, t_0, &y_0, python_step, &atol, rtol);
+        let step_val_2 = integ.step(&test_dyn, t_0, &y_0, python_step, &atol, rtol);
         assert!((python_val - step_val_2.value[0]).abs() < TOL_VAL);
 
         // another test against python for a slightly more complex function
         let python_step = 9.999999999999999e-05_f64;
         let python_val = 0.0000000249975000000156198;
-        let step_val_3 = integ.step(test_dyn_2, t_0, &y_0, python_step, &atol, rtol);
+        let step_val_3 = integ.step(&test_dyn_2, t_0, &y_0, python_step, &atol, rtol);
         assert!((python_val - step_val_3.value[0]).abs() < TOL_VAL);
     }
 }
@@ -0,0 +1,180 @@
+/// Running Extrema (runge_kutta/extrema)
+///
+/// Tracks the minimum and maximum of selected components of the state over an
+/// integration (`IntegOptions::extrema`), e.g. the lowest perigee altitude or the
+/// peak temperature over a long run, reported in `IntegResult::extrema` with the
+/// times they occur. An extremum rarely falls on a step: within each accepted step
+/// the component follows the cubic hermite through the states and dynamics at both
+/// ends (the dense output of `defect`), and its extrema inside the step are the
+/// roots of the derivative of the cubic, so they are found to the accuracy of the
+/// interpolant rather than of the step size. The extrema only need the current
+/// step, not the stored trajectory.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DefaultAllocator, Dim, DimName, OVector};
+
+// === End Imports ===
+
+// Minimum and maximum of a component of the state over an integration
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Extremum {
+    // Index of the component in the state
+    pub component: usize,
+    // Smallest value
+    pub min: f64,
+    // Time of the smallest value
+    pub t_min: f64,
+    // Largest value
+    pub max: f64,
+    // Time of the largest value
+    pub t_max: f64,
+}
+
+impl Extremum {
+    // Extremum of a single value
+    pub fn new(component: usize, t: f64, value: f64) -> Self {
+        Extremum {
+            component,
+            min: value,
+            t_min: t,
+            max: value,
+            t_max: t,
+        }
+    }
+
+    // Extrema of the components at the start of an integration
+    pub(crate) fn start<N: Dim + DimName>(
+        components: &[usize],
+        t_0: f64,
+        y_0: &OVector<f64, N>,
+    ) -> Result<Vec<Self>, &'static str>
+    where
+        DefaultAllocator: Allocator<N>,
+    {
+        components
+            .iter()
+            .map(|c| match *c < y_0.len() {
+                true => Ok(Extremum::new(*c, t_0, y_0[*c])),
+                false => Err("[EXTREMA] Component outside of the state"),
+            })
+            .collect()
+    }
+
+    // Takes in the value v at time t
+    pub fn update(&mut self, t: f64, v: f64) {
+        if v < self.min {
+            self.min = v;
+            self.t_min = t;
+        }
+        if v > self.max {
+            self.max = v;
+            self.t_max = t;
+        }
+    }
+
+    // Takes in the extrema of the same component over another interval
+    pub fn merge(&mut self, other: &Extremum) {
+        self.update(other.t_min, other.min);
+        self.update(other.t_max, other.max);
+    }
+
+    // Takes in a step of length h from (y_0, f_0) at t_0 to (y_1, f_1): the end of
+    // the step and the extrema of the cubic hermite of the component inside it
+    pub(crate) fn update_step<N: Dim + DimName>(
+        &mut self,
+        t_0: f64,
+        h: f64,
+        (y_0, f_0): (&OVector<f64, N>, &OVector<f64, N>),
+        (y_1, f_1): (&OVector<f64, N>, &OVector<f64, N>),
+    ) where
+        DefaultAllocator: Allocator<N>,
+    {
+        let c = self.component;
+        // p(theta) = a theta^3 + b theta^2 + c theta + y_0 on theta in [0, 1]
+        let (p_0, p_1) = (y_0[c], y_1[c]);
+        let (d_0, d_1) = (h * f_0[c], h * f_1[c]);
+        let cubic = 2.0 * (p_0 - p_1) + d_0 + d_1;
+        let quad = 3.0 * (p_1 - p_0) - 2.0 * d_0 - d_1;
+        let p = |s: f64| ((cubic * s + quad) * s + d_0) * s + p_0;
+
+        // roots of p'(theta) = 3 a theta^2 + 2 b theta + c
+        let (qa, qb, qc) = (3.0 * cubic, 2.0 * quad, d_0);
+        let roots = if qa.abs() <= 1e-12 * (qb.abs() + qc.abs()) {
+            vec![-qc / qb]
+        } else {
+            let disc = qb * qb - 4.0 * qa * qc;
+            if disc < 0.0 {
+                Vec::new()
+            } else {
+                // stable form of the quadratic formula
+                let q = -0.5 * (qb + disc.sqrt().copysign(qb));
+                vec![q / qa, qc / q]
+            }
+        };
+        for s in roots.into_iter().filter(|s| *s > 0.0 && *s < 1.0) {
+            self.update(t_0 + s * h, p(s));
+        }
+        self.update(t_0 + h, p_1);
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runge_kutta::adaptive::AdaptiveStep;
+    use crate::runge_kutta::common::IntegOptions;
+    use crate::runge_kutta::fixed::FixedStep;
+    use crate::runge_kutta::rk_embed::DOPRI78;
+    use crate::runge_kutta::rk_simp::RK4;
+    use na::Vector4;
+    use std::f64::consts::PI;
+
+    fn kepler(_t: f64, y: &Vector4<f64>) -> Vector4<f64> {
+        let r3 = (y[0] * y[0] + y[1] * y[1]).powf(1.5);
+        Vector4::new(y[2], y[3], -y[0] / r3, -y[1] / r3)
+    }
+
+    #[test]
+    fn test_extrema() {
+        // orbit with a = 1 and e = 0.5 from periapsis at x = 0.5: x reaches -1.5 at
+        // apoapsis (t = pi), and y is largest a quarter of the orbit in, where
+        // the eccentric anomaly is pi / 2 (y = b, t = (pi / 2 - e)), smallest at
+        // 2 pi - that
+        let y_0 = Vector4::new(0.5, 0.0, 0.0, 3.0_f64.sqrt());
+        let opts = IntegOptions {
+            extrema: Some(vec![0, 1]),
+            ..IntegOptions::default()
+        };
+        let b = 0.75_f64.sqrt();
+        let t_top = 0.5 * PI - 0.5;
+        let check = |extrema: &[Extremum], tol: f64| {
+            assert_eq!(extrema.len(), 2);
+            let (x, y) = (extrema[0], extrema[1]);
+            assert!((x.min + 1.5).abs() < tol && (x.t_min - PI).abs() < tol.sqrt());
+            assert!(x.max == 0.5 && x.t_max == 0.0);
+            assert!((y.max - b).abs() < tol && (y.t_max - t_top).abs() < tol.sqrt());
+            assert!((y.min + b).abs() < tol && (y.t_min - (2.0 * PI - t_top)).abs() < tol.sqrt());
+        };
+        // long steps which straddle the extrema: the error is that of the cubic
+        // over a step
+        let ans = DOPRI78
+            .integrate(kepler, 0.0, y_0, 2.0 * PI - 0.1, opts.clone())
+            .unwrap();
+        check(&ans.extrema, 2e-3);
+        let ans = RK4
+            .integrate(kepler, 0.0, y_0, 2.0 * PI - 0.1, 0.1, opts)
+            .unwrap();
+        check(&ans.extrema, 1e-3);
+        assert!(ans.times.iter().all(|t| (t - PI).abs() > 0.04));
+
+        let bad = IntegOptions {
+            extrema: Some(vec![4]),
+            ..IntegOptions::default()
+        };
+        assert!(RK4.integrate(kepler, 0.0, y_0, 1.0, 0.1, bad).is_err());
+    }
+}
@@ -3,52 +3,143 @@
 // === Begin Imports ===
 // third party imports
 extern crate nalgebra as na;
-use super::common::{IntegOptions, IntegResult, StepSimple};
+use super::common::{approach_end, Breakpoints, IntegError, IntegOptions, IntegResult, StepSimple};
+use super::domain::{shrink_step, DomainGuard};
+use super::extrema::Extremum;
+use super::stopping::StopMonitor;
+use crate::systems::OdeSystem;
 use na::allocator::Allocator;
-use na::{DefaultAllocator, Dim, DimName, VectorN};
+use na::{DefaultAllocator, Dim, DimName, OVector};
 
 // === End Imports ===
 
 pub trait FixedStep: StepSimple {
     // Note: Step should be strictly positive. If it is negative
     // it will be changed to a positive value.
-    fn integrate<N: DimName + Dim>(
+    fn integrate<N: DimName + Dim, S: OdeSystem<N>>(
         &self,
-        fxn: fn(f64, &VectorN<f64, N>) -> VectorN<f64, N>,
+        fxn: S,
         t_0: f64,
-        y_0: VectorN<f64, N>,
+        y_0: OVector<f64, N>,
         dt: f64,
         step: f64,
         integ_opts: IntegOptions<N>,
-    ) -> Result<IntegResult<N>, &'static str>
+    ) -> Result<IntegResult<N>, IntegError>
     where
-        DefaultAllocator: Allocator<f64, N>,
+        DefaultAllocator: Allocator<N>,
     {
         // extract options
         let min_step_size = integ_opts.min_step.unwrap_or(1e-10_f64);
+        let diagnostics = integ_opts.diagnostics.unwrap_or_default();
+        let integrands = integ_opts.integrands.unwrap_or_default();
+        let mut breakpoint_times = integ_opts.breakpoints.unwrap_or_default();
+        breakpoint_times.extend(fxn.breakpoints());
         if step.abs() < min_step_size {
-            return Err("Requested Step size is smaller than minimum step size");
+            return Err("Requested Step size is smaller than minimum step size".into());
         }
 
+        let mut extrema = Extremum::start(&integ_opts.extrema.unwrap_or_default(), t_0, &y_0)?;
+        // dynamics at the start of the step, for the interpolant when tracking extrema
+        let mut f_last = match extrema.is_empty() {
+            true => None,
+            false => Some(fxn.dynamics(t_0, &y_0)),
+        };
+
         // initialize results
         let mut results = IntegResult::new(t_0, y_0);
+        results.update_diagnostics(&diagnostics);
+        results.add_integrals(&vec![0.0; integrands.len()]);
         let t_end = t_0 + dt;
         let backward: bool = dt < 0.0;
         let mut step = step.abs();
         if backward {
             step = -step;
         }
+        let mut breakpoints = Breakpoints::new(&breakpoint_times, t_0, t_end);
+        let stop_conditions = integ_opts.stop_conditions.unwrap_or_default();
+        let events = integ_opts.events.unwrap_or_default();
+        let mut monitor = StopMonitor::new(&stop_conditions, t_0, results.last_y())
+            .with_chattering_guard(integ_opts.event_dead_band, integ_opts.event_rearm)
+            .with_events(&events, t_0, results.last_y());
+        let constraints = integ_opts.domain.unwrap_or_default();
+        let guarded = DomainGuard::new(&fxn, &constraints);
+        // shortened step after leaving the domain, grown back over the next steps
+        let mut shrunk: Option<f64> = None;
 
         while results.t != t_end {
             // Ensures integrator does not over-step the goal
-            if (backward && step.abs() > (t_end - results.t).abs())
-                || (!backward && step > (t_end - results.t))
-            {
-                step = t_end - results.t;
+            let (h_end, last) = approach_end(results.t, t_end, shrunk.unwrap_or(step));
+            let (h, landing) = breakpoints.limit(results.t, h_end);
+            let (t, y) = (results.t, results.last_y());
+            let (res, mut step_integrals) = self.step_integrating(&guarded, t, y, h, &integrands);
+            if guarded.rejected()? {
+                match shrink_step(results.t, h, min_step_size) {
+                    Ok(h) => shrunk = Some(h),
+                    Err(err) => {
+                        results.stopped = Some(monitor.exit_domain(results.t, err)?);
+                        break;
+                    }
+                }
+                continue;
+            }
+            if !monitor.is_empty() {
+                let (t, y) = (results.t, results.last_y());
+                let stop = monitor.check(t, h, &res.value, |s| self.step(&guarded, t, y, s).value);
+                if let Some((stop, s, y_s)) = stop {
+                    if !integrands.is_empty() {
+                        step_integrals = self.step_integrating(&guarded, t, y, s, &integrands).1;
+                    }
+                    if let Some(f_0) = &f_last {
+                        let f_s = fxn.dynamics(stop.t, &y_s);
+                        for ext in extrema.iter_mut() {
+                            ext.update_step(t, s, (y, f_0), (&y_s, &f_s));
+                        }
+                    }
+                    results.events.extend(monitor.take_hits());
+                    results.add_val(s, y_s);
+                    results.add_integrals(&step_integrals);
+                    results.land_on(stop.t);
+                    results.stopped = Some(stop);
+                    results.update_diagnostics(&diagnostics);
+                    break;
+                }
+            }
+            if !guarded.contains(results.t + h, &res.value) {
+                match shrink_step(results.t, h, min_step_size) {
+                    Ok(h) => shrunk = Some(h),
+                    Err(err) => {
+                        results.stopped = Some(monitor.exit_domain(results.t, err)?);
+                        break;
+                    }
+                }
+                continue;
+            }
+            shrunk = shrunk.map(|h| 2.0 * h).filter(|h| h.abs() < step.abs());
+            if let Some(f_0) = &f_last {
+                let (y_0, y_1) = (results.last_y(), &res.value);
+                for ext in extrema.iter_mut() {
+                    ext.update_step(results.t, h, (y_0, f_0), (y_1, &res.dyn_eval));
+                }
+            }
+            results.events.extend(monitor.take_hits());
+            results.add_val(h, res.value);
+            results.add_integrals(&step_integrals);
+            if let Some(t_bp) = landing {
+                results.land_on(t_bp);
+                breakpoints.passed();
+            } else if last {
+                results.land_on(t_end);
+            }
+            if f_last.is_some() {
+                // the dynamics may jump at a breakpoint
+                f_last = Some(match landing {
+                    Some(_) => fxn.dynamics(results.t, results.last_y()),
+                    None => res.dyn_eval,
+                });
             }
-            let res = self.step(fxn, results.t, results.last_y(), step);
-            results.add_val(step, res.value);
+            results.update_diagnostics(&diagnostics);
         }
+        results.extrema = extrema;
         Ok(results)
     }
 }
@@ -75,4 +166,113 @@ mod tests {
         );
         println!("{:?}", ans);
     }
+
+    #[test]
+    fn test_fixed_integ_final_time() {
+        use crate::runge_kutta::rk_simp::RK4;
+
+        // steps that do not divide the span: the last two steps share the rest
+        let ans = RK4
+            .integrate(
+                test_dyn,
+                0.0,
+                Vector1::new(0.0),
+                1.0,
+                0.3,
+                IntegOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(ans.times.len(), 5);
+        assert!((ans.times[3] - 0.8).abs() < 1e-15);
+        assert_eq!(ans.t, 1.0);
+        assert_eq!(*ans.times.last().unwrap(), 1.0);
+
+        // steps that divide the span up to round-off end on t_end without a sliver
+        for (t_0, span) in [(0.0, 1.0), (0.3, 7.0), (1.7, -1.1)].iter() {
+            let ans = RK4
+                .integrate(
+                    test_dyn,
+                    *t_0,
+                    Vector1::new(0.0),
+                    *span,
+                    0.1,
+                    IntegOptions::default(),
+                )
+                .unwrap();
+            assert_eq!(ans.times.len(), (span.abs() / 0.1).round() as usize + 1);
+            assert_eq!(*ans.times.last().unwrap(), t_0 + span);
+        }
+    }
+
+    #[test]
+    fn test_fixed_integ_integrals() {
+        use crate::runge_kutta::adaptive::AdaptiveStep;
+        use crate::runge_kutta::rk_embed::DOPRI78;
+        use crate::runge_kutta::rk_simp::RK4;
+        use crate::runge_kutta::stopping::StopCondition;
+
+        // y = e^t with the integrand y^2: the integral to t is (e^2t - 1) / 2
+        let grow = |_t: f64, y: &Vector1<f64>| *y;
+        let truth = |t: f64| 0.5 * ((2.0 * t).exp() - 1.0);
+        let options = || IntegOptions {
+            integrands: Some(vec![|_t, y| y[0] * y[0]]),
+            ..IntegOptions::default()
+        };
+        // taken at the stages, the integral converges at the order of the method
+        let error = |dt: f64| {
+            let ans = RK4
+                .integrate(grow, 0.0, Vector1::new(1.0), 1.0, dt, options())
+                .unwrap();
+            assert_eq!(ans.integrals.len(), ans.states.len());
+            (ans.integrals.last().unwrap()[0] - truth(1.0)).abs()
+        };
+        let ratio = error(0.1) / error(0.05);
+        assert!(ratio > 14.0 && ratio < 18.0);
+
+        // adaptive steps cut short by a stop, and step doubling (Simpson's rule)
+        let stopped = IntegOptions {
+            stop_conditions: Some(vec![StopCondition::Event(|t, _y| t - 0.5)]),
+            atol: Some(Vector1::new(1e-12)),
+            rtol: Some(1e-12),
+            ..options()
+        };
+        let ans = DOPRI78
+            .integrate(grow, 0.0, Vector1::new(1.0), 1.0, stopped.clone())
+            .unwrap();
+        assert!((ans.integrals.last().unwrap()[0] - truth(0.5)).abs() < 1e-10);
+        let ans = RK4
+            .step_doubling()
+            .integrate(grow, 0.0, Vector1::new(1.0), 1.0, stopped)
+            .unwrap();
+        assert!((ans.integrals.last().unwrap()[0] - truth(0.5)).abs() < 1e-8);
+    }
+
+    #[test]
+    fn test_fixed_integ_breakpoints() {
+        use crate::runge_kutta::rk_simp::RK4;
+
+        // forcing switched on part way through a step
+        let fxn =
+            |t: f64, y: &Vector1<f64>| Vector1::new(-y[0] + if t >= 1.05 { 1.0 } else { 0.0 });
+        let truth = |t: f64| (-t).exp() + 1.0 - (1.05 - t).exp();
+        let run = |breakpoints: Option<Vec<f64>>| {
+            let options = IntegOptions {
+                breakpoints,
+                ..IntegOptions::default()
+            };
+            RK4.integrate(fxn, 0.0, Vector1::new(1.0), 3.0, 0.1, options)
+                .unwrap()
+        };
+
+        let smeared = run(None);
+        // breakpoints outside of the interval are ignored
+        let aligned = run(Some(vec![5.0, 1.05, -1.0]));
+        assert!(aligned.times.contains(&1.05));
+        assert_eq!(aligned.times.len(), smeared.times.len() + 1);
+
+        let err_smeared = (smeared.last_y()[0] - truth(3.0)).abs();
+        let err_aligned = (aligned.last_y()[0] - truth(3.0)).abs();
+        assert!(err_smeared > 1e-3);
+        assert!(err_aligned < 1e-6);
+    }
 }
@@ -0,0 +1,387 @@
+/// Hybrid Systems (runge_kutta/hybrid)
+///
+/// Switched and impacting systems (a bouncing ball, a thermostat, a gear shifting
+/// vehicle, a spacecraft switching control laws) are a set of modes, each with its
+/// own smooth dynamics, and transitions between them. A transition has a guard
+/// g(t, y), which triggers it when it turns negative, a target mode, and a reset
+/// map giving the state the target mode starts from (the bounce reversing the
+/// velocity, or the identity when only the dynamics change).
+///
+/// `HybridSystem::integrate` runs the dynamics of the current mode with the guards
+/// of its transitions registered as stopping conditions (`StopCondition::Guard`),
+/// so every switch is located within the step where it happens and no step ever
+/// straddles a discontinuity. At a switch the reset is applied and the integration
+/// restarts in the target mode from the located time, warm started with the step
+/// the controller last proposed. The solutions of all modes are gathered in one
+/// result, the time of each switch appearing twice: with the state before and after
+/// the reset. The switches are logged in order, giving the mode sequence. The state
+/// transition matrix across the switches follows from the saltation matrices of
+/// `analysis::stm`. Modes whose states differ in dimension (dropping the states of
+/// a spent stage, adding those of a deployed object) are handled by `staged`.
+///
+/// A guard only fires on going from non-negative to negative, so a mode entered
+/// with one of its guards already negative does not switch on it until it has
+/// become positive again. Like any event, a guard turning positive and negative
+/// again within one step goes unnoticed (`max_step` bounds the steps). Stopping
+/// conditions of the options are checked in every mode and restarted with it (the
+/// wall clock and the reference of drift conditions). An integration switching
+/// more than `max_switches` times (e.g. a ball bouncing infinitely often before
+/// coming to rest, Zeno behaviour) errors. Chattering across a switching surface
+/// (sliding mode) is held back by the dead band and re-arm time of the events
+/// (`IntegOptions::event_dead_band` and `event_rearm`, see `stopping`).
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DefaultAllocator, Dim, DimName, OVector};
+
+// local imports
+use super::adaptive::AdaptiveStep;
+use super::common::{Diagnostic, IntegOptions, IntegResult};
+use super::stopping::{Stop, StopCondition};
+
+// === End Imports ===
+
+// Dynamics of a mode, or reset map of a transition
+pub type ModeMap<N> = fn(f64, &OVector<f64, N>) -> OVector<f64, N>;
+
+#[derive(Debug, Clone)]
+pub struct Transition<N: Dim + DimName>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    // Triggers the transition when turning negative
+    pub guard: Diagnostic<N>,
+    // Index of the mode switched to
+    pub target: usize,
+    // State the target mode starts from, given the time and state at the switch
+    pub reset: ModeMap<N>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Mode<N: Dim + DimName>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    // Name of the mode (for reporting)
+    pub name: String,
+    // Dynamics while in the mode
+    pub dynamics: ModeMap<N>,
+    // Transitions out of the mode, in order of priority
+    pub transitions: Vec<Transition<N>>,
+}
+
+// A switch between modes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Switch {
+    // Time of the switch
+    pub t: f64,
+    // Mode left
+    pub from: usize,
+    // Mode entered
+    pub to: usize,
+    // Index of the transition taken among those of the mode left
+    pub transition: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct HybridSystem<N: Dim + DimName>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    // Modes of the system
+    modes: Vec<Mode<N>>,
+    // Number of switches after which the integration errors
+    max_switches: usize,
+}
+
+pub struct HybridResult<N: Dim + DimName>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    // Solutions of all modes, the time of each switch appearing before and after
+    // the reset
+    pub results: IntegResult<N>,
+    // Mode the integration started in
+    pub initial_mode: usize,
+    // Switches in order
+    pub switches: Vec<Switch>,
+}
+
+impl<N: Dim + DimName> HybridResult<N>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    // Sequence of the modes visited, starting with the initial one
+    pub fn modes(&self) -> Vec<usize> {
+        std::iter::once(self.initial_mode)
+            .chain(self.switches.iter().map(|switch| switch.to))
+            .collect()
+    }
+
+    // Mode the integration ended in
+    pub fn final_mode(&self) -> usize {
+        self.switches
+            .last()
+            .map_or(self.initial_mode, |switch| switch.to)
+    }
+}
+
+impl<N: Dim + DimName> HybridSystem<N>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    pub fn new(modes: Vec<Mode<N>>) -> Result<Self, &'static str> {
+        if modes.is_empty() {
+            return Err("[HYBRID] A hybrid system needs at least one mode");
+        }
+        for mode in modes.iter() {
+            if mode.transitions.iter().any(|t| t.target >= modes.len()) {
+                return Err("[HYBRID] Transition targets a mode that does not exist");
+            }
+        }
+        Ok(HybridSystem {
+            modes,
+            max_switches: 1000,
+        })
+    }
+
+    // Sets the number of switches after which the integration errors (default 1000)
+    pub fn with_max_switches(mut self, max_switches: usize) -> Self {
+        self.max_switches = max_switches;
+        self
+    }
+
+    pub fn modes(&self) -> &[Mode<N>] {
+        &self.modes
+    }
+
+    // Integrates over `span` from `y_0` in mode `mode` with an adaptive stepper
+    pub fn integrate<A: AdaptiveStep>(
+        &self,
+        stepper: &A,
+        mode: usize,
+        t_0: f64,
+        y_0: OVector<f64, N>,
+        span: f64,
+        integ_opts: IntegOptions<N>,
+    ) -> Result<HybridResult<N>, &'static str> {
+        if mode >= self.modes.len() {
+            return Err("[HYBRID] Initial mode out of range");
+        }
+        let t_end = t_0 + span;
+        let user_stops = integ_opts.stop_conditions.clone().unwrap_or_default();
+        let diagnostics = integ_opts.diagnostics.clone().unwrap_or_default();
+        let no_change = vec![0.0; integ_opts.integrands.as_ref().map_or(0, |g| g.len())];
+
+        let mut results = IntegResult::new(t_0, y_0.clone());
+        results.add_integrals(&no_change);
+        let mut switches = Vec::new();
+        let (mut current, mut t, mut y) = (mode, t_0, y_0);
+        let mut first_step = integ_opts.first_step;
+        while t != t_end {
+            let transitions = &self.modes[current].transitions;
+            let stop_conditions = transitions
+                .iter()
+                .map(|trans| StopCondition::Guard(trans.guard))
+                .chain(user_stops.iter().cloned())
+                .collect();
+            let options = IntegOptions {
+                first_step,
+                diagnostics: None,
+                stop_conditions: Some(stop_conditions),
+                ..integ_opts.clone()
+            };
+            let ans = stepper.integrate(
+                self.modes[current].dynamics,
+                t,
+                y.clone(),
+                t_end - t,
+                options,
+            )?;
+            for (t_i, y_i) in ans.times.iter().zip(ans.states.iter()).skip(1) {
+                results.add_val(t_i - results.t, y_i.clone());
+                results.land_on(*t_i);
+            }
+            // the integrals of each leg start over from zero
+            for pair in ans.integrals.windows(2) {
+                let increments: Vec<f64> =
+                    pair[1].iter().zip(&pair[0]).map(|(b, a)| b - a).collect();
+                results.add_integrals(&increments);
+            }
+            results.rejected.extend(ans.rejected.iter().cloned());
+            results.events.extend(ans.events.iter().cloned());
+            match results.extrema.is_empty() {
+                true => results.extrema = ans.extrema.clone(),
+                false => {
+                    for (ext, leg) in results.extrema.iter_mut().zip(&ans.extrema) {
+                        ext.merge(leg);
+                    }
+                }
+            }
+            first_step = ans.next_step.or(first_step);
+            (t, y) = (ans.t, ans.last_y().clone());
+
+            match ans.stopped {
+                Some(Stop { condition, .. }) if condition < transitions.len() => {
+                    if switches.len() == self.max_switches {
+                        return Err("[HYBRID] Too many switches (Zeno behaviour?)");
+                    }
+                    let trans = &transitions[condition];
+                    y = (trans.reset)(t, &y);
+                    results.add_val(0.0, y.clone());
+                    results.add_integrals(&no_change);
+                    switches.push(Switch {
+                        t,
+                        from: current,
+                        to: trans.target,
+                        transition: condition,
+                    });
+                    current = trans.target;
+                }
+                Some(Stop { condition, t: t_s }) => {
+                    results.stopped = Some(Stop {
+                        condition: condition - transitions.len(),
+                        t: t_s,
+                    });
+                    break;
+                }
+                None => break,
+            }
+        }
+        results.next_step = first_step;
+        results.update_diagnostics(&diagnostics);
+        Ok(HybridResult {
+            results,
+            initial_mode: mode,
+            switches,
+        })
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runge_kutta::rk_embed::DOPRI78;
+    use na::{Vector1, Vector2};
+
+    fn heating(_t: f64, y: &Vector1<f64>) -> Vector1<f64> {
+        Vector1::new(-0.1 * (y[0] - 50.0))
+    }
+
+    fn cooling(_t: f64, y: &Vector1<f64>) -> Vector1<f64> {
+        Vector1::new(-0.1 * y[0])
+    }
+
+    fn keep(_t: f64, y: &Vector1<f64>) -> Vector1<f64> {
+        *y
+    }
+
+    fn fall(_t: f64, y: &Vector2<f64>) -> Vector2<f64> {
+        Vector2::new(y[1], -9.8)
+    }
+
+    fn bounce(_t: f64, y: &Vector2<f64>) -> Vector2<f64> {
+        Vector2::new(0.0, -0.5 * y[1])
+    }
+
+    #[test]
+    fn test_hybrid_system() {
+        // thermostat switching the heater off above 22 and back on below 18: the
+        // time in each mode follows from the exponential relaxations
+        let thermostat = HybridSystem::new(vec![
+            Mode {
+                name: "heating".to_string(),
+                dynamics: heating,
+                transitions: vec![Transition {
+                    guard: |_t, y| 22.0 - y[0],
+                    target: 1,
+                    reset: keep,
+                }],
+            },
+            Mode {
+                name: "cooling".to_string(),
+                dynamics: cooling,
+                transitions: vec![Transition {
+                    guard: |_t, y| y[0] - 18.0,
+                    target: 0,
+                    reset: keep,
+                }],
+            },
+        ])
+        .unwrap();
+        let ans = thermostat
+            .integrate(
+                &*DOPRI78,
+                0,
+                0.0,
+                Vector1::new(20.0),
+                7.0,
+                IntegOptions::default(),
+            )
+            .unwrap();
+        let first = 10.0 * (30.0_f64 / 28.0).ln();
+        let off = 10.0 * (22.0_f64 / 18.0).ln();
+        let on = 10.0 * (32.0_f64 / 28.0).ln();
+        let expected = [first, first + off, first + off + on, first + 2.0 * off + on];
+        assert_eq!(ans.modes(), vec![0, 1, 0, 1, 0]);
+        for (switch, t_exp) in ans.switches.iter().zip(expected.iter()) {
+            assert!((switch.t - t_exp).abs() < 1e-9);
+        }
+        // the switch times appear twice, and the run ends at the final time
+        let times = &ans.results.times;
+        assert_eq!(times.len(), ans.results.states.len());
+        assert_eq!(times.windows(2).filter(|w| w[0] == w[1]).count(), 4);
+        assert_eq!(ans.results.t, 7.0);
+        assert!(HybridSystem::new(vec![Mode {
+            name: "lost".to_string(),
+            dynamics: keep,
+            transitions: vec![Transition {
+                guard: |_t, y| y[0],
+                target: 1,
+                reset: keep,
+            }],
+        }])
+        .is_err());
+
+        // bouncing ball dropped from 10 m, losing half its speed at every bounce:
+        // the flights shrink geometrically, and the bounces pile up before the
+        // ball comes to rest at 3 * sqrt(2 h / g)
+        let ball = HybridSystem::new(vec![Mode {
+            name: "flight".to_string(),
+            dynamics: fall,
+            transitions: vec![Transition {
+                guard: |_t, y| y[0],
+                target: 0,
+                reset: bounce,
+            }],
+        }])
+        .unwrap()
+        .with_max_switches(20);
+        let drop = (2.0 * 10.0 / 9.8_f64).sqrt();
+        let ans = ball
+            .integrate(
+                &*DOPRI78,
+                0,
+                0.0,
+                Vector2::new(10.0, 0.0),
+                3.0,
+                IntegOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(ans.switches.len(), 2);
+        assert!((ans.switches[0].t - drop).abs() < 1e-9);
+        assert!((ans.switches[1].t - 2.0 * drop).abs() < 1e-9);
+        let zeno = ball.integrate(
+            &*DOPRI78,
+            0,
+            0.0,
+            Vector2::new(10.0, 0.0),
+            10.0,
+            IntegOptions::default(),
+        );
+        assert!(zeno.err().unwrap().contains("switches"));
+    }
+}
@@ -6,9 +6,18 @@ extern crate nalgebra;
 pub mod adaptive;
 pub mod base;
 pub mod common;
+pub mod cosim;
+pub mod defect;
+pub mod domain;
+pub mod doubling;
 pub mod embedded;
+pub mod extrema;
 pub mod fixed;
+pub mod hybrid;
+pub mod staged;
+pub mod stopping;
 pub mod tableaus;
+pub mod zero_alloc;
 
 // === PRE-BUILT: Simple ===
 // Note: only explicit integrators are provided
@@ -67,11 +76,11 @@ pub mod rk_simp {
 pub mod rk_embed {
     use super::embedded::EmbeddedRKStepper;
     use super::tableaus::EmbeddedTableau;
-    use nalgebra::{Matrix4, Matrix6, MatrixN, Vector4, Vector6, VectorN, U13, U4, U6};
+    use nalgebra::{Matrix4, Matrix6, OMatrix, OVector, Vector4, Vector6, U13, U4, U6};
 
     // Types for 8th order integrators
-    type Vector13<N> = VectorN<N, U13>;
-    type Matrix13<N> = MatrixN<N, U13>;
+    type Vector13<N> = OVector<N, U13>;
+    type Matrix13<N> = OMatrix<N, U13, U13>;
 
     // RK 3(2) (Bogacki Shampine Method)
     lazy_static! {
@@ -420,11 +429,11 @@ pub mod rk_embed {
                     -59238493.0 / 1068277825.0,
                     181606767.0 / 758867731.0,
                     561292985.0 / 797845732.0,
-                    -1041891430.0 / 13713433529.0,
+                    -1041891430.0 / 1371343529.0,
                     760417239.0 / 1151165299.0,
                     118820643.0 / 751138087.0,
                     -528747749.0 / 2220607170.0,
-                    0.0
+                    0.25
                 ]),
 
                 b_hat_vals: Vector13::from_row_slice(&[
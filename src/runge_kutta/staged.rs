@@ -0,0 +1,412 @@
+/// Staged Systems (runge_kutta/staged)
+///
+/// Hybrid systems whose state changes dimension at a switch: a launcher dropping
+/// the mass and propellant states of a spent stage, a spacecraft deploying a
+/// probe whose position and velocity join the state, a chemical network losing a
+/// species once it is consumed. Each mode (`StagedMode`) keeps a compile time
+/// dimension of its own, so its dynamics are integrated by the usual steppers on
+/// `OVector<f64, N>`, and its transitions work like those of `hybrid`: a guard
+/// turning negative triggers them, and the reset maps the state at the switch to
+/// the starting state of the target mode, whatever its dimension. Between modes
+/// (and in the result) the states are dynamic dimension vectors (`DVector`).
+///
+/// `StagedSystem::integrate` takes the modes as trait objects (`AnyMode`) erasing
+/// their dimension. Since the options hold functions of the state (diagnostics,
+/// stopping conditions, events), every mode carries its own `IntegOptions`,
+/// tolerances included: the absolute tolerance of a mass state rarely suits a
+/// position. A stopping condition of a mode ends the integration, reported by its
+/// index among the stopping conditions of that mode. The switches are logged as
+/// `hybrid::Switch`, and more than `max_switches` of them error as Zeno behaviour.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DVector, DefaultAllocator, Dim, DimName, OVector};
+
+// local imports
+use super::adaptive::AdaptiveStep;
+use super::common::{Diagnostic, IntegOptions};
+use super::hybrid::{ModeMap, Switch};
+use super::stopping::{Stop, StopCondition};
+
+// === End Imports ===
+
+// Reset map of a transition to a mode of another dimension
+pub type StageReset<N> = fn(f64, &OVector<f64, N>) -> DVector<f64>;
+
+#[derive(Debug, Clone)]
+pub struct StagedTransition<N: Dim + DimName>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    // Triggers the transition when turning negative
+    pub guard: Diagnostic<N>,
+    // Index of the mode switched to
+    pub target: usize,
+    // State the target mode starts from (of its dimension), given the time and
+    // state at the switch
+    pub reset: StageReset<N>,
+}
+
+#[derive(Debug, Clone)]
+pub struct StagedMode<N: Dim + DimName>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    // Name of the mode (for reporting)
+    pub name: String,
+    // Dynamics while in the mode
+    pub dynamics: ModeMap<N>,
+    // Transitions out of the mode, in order of priority
+    pub transitions: Vec<StagedTransition<N>>,
+    // Options of the integration while in the mode
+    pub options: IntegOptions<N>,
+}
+
+// Leg of a staged integration, run in a single mode
+#[derive(Debug, Clone)]
+pub struct Leg {
+    // Times of the solutions
+    pub times: Vec<f64>,
+    // Solutions, of the dimension of the mode
+    pub states: Vec<DVector<f64>>,
+    // Transition taken at the end of the leg, with the state it resets to
+    pub transition: Option<(usize, DVector<f64>)>,
+    // Stopping condition of the mode which ended the leg
+    pub stopped: Option<Stop>,
+    // Step the controller proposed last
+    pub next_step: Option<f64>,
+}
+
+// Mode of a staged system with its dimension erased
+pub trait AnyMode<A: AdaptiveStep> {
+    fn name(&self) -> &str;
+
+    // Dimension of the state in the mode
+    fn dim(&self) -> usize;
+
+    // Modes targeted by the transitions out of the mode
+    fn targets(&self) -> Vec<usize>;
+
+    // Integrates from y_0 at t_0 over span until a transition or a stopping
+    // condition of the mode, warm started with first_step if given
+    fn run(
+        &self,
+        stepper: &A,
+        t_0: f64,
+        y_0: &DVector<f64>,
+        span: f64,
+        first_step: Option<f64>,
+    ) -> Result<Leg, &'static str>;
+}
+
+impl<A: AdaptiveStep, N: Dim + DimName> AnyMode<A> for StagedMode<N>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn dim(&self) -> usize {
+        N::dim()
+    }
+
+    fn targets(&self) -> Vec<usize> {
+        self.transitions.iter().map(|trans| trans.target).collect()
+    }
+
+    fn run(
+        &self,
+        stepper: &A,
+        t_0: f64,
+        y_0: &DVector<f64>,
+        span: f64,
+        first_step: Option<f64>,
+    ) -> Result<Leg, &'static str> {
+        if y_0.len() != N::dim() {
+            return Err("[STAGED] State of another dimension than its mode's");
+        }
+        let stop_conditions = self
+            .transitions
+            .iter()
+            .map(|trans| StopCondition::Guard(trans.guard))
+            .chain(self.options.stop_conditions.iter().flatten().cloned())
+            .collect();
+        let options = IntegOptions {
+            first_step: first_step.or(self.options.first_step),
+            stop_conditions: Some(stop_conditions),
+            ..self.options.clone()
+        };
+        let ans = stepper.integrate(
+            self.dynamics,
+            t_0,
+            OVector::<f64, N>::from_column_slice(y_0.as_slice()),
+            span,
+            options,
+        )?;
+        let (transition, stopped) = match ans.stopped {
+            Some(Stop { condition, .. }) if condition < self.transitions.len() => {
+                let reset = (self.transitions[condition].reset)(ans.t, ans.last_y());
+                (Some((condition, reset)), None)
+            }
+            Some(Stop { condition, t }) => (
+                None,
+                Some(Stop {
+                    condition: condition - self.transitions.len(),
+                    t,
+                }),
+            ),
+            None => (None, None),
+        };
+        Ok(Leg {
+            times: ans.times.clone(),
+            states: ans
+                .states
+                .iter()
+                .map(|y| DVector::from_column_slice(y.as_slice()))
+                .collect(),
+            transition,
+            stopped,
+            next_step: ans.next_step,
+        })
+    }
+}
+
+pub struct StagedSystem<'a, A: AdaptiveStep> {
+    // Modes of the system
+    modes: Vec<Box<dyn AnyMode<A> + 'a>>,
+    // Number of switches after which the integration errors
+    max_switches: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct StagedResult {
+    // Times of the solutions, the time of each switch appearing before and after
+    // the reset
+    pub times: Vec<f64>,
+    // Solutions, of the dimension of the mode they were found in
+    pub states: Vec<DVector<f64>>,
+    // Mode the integration started in
+    pub initial_mode: usize,
+    // Switches in order
+    pub switches: Vec<Switch>,
+    // Stopping condition which ended the integration, by its index among those of
+    // the mode it fired in
+    pub stopped: Option<Stop>,
+}
+
+impl StagedResult {
+    // Sequence of the modes visited, starting with the initial one
+    pub fn modes(&self) -> Vec<usize> {
+        std::iter::once(self.initial_mode)
+            .chain(self.switches.iter().map(|switch| switch.to))
+            .collect()
+    }
+
+    // Mode the integration ended in
+    pub fn final_mode(&self) -> usize {
+        self.switches
+            .last()
+            .map_or(self.initial_mode, |switch| switch.to)
+    }
+
+    pub fn last_y(&self) -> &DVector<f64> {
+        &self.states[self.states.len() - 1]
+    }
+}
+
+impl<'a, A: AdaptiveStep> StagedSystem<'a, A> {
+    pub fn new(modes: Vec<Box<dyn AnyMode<A> + 'a>>) -> Result<Self, &'static str> {
+        if modes.is_empty() {
+            return Err("[STAGED] A staged system needs at least one mode");
+        }
+        for mode in modes.iter() {
+            if mode.targets().into_iter().any(|t| t >= modes.len()) {
+                return Err("[STAGED] Transition targets a mode that does not exist");
+            }
+        }
+        Ok(StagedSystem {
+            modes,
+            max_switches: 1000,
+        })
+    }
+
+    // Sets the number of switches after which the integration errors (default 1000)
+    pub fn with_max_switches(mut self, max_switches: usize) -> Self {
+        self.max_switches = max_switches;
+        self
+    }
+
+    // Dimensions of the states in each mode
+    pub fn dims(&self) -> Vec<usize> {
+        self.modes.iter().map(|mode| mode.dim()).collect()
+    }
+
+    // Integrates over `span` from `y_0` (of the dimension of `mode`) in mode `mode`
+    pub fn integrate(
+        &self,
+        stepper: &A,
+        mode: usize,
+        t_0: f64,
+        y_0: DVector<f64>,
+        span: f64,
+    ) -> Result<StagedResult, &'static str> {
+        if mode >= self.modes.len() {
+            return Err("[STAGED] Initial mode out of range");
+        }
+        let t_end = t_0 + span;
+        let mut results = StagedResult {
+            times: vec![t_0],
+            states: vec![y_0.clone()],
+            initial_mode: mode,
+            switches: Vec::new(),
+            stopped: None,
+        };
+        let (mut current, mut t, mut y) = (mode, t_0, y_0);
+        let mut first_step = None;
+        while t != t_end {
+            let leg = self.modes[current].run(stepper, t, &y, t_end - t, first_step)?;
+            results.times.extend(leg.times.iter().skip(1));
+            results.states.extend(leg.states.iter().skip(1).cloned());
+            first_step = leg.next_step.or(first_step);
+            t = leg.times[leg.times.len() - 1];
+
+            match leg.transition {
+                Some((transition, reset)) => {
+                    if results.switches.len() == self.max_switches {
+                        return Err("[STAGED] Too many switches (Zeno behaviour?)");
+                    }
+                    let target = self.modes[current].targets()[transition];
+                    results.times.push(t);
+                    results.states.push(reset.clone());
+                    results.switches.push(Switch {
+                        t,
+                        from: current,
+                        to: target,
+                        transition,
+                    });
+                    (current, y) = (target, reset);
+                }
+                None => {
+                    results.stopped = leg.stopped;
+                    break;
+                }
+            }
+        }
+        Ok(results)
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runge_kutta::rk_embed::DOPRI78;
+    use na::{Vector2, Vector3, Vector4};
+
+    // burning stage: (x, v, m) with a thrust of 10 and a mass flow of 1
+    fn burn(_t: f64, y: &Vector3<f64>) -> Vector3<f64> {
+        Vector3::new(y[1], 10.0 / y[2], -1.0)
+    }
+
+    // coasting upper stage: (x, v)
+    fn coast(_t: f64, y: &Vector2<f64>) -> Vector2<f64> {
+        Vector2::new(y[1], 0.0)
+    }
+
+    // upper stage and a deployed probe: (x, v, x_probe, v_probe)
+    fn deployed(_t: f64, y: &Vector4<f64>) -> Vector4<f64> {
+        Vector4::new(y[1], 0.0, y[3], 0.0)
+    }
+
+    fn tight<N: Dim + DimName>() -> IntegOptions<N>
+    where
+        DefaultAllocator: Allocator<N>,
+    {
+        IntegOptions {
+            atol: Some(OVector::<f64, N>::repeat(1e-12)),
+            rtol: Some(1e-12),
+            ..IntegOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_staged_system() {
+        // burn from 10 to 4 mass units with an exhaust velocity of 10, drop the
+        // spent stage, and deploy a probe at 1 unit/s once 100 units out: the
+        // rocket equation gives the burnout state
+        let system = StagedSystem::new(vec![
+            Box::new(StagedMode {
+                name: "burn".to_string(),
+                dynamics: burn,
+                transitions: vec![StagedTransition {
+                    guard: |_t, y| y[2] - 4.0,
+                    target: 1,
+                    reset: |_t, y| DVector::from_column_slice(&[y[0], y[1]]),
+                }],
+                options: tight(),
+            }),
+            Box::new(StagedMode {
+                name: "coast".to_string(),
+                dynamics: coast,
+                transitions: vec![StagedTransition {
+                    guard: |_t, y| 100.0 - y[0],
+                    target: 2,
+                    reset: |_t, y| DVector::from_column_slice(&[y[0], y[1], y[0], y[1] + 1.0]),
+                }],
+                options: tight(),
+            }),
+            Box::new(StagedMode {
+                name: "deployed".to_string(),
+                dynamics: deployed,
+                transitions: Vec::new(),
+                options: tight(),
+            }),
+        ])
+        .unwrap();
+        assert_eq!(system.dims(), vec![3, 2, 4]);
+        let ans = system
+            .integrate(
+                &*DOPRI78,
+                0,
+                0.0,
+                DVector::from_column_slice(&[0.0, 0.0, 10.0]),
+                20.0,
+            )
+            .unwrap();
+        let v_b = 10.0 * 2.5_f64.ln();
+        let x_b = 60.0 - 40.0 * 2.5_f64.ln();
+        let t_d = 6.0 + (100.0 - x_b) / v_b;
+        assert_eq!(ans.modes(), vec![0, 1, 2]);
+        assert!((ans.switches[0].t - 6.0).abs() < 1e-9);
+        assert!((ans.switches[1].t - t_d).abs() < 1e-9);
+        let expected = [
+            x_b + v_b * 14.0,
+            v_b,
+            100.0 + (v_b + 1.0) * (20.0 - t_d),
+            v_b + 1.0,
+        ];
+        let y = ans.last_y();
+        assert_eq!(y.len(), 4);
+        assert!(y
+            .iter()
+            .zip(expected.iter())
+            .all(|(a, b)| (a - b).abs() < 1e-8));
+        // every state has the dimension of its mode, the switch times appearing
+        // with the state before and after the reset
+        let i = ans
+            .times
+            .iter()
+            .position(|t| *t == ans.switches[0].t)
+            .unwrap();
+        assert_eq!((ans.states[i].len(), ans.states[i + 1].len()), (3, 2));
+        assert!(ans.states.iter().all(|y| [2, 3, 4].contains(&y.len())));
+
+        // a state of the wrong dimension for the initial mode
+        assert!(system
+            .integrate(&*DOPRI78, 1, 0.0, DVector::from_column_slice(&[0.0]), 1.0)
+            .is_err());
+    }
+}
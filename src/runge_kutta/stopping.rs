@@ -0,0 +1,799 @@
+/// Stopping Conditions (runge_kutta/stopping)
+///
+/// Terminal conditions which end an integration before its final time. Several can
+/// be registered at once (`IntegOptions::stop_conditions`):
+/// - a wall clock limit on the run
+/// - an event, the first zero crossing of a function g(t, y) in either direction
+/// - a guard, a function g(t, y) turning negative (the one-sided events switching
+///   the modes of a hybrid system, see `hybrid`)
+/// - a crossing of an `Event` (below), with its direction, activation time and
+///   occurrence: "stop at the 10th apoapsis"
+/// - a state bound, a component leaving [lower, upper]
+/// - a diagnostic threshold, a functional drifting from its initial value by more
+///   than a given amount
+/// - leaving the domain of the dynamics (see `domain`), which ends the integration
+///   on the boundary where it would otherwise error
+///
+/// After every accepted step each condition is checked at the end of the step. The
+/// crossing of each condition that fired is located within the step by re-taking
+/// the step with shorter lengths (Illinois variant of regula falsi on the step
+/// length), and the integration ends on the earliest crossing, with the state
+/// there as the last solution. Conditions which fire at the same time (within the
+/// location tolerance) are ranked by their position in the list, earlier
+/// conditions taking priority. The result reports the condition that ended the run
+/// in `IntegResult::stopped`.
+///
+/// Events which do not end the integration (`IntegOptions::events`, e.g. eclipse
+/// entries and exits or the rise and set of a ground station) are recorded in
+/// `IntegResult::events` with the time and state of each crossing. All the events
+/// crossing within one step are located, each with the step re-taken from its
+/// start, and recorded in chronological order (ties in the order of the list),
+/// those after a stopping condition that fired in the same step left out. An event
+/// can be restricted to rising crossings (g going from negative to non-negative as
+/// time increases, e.g. r . v at periapsis) or falling ones (apoapsis), and to
+/// crossings after a time (`active_after`, skipping a warm-up interval). The
+/// crossings of each event are counted (`EventHit::occurrence`, counting those in
+/// its direction after its activation time), and an event given an occurrence
+/// (`nth`) acts only on that crossing: it is recorded, or ends the integration as
+/// a stopping condition, only there. Counts start over with every integration (and
+/// every mode of a hybrid system).
+///
+/// Events and guards re-registered whenever an integration restarts (the guards of
+/// a hybrid system chattering across a switching surface, as in sliding mode) can
+/// fire again right after the restart, each time after a shorter step, until the
+/// integration stalls. Two options guard against it:
+/// - a dead band (`IntegOptions::event_dead_band`): a guard fires once g(t, y)
+///   has gone below -band rather than below zero, and an event once g has crossed
+///   from the sign it started on to beyond band on the other side. The switching
+///   surfaces of the modes of a hybrid system become a hysteresis loop of width
+///   twice the band
+/// - a re-arm time (`IntegOptions::event_rearm`): events and guards are disarmed
+///   for this long after the start of the integration, giving a minimum dwell time
+///   in each mode. A condition which would have fired while disarmed fires when it
+///   is re-armed
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DefaultAllocator, Dim, DimName, OVector};
+
+// local imports
+use super::common::Diagnostic;
+
+// Standard library imports
+use std::time::{Duration, Instant};
+
+// === End Imports ===
+
+// (compared like the diagnostics of `IntegOptions`, by function address)
+#[allow(unpredictable_function_pointer_comparisons)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum StopCondition<N: Dim + DimName>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    // Wall clock time the integration may run for
+    WallClock(Duration),
+    // Zero crossing of g(t, y)
+    Event(Diagnostic<N>),
+    // g(t, y) going from non-negative to negative
+    Guard(Diagnostic<N>),
+    // Crossing of an event (the first one, or its occurrence)
+    Crossing(Event<N>),
+    // Component `index` of the state leaving [lower, upper]
+    StateBound {
+        index: usize,
+        lower: f64,
+        upper: f64,
+    },
+    // Diagnostic g(t, y) drifting from its initial value by more than `threshold`
+    DiagnosticDrift {
+        diagnostic: Diagnostic<N>,
+        threshold: f64,
+    },
+    // Solution reaching the boundary of `IntegOptions::domain`, where steps kept
+    // inside shrink below the minimum step
+    DomainExit,
+}
+
+// Direction of the zero crossings an event detects, as time increases
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    // From negative to non-negative
+    Rising,
+    // From positive to non-positive
+    Falling,
+    // Either
+    Both,
+}
+
+// Zero crossing of g(t, y) recorded over an integration without ending it
+#[allow(unpredictable_function_pointer_comparisons)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event<N: Dim + DimName>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    // Event function
+    pub g: Diagnostic<N>,
+    // Crossings detected
+    pub direction: Direction,
+    // Time before which crossings are ignored
+    pub active_after: Option<f64>,
+    // Crossing the event acts on (1 for the first), every one when None
+    pub occurrence: Option<usize>,
+}
+
+impl<N: Dim + DimName> Event<N>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    // Event detecting crossings in both directions from the start
+    pub fn new(g: Diagnostic<N>) -> Self {
+        Event {
+            g,
+            direction: Direction::Both,
+            active_after: None,
+            occurrence: None,
+        }
+    }
+
+    // Detects only rising crossings
+    pub fn rising(mut self) -> Self {
+        self.direction = Direction::Rising;
+        self
+    }
+
+    // Detects only falling crossings
+    pub fn falling(mut self) -> Self {
+        self.direction = Direction::Falling;
+        self
+    }
+
+    // Ignores crossings before time t
+    pub fn active_after(mut self, t: f64) -> Self {
+        self.active_after = Some(t);
+        self
+    }
+
+    // Acts only on the n-th crossing (counting from 1)
+    pub fn nth(mut self, n: usize) -> Self {
+        self.occurrence = Some(n);
+        self
+    }
+
+    // Whether the event acts on the crossing which is the `count`-th
+    fn acts_on(&self, count: usize) -> bool {
+        self.occurrence.is_none_or(|n| n == count)
+    }
+
+    // Whether the value going from `before` to `after` over a step forward
+    // (`forward`) or backward in time is a crossing in the direction of the event
+    fn fires(&self, before: f64, after: f64, forward: bool) -> bool {
+        let rising = before < 0.0 && after >= 0.0;
+        let falling = before > 0.0 && after <= 0.0;
+        match (self.direction, forward) {
+            (Direction::Both, _) => rising || falling,
+            (Direction::Rising, true) | (Direction::Falling, false) => rising,
+            (Direction::Rising, false) | (Direction::Falling, true) => falling,
+        }
+    }
+
+    // Whether a crossing at time t is detected, moving forward or backward in time
+    fn active(&self, t: f64, forward: bool) -> bool {
+        self.active_after
+            .is_none_or(|t_on| (forward && t >= t_on) || (!forward && t <= t_on))
+    }
+}
+
+// Crossing of an event recorded over an integration
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventHit<N: Dim + DimName>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    // Index of the event in the registered list
+    pub event: usize,
+    // Count of the crossing among those of the event (1 for the first)
+    pub occurrence: usize,
+    // Time of the crossing
+    pub t: f64,
+    // State at the crossing
+    pub state: OVector<f64, N>,
+}
+
+// Condition that ended an integration
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stop {
+    // Index of the condition in the registered list
+    pub condition: usize,
+    // Time the condition fired
+    pub t: f64,
+}
+
+impl<N: Dim + DimName> StopCondition<N>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    // Signed distance to firing, None for the wall clock. `initial` is the value of
+    // the diagnostic at the start for drift conditions
+    fn value(&self, t: f64, y: &OVector<f64, N>, initial: f64) -> Option<f64> {
+        match self {
+            StopCondition::WallClock(_) | StopCondition::DomainExit => None,
+            StopCondition::Event(g) | StopCondition::Guard(g) => Some(g(t, y)),
+            StopCondition::Crossing(event) => Some((event.g)(t, y)),
+            StopCondition::StateBound {
+                index,
+                lower,
+                upper,
+            } => Some((y[*index] - lower).min(upper - y[*index])),
+            StopCondition::DiagnosticDrift {
+                diagnostic,
+                threshold,
+            } => Some(threshold - (diagnostic(t, y) - initial).abs()),
+        }
+    }
+
+    // Whether the value going from `before` to `after` fires the condition. Events
+    // fire on crossings in either direction, the others on leaving the region where
+    // their value is positive (crossings are checked by their `Event`)
+    fn fires(&self, before: f64, after: f64) -> bool {
+        match self {
+            StopCondition::Event(_) => {
+                (before > 0.0 && after <= 0.0) || (before < 0.0 && after >= 0.0)
+            }
+            _ => before >= 0.0 && after < 0.0,
+        }
+    }
+}
+
+// Tracks registered stopping conditions over an integration
+pub struct StopMonitor<'a, N: Dim + DimName>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    // Registered conditions, in order of priority
+    conditions: &'a [StopCondition<N>],
+    // Value of each condition at the last accepted step
+    values: Vec<Option<f64>>,
+    // Initial value of the diagnostic of drift conditions
+    initial: Vec<f64>,
+    // Wall clock start of the integration
+    start: Instant,
+    // Time the integration started at
+    t_0: f64,
+    // Distance past zero events and guards must reach to fire
+    dead_band: f64,
+    // Time after the start during which events and guards are disarmed
+    rearm: f64,
+    // Sign each event started on (0 until it leaves zero)
+    sides: Vec<f64>,
+    // Registered events, recorded without ending the integration
+    events: &'a [Event<N>],
+    // Value of each event at the last accepted step
+    event_values: Vec<f64>,
+    // Crossings of the events over the last checked step
+    hits: Vec<EventHit<N>>,
+    // Crossings counted so far for each condition
+    counts: Vec<usize>,
+    // Crossings counted so far for each event
+    event_counts: Vec<usize>,
+}
+
+impl<'a, N: Dim + DimName> StopMonitor<'a, N>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    pub fn new(conditions: &'a [StopCondition<N>], t_0: f64, y_0: &OVector<f64, N>) -> Self {
+        let initial: Vec<f64> = conditions
+            .iter()
+            .map(|cond| match cond {
+                StopCondition::DiagnosticDrift { diagnostic, .. } => diagnostic(t_0, y_0),
+                _ => 0.0,
+            })
+            .collect();
+        let values: Vec<Option<f64>> = conditions
+            .iter()
+            .zip(initial.iter())
+            .map(|(cond, init)| cond.value(t_0, y_0, *init))
+            .collect();
+        let sides = values
+            .iter()
+            .map(|value| value.map_or(0.0, |v| if v == 0.0 { 0.0 } else { v.signum() }))
+            .collect();
+        StopMonitor {
+            conditions,
+            values,
+            initial,
+            start: Instant::now(),
+            t_0,
+            dead_band: 0.0,
+            rearm: 0.0,
+            sides,
+            events: &[],
+            event_values: Vec::new(),
+            hits: Vec::new(),
+            counts: vec![0; conditions.len()],
+            event_counts: Vec::new(),
+        }
+    }
+
+    // Registers events to record (at the state the integration starts from)
+    pub fn with_events(mut self, events: &'a [Event<N>], t_0: f64, y_0: &OVector<f64, N>) -> Self {
+        self.events = events;
+        self.event_values = events.iter().map(|event| (event.g)(t_0, y_0)).collect();
+        self.event_counts = vec![0; events.len()];
+        self
+    }
+
+    // Crossings of the events over the last checked step, in chronological order
+    pub fn take_hits(&mut self) -> Vec<EventHit<N>> {
+        std::mem::take(&mut self.hits)
+    }
+
+    // Sets the dead band and the re-arm time of the events and guards (both zero by
+    // default)
+    pub fn with_chattering_guard(mut self, dead_band: Option<f64>, rearm: Option<f64>) -> Self {
+        self.dead_band = dead_band.unwrap_or(0.0).abs();
+        self.rearm = rearm.unwrap_or(0.0).abs();
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.conditions.is_empty() && self.events.is_empty()
+    }
+
+    // Stop at time t for leaving the domain, or the error `err` when no
+    // `DomainExit` condition is registered
+    pub fn exit_domain<E>(&self, t: f64, err: E) -> Result<Stop, E> {
+        self.conditions
+            .iter()
+            .position(|cond| *cond == StopCondition::DomainExit)
+            .map(|condition| Stop { condition, t })
+            .ok_or(err)
+    }
+
+    // Checks the conditions over an accepted step of length h from t ending on
+    // y_new. `step_to(s)` re-takes the step from t with length s. Returns the
+    // earliest condition to fire with the step length to its crossing and the state
+    // there, or None (after recording the values at the end of the step). The
+    // crossings of the events up to there are left for `take_hits`
+    pub fn check<F>(
+        &mut self,
+        t: f64,
+        h: f64,
+        y_new: &OVector<f64, N>,
+        step_to: F,
+    ) -> Option<(Stop, f64, OVector<f64, N>)>
+    where
+        F: Fn(f64) -> OVector<f64, N>,
+    {
+        let tol = 1e-12 * h.abs().max(f64::EPSILON * t.abs());
+        let forward = h > 0.0;
+        let mut first: Option<(usize, f64)> = None;
+        let mut new_values = Vec::with_capacity(self.conditions.len());
+        for (idx, cond) in self.conditions.iter().enumerate() {
+            let event = matches!(cond, StopCondition::Event(_) | StopCondition::Guard(_));
+            // disarmed conditions keep the value they had before
+            if event && (t + h - self.t_0).abs() < self.rearm {
+                new_values.push(self.values[idx]);
+                continue;
+            }
+            let after = cond.value(t + h, y_new, self.initial[idx]);
+            new_values.push(after);
+            if let (true, 0.0, Some(before)) = (event, self.sides[idx], self.values[idx]) {
+                self.sides[idx] = if before == 0.0 { 0.0 } else { before.signum() };
+            }
+            let shift = match cond {
+                StopCondition::Guard(_) => self.dead_band,
+                StopCondition::Event(_) => self.dead_band * self.sides[idx],
+                _ => 0.0,
+            };
+            let s = match (cond, self.values[idx], after) {
+                (StopCondition::WallClock(limit), _, _) => {
+                    if self.start.elapsed() >= *limit {
+                        h
+                    } else {
+                        continue;
+                    }
+                }
+                (StopCondition::Crossing(event), Some(before), Some(after)) => {
+                    if !event.fires(before, after, forward) || !event.active(t + h, forward) {
+                        continue;
+                    }
+                    let g = |s: f64| (event.g)(t + s, &step_to(s));
+                    let fires = |a: f64, b: f64| event.fires(a, b, forward);
+                    let s = locate(&fires, &g, (0.0, before), after, h, tol);
+                    if !event.active(t + s, forward) {
+                        continue;
+                    }
+                    self.counts[idx] += 1;
+                    if !event.acts_on(self.counts[idx]) {
+                        continue;
+                    }
+                    s
+                }
+                (_, Some(before), Some(after)) if cond.fires(before + shift, after + shift) => {
+                    let g =
+                        |s: f64| cond.value(t + s, &step_to(s), self.initial[idx]).unwrap() + shift;
+                    // step length at which the condition was re-armed
+                    let s_arm = match event {
+                        true => (self.rearm - (t - self.t_0).abs()).max(0.0).copysign(h),
+                        false => 0.0,
+                    };
+                    let fires = |a: f64, b: f64| cond.fires(a, b);
+                    if s_arm == 0.0 {
+                        locate(&fires, &g, (0.0, before + shift), after + shift, h, tol)
+                    } else {
+                        let g_arm = g(s_arm);
+                        match cond.fires(before + shift, g_arm) {
+                            true => s_arm,
+                            false => locate(&fires, &g, (s_arm, g_arm), after + shift, h, tol),
+                        }
+                    }
+                }
+                _ => continue,
+            };
+            // earlier crossings win, ties go to the earlier condition
+            match first {
+                Some((_, s_first)) if s.abs() >= s_first.abs() - tol => {}
+                _ => first = Some((idx, s)),
+            }
+        }
+        self.values = new_values;
+
+        // every event crossing before the stop, in chronological order
+        let mut crossings = Vec::new();
+        for (idx, event) in self.events.iter().enumerate() {
+            let (before, after) = (self.event_values[idx], (event.g)(t + h, y_new));
+            self.event_values[idx] = after;
+            if event.fires(before, after, forward) && event.active(t + h, forward) {
+                let g = |s: f64| (event.g)(t + s, &step_to(s));
+                let fires = |a: f64, b: f64| event.fires(a, b, forward);
+                let s = locate(&fires, &g, (0.0, before), after, h, tol);
+                if !event.active(t + s, forward) {
+                    continue;
+                }
+                if first.is_some_and(|(_, s_stop)| s.abs() > s_stop.abs() + tol) {
+                    continue;
+                }
+                self.event_counts[idx] += 1;
+                if event.acts_on(self.event_counts[idx]) {
+                    crossings.push((idx, self.event_counts[idx], s));
+                }
+            }
+        }
+        crossings.sort_by(|a, b| a.2.abs().partial_cmp(&b.2.abs()).unwrap());
+        self.hits = crossings
+            .into_iter()
+            .map(|(idx, occurrence, s)| EventHit {
+                event: idx,
+                occurrence,
+                t: t + s,
+                state: if s == h { y_new.clone() } else { step_to(s) },
+            })
+            .collect();
+
+        first.map(|(idx, s)| {
+            let y_s = if s == h { y_new.clone() } else { step_to(s) };
+            (
+                Stop {
+                    condition: idx,
+                    t: t + s,
+                },
+                s,
+                y_s,
+            )
+        })
+    }
+}
+
+// Step length in [s_0, h] at which a condition fires (`fires(before, after)`),
+// bracketed by the values at `start` = (s_0, g(s_0)) and the end of the step
+// (Illinois algorithm). Returns the end of the bracket on the fired side so that
+// the condition holds at the returned point
+fn locate<P: Fn(f64, f64) -> bool, G: Fn(f64) -> f64>(
+    fires: &P,
+    g: &G,
+    start: (f64, f64),
+    g_h: f64,
+    h: f64,
+    tol: f64,
+) -> f64 {
+    let g_0 = start.1;
+    let (mut a, mut g_a) = start;
+    let (mut b, mut g_b) = (h, g_h);
+    let mut side = 0;
+    for _ in 0..100 {
+        if (b - a).abs() <= tol {
+            break;
+        }
+        let s = b - g_b * (b - a) / (g_b - g_a);
+        let s = if s.is_finite() && (s - a) * (b - s) > 0.0 {
+            s
+        } else {
+            0.5 * (a + b)
+        };
+        let g_s = g(s);
+        if fires(g_0, g_s) {
+            b = s;
+            g_b = g_s;
+            if side == -1 {
+                g_a *= 0.5;
+            }
+            side = -1;
+        } else {
+            a = s;
+            g_a = g_s;
+            if side == 1 {
+                g_b *= 0.5;
+            }
+            side = 1;
+        }
+    }
+    b
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runge_kutta::adaptive::AdaptiveStep;
+    use crate::runge_kutta::common::IntegOptions;
+    use crate::runge_kutta::fixed::FixedStep;
+    use crate::runge_kutta::hybrid::{HybridSystem, Mode, Transition};
+    use crate::runge_kutta::rk_embed::{DOPRI78, RKF45};
+    use crate::runge_kutta::rk_simp::RK4;
+    use na::{Vector1, Vector2, Vector4, U1};
+    use std::f64::consts::PI;
+
+    #[test]
+    fn test_stop_conditions() {
+        // ball thrown up from the ground: y = 10 t - 4.9 t^2 lands at t = 10 / 4.9
+        let ball = |_t: f64, y: &Vector2<f64>| Vector2::new(y[1], -9.8);
+        let height = |_t: f64, y: &Vector2<f64>| y[0];
+        let energy = |_t: f64, y: &Vector2<f64>| 0.5 * y[1] * y[1] + 9.8 * y[0];
+        let y_0 = Vector2::new(0.0, 10.0);
+        // an event at the apex and a floor under the ground: the apex comes first
+        let opts = IntegOptions {
+            stop_conditions: Some(vec![
+                StopCondition::StateBound {
+                    index: 0,
+                    lower: -1.0,
+                    upper: 100.0,
+                },
+                StopCondition::Event(|_t, y| y[1]),
+            ]),
+            ..IntegOptions::default()
+        };
+        let ans = RK4.integrate(ball, 0.0, y_0, 10.0, 0.1, opts).unwrap();
+        let stop = ans.stopped.unwrap();
+        assert_eq!(stop.condition, 1);
+        assert!((stop.t - 10.0 / 9.8).abs() < 1e-10);
+        assert_eq!(*ans.times.last().unwrap(), stop.t);
+        assert!(ans.last_y()[1].abs() < 1e-9);
+
+        // landing, with the height as an event and as a bound firing at the same
+        // time: the first registered wins
+        let opts = IntegOptions {
+            stop_conditions: Some(vec![
+                StopCondition::WallClock(Duration::from_secs(60)),
+                StopCondition::StateBound {
+                    index: 0,
+                    lower: 0.0,
+                    upper: 100.0,
+                },
+                StopCondition::Event(height),
+            ]),
+            ..IntegOptions::default()
+        };
+        let ans = RKF45.integrate(ball, 0.0, y_0, 10.0, opts.clone()).unwrap();
+        let stop = ans.stopped.unwrap();
+        assert_eq!(stop.condition, 1);
+        assert!((stop.t - 10.0 / 4.9).abs() < 1e-9);
+        assert!(ans.last_y()[0] <= 0.0);
+
+        // a drift threshold which the exact solution never reaches does not fire
+        let opts = IntegOptions {
+            stop_conditions: Some(vec![StopCondition::DiagnosticDrift {
+                diagnostic: energy,
+                threshold: 1e-6,
+            }]),
+            ..IntegOptions::default()
+        };
+        let ans = RK4.integrate(ball, 0.0, y_0, 1.0, 0.1, opts).unwrap();
+        assert!(ans.stopped.is_none());
+        assert_eq!(ans.t, 1.0);
+    }
+
+    #[test]
+    fn test_event_ordering() {
+        // oscillator x = cos t: the two levels are crossed within the same steps,
+        // in the opposite order of the list on the way down and on the way up, the
+        // last crossing after the stop at t = 5.2 in that step left out
+        let spring = |_t: f64, y: &Vector2<f64>| Vector2::new(y[1], -y[0]);
+        let opts = IntegOptions {
+            events: Some(vec![
+                Event::new(|_t, y| y[0] - 0.4),
+                Event::new(|_t, y| y[0] - 0.5),
+            ]),
+            stop_conditions: Some(vec![StopCondition::Event(|t, _y| t - 5.2)]),
+            ..IntegOptions::default()
+        };
+        let ans = RK4
+            .integrate(spring, 0.0, Vector2::new(1.0, 0.0), 7.0, 0.5, opts)
+            .unwrap();
+        let expected = [
+            (1, 0.5_f64.acos()),
+            (0, 0.4_f64.acos()),
+            (0, 2.0 * PI - 0.4_f64.acos()),
+        ];
+        assert_eq!(ans.events.len(), expected.len());
+        for (hit, (event, t)) in ans.events.iter().zip(expected.iter()) {
+            assert_eq!(hit.event, *event);
+            assert!((hit.t - t).abs() < 1e-2);
+            let level = [0.4, 0.5][hit.event];
+            assert!((hit.state[0] - level).abs() < 1e-9);
+        }
+        assert!((ans.t - 5.2).abs() < 1e-9);
+    }
+
+    fn kepler(_t: f64, y: &Vector4<f64>) -> Vector4<f64> {
+        let r3 = (y[0] * y[0] + y[1] * y[1]).powf(1.5);
+        Vector4::new(y[2], y[3], -y[0] / r3, -y[1] / r3)
+    }
+
+    #[test]
+    fn test_event_count() {
+        // stop at the third apoapsis of an orbit of period 2 pi started at
+        // periapsis, recording every periapsis and only the second turning point
+        let radial = |_t: f64, y: &Vector4<f64>| y[0] * y[2] + y[1] * y[3];
+        let opts = IntegOptions {
+            atol: Some(Vector4::repeat(1e-12)),
+            rtol: Some(1e-12),
+            events: Some(vec![Event::new(radial).rising(), Event::new(radial).nth(2)]),
+            stop_conditions: Some(vec![StopCondition::Crossing(
+                Event::new(radial).falling().nth(3),
+            )]),
+            ..IntegOptions::default()
+        };
+        let y_0 = Vector4::new(0.5, 0.0, 0.0, 3.0_f64.sqrt());
+        let ans = DOPRI78
+            .integrate(kepler, 0.0, y_0, 20.0 * PI, opts)
+            .unwrap();
+        assert!((ans.stopped.unwrap().t - 5.0 * PI).abs() < 1e-8);
+        let found: Vec<(usize, usize, f64)> = ans
+            .events
+            .iter()
+            .map(|hit| (hit.event, hit.occurrence, hit.t / PI))
+            .collect();
+        let expected = [(0, 1, 2.0), (1, 2, 2.0), (0, 2, 4.0)];
+        assert_eq!(found.len(), expected.len());
+        for (hit, hit_exp) in found.iter().zip(expected.iter()) {
+            assert_eq!((hit.0, hit.1), (hit_exp.0, hit_exp.1));
+            assert!((hit.2 - hit_exp.2).abs() < 1e-8);
+        }
+    }
+
+    #[test]
+    fn test_event_direction() {
+        // orbit of period 2 pi from periapsis: r . v rises through zero at
+        // periapsis and falls at apoapsis
+        let radial = |_t: f64, y: &Vector4<f64>| y[0] * y[2] + y[1] * y[3];
+        let y_0 = Vector4::new(0.5, 0.0, 0.0, 3.0_f64.sqrt());
+        let opts = |events| IntegOptions {
+            atol: Some(Vector4::repeat(1e-12)),
+            rtol: Some(1e-12),
+            events: Some(events),
+            ..IntegOptions::default()
+        };
+        let hits = |span: f64, events| {
+            let ans = DOPRI78.integrate(kepler, 0.0, y_0, span, opts(events));
+            let times: Vec<(usize, f64)> = ans
+                .unwrap()
+                .events
+                .iter()
+                .map(|hit| (hit.event, hit.t / PI))
+                .collect();
+            times
+        };
+        let events = vec![
+            Event::new(radial).rising(),
+            Event::new(radial).falling(),
+            Event::new(radial).rising().active_after(3.5 * PI),
+        ];
+        let found = hits(5.5 * PI, events.clone());
+        let expected = [(1, 1.0), (0, 2.0), (1, 3.0), (0, 4.0), (2, 4.0), (1, 5.0)];
+        assert_eq!(found.len(), expected.len());
+        for ((event, t), (event_exp, t_exp)) in found.iter().zip(expected.iter()) {
+            assert_eq!(event, event_exp);
+            assert!((t - t_exp).abs() < 1e-8);
+        }
+        // backward in time the directions still refer to increasing time
+        let found = hits(-2.5 * PI, events[..2].to_vec());
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].0, 1);
+        assert!((found[0].1 + 1.0).abs() < 1e-8);
+        assert_eq!(found[1].0, 0);
+        assert!((found[1].1 + 2.0).abs() < 1e-8);
+    }
+
+    fn down(_t: f64, _y: &Vector1<f64>) -> Vector1<f64> {
+        Vector1::new(-1.0)
+    }
+
+    fn up(_t: f64, _y: &Vector1<f64>) -> Vector1<f64> {
+        Vector1::new(1.0)
+    }
+
+    fn keep(_t: f64, y: &Vector1<f64>) -> Vector1<f64> {
+        *y
+    }
+
+    #[test]
+    fn test_chattering_guard() {
+        // a terminal event with a dead band fires past it: the ball lands in a pit
+        // half a metre deep
+        let ball = |_t: f64, y: &Vector2<f64>| Vector2::new(y[1], -9.8);
+        let opts = IntegOptions {
+            stop_conditions: Some(vec![StopCondition::Event(|_t, y| y[0])]),
+            event_dead_band: Some(0.5),
+            ..IntegOptions::default()
+        };
+        let ans = RK4
+            .integrate(ball, 0.0, Vector2::new(0.0, 10.0), 10.0, 0.1, opts)
+            .unwrap();
+        let t_pit = (10.0 + (100.0_f64 + 9.8).sqrt()) / 9.8;
+        assert!((ans.stopped.unwrap().t - t_pit).abs() < 1e-9);
+
+        // sliding mode: both modes drive x toward zero, and reach it at t = 1.
+        // Unprotected, the switches pile up there; a dead band switches every
+        // 2 band, a re-arm time every re-arm time
+        let sliding = HybridSystem::new(vec![
+            Mode {
+                name: "above".to_string(),
+                dynamics: down,
+                transitions: vec![Transition {
+                    guard: |_t, y| y[0],
+                    target: 1,
+                    reset: keep,
+                }],
+            },
+            Mode {
+                name: "below".to_string(),
+                dynamics: up,
+                transitions: vec![Transition {
+                    guard: |_t, y| -y[0],
+                    target: 0,
+                    reset: keep,
+                }],
+            },
+        ])
+        .unwrap();
+        let run = |opts: IntegOptions<U1>| {
+            sliding.integrate(&*RKF45, 0, 0.0, Vector1::new(1.0), 2.0, opts)
+        };
+        assert!(run(IntegOptions::default()).is_err());
+        let banded = run(IntegOptions {
+            event_dead_band: Some(0.01),
+            ..IntegOptions::default()
+        })
+        .unwrap();
+        assert!((banded.switches.len() as i64 - 50).abs() <= 1);
+        assert!(banded.results.states.iter().all(|y| y[0] <= 1.0));
+        let after = banded.results.times.iter().position(|t| *t > 1.0).unwrap();
+        assert!(banded.results.states[after..]
+            .iter()
+            .all(|y| y[0].abs() < 0.01 + 1e-9));
+        let rearmed = run(IntegOptions {
+            event_rearm: Some(0.05),
+            ..IntegOptions::default()
+        })
+        .unwrap();
+        assert!((rearmed.switches.len() as i64 - 20).abs() <= 1);
+        for pair in rearmed.switches.windows(2) {
+            assert!(pair[1].t - pair[0].t >= 0.05 - 1e-12);
+        }
+    }
+}
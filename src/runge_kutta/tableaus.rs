@@ -22,11 +22,17 @@
 /// (4) Diagonally-implicit method: a_ii = const for i = 1, 2, ... , s
 ///
 /// NOTE: This library does not currently support Diagonally-implicit methods
+///
+/// The linear stability function R(z) (the amplification of one step on the test
+/// equation y' = lambda y with z = h lambda) and the order of accuracy (from the
+/// order conditions of every rooted tree up to order 8) can be computed from any
+/// tableau, for analysing new methods.
+///
 // === Begin Imports ===
 // third party imports
 extern crate nalgebra as na;
 use na::allocator::Allocator;
-use na::{DefaultAllocator, Dim, DimName, MatrixMN, VectorN};
+use na::{Complex, DefaultAllocator, Dim, DimName, OMatrix, OVector};
 
 // === End Imports ===
 
@@ -41,14 +47,14 @@ pub enum RkType {
 #[derive(Debug, Clone, PartialEq)]
 pub struct Tableau<D: DimName + Dim>
 where
-    DefaultAllocator: Allocator<f64, D> + Allocator<f64, D, D>,
+    DefaultAllocator: Allocator<D> + Allocator<D, D>,
 {
     // A coefficients
-    pub a_vals: MatrixMN<f64, D, D>,
+    pub a_vals: OMatrix<f64, D, D>,
     // C coefficients for time step variation
-    pub c_vals: VectorN<f64, D>,
+    pub c_vals: OVector<f64, D>,
     // B coefficients for final slope averaging
-    pub b_vals: VectorN<f64, D>,
+    pub b_vals: OVector<f64, D>,
 }
 
 /// Butcher Tableau for Embedded RK methods
@@ -80,16 +86,16 @@ where
 #[derive(Debug, Clone, PartialEq)]
 pub struct EmbeddedTableau<D: DimName + Dim>
 where
-    DefaultAllocator: Allocator<f64, D> + Allocator<f64, D, D>,
+    DefaultAllocator: Allocator<D> + Allocator<D, D>,
 {
     // A coefficients
-    pub a_vals: MatrixMN<f64, D, D>,
+    pub a_vals: OMatrix<f64, D, D>,
     // C coefficients for time step variation
-    pub c_vals: VectorN<f64, D>,
+    pub c_vals: OVector<f64, D>,
     // B coefficients for lower order slope averaging
-    pub b_vals: VectorN<f64, D>,
+    pub b_vals: OVector<f64, D>,
     // B coefficients for higher order slope averaging
-    pub b_hat_vals: VectorN<f64, D>,
+    pub b_hat_vals: OVector<f64, D>,
 }
 
 /// Checks that row-sum condition holds for a given tableau
@@ -100,7 +106,7 @@ where
 ///
 pub fn check_row_sum<D: DimName + Dim>(tab: &Tableau<D>) -> bool
 where
-    DefaultAllocator: Allocator<f64, D> + Allocator<f64, D, D>,
+    DefaultAllocator: Allocator<D> + Allocator<D, D>,
 {
     let (rows, cols) = tab.a_vals.shape();
     let mut row_sum: f64;
@@ -116,10 +122,166 @@ where
     true
 }
 
+// Highest order for which the order conditions are checked
+const MAX_CONDITION_ORDER: usize = 8;
+
+impl<D: DimName + Dim> Tableau<D>
+where
+    DefaultAllocator: Allocator<D> + Allocator<D, D>,
+{
+    // Stability function R(z) = 1 + z b^T (I - z A)^-1 1
+    pub fn stability_function(&self, z: Complex<f64>) -> Complex<f64> {
+        stability_function(&self.a_vals, &self.b_vals, z)
+    }
+
+    // Order of accuracy (up to 8) from the order conditions
+    pub fn order(&self) -> usize {
+        order_of_accuracy(&self.a_vals, &self.b_vals)
+    }
+}
+
+impl<D: DimName + Dim> EmbeddedTableau<D>
+where
+    DefaultAllocator: Allocator<D> + Allocator<D, D>,
+{
+    // Stability function of the propagated (b_hat) solution
+    pub fn stability_function(&self, z: Complex<f64>) -> Complex<f64> {
+        stability_function(&self.a_vals, &self.b_hat_vals, z)
+    }
+
+    // Orders of accuracy (up to 8) of the b and b_hat solutions
+    pub fn orders(&self) -> (usize, usize) {
+        (
+            order_of_accuracy(&self.a_vals, &self.b_vals),
+            order_of_accuracy(&self.a_vals, &self.b_hat_vals),
+        )
+    }
+}
+
+// R(z) = 1 + z b^T k with (I - z A) k = 1, solved by gaussian elimination with
+// partial pivoting (so implicit tableaus work as well)
+fn stability_function<D: DimName + Dim>(
+    a_vals: &OMatrix<f64, D, D>,
+    b_vals: &OVector<f64, D>,
+    z: Complex<f64>,
+) -> Complex<f64>
+where
+    DefaultAllocator: Allocator<D> + Allocator<D, D>,
+{
+    let s = b_vals.len();
+    let one = Complex::new(1.0, 0.0);
+    let mut mat: Vec<Vec<Complex<f64>>> = (0..s)
+        .map(|i| {
+            (0..s)
+                .map(|j| if i == j { one } else { Complex::new(0.0, 0.0) } - z * a_vals[(i, j)])
+                .collect()
+        })
+        .collect();
+    let mut k = vec![one; s];
+    for col in 0..s {
+        let pivot = (col..s)
+            .max_by(|p, q| {
+                let (p, q) = (mat[*p][col].norm_sqr(), mat[*q][col].norm_sqr());
+                p.partial_cmp(&q).unwrap()
+            })
+            .unwrap();
+        mat.swap(col, pivot);
+        k.swap(col, pivot);
+        for row in col + 1..s {
+            let factor = mat[row][col] / mat[col][col];
+            let pivot_row = mat[col].clone();
+            for (entry, pivot) in mat[row].iter_mut().zip(pivot_row.iter()).skip(col) {
+                *entry -= factor * pivot;
+            }
+            let sub = factor * k[col];
+            k[row] -= sub;
+        }
+    }
+    for row in (0..s).rev() {
+        let mut sum = k[row];
+        for j in row + 1..s {
+            sum -= mat[row][j] * k[j];
+        }
+        k[row] = sum / mat[row][row];
+    }
+    one + z * (0..s).fold(Complex::new(0.0, 0.0), |acc, i| acc + k[i] * b_vals[i])
+}
+
+// Largest p for which b^T Phi(t) = 1 / gamma(t) holds for every rooted tree t of
+// order up to p (Butcher's order conditions, with the stage vectors Phi(t) built
+// from the children of each tree)
+fn order_of_accuracy<D: DimName + Dim>(
+    a_vals: &OMatrix<f64, D, D>,
+    b_vals: &OVector<f64, D>,
+) -> usize
+where
+    DefaultAllocator: Allocator<D> + Allocator<D, D>,
+{
+    const TOL: f64 = 1e-9;
+    let s = b_vals.len();
+    let trees = rooted_trees(MAX_CONDITION_ORDER);
+    // stage values prod_children (A Phi(child)) and density gamma of each tree
+    let mut phis: Vec<Vec<f64>> = Vec::with_capacity(trees.len());
+    let mut gammas: Vec<f64> = Vec::with_capacity(trees.len());
+    let mut order = MAX_CONDITION_ORDER;
+    for (t_order, children) in trees.iter() {
+        let mut phi = vec![1.0; s];
+        let mut gamma = *t_order as f64;
+        for child in children {
+            for (i, phi_i) in phi.iter_mut().enumerate() {
+                *phi_i *= (0..s)
+                    .map(|j| a_vals[(i, j)] * phis[*child][j])
+                    .sum::<f64>();
+            }
+            gamma *= gammas[*child];
+        }
+        let weight: f64 = (0..s).map(|i| b_vals[i] * phi[i]).sum();
+        if (gamma * weight - 1.0).abs() > TOL {
+            order = order.min(t_order - 1);
+        }
+        phis.push(phi);
+        gammas.push(gamma);
+    }
+    order
+}
+
+// Rooted trees up to the given order, in increasing order, each as its order and
+// the indices (into the list) of the subtrees under its root
+fn rooted_trees(max_order: usize) -> Vec<(usize, Vec<usize>)> {
+    // forests of the given order as non-increasing lists of tree indices below bound
+    fn forests(
+        trees: &[(usize, Vec<usize>)],
+        remaining: usize,
+        bound: usize,
+        current: &mut Vec<usize>,
+        out: &mut Vec<Vec<usize>>,
+    ) {
+        if remaining == 0 {
+            out.push(current.clone());
+            return;
+        }
+        for idx in 0..bound {
+            if trees[idx].0 <= remaining {
+                current.push(idx);
+                forests(trees, remaining - trees[idx].0, idx + 1, current, out);
+                current.pop();
+            }
+        }
+    }
+
+    let mut trees = vec![(1, Vec::new())];
+    for order in 2..=max_order {
+        let mut out = Vec::new();
+        forests(&trees, order - 1, trees.len(), &mut Vec::new(), &mut out);
+        trees.extend(out.into_iter().map(|children| (order, children)));
+    }
+    trees
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use nalgebra::{Matrix4, Vector4};
+    use nalgebra::{Matrix1, Matrix4, Vector1, Vector4};
 
     #[test]
     fn tableau_manual() {
@@ -149,4 +311,38 @@ mod tests {
         };
         assert_eq!(true, check_row_sum(&b))
     }
+
+    #[test]
+    fn test_order_and_stability_function() {
+        // 1, 1, 2, 4, 9, 20, 48 and 115 rooted trees of orders 1 to 8
+        let trees = rooted_trees(8);
+        let counts: Vec<usize> = (1..=8)
+            .map(|p| trees.iter().filter(|t| t.0 == p).count())
+            .collect();
+        assert_eq!(counts, vec![1, 1, 2, 4, 9, 20, 48, 115]);
+
+        let a_vals = Matrix4::new(
+            0.0, 0.0, 0.0, 0.0, 0.5, 0.0, 0.0, 0.0, 0.0, 0.5, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0,
+        );
+        let rk4 = Tableau {
+            a_vals,
+            c_vals: Vector4::new(0.0, 0.5, 0.5, 1.0),
+            b_vals: Vector4::new(1.0 / 6.0, 1.0 / 3.0, 1.0 / 3.0, 1.0 / 6.0),
+        };
+        assert_eq!(rk4.order(), 4);
+        let z = Complex::new(-1.5_f64, 0.7);
+        let taylor =
+            Complex::new(1.0, 0.0) + z + z * z / 2.0 + z * z * z / 6.0 + z * z * z * z / 24.0;
+        assert!((rk4.stability_function(z) - taylor).norm_sqr() < 1e-28);
+
+        // implicit midpoint: order 2 and R(z) = (1 + z / 2) / (1 - z / 2)
+        let midpoint = Tableau {
+            a_vals: Matrix1::new(0.5),
+            c_vals: Vector1::new(0.5),
+            b_vals: Vector1::new(1.0),
+        };
+        assert_eq!(midpoint.order(), 2);
+        let exact = (Complex::new(1.0, 0.0) + z / 2.0) / (Complex::new(1.0, 0.0) - z / 2.0);
+        assert!((midpoint.stability_function(z) - exact).norm_sqr() < 1e-28);
+    }
 }
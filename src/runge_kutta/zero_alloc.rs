@@ -0,0 +1,254 @@
+/// Allocation Free Stepping (runge_kutta/zero_alloc)
+///
+/// Embedded and flight software often forbids heap allocation after start up. With
+/// fixed size states (`OVector<f64, N>` for a compile time N, the `Vector3`,
+/// `Vector6`, ... of nalgebra) every state and stage of the explicit Runge Kutta
+/// steppers lives on the stack: the stages are held in an array of `MAX_STAGES`
+/// vectors rather than a `Vec`, so once a stepper is built its steps never touch
+/// the heap. Tableaus with more stages are rejected when the stepper is built.
+///
+/// `ZeroAlloc` marks the steppers which keep that promise, so code which must not
+/// allocate can say so in its bounds (`S: StepSimple + ZeroAlloc`) and have the
+/// compiler turn away any other stepper. It holds for steps without integrands, of
+/// dynamics which do not allocate themselves. The integrators (`FixedStep`,
+/// `AdaptiveStep`) store the whole trajectory in their result and do allocate;
+/// `propagate` and `propagate_adaptive` run the same loops keeping only the current
+/// state, and allocate nothing unless they error. Like the integrators they retry
+/// steps on which fallible dynamics fail recoverably with half the step and end on
+/// unrecoverable failures (see `systems::fallible`). The guarantee is checked by a test
+/// counting the allocations of the steps with a counting global allocator.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DefaultAllocator, Dim, DimName, OVector};
+
+// local imports
+use super::adaptive::{AdaptiveStep, StepValid};
+use super::base::RKStepper;
+use super::common::{approach_end, IntegError, StepBounds, StepSimple, Tolerances};
+use super::domain::shrink_step;
+use super::doubling::StepDoubling;
+use super::embedded::EmbeddedRKStepper;
+use crate::ridc::predictor::Predictor;
+use crate::systems::fallible::step_failed;
+use crate::systems::OdeSystem;
+use crate::utils::kahan::CompensatedSum;
+
+// === End Imports ===
+
+// Largest number of stages of an explicit Runge Kutta stepper
+pub const MAX_STAGES: usize = 16;
+
+// Smallest step of the propagation loops
+const MIN_STEP: f64 = 1e-10;
+
+// Stages of a step, on the stack for fixed size states
+pub(crate) type Stages<N> = [OVector<f64, N>; MAX_STAGES];
+
+pub(crate) fn stages<N: Dim + DimName>() -> Stages<N>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    std::array::from_fn(|_| OVector::<f64, N>::zeros())
+}
+
+// Steppers whose steps of fixed size states never allocate
+pub trait ZeroAlloc {}
+
+impl<D: DimName + Dim> ZeroAlloc for RKStepper<D> where
+    DefaultAllocator: Allocator<D> + Allocator<D, D>
+{
+}
+
+impl<D: DimName + Dim> ZeroAlloc for EmbeddedRKStepper<D> where
+    DefaultAllocator: Allocator<D> + Allocator<D, D>
+{
+}
+
+impl<P: Predictor + ZeroAlloc> ZeroAlloc for StepDoubling<P> {}
+
+// Fixed step propagation over span with steps of dt (the last one shortened to
+// land on the end), returning only the final state. Steps on which the dynamics
+// fail recoverably are retried with half the step, grown back over the next steps
+pub fn propagate<Z, N, S>(
+    stepper: &Z,
+    fxn: &S,
+    t_0: f64,
+    y_0: OVector<f64, N>,
+    span: f64,
+    dt: f64,
+) -> Result<OVector<f64, N>, IntegError>
+where
+    Z: StepSimple + ZeroAlloc,
+    N: Dim + DimName,
+    S: OdeSystem<N> + ?Sized,
+    DefaultAllocator: Allocator<N>,
+{
+    let t_end = t_0 + span;
+    let dt = dt.abs().copysign(span);
+    let (mut t, mut y) = (t_0, y_0);
+    let mut clock = CompensatedSum::new(t_0);
+    let mut shrunk: Option<f64> = None;
+    while t != t_end {
+        let (h, last) = approach_end(t, t_end, shrunk.unwrap_or(dt));
+        let res = stepper.step(fxn, t, &y, h);
+        if step_failed(fxn)? {
+            shrunk = Some(shrink_step(t, h, MIN_STEP)?);
+            continue;
+        }
+        shrunk = shrunk.map(|h| 2.0 * h).filter(|h| h.abs() < dt.abs());
+        y = res.value;
+        clock.add(h);
+        t = if last { t_end } else { clock.value() };
+    }
+    Ok(y)
+}
+
+// Adaptive propagation over span from a first step (tolerances default to those of
+// `AdaptiveStep::integrate`), returning the final state and the step proposed next.
+// Steps on which the dynamics fail recoverably are retried with half the step
+pub fn propagate_adaptive<Z, N, S>(
+    stepper: &Z,
+    fxn: &S,
+    t_0: f64,
+    y_0: OVector<f64, N>,
+    span: f64,
+    first_step: f64,
+    tol: &Tolerances<N>,
+) -> Result<(OVector<f64, N>, f64), IntegError>
+where
+    Z: AdaptiveStep + ZeroAlloc,
+    N: Dim + DimName,
+    S: OdeSystem<N> + ?Sized,
+    DefaultAllocator: Allocator<N>,
+{
+    let atol = tol
+        .abs
+        .clone()
+        .unwrap_or(OVector::<f64, N>::repeat(1e-3_f64));
+    let rtol = tol.rel.unwrap_or(1e-6_f64);
+    let mut bounds = StepBounds::new(MIN_STEP, None, None);
+    let t_end = t_0 + span;
+    let mut step = first_step.abs().copysign(span);
+    let (mut t, mut y) = (t_0, y_0);
+    let mut clock = CompensatedSum::new(t_0);
+    while t != t_end {
+        let (h, last) = approach_end(t, t_end, step);
+        let res = stepper.step(fxn, t, &y, h, &atol, rtol);
+        if step_failed(fxn)? {
+            step = shrink_step(t, h, MIN_STEP)?;
+            continue;
+        }
+        match stepper.revise_step(res.error, h) {
+            StepValid::Accept(next) => {
+                y = res.value;
+                clock.add(h);
+                t = if last { t_end } else { clock.value() };
+                step = bounds.accept(next);
+            }
+            StepValid::Refine(next) => step = bounds.reject(t, next, res.error)?,
+        }
+    }
+    Ok((y, step))
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runge_kutta::common::{IntegOptions, StepWithError};
+    use crate::runge_kutta::fixed::FixedStep;
+    use crate::runge_kutta::rk_embed::DOPRI78;
+    use crate::runge_kutta::rk_simp::RK4;
+    use crate::systems::fallible::{EvalFailure, Fallible};
+    use crate::test_fxns::alloc_count::allocations;
+    use na::{Vector1, Vector6};
+
+    // Allocations made by the current thread while running
+    fn count<R>(run: impl FnOnce() -> R) -> (R, usize) {
+        let start = allocations();
+        let out = run();
+        (out, allocations() - start)
+    }
+
+    fn kepler(_t: f64, y: &Vector6<f64>) -> Vector6<f64> {
+        let r3 = y.fixed_rows::<3>(0).norm().powi(3);
+        Vector6::new(y[3], y[4], y[5], -y[0] / r3, -y[1] / r3, -y[2] / r3)
+    }
+
+    #[test]
+    fn test_zero_alloc() {
+        // the steppers are built (and the lazy statics initialized) before counting
+        let y_0 = Vector6::new(1.0, 0.0, 0.0, 0.0, 1.0, 0.0);
+        let atol = Vector6::repeat(1e-10);
+        let (rk4, dopri, doubling) = (&*RK4, &*DOPRI78, RK4.step_doubling());
+        let tol = Tolerances {
+            abs: Some(atol),
+            rel: Some(1e-10),
+        };
+        let (_, n) = count(|| {
+            rk4.step(&kepler, 0.0, &y_0, 0.1);
+            StepWithError::step(dopri, &kepler, 0.0, &y_0, 0.1, &atol, 1e-10);
+            StepWithError::step(&doubling, &kepler, 0.0, &y_0, 0.1, &atol, 1e-10);
+        });
+        assert_eq!(n, 0);
+
+        // a whole orbit, fixed and adaptive
+        let period = 2.0 * std::f64::consts::PI;
+        let (y_fixed, n) = count(|| propagate(rk4, &kepler, 0.0, y_0, period, 0.01));
+        assert_eq!(n, 0);
+        assert!((y_fixed.unwrap() - y_0).amax() < 1e-7);
+        let (ans, n) = count(|| propagate_adaptive(dopri, &kepler, 0.0, y_0, period, 0.1, &tol));
+        assert_eq!(n, 0);
+        // the same steps as the integrator
+        let (y_adaptive, _next) = ans.unwrap();
+        let opts = IntegOptions {
+            atol: Some(atol),
+            rtol: Some(1e-10),
+            first_step: Some(0.1),
+            ..IntegOptions::default()
+        };
+        let reference = dopri.integrate(kepler, 0.0, y_0, period, opts).unwrap();
+        assert!((y_adaptive - reference.last_y()).amax() < 1e-12);
+
+        // the counting itself works: the integrators store the trajectory
+        let (_, n) = count(|| {
+            rk4.integrate(kepler, 0.0, y_0, 1.0, 0.1, IntegOptions::default())
+                .unwrap()
+        });
+        assert!(n > 0);
+    }
+
+    #[test]
+    fn test_propagate_failures() {
+        // stages overshooting below zero are retried with half the step, and the
+        // table ends at t = 1.5
+        let decay = |t: f64, y: &Vector1<f64>| {
+            if t > 1.5 {
+                Err(EvalFailure::Fatal("[TABLE] Time out of range"))
+            } else if y[0] < 0.0 {
+                Err(EvalFailure::Retry)
+            } else {
+                Ok(Vector1::new(-3.0 * y[0]))
+            }
+        };
+        let system = Fallible::new(decay);
+        let y_0 = Vector1::new(1.0);
+        let (rk4, dopri) = (&*RK4, &*DOPRI78);
+        let y = propagate(rk4, &system, 0.0, y_0, 1.0, 1.0).unwrap();
+        assert!((y[0] - (-3.0_f64).exp()).abs() < 1e-2);
+        let err = propagate(rk4, &system, 0.0, y_0, 2.0, 0.1).unwrap_err();
+        assert_eq!(err, IntegError::Failed("[TABLE] Time out of range"));
+
+        let tol = Tolerances {
+            abs: Some(Vector1::repeat(1e-10)),
+            rel: Some(1e-10),
+        };
+        let (y, _next) = propagate_adaptive(dopri, &system, 0.0, y_0, 1.0, 1.0, &tol).unwrap();
+        assert!((y[0] - (-3.0_f64).exp()).abs() < 1e-9);
+        let err = propagate_adaptive(dopri, &system, 0.0, y_0, 2.0, 0.1, &tol).unwrap_err();
+        assert_eq!(err, IntegError::Failed("[TABLE] Time out of range"));
+    }
+}
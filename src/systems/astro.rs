@@ -0,0 +1,303 @@
+/// Astrodynamics Systems (systems/astro)
+///
+/// Ready to use `OdeSystem` implementations of common astrodynamics problems:
+/// - Circular restricted three body problem (CR3BP) in the rotating frame
+/// - Two body problem perturbed by the J2 zonal harmonic
+///
+/// Also provides transformations between the rotating (synodic) frame of the
+/// CR3BP and an inertial frame sharing the same origin.
+///
+/// The CR3BP is written in non-dimensional units: the distance between the
+/// primaries, the total mass of the primaries and the mean motion are all unity.
+/// The larger primary sits at (-mu, 0, 0) and the smaller one at (1 - mu, 0, 0).
+/// States are ordered [x, y, z, vx, vy, vz].
+///
+/// The Arenstorf orbit constants are taken from Hairer, Norsett & Wanner
+/// "Solving Ordinary Differential Equations I" (pg 129)
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::{Vector6, U6};
+
+// local imports
+use super::OdeSystem;
+
+// === End Imports ===
+
+// === Circular Restricted Three Body Problem ===
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cr3bp {
+    // Mass ratio of the primaries m_2 / (m_1 + m_2)
+    pub mu: f64,
+}
+
+impl Cr3bp {
+    // Earth-Moon system
+    pub const EARTH_MOON: Cr3bp = Cr3bp {
+        mu: 0.012_150_585_609_624,
+    };
+    // Mass ratio used for the Arenstorf periodic orbit
+    pub const ARENSTORF: Cr3bp = Cr3bp { mu: 0.012_277_471 };
+
+    pub fn new(mu: f64) -> Self {
+        Cr3bp { mu }
+    }
+
+    // Distances from the state to the larger and smaller primary
+    fn radii(&self, state: &Vector6<f64>) -> (f64, f64) {
+        let r_1 = ((state[0] + self.mu).powi(2) + state[1].powi(2) + state[2].powi(2)).sqrt();
+        let r_2 = ((state[0] - 1.0 + self.mu).powi(2) + state[1].powi(2) + state[2].powi(2)).sqrt();
+        (r_1, r_2)
+    }
+
+    // Jacobi constant C = 2 U - v^2 where U is the pseudo-potential. This is the
+    // only integral of motion of the CR3BP, making it a good diagnostic of drift
+    pub fn jacobi_constant(&self, state: &Vector6<f64>) -> f64 {
+        let (r_1, r_2) = self.radii(state);
+        let pseudo_pot =
+            0.5 * (state[0].powi(2) + state[1].powi(2)) + (1.0 - self.mu) / r_1 + self.mu / r_2;
+        2.0 * pseudo_pot - (state[3].powi(2) + state[4].powi(2) + state[5].powi(2))
+    }
+}
+
+impl OdeSystem<U6> for Cr3bp {
+    fn dynamics(&self, _t: f64, state: &Vector6<f64>) -> Vector6<f64> {
+        let (r_1, r_2) = self.radii(state);
+        let c_1 = (1.0 - self.mu) / r_1.powi(3);
+        let c_2 = self.mu / r_2.powi(3);
+        Vector6::new(
+            state[3],
+            state[4],
+            state[5],
+            2.0 * state[4] + state[0]
+                - c_1 * (state[0] + self.mu)
+                - c_2 * (state[0] - 1.0 + self.mu),
+            -2.0 * state[3] + state[1] - (c_1 + c_2) * state[1],
+            -(c_1 + c_2) * state[2],
+        )
+    }
+}
+
+// Period of the Arenstorf orbit in non-dimensional time
+pub const ARENSTORF_PERIOD: f64 = 17.065_216_560_157_964;
+
+lazy_static! {
+    // Initial state of the Arenstorf orbit (planar, periodic in the rotating frame)
+    pub static ref ARENSTORF_INIT: Vector6<f64> =
+        Vector6::new(0.994, 0.0, 0.0, 0.0, -2.001_585_106_379_082_4, 0.0);
+}
+
+// === Two body problem with J2 ===
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TwoBodyJ2 {
+    // Gravitational parameter of the central body (km^3/s^2)
+    pub mu: f64,
+    // Unnormalized J2 coefficient of the central body
+    pub j2: f64,
+    // Equatorial radius of the central body (km)
+    pub r_eq: f64,
+}
+
+impl TwoBodyJ2 {
+    // Earth (EGM-96 values)
+    pub const EARTH: TwoBodyJ2 = TwoBodyJ2 {
+        mu: 398_600.441_8,
+        j2: 1.082_626_925_638_815e-3,
+        r_eq: 6_378.136_3,
+    };
+
+    pub fn new(mu: f64, j2: f64, r_eq: f64) -> Self {
+        TwoBodyJ2 { mu, j2, r_eq }
+    }
+
+    // Specific orbital energy including the J2 potential. Conserved by the
+    // dynamics since the field is time invariant
+    pub fn energy(&self, state: &Vector6<f64>) -> f64 {
+        let r = (state[0].powi(2) + state[1].powi(2) + state[2].powi(2)).sqrt();
+        let sin_lat_2 = state[2].powi(2) / r.powi(2);
+        let potential =
+            self.mu / r * (1.0 - self.j2 * (self.r_eq / r).powi(2) * 0.5 * (3.0 * sin_lat_2 - 1.0));
+        0.5 * (state[3].powi(2) + state[4].powi(2) + state[5].powi(2)) - potential
+    }
+}
+
+impl OdeSystem<U6> for TwoBodyJ2 {
+    fn dynamics(&self, _t: f64, state: &Vector6<f64>) -> Vector6<f64> {
+        let r_2 = state[0].powi(2) + state[1].powi(2) + state[2].powi(2);
+        let r = r_2.sqrt();
+        let z_2_ratio = state[2].powi(2) / r_2;
+        let j2_factor = 1.5 * self.j2 * self.r_eq.powi(2) / r_2;
+        let grav = -self.mu / r.powi(3);
+        Vector6::new(
+            state[3],
+            state[4],
+            state[5],
+            grav * state[0] * (1.0 - j2_factor * (5.0 * z_2_ratio - 1.0)),
+            grav * state[1] * (1.0 - j2_factor * (5.0 * z_2_ratio - 1.0)),
+            grav * state[2] * (1.0 - j2_factor * (5.0 * z_2_ratio - 3.0)),
+        )
+    }
+}
+
+// === Rotating frame transformations ===
+// Converts a state in a frame rotating about z at rate `omega` into the inertial
+// frame. The frames are aligned at t = 0
+pub fn rotating_to_inertial(t: f64, omega: f64, state: &Vector6<f64>) -> Vector6<f64> {
+    let (sin, cos) = (omega * t).sin_cos();
+    // velocity in inertial frame coordinates aligned with the rotating axes
+    let vx = state[3] - omega * state[1];
+    let vy = state[4] + omega * state[0];
+    Vector6::new(
+        cos * state[0] - sin * state[1],
+        sin * state[0] + cos * state[1],
+        state[2],
+        cos * vx - sin * vy,
+        sin * vx + cos * vy,
+        state[5],
+    )
+}
+
+// Converts an inertial state into a frame rotating about z at rate `omega`.
+// The frames are aligned at t = 0
+pub fn inertial_to_rotating(t: f64, omega: f64, state: &Vector6<f64>) -> Vector6<f64> {
+    let (sin, cos) = (omega * t).sin_cos();
+    let x = cos * state[0] + sin * state[1];
+    let y = -sin * state[0] + cos * state[1];
+    Vector6::new(
+        x,
+        y,
+        state[2],
+        cos * state[3] + sin * state[4] + omega * y,
+        -sin * state[3] + cos * state[4] - omega * x,
+        state[5],
+    )
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ridc::base::RIDCIntegratorFixed;
+    use crate::ridc::common::IntegOptionsParallel;
+    use crate::runge_kutta::adaptive::AdaptiveStep;
+    use crate::runge_kutta::common::IntegOptions;
+    use crate::runge_kutta::fixed::FixedStep;
+    use crate::runge_kutta::rk_embed::RKF45;
+    use crate::runge_kutta::rk_simp::RK4;
+
+    fn arenstorf_jacobi(_t: f64, y: &Vector6<f64>) -> f64 {
+        Cr3bp::ARENSTORF.jacobi_constant(y)
+    }
+
+    fn em_jacobi(_t: f64, y: &Vector6<f64>) -> f64 {
+        Cr3bp::EARTH_MOON.jacobi_constant(y)
+    }
+
+    fn j2_energy(_t: f64, y: &Vector6<f64>) -> f64 {
+        TwoBodyJ2::EARTH.energy(y)
+    }
+
+    fn max_drift(drift: Vec<f64>) -> f64 {
+        drift.iter().fold(0.0_f64, |max, d| max.max(d.abs()))
+    }
+
+    // Inclined orbit about the Earth in the Earth-Moon rotating frame
+    fn em_init() -> Vector6<f64> {
+        let mu = Cr3bp::EARTH_MOON.mu;
+        Vector6::new(
+            0.3 - mu,
+            0.0,
+            0.0,
+            0.0,
+            ((1.0 - mu) / 0.3_f64).sqrt() - 0.3,
+            0.05,
+        )
+    }
+
+    #[test]
+    fn test_arenstorf_periodic() {
+        let options = IntegOptions {
+            atol: Some(Vector6::repeat(4e-13)),
+            rtol: Some(4e-11),
+            diagnostics: Some(vec![arenstorf_jacobi]),
+            ..IntegOptions::default()
+        };
+        let ans = RKF45
+            .clone()
+            .integrate(
+                Cr3bp::ARENSTORF,
+                0.0,
+                *ARENSTORF_INIT,
+                ARENSTORF_PERIOD,
+                options,
+            )
+            .unwrap();
+
+        // orbit should close on itself after one period
+        assert!((ans.last_y() - *ARENSTORF_INIT).norm() < 1e-4);
+        assert!(max_drift(ans.diagnostic_drift(0)) < 1e-8);
+    }
+
+    #[test]
+    fn test_j2_energy_conservation() {
+        let y_0 = Vector6::new(7000.0, 0.0, 0.0, 0.0, 5.0, 5.5);
+        let options = IntegOptions {
+            diagnostics: Some(vec![j2_energy]),
+            ..IntegOptions::default()
+        };
+        // one day of propagation of a low earth orbit
+        let ans = RK4
+            .clone()
+            .integrate(TwoBodyJ2::EARTH, 0.0, y_0, 86400.0, 30.0, options)
+            .unwrap();
+        let energy = TwoBodyJ2::EARTH.energy(&ans.states[0]);
+
+        assert!(max_drift(ans.diagnostic_drift(0)) < 1e-6 * energy.abs());
+    }
+
+    #[test]
+    fn test_ridc_cr3bp_long_term() {
+        let y_0 = em_init();
+        let t_f = 10.0;
+
+        // tight serial solution used as reference
+        let options = IntegOptions {
+            atol: Some(Vector6::repeat(1e-12)),
+            rtol: Some(1e-12),
+            ..IntegOptions::default()
+        };
+        let truth = RKF45
+            .clone()
+            .integrate(Cr3bp::EARTH_MOON, 0.0, y_0, t_f, options)
+            .unwrap();
+
+        let par_options = IntegOptionsParallel {
+            corrector_order: Some(1),
+            diagnostics: Some(vec![em_jacobi]),
+            ..IntegOptionsParallel::default()
+        };
+        let ans = RK4
+            .parallel_integrator(Cr3bp::EARTH_MOON, 0.0, &y_0, t_f, 5e-3, par_options)
+            .unwrap();
+
+        assert!((ans.last_y() - truth.last_y()).norm() < 1e-4);
+        assert!(max_drift(ans.diagnostic_drift(0)) < 1e-5);
+    }
+
+    #[test]
+    fn test_rotating_frame_round_trip() {
+        let state = em_init();
+        for t in [0.0, 0.7, 3.2, 11.5] {
+            let inertial = rotating_to_inertial(t, 1.0, &state);
+            let back = inertial_to_rotating(t, 1.0, &inertial);
+            assert!((back - state).norm() < 1e-12);
+        }
+
+        // A body fixed in the rotating frame moves on a circle in the inertial one
+        let fixed = Vector6::new(1.0, 0.0, 0.0, 0.0, 0.0, 0.0);
+        let inertial = rotating_to_inertial(std::f64::consts::FRAC_PI_2, 1.0, &fixed);
+        let expected = Vector6::new(0.0, 1.0, 0.0, -1.0, 0.0, 0.0);
+        assert!((inertial - expected).norm() < 1e-12);
+    }
+}
@@ -0,0 +1,135 @@
+/// Batched Dynamics (systems/batch)
+///
+/// Interface for evaluating the dynamics of many states in one call, so the heavy
+/// evaluations of large ensembles (or of very large systems) can be handed to a
+/// device kernel (wgpu, cust, ...) while the integrator keeps the control flow on
+/// the CPU. States are passed as one flat buffer holding the members one after
+/// another (`dim` values each), the layout a kernel uploads directly, and the
+/// derivatives are written into a buffer of the same layout.
+///
+/// The batched steppers (see `RKStepper::step_batch`) make exactly one call per
+/// stage for the whole ensemble. Any `OdeSystem` can be used member by member on
+/// the CPU through `PerMember`, e.g. as a reference for a kernel.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DefaultAllocator, Dim, DimName, OVector};
+
+// local imports
+use super::OdeSystem;
+
+// Standard library imports
+use std::marker::PhantomData;
+
+// === End Imports ===
+
+pub trait BatchSystem {
+    // Number of values in the state of each member
+    fn dim(&self) -> usize;
+
+    // Evaluates the dynamics of every member at time t. `states` holds the members
+    // one after another and `out` (of the same length) receives their derivatives
+    fn dynamics_batch(&self, t: f64, states: &[f64], out: &mut [f64]);
+}
+
+// Evaluates an `OdeSystem` member by member
+#[derive(Debug, Clone)]
+pub struct PerMember<N: Dim + DimName, S: OdeSystem<N>>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    // Dynamics of a single member
+    pub system: S,
+    // Dimension of the state of each member
+    dim: PhantomData<N>,
+}
+
+impl<N: Dim + DimName, S: OdeSystem<N>> PerMember<N, S>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    pub fn new(system: S) -> Self {
+        PerMember {
+            system,
+            dim: PhantomData,
+        }
+    }
+}
+
+impl<N: Dim + DimName, S: OdeSystem<N>> BatchSystem for PerMember<N, S>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    fn dim(&self) -> usize {
+        N::dim()
+    }
+
+    fn dynamics_batch(&self, t: f64, states: &[f64], out: &mut [f64]) {
+        for (y, dy) in states.chunks(N::dim()).zip(out.chunks_mut(N::dim())) {
+            let y = OVector::<f64, N>::from_column_slice(y);
+            dy.copy_from_slice(self.system.dynamics(t, &y).as_slice());
+        }
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runge_kutta::common::StepSimple;
+    use crate::runge_kutta::rk_simp::RK4;
+    use na::Vector2;
+    use std::cell::Cell;
+
+    fn oscillator(_t: f64, y: &Vector2<f64>) -> Vector2<f64> {
+        Vector2::new(y[1], -y[0])
+    }
+
+    // Counts its (batched) evaluations
+    struct Counted {
+        calls: Cell<usize>,
+    }
+
+    impl BatchSystem for Counted {
+        fn dim(&self) -> usize {
+            2
+        }
+
+        fn dynamics_batch(&self, t: f64, states: &[f64], out: &mut [f64]) {
+            self.calls.set(self.calls.get() + 1);
+            PerMember::new(oscillator).dynamics_batch(t, states, out);
+        }
+    }
+
+    #[test]
+    fn test_batch_integration() {
+        let members: Vec<Vector2<f64>> = (0..100)
+            .map(|i| Vector2::new(i as f64 / 10.0, 1.0 - i as f64 / 50.0))
+            .collect();
+        let flat: Vec<f64> = members.iter().flat_map(|y| y.iter().copied()).collect();
+
+        let system = Counted {
+            calls: Cell::new(0),
+        };
+        let (times, history) = RK4.integrate_batch(&system, 0.0, flat, 2.0, 0.1).unwrap();
+        assert_eq!(times.len(), 21);
+        assert_eq!(*times.last().unwrap(), 2.0);
+        // one call per stage for the whole ensemble
+        assert_eq!(system.calls.get(), 4 * 20);
+
+        // same as stepping the members one at a time
+        for (m, y_0) in members.iter().enumerate() {
+            let mut y = *y_0;
+            for i in 0..20 {
+                y = RK4.step(&oscillator, i as f64 * 0.1, &y, 0.1).value;
+            }
+            let batched = &history[20][2 * m..2 * m + 2];
+            assert!((y[0] - batched[0]).abs() < 1e-14);
+            assert!((y[1] - batched[1]).abs() < 1e-14);
+        }
+
+        assert!(RK4.step_batch(&system, 0.0, &[1.0, 2.0, 3.0], 0.1).is_err());
+    }
+}
@@ -0,0 +1,331 @@
+/// Controlled Systems (systems/control)
+///
+/// Dynamics driven by a control input, y' = f(t, y, u(t)), for guidance and control
+/// simulations. The dynamics implement `ControlledSystem` and receive the control
+/// from a separate `ControlProvider`, so the same plant model can be flown with
+/// different control laws. `Controlled` pairs the two into an `OdeSystem` for the
+/// integrators.
+///
+/// Provided control providers:
+/// - `PiecewiseConstant`: values held between switch times (e.g. bang-bang thrust)
+/// - `CubicSpline`: natural cubic spline through tabulated values
+/// - any closure of the form `Fn(f64) -> OVector<f64, M>`
+///
+/// Providers report the times at which the control is discontinuous through
+/// `switch_times`. These become breakpoints of the controlled system, so the
+/// integrators land their steps exactly on every switch.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DefaultAllocator, Dim, DimName, OVector};
+
+// local imports
+use super::OdeSystem;
+
+// Standard library imports
+use std::marker::PhantomData;
+
+// === End Imports ===
+
+pub trait ControlledSystem<N: Dim + DimName, M: Dim + DimName>
+where
+    DefaultAllocator: Allocator<N> + Allocator<M>,
+{
+    // Evaluates the dynamics f(t, y, u) for the control input u
+    fn controlled_dynamics(
+        &self,
+        t: f64,
+        y: &OVector<f64, N>,
+        u: &OVector<f64, M>,
+    ) -> OVector<f64, N>;
+}
+
+impl<N: Dim + DimName, M: Dim + DimName, F> ControlledSystem<N, M> for F
+where
+    F: Fn(f64, &OVector<f64, N>, &OVector<f64, M>) -> OVector<f64, N>,
+    DefaultAllocator: Allocator<N> + Allocator<M>,
+{
+    fn controlled_dynamics(
+        &self,
+        t: f64,
+        y: &OVector<f64, N>,
+        u: &OVector<f64, M>,
+    ) -> OVector<f64, N> {
+        self(t, y, u)
+    }
+}
+
+pub trait ControlProvider<M: Dim + DimName>
+where
+    DefaultAllocator: Allocator<M>,
+{
+    // Control input u(t)
+    fn control(&self, t: f64) -> OVector<f64, M>;
+
+    // Times at which the control is discontinuous
+    fn switch_times(&self) -> Vec<f64> {
+        Vec::new()
+    }
+}
+
+impl<M: Dim + DimName, F> ControlProvider<M> for F
+where
+    F: Fn(f64) -> OVector<f64, M>,
+    DefaultAllocator: Allocator<M>,
+{
+    fn control(&self, t: f64) -> OVector<f64, M> {
+        self(t)
+    }
+}
+
+// Checks that knot times are strictly increasing and match the number of values
+pub(crate) fn check_knots(
+    times: &[f64],
+    n_values: usize,
+    min_len: usize,
+) -> Result<(), &'static str> {
+    if times.len() != n_values {
+        return Err("[CONTROL] Number of times and values differ");
+    }
+    if times.len() < min_len {
+        return Err("[CONTROL] Not enough knots");
+    }
+    if times.windows(2).any(|pair| pair[1] <= pair[0]) {
+        return Err("[CONTROL] Knot times must be strictly increasing");
+    }
+    Ok(())
+}
+
+// === Piecewise Constant ===
+#[derive(Debug, Clone, PartialEq)]
+pub struct PiecewiseConstant<M: Dim + DimName>
+where
+    DefaultAllocator: Allocator<M>,
+{
+    // Times at which each value starts to apply (increasing)
+    times: Vec<f64>,
+    // Control values. values[k] holds from times[k] until times[k + 1]
+    values: Vec<OVector<f64, M>>,
+}
+
+impl<M: Dim + DimName> PiecewiseConstant<M>
+where
+    DefaultAllocator: Allocator<M>,
+{
+    // The first value also applies before times[0] and the last one after the last
+    // switch
+    pub fn new(times: Vec<f64>, values: Vec<OVector<f64, M>>) -> Result<Self, &'static str> {
+        check_knots(&times, values.len(), 1)?;
+        Ok(PiecewiseConstant { times, values })
+    }
+}
+
+impl<M: Dim + DimName> ControlProvider<M> for PiecewiseConstant<M>
+where
+    DefaultAllocator: Allocator<M>,
+{
+    fn control(&self, t: f64) -> OVector<f64, M> {
+        let k = self.times.partition_point(|t_k| *t_k <= t);
+        self.values[k.saturating_sub(1)].clone()
+    }
+
+    fn switch_times(&self) -> Vec<f64> {
+        self.times[1..].to_vec()
+    }
+}
+
+// === Cubic Spline ===
+#[derive(Debug, Clone, PartialEq)]
+pub struct CubicSpline<M: Dim + DimName>
+where
+    DefaultAllocator: Allocator<M>,
+{
+    // Knot times (increasing)
+    times: Vec<f64>,
+    // Control values at the knots
+    values: Vec<OVector<f64, M>>,
+    // Second derivative of the spline at the knots
+    second_derivs: Vec<OVector<f64, M>>,
+}
+
+impl<M: Dim + DimName> CubicSpline<M>
+where
+    DefaultAllocator: Allocator<M>,
+{
+    // Natural cubic spline (zero second derivative at both ends). The end values are
+    // held outside of the knot range
+    pub fn new(times: Vec<f64>, values: Vec<OVector<f64, M>>) -> Result<Self, &'static str> {
+        check_knots(&times, values.len(), 2)?;
+        let n = times.len();
+        let h: Vec<f64> = times.windows(2).map(|pair| pair[1] - pair[0]).collect();
+
+        // tri-diagonal system for the interior second derivatives (thomas algorithm)
+        let zero = OVector::<f64, M>::zeros();
+        let mut diag = vec![1.0; n];
+        let mut rhs = vec![zero.clone(); n];
+        for i in 1..n - 1 {
+            diag[i] = 2.0 * (h[i - 1] + h[i]);
+            rhs[i] = 6.0
+                * ((&values[i + 1] - &values[i]) / h[i] - (&values[i] - &values[i - 1]) / h[i - 1]);
+        }
+        for i in 2..n - 1 {
+            let factor = h[i - 1] / diag[i - 1];
+            diag[i] -= factor * h[i - 1];
+            let prev = rhs[i - 1].clone();
+            rhs[i] -= factor * prev;
+        }
+        let mut second_derivs = vec![zero; n];
+        for i in (1..n - 1).rev() {
+            let upper = if i + 1 < n - 1 {
+                h[i] * &second_derivs[i + 1]
+            } else {
+                OVector::<f64, M>::zeros()
+            };
+            second_derivs[i] = (&rhs[i] - upper) / diag[i];
+        }
+
+        Ok(CubicSpline {
+            times,
+            values,
+            second_derivs,
+        })
+    }
+}
+
+impl<M: Dim + DimName> ControlProvider<M> for CubicSpline<M>
+where
+    DefaultAllocator: Allocator<M>,
+{
+    fn control(&self, t: f64) -> OVector<f64, M> {
+        let last = self.times.len() - 1;
+        if t <= self.times[0] {
+            return self.values[0].clone();
+        } else if t >= self.times[last] {
+            return self.values[last].clone();
+        }
+
+        let i = self.times.partition_point(|t_k| *t_k <= t) - 1;
+        let h = self.times[i + 1] - self.times[i];
+        let a = (self.times[i + 1] - t) / h;
+        let b = (t - self.times[i]) / h;
+        a * &self.values[i]
+            + b * &self.values[i + 1]
+            + ((a.powi(3) - a) * &self.second_derivs[i]
+                + (b.powi(3) - b) * &self.second_derivs[i + 1])
+                * (h * h / 6.0)
+    }
+}
+
+// === Controlled System ===
+// Dynamics paired with the control provider driving them
+#[derive(Debug, Clone)]
+pub struct Controlled<S, C, M> {
+    // Controlled dynamics f(t, y, u)
+    pub system: S,
+    // Provider of the control input u(t)
+    pub control: C,
+    // Dimension of the control input
+    control_dim: PhantomData<M>,
+}
+
+impl<S, C, M> Controlled<S, C, M> {
+    pub fn new(system: S, control: C) -> Self {
+        Controlled {
+            system,
+            control,
+            control_dim: PhantomData,
+        }
+    }
+}
+
+impl<N: Dim + DimName, M: Dim + DimName, S, C> OdeSystem<N> for Controlled<S, C, M>
+where
+    S: ControlledSystem<N, M>,
+    C: ControlProvider<M>,
+    DefaultAllocator: Allocator<N> + Allocator<M>,
+{
+    fn dynamics(&self, t: f64, y: &OVector<f64, N>) -> OVector<f64, N> {
+        self.system
+            .controlled_dynamics(t, y, &self.control.control(t))
+    }
+
+    fn breakpoints(&self) -> Vec<f64> {
+        self.control.switch_times()
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ridc::base::RIDCIntegratorFixed;
+    use crate::ridc::common::IntegOptionsParallel;
+    use crate::runge_kutta::common::IntegOptions;
+    use crate::runge_kutta::fixed::FixedStep;
+    use crate::runge_kutta::rk_simp::RK4;
+    use na::{Vector1, Vector2, U1};
+
+    #[test]
+    fn test_bang_bang_double_integrator() {
+        // x'' = u with full thrust, full braking, then coasting
+        let plant = |_t: f64, y: &Vector2<f64>, u: &Vector1<f64>| Vector2::new(y[1], u[0]);
+        let thrust = PiecewiseConstant::new(
+            vec![0.0, 1.0, 2.0],
+            vec![Vector1::new(1.0), Vector1::new(-1.0), Vector1::new(0.0)],
+        )
+        .unwrap();
+        assert_eq!(thrust.control(1.5), Vector1::new(-1.0));
+        let system = Controlled::new(plant, thrust);
+        assert_eq!(system.breakpoints(), vec![1.0, 2.0]);
+
+        // the trajectory is piecewise quadratic, so RK4 is exact once its steps land
+        // on the switches
+        let truth = Vector2::new(1.0, 0.0);
+        let y_0 = Vector2::new(0.0, 0.0);
+        let serial = RK4
+            .integrate(system.clone(), 0.0, y_0, 3.0, 0.3, IntegOptions::default())
+            .unwrap();
+        assert!(serial.times.contains(&1.0) && serial.times.contains(&2.0));
+        assert!((serial.last_y() - truth).norm() < 1e-12);
+
+        let parallel = RK4
+            .parallel_integrator(system, 0.0, &y_0, 3.0, 0.3, IntegOptionsParallel::default())
+            .unwrap();
+        assert_eq!(parallel.restarts, vec![1.0, 2.0]);
+        assert!((parallel.last_y() - truth).norm() < 1e-12);
+    }
+
+    #[test]
+    fn test_cubic_spline() {
+        let times: Vec<f64> = (0..=20).map(|i| 0.1 * i as f64).collect();
+        let values = times.iter().map(|t| Vector1::new(t.sin())).collect();
+        let spline = CubicSpline::<U1>::new(times.clone(), values).unwrap();
+
+        for t in &times {
+            assert!((spline.control(*t)[0] - t.sin()).abs() < 1e-14);
+        }
+        // the natural end conditions only cost accuracy near the ends
+        for i in 0..100 {
+            let t = 0.5 + 0.01 * i as f64;
+            assert!((spline.control(t)[0] - t.sin()).abs() < 1e-5);
+        }
+        assert!(spline.switch_times().is_empty());
+
+        // linear data is reproduced exactly
+        let line = CubicSpline::<U1>::new(
+            vec![0.0, 0.5, 2.0, 3.0],
+            vec![
+                Vector1::new(1.0),
+                Vector1::new(2.0),
+                Vector1::new(5.0),
+                Vector1::new(7.0),
+            ],
+        )
+        .unwrap();
+        assert!((line.control(1.3)[0] - 3.6).abs() < 1e-12);
+
+        assert!(CubicSpline::<U1>::new(vec![0.0, 0.0], vec![Vector1::zeros(); 2]).is_err());
+    }
+}
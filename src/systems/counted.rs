@@ -0,0 +1,186 @@
+/// Counted Dynamics (systems/counted)
+///
+/// `CountedSystem` wraps any `OdeSystem` and counts the evaluations of its dynamics
+/// along with the wall time spent in them, so the cost of a model can be told apart
+/// from the overhead of the integrator without an external profiler. The counters
+/// are shared by every clone of the wrapper, so the evaluations of all the RIDC
+/// correction threads (which each work on a clone) are added up.
+///
+/// Hand written jacobians can be counted the same way by wrapping them with
+/// `counted_jacobian`.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DefaultAllocator, Dim, DimName, OMatrix, OVector};
+
+// local imports
+use super::fallible::EvalFailure;
+use super::OdeSystem;
+
+// Standard library imports
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// === End Imports ===
+
+// Number of calls to a function and the total time spent in them
+#[derive(Debug, Default)]
+struct CallCounter {
+    // Number of calls
+    calls: AtomicU64,
+    // Total wall time of the calls in nanoseconds
+    nanos: AtomicU64,
+}
+
+impl CallCounter {
+    fn time<T>(&self, call: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let out = call();
+        self.nanos
+            .fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        out
+    }
+
+    fn calls(&self) -> u64 {
+        self.calls.load(Ordering::Relaxed)
+    }
+
+    fn time_spent(&self) -> Duration {
+        Duration::from_nanos(self.nanos.load(Ordering::Relaxed))
+    }
+
+    fn reset(&self) {
+        self.calls.store(0, Ordering::Relaxed);
+        self.nanos.store(0, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CountedSystem<S> {
+    // Wrapped dynamics
+    system: S,
+    // Counter of dynamics evaluations, shared by the clones
+    rhs: Arc<CallCounter>,
+    // Counter of jacobian evaluations, shared by the clones
+    jacobian: Arc<CallCounter>,
+}
+
+impl<S> CountedSystem<S> {
+    pub fn new(system: S) -> Self {
+        CountedSystem {
+            system,
+            rhs: Arc::new(CallCounter::default()),
+            jacobian: Arc::new(CallCounter::default()),
+        }
+    }
+
+    // Wrapped dynamics
+    pub fn inner(&self) -> &S {
+        &self.system
+    }
+
+    // Number of evaluations of the dynamics so far
+    pub fn rhs_calls(&self) -> u64 {
+        self.rhs.calls()
+    }
+
+    // Total wall time spent evaluating the dynamics (summed over threads)
+    pub fn rhs_time(&self) -> Duration {
+        self.rhs.time_spent()
+    }
+
+    // Number of evaluations of jacobians wrapped by `counted_jacobian` so far
+    pub fn jacobian_calls(&self) -> u64 {
+        self.jacobian.calls()
+    }
+
+    // Total wall time spent evaluating jacobians wrapped by `counted_jacobian`
+    pub fn jacobian_time(&self) -> Duration {
+        self.jacobian.time_spent()
+    }
+
+    // Zeros the counters (of every clone)
+    pub fn reset(&self) {
+        self.rhs.reset();
+        self.jacobian.reset();
+    }
+
+    // Wraps a jacobian of the dynamics so its evaluations are counted as well
+    pub fn counted_jacobian<N: Dim + DimName, J>(
+        &self,
+        jacobian: J,
+    ) -> impl Fn(f64, &OVector<f64, N>) -> OMatrix<f64, N, N>
+    where
+        J: Fn(f64, &OVector<f64, N>) -> OMatrix<f64, N, N>,
+        DefaultAllocator: Allocator<N> + Allocator<N, N>,
+    {
+        let counter = self.jacobian.clone();
+        move |t: f64, y: &OVector<f64, N>| counter.time(|| jacobian(t, y))
+    }
+}
+
+impl<N: Dim + DimName, S: OdeSystem<N>> OdeSystem<N> for CountedSystem<S>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    fn dynamics(&self, t: f64, y: &OVector<f64, N>) -> OVector<f64, N> {
+        self.rhs.time(|| self.system.dynamics(t, y))
+    }
+
+    fn breakpoints(&self) -> Vec<f64> {
+        self.system.breakpoints()
+    }
+
+    fn take_failure(&self) -> Option<EvalFailure> {
+        self.system.take_failure()
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ridc::base::RIDCIntegratorFixed;
+    use crate::ridc::common::IntegOptionsParallel;
+    use crate::ridc::corrector::ExplicitEuler;
+    use crate::runge_kutta::common::IntegOptions;
+    use crate::runge_kutta::fixed::FixedStep;
+    use crate::runge_kutta::rk_simp::RK4;
+    use crate::utils::finite_diff::check_jacobian;
+    use na::{Matrix1, Vector1};
+
+    #[test]
+    fn test_counted_system() {
+        let decay = CountedSystem::new(|_t: f64, y: &Vector1<f64>| -y);
+        RK4.integrate(
+            decay.clone(),
+            0.0,
+            Vector1::new(1.0),
+            1.0,
+            0.1,
+            IntegOptions::default(),
+        )
+        .unwrap();
+        // four stages and the dynamics at the new state per step
+        assert_eq!(decay.rhs_calls(), 50);
+        assert!(decay.rhs_time() > Duration::ZERO);
+
+        // the correction threads count into the same counters
+        decay.reset();
+        let options = IntegOptionsParallel {
+            corrector: Some(Arc::new(ExplicitEuler)),
+            ..IntegOptionsParallel::default()
+        };
+        RK4.parallel_integrator(decay.clone(), 0.0, &Vector1::new(1.0), 1.0, 0.1, options)
+            .unwrap();
+        assert!(decay.rhs_calls() > 50);
+
+        let jac = decay.counted_jacobian(|_t: f64, _y: &Vector1<f64>| Matrix1::new(-1.0));
+        assert!(check_jacobian(decay.inner(), &jac, 0.0, &Vector1::new(1.0), 1e-8).is_empty());
+        assert_eq!(decay.jacobian_calls(), 1);
+    }
+}
@@ -0,0 +1,173 @@
+/// Absolute Epochs (systems/epoch)
+///
+/// Only available with the `hifitime` feature.
+///
+/// The integrators work in f64 seconds. Astrodynamics problems are posed in
+/// absolute time: an orbit determination arc from one UTC date to another, an
+/// ephemeris evaluated at a TDB epoch. `SimClock` ties the two together: it fixes
+/// a reference `hifitime::Epoch` (t = 0) and the time scale in which seconds are
+/// counted, and converts epochs to the f64 times the integrators take and the times
+/// of a result back to epochs. Seconds relative to a nearby reference keep the
+/// integrators' time variable small, so it resolves well below a nanosecond over
+/// arcs of days.
+///
+/// The time scale must be uniform: TAI, TT, TDB (or SPICE ET) or a GNSS time. UTC
+/// is not, since a leap second is an extra SI second the UTC count does not have;
+/// epochs given or reported in UTC are still converted exactly, across leap
+/// seconds, by counting in one of the uniform scales. TDB differs from TT by
+/// periodic terms below two milliseconds, so the choice matters for high precision
+/// dynamics posed in one of them (planetary ephemerides are in TDB).
+///
+/// `EpochSystem` wraps dynamics of the form `Fn(Epoch, &OVector) -> OVector`,
+/// which need the absolute time (e.g. to look up the sun or moon position), into an
+/// `OdeSystem` for every integrator.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use hifitime::{Duration, Epoch, TimeScale};
+use na::allocator::Allocator;
+use na::{DefaultAllocator, Dim, DimName, OVector};
+
+// local imports
+use super::OdeSystem;
+use crate::runge_kutta::common::IntegResult;
+
+// === End Imports ===
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SimClock {
+    // Epoch of t = 0
+    reference: Epoch,
+    // Time scale in which the seconds are counted
+    scale: TimeScale,
+}
+
+impl SimClock {
+    pub fn new(reference: Epoch, scale: TimeScale) -> Result<Self, &'static str> {
+        if scale == TimeScale::UTC {
+            return Err("[CLOCK] UTC is not uniform (leap seconds): count in TAI, TT or TDB");
+        }
+        Ok(SimClock { reference, scale })
+    }
+
+    pub fn reference(&self) -> Epoch {
+        self.reference
+    }
+
+    pub fn scale(&self) -> TimeScale {
+        self.scale
+    }
+
+    // Integrator time of an epoch: seconds since the reference in the clock's scale
+    pub fn seconds(&self, epoch: Epoch) -> f64 {
+        (epoch.to_duration_in_time_scale(self.scale)
+            - self.reference.to_duration_in_time_scale(self.scale))
+        .to_seconds()
+    }
+
+    // Epoch of an integrator time, in the clock's scale
+    pub fn epoch(&self, t: f64) -> Epoch {
+        Epoch::from_duration(
+            self.reference.to_duration_in_time_scale(self.scale) + Duration::from_seconds(t),
+            self.scale,
+        )
+    }
+
+    // Seconds of the clock between two epochs (the span to integrate over)
+    pub fn span(&self, from: Epoch, to: Epoch) -> f64 {
+        self.seconds(to) - self.seconds(from)
+    }
+
+    // Epochs of the solutions of an integration run on this clock
+    pub fn epochs<N: Dim + DimName>(&self, results: &IntegResult<N>) -> Vec<Epoch>
+    where
+        DefaultAllocator: Allocator<N>,
+    {
+        results.times.iter().map(|t| self.epoch(*t)).collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EpochSystem<F> {
+    // Clock converting the integrator time to epochs
+    clock: SimClock,
+    // Dynamics in absolute time
+    fxn: F,
+}
+
+impl<F> EpochSystem<F> {
+    pub fn new(clock: SimClock, fxn: F) -> Self {
+        EpochSystem { clock, fxn }
+    }
+
+    pub fn clock(&self) -> &SimClock {
+        &self.clock
+    }
+}
+
+impl<N: Dim + DimName, F> OdeSystem<N> for EpochSystem<F>
+where
+    F: Fn(Epoch, &OVector<f64, N>) -> OVector<f64, N>,
+    DefaultAllocator: Allocator<N>,
+{
+    fn dynamics(&self, t: f64, y: &OVector<f64, N>) -> OVector<f64, N> {
+        (self.fxn)(self.clock.epoch(t), y)
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runge_kutta::adaptive::AdaptiveStep;
+    use crate::runge_kutta::common::IntegOptions;
+    use crate::runge_kutta::rk_embed::DOPRI78;
+    use na::Vector1;
+
+    #[test]
+    fn test_sim_clock() {
+        // two minutes of UTC across the leap second at the end of 2016 last 121 SI
+        // seconds
+        let start = Epoch::from_gregorian_utc_hms(2016, 12, 31, 23, 59, 0);
+        let end = Epoch::from_gregorian_utc_hms(2017, 1, 1, 0, 1, 0);
+        let clock = SimClock::new(start, TimeScale::TAI).unwrap();
+        assert_eq!(clock.span(start, end), 121.0);
+        assert!(SimClock::new(start, TimeScale::UTC).is_err());
+
+        // the epoch passed to the dynamics is that of the integrator time: y' = 1
+        // counts the seconds, and the last epoch reported is the requested end
+        let seen = std::sync::Mutex::new(Vec::new());
+        let system = EpochSystem::new(clock, |epoch: Epoch, _y: &Vector1<f64>| {
+            seen.lock().unwrap().push(epoch);
+            Vector1::new(1.0)
+        });
+        let ans = DOPRI78
+            .integrate(
+                system,
+                clock.seconds(start),
+                Vector1::new(0.0),
+                clock.span(start, end),
+                IntegOptions::default(),
+            )
+            .unwrap();
+        assert!((ans.last_y()[0] - 121.0).abs() < 1e-9);
+        let epochs = clock.epochs(&ans);
+        assert_eq!(epochs[0], start);
+        assert!((epochs[epochs.len() - 1] - end).abs() < Duration::from_seconds(1e-9));
+        let seen = seen.lock().unwrap();
+        assert!(seen.iter().all(|e| *e >= start && *e <= end));
+
+        // dynamical time: TDB and TT seconds differ by periodic terms, and epochs
+        // survive the round trip through integrator time
+        let j2000 = Epoch::from_gregorian_utc_hms(2000, 1, 1, 12, 0, 0);
+        let tdb = SimClock::new(j2000, TimeScale::TDB).unwrap();
+        let tt = SimClock::new(j2000, TimeScale::TT).unwrap();
+        let later = j2000 + Duration::from_seconds(86_400.0 * 91.0);
+        let drift = tdb.seconds(later) - tt.seconds(later);
+        println!("TDB - TT AFTER 91 DAYS {:e} s", drift);
+        assert!(drift.abs() > 1e-4 && drift.abs() < 4e-3);
+        let t = 12_345.678_9;
+        assert!((tdb.seconds(tdb.epoch(t)) - t).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,521 @@
+/// Expression Dynamics (systems/expr)
+///
+/// Dynamics written as text instead of a Rust closure, for simple systems in tests
+/// and in the `ridc` binary (built with the `expr` feature):
+///
+/// dy0 = y1; dy1 = -sin(y0) - c * y1
+///
+/// Each equation `dy<i> = <expression>` gives the derivative of state component i;
+/// equations are separated by `;` or new lines and every component of the state
+/// needs exactly one. Expressions are built from
+/// - numbers (`2`, `0.5`, `1e-3`), the time `t`, the states `y0`, `y1`, ... and
+///   named parameters given with the equations (`c` above)
+/// - the constants `pi` and `e`
+/// - `+ - * /`, `^` for powers (binding tighter than a leading minus, so `-y0^2` is
+///   -(y0^2)) and parentheses
+/// - the functions `sin cos tan asin acos atan sinh cosh tanh exp ln log10 sqrt
+///   abs` of one argument and `atan2 pow min max` of two
+///
+/// The text is parsed once into a syntax tree, which the dynamics walk at every
+/// evaluation: far slower than compiled dynamics, but fine for small systems.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DefaultAllocator, Dim, DimName, OVector};
+
+// local imports
+use super::OdeSystem;
+
+// Standard library imports
+use std::f64::consts::{E, PI};
+use std::marker::PhantomData;
+
+// === End Imports ===
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Func {
+    Sin,
+    Cos,
+    Tan,
+    Asin,
+    Acos,
+    Atan,
+    Sinh,
+    Cosh,
+    Tanh,
+    Exp,
+    Ln,
+    Log10,
+    Sqrt,
+    Abs,
+    Atan2,
+    Pow,
+    Min,
+    Max,
+}
+
+impl Func {
+    fn named(name: &str) -> Option<Func> {
+        Some(match name {
+            "sin" => Func::Sin,
+            "cos" => Func::Cos,
+            "tan" => Func::Tan,
+            "asin" => Func::Asin,
+            "acos" => Func::Acos,
+            "atan" => Func::Atan,
+            "sinh" => Func::Sinh,
+            "cosh" => Func::Cosh,
+            "tanh" => Func::Tanh,
+            "exp" => Func::Exp,
+            "ln" => Func::Ln,
+            "log10" => Func::Log10,
+            "sqrt" => Func::Sqrt,
+            "abs" => Func::Abs,
+            "atan2" => Func::Atan2,
+            "pow" => Func::Pow,
+            "min" => Func::Min,
+            "max" => Func::Max,
+            _ => return None,
+        })
+    }
+
+    fn arity(&self) -> usize {
+        match self {
+            Func::Atan2 | Func::Pow | Func::Min | Func::Max => 2,
+            _ => 1,
+        }
+    }
+
+    fn apply(&self, args: &[f64]) -> f64 {
+        let x = args[0];
+        match self {
+            Func::Sin => x.sin(),
+            Func::Cos => x.cos(),
+            Func::Tan => x.tan(),
+            Func::Asin => x.asin(),
+            Func::Acos => x.acos(),
+            Func::Atan => x.atan(),
+            Func::Sinh => x.sinh(),
+            Func::Cosh => x.cosh(),
+            Func::Tanh => x.tanh(),
+            Func::Exp => x.exp(),
+            Func::Ln => x.ln(),
+            Func::Log10 => x.log10(),
+            Func::Sqrt => x.sqrt(),
+            Func::Abs => x.abs(),
+            Func::Atan2 => x.atan2(args[1]),
+            Func::Pow => x.powf(args[1]),
+            Func::Min => x.min(args[1]),
+            Func::Max => x.max(args[1]),
+        }
+    }
+}
+
+// Syntax tree of an expression
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Num(f64),
+    Time,
+    State(usize),
+    Param(usize),
+    Neg(Box<Expr>),
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    Div(Box<Expr>, Box<Expr>),
+    Pow(Box<Expr>, Box<Expr>),
+    Call(Func, Vec<Expr>),
+}
+
+impl Expr {
+    fn eval(&self, t: f64, y: &[f64], params: &[f64]) -> f64 {
+        match self {
+            Expr::Num(val) => *val,
+            Expr::Time => t,
+            Expr::State(i) => y[*i],
+            Expr::Param(i) => params[*i],
+            Expr::Neg(a) => -a.eval(t, y, params),
+            Expr::Add(a, b) => a.eval(t, y, params) + b.eval(t, y, params),
+            Expr::Sub(a, b) => a.eval(t, y, params) - b.eval(t, y, params),
+            Expr::Mul(a, b) => a.eval(t, y, params) * b.eval(t, y, params),
+            Expr::Div(a, b) => a.eval(t, y, params) / b.eval(t, y, params),
+            Expr::Pow(a, b) => a.eval(t, y, params).powf(b.eval(t, y, params)),
+            Expr::Call(func, args) => {
+                let vals: Vec<f64> = args.iter().map(|a| a.eval(t, y, params)).collect();
+                func.apply(&vals)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Num(f64),
+    Ident(String),
+    Op(char),
+    // end of an equation (`;` or a new line)
+    End,
+}
+
+// Tokens of the text, ending with an `End`
+fn tokenize(src: &str) -> Result<Vec<Token>, &'static str> {
+    let chars: Vec<char> = src.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        if c == ';' || c == '\n' {
+            tokens.push(Token::End);
+            i += 1;
+        } else if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() || c == '.' {
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            // exponent, only when followed by digits
+            if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                let mut j = i + 1;
+                if j < chars.len() && (chars[j] == '+' || chars[j] == '-') {
+                    j += 1;
+                }
+                if j < chars.len() && chars[j].is_ascii_digit() {
+                    i = j;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+            let text: String = chars[start..i].iter().collect();
+            let val = text.parse().map_err(|_| "[EXPR] Invalid number")?;
+            tokens.push(Token::Num(val));
+        } else if c.is_alphabetic() || c == '_' {
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Ident(chars[start..i].iter().collect()));
+        } else if "+-*/^(),=".contains(c) {
+            tokens.push(Token::Op(c));
+            i += 1;
+        } else {
+            return Err("[EXPR] Unexpected character");
+        }
+    }
+    tokens.push(Token::End);
+    Ok(tokens)
+}
+
+// Recursive descent parser of one equation
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    // Names of the parameters
+    params: &'a [String],
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn expect(&mut self, op: char) -> Result<(), &'static str> {
+        match self.peek() {
+            Some(Token::Op(c)) if *c == op => {
+                self.pos += 1;
+                Ok(())
+            }
+            _ => Err("[EXPR] Unbalanced parentheses"),
+        }
+    }
+
+    // expr := term (('+' | '-') term)*
+    fn expr(&mut self) -> Result<Expr, &'static str> {
+        let mut lhs = self.term()?;
+        while let Some(Token::Op(c @ ('+' | '-'))) = self.peek() {
+            let c = *c;
+            self.pos += 1;
+            let rhs = self.term()?;
+            lhs = match c {
+                '+' => Expr::Add(Box::new(lhs), Box::new(rhs)),
+                _ => Expr::Sub(Box::new(lhs), Box::new(rhs)),
+            };
+        }
+        Ok(lhs)
+    }
+
+    // term := unary (('*' | '/') unary)*
+    fn term(&mut self) -> Result<Expr, &'static str> {
+        let mut lhs = self.unary()?;
+        while let Some(Token::Op(c @ ('*' | '/'))) = self.peek() {
+            let c = *c;
+            self.pos += 1;
+            let rhs = self.unary()?;
+            lhs = match c {
+                '*' => Expr::Mul(Box::new(lhs), Box::new(rhs)),
+                _ => Expr::Div(Box::new(lhs), Box::new(rhs)),
+            };
+        }
+        Ok(lhs)
+    }
+
+    // unary := ('-' | '+') unary | power
+    fn unary(&mut self) -> Result<Expr, &'static str> {
+        match self.peek() {
+            Some(Token::Op('-')) => {
+                self.pos += 1;
+                Ok(Expr::Neg(Box::new(self.unary()?)))
+            }
+            Some(Token::Op('+')) => {
+                self.pos += 1;
+                self.unary()
+            }
+            _ => self.power(),
+        }
+    }
+
+    // power := atom ('^' unary)?, right associative
+    fn power(&mut self) -> Result<Expr, &'static str> {
+        let base = self.atom()?;
+        match self.peek() {
+            Some(Token::Op('^')) => {
+                self.pos += 1;
+                Ok(Expr::Pow(Box::new(base), Box::new(self.unary()?)))
+            }
+            _ => Ok(base),
+        }
+    }
+
+    // atom := number | name | function '(' expr (',' expr)* ')' | '(' expr ')'
+    fn atom(&mut self) -> Result<Expr, &'static str> {
+        let token = self.peek().cloned();
+        self.pos += 1;
+        match token {
+            Some(Token::Num(val)) => Ok(Expr::Num(val)),
+            Some(Token::Op('(')) => {
+                let inner = self.expr()?;
+                self.expect(')')?;
+                Ok(inner)
+            }
+            Some(Token::Ident(name)) => {
+                if let Some(Token::Op('(')) = self.peek() {
+                    let func = Func::named(&name).ok_or("[EXPR] Unknown function")?;
+                    self.pos += 1;
+                    let mut args = vec![self.expr()?];
+                    while let Some(Token::Op(',')) = self.peek() {
+                        self.pos += 1;
+                        args.push(self.expr()?);
+                    }
+                    self.expect(')')?;
+                    if args.len() != func.arity() {
+                        return Err("[EXPR] Wrong number of arguments to a function");
+                    }
+                    return Ok(Expr::Call(func, args));
+                }
+                self.name(&name)
+            }
+            _ => Err("[EXPR] Expected a value"),
+        }
+    }
+
+    // Variable, parameter or constant
+    fn name(&self, name: &str) -> Result<Expr, &'static str> {
+        if let Some(i) = self.params.iter().position(|p| p == name) {
+            return Ok(Expr::Param(i));
+        }
+        match name {
+            "t" => Ok(Expr::Time),
+            "pi" => Ok(Expr::Num(PI)),
+            "e" => Ok(Expr::Num(E)),
+            _ => match state_index(name, "y") {
+                Some(i) => Ok(Expr::State(i)),
+                None => Err("[EXPR] Unknown name"),
+            },
+        }
+    }
+}
+
+// Index i of a name `<prefix><i>`
+fn state_index(name: &str, prefix: &str) -> Option<usize> {
+    let digits = name.strip_prefix(prefix)?;
+    match !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()) {
+        true => digits.parse().ok(),
+        false => None,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExprSystem<N: Dim + DimName> {
+    // Right hand side of each state component
+    equations: Vec<Expr>,
+    // Names of the parameters
+    names: Vec<String>,
+    // Values of the parameters
+    values: Vec<f64>,
+    _dim: PhantomData<N>,
+}
+
+impl<N: Dim + DimName> ExprSystem<N> {
+    // Dynamics from the text of the equations, with the named parameters
+    pub fn parse(src: &str, params: &[(&str, f64)]) -> Result<Self, &'static str> {
+        let names: Vec<String> = params.iter().map(|(name, _)| name.to_string()).collect();
+        let tokens = tokenize(src)?;
+        let mut equations: Vec<Option<Expr>> = vec![None; N::dim()];
+        let mut first = 0;
+        for (k, token) in tokens.iter().enumerate() {
+            if *token != Token::End {
+                continue;
+            }
+            let statement = &tokens[first..k];
+            first = k + 1;
+            if statement.is_empty() {
+                continue;
+            }
+            let index = match (&statement[0], statement.get(1)) {
+                (Token::Ident(name), Some(Token::Op('='))) => {
+                    state_index(name, "dy").ok_or("[EXPR] Expected `dy<i> =`")?
+                }
+                _ => return Err("[EXPR] Expected `dy<i> =`"),
+            };
+            if index >= N::dim() {
+                return Err("[EXPR] Equation of a state outside of the states");
+            }
+            if equations[index].is_some() {
+                return Err("[EXPR] State with two equations");
+            }
+            let mut parser = Parser {
+                tokens: &statement[2..],
+                pos: 0,
+                params: &names,
+            };
+            let rhs = parser.expr()?;
+            if parser.pos < parser.tokens.len() {
+                return Err("[EXPR] Unexpected input after an expression");
+            }
+            if states_used(&rhs).into_iter().any(|i| i >= N::dim()) {
+                return Err("[EXPR] State outside of the states");
+            }
+            equations[index] = Some(rhs);
+        }
+        let equations = equations
+            .into_iter()
+            .map(|eq| eq.ok_or("[EXPR] State without an equation"))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(ExprSystem {
+            equations,
+            names,
+            values: params.iter().map(|(_, val)| *val).collect(),
+            _dim: PhantomData,
+        })
+    }
+
+    // Changes the value of a parameter
+    pub fn set_param(&mut self, name: &str, value: f64) -> Result<(), &'static str> {
+        match self.names.iter().position(|p| p == name) {
+            Some(i) => {
+                self.values[i] = value;
+                Ok(())
+            }
+            None => Err("[EXPR] Unknown parameter"),
+        }
+    }
+}
+
+// State components an expression reads
+fn states_used(expr: &Expr) -> Vec<usize> {
+    match expr {
+        Expr::State(i) => vec![*i],
+        Expr::Num(_) | Expr::Time | Expr::Param(_) => Vec::new(),
+        Expr::Neg(a) => states_used(a),
+        Expr::Add(a, b) | Expr::Sub(a, b) | Expr::Mul(a, b) | Expr::Div(a, b) | Expr::Pow(a, b) => {
+            let mut used = states_used(a);
+            used.extend(states_used(b));
+            used
+        }
+        Expr::Call(_, args) => args.iter().flat_map(states_used).collect(),
+    }
+}
+
+impl<N: Dim + DimName> OdeSystem<N> for ExprSystem<N>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    fn dynamics(&self, t: f64, y: &OVector<f64, N>) -> OVector<f64, N> {
+        OVector::<f64, N>::from_fn(|i, _| self.equations[i].eval(t, y.as_slice(), &self.values))
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runge_kutta::common::IntegOptions;
+    use crate::runge_kutta::fixed::FixedStep;
+    use crate::runge_kutta::rk_simp::RK4;
+    use na::{Vector1, Vector2, U1, U2};
+
+    #[test]
+    fn test_expr_system() {
+        // damped pendulum as text and as a closure
+        let mut text =
+            ExprSystem::<U2>::parse("dy0 = y1\n  dy1 = -sin(y0) - c * y1;", &[("c", 0.1)]).unwrap();
+        let closure = |_t: f64, y: &Vector2<f64>| Vector2::new(y[1], -y[0].sin() - 0.1 * y[1]);
+        let y_0 = Vector2::new(1.0, 0.0);
+        let run = |system: &ExprSystem<U2>| {
+            RK4.integrate(system.clone(), 0.0, y_0, 5.0, 0.01, IntegOptions::default())
+                .unwrap()
+        };
+        let ans = RK4
+            .integrate(closure, 0.0, y_0, 5.0, 0.01, IntegOptions::default())
+            .unwrap();
+        assert_eq!(run(&text).last_y(), ans.last_y());
+        text.set_param("c", 0.0).unwrap();
+        assert_ne!(run(&text).last_y(), ans.last_y());
+        assert!(text.set_param("k", 1.0).is_err());
+
+        // precedence, associativity, functions and constants
+        let eval = |src: &str| {
+            let system = ExprSystem::<U1>::parse(src, &[]).unwrap();
+            system.dynamics(2.0, &Vector1::new(3.0))[0]
+        };
+        assert_eq!(eval("dy0 = 1 + 2 * 3 - 4 / 2"), 5.0);
+        assert_eq!(eval("dy0 = -y0^2 + 2^3^2"), -9.0 + 512.0);
+        assert_eq!(eval("dy0 = 2^-1 * (t + 1e1) - +1.5E-1"), 6.0 - 0.15);
+        assert_eq!(eval("dy0 = max(y0, t) * pow(t, 2) + abs(-1)"), 13.0);
+        assert!((eval("dy0 = 4 * atan2(1, 1) - pi + ln(e)") - 1.0).abs() < 1e-15);
+
+        // mistakes are reported
+        let error = |src: &str| ExprSystem::<U2>::parse(src, &[]).unwrap_err();
+        assert_eq!(error("dy0 = y1; dy1 = y0 +"), "[EXPR] Expected a value");
+        assert_eq!(
+            error("dy0 = y1; dy1 = (y0"),
+            "[EXPR] Unbalanced parentheses"
+        );
+        assert_eq!(error("dy0 = y1; dy1 = k * y0"), "[EXPR] Unknown name");
+        assert_eq!(error("dy0 = y1; dy1 = sinc(y0)"), "[EXPR] Unknown function");
+        assert_eq!(
+            error("dy0 = y1; dy1 = y2"),
+            "[EXPR] State outside of the states"
+        );
+        assert_eq!(
+            error("dy0 = y1; dy2 = y0"),
+            "[EXPR] Equation of a state outside of the states"
+        );
+        assert_eq!(error("dy0 = y1"), "[EXPR] State without an equation");
+        assert_eq!(
+            error("dy0 = y1; dy0 = y0"),
+            "[EXPR] State with two equations"
+        );
+        assert_eq!(
+            error("dy0 = y1; dy1 = min(y0)"),
+            "[EXPR] Wrong number of arguments to a function"
+        );
+        assert_eq!(
+            error("dy0 = y1; dy1 = y0 y1"),
+            "[EXPR] Unexpected input after an expression"
+        );
+        assert_eq!(error("dy0 = y1; dy1 = y0 $"), "[EXPR] Unexpected character");
+    }
+}
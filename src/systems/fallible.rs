@@ -0,0 +1,172 @@
+/// Fallible Dynamics (systems/fallible)
+///
+/// Dynamics which can fail to evaluate, e.g. through table lookups out of range, an
+/// iterative sub-solve which does not converge or a state the model cannot
+/// handle. Wrapping a function `Fn(f64, &OVector<f64, N>) -> Result<OVector<f64, N>,
+/// EvalFailure>` in `Fallible` makes it an `OdeSystem` which records failures
+/// instead of returning garbage, and the Runge-Kutta integrators react to them
+/// after each step (as CVODE does for recoverable right hand side failures):
+/// - `EvalFailure::Retry` rejects the step, which is retried with half the step
+///   (erroring once that goes below the minimum step)
+/// - `EvalFailure::Fatal` ends the integration with the given error
+///
+/// The evaluations after a failure within the same step are skipped.
+///
+/// The RIDC integrators check the same way: a recoverable failure in the predictor
+/// retries its step with half the step, and any failure in a correction level
+/// (which cannot retry its step) ends the integration with an error. `propagate`
+/// and `propagate_adaptive` (runge_kutta/zero_alloc) retry like the integrators,
+/// while `FixedCost`, which never retries a step, ends on any failure.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DefaultAllocator, Dim, DimName, OVector};
+
+// local imports
+use super::OdeSystem;
+
+// Standard library imports
+use std::cell::Cell;
+
+// === End Imports ===
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EvalFailure {
+    // Recoverable: retry the step with a smaller step
+    Retry,
+    // Unrecoverable: end the integration with this error
+    Fatal(&'static str),
+}
+
+#[derive(Clone)]
+pub struct Fallible<F> {
+    // Fallible dynamics function
+    fxn: F,
+    // Failure of an evaluation since the failures were last taken
+    failure: Cell<Option<EvalFailure>>,
+}
+
+impl EvalFailure {
+    // Error of the failure of a step which cannot be retried
+    pub fn error(self) -> &'static str {
+        match self {
+            EvalFailure::Retry => {
+                "[FALLIBLE] Dynamics failed recoverably on a step which cannot be retried"
+            }
+            EvalFailure::Fatal(err) => err,
+        }
+    }
+}
+
+// Whether the evaluations of `system` since the last check failed recoverably, so
+// that the step has to be retried with a smaller step. Errors on unrecoverable
+// failures
+pub fn step_failed<N: Dim + DimName, S: OdeSystem<N> + ?Sized>(
+    system: &S,
+) -> Result<bool, &'static str>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    match system.take_failure() {
+        Some(EvalFailure::Fatal(err)) => Err(err),
+        Some(EvalFailure::Retry) => Ok(true),
+        None => Ok(false),
+    }
+}
+
+// Errors on any failure of the evaluations of `system` since the last check, for
+// steps which cannot be retried
+pub fn check_failure<N: Dim + DimName, S: OdeSystem<N> + ?Sized>(
+    system: &S,
+) -> Result<(), &'static str>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    match system.take_failure() {
+        Some(failure) => Err(failure.error()),
+        None => Ok(()),
+    }
+}
+
+impl<F> Fallible<F> {
+    pub fn new(fxn: F) -> Self {
+        Fallible {
+            fxn,
+            failure: Cell::new(None),
+        }
+    }
+}
+
+impl<N: Dim + DimName, F> OdeSystem<N> for Fallible<F>
+where
+    F: Fn(f64, &OVector<f64, N>) -> Result<OVector<f64, N>, EvalFailure>,
+    DefaultAllocator: Allocator<N>,
+{
+    fn dynamics(&self, t: f64, y: &OVector<f64, N>) -> OVector<f64, N> {
+        if self.failure.get().is_none() {
+            match (self.fxn)(t, y) {
+                Ok(dy) => return dy,
+                Err(failure) => self.failure.set(Some(failure)),
+            }
+        }
+        // the step is retried or abandoned: its value does not matter
+        OVector::<f64, N>::zeros()
+    }
+
+    fn take_failure(&self) -> Option<EvalFailure> {
+        self.failure.take()
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runge_kutta::common::{IntegError, IntegOptions};
+    use crate::runge_kutta::fixed::FixedStep;
+    use crate::runge_kutta::rk_simp::RK4;
+    use na::Vector1;
+
+    #[test]
+    fn test_fallible_dynamics() {
+        // fast decay where steps of 0.1 overshoot into negative (unphysical) stages,
+        // and a table that ends at t = 1.5
+        let decay = |t: f64, y: &Vector1<f64>| {
+            if t > 1.5 {
+                Err(EvalFailure::Fatal("[TABLE] Time out of range"))
+            } else if y[0] < 0.0 {
+                Err(EvalFailure::Retry)
+            } else {
+                Ok(Vector1::new(-30.0 * y[0]))
+            }
+        };
+        let ans = RK4
+            .integrate(
+                Fallible::new(decay),
+                0.0,
+                Vector1::new(1.0),
+                1.0,
+                0.1,
+                IntegOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(ans.t, 1.0);
+        assert!(ans.times.windows(2).any(|w| w[1] - w[0] < 0.1));
+        assert!(ans.states.iter().all(|y| y[0] >= 0.0));
+        assert!((ans.last_y()[0] - (-30.0_f64).exp()).abs() < 1e-10);
+
+        let err = RK4
+            .integrate(
+                Fallible::new(decay),
+                0.0,
+                Vector1::new(1.0),
+                2.0,
+                0.1,
+                IntegOptions::default(),
+            )
+            .unwrap_err();
+        assert_eq!(err, IntegError::Failed("[TABLE] Time out of range"));
+    }
+}
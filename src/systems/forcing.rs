@@ -0,0 +1,273 @@
+/// Tabulated Forcing (systems/forcing)
+///
+/// Wraps a table of times and values (measured wind, thrust curves, etc) into an
+/// interpolant which can be evaluated inside the dynamics. Three interpolations are
+/// available:
+/// - `Linear`: piecewise linear between the knots
+/// - `Cubic`: natural cubic spline (twice continuously differentiable)
+/// - `Pchip`: piecewise cubic hermite with Fritsch-Carlson slopes. Continuously
+///   differentiable and does not overshoot the data, which suits step-like tables
+///
+/// All of them are only piecewise smooth, so by default a `TimeSeries` reports its
+/// interior knots as switch times. Used as the control provider of a `Controlled`
+/// system (or with its `knots` passed as integration breakpoints) the integrators
+/// then land their steps on the knots rather than stepping over the kinks. For dense
+/// tables this can be turned off with `align_knots`.
+///
+/// Outside of the table the end values are held.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DefaultAllocator, Dim, DimName, OVector};
+
+// local imports
+use super::control::{check_knots, ControlProvider, CubicSpline};
+
+// === End Imports ===
+
+// Interpolation methods for tabulated data
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interpolation {
+    Linear,
+    Cubic,
+    Pchip,
+}
+
+// Per-method data of the interpolant
+#[derive(Debug, Clone, PartialEq)]
+enum Interpolant<M: Dim + DimName>
+where
+    DefaultAllocator: Allocator<M>,
+{
+    Linear,
+    Cubic(CubicSpline<M>),
+    // slopes at the knots
+    Pchip(Vec<OVector<f64, M>>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeSeries<M: Dim + DimName>
+where
+    DefaultAllocator: Allocator<M>,
+{
+    // Whether the interior knots are reported as switch times for step alignment
+    pub align_knots: bool,
+    // Table times (increasing)
+    times: Vec<f64>,
+    // Table values
+    values: Vec<OVector<f64, M>>,
+    // Interpolation between the knots
+    interpolant: Interpolant<M>,
+}
+
+impl<M: Dim + DimName> TimeSeries<M>
+where
+    DefaultAllocator: Allocator<M>,
+{
+    pub fn new(
+        times: Vec<f64>,
+        values: Vec<OVector<f64, M>>,
+        interpolation: Interpolation,
+    ) -> Result<Self, &'static str> {
+        check_knots(&times, values.len(), 2)?;
+        let interpolant = match interpolation {
+            Interpolation::Linear => Interpolant::Linear,
+            Interpolation::Cubic => {
+                Interpolant::Cubic(CubicSpline::new(times.clone(), values.clone())?)
+            }
+            Interpolation::Pchip => Interpolant::Pchip(pchip_slopes(&times, &values)),
+        };
+        Ok(TimeSeries {
+            align_knots: true,
+            times,
+            values,
+            interpolant,
+        })
+    }
+
+    // Interior knots of the table (where the interpolant is not smooth)
+    pub fn knots(&self) -> Vec<f64> {
+        self.times[1..self.times.len() - 1].to_vec()
+    }
+
+    // Interpolated value of the table at time t
+    pub fn value(&self, t: f64) -> OVector<f64, M> {
+        let last = self.times.len() - 1;
+        if t <= self.times[0] {
+            return self.values[0].clone();
+        } else if t >= self.times[last] {
+            return self.values[last].clone();
+        }
+
+        let k = self.times.partition_point(|t_k| *t_k <= t) - 1;
+        let h = self.times[k + 1] - self.times[k];
+        let s = (t - self.times[k]) / h;
+        match &self.interpolant {
+            Interpolant::Linear => (1.0 - s) * &self.values[k] + s * &self.values[k + 1],
+            Interpolant::Cubic(spline) => spline.control(t),
+            Interpolant::Pchip(slopes) => {
+                // cubic hermite basis
+                let h_00 = (1.0 + 2.0 * s) * (1.0 - s).powi(2);
+                let h_10 = s * (1.0 - s).powi(2);
+                let h_01 = s * s * (3.0 - 2.0 * s);
+                let h_11 = s * s * (s - 1.0);
+                h_00 * &self.values[k]
+                    + (h_10 * h) * &slopes[k]
+                    + h_01 * &self.values[k + 1]
+                    + (h_11 * h) * &slopes[k + 1]
+            }
+        }
+    }
+}
+
+impl<M: Dim + DimName> ControlProvider<M> for TimeSeries<M>
+where
+    DefaultAllocator: Allocator<M>,
+{
+    fn control(&self, t: f64) -> OVector<f64, M> {
+        self.value(t)
+    }
+
+    fn switch_times(&self) -> Vec<f64> {
+        if self.align_knots {
+            self.knots()
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+// Fritsch-Carlson slopes of the monotone piecewise cubic hermite interpolant, computed
+// independently for each component
+fn pchip_slopes<M: Dim + DimName>(times: &[f64], values: &[OVector<f64, M>]) -> Vec<OVector<f64, M>>
+where
+    DefaultAllocator: Allocator<M>,
+{
+    let n = times.len();
+    let h: Vec<f64> = times.windows(2).map(|pair| pair[1] - pair[0]).collect();
+    let deltas: Vec<OVector<f64, M>> = (0..n - 1)
+        .map(|k| (&values[k + 1] - &values[k]) / h[k])
+        .collect();
+    let mut slopes = vec![OVector::<f64, M>::zeros(); n];
+    if n == 2 {
+        slopes[0] = deltas[0].clone();
+        slopes[1] = deltas[0].clone();
+        return slopes;
+    }
+
+    for c in 0..M::dim() {
+        // interior points: weighted harmonic mean, zero at local extrema
+        for k in 1..n - 1 {
+            let (d_0, d_1) = (deltas[k - 1][c], deltas[k][c]);
+            if d_0 * d_1 > 0.0 {
+                let w_0 = 2.0 * h[k] + h[k - 1];
+                let w_1 = h[k] + 2.0 * h[k - 1];
+                slopes[k][c] = (w_0 + w_1) / (w_0 / d_0 + w_1 / d_1);
+            }
+        }
+        // end points: shape preserving three point formula
+        slopes[0][c] = pchip_end_slope(h[0], h[1], deltas[0][c], deltas[1][c]);
+        slopes[n - 1][c] = pchip_end_slope(h[n - 2], h[n - 3], deltas[n - 2][c], deltas[n - 3][c]);
+    }
+    slopes
+}
+
+// One sided slope at the end of a pchip interpolant (h_0 and delta_0 belong to the
+// end interval)
+fn pchip_end_slope(h_0: f64, h_1: f64, delta_0: f64, delta_1: f64) -> f64 {
+    let slope = ((2.0 * h_0 + h_1) * delta_0 - h_0 * delta_1) / (h_0 + h_1);
+    if slope * delta_0 <= 0.0 {
+        0.0
+    } else if delta_0 * delta_1 <= 0.0 && slope.abs() > (3.0 * delta_0).abs() {
+        3.0 * delta_0
+    } else {
+        slope
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runge_kutta::common::IntegOptions;
+    use crate::runge_kutta::fixed::FixedStep;
+    use crate::runge_kutta::rk_simp::RK4;
+    use crate::systems::control::Controlled;
+    use na::{Vector1, U1};
+
+    fn table(values: &[f64], interpolation: Interpolation) -> TimeSeries<U1> {
+        let times = (0..values.len()).map(|i| i as f64).collect();
+        let values = values.iter().map(|v| Vector1::new(*v)).collect();
+        TimeSeries::new(times, values, interpolation).unwrap()
+    }
+
+    #[test]
+    fn test_interpolations() {
+        let data = [0.0, 0.0, 1.0, 1.0, 1.0];
+        for interpolation in &[
+            Interpolation::Linear,
+            Interpolation::Cubic,
+            Interpolation::Pchip,
+        ] {
+            let series = table(&data, *interpolation);
+            for (i, v) in data.iter().enumerate() {
+                assert!((series.value(i as f64)[0] - v).abs() < 1e-14);
+            }
+            assert_eq!(series.value(-1.0)[0], 0.0);
+            assert_eq!(series.value(10.0)[0], 1.0);
+            assert_eq!(series.switch_times(), vec![1.0, 2.0, 3.0]);
+        }
+        assert_eq!(table(&data, Interpolation::Linear).value(1.25)[0], 0.25);
+
+        // the spline overshoots the step, pchip stays within the data
+        let samples: Vec<f64> = (0..=40).map(|i| 0.1 * i as f64).collect();
+        let cubic = table(&data, Interpolation::Cubic);
+        assert!(samples.iter().any(|t| cubic.value(*t)[0] > 1.0));
+        let pchip = table(&data, Interpolation::Pchip);
+        assert!(samples
+            .iter()
+            .all(|t| (0.0..=1.0).contains(&pchip.value(*t)[0])));
+        assert!(samples
+            .windows(2)
+            .all(|pair| pchip.value(pair[1])[0] >= pchip.value(pair[0])[0] - 1e-15));
+    }
+
+    #[test]
+    fn test_forcing_alignment() {
+        // y' = u(t) for a piecewise linear thrust table with knots off the step grid
+        let thrust = TimeSeries::new(
+            vec![0.0, 0.35, 1.1, 2.0],
+            vec![
+                Vector1::new(0.0),
+                Vector1::new(2.0),
+                Vector1::new(-1.0),
+                Vector1::new(0.5),
+            ],
+            Interpolation::Linear,
+        )
+        .unwrap();
+        let truth = 0.35 * 1.0 + 0.75 * 0.5 + 0.9 * -0.25;
+        let plant = |_t: f64, _y: &Vector1<f64>, u: &Vector1<f64>| *u;
+        let run = |series: TimeSeries<U1>| {
+            let system = Controlled::new(plant, series);
+            let ans = RK4
+                .integrate(
+                    system,
+                    0.0,
+                    Vector1::zeros(),
+                    2.0,
+                    0.25,
+                    IntegOptions::default(),
+                )
+                .unwrap();
+            (ans.last_y()[0] - truth).abs()
+        };
+
+        let mut unaligned = thrust.clone();
+        unaligned.align_knots = false;
+        assert!(run(thrust) < 1e-14);
+        assert!(run(unaligned) > 1e-4);
+    }
+}
@@ -0,0 +1,340 @@
+/// Floating-Point Exception Policy (systems/fp_policy)
+///
+/// By default the integrators carry whatever the dynamics produce: a NaN from an
+/// invalid operation or an infinity from an overflow propagates silently into the
+/// solution, and subnormal values (which many targets flush to zero) go unnoticed.
+/// Validated or safety-critical runs usually need to know. `FpChecked` wraps any
+/// `OdeSystem` and classifies the values crossing it:
+/// - `Invalid`: NaN
+/// - `Overflow`: +-infinity
+/// - `Subnormal`: non-zero values below the smallest normal f64
+///
+/// in two phases, the states the integrator hands to the dynamics (stages and steps)
+/// and the dynamics they return. Each phase has a mask of the classes checked in it,
+/// and each class an action:
+/// - `Error`: the evaluation fails fatally (see `fallible`), ending Runge-Kutta
+///   integrations with an error naming the class and phase (the observer is told
+///   as well)
+/// - `Warn`: the observer is told (`FpEvent`) and the evaluation goes on
+/// - `Silent`: nothing happens (the behavior without the wrapper)
+///
+/// Errors reach the integrators through `take_failure` like those of fallible
+/// dynamics, so they end RIDC and the other integrators as well (see `fallible`).
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DefaultAllocator, Dim, DimName, OVector};
+
+// local imports
+use super::fallible::EvalFailure;
+use super::OdeSystem;
+
+// Standard library imports
+use std::cell::Cell;
+use std::sync::Arc;
+
+// === End Imports ===
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FpClass {
+    // NaN
+    Invalid,
+    // Infinite value
+    Overflow,
+    // Non-zero value below the normal range
+    Subnormal,
+}
+
+impl FpClass {
+    // Class of a value, None for normal values and zero
+    pub fn of(val: f64) -> Option<FpClass> {
+        if val.is_nan() {
+            Some(FpClass::Invalid)
+        } else if val.is_infinite() {
+            Some(FpClass::Overflow)
+        } else if val.is_subnormal() {
+            Some(FpClass::Subnormal)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FpPhase {
+    // State handed to the dynamics
+    State,
+    // Dynamics returned
+    Dynamics,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FpAction {
+    Error,
+    Warn,
+    Silent,
+}
+
+// Classes checked in a phase
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FpMask {
+    pub invalid: bool,
+    pub overflow: bool,
+    pub subnormal: bool,
+}
+
+impl FpMask {
+    pub const ALL: FpMask = FpMask {
+        invalid: true,
+        overflow: true,
+        subnormal: true,
+    };
+    pub const NONE: FpMask = FpMask {
+        invalid: false,
+        overflow: false,
+        subnormal: false,
+    };
+
+    pub fn contains(&self, class: FpClass) -> bool {
+        match class {
+            FpClass::Invalid => self.invalid,
+            FpClass::Overflow => self.overflow,
+            FpClass::Subnormal => self.subnormal,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FpPolicy {
+    // Action on NaN
+    pub invalid: FpAction,
+    // Action on infinite values
+    pub overflow: FpAction,
+    // Action on subnormal values
+    pub subnormal: FpAction,
+    // Classes checked in the states handed to the dynamics
+    pub states: FpMask,
+    // Classes checked in the dynamics returned
+    pub dynamics: FpMask,
+}
+
+impl FpPolicy {
+    // Everything silent: the behavior of the unwrapped dynamics
+    pub fn default() -> Self {
+        FpPolicy {
+            invalid: FpAction::Silent,
+            overflow: FpAction::Silent,
+            subnormal: FpAction::Silent,
+            states: FpMask::ALL,
+            dynamics: FpMask::ALL,
+        }
+    }
+
+    // NaN and infinities are errors and subnormals warnings, in both phases
+    pub fn strict() -> Self {
+        FpPolicy {
+            invalid: FpAction::Error,
+            overflow: FpAction::Error,
+            subnormal: FpAction::Warn,
+            ..FpPolicy::default()
+        }
+    }
+
+    pub fn action(&self, class: FpClass) -> FpAction {
+        match class {
+            FpClass::Invalid => self.invalid,
+            FpClass::Overflow => self.overflow,
+            FpClass::Subnormal => self.subnormal,
+        }
+    }
+
+    fn mask(&self, phase: FpPhase) -> FpMask {
+        match phase {
+            FpPhase::State => self.states,
+            FpPhase::Dynamics => self.dynamics,
+        }
+    }
+}
+
+// A checked value of an exceptional class
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FpEvent {
+    pub class: FpClass,
+    pub phase: FpPhase,
+    // Time of the evaluation
+    pub t: f64,
+    // Component of the (first) exceptional value
+    pub index: usize,
+    // The value itself
+    pub value: f64,
+}
+
+impl FpEvent {
+    fn message(&self) -> &'static str {
+        match (self.class, self.phase) {
+            (FpClass::Invalid, FpPhase::State) => "[FP] Invalid value (NaN) in a state",
+            (FpClass::Invalid, FpPhase::Dynamics) => "[FP] Invalid value (NaN) in the dynamics",
+            (FpClass::Overflow, FpPhase::State) => "[FP] Overflow (infinity) in a state",
+            (FpClass::Overflow, FpPhase::Dynamics) => "[FP] Overflow (infinity) in the dynamics",
+            (FpClass::Subnormal, FpPhase::State) => "[FP] Subnormal value in a state",
+            (FpClass::Subnormal, FpPhase::Dynamics) => "[FP] Subnormal value in the dynamics",
+        }
+    }
+}
+
+// Receives the events with a `Warn` or `Error` action
+pub type FpObserver = Arc<dyn Fn(&FpEvent) + Send + Sync>;
+
+#[derive(Clone)]
+pub struct FpChecked<S> {
+    // Checked dynamics
+    system: S,
+    // Classes checked and the action on each
+    policy: FpPolicy,
+    // Receiver of the warnings
+    observer: Option<FpObserver>,
+    // Error of an evaluation since the failures were last taken
+    failure: Cell<Option<EvalFailure>>,
+}
+
+impl<S> FpChecked<S> {
+    pub fn new(system: S, policy: FpPolicy) -> Self {
+        FpChecked {
+            system,
+            policy,
+            observer: None,
+            failure: Cell::new(None),
+        }
+    }
+
+    pub fn with_observer(self, observer: FpObserver) -> Self {
+        FpChecked {
+            observer: Some(observer),
+            ..self
+        }
+    }
+
+    // Applies the policy to the values of a phase. Whether the evaluation failed
+    fn check(&self, phase: FpPhase, t: f64, vals: &[f64]) -> bool {
+        let mask = self.policy.mask(phase);
+        for class in [FpClass::Invalid, FpClass::Overflow, FpClass::Subnormal] {
+            let action = self.policy.action(class);
+            if !mask.contains(class) || action == FpAction::Silent {
+                continue;
+            }
+            let found = vals.iter().position(|val| FpClass::of(*val) == Some(class));
+            if let Some(index) = found {
+                let event = FpEvent {
+                    class,
+                    phase,
+                    t,
+                    index,
+                    value: vals[index],
+                };
+                if let Some(observer) = &self.observer {
+                    observer(&event);
+                }
+                if action == FpAction::Error {
+                    self.failure.set(Some(EvalFailure::Fatal(event.message())));
+                    return true;
+                }
+            }
+        }
+        false
+    }
+}
+
+impl<N: Dim + DimName, S: OdeSystem<N>> OdeSystem<N> for FpChecked<S>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    fn dynamics(&self, t: f64, y: &OVector<f64, N>) -> OVector<f64, N> {
+        if self.failure.get().is_none() && !self.check(FpPhase::State, t, y.as_slice()) {
+            let dy = self.system.dynamics(t, y);
+            if !self.check(FpPhase::Dynamics, t, dy.as_slice()) {
+                return dy;
+            }
+        }
+        // the integration is abandoned: the value does not matter
+        OVector::<f64, N>::zeros()
+    }
+
+    fn breakpoints(&self) -> Vec<f64> {
+        self.system.breakpoints()
+    }
+
+    fn take_failure(&self) -> Option<EvalFailure> {
+        match self.failure.take() {
+            Some(failure) => Some(failure),
+            None => self.system.take_failure(),
+        }
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runge_kutta::common::{IntegError, IntegOptions};
+    use crate::runge_kutta::fixed::FixedStep;
+    use crate::runge_kutta::rk_simp::RK4;
+    use na::Vector2;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_fp_policy() {
+        // the second component decays into the subnormal range and the first
+        // blows up (1 / (1 - t)) toward t = 1
+        let fxn = |_t: f64, y: &Vector2<f64>| Vector2::new(y[0] * y[0], -1000.0 * y[1]);
+        let y_0 = Vector2::new(1.0, 1.0);
+        let run = |policy: FpPolicy, t_end: f64| {
+            let events = Arc::new(Mutex::new(Vec::new()));
+            let log = events.clone();
+            let system = FpChecked::new(fxn, policy)
+                .with_observer(Arc::new(move |e: &FpEvent| log.lock().unwrap().push(*e)));
+            let ans = RK4.integrate(system, 0.0, y_0, t_end, 1e-3, IntegOptions::default());
+            let events = events.lock().unwrap().clone();
+            (ans, events)
+        };
+
+        // silent: runs on as before
+        let (ans, events) = run(FpPolicy::default(), 2.5);
+        assert!(ans.is_ok() && events.is_empty());
+
+        // strict: subnormals are warned about, then the overflow ends the run
+        let (ans, events) = run(FpPolicy::strict(), 2.5);
+        assert_eq!(
+            ans.unwrap_err(),
+            IntegError::Failed("[FP] Overflow (infinity) in the dynamics")
+        );
+        let first = events[0];
+        assert_eq!(
+            (first.class, first.phase, first.index),
+            (FpClass::Subnormal, FpPhase::State, 1)
+        );
+        assert!(first.value != 0.0 && first.value.abs() < f64::MIN_POSITIVE);
+        let last = events[events.len() - 1];
+        assert_eq!((last.class, last.index), (FpClass::Overflow, 0));
+        assert!((last.t - 1.0).abs() < 0.01);
+
+        // masking the subnormals out of both phases and checking only the states
+        let policy = FpPolicy {
+            states: FpMask {
+                subnormal: false,
+                ..FpMask::ALL
+            },
+            dynamics: FpMask::NONE,
+            ..FpPolicy::strict()
+        };
+        let (ans, events) = run(policy, 2.5);
+        assert_eq!(
+            ans.unwrap_err(),
+            IntegError::Failed("[FP] Overflow (infinity) in a state")
+        );
+        assert_eq!(events.len(), 1);
+        let (ans, events) = run(policy, 0.5);
+        assert!(ans.is_ok() && events.is_empty());
+    }
+}
@@ -0,0 +1,175 @@
+/// Linear Systems (systems/linear)
+///
+/// Constant coefficient linear dynamics y' = A y + b. Their solution is known in
+/// closed form,
+///
+/// y(t + h) = exp(h A) y(t) + h phi_1(h A) b
+///
+/// and both terms are read off one matrix exponential of the augmented matrix
+/// [[A, b], [0, 0]] (see `utils::linalg`, which also gives phi_1 on its own). `LinearSystem` is an `OdeSystem`
+/// like any other, so it can be handed to the integrators, but it can also be
+/// propagated exactly: `propagate` steps a state by any h, and `solve` samples the
+/// solution on a fixed grid with the propagator of the grid step formed only once.
+/// The exact solution makes linear problems (including stiff ones) convenient
+/// references for testing integrators.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DMatrix, DefaultAllocator, Dim, DimName, OMatrix, OVector};
+
+// local imports
+use super::OdeSystem;
+use crate::runge_kutta::common::{approach_end, IntegResult};
+use crate::utils::linalg::expm;
+
+// === End Imports ===
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinearSystem<N: Dim + DimName>
+where
+    DefaultAllocator: Allocator<N> + Allocator<N, N>,
+{
+    // Coefficient matrix A
+    pub a: OMatrix<f64, N, N>,
+    // Constant forcing b
+    pub b: OVector<f64, N>,
+}
+
+// Exact step of a linear system: y(t + step) = transition y(t) + forcing
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinearPropagator<N: Dim + DimName>
+where
+    DefaultAllocator: Allocator<N> + Allocator<N, N>,
+{
+    // Step the propagator advances by
+    pub step: f64,
+    // State transition matrix exp(step A)
+    pub transition: OMatrix<f64, N, N>,
+    // Response to the forcing over the step
+    pub forcing: OVector<f64, N>,
+}
+
+impl<N: Dim + DimName> LinearPropagator<N>
+where
+    DefaultAllocator: Allocator<N> + Allocator<N, N>,
+{
+    pub fn apply(&self, y: &OVector<f64, N>) -> OVector<f64, N> {
+        &self.transition * y + &self.forcing
+    }
+}
+
+impl<N: Dim + DimName> LinearSystem<N>
+where
+    DefaultAllocator: Allocator<N> + Allocator<N, N>,
+{
+    pub fn new(a: OMatrix<f64, N, N>, b: OVector<f64, N>) -> Self {
+        LinearSystem { a, b }
+    }
+
+    // Unforced system y' = A y
+    pub fn homogeneous(a: OMatrix<f64, N, N>) -> Self {
+        LinearSystem {
+            a,
+            b: OVector::<f64, N>::zeros(),
+        }
+    }
+
+    // Exact propagator over a step
+    pub fn propagator(&self, step: f64) -> Result<LinearPropagator<N>, &'static str> {
+        let n = N::dim();
+        let mut aug = DMatrix::<f64>::zeros(n + 1, n + 1);
+        aug.view_mut((0, 0), (n, n)).copy_from(&(&self.a * step));
+        aug.view_mut((0, n), (n, 1)).copy_from(&(&self.b * step));
+        let exp = expm(&aug)?;
+        Ok(LinearPropagator {
+            step,
+            transition: OMatrix::<f64, N, N>::from_fn(|i, j| exp[(i, j)]),
+            forcing: OVector::<f64, N>::from_fn(|i, _| exp[(i, n)]),
+        })
+    }
+
+    // Exact solution a step from y
+    pub fn propagate(
+        &self,
+        y: &OVector<f64, N>,
+        step: f64,
+    ) -> Result<OVector<f64, N>, &'static str> {
+        Ok(self.propagator(step)?.apply(y))
+    }
+
+    // Exact solution from t_0 to t_end sampled every dt (the last step is shortened
+    // to land on t_end)
+    pub fn solve(
+        &self,
+        t_0: f64,
+        y_0: &OVector<f64, N>,
+        t_end: f64,
+        dt: f64,
+    ) -> Result<IntegResult<N>, &'static str> {
+        if dt == 0.0 || !dt.is_finite() {
+            return Err("[LINEAR] Step must be finite and non-zero");
+        }
+        let full = self.propagator(dt.abs().copysign(t_end - t_0))?;
+        let mut results = IntegResult::new(t_0, y_0.clone());
+        while results.t != t_end {
+            let (h, last) = approach_end(results.t, t_end, dt);
+            let y_nxt = match h == full.step {
+                true => full.apply(results.last_y()),
+                false => self.propagate(results.last_y(), h)?,
+            };
+            results.add_val(h, y_nxt);
+            if last {
+                results.land_on(t_end);
+            }
+        }
+        Ok(results)
+    }
+}
+
+impl<N: Dim + DimName> OdeSystem<N> for LinearSystem<N>
+where
+    DefaultAllocator: Allocator<N> + Allocator<N, N>,
+{
+    fn dynamics(&self, _t: f64, y: &OVector<f64, N>) -> OVector<f64, N> {
+        &self.a * y + &self.b
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runge_kutta::common::IntegOptions;
+    use crate::runge_kutta::fixed::FixedStep;
+    use crate::runge_kutta::rk_simp::RK4;
+    use na::{Matrix2, Vector2};
+
+    #[test]
+    fn test_linear_system() {
+        // damped oscillator pushed by a constant force, equilibrium at (1, 0)
+        let sys = LinearSystem::new(Matrix2::new(0.0, 1.0, -4.0, -0.4), Vector2::new(0.0, 4.0));
+        let y_0 = Vector2::new(0.0, 0.0);
+        let exact = sys.solve(0.0, &y_0, 10.0, 0.3).unwrap();
+        assert_eq!(exact.t, 10.0);
+        assert_eq!(exact.times.len(), 35);
+
+        // y_1 = 1 - exp(-0.2 t) (cos(w t) + 0.2 / w sin(w t)), w = sqrt(3.96)
+        let w = 3.96_f64.sqrt();
+        let truth = |t: f64| 1.0 - (-0.2 * t).exp() * ((w * t).cos() + 0.2 / w * (w * t).sin());
+        for (t, y) in exact.times.iter().zip(&exact.states) {
+            assert!((y[0] - truth(*t)).abs() < 1e-13);
+        }
+
+        // a fine RK4 integration converges onto it
+        let rk = RK4
+            .integrate(sys.clone(), 0.0, y_0, 10.0, 0.01, IntegOptions::default())
+            .unwrap();
+        assert!((rk.last_y() - exact.last_y()).amax() < 1e-8);
+
+        // backward in time returns to the initial state
+        let back = sys.solve(10.0, exact.last_y(), 0.0, 0.3).unwrap();
+        assert!(back.last_y().amax() < 1e-12);
+    }
+}
@@ -0,0 +1,97 @@
+/// Ode Systems (systems)
+///
+/// Defines the `OdeSystem` trait used by all integrators to evaluate the dynamics
+/// (right hand side) of an initial value problem. Any closure or function of the
+/// form `Fn(f64, &OVector<f64, N>) -> OVector<f64, N>` is an `OdeSystem`, so plain
+/// dynamics functions can still be passed directly to the integrators. Structs
+/// implementing the trait can carry their own parameters (gravitational parameters,
+/// rates, etc) which is not possible with bare function pointers.
+///
+/// Dynamics which can fail to evaluate report failures the integrators can retry
+/// or stop on through `take_failure` (see `fallible`). NaN, overflow and subnormal
+/// values crossing the dynamics can be turned into errors or warnings (see
+/// `fp_policy`).
+///
+/// Systems with known discontinuities (e.g. a switched control, see `control`) report
+/// them through `breakpoints` and the integrators land their steps on them. Tabulated
+/// forcing data (see `forcing`) reports its knots the same way.
+///
+/// Dynamics written against custom state containers are supported through the
+/// `State` trait (see `state`). With the `ndarray` feature, dynamics written against
+/// `ndarray::Array1` can be used directly (see `ndarray_interop`), and with the
+/// `uom` feature states can carry units of measure (see `units`). With the
+/// `hifitime` feature, times can be given as absolute epochs (see `epoch`). Problems
+/// with widely different scales can be integrated in nondimensional form (see
+/// `scaling`).
+/// Ensembles can evaluate the dynamics of all their members in one batched call,
+/// e.g. on a GPU (see `batch`). Evaluations can be counted and timed for profiling
+/// (see `counted`). Constant coefficient linear systems can be propagated exactly
+/// (see `linear`), and 1D diffusion / advection PDEs discretized into large stiff
+/// systems (see `mol`). Heat equation and Brusselator semidiscretizations with
+/// reference solutions serve as large benchmark problems (see `pde`), alongside the
+/// standard stiff and nonstiff test problems (see `stiff`, `nonstiff`). With the
+/// `expr` feature, simple dynamics can be written as text (see `expr`).
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DefaultAllocator, Dim, DimName, OVector};
+
+// local imports
+use fallible::EvalFailure;
+
+// === End Imports ===
+
+pub mod astro;
+pub mod batch;
+pub mod control;
+pub mod counted;
+#[cfg(feature = "hifitime")]
+pub mod epoch;
+#[cfg(feature = "expr")]
+pub mod expr;
+pub mod fallible;
+pub mod forcing;
+pub mod fp_policy;
+pub mod linear;
+pub mod mol;
+#[cfg(feature = "ndarray")]
+pub mod ndarray_interop;
+pub mod nonstiff;
+pub mod pde;
+pub mod scaling;
+pub mod state;
+pub mod stiff;
+#[cfg(feature = "uom")]
+pub mod units;
+
+pub trait OdeSystem<N: Dim + DimName>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    // Evaluates the dynamics function f(t, y)
+    fn dynamics(&self, t: f64, y: &OVector<f64, N>) -> OVector<f64, N>;
+
+    // Times at which the dynamics are discontinuous. Integrators treat them like the
+    // `breakpoints` integration option
+    fn breakpoints(&self) -> Vec<f64> {
+        Vec::new()
+    }
+
+    // Failure of an evaluation since the last call, which clears it. Only fallible
+    // dynamics (see `fallible`) fail
+    fn take_failure(&self) -> Option<EvalFailure> {
+        None
+    }
+}
+
+impl<N: Dim + DimName, F> OdeSystem<N> for F
+where
+    F: Fn(f64, &OVector<f64, N>) -> OVector<f64, N>,
+    DefaultAllocator: Allocator<N>,
+{
+    fn dynamics(&self, t: f64, y: &OVector<f64, N>) -> OVector<f64, N> {
+        self(t, y)
+    }
+}
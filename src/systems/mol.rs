@@ -0,0 +1,305 @@
+/// Method of Lines (systems/mol)
+///
+/// Finite difference discretizations of 1D diffusion and advection operators, which
+/// turn a PDE u_t = D u_xx - c u_x on [x_0, x_1] into a large (and, for fine grids,
+/// stiff) system of ODEs in the values of u on a grid. Grids are cell centered: n
+/// cells of width h = (x_1 - x_0) / n with the unknowns at their midpoints, and the
+/// boundary conditions enter through ghost cells:
+/// - `Dirichlet(g)`: u = g on the boundary
+/// - `Neumann(q)`: u_x = q on the boundary (q = 0 is a closed, no flux boundary)
+/// - `Periodic`: the grid wraps around (both ends must be periodic)
+///
+/// Diffusion uses the second order central difference. Advection is upwinded (first
+/// order), so only the boundary the flow comes in through is used.
+///
+/// Each operator is the sparse matrix L of the discretization plus the constant
+/// forcing f the boundary values contribute, u' = L u + f, and operators on the
+/// same grid add up. `MolSystem` is the `OdeSystem` of an operator; reactions or
+/// sources can be added by wrapping its dynamics in a closure. The matrix can also
+/// be handed to the krylov solvers and `expmv` (see `utils::linalg`) directly.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DefaultAllocator, Dim, DimName, OVector};
+
+// local imports
+use super::OdeSystem;
+use crate::utils::sparse::CsrMatrix;
+
+// Standard library imports
+use std::marker::PhantomData;
+
+// === End Imports ===
+
+// Uniform cell centered grid on [x_0, x_1]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Grid {
+    // Left end of the domain
+    pub x_0: f64,
+    // Right end of the domain
+    pub x_1: f64,
+    // Number of cells (unknowns)
+    pub cells: usize,
+}
+
+impl Grid {
+    pub fn new(x_0: f64, x_1: f64, cells: usize) -> Result<Self, &'static str> {
+        if cells < 2 || x_1 <= x_0 || x_1.is_nan() || x_0.is_nan() {
+            return Err("[MOL] Grid needs at least two cells on a non-empty interval");
+        }
+        Ok(Grid { x_0, x_1, cells })
+    }
+
+    // Width of the cells
+    pub fn h(&self) -> f64 {
+        (self.x_1 - self.x_0) / self.cells as f64
+    }
+
+    // Midpoints of the cells, where the unknowns live
+    pub fn points(&self) -> Vec<f64> {
+        (0..self.cells)
+            .map(|i| self.x_0 + (i as f64 + 0.5) * self.h())
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Boundary {
+    // Value of u on the boundary
+    Dirichlet(f64),
+    // Derivative u_x on the boundary
+    Neumann(f64),
+    Periodic,
+}
+
+// Discretized operator u' = matrix u + forcing
+#[derive(Debug, Clone, PartialEq)]
+pub struct MolOperator {
+    // Grid the operator is discretized on
+    pub grid: Grid,
+    // Sparse matrix of the discretization
+    pub matrix: CsrMatrix,
+    // Constant contribution of the boundary values
+    pub forcing: Vec<f64>,
+}
+
+impl MolOperator {
+    // Sum of two operators on the same grid
+    pub fn add(&self, other: &MolOperator) -> Result<MolOperator, &'static str> {
+        if self.grid != other.grid {
+            return Err("[MOL] Operators must share a grid to be added");
+        }
+        let mut entries = triplets(&self.matrix);
+        entries.extend(triplets(&other.matrix));
+        let n = self.grid.cells;
+        Ok(MolOperator {
+            grid: self.grid,
+            matrix: CsrMatrix::from_triplets(n, n, &entries)?,
+            forcing: self
+                .forcing
+                .iter()
+                .zip(&other.forcing)
+                .map(|(a, b)| a + b)
+                .collect(),
+        })
+    }
+}
+
+fn triplets(mat: &CsrMatrix) -> Vec<(usize, usize, f64)> {
+    let mut out = Vec::with_capacity(mat.nnz());
+    for i in 0..mat.nrows {
+        let (cols, vals) = mat.row(i);
+        out.extend(cols.iter().zip(vals).map(|(j, val)| (i, *j, *val)));
+    }
+    out
+}
+
+// Ghost cell beyond one end of the grid as (weight on the boundary cell, constant):
+// u_ghost = weight u_boundary + constant. None for a periodic boundary (the ghost is
+// the cell at the other end). `outward` is the direction of the ghost (-1 left, +1
+// right) and `face_value` whether the boundary value is taken at the cell face
+// (diffusion) rather than as the upwind value (advection)
+fn ghost(bc: Boundary, h: f64, outward: f64, face_value: bool) -> Option<(f64, f64)> {
+    match bc {
+        Boundary::Dirichlet(g) if face_value => Some((-1.0, 2.0 * g)),
+        Boundary::Dirichlet(g) => Some((0.0, g)),
+        Boundary::Neumann(q) => Some((1.0, outward * q * h)),
+        Boundary::Periodic => None,
+    }
+}
+
+// Builds an operator from the three point stencil (lower, center, upper) applied at
+// every cell, closing it with the boundary conditions
+fn three_point(
+    grid: &Grid,
+    stencil: [f64; 3],
+    left: Boundary,
+    right: Boundary,
+    face_value: bool,
+) -> Result<MolOperator, &'static str> {
+    if (left == Boundary::Periodic) != (right == Boundary::Periodic) {
+        return Err("[MOL] Periodic boundaries must be periodic at both ends");
+    }
+    let (n, h) = (grid.cells, grid.h());
+    let mut triplets = Vec::with_capacity(3 * n);
+    let mut forcing = vec![0.0; n];
+    for i in 0..n {
+        triplets.push((i, i, stencil[1]));
+        if i > 0 {
+            triplets.push((i, i - 1, stencil[0]));
+        }
+        if i + 1 < n {
+            triplets.push((i, i + 1, stencil[2]));
+        }
+    }
+    for (bc, cell, other_end, outward, weight) in [
+        (left, 0, n - 1, -1.0, stencil[0]),
+        (right, n - 1, 0, 1.0, stencil[2]),
+    ] {
+        match ghost(bc, h, outward, face_value) {
+            Some((w_cell, constant)) => {
+                triplets.push((cell, cell, weight * w_cell));
+                forcing[cell] += weight * constant;
+            }
+            None => triplets.push((cell, other_end, weight)),
+        }
+    }
+    // one sided (upwind) stencils leave out a neighbor
+    triplets.retain(|entry| entry.2 != 0.0);
+    Ok(MolOperator {
+        grid: *grid,
+        matrix: CsrMatrix::from_triplets(n, n, &triplets)?,
+        forcing,
+    })
+}
+
+// Diffusion D u_xx (second order central differences)
+pub fn diffusion(
+    grid: &Grid,
+    coeff: f64,
+    left: Boundary,
+    right: Boundary,
+) -> Result<MolOperator, &'static str> {
+    let k = coeff / grid.h().powi(2);
+    three_point(grid, [k, -2.0 * k, k], left, right, true)
+}
+
+// Advection -c u_x (first order upwind differences)
+pub fn advection(
+    grid: &Grid,
+    velocity: f64,
+    left: Boundary,
+    right: Boundary,
+) -> Result<MolOperator, &'static str> {
+    let k = velocity / grid.h();
+    let stencil = if velocity >= 0.0 {
+        [k, -k, 0.0]
+    } else {
+        [0.0, k, -k]
+    };
+    three_point(grid, stencil, left, right, false)
+}
+
+// ODE system u' = L u + f of an operator, with N the number of cells
+#[derive(Debug, Clone, PartialEq)]
+pub struct MolSystem<N: Dim + DimName> {
+    // Discretized operator
+    pub operator: MolOperator,
+    // Dimension of the states
+    dim: PhantomData<N>,
+}
+
+impl<N: Dim + DimName> MolSystem<N> {
+    pub fn new(operator: MolOperator) -> Result<Self, &'static str> {
+        if operator.grid.cells != N::dim() {
+            return Err("[MOL] State dimension must match the number of cells");
+        }
+        Ok(MolSystem {
+            operator,
+            dim: PhantomData,
+        })
+    }
+}
+
+impl<N: Dim + DimName> OdeSystem<N> for MolSystem<N>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    fn dynamics(&self, _t: f64, y: &OVector<f64, N>) -> OVector<f64, N> {
+        let mut dy = self.operator.matrix.mul_vec(y);
+        for (dy_i, f_i) in dy.iter_mut().zip(&self.operator.forcing) {
+            *dy_i += f_i;
+        }
+        dy
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runge_kutta::common::IntegOptions;
+    use crate::runge_kutta::fixed::FixedStep;
+    use crate::runge_kutta::rk_simp::RK4;
+    use na::{Const, DVector};
+    use std::f64::consts::PI;
+
+    #[test]
+    fn test_mol_operators() {
+        let grid = Grid::new(0.0, 1.0, 100).unwrap();
+        let x = DVector::from_vec(grid.points());
+
+        // second order consistent with u_xx, including the boundary rows
+        let dirichlet = diffusion(
+            &grid,
+            1.0,
+            Boundary::Dirichlet(0.0),
+            Boundary::Dirichlet(0.0),
+        )
+        .unwrap();
+        let u = x.map(|x| (PI * x).sin());
+        let lap = dirichlet.matrix.mul_vec(&u);
+        assert!((lap + PI.powi(2) * &u).amax() < 0.05);
+
+        // closed boundaries and periodic grids conserve mass (columns sum to zero)
+        let ones = DVector::from_element(100, 1.0);
+        let closed = diffusion(&grid, 0.3, Boundary::Neumann(0.0), Boundary::Neumann(0.0)).unwrap();
+        assert!((closed.matrix.to_dense().transpose() * &ones).amax() < 1e-9);
+        let periodic = diffusion(&grid, 0.3, Boundary::Periodic, Boundary::Periodic)
+            .unwrap()
+            .add(&advection(&grid, -2.0, Boundary::Periodic, Boundary::Periodic).unwrap())
+            .unwrap();
+        assert!((periodic.matrix.to_dense().transpose() * &ones).amax() < 1e-9);
+        assert_eq!(periodic.matrix.nnz(), 300);
+
+        // the boundary value is a steady state of the diffusion, and the flux given
+        // by a neumann condition is a linear one
+        let steady =
+            diffusion(&grid, 1.0, Boundary::Dirichlet(1.0), Boundary::Neumann(0.5)).unwrap();
+        let line = x.map(|x| 1.0 + 0.5 * x);
+        let rate = steady.matrix.mul_vec(&line) + DVector::from_vec(steady.forcing.clone());
+        assert!(rate.amax() < 1e-8);
+
+        // heat equation through the OdeSystem: u = exp(-pi^2 t) sin(pi x)
+        let grid = Grid::new(0.0, 1.0, 20).unwrap();
+        let heat = MolSystem::<Const<20>>::new(
+            diffusion(
+                &grid,
+                1.0,
+                Boundary::Dirichlet(0.0),
+                Boundary::Dirichlet(0.0),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let u_0 =
+            OVector::<f64, Const<20>>::from_iterator(grid.points().iter().map(|x| (PI * x).sin()));
+        let ans = RK4
+            .integrate(heat, 0.0, u_0, 0.1, 1e-4, IntegOptions::default())
+            .unwrap();
+        assert!((ans.last_y() - u_0 * (-PI.powi(2) * 0.1).exp()).amax() < 2e-3);
+        assert!(MolSystem::<Const<10>>::new(periodic).is_err());
+    }
+}
@@ -0,0 +1,137 @@
+/// ndarray Interop (systems/ndarray_interop)
+///
+/// Only available with the `ndarray` feature.
+///
+/// Lets dynamics written against `ndarray::Array1<f64>` be used directly with the
+/// integrators:
+/// - `Array1<f64>` is a `State`, so it works with `RKStepper::integrate_state` and
+///   `StateSystem`
+/// - `ArraySystem` wraps a function `Fn(f64, &Array1<f64>) -> Array1<f64>` into an
+///   `OdeSystem` for every integrator
+/// - `to_array1` / `from_array1` convert single states and `states_to_array2`
+///   collects a whole solution into one array (one row per solution time)
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DefaultAllocator, Dim, DimName, OVector};
+use ndarray::{Array1, Array2};
+
+// local imports
+use super::state::State;
+use super::OdeSystem;
+use crate::runge_kutta::common::IntegResult;
+
+// === End Imports ===
+
+impl State for Array1<f64> {
+    fn dim(&self) -> usize {
+        self.len()
+    }
+
+    fn get(&self, i: usize) -> f64 {
+        self[i]
+    }
+
+    fn get_mut(&mut self, i: usize) -> &mut f64 {
+        &mut self[i]
+    }
+
+    fn axpy(&mut self, a: f64, x: &Self) {
+        self.scaled_add(a, x);
+    }
+}
+
+// Copies a vector into a new array
+pub fn to_array1<N: Dim + DimName>(v: &OVector<f64, N>) -> Array1<f64>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    v.iter().cloned().collect()
+}
+
+// Copies an array into a vector. Errors if the lengths differ
+pub fn from_array1<N: Dim + DimName>(a: &Array1<f64>) -> Result<OVector<f64, N>, &'static str>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    if a.len() != N::dim() {
+        return Err("[NDARRAY] Array length does not match the state dimension");
+    }
+    Ok(OVector::<f64, N>::from_iterator(a.iter().cloned()))
+}
+
+// Collects the states of a solution into an array with one row per solution time
+pub fn states_to_array2<N: Dim + DimName>(results: &IntegResult<N>) -> Array2<f64>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    Array2::from_shape_fn((results.states.len(), N::dim()), |(i, j)| {
+        results.states[i][j]
+    })
+}
+
+// Dynamics written against ndarray arrays, usable with every integrator
+#[derive(Debug, Clone)]
+pub struct ArraySystem<F> {
+    // Dynamics f(t, y) on arrays
+    pub fxn: F,
+}
+
+impl<F> ArraySystem<F> {
+    pub fn new(fxn: F) -> Self {
+        ArraySystem { fxn }
+    }
+}
+
+impl<N: Dim + DimName, F> OdeSystem<N> for ArraySystem<F>
+where
+    F: Fn(f64, &Array1<f64>) -> Array1<f64>,
+    DefaultAllocator: Allocator<N>,
+{
+    fn dynamics(&self, t: f64, y: &OVector<f64, N>) -> OVector<f64, N> {
+        from_array1(&(self.fxn)(t, &to_array1(y)))
+            .expect("[NDARRAY] Dynamics returned an array of the wrong length")
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runge_kutta::common::IntegOptions;
+    use crate::runge_kutta::fixed::FixedStep;
+    use crate::runge_kutta::rk_simp::RK4;
+    use na::{Vector2, U2, U3};
+    use ndarray::array;
+
+    fn spring(_t: f64, y: &Array1<f64>) -> Array1<f64> {
+        array![y[1], -y[0]]
+    }
+
+    #[test]
+    fn test_ndarray_interop() {
+        let y_0 = Vector2::new(1.0, 0.0);
+        let ans = RK4
+            .integrate(
+                ArraySystem::new(spring),
+                0.0,
+                y_0,
+                1.0,
+                0.01,
+                IntegOptions::default(),
+            )
+            .unwrap();
+        let states = states_to_array2(&ans);
+        assert_eq!(states.dim(), (ans.states.len(), 2));
+        assert!((states[[states.nrows() - 1, 0]] - 1.0_f64.cos()).abs() < 1e-10);
+
+        // stepping directly on arrays gives the same solution
+        let (_, arrays) = RK4.integrate_state(&spring, 0.0, to_array1(&y_0), 1.0, 0.01);
+        let last = from_array1::<U2>(arrays.last().unwrap()).unwrap();
+        assert!((last - ans.last_y()).norm() < 1e-14);
+
+        assert!(from_array1::<U3>(&array![1.0, 2.0]).is_err());
+    }
+}
@@ -0,0 +1,116 @@
+/// Nonstiff Test Systems (systems/nonstiff)
+///
+/// Standard nonstiff benchmark problems as ready to use `OdeSystem` implementations
+/// with reference solutions, the counterpart of `stiff`:
+/// - Arenstorf orbit: a periodic orbit of the restricted three body problem, whose
+///   reference solution at the end of a period is the initial state
+/// - DETEST class D: the planar Kepler problem at eccentricities from 0.1 (D1) to
+///   0.9 (D5), with the analytic solution from Kepler's equation
+///
+/// The Arenstorf orbit and its period are from Hairer, Norsett & Wanner "Solving
+/// Ordinary Differential Equations I" (pg 129-130), where it is the test problem of
+/// DOPRI5 and DOP853. Class D is from the DETEST suite (Hull, Enright, Fellen &
+/// Sedgwick, "Comparing Numerical Methods for Ordinary Differential Equations",
+/// 1972), integrated to t = 20.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::{Vector4, U4};
+
+// local imports
+use super::OdeSystem;
+
+// === End Imports ===
+
+// === Arenstorf ===
+// y_1'' = y_1 + 2 y_2' - mu' (y_1 + mu) / D_1 - mu (y_1 - mu') / D_2
+// y_2'' = y_2 - 2 y_1' - mu' y_2 / D_1 - mu y_2 / D_2
+// with mu' = 1 - mu, D_1 = ((y_1 + mu)^2 + y_2^2)^1.5, D_2 = ((y_1 - mu')^2 + y_2^2)^1.5
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Arenstorf {
+    // Mass ratio of the moon to the earth and moon
+    pub mu: f64,
+}
+
+impl Arenstorf {
+    // Earth moon mass ratio of the published orbit
+    pub const EARTH_MOON: Arenstorf = Arenstorf { mu: 0.012_277_471 };
+}
+
+impl OdeSystem<U4> for Arenstorf {
+    fn dynamics(&self, _t: f64, y: &Vector4<f64>) -> Vector4<f64> {
+        let mu_p = 1.0 - self.mu;
+        let d_1 = ((y[0] + self.mu).powi(2) + y[1].powi(2)).powf(1.5);
+        let d_2 = ((y[0] - mu_p).powi(2) + y[1].powi(2)).powf(1.5);
+        Vector4::new(
+            y[2],
+            y[3],
+            y[0] + 2.0 * y[3] - mu_p * (y[0] + self.mu) / d_1 - self.mu * (y[0] - mu_p) / d_2,
+            y[1] - 2.0 * y[2] - mu_p * y[1] / d_1 - self.mu * y[1] / d_2,
+        )
+    }
+}
+
+// Period of the orbit
+pub const ARENSTORF_END: f64 = 17.065_216_560_157_96;
+
+lazy_static! {
+    // Initial state, which the orbit returns to after a period
+    pub static ref ARENSTORF_INIT: Vector4<f64> =
+        Vector4::new(0.994, 0.0, 0.0, -2.001_585_106_379_082_5);
+}
+
+// === DETEST class D ===
+// y_1'' = -y_1 / r^3
+// y_2'' = -y_2 / r^3
+// from pericenter at (1 - e, 0) with velocity (0, sqrt((1 + e) / (1 - e)))
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DetestD {
+    // Eccentricity of the orbit
+    pub e: f64,
+}
+
+impl DetestD {
+    // Problems D1 to D5
+    pub const D1: DetestD = DetestD { e: 0.1 };
+    pub const D2: DetestD = DetestD { e: 0.3 };
+    pub const D3: DetestD = DetestD { e: 0.5 };
+    pub const D4: DetestD = DetestD { e: 0.7 };
+    pub const D5: DetestD = DetestD { e: 0.9 };
+
+    pub fn init(&self) -> Vector4<f64> {
+        Vector4::new(
+            1.0 - self.e,
+            0.0,
+            0.0,
+            ((1.0 + self.e) / (1.0 - self.e)).sqrt(),
+        )
+    }
+
+    // Analytic solution at t, from the eccentric anomaly solving Kepler's equation
+    // E - e sin E = t
+    pub fn exact(&self, t: f64) -> Vector4<f64> {
+        let mut anomaly = t;
+        for _ in 0..100 {
+            let delta = (anomaly - self.e * anomaly.sin() - t) / (1.0 - self.e * anomaly.cos());
+            anomaly -= delta;
+            if delta.abs() < 1e-15 {
+                break;
+            }
+        }
+        let (sin, cos) = anomaly.sin_cos();
+        let b = (1.0 - self.e.powi(2)).sqrt();
+        let rate = 1.0 / (1.0 - self.e * cos);
+        Vector4::new(cos - self.e, b * sin, -sin * rate, b * cos * rate)
+    }
+}
+
+impl OdeSystem<U4> for DetestD {
+    fn dynamics(&self, _t: f64, y: &Vector4<f64>) -> Vector4<f64> {
+        let r3 = (y[0].powi(2) + y[1].powi(2)).powf(1.5);
+        Vector4::new(y[2], y[3], -y[0] / r3, -y[1] / r3)
+    }
+}
+
+pub const DETEST_D_END: f64 = 20.0;
@@ -0,0 +1,606 @@
+/// PDE Benchmark Systems (systems/pde)
+///
+/// Semidiscretized PDEs as ready to use `OdeSystem` implementations, sized by the
+/// state dimension they are used with, for exercising the sparse jacobian, krylov
+/// and exponential code paths on problems with many states:
+/// - Heat equation u_t = D (u_xx + u_yy) in 1D and 2D on the unit interval / square,
+///   zero at the boundary, discretized on the cell centered grid of `mol`. The
+///   initial condition is a sum of two sine modes, which are eigenvectors of the
+///   discrete laplacian, so the reference solution of the semidiscrete system is
+///   known exactly at all times (`reference`)
+/// - Brusselator reaction diffusion in 1D (Hairer & Wanner "Solving Ordinary
+///   Differential Equations II", pg 148, problem BRUSS) and 2D (pg 151,
+///   BRUSS-2D with its pulse of forcing from t = 1.1)
+///
+/// Each system also splits its dynamics into the stiff diffusion (a constant
+/// sparse matrix, see `diffusion_matrix`) and the non-stiff reaction, for
+/// implicit-explicit and exponential integrators. The Brusselators give their full
+/// jacobian as a sparse matrix.
+///
+/// Brusselator states interleave the two species (u_0, v_0, u_1, v_1, ...). Their
+/// reference values were computed with DOPRI78 (`runge_kutta::rk_embed`) at an atol
+/// and rtol of 1e-13 and agree with RK4 at a step of 1e-4 to 1e-12.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DefaultAllocator, Dim, DimName, OVector, U20};
+
+// local imports
+use super::mol::{diffusion, Boundary, Grid};
+use super::OdeSystem;
+use crate::utils::sparse::CsrMatrix;
+
+// Standard library imports
+use std::f64::consts::PI;
+
+// === End Imports ===
+
+// Sine modes (k, amplitude) of the heat equation initial conditions, per direction
+const HEAT_MODES: [(f64, f64); 2] = [(1.0, 1.0), (3.0, 0.5)];
+
+// State of dimension N with entries f(i)
+fn state<N: Dim + DimName>(
+    dim: usize,
+    f: impl Fn(usize) -> f64,
+) -> Result<OVector<f64, N>, &'static str>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    if N::dim() != dim {
+        return Err("[PDE] State dimension does not match the discretization");
+    }
+    Ok(OVector::<f64, N>::from_fn(|i, _| f(i)))
+}
+
+// Sparse matrix of a (row major) grid operator on a tensor product grid, the
+// kronecker sum I (x) a + b (x) I of 1D operators in x (a) and y (b)
+fn kron_sum(a: &CsrMatrix, b: &CsrMatrix) -> Result<CsrMatrix, &'static str> {
+    let (nx, ny) = (a.nrows, b.nrows);
+    let mut triplets = Vec::new();
+    for j in 0..ny {
+        for i in 0..nx {
+            let (cols, vals) = a.row(i);
+            triplets.extend(
+                cols.iter()
+                    .zip(vals)
+                    .map(|(c, v)| (j * nx + i, j * nx + c, *v)),
+            );
+            let (cols, vals) = b.row(j);
+            triplets.extend(
+                cols.iter()
+                    .zip(vals)
+                    .map(|(c, v)| (j * nx + i, c * nx + i, *v)),
+            );
+        }
+    }
+    CsrMatrix::from_triplets(nx * ny, nx * ny, &triplets)
+}
+
+// === Heat equation ===
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heat1D {
+    // Grid of the unknowns
+    pub grid: Grid,
+    // Diffusivity D
+    pub diffusivity: f64,
+    // Discrete laplacian times the diffusivity
+    matrix: CsrMatrix,
+}
+
+impl Heat1D {
+    pub fn new(cells: usize, diffusivity: f64) -> Result<Self, &'static str> {
+        let grid = Grid::new(0.0, 1.0, cells)?;
+        let zero = Boundary::Dirichlet(0.0);
+        Ok(Heat1D {
+            grid,
+            diffusivity,
+            matrix: diffusion(&grid, diffusivity, zero, zero)?.matrix,
+        })
+    }
+
+    pub fn diffusion_matrix(&self) -> &CsrMatrix {
+        &self.matrix
+    }
+
+    pub fn init<N: Dim + DimName>(&self) -> Result<OVector<f64, N>, &'static str>
+    where
+        DefaultAllocator: Allocator<N>,
+    {
+        self.reference(0.0)
+    }
+
+    // Exact solution of the semidiscrete system at t
+    pub fn reference<N: Dim + DimName>(&self, t: f64) -> Result<OVector<f64, N>, &'static str>
+    where
+        DefaultAllocator: Allocator<N>,
+    {
+        let x = self.grid.points();
+        let rate = |k: f64| discrete_eigenvalue(k, self.grid.h(), self.diffusivity);
+        state(self.grid.cells, |i| {
+            HEAT_MODES
+                .iter()
+                .map(|(k, amp)| amp * (rate(*k) * t).exp() * (k * PI * x[i]).sin())
+                .sum()
+        })
+    }
+}
+
+// Eigenvalue of the 1D cell centered laplacian (times D) for the sine mode k
+fn discrete_eigenvalue(k: f64, h: f64, diffusivity: f64) -> f64 {
+    -4.0 * diffusivity / h.powi(2) * (0.5 * k * PI * h).sin().powi(2)
+}
+
+impl<N: Dim + DimName> OdeSystem<N> for Heat1D
+where
+    DefaultAllocator: Allocator<N>,
+{
+    fn dynamics(&self, _t: f64, y: &OVector<f64, N>) -> OVector<f64, N> {
+        self.matrix.mul_vec(y)
+    }
+}
+
+// Heat equation on the unit square, with the unknowns ordered row by row (x fastest)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heat2D {
+    // Grid of the unknowns in each direction
+    pub grid: Grid,
+    // Diffusivity D
+    pub diffusivity: f64,
+    // Discrete laplacian times the diffusivity
+    matrix: CsrMatrix,
+}
+
+impl Heat2D {
+    // Square grid with `cells` cells per side
+    pub fn new(cells: usize, diffusivity: f64) -> Result<Self, &'static str> {
+        let grid = Grid::new(0.0, 1.0, cells)?;
+        let zero = Boundary::Dirichlet(0.0);
+        let lap = diffusion(&grid, diffusivity, zero, zero)?.matrix;
+        Ok(Heat2D {
+            grid,
+            diffusivity,
+            matrix: kron_sum(&lap, &lap)?,
+        })
+    }
+
+    pub fn diffusion_matrix(&self) -> &CsrMatrix {
+        &self.matrix
+    }
+
+    pub fn init<N: Dim + DimName>(&self) -> Result<OVector<f64, N>, &'static str>
+    where
+        DefaultAllocator: Allocator<N>,
+    {
+        self.reference(0.0)
+    }
+
+    // Exact solution of the semidiscrete system at t. The modes are products of the
+    // 1D ones (k_x, k_y) = (1, 1) and (3, 1) with amplitudes 1 and 0.5
+    pub fn reference<N: Dim + DimName>(&self, t: f64) -> Result<OVector<f64, N>, &'static str>
+    where
+        DefaultAllocator: Allocator<N>,
+    {
+        let (n, h) = (self.grid.cells, self.grid.h());
+        let x = self.grid.points();
+        let rate = |k: f64| discrete_eigenvalue(k, h, self.diffusivity);
+        state(n * n, |idx| {
+            let (i, j) = (idx % n, idx / n);
+            HEAT_MODES
+                .iter()
+                .map(|(k, amp)| {
+                    amp * ((rate(*k) + rate(1.0)) * t).exp()
+                        * (k * PI * x[i]).sin()
+                        * (PI * x[j]).sin()
+                })
+                .sum()
+        })
+    }
+}
+
+impl<N: Dim + DimName> OdeSystem<N> for Heat2D
+where
+    DefaultAllocator: Allocator<N>,
+{
+    fn dynamics(&self, _t: f64, y: &OVector<f64, N>) -> OVector<f64, N> {
+        self.matrix.mul_vec(y)
+    }
+}
+
+// === Brusselator ===
+// u_t = A + u^2 v - (B + 1) u + alpha u_xx
+// v_t = B u - u^2 v + alpha v_xx
+//
+// on the grid x_i = i / (cells + 1), i = 1..cells with u = 1, v = 3 at x = 0, 1
+// and u = 1 + sin(2 pi x), v = 3 initially (A = 1, B = 3)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Brusselator1D {
+    // Number of grid points (the state has two species per point)
+    pub cells: usize,
+    // Diffusion coefficient alpha
+    pub alpha: f64,
+    // Diffusion of both species (with the boundary values as constant forcing)
+    matrix: CsrMatrix,
+    forcing: Vec<f64>,
+}
+
+impl Brusselator1D {
+    // Standard configuration alpha = 1 / 50
+    pub fn standard(cells: usize) -> Result<Self, &'static str> {
+        Brusselator1D::new(cells, 0.02)
+    }
+
+    pub fn new(cells: usize, alpha: f64) -> Result<Self, &'static str> {
+        if cells < 2 {
+            return Err("[PDE] Brusselator needs at least two grid points");
+        }
+        let k = alpha * (cells + 1).pow(2) as f64;
+        let mut triplets = Vec::new();
+        let mut forcing = vec![0.0; 2 * cells];
+        for i in 0..cells {
+            for (s, edge) in [(0, 1.0), (1, 3.0)] {
+                let row = 2 * i + s;
+                triplets.push((row, row, -2.0 * k));
+                if i > 0 {
+                    triplets.push((row, row - 2, k));
+                } else {
+                    forcing[row] += k * edge;
+                }
+                if i + 1 < cells {
+                    triplets.push((row, row + 2, k));
+                } else {
+                    forcing[row] += k * edge;
+                }
+            }
+        }
+        Ok(Brusselator1D {
+            cells,
+            alpha,
+            matrix: CsrMatrix::from_triplets(2 * cells, 2 * cells, &triplets)?,
+            forcing,
+        })
+    }
+
+    pub fn init<N: Dim + DimName>(&self) -> Result<OVector<f64, N>, &'static str>
+    where
+        DefaultAllocator: Allocator<N>,
+    {
+        let h = 1.0 / (self.cells + 1) as f64;
+        state(2 * self.cells, |idx| match idx % 2 {
+            0 => 1.0 + (2.0 * PI * (idx / 2 + 1) as f64 * h).sin(),
+            _ => 3.0,
+        })
+    }
+
+    pub fn diffusion_matrix(&self) -> &CsrMatrix {
+        &self.matrix
+    }
+
+    // Stiff part: diffusion with the boundary values
+    pub fn diffusion<N: Dim + DimName>(&self, y: &OVector<f64, N>) -> OVector<f64, N>
+    where
+        DefaultAllocator: Allocator<N>,
+    {
+        let mut dy = self.matrix.mul_vec(y);
+        for (dy_i, f_i) in dy.iter_mut().zip(&self.forcing) {
+            *dy_i += f_i;
+        }
+        dy
+    }
+
+    // Non-stiff part: the reaction at each grid point
+    pub fn reaction<N: Dim + DimName>(&self, y: &OVector<f64, N>) -> OVector<f64, N>
+    where
+        DefaultAllocator: Allocator<N>,
+    {
+        brusselator_reaction(y, 1.0, 3.0)
+    }
+
+    // Jacobian of the full dynamics
+    pub fn jacobian<N: Dim + DimName>(&self, y: &OVector<f64, N>) -> CsrMatrix
+    where
+        DefaultAllocator: Allocator<N>,
+    {
+        with_reaction_jacobian(&self.matrix, y, 3.0)
+    }
+}
+
+// Reaction terms A + u^2 v - (B + 1) u and B u - u^2 v of interleaved states
+fn brusselator_reaction<N: Dim + DimName>(y: &OVector<f64, N>, a: f64, b: f64) -> OVector<f64, N>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    let mut dy = OVector::<f64, N>::zeros();
+    for p in 0..N::dim() / 2 {
+        let (u, v) = (y[2 * p], y[2 * p + 1]);
+        dy[2 * p] = a + u * u * v - (b + 1.0) * u;
+        dy[2 * p + 1] = b * u - u * u * v;
+    }
+    dy
+}
+
+// Diffusion matrix plus the 2x2 reaction jacobian blocks of interleaved states
+fn with_reaction_jacobian<N: Dim + DimName>(
+    diff: &CsrMatrix,
+    y: &OVector<f64, N>,
+    b: f64,
+) -> CsrMatrix
+where
+    DefaultAllocator: Allocator<N>,
+{
+    let mut triplets = Vec::new();
+    for i in 0..diff.nrows {
+        let (cols, vals) = diff.row(i);
+        triplets.extend(cols.iter().zip(vals).map(|(j, v)| (i, *j, *v)));
+    }
+    for p in 0..N::dim() / 2 {
+        let (u, v) = (y[2 * p], y[2 * p + 1]);
+        let (r_u, r_v) = (2 * p, 2 * p + 1);
+        triplets.push((r_u, r_u, 2.0 * u * v - (b + 1.0)));
+        triplets.push((r_u, r_v, u * u));
+        triplets.push((r_v, r_u, b - 2.0 * u * v));
+        triplets.push((r_v, r_v, -u * u));
+    }
+    CsrMatrix::from_triplets(diff.nrows, diff.ncols, &triplets).unwrap()
+}
+
+impl<N: Dim + DimName> OdeSystem<N> for Brusselator1D
+where
+    DefaultAllocator: Allocator<N>,
+{
+    fn dynamics(&self, _t: f64, y: &OVector<f64, N>) -> OVector<f64, N> {
+        self.diffusion(y) + self.reaction(y)
+    }
+}
+
+pub const BRUSS_1D_END: f64 = 10.0;
+
+lazy_static! {
+    // Reference solution at t = 10 of the standard configuration with 10 grid points
+    pub static ref BRUSS_1D_REF: OVector<f64, U20> = OVector::<f64, U20>::from_column_slice(&[
+        0.772_314_910_826_201_5,
+        3.290_021_469_346_591_7,
+        0.605_730_853_431_489,
+        3.498_267_417_933_142,
+        0.506_073_664_005_619_4,
+        3.615_648_479_391_905,
+        0.454_280_044_156_137_93,
+        3.670_841_278_522_629,
+        0.432_829_334_480_36,
+        3.692_896_404_641_919_7,
+        0.432_949_157_368_526_3,
+        3.696_966_912_829_218,
+        0.454_683_302_742_963,
+        3.681_712_327_732_689,
+        0.506_872_975_747_700_4,
+        3.629_672_472_503_904_7,
+        0.606_964_917_811_667_4,
+        3.510_674_462_819_618,
+        0.773_525_356_194_847_3,
+        3.296_892_747_176_490_6,
+    ]);
+}
+
+// u_t = 1 + u^2 v - 4.4 u + alpha (u_xx + u_yy) + f(x, y, t)
+// v_t = 3.4 u - u^2 v + alpha (v_xx + v_yy)
+//
+// periodic on the grid x_i = i / cells, y_j = j / cells (row by row, x fastest)
+// with f = 5 inside the disk of radius 0.1 around (0.3, 0.6) from t = 1.1, and
+// u = 22 y (1 - y)^1.5, v = 27 x (1 - x)^1.5 initially
+#[derive(Debug, Clone, PartialEq)]
+pub struct Brusselator2D {
+    // Number of grid points per side
+    pub cells: usize,
+    // Diffusion coefficient alpha
+    pub alpha: f64,
+    // Periodic diffusion of both species
+    matrix: CsrMatrix,
+}
+
+impl Brusselator2D {
+    // Time from which the forcing pulse is on
+    pub const PULSE_START: f64 = 1.1;
+
+    // Standard configuration alpha = 0.1
+    pub fn standard(cells: usize) -> Result<Self, &'static str> {
+        Brusselator2D::new(cells, 0.1)
+    }
+
+    pub fn new(cells: usize, alpha: f64) -> Result<Self, &'static str> {
+        if cells < 3 {
+            return Err("[PDE] Brusselator needs at least three grid points per side");
+        }
+        let k = alpha * (cells * cells) as f64;
+        let lap = CsrMatrix::from_triplets(
+            cells,
+            cells,
+            &(0..cells)
+                .flat_map(|i| {
+                    [
+                        (i, i, -2.0 * k),
+                        (i, (i + 1) % cells, k),
+                        (i, (i + cells - 1) % cells, k),
+                    ]
+                })
+                .collect::<Vec<_>>(),
+        )?;
+        // both species of each grid point share the scalar laplacian
+        let scalar = kron_sum(&lap, &lap)?;
+        let mut triplets = Vec::new();
+        for i in 0..scalar.nrows {
+            let (cols, vals) = scalar.row(i);
+            for (j, v) in cols.iter().zip(vals) {
+                triplets.push((2 * i, 2 * j, *v));
+                triplets.push((2 * i + 1, 2 * j + 1, *v));
+            }
+        }
+        let n = 2 * scalar.nrows;
+        Ok(Brusselator2D {
+            cells,
+            alpha,
+            matrix: CsrMatrix::from_triplets(n, n, &triplets)?,
+        })
+    }
+
+    pub fn init<N: Dim + DimName>(&self) -> Result<OVector<f64, N>, &'static str>
+    where
+        DefaultAllocator: Allocator<N>,
+    {
+        let n = self.cells;
+        state(2 * n * n, |idx| {
+            let p = idx / 2;
+            let (x, y) = ((p % n) as f64 / n as f64, (p / n) as f64 / n as f64);
+            match idx % 2 {
+                0 => 22.0 * y * (1.0 - y).powf(1.5),
+                _ => 27.0 * x * (1.0 - x).powf(1.5),
+            }
+        })
+    }
+
+    pub fn diffusion_matrix(&self) -> &CsrMatrix {
+        &self.matrix
+    }
+
+    // Stiff part: diffusion
+    pub fn diffusion<N: Dim + DimName>(&self, y: &OVector<f64, N>) -> OVector<f64, N>
+    where
+        DefaultAllocator: Allocator<N>,
+    {
+        self.matrix.mul_vec(y)
+    }
+
+    // Non-stiff part: the reaction at each grid point and the forcing pulse
+    pub fn reaction<N: Dim + DimName>(&self, t: f64, y: &OVector<f64, N>) -> OVector<f64, N>
+    where
+        DefaultAllocator: Allocator<N>,
+    {
+        let mut dy = brusselator_reaction(y, 1.0, 3.4);
+        if t >= Brusselator2D::PULSE_START {
+            let n = self.cells;
+            for p in 0..n * n {
+                let (x, y) = ((p % n) as f64 / n as f64, (p / n) as f64 / n as f64);
+                if (x - 0.3).powi(2) + (y - 0.6).powi(2) <= 0.01 {
+                    dy[2 * p] += 5.0;
+                }
+            }
+        }
+        dy
+    }
+
+    // Jacobian of the full dynamics
+    pub fn jacobian<N: Dim + DimName>(&self, y: &OVector<f64, N>) -> CsrMatrix
+    where
+        DefaultAllocator: Allocator<N>,
+    {
+        with_reaction_jacobian(&self.matrix, y, 3.4)
+    }
+}
+
+impl<N: Dim + DimName> OdeSystem<N> for Brusselator2D
+where
+    DefaultAllocator: Allocator<N>,
+{
+    fn dynamics(&self, t: f64, y: &OVector<f64, N>) -> OVector<f64, N> {
+        self.diffusion(y) + self.reaction(t, y)
+    }
+
+    // the forcing pulse switches on
+    fn breakpoints(&self) -> Vec<f64> {
+        vec![Brusselator2D::PULSE_START]
+    }
+}
+
+pub const BRUSS_2D_END: f64 = 1.5;
+
+// Reference solution at t = 1.5 of the standard configuration with 8 x 8 grid points,
+// as (state index, value): u and v at the origin and u at the two forced points
+pub const BRUSS_2D_REF: [(usize, f64); 4] = [
+    (0, 1.178_296_334_666_950_2),
+    (1, 1.999_625_208_573_956_3),
+    (84, 1.627_077_012_714_185_6),
+    (86, 1.628_591_999_091_056_5),
+];
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runge_kutta::adaptive::AdaptiveStep;
+    use crate::runge_kutta::common::IntegOptions;
+    use crate::runge_kutta::fixed::FixedStep;
+    use crate::runge_kutta::rk_embed::DOPRI78;
+    use crate::runge_kutta::rk_simp::RK4;
+    use crate::utils::finite_diff::check_jacobian;
+    use crate::utils::linalg::expmv;
+    use na::{Const, OMatrix};
+
+    #[test]
+    fn test_pde_benchmarks() {
+        // heat equation against the exact semidiscrete solution
+        let heat = Heat1D::new(20, 1.0).unwrap();
+        let ans = RK4
+            .integrate(
+                heat.clone(),
+                0.0,
+                heat.init::<U20>().unwrap(),
+                0.05,
+                1e-4,
+                IntegOptions::default(),
+            )
+            .unwrap();
+        assert!((ans.last_y() - heat.reference::<U20>(0.05).unwrap()).amax() < 1e-10);
+        assert!(heat.init::<Const<10>>().is_err());
+
+        // 2D through the krylov exponential of the sparse matrix
+        let heat = Heat2D::new(16, 0.5).unwrap();
+        assert_eq!(heat.diffusion_matrix().nnz(), 5 * 256 - 4 * 16);
+        let u_0 = heat.init::<Const<256>>().unwrap();
+        let matrix = heat.diffusion_matrix();
+        let u_1 = expmv(
+            |v: &OVector<f64, Const<256>>| matrix.mul_vec(v),
+            &u_0,
+            0.1,
+            30,
+            1e-12,
+        );
+        assert!((u_1.unwrap() - heat.reference::<Const<256>>(0.1).unwrap()).amax() < 1e-10);
+
+        // brusselators against the embedded references
+        let bruss = Brusselator1D::standard(10).unwrap();
+        let y_0 = bruss.init::<U20>().unwrap();
+        let ans = RK4
+            .integrate(
+                bruss.clone(),
+                0.0,
+                y_0,
+                BRUSS_1D_END,
+                1e-3,
+                IntegOptions::default(),
+            )
+            .unwrap();
+        assert!((ans.last_y() - *BRUSS_1D_REF).amax() < 1e-9);
+        let split = bruss.diffusion(&y_0) + bruss.reaction(&y_0);
+        assert_eq!(split, bruss.dynamics(0.0, &y_0));
+        let jacobian = |_t: f64, y: &OVector<f64, U20>| {
+            OMatrix::<f64, U20, U20>::from_iterator(bruss.jacobian(y).to_dense().iter().cloned())
+        };
+        assert!(check_jacobian(&bruss, jacobian, 0.0, ans.last_y(), 1e-6).is_empty());
+
+        let bruss = Brusselator2D::standard(8).unwrap();
+        let options = IntegOptions {
+            atol: Some(OVector::<f64, Const<128>>::repeat(1e-11)),
+            rtol: Some(1e-11),
+            ..IntegOptions::default()
+        };
+        let y_0 = bruss.init::<Const<128>>().unwrap();
+        let ans = DOPRI78
+            .integrate(bruss.clone(), 0.0, y_0, BRUSS_2D_END, options)
+            .unwrap();
+        for (i, val) in BRUSS_2D_REF.iter() {
+            assert!((ans.last_y()[*i] - val).abs() < 1e-9);
+        }
+        // the pulse is what sets the forced points apart
+        assert!(ans.last_y()[84] > ans.last_y()[0] + 0.4);
+    }
+}
@@ -0,0 +1,273 @@
+/// Nondimensionalization (systems/scaling)
+///
+/// Integrates a system in nondimensional form from user supplied characteristic
+/// scales of length, time and mass. Orbital problems in particular mix positions of
+/// thousands of km with velocities of a few km/s, so a single absolute tolerance
+/// (and the step size heuristics) fit neither, and forgetting to scale one of the
+/// state, time span or tolerances consistently is a common source of error.
+///
+/// Every state component is given its dimension as exponents of (length, time, mass),
+/// from which its characteristic scale follows. `Scaling` then converts:
+/// - the dynamics: `system` wraps an `OdeSystem` into its nondimensional form
+/// - states and times: `to_nondim` / `to_dim` and `time_to_nondim` / `time_to_dim`
+/// - integration options: `options` / `options_parallel` scale the absolute
+///   tolerance, step bounds, breakpoints and slab length (relative tolerances are
+///   already dimensionless)
+/// - results: `result_to_dim` converts the solution back into dimensional units
+///
+/// Diagnostics registered in the options see the nondimensional states.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DefaultAllocator, Dim, DimName, OVector, U6};
+
+// local imports
+use super::OdeSystem;
+use crate::ridc::common::IntegOptionsParallel;
+use crate::runge_kutta::common::{IntegOptions, IntegResult};
+
+// === End Imports ===
+
+// Dimension of a quantity as exponents of (length, time, mass)
+pub type Dimension = (i32, i32, i32);
+pub const LENGTH: Dimension = (1, 0, 0);
+pub const VELOCITY: Dimension = (1, -1, 0);
+pub const ACCELERATION: Dimension = (1, -2, 0);
+pub const MASS: Dimension = (0, 0, 1);
+pub const DIMENSIONLESS: Dimension = (0, 0, 0);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scaling<N: Dim + DimName>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    // Characteristic length
+    pub length: f64,
+    // Characteristic time
+    pub time: f64,
+    // Characteristic mass
+    pub mass: f64,
+    // Characteristic scale of each state component
+    scales: OVector<f64, N>,
+}
+
+impl<N: Dim + DimName> Scaling<N>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    // Scaling from the characteristic scales and the dimension of each state component
+    pub fn new(
+        length: f64,
+        time: f64,
+        mass: f64,
+        dims: &[Dimension],
+    ) -> Result<Self, &'static str> {
+        if dims.len() != N::dim() {
+            return Err("[SCALING] One dimension is needed per state component");
+        }
+        if !(length > 0.0 && time > 0.0 && mass > 0.0) {
+            return Err("[SCALING] Characteristic scales must be positive");
+        }
+        let scales = OVector::<f64, N>::from_iterator(
+            dims.iter()
+                .map(|(l, t, m)| length.powi(*l) * time.powi(*t) * mass.powi(*m)),
+        );
+        Ok(Scaling {
+            length,
+            time,
+            mass,
+            scales,
+        })
+    }
+
+    // Characteristic scale of each state component
+    pub fn scales(&self) -> &OVector<f64, N> {
+        &self.scales
+    }
+
+    // Dimensional state to nondimensional
+    pub fn to_nondim(&self, y: &OVector<f64, N>) -> OVector<f64, N> {
+        y.component_div(&self.scales)
+    }
+
+    // Nondimensional state to dimensional
+    pub fn to_dim(&self, x: &OVector<f64, N>) -> OVector<f64, N> {
+        x.component_mul(&self.scales)
+    }
+
+    // Dimensional time (or time span) to nondimensional
+    pub fn time_to_nondim(&self, t: f64) -> f64 {
+        t / self.time
+    }
+
+    // Nondimensional time (or time span) to dimensional
+    pub fn time_to_dim(&self, tau: f64) -> f64 {
+        tau * self.time
+    }
+
+    // Nondimensional form of the dynamics
+    pub fn system<S: OdeSystem<N>>(&self, system: S) -> ScaledSystem<S, N> {
+        ScaledSystem {
+            system,
+            time: self.time,
+            scales: self.scales.clone(),
+        }
+    }
+
+    // Integration options for the nondimensional problem
+    pub fn options(&self, opts: IntegOptions<N>) -> IntegOptions<N> {
+        IntegOptions {
+            atol: opts.atol.map(|atol| self.to_nondim(&atol)),
+            min_step: opts.min_step.map(|h| self.time_to_nondim(h)),
+            max_step: opts.max_step.map(|h| self.time_to_nondim(h)),
+            first_step: opts.first_step.map(|h| self.time_to_nondim(h)),
+            breakpoints: opts.breakpoints.map(|bps| self.times_to_nondim(&bps)),
+            ..opts
+        }
+    }
+
+    // Parallel integration options for the nondimensional problem
+    pub fn options_parallel(&self, opts: IntegOptionsParallel<N>) -> IntegOptionsParallel<N> {
+        IntegOptionsParallel {
+            atol: opts.atol.map(|atol| self.to_nondim(&atol)),
+            min_step: opts.min_step.map(|h| self.time_to_nondim(h)),
+            max_step: opts.max_step.map(|h| self.time_to_nondim(h)),
+            first_step: opts.first_step.map(|h| self.time_to_nondim(h)),
+            slab_length: opts.slab_length.map(|h| self.time_to_nondim(h)),
+            breakpoints: opts.breakpoints.map(|bps| self.times_to_nondim(&bps)),
+            ..opts
+        }
+    }
+
+    // Converts the solution of the nondimensional problem into dimensional units
+    pub fn result_to_dim(&self, mut results: IntegResult<N>) -> IntegResult<N> {
+        results.t = self.time_to_dim(results.t);
+        for t in results.times.iter_mut().chain(results.restarts.iter_mut()) {
+            *t = self.time_to_dim(*t);
+        }
+        for state in results.states.iter_mut() {
+            *state = self.to_dim(state);
+        }
+        for rejected in results.rejected.iter_mut() {
+            rejected.t = self.time_to_dim(rejected.t);
+            rejected.step = self.time_to_dim(rejected.step);
+        }
+        if let Some(stop) = results.stopped.as_mut() {
+            stop.t = self.time_to_dim(stop.t);
+        }
+        results.next_step = results.next_step.map(|h| self.time_to_dim(h));
+        results
+    }
+
+    fn times_to_nondim(&self, times: &[f64]) -> Vec<f64> {
+        times.iter().map(|t| self.time_to_nondim(*t)).collect()
+    }
+}
+
+impl Scaling<U6> {
+    // Canonical units of a cartesian (position, velocity) orbit state: the length
+    // scale and the time scale for which the gravitational parameter mu is 1
+    pub fn canonical(length: f64, mu: f64) -> Result<Self, &'static str> {
+        let time = (length.powi(3) / mu).sqrt();
+        Scaling::new(
+            length,
+            time,
+            1.0,
+            &[LENGTH, LENGTH, LENGTH, VELOCITY, VELOCITY, VELOCITY],
+        )
+    }
+}
+
+// Nondimensional form of a system: dx/dtau = T / s * f(T tau, s x)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScaledSystem<S, N: Dim + DimName>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    // Dimensional system
+    pub system: S,
+    // Characteristic time
+    time: f64,
+    // Characteristic scale of each state component
+    scales: OVector<f64, N>,
+}
+
+impl<S: OdeSystem<N>, N: Dim + DimName> OdeSystem<N> for ScaledSystem<S, N>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    fn dynamics(&self, tau: f64, x: &OVector<f64, N>) -> OVector<f64, N> {
+        let y = x.component_mul(&self.scales);
+        self.system
+            .dynamics(tau * self.time, &y)
+            .component_div(&self.scales)
+            * self.time
+    }
+
+    fn breakpoints(&self) -> Vec<f64> {
+        self.system
+            .breakpoints()
+            .iter()
+            .map(|t| t / self.time)
+            .collect()
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runge_kutta::adaptive::AdaptiveStep;
+    use crate::runge_kutta::rk_embed::RKF45;
+    use crate::systems::astro::TwoBodyJ2;
+    use na::{Vector2, Vector6};
+    use std::f64::consts::PI;
+
+    #[test]
+    fn test_canonical_orbit() {
+        // circular orbit in km and km/s
+        let mu = TwoBodyJ2::EARTH.mu;
+        let two_body = TwoBodyJ2::new(mu, 0.0, TwoBodyJ2::EARTH.r_eq);
+        let r = 7000.0;
+        let y_0 = Vector6::new(r, 0.0, 0.0, 0.0, (mu / r).sqrt(), 0.0);
+        let period = 2.0 * PI * (r.powi(3) / mu).sqrt();
+
+        let scaling = Scaling::canonical(r, mu).unwrap();
+        let x_0 = Vector6::new(1.0, 0.0, 0.0, 0.0, 1.0, 0.0);
+        assert!((scaling.to_nondim(&y_0) - x_0).norm() < 1e-15);
+        assert!((scaling.time_to_nondim(period) - 2.0 * PI).abs() < 1e-12);
+
+        // tolerances given in km and km/s
+        let options = scaling.options(IntegOptions {
+            atol: Some(Vector6::new(1e-6, 1e-6, 1e-6, 1e-9, 1e-9, 1e-9)),
+            rtol: Some(1e-10),
+            ..IntegOptions::default()
+        });
+        assert!((options.atol.unwrap()[0] - 1e-6 / r).abs() < 1e-20);
+
+        let ans = RKF45
+            .integrate(
+                scaling.system(two_body),
+                0.0,
+                scaling.to_nondim(&y_0),
+                scaling.time_to_nondim(period),
+                options,
+            )
+            .unwrap();
+        let ans = scaling.result_to_dim(ans);
+        assert!((ans.t - period).abs() < 1e-9 * period);
+        assert!((ans.last_y() - y_0).norm() < 1e-3);
+    }
+
+    #[test]
+    fn test_scaling_round_trip() {
+        let scaling = Scaling::new(10.0, 2.0, 1.0, &[LENGTH, ACCELERATION]).unwrap();
+        assert_eq!(scaling.scales(), &Vector2::new(10.0, 2.5));
+        let y = Vector2::new(3.0, -7.0);
+        assert_eq!(scaling.to_dim(&scaling.to_nondim(&y)), y);
+        assert!(Scaling::<na::U2>::new(10.0, 2.0, 1.0, &[LENGTH]).is_err());
+        assert!(Scaling::<na::U1>::new(10.0, 0.0, 1.0, &[MASS]).is_err());
+    }
+}
@@ -0,0 +1,269 @@
+/// Generic States (systems/state)
+///
+/// Abstracts the state of an initial value problem behind the `State` trait so
+/// dynamics can be written against the user's own containers (a struct of named
+/// fields, a plain `Vec<f64>` or array, ...) rather than nalgebra vectors. A state
+/// only has to expose its scalar components, the vector operations the integrators
+/// need (axpy, norm) are provided from those.
+///
+/// There are two ways to integrate such dynamics:
+/// - `RKStepper::integrate_state` steps explicit Runge-Kutta methods directly on the
+///   state type. Dynamics writing into an output state can be stepped in place with
+///   `integrate_state_in_place`, which reuses its temporaries (see utils::arena)
+/// - `StateSystem` wraps the dynamics into an `OdeSystem` so every integrator (adaptive,
+///   implicit, RIDC) can be used. The state is copied into and out of an `OVector`
+///   around each dynamics evaluation, which the user no longer has to do by hand
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DefaultAllocator, Dim, DimName, OVector};
+
+// local imports
+use super::OdeSystem;
+
+// === End Imports ===
+
+pub trait State: Clone {
+    // Number of scalar components of the state
+    fn dim(&self) -> usize;
+
+    // Value of component i
+    fn get(&self, i: usize) -> f64;
+
+    // Mutable reference to component i
+    fn get_mut(&mut self, i: usize) -> &mut f64;
+
+    // Overwrites the components with those of x (of the same dimension) without
+    // allocating
+    fn copy_from(&mut self, x: &Self) {
+        for i in 0..self.dim() {
+            *self.get_mut(i) = x.get(i);
+        }
+    }
+
+    // self += a * x
+    fn axpy(&mut self, a: f64, x: &Self) {
+        for i in 0..self.dim() {
+            *self.get_mut(i) += a * x.get(i);
+        }
+    }
+
+    // Euclidean norm of the components
+    fn norm(&self) -> f64 {
+        (0..self.dim())
+            .map(|i| self.get(i).powi(2))
+            .sum::<f64>()
+            .sqrt()
+    }
+}
+
+impl<N: Dim + DimName> State for OVector<f64, N>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    fn dim(&self) -> usize {
+        self.len()
+    }
+
+    fn get(&self, i: usize) -> f64 {
+        self[i]
+    }
+
+    fn get_mut(&mut self, i: usize) -> &mut f64 {
+        &mut self[i]
+    }
+
+    fn copy_from(&mut self, x: &Self) {
+        na::Matrix::copy_from(self, x);
+    }
+
+    fn axpy(&mut self, a: f64, x: &Self) {
+        na::Matrix::axpy(self, a, x, 1.0);
+    }
+
+    fn norm(&self) -> f64 {
+        na::Matrix::norm(self)
+    }
+}
+
+impl State for Vec<f64> {
+    fn dim(&self) -> usize {
+        self.len()
+    }
+
+    fn get(&self, i: usize) -> f64 {
+        self[i]
+    }
+
+    fn get_mut(&mut self, i: usize) -> &mut f64 {
+        &mut self[i]
+    }
+
+    fn copy_from(&mut self, x: &Self) {
+        self.copy_from_slice(x);
+    }
+}
+
+impl<const K: usize> State for [f64; K] {
+    fn dim(&self) -> usize {
+        K
+    }
+
+    fn get(&self, i: usize) -> f64 {
+        self[i]
+    }
+
+    fn get_mut(&mut self, i: usize) -> &mut f64 {
+        &mut self[i]
+    }
+}
+
+// Copies the components of a state into a vector
+pub fn to_vector<N: Dim + DimName, X: State>(x: &X) -> OVector<f64, N>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    assert_eq!(x.dim(), N::dim(), "[STATE] Dimension mismatch");
+    OVector::<f64, N>::from_fn(|i, _| x.get(i))
+}
+
+// Copies the components of a vector into (a copy of) the template state
+pub fn from_vector<N: Dim + DimName, X: State>(v: &OVector<f64, N>, template: &X) -> X
+where
+    DefaultAllocator: Allocator<N>,
+{
+    assert_eq!(template.dim(), N::dim(), "[STATE] Dimension mismatch");
+    let mut x = template.clone();
+    for i in 0..N::dim() {
+        *x.get_mut(i) = v[i];
+    }
+    x
+}
+
+// Dynamics written against a custom state type, usable with every integrator
+#[derive(Debug, Clone)]
+pub struct StateSystem<F, X> {
+    // Dynamics f(t, x) on the custom state
+    pub fxn: F,
+    // State used as the layout when converting vectors back into states
+    template: X,
+}
+
+impl<F, X: State> StateSystem<F, X> {
+    pub fn new(fxn: F, template: X) -> Self {
+        StateSystem { fxn, template }
+    }
+
+    // Converts a state into the vector the integrators work with
+    pub fn to_vector<N: Dim + DimName>(&self, x: &X) -> OVector<f64, N>
+    where
+        DefaultAllocator: Allocator<N>,
+    {
+        to_vector(x)
+    }
+
+    // Converts an integrator vector (e.g. a solution) back into a state
+    pub fn from_vector<N: Dim + DimName>(&self, v: &OVector<f64, N>) -> X
+    where
+        DefaultAllocator: Allocator<N>,
+    {
+        from_vector(v, &self.template)
+    }
+}
+
+impl<N: Dim + DimName, F, X> OdeSystem<N> for StateSystem<F, X>
+where
+    F: Fn(f64, &X) -> X,
+    X: State,
+    DefaultAllocator: Allocator<N>,
+{
+    fn dynamics(&self, t: f64, y: &OVector<f64, N>) -> OVector<f64, N> {
+        to_vector(&(self.fxn)(t, &self.from_vector(y)))
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runge_kutta::adaptive::AdaptiveStep;
+    use crate::runge_kutta::common::IntegOptions;
+    use crate::runge_kutta::fixed::FixedStep;
+    use crate::runge_kutta::rk_embed::RKF45;
+    use crate::runge_kutta::rk_simp::RK4;
+    use na::{Vector2, U2};
+
+    // struct-of-fields state of a 1-d particle
+    #[derive(Debug, Clone, PartialEq)]
+    struct Particle {
+        pos: f64,
+        vel: f64,
+    }
+
+    impl State for Particle {
+        fn dim(&self) -> usize {
+            2
+        }
+
+        fn get(&self, i: usize) -> f64 {
+            [self.pos, self.vel][i]
+        }
+
+        fn get_mut(&mut self, i: usize) -> &mut f64 {
+            match i {
+                0 => &mut self.pos,
+                _ => &mut self.vel,
+            }
+        }
+    }
+
+    fn spring(_t: f64, x: &Particle) -> Particle {
+        Particle {
+            pos: x.vel,
+            vel: -x.pos,
+        }
+    }
+
+    #[test]
+    fn test_state_integration() {
+        let x_0 = Particle { pos: 1.0, vel: 0.0 };
+        let t_end = std::f64::consts::PI;
+
+        // stepping directly on the custom state matches stepping on vectors
+        let (times, states) = RK4.integrate_state(&spring, 0.0, x_0.clone(), t_end, 0.01);
+        assert_eq!(times.len(), states.len());
+        let ans = RK4
+            .integrate(
+                |_t: f64, y: &Vector2<f64>| Vector2::new(y[1], -y[0]),
+                0.0,
+                Vector2::new(1.0, 0.0),
+                t_end,
+                0.01,
+                IntegOptions::default(),
+            )
+            .unwrap();
+        let last = states.last().unwrap();
+        assert!((to_vector::<U2, _>(last) - ans.last_y()).norm() < 1e-14);
+        assert!((last.pos + 1.0).abs() < 1e-8 && last.vel.abs() < 1e-8);
+
+        // the adapter makes the same dynamics usable with any integrator
+        let system = StateSystem::new(spring, x_0.clone());
+        let y_0: Vector2<f64> = system.to_vector(&x_0);
+        let ans = RKF45
+            .integrate(system.clone(), 0.0, y_0, t_end, IntegOptions::default())
+            .unwrap();
+        let x_end = system.from_vector(ans.last_y());
+        assert!((x_end.pos + 1.0).abs() < 1e-2);
+
+        // plain containers are states too
+        let (_, arrays) =
+            RK4.integrate_state(&|_t: f64, y: &[f64; 1]| [-y[0]], 0.0, [1.0], 1.0, 0.1);
+        assert!((arrays.last().unwrap()[0] - (-1.0_f64).exp()).abs() < 1e-6);
+        let mut v = vec![1.0, 2.0];
+        v.axpy(2.0, &vec![1.0, 0.5]);
+        assert_eq!(v, vec![3.0, 3.0]);
+        assert!((State::norm(&v) - 18.0_f64.sqrt()).abs() < 1e-15);
+    }
+}
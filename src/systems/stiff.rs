@@ -0,0 +1,276 @@
+/// Stiff Test Systems (systems/stiff)
+///
+/// Standard stiff benchmark problems as ready to use `OdeSystem` implementations
+/// along with high accuracy reference solutions at the end of the usual
+/// integration interval:
+/// - Van der Pol oscillator in the scaled form with stiffness parameter mu = 1e6
+/// - Robertson chemical kinetics
+/// - HIRES (High Irradiance RESponse) photomorphogenesis model
+///
+/// Problem definitions and reference values are taken from the "Test Set for IVP
+/// Solvers" (Mazzia & Magherini, University of Bari) and from Hairer & Wanner
+/// "Solving Ordinary Differential Equations II" (pg 144-157). The reference values
+/// were computed there with RADAU5 at tolerances near machine precision.
+///
+/// Documented error levels for fixed step backward euler (`utils::euler`), given as
+/// the max relative error against the reference:
+/// - Robertson, 1000 steps: ~6e-4 (first order, ~6e-5 with 10000 steps)
+/// - HIRES, 2000 steps: ~1.4e-2
+/// - Van der Pol: fixed steps cannot follow the relaxation jumps, so only the
+///   slow manifold up to t = 0.5 is checked (5000 steps: ~2e-5). With step
+///   doubling and local extrapolation the full interval to t = 2 is reached
+///   (tol 1e-4, ~3800 steps: ~3e-4; tol 1e-6, ~38000 steps: ~3e-6)
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::{OVector, Vector2, Vector3, U2, U3, U8};
+
+// local imports
+use super::OdeSystem;
+
+// === End Imports ===
+
+// === Van der Pol ===
+// y_1' = y_2
+// y_2' = mu ((1 - y_1^2) y_2 - y_1)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VanDerPol {
+    // Stiffness parameter
+    pub mu: f64,
+}
+
+impl VanDerPol {
+    // Standard stiff configuration
+    pub const STIFF: VanDerPol = VanDerPol { mu: 1.0e6 };
+
+    pub fn new(mu: f64) -> Self {
+        VanDerPol { mu }
+    }
+}
+
+impl OdeSystem<U2> for VanDerPol {
+    fn dynamics(&self, _t: f64, y: &Vector2<f64>) -> Vector2<f64> {
+        Vector2::new(y[1], self.mu * ((1.0 - y[0].powi(2)) * y[1] - y[0]))
+    }
+}
+
+pub const VAN_DER_POL_END: f64 = 2.0;
+
+lazy_static! {
+    pub static ref VAN_DER_POL_INIT: Vector2<f64> = Vector2::new(2.0, 0.0);
+    // Reference solution at t = 2 for mu = 1e6
+    pub static ref VAN_DER_POL_REF: Vector2<f64> =
+        Vector2::new(1.706_167_732_170_483, -0.892_809_701_024_812_5);
+}
+
+// === Robertson ===
+// y_1' = -0.04 y_1 + 1e4 y_2 y_3
+// y_2' = 0.04 y_1 - 1e4 y_2 y_3 - 3e7 y_2^2
+// y_3' = 3e7 y_2^2
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Robertson;
+
+impl OdeSystem<U3> for Robertson {
+    fn dynamics(&self, _t: f64, y: &Vector3<f64>) -> Vector3<f64> {
+        let slow = 0.04 * y[0];
+        let mid = 1.0e4 * y[1] * y[2];
+        let fast = 3.0e7 * y[1].powi(2);
+        Vector3::new(-slow + mid, slow - mid - fast, fast)
+    }
+}
+
+pub const ROBERTSON_END: f64 = 40.0;
+
+lazy_static! {
+    pub static ref ROBERTSON_INIT: Vector3<f64> = Vector3::new(1.0, 0.0, 0.0);
+    // Reference solution at t = 40
+    pub static ref ROBERTSON_REF: Vector3<f64> = Vector3::new(
+        0.715_827_068_719_377_2,
+        0.918_553_476_452_979_8e-5,
+        0.284_163_745_745_813_1,
+    );
+}
+
+// === HIRES ===
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hires;
+
+impl OdeSystem<U8> for Hires {
+    fn dynamics(&self, _t: f64, y: &OVector<f64, U8>) -> OVector<f64, U8> {
+        let react = 280.0 * y[5] * y[7];
+        OVector::<f64, U8>::from_column_slice(&[
+            -1.71 * y[0] + 0.43 * y[1] + 8.32 * y[2] + 0.0007,
+            1.71 * y[0] - 8.75 * y[1],
+            -10.03 * y[2] + 0.43 * y[3] + 0.035 * y[4],
+            8.32 * y[1] + 1.71 * y[2] - 1.12 * y[3],
+            -1.745 * y[4] + 0.43 * y[5] + 0.43 * y[6],
+            -react + 0.69 * y[3] + 1.71 * y[4] - 0.43 * y[5] + 0.69 * y[6],
+            react - 1.81 * y[6],
+            -react + 1.81 * y[6],
+        ])
+    }
+}
+
+pub const HIRES_END: f64 = 321.8122;
+
+lazy_static! {
+    pub static ref HIRES_INIT: OVector<f64, U8> =
+        OVector::<f64, U8>::from_column_slice(&[1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0057]);
+    // Reference solution at t = 321.8122
+    pub static ref HIRES_REF: OVector<f64, U8> = OVector::<f64, U8>::from_column_slice(&[
+        0.737_131_257_332_566_8e-3,
+        0.144_248_572_631_618_5e-3,
+        0.588_872_974_096_757_5e-4,
+        0.117_565_134_328_333_1e-2,
+        0.238_635_619_883_133_1e-2,
+        0.623_896_825_274_279_6e-2,
+        0.284_999_839_518_576_9e-2,
+        0.285_000_160_481_423_1e-2,
+    ]);
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::euler::{bwd_euler, bwd_euler_lazy};
+    use crate::utils::kron::LazyJacobian;
+    use crate::utils::solver_dim::{SolverAllocator, SolverDim};
+    use na::DefaultAllocator;
+
+    // Fixed step backward euler from t = 0 to t_f
+    fn bwd_euler_march<N: SolverDim, S: OdeSystem<N>>(
+        sys: &S,
+        y_0: &OVector<f64, N>,
+        t_f: f64,
+        steps: usize,
+    ) -> OVector<f64, N>
+    where
+        DefaultAllocator: SolverAllocator<N>,
+    {
+        let h = t_f / steps as f64;
+        let mut y = y_0.clone();
+        for i in 0..steps {
+            y = bwd_euler(i as f64 * h, &y, sys, (i + 1) as f64 * h).unwrap();
+        }
+        y
+    }
+
+    // Backward euler with step doubling error control and local extrapolation
+    // from t = 0 to t_f. Steps where newton fails to converge are retried with a
+    // quarter of the step
+    fn bwd_euler_doubling<N: SolverDim, S: OdeSystem<N>>(
+        sys: &S,
+        y_0: &OVector<f64, N>,
+        t_f: f64,
+        tol: f64,
+    ) -> (OVector<f64, N>, usize)
+    where
+        DefaultAllocator: SolverAllocator<N>,
+    {
+        let mut t = 0.0;
+        let mut h = 1e-6;
+        let mut y = y_0.clone();
+        let mut steps = 0;
+        while t < t_f {
+            let t_nxt = (t + h).min(t_f);
+            let t_mid = 0.5 * (t + t_nxt);
+            let full = bwd_euler(t, &y, sys, t_nxt);
+            let half =
+                bwd_euler(t, &y, sys, t_mid).and_then(|y_m| bwd_euler(t_mid, &y_m, sys, t_nxt));
+            match (full, half) {
+                (Ok(y_full), Ok(y_half)) => {
+                    let err = (&y_half - &y_full)
+                        .component_div(&y_half.abs().add_scalar(1.0))
+                        .abs()
+                        .max();
+                    if err <= tol {
+                        y = 2.0 * y_half - y_full;
+                        t = t_nxt;
+                        steps += 1;
+                    }
+                    h *= (0.9 * (tol / err.max(1e-16)).sqrt()).max(0.2).min(5.0);
+                }
+                _ => h /= 4.0,
+            }
+            assert!(h > 1e-15, "step size underflow at t = {}", t);
+        }
+        (y, steps)
+    }
+
+    fn max_rel_err<N: SolverDim>(y: &OVector<f64, N>, y_ref: &OVector<f64, N>) -> f64
+    where
+        DefaultAllocator: SolverAllocator<N>,
+    {
+        (y - y_ref).component_div(y_ref).abs().max()
+    }
+
+    #[test]
+    fn test_robertson_bwd_euler() {
+        let coarse = bwd_euler_march(&Robertson, &ROBERTSON_INIT, ROBERTSON_END, 1000);
+        let fine = bwd_euler_march(&Robertson, &ROBERTSON_INIT, ROBERTSON_END, 10000);
+        let err_coarse = max_rel_err(&coarse, &ROBERTSON_REF);
+        let err_fine = max_rel_err(&fine, &ROBERTSON_REF);
+
+        assert!(err_coarse < 1e-3);
+        assert!(err_fine < 1e-4);
+        // first order convergence
+        assert!(err_coarse / err_fine > 8.0);
+        // linear invariant y_1 + y_2 + y_3 = 1 is preserved by the implicit step
+        assert!((fine.sum() - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_robertson_lazy_jacobian() {
+        let steps = 1000;
+        let h = ROBERTSON_END / steps as f64;
+        let mut lazy = LazyJacobian::default();
+        let mut y = *ROBERTSON_INIT;
+        for i in 0..steps {
+            y = bwd_euler_lazy(i as f64 * h, &y, &Robertson, (i + 1) as f64 * h, &mut lazy)
+                .unwrap();
+        }
+
+        let full = bwd_euler_march(&Robertson, &ROBERTSON_INIT, ROBERTSON_END, steps);
+        assert!(max_rel_err(&y, &full) < 1e-4);
+        assert!(max_rel_err(&y, &ROBERTSON_REF) < 1e-3);
+        // the jacobian is only refreshed a small fraction of the steps
+        assert!(lazy.jacobian_evals() < steps / 10);
+        assert_eq!(lazy.factorizations(), lazy.jacobian_evals());
+    }
+
+    #[test]
+    fn test_hires_bwd_euler() {
+        let ans = bwd_euler_march(&Hires, &HIRES_INIT, HIRES_END, 2000);
+        assert!(max_rel_err(&ans, &HIRES_REF) < 2e-2);
+    }
+
+    #[test]
+    fn test_van_der_pol_slow_manifold() {
+        // Away from the relaxation jumps the solution follows the slow manifold
+        // ln(y_1) - y_1^2 / 2 = t + C up to O(1 / mu). Value at t = 0.5 from the
+        // reduced equation
+        const REDUCED_Y1: f64 = 1.596_768_394_457_374_3;
+        let ans = bwd_euler_march(&VanDerPol::STIFF, &VAN_DER_POL_INIT, 0.5, 5000);
+        assert!((ans[0] - REDUCED_Y1).abs() < 1e-4);
+        assert!((ans[1] - REDUCED_Y1 / (1.0 - REDUCED_Y1.powi(2))).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_van_der_pol_reference() {
+        // The step doubling march follows both relaxation jumps to the end of the
+        // standard interval and lands on the reference at roughly the tolerance
+        let (coarse, coarse_steps) =
+            bwd_euler_doubling(&VanDerPol::STIFF, &VAN_DER_POL_INIT, VAN_DER_POL_END, 1e-4);
+        let (fine, fine_steps) =
+            bwd_euler_doubling(&VanDerPol::STIFF, &VAN_DER_POL_INIT, VAN_DER_POL_END, 1e-6);
+        let err_coarse = max_rel_err(&coarse, &VAN_DER_POL_REF);
+        let err_fine = max_rel_err(&fine, &VAN_DER_POL_REF);
+
+        assert!(err_coarse < 1e-3);
+        assert!(err_fine < 1e-5);
+        assert!(err_coarse / err_fine > 30.0);
+        assert!(coarse_steps < fine_steps);
+    }
+}
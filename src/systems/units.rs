@@ -0,0 +1,176 @@
+/// Units of Measure (systems/units)
+///
+/// Only available with the `uom` feature.
+///
+/// Lets the state, its time derivative and time be expressed with `uom` quantities,
+/// so a dynamics function returning e.g. a length where a velocity is expected does
+/// not compile. The state type implements `UnitState`, naming its time derivative
+/// (`Rate`) and how both map to plain components. `UnitSystem` wraps dynamics of the
+/// form `Fn(Time, &X) -> X::Rate` into an `OdeSystem` for every integrator.
+///
+/// uom stores every quantity in SI base units, so the integrators work on the SI
+/// values of the components (time in seconds). Components of very different
+/// magnitudes can additionally be nondimensionalized with `scaling`.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DefaultAllocator, Dim, DimName, OVector};
+use uom::si::f64::Time;
+use uom::si::time::second;
+
+// local imports
+use super::OdeSystem;
+
+// Standard library imports
+use std::marker::PhantomData;
+
+// === End Imports ===
+
+pub trait UnitState: Clone {
+    // Time derivative of the state (every quantity divided by time)
+    type Rate;
+
+    // Components of the state in SI base units
+    fn to_si(&self) -> Vec<f64>;
+
+    // State from its components in SI base units
+    fn from_si(si: &[f64]) -> Self;
+
+    // Components of a rate in SI base units (same order as the state)
+    fn rate_to_si(rate: &Self::Rate) -> Vec<f64>;
+}
+
+// Time in seconds (the time variable of the integrators)
+pub fn seconds(t: Time) -> f64 {
+    t.get::<second>()
+}
+
+// Time of the integrators (seconds) as a quantity
+pub fn time(t: f64) -> Time {
+    Time::new::<second>(t)
+}
+
+// Dynamics over a state with units, usable with every integrator
+#[derive(Debug, Clone)]
+pub struct UnitSystem<F, X> {
+    // Dynamics dX/dt = f(t, X)
+    pub fxn: F,
+    // Type of the state
+    state: PhantomData<fn() -> X>,
+}
+
+impl<F, X: UnitState> UnitSystem<F, X> {
+    pub fn new(fxn: F) -> Self {
+        UnitSystem {
+            fxn,
+            state: PhantomData,
+        }
+    }
+
+    // Converts a state into the vector the integrators work with
+    pub fn to_vector<N: Dim + DimName>(&self, x: &X) -> OVector<f64, N>
+    where
+        DefaultAllocator: Allocator<N>,
+    {
+        OVector::<f64, N>::from_iterator(x.to_si())
+    }
+
+    // Converts an integrator vector (e.g. a solution) back into a state
+    pub fn from_vector<N: Dim + DimName>(&self, v: &OVector<f64, N>) -> X
+    where
+        DefaultAllocator: Allocator<N>,
+    {
+        X::from_si(v.as_slice())
+    }
+}
+
+impl<N: Dim + DimName, F, X> OdeSystem<N> for UnitSystem<F, X>
+where
+    F: Fn(Time, &X) -> X::Rate,
+    X: UnitState,
+    DefaultAllocator: Allocator<N>,
+{
+    fn dynamics(&self, t: f64, y: &OVector<f64, N>) -> OVector<f64, N> {
+        let rate = (self.fxn)(time(t), &self.from_vector(y));
+        OVector::<f64, N>::from_iterator(X::rate_to_si(&rate))
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runge_kutta::common::IntegOptions;
+    use crate::runge_kutta::fixed::FixedStep;
+    use crate::runge_kutta::rk_simp::RK4;
+    use na::Vector2;
+    use uom::si::acceleration::meter_per_second_squared;
+    use uom::si::f64::{Acceleration, Length, Velocity};
+    use uom::si::length::{kilometer, meter};
+    use uom::si::velocity::meter_per_second;
+
+    #[derive(Debug, Clone)]
+    struct Fall {
+        height: Length,
+        speed: Velocity,
+    }
+
+    struct FallRate {
+        climb: Velocity,
+        accel: Acceleration,
+    }
+
+    impl UnitState for Fall {
+        type Rate = FallRate;
+
+        fn to_si(&self) -> Vec<f64> {
+            vec![self.height.value, self.speed.value]
+        }
+
+        fn from_si(si: &[f64]) -> Self {
+            Fall {
+                height: Length::new::<meter>(si[0]),
+                speed: Velocity::new::<meter_per_second>(si[1]),
+            }
+        }
+
+        fn rate_to_si(rate: &FallRate) -> Vec<f64> {
+            vec![rate.climb.value, rate.accel.value]
+        }
+    }
+
+    #[test]
+    fn test_unit_system() {
+        let gravity = Acceleration::new::<meter_per_second_squared>(-9.81);
+        let system = UnitSystem::new(move |_t: Time, x: &Fall| FallRate {
+            climb: x.speed,
+            accel: gravity,
+        });
+        let x_0 = Fall {
+            height: Length::new::<kilometer>(1.0),
+            speed: Velocity::new::<meter_per_second>(20.0),
+        };
+        let y_0: Vector2<f64> = system.to_vector(&x_0);
+        assert_eq!(y_0, Vector2::new(1000.0, 20.0));
+
+        let t_end = time(2.0);
+        let ans = RK4
+            .integrate(
+                system.clone(),
+                0.0,
+                y_0,
+                seconds(t_end),
+                0.5,
+                IntegOptions::default(),
+            )
+            .unwrap();
+        let x_end = system.from_vector(ans.last_y());
+
+        // free fall is quadratic in time so RK4 is exact
+        let expected = x_0.height + x_0.speed * t_end + 0.5 * gravity * t_end * t_end;
+        assert!((x_end.height - expected).abs().get::<meter>() < 1e-9);
+        assert!((x_end.speed - (x_0.speed + gravity * t_end)).abs().value < 1e-12);
+    }
+}
@@ -0,0 +1,39 @@
+/// Allocation Counting (test_fxns/alloc_count)
+///
+/// Global allocator of the unit tests, counting the allocations made by each
+/// thread, for tests checking that a code path does not allocate. Counts are per
+/// thread so tests running in parallel do not disturb each other: take the
+/// difference of `allocations` around the code checked.
+///
+// === Begin Imports ===
+// Standard library imports
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+// === End Imports ===
+
+// Counts the allocations made by each thread
+struct Counting;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+// Allocations made by the current thread so far
+pub fn allocations() -> usize {
+    ALLOCATIONS.with(|count| count.get())
+}
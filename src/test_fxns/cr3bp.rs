@@ -70,6 +70,7 @@ mod tests {
             atol: Some(Vector4::repeat(1e-9_f64)),
             rtol: Some(1e-6_f64),
             min_step: Some(1e-10_f64),
+            ..IntegOptions::default()
         };
         let truth_res = RK32
             .clone()
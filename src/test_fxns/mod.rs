@@ -1,3 +1,5 @@
+#[cfg(test)]
+pub mod alloc_count;
 pub mod cr3bp;
 pub mod kepler;
 pub mod one_d;
@@ -44,6 +44,7 @@ mod tests {
             atol: Some(Vector4::repeat(1e-14_f64)),
             rtol: Some(1e-11_f64),
             min_step: Some(1e-10_f64),
+            ..IntegOptions::default()
         };
         //let step_true = 1e-_f64;
         let truth_res = CASH_KARP45
@@ -75,6 +76,7 @@ mod tests {
                     atol: Some(Vector4::repeat(*acc)),
                     rtol: Some(*acc * 10e3_f64),
                     min_step: Some(1e-12_f64),
+                    ..IntegOptions::default()
                 };
                 let start = Instant::now();
                 let (dur, diff) = match integ {
@@ -113,6 +115,7 @@ mod tests {
                                 atol: Some(Vector4::repeat(*acc * 10e2_f64)),
                                 rtol: Some(*acc * 10e5_f64),
                                 min_step: Some(1e-10_f64),
+                                ..IntegOptions::default()
                             };
                             let ans_reg = dopri78
                                 .integrate(
@@ -148,6 +151,7 @@ mod tests {
                     corrector_order: Some(n),
                     restart_length: Some(50),
                     convergence_tol: Some(1e-10_f64),
+                    ..IntegOptionsParallel::default()
                 };
 
                 let start = Instant::now();
@@ -169,6 +173,7 @@ mod tests {
                     corrector_order: Some(c),
                     restart_length: Some(100),
                     convergence_tol: Some(1e-8_f64),
+                    ..IntegOptionsParallel::default()
                 };
                 let start = Instant::now();
                 let ans_par = RK4
@@ -82,6 +82,7 @@ mod tests {
                     atol: Some(Vector6::repeat(*acc)),
                     rtol: Some(*acc * 10e3_f64),
                     min_step: Some(1e-10_f64),
+                    ..IntegOptions::default()
                 };
                 let start = Instant::now();
                 let (dur, diff_pos, diff_vel) = match integ {
@@ -132,6 +133,7 @@ mod tests {
                                 atol: Some(Vector6::repeat(*acc * 10e2_f64)),
                                 rtol: Some(*acc * 10e5_f64),
                                 min_step: Some(1e-10_f64),
+                                ..IntegOptions::default()
                             };
                             let ans_reg = DOPRI78
                                 .integrate(
@@ -170,6 +172,7 @@ mod tests {
                     corrector_order: Some(c),
                     restart_length: Some(100),
                     convergence_tol: Some(1e-8_f64),
+                    ..IntegOptionsParallel::default()
                 };
                 let start = Instant::now();
                 let ans_par = RK4
@@ -205,6 +208,7 @@ mod tests {
                     corrector_order: Some(n),
                     restart_length: Some(100),
                     convergence_tol: Some(1e-8),
+                    ..IntegOptionsParallel::default()
                 };
                 let start = Instant::now();
                 let ans_par = RK32
@@ -1,5 +1,7 @@
 pub mod cr3bp;
 pub mod kepler;
 pub mod one_d;
+pub mod parity;
 pub mod pert;
+pub mod properties;
 pub mod two_d;
@@ -41,6 +41,7 @@ mod tests {
                     atol: Some(Vector1::repeat(*acc)),
                     rtol: Some(*acc * 10e3_f64),
                     min_step: Some(1e-10_f64),
+                    ..IntegOptions::default()
                 };
                 let start = Instant::now();
                 let (dur, diff) = match integ {
@@ -79,6 +80,7 @@ mod tests {
                                 atol: Some(Vector1::repeat(*acc * 10e2_f64)),
                                 rtol: Some(*acc * 10e5_f64),
                                 min_step: Some(1e-10_f64),
+                                ..IntegOptions::default()
                             };
                             let ans_reg = dopri78
                                 .integrate(
@@ -113,6 +115,7 @@ mod tests {
                     corrector_order: Some(n),
                     restart_length: Some(200),
                     convergence_tol: Some(1e-8_f64),
+                    ..IntegOptionsParallel::default()
                 };
 
                 let start = Instant::now();
@@ -141,6 +144,7 @@ mod tests {
                     corrector_order: Some(c),
                     restart_length: Some(100),
                     convergence_tol: Some(1e-8_f64),
+                    ..IntegOptionsParallel::default()
                 };
                 let start = Instant::now();
                 let ans_par = RK4
@@ -176,6 +180,7 @@ mod tests {
                 corrector_order: Some(3),
                 restart_length: Some(20 * n),
                 convergence_tol: Some(1e-8_f64),
+                ..IntegOptionsParallel::default()
             };
             let start = Instant::now();
             let ans_par = RK32
@@ -214,6 +219,7 @@ mod tests {
                 corrector_order: Some(3),
                 restart_length: Some(20 * n),
                 convergence_tol: Some(1e-8_f64),
+                ..IntegOptionsParallel::default()
             };
             let start = Instant::now();
             let ans_par = RK4
@@ -253,6 +259,7 @@ mod tests {
                 corrector_order: Some(n),
                 restart_length: Some(100),
                 convergence_tol: Some(1e-8_f64),
+                ..IntegOptionsParallel::default()
             };
             let start = Instant::now();
             let ans_par = RK32
@@ -290,6 +297,7 @@ mod tests {
                     atol: Some(Vector1::repeat(*acc)),
                     rtol: Some(*acc * 10e3_f64),
                     min_step: Some(1e-10_f64),
+                    ..IntegOptions::default()
                 };
                 let start = Instant::now();
                 let (dur, diff) = match integ {
@@ -328,6 +336,7 @@ mod tests {
                                 atol: Some(Vector1::repeat(*acc * 10e2_f64)),
                                 rtol: Some(*acc * 10e5_f64),
                                 min_step: Some(1e-10_f64),
+                                ..IntegOptions::default()
                             };
                             let ans_reg = dopri78
                                 .integrate(
@@ -362,6 +371,7 @@ mod tests {
                     corrector_order: Some(n),
                     restart_length: Some(100),
                     convergence_tol: Some(1e-8_f64),
+                    ..IntegOptionsParallel::default()
                 };
 
                 let start = Instant::now();
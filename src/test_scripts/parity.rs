@@ -0,0 +1,100 @@
+/// Test Script for Parity with Established Codes
+///
+/// Runs the standard problems of `systems::nonstiff` and `systems::stiff` and checks
+/// the solutions against their published references (the closed Arenstorf orbit
+/// DOP853 is demonstrated on, the analytic DETEST solutions, and the RADAU5
+/// reference values of the "Test Set for IVP Solvers" which CVODE is also checked
+/// against there), to the accuracy expected of codes of the same order at the same
+/// tolerances. The work precision numbers behind the bounds are printed by the
+/// ignored `test_parity_report`.
+#[cfg(test)]
+mod tests {
+    // === Begin Imports ===
+    // local imports
+    use crate::analysis::benchmark::{report, Adaptive, Benchmark};
+    use crate::ridc::fixed_cost::FixedCost;
+    use crate::runge_kutta::rk_embed::{DOPRI78, RKF45};
+    use crate::systems::nonstiff::DetestD;
+
+    // === End Imports ===
+
+    #[test]
+    fn test_parity_arenstorf() {
+        // the orbit closes after a period, to the digits an eighth order pair gets
+        // at these tolerances, the errors growing along the close approaches to
+        // the moon
+        let dopri = Adaptive::new("dopri78", &*DOPRI78);
+        let rows = Benchmark::arenstorf()
+            .compare(&[&dopri], &[1e-10, 1e-13])
+            .unwrap();
+        assert!(rows[0].digits > 5.5);
+        assert!(rows[1].digits > 8.0);
+    }
+
+    #[test]
+    fn test_parity_detest_d() {
+        // the kepler problems from e = 0.1 to 0.9 over about three orbits, the
+        // global error a couple of orders above the tolerance
+        let dopri = Adaptive::new("dopri78", &*DOPRI78);
+        for problem in [
+            DetestD::D1,
+            DetestD::D2,
+            DetestD::D3,
+            DetestD::D4,
+            DetestD::D5,
+        ] {
+            let rows = Benchmark::detest_d(problem)
+                .compare(&[&dopri], &[1e-8, 1e-12])
+                .unwrap();
+            assert!(rows[0].digits > 5.0, "{}", rows[0].problem);
+            assert!(rows[1].digits > 8.0, "{}", rows[1].problem);
+        }
+    }
+
+    #[test]
+    fn test_parity_stiff() {
+        // fixed cost implicit deferred correction against the RADAU5 references
+        let stepper = FixedCost::new(3, 3, 2).unwrap();
+        let robertson = Benchmark::robertson();
+        let ans = stepper
+            .integrate(
+                robertson.system,
+                robertson.t_0,
+                robertson.y_0,
+                robertson.t_end - robertson.t_0,
+                0.01,
+            )
+            .unwrap();
+        assert!(robertson.error(ans.last_y()) < 1e-5);
+        let hires = Benchmark::hires();
+        let ans = stepper
+            .integrate(
+                hires.system,
+                hires.t_0,
+                hires.y_0,
+                hires.t_end - hires.t_0,
+                0.05,
+            )
+            .unwrap();
+        assert!(hires.error(ans.last_y()) < 1e-4);
+    }
+
+    // Prints the work precision numbers of the explicit pairs on the nonstiff
+    // problems
+    #[test]
+    #[ignore]
+    fn test_parity_report() {
+        let tols = [1e-6, 1e-8, 1e-10, 1e-12];
+        let dopri = Adaptive::new("dopri78", &*DOPRI78);
+        let rkf = Adaptive::new("rkf45", &*RKF45);
+        let mut rows = Benchmark::arenstorf()
+            .compare(&[&dopri, &rkf], &tols)
+            .unwrap();
+        rows.extend(
+            Benchmark::detest_d(DetestD::D5)
+                .compare(&[&dopri, &rkf], &tols)
+                .unwrap(),
+        );
+        println!("{}", report(&rows));
+    }
+}
@@ -37,6 +37,7 @@ mod tests {
             atol: Some(Vector6::repeat(1e-8)),
             rtol: Some(1e-5),
             min_step: Some(1e-12_f64),
+            ..IntegOptions::default()
         };
         let cart_truth = DOPRI78
             .clone()
@@ -73,6 +74,7 @@ mod tests {
                     atol: Some(Vector6::repeat(*acc)),
                     rtol: Some(*acc * 10e3_f64),
                     min_step: Some(1e-10_f64),
+                    ..IntegOptions::default()
                 };
                 let start = Instant::now();
                 let (dur, diff_pos, diff_vel) = match integ {
@@ -131,6 +133,7 @@ mod tests {
                     corrector_order: Some(c),
                     restart_length: Some(50),
                     convergence_tol: Some(1e-8_f64),
+                    ..IntegOptionsParallel::default()
                 };
                 let start = Instant::now();
                 let ans_par = RK4
@@ -167,6 +170,7 @@ mod tests {
                     corrector_order: Some(n),
                     restart_length: Some(50),
                     convergence_tol: Some(1e-8),
+                    ..IntegOptionsParallel::default()
                 };
                 let start = Instant::now();
                 let ans_par = RK32
@@ -0,0 +1,164 @@
+/// Test Script for Solver Invariants
+///
+/// Property based tests (proptest) of invariants which hold for every input rather
+/// than for the hand picked cases of the unit tests, on randomly drawn problems:
+/// - a root returned by the newton solvers is a root to the requested accuracy
+/// - a Runge Kutta method of order p integrates y' = q(t) exactly for polynomials q
+///   of degree below p, and its error on polynomials of higher degree falls at
+///   least as fast as h^p
+/// - integrating forward over a span and back over it again returns to the
+///   initial state, to the tolerance
+///
+/// A failing case is shrunk by proptest to a minimal one and saved under
+/// `proptest-regressions`, to be replayed by the following runs.
+#[cfg(test)]
+mod tests {
+    // === Begin Imports ===
+    // third party imports
+    extern crate nalgebra as na;
+    use na::{Matrix3, Vector1, Vector2, Vector3};
+    use proptest::prelude::*;
+
+    // local imports
+    use crate::runge_kutta::adaptive::AdaptiveStep;
+    use crate::runge_kutta::common::IntegOptions;
+    use crate::runge_kutta::fixed::FixedStep;
+    use crate::runge_kutta::rk_embed::DOPRI78;
+    use crate::runge_kutta::rk_simp::{HEUN, RK4};
+    use crate::utils::newton_raphson::{
+        newton_raphson_broyden, newton_raphson_fdiff, newton_raphson_linsrch,
+    };
+
+    // === End Imports ===
+
+    // Value of the polynomial with the coefficients (lowest degree first) at t
+    fn poly(coeffs: &[f64], t: f64) -> f64 {
+        coeffs.iter().rev().fold(0.0, |acc, c| acc * t + c)
+    }
+
+    // Integral of the polynomial from 0 to t
+    fn poly_integral(coeffs: &[f64], t: f64) -> f64 {
+        coeffs
+            .iter()
+            .enumerate()
+            .map(|(k, c)| c * t.powi(k as i32 + 1) / (k + 1) as f64)
+            .sum()
+    }
+
+    // Error at t = 1 of a fixed step integration of y' = q(t) from y(0) = 0
+    fn quadrature_error<S: FixedStep>(stepper: &S, coeffs: &[f64], dt: f64) -> f64 {
+        let fxn = |t: f64, _y: &Vector1<f64>| Vector1::new(poly(coeffs, t));
+        let ans = stepper
+            .integrate(
+                fxn,
+                0.0,
+                Vector1::new(0.0),
+                1.0,
+                dt,
+                IntegOptions::default(),
+            )
+            .unwrap();
+        (ans.last_y()[0] - poly_integral(coeffs, 1.0)).abs()
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn prop_newton_root(
+            diag in prop::array::uniform3(2.0..5.0_f64),
+            coupling in prop::array::uniform6(-0.5..0.5_f64),
+            b in prop::array::uniform3(-10.0..10.0_f64),
+            x_0 in prop::array::uniform3(-3.0..3.0_f64),
+        ) {
+            // a diagonally dominant linear part with a monotone cubic:
+            // f(x) = A x + x^3 - b has a single root
+            let a = Matrix3::new(
+                diag[0], coupling[0], coupling[1],
+                coupling[2], diag[1], coupling[3],
+                coupling[4], coupling[5], diag[2],
+            );
+            let b = Vector3::from(b);
+            let fxn = |x: &Vector3<f64>| a * x + x.map(|v| v.powi(3)) - b;
+            let acc = 1e-9;
+            let x_0 = Vector3::from(x_0);
+            let found = [
+                newton_raphson_fdiff(fxn, x_0, acc),
+                newton_raphson_linsrch(fxn, x_0, acc),
+                newton_raphson_broyden(fxn, x_0, acc),
+            ];
+            // the globally convergent line search always finds it
+            prop_assert!(found[1].is_ok());
+            for x in found.iter().flatten() {
+                prop_assert!(fxn(x).amax() < acc, "residual {}", fxn(x).amax());
+            }
+        }
+
+        #[test]
+        fn prop_polynomial_exact(
+            coeffs in prop::collection::vec(-5.0..5.0_f64, 1..=8),
+            steps in 1..20_usize,
+        ) {
+            // exact for degrees below the order from the order conditions of the
+            // tableau (of the propagated solution for the embedded pair)
+            let dt = 1.0 / steps as f64;
+            let scale = 1.0 + coeffs.iter().map(|c| c.abs()).sum::<f64>();
+            let below = |order: usize| &coeffs[..coeffs.len().min(order)];
+            for (order, error) in [
+                (HEUN.tableau().order(), quadrature_error(&*HEUN, below(HEUN.tableau().order()), dt)),
+                (RK4.tableau().order(), quadrature_error(&*RK4, below(RK4.tableau().order()), dt)),
+            ] {
+                prop_assert!(error < 1e-13 * scale, "order {} error {}", order, error);
+            }
+            let (_, order) = DOPRI78.tableau().orders();
+            let fxn = |t: f64, _y: &Vector1<f64>| Vector1::new(poly(below(order), t));
+            let opts = IntegOptions {
+                first_step: Some(dt),
+                ..IntegOptions::default()
+            };
+            let ans = DOPRI78
+                .integrate(fxn, 0.0, Vector1::new(0.0), 1.0, opts)
+                .unwrap();
+            let exact = poly_integral(below(order), 1.0);
+            prop_assert!((ans.last_y()[0] - exact).abs() < 1e-13 * scale);
+        }
+
+        #[test]
+        fn prop_observed_order(
+            coeffs in prop::collection::vec(0.5..2.0_f64, 7..=9),
+        ) {
+            // positive coefficients keep the leading error terms from cancelling;
+            // halving the step divides the error by at least ~2^p
+            for (order, error_h, error_half) in [
+                (2, quadrature_error(&*HEUN, &coeffs, 0.05), quadrature_error(&*HEUN, &coeffs, 0.025)),
+                (4, quadrature_error(&*RK4, &coeffs, 0.05), quadrature_error(&*RK4, &coeffs, 0.025)),
+            ] {
+                let observed = (error_h / error_half).log2();
+                prop_assert!(observed > order as f64 - 0.1, "order {} observed {}", order, observed);
+            }
+        }
+
+        #[test]
+        fn prop_time_reversal(
+            theta in -3.0..3.0_f64,
+            omega in -1.0..1.0_f64,
+            span in 0.5..10.0_f64,
+        ) {
+            // a pendulum run forward over the span and back returns to its start
+            let pendulum = |_t: f64, y: &Vector2<f64>| Vector2::new(y[1], -y[0].sin());
+            let opts = IntegOptions {
+                atol: Some(Vector2::repeat(1e-12)),
+                rtol: Some(1e-12),
+                ..IntegOptions::default()
+            };
+            let y_0 = Vector2::new(theta, omega);
+            let forward = DOPRI78
+                .integrate(pendulum, 0.0, y_0, span, opts.clone())
+                .unwrap();
+            let back = DOPRI78
+                .integrate(pendulum, span, *forward.last_y(), -span, opts)
+                .unwrap();
+            prop_assert!((back.last_y() - y_0).amax() < 1e-8);
+        }
+    }
+}
@@ -40,6 +40,7 @@ mod tests {
                     atol: Some(Vector2::repeat(*acc)),
                     rtol: Some(*acc * 10e3_f64),
                     min_step: Some(1e-10_f64),
+                    ..IntegOptions::default()
                 };
                 let start = Instant::now();
                 let (dur, diff) = match integ {
@@ -78,6 +79,7 @@ mod tests {
                                 atol: Some(Vector2::repeat(*acc * 10e2_f64)),
                                 rtol: Some(*acc * 10e5_f64),
                                 min_step: Some(1e-10_f64),
+                                ..IntegOptions::default()
                             };
                             let ans_reg = dopri78
                                 .integrate(
@@ -112,6 +114,7 @@ mod tests {
                     corrector_order: Some(n),
                     restart_length: Some(100),
                     convergence_tol: Some(1e-6_f64),
+                    ..IntegOptionsParallel::default()
                 };
 
                 let start = Instant::now();
@@ -134,6 +137,7 @@ mod tests {
                     corrector_order: Some(c),
                     restart_length: Some(100),
                     convergence_tol: Some(1e-10_f64),
+                    ..IntegOptionsParallel::default()
                 };
                 let start = Instant::now();
                 let ans_par = RK4
@@ -0,0 +1,129 @@
+/// Temporary Arena (arena)
+///
+/// Pool of state buffers reused for the short-lived temporaries of a step (stage
+/// states, stage derivatives, ...). With heap allocated states (`Vec<f64>`, dynamic
+/// vectors) the allocations of those temporaries otherwise dominate the cost of
+/// cheap dynamics. Buffers are taken from the arena, written in place and given
+/// back, so once the arena is warm (after the first step) stepping does not
+/// allocate at all. The number of buffers the arena had to create is kept for
+/// profiling.
+///
+// === Begin Imports ===
+// local imports
+use crate::systems::state::State;
+
+// === End Imports ===
+
+#[derive(Debug, Clone)]
+pub struct Arena<X: State> {
+    // Buffers available for reuse
+    free: Vec<X>,
+    // Empty list keeping its capacity, for holding a set of buffers (stages)
+    spare: Vec<X>,
+    // Number of buffers created because none were free
+    created: usize,
+}
+
+impl<X: State> Arena<X> {
+    pub fn new() -> Self {
+        Arena {
+            free: Vec::new(),
+            spare: Vec::new(),
+            created: 0,
+        }
+    }
+
+    // Buffer holding a copy of x. Reuses a free buffer when there is one
+    pub fn copy_of(&mut self, x: &X) -> X {
+        match self.free.pop() {
+            Some(mut buf) if buf.dim() == x.dim() => {
+                buf.copy_from(x);
+                buf
+            }
+            _ => {
+                self.created += 1;
+                x.clone()
+            }
+        }
+    }
+
+    // Returns a buffer to the arena
+    pub fn give(&mut self, x: X) {
+        self.free.push(x);
+    }
+
+    // Empty list for holding buffers, with the capacity of the last one given back
+    pub fn list(&mut self) -> Vec<X> {
+        std::mem::take(&mut self.spare)
+    }
+
+    // Returns a list and the buffers it holds to the arena
+    pub fn give_list(&mut self, mut list: Vec<X>) {
+        self.free.append(&mut list);
+        self.spare = list;
+    }
+
+    // Number of buffers the arena had to create
+    pub fn created(&self) -> usize {
+        self.created
+    }
+}
+
+impl<X: State> Default for Arena<X> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runge_kutta::rk_simp::RK4;
+    use crate::test_fxns::alloc_count::allocations;
+
+    #[test]
+    fn test_arena_stepping() {
+        // chain of coupled decays with a heap allocated state
+        let dim = 50;
+        let rhs = |_t: f64, y: &Vec<f64>, dy: &mut Vec<f64>| {
+            for i in 0..y.len() {
+                let prev = if i == 0 { 0.0 } else { y[i - 1] };
+                dy[i] = prev - y[i];
+            }
+        };
+        let fxn = |t: f64, y: &Vec<f64>| {
+            let mut dy = vec![0.0; y.len()];
+            rhs(t, y, &mut dy);
+            dy
+        };
+        let y_0 = vec![1.0; dim];
+
+        // before: every stage allocates its state and derivative
+        let mut y = y_0.clone();
+        let start = allocations();
+        for i in 0..100 {
+            y = RK4.step_state(&fxn, i as f64 * 0.01, &y, 0.01);
+        }
+        let before = allocations() - start;
+
+        // after: only the first step fills the arena
+        let mut arena = Arena::new();
+        let mut y_arena = y_0.clone();
+        RK4.step_state_in_place(&rhs, 0.0, &mut y_arena, 0.01, &mut arena);
+        let start = allocations();
+        for i in 1..100 {
+            RK4.step_state_in_place(&rhs, i as f64 * 0.01, &mut y_arena, 0.01, &mut arena);
+        }
+        let after = allocations() - start;
+        println!("ALLOCATIONS | BEFORE {} | AFTER {}", before, after);
+        assert!(before >= 100 * 4);
+        assert_eq!(after, 0);
+        assert_eq!(arena.created(), 5);
+        assert_eq!(y_arena, y);
+
+        let y_end = RK4.integrate_state_in_place(&rhs, 0.0, y_0, 1.0, 0.01, &mut arena);
+        assert_eq!(y_end, y);
+        assert_eq!(arena.created(), 5);
+    }
+}
@@ -0,0 +1,230 @@
+/// Banded Matrices
+///
+/// Storage and LU factorization for matrices whose non-zero entries are confined to
+/// `lower` sub-diagonals and `upper` super-diagonals. Jacobians of method-of-lines
+/// discretizations are typically banded, and factoring them in banded form costs
+/// O(n b^2) instead of the O(n^3) of a dense factorization.
+///
+/// The factorization uses partial pivoting following LAPACK's dgbtrf: pivoting can
+/// grow the upper bandwidth of U to `lower + upper`, so the factor storage reserves
+/// room for that fill-in.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DMatrix, DefaultAllocator, Dim, OVector};
+
+// === End Imports ===
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BandedMatrix {
+    // Number of rows (and columns)
+    pub n: usize,
+    // Number of sub-diagonals
+    pub lower: usize,
+    // Number of super-diagonals
+    pub upper: usize,
+    // Row major band storage. Row i holds columns i - lower ..= i + upper
+    data: Vec<f64>,
+}
+
+impl BandedMatrix {
+    pub fn zeros(n: usize, lower: usize, upper: usize) -> Self {
+        BandedMatrix {
+            n,
+            lower,
+            upper,
+            data: vec![0.0; n * (lower + upper + 1)],
+        }
+    }
+
+    // Copies the band of a dense matrix (entries outside of the band are ignored)
+    pub fn from_dense(mat: &DMatrix<f64>, lower: usize, upper: usize) -> Self {
+        let mut banded = BandedMatrix::zeros(mat.nrows(), lower, upper);
+        for i in 0..banded.n {
+            for j in banded.col_range(i) {
+                banded.set(i, j, mat[(i, j)]);
+            }
+        }
+        banded
+    }
+
+    // Columns of row i which are inside the band
+    pub fn col_range(&self, i: usize) -> std::ops::Range<usize> {
+        i.saturating_sub(self.lower)..(i + self.upper + 1).min(self.n)
+    }
+
+    pub fn in_band(&self, i: usize, j: usize) -> bool {
+        j + self.lower >= i && j <= i + self.upper
+    }
+
+    // Value at (i, j), zero outside the band
+    pub fn get(&self, i: usize, j: usize) -> f64 {
+        if self.in_band(i, j) {
+            self.data[i * (self.lower + self.upper + 1) + j + self.lower - i]
+        } else {
+            0.0
+        }
+    }
+
+    // Sets the value at (i, j). Panics if (i, j) is outside the band
+    pub fn set(&mut self, i: usize, j: usize, val: f64) {
+        assert!(self.in_band(i, j), "Entry outside of the band");
+        self.data[i * (self.lower + self.upper + 1) + j + self.lower - i] = val;
+    }
+
+    // Matrix-vector product A v
+    pub fn mul_vec<N: Dim>(&self, v: &OVector<f64, N>) -> OVector<f64, N>
+    where
+        DefaultAllocator: Allocator<N>,
+    {
+        let mut out = v.clone();
+        for i in 0..self.n {
+            out[i] = self.col_range(i).map(|j| self.get(i, j) * v[j]).sum();
+        }
+        out
+    }
+
+    pub fn to_dense(&self) -> DMatrix<f64> {
+        DMatrix::<f64>::from_fn(self.n, self.n, |i, j| self.get(i, j))
+    }
+
+    // Factors the matrix into P A = L U
+    pub fn lu(&self) -> Result<BandedLu, &'static str> {
+        BandedLu::new(self)
+    }
+}
+
+// LU factors of a banded matrix with partial pivoting
+#[derive(Debug, Clone, PartialEq)]
+pub struct BandedLu {
+    // L multipliers (lower band) and U (upper band widened by `lower` for fill-in)
+    factors: BandedMatrix,
+    // Row swapped with row k at elimination step k
+    pivots: Vec<usize>,
+}
+
+impl BandedLu {
+    pub fn new(mat: &BandedMatrix) -> Result<Self, &'static str> {
+        let n = mat.n;
+        let kl = mat.lower;
+        let mut lu = BandedMatrix::zeros(n, kl, mat.upper + kl);
+        for i in 0..n {
+            for j in mat.col_range(i) {
+                lu.set(i, j, mat.get(i, j));
+            }
+        }
+
+        let mut pivots = Vec::with_capacity(n);
+        for k in 0..n {
+            let last_row = (k + kl).min(n - 1);
+            let last_col = (k + lu.upper).min(n - 1);
+
+            // partial pivoting within the band
+            let mut piv = k;
+            for i in k + 1..=last_row {
+                if lu.get(i, k).abs() > lu.get(piv, k).abs() {
+                    piv = i;
+                }
+            }
+            if lu.get(piv, k) == 0.0 {
+                return Err("[BANDED LU] Matrix is singular");
+            }
+            pivots.push(piv);
+            // only the trailing columns are swapped (LAPACK convention)
+            if piv != k {
+                for j in k..=last_col {
+                    let temp = lu.get(k, j);
+                    lu.set(k, j, lu.get(piv, j));
+                    lu.set(piv, j, temp);
+                }
+            }
+
+            let pivot = lu.get(k, k);
+            for i in k + 1..=last_row {
+                let l_ik = lu.get(i, k) / pivot;
+                lu.set(i, k, l_ik);
+                if l_ik != 0.0 {
+                    for j in k + 1..=last_col {
+                        lu.set(i, j, lu.get(i, j) - l_ik * lu.get(k, j));
+                    }
+                }
+            }
+        }
+
+        Ok(BandedLu {
+            factors: lu,
+            pivots,
+        })
+    }
+
+    // Solves A x = b
+    pub fn solve<N: Dim>(&self, b: &OVector<f64, N>) -> OVector<f64, N>
+    where
+        DefaultAllocator: Allocator<N>,
+    {
+        let lu = &self.factors;
+        let n = lu.n;
+        let mut x = b.clone();
+
+        // apply the row swaps and L^-1 in elimination order
+        for k in 0..n {
+            x.swap_rows(k, self.pivots[k]);
+            for i in k + 1..(k + lu.lower + 1).min(n) {
+                x[i] -= lu.get(i, k) * x[k];
+            }
+        }
+
+        // back substitution with U
+        for i in (0..n).rev() {
+            let mut sum = x[i];
+            for j in i + 1..(i + lu.upper + 1).min(n) {
+                sum -= lu.get(i, j) * x[j];
+            }
+            x[i] = sum / lu.get(i, i);
+        }
+        x
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use na::DVector;
+
+    #[test]
+    fn test_banded_lu_pivoting() {
+        // penta-diagonal matrix with small diagonal entries so pivoting is required
+        let n = 30;
+        let dense = DMatrix::<f64>::from_fn(n, n, |i, j| {
+            let off = j as i64 - i as i64;
+            match off {
+                0 => 1.0e-3 * (i as f64 + 1.0),
+                -2 => 1.5,
+                -1 => -2.0 + (i as f64).sin(),
+                1 => 3.0,
+                2 => 0.5 * (j as f64).cos(),
+                _ => 0.0,
+            }
+        });
+        let banded = BandedMatrix::from_dense(&dense, 2, 2);
+        assert_eq!(banded.to_dense(), dense);
+
+        let b = DVector::<f64>::from_fn(n, |i, _| 1.0 + i as f64);
+        assert!((banded.mul_vec(&b) - &dense * &b).norm() < 1e-12);
+
+        let x = banded.lu().unwrap().solve(&b);
+        let sol = dense.lu().solve(&b).unwrap();
+        assert!((&x - &sol).norm() / sol.norm() < 1e-10);
+    }
+
+    #[test]
+    fn test_banded_lu_singular() {
+        let mut banded = BandedMatrix::zeros(3, 1, 1);
+        banded.set(0, 0, 1.0);
+        banded.set(1, 0, 1.0);
+        assert!(banded.lu().is_err());
+    }
+}
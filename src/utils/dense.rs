@@ -0,0 +1,255 @@
+/// Dense Output (dense)
+///
+/// Evaluates a solution between the times the integrator stored it. On each
+/// interval [t_i, t_i+1] the solution is approximated by the cubic hermite
+/// interpolant matching the stored states and the slopes f(t_i, y_i) of the
+/// system, so it is continuously differentiable and third order accurate
+/// independently of the integrator which produced the solution.
+///
+/// Solutions integrated backward in time (decreasing times) are supported. Zero
+/// length intervals (e.g. a state stored twice at a breakpoint) are skipped.
+///
+/// Long solutions can be thinned for storage: `thin` keeps only the stored
+/// solutions needed for the interpolant through them to stay within a tolerance
+/// of the interpolant through all of them.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DefaultAllocator, Dim, DimName, OVector};
+
+// local imports
+use crate::runge_kutta::common::IntegResult;
+use crate::systems::OdeSystem;
+
+// === End Imports ===
+
+// Sample times and the solution at each
+pub type Samples<N> = (Vec<f64>, Vec<OVector<f64, N>>);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DenseOutput<N: Dim + DimName>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    // Solution times
+    times: Vec<f64>,
+    // Solution states
+    states: Vec<OVector<f64, N>>,
+    // Time derivative of the solution at each time
+    slopes: Vec<OVector<f64, N>>,
+}
+
+impl<N: Dim + DimName> DenseOutput<N>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    // Interpolant of a solution of the system
+    pub fn new<S: OdeSystem<N>>(system: &S, results: &IntegResult<N>) -> Self {
+        let slopes = results
+            .times
+            .iter()
+            .zip(results.states.iter())
+            .map(|(t, y)| system.dynamics(*t, y))
+            .collect();
+        DenseOutput {
+            times: results.times.clone(),
+            states: results.states.clone(),
+            slopes,
+        }
+    }
+
+    // First and last time of the solution
+    pub fn span(&self) -> (f64, f64) {
+        (self.times[0], *self.times.last().unwrap())
+    }
+
+    // Solution at time t. Errors outside of the span of the solution
+    pub fn eval(&self, t: f64) -> Result<OVector<f64, N>, &'static str> {
+        let (t_0, t_end) = self.span();
+        let dir = if t_end < t_0 { -1.0 } else { 1.0 };
+        if (t - t_0) * dir < 0.0 || (t - t_end) * dir > 0.0 {
+            return Err("[DENSE OUTPUT] Time is outside of the solution");
+        }
+
+        // last stored time at or before t (in the direction of integration)
+        let idx = self.times.partition_point(|t_i| (t_i - t) * dir <= 0.0);
+        let i = idx.saturating_sub(1).min(self.times.len() - 1);
+        if i == self.times.len() - 1 || self.times[i] == t {
+            return Ok(self.states[i].clone());
+        }
+
+        Ok(self.hermite(i, i + 1, t))
+    }
+
+    // Stored solution times
+    pub fn times(&self) -> &[f64] {
+        &self.times
+    }
+
+    // Stored solution states
+    pub fn states(&self) -> &[OVector<f64, N>] {
+        &self.states
+    }
+
+    // Interpolant through a subset of the stored solutions, differing from this one
+    // by at most tol (max norm) at the dropped solutions and at the midpoints of
+    // the intervals between the stored solutions. The first and last solutions are
+    // always kept.
+    //
+    // Each kept interval is grown from the last kept solution by doubling its
+    // length until the tolerance is exceeded, then bisecting between the last
+    // length which met it and the first which did not, so thinning n solutions
+    // into intervals of about m solutions costs O(n log m) evaluations per
+    // solution checked
+    pub fn thin(&self, tol: f64) -> Result<DenseOutput<N>, &'static str> {
+        if tol.is_nan() || tol < 0.0 {
+            return Err("[DENSE OUTPUT] Thinning tolerance must be non-negative");
+        }
+        let last = self.times.len() - 1;
+        let mut kept = vec![0];
+        let mut start = 0;
+        while start < last {
+            // longest checked interval meeting the tolerance and shortest failing it
+            let mut good = start + 1;
+            let mut bad = None;
+            let mut len = 2;
+            while bad.is_none() && good < last {
+                let end = (start + len).min(last);
+                match self.within(start, end, tol) {
+                    true => good = end,
+                    false => bad = Some(end),
+                }
+                len *= 2;
+            }
+            if let Some(mut bad) = bad {
+                while bad - good > 1 {
+                    let mid = good + (bad - good) / 2;
+                    match self.within(start, mid, tol) {
+                        true => good = mid,
+                        false => bad = mid,
+                    }
+                }
+            }
+            kept.push(good);
+            start = good;
+        }
+        Ok(DenseOutput {
+            times: kept.iter().map(|&i| self.times[i]).collect(),
+            states: kept.iter().map(|&i| self.states[i].clone()).collect(),
+            slopes: kept.iter().map(|&i| self.slopes[i].clone()).collect(),
+        })
+    }
+
+    // Whether the interpolant on [t_start, t_end] stays within tol of the stored
+    // solutions between them and of the interpolant at the midpoints of the
+    // intervals between them
+    fn within(&self, start: usize, end: usize, tol: f64) -> bool {
+        let close = |t: f64, y: &OVector<f64, N>| (self.hermite(start, end, t) - y).amax() <= tol;
+        (start..end).all(|k| {
+            let h = self.times[k + 1] - self.times[k];
+            let mid = self.times[k] + 0.5 * h;
+            (k == start || close(self.times[k], &self.states[k]))
+                && (h == 0.0 || close(mid, &self.hermite(k, k + 1, mid)))
+        })
+    }
+
+    // Cubic hermite interpolant through the stored solutions i and j at time t
+    fn hermite(&self, i: usize, j: usize, t: f64) -> OVector<f64, N> {
+        let h = self.times[j] - self.times[i];
+        let s = (t - self.times[i]) / h;
+        let h00 = (1.0 + 2.0 * s) * (1.0 - s).powi(2);
+        let h10 = s * (1.0 - s).powi(2);
+        let h01 = s * s * (3.0 - 2.0 * s);
+        let h11 = s * s * (s - 1.0);
+        &self.states[i] * h00
+            + &self.slopes[i] * (h10 * h)
+            + &self.states[j] * h01
+            + &self.slopes[j] * (h11 * h)
+    }
+
+    // Solution at `count` uniformly spaced times from t_0 to t_end (both included)
+    pub fn resample(&self, t_0: f64, t_end: f64, count: usize) -> Result<Samples<N>, &'static str> {
+        if count < 2 {
+            return Err("[DENSE OUTPUT] At least two samples are needed");
+        }
+        let dt = (t_end - t_0) / (count - 1) as f64;
+        let times: Vec<f64> = (0..count).map(|k| t_0 + k as f64 * dt).collect();
+        let states = times
+            .iter()
+            .map(|t| self.eval(*t))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok((times, states))
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runge_kutta::common::IntegOptions;
+    use crate::runge_kutta::fixed::FixedStep;
+    use crate::runge_kutta::rk_simp::RK4;
+    use nalgebra::Vector2;
+
+    #[test]
+    fn test_dense_output() {
+        let spring = |_t: f64, y: &Vector2<f64>| Vector2::new(y[1], -y[0]);
+        for &dir in [1.0, -1.0].iter() {
+            let ans = RK4
+                .integrate(
+                    spring,
+                    0.0,
+                    Vector2::new(1.0, 0.0),
+                    dir * 5.0,
+                    dir * 0.05,
+                    IntegOptions::default(),
+                )
+                .unwrap();
+            let dense = DenseOutput::new(&spring, &ans);
+            assert_eq!(dense.span(), (0.0, dir * 5.0));
+
+            // between the steps the error is that of the hermite interpolant, O(h^4)
+            let (times, states) = dense.resample(0.0, dir * 5.0, 333).unwrap();
+            for (t, y) in times.iter().zip(states.iter()) {
+                assert!((y - Vector2::new(t.cos(), -t.sin())).norm() < 1e-6);
+            }
+            assert_eq!(&dense.eval(dir * 5.0).unwrap(), ans.last_y());
+            assert!(dense.eval(dir * 5.1).is_err());
+            assert!(dense.eval(-dir * 0.1).is_err());
+        }
+    }
+
+    #[test]
+    fn test_dense_thin() {
+        let spring = |_t: f64, y: &Vector2<f64>| Vector2::new(y[1], -y[0]);
+        let ans = RK4
+            .integrate(
+                spring,
+                0.0,
+                Vector2::new(1.0, 0.0),
+                20.0,
+                0.001,
+                IntegOptions::default(),
+            )
+            .unwrap();
+        let dense = DenseOutput::new(&spring, &ans);
+        for &tol in [1e-4, 1e-7].iter() {
+            let thin = dense.thin(tol).unwrap();
+            assert!(thin.times().len() < dense.times().len() / 10);
+            assert_eq!(thin.span(), dense.span());
+            assert_eq!(thin.states().last(), dense.states().last());
+
+            // the thinned interpolant stays within tol of the dense one everywhere
+            let (times, states) = dense.resample(0.0, 20.0, 4001).unwrap();
+            for (t, y) in times.iter().zip(states.iter()) {
+                assert!((thin.eval(*t).unwrap() - y).amax() <= 1.01 * tol);
+            }
+        }
+
+        // a zero tolerance keeps every solution of a trajectory which is not a cubic
+        assert_eq!(dense.thin(0.0).unwrap(), dense);
+        assert!(dense.thin(-1.0).is_err());
+    }
+}
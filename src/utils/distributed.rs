@@ -0,0 +1,379 @@
+/// Distributed Ensembles (distributed)
+///
+/// Farms the cases of an ensemble or parameter sweep out to worker processes on
+/// other machines over TCP (the `distributed` feature), for Monte Carlo campaigns
+/// that exceed a single node. Like `sweep`, the caller supplies the parameter values
+/// and a closure which integrates one case and reduces it to whatever summary is
+/// needed.
+///
+/// The coordinator (`coordinate`) listens for workers and hands out one case at a
+/// time to each connection, so fast and slow machines balance themselves. A worker
+/// (`work`) opens one connection per local thread and runs cases until the
+/// coordinator has no more. Cases in flight on a worker that disconnects are handed
+/// to another worker, and results are returned in the order of the parameters.
+///
+/// Parameters and results cross the network in a small binary format (`Wire`)
+/// implemented for floats, integers, vectors, tuples and nalgebra vectors. The
+/// protocol carries no authentication, so the coordinator should only listen on a
+/// trusted network.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DefaultAllocator, Dim, DimName, OVector};
+
+// Standard library imports
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+// === End Imports ===
+
+// Frame tags of the protocol
+const TASK: u8 = 1;
+const RESULT: u8 = 2;
+const DONE: u8 = 3;
+
+// Interval at which idle connections check for work
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+// Binary encoding of parameters and results
+pub trait Wire: Sized {
+    // Appends the encoding of the value
+    fn encode(&self, buf: &mut Vec<u8>);
+
+    // Decodes a value from the front of the bytes, advancing past it
+    fn decode(bytes: &mut &[u8]) -> Result<Self, &'static str>;
+}
+
+// Splits n bytes off of the front
+fn take<'a>(bytes: &mut &'a [u8], n: usize) -> Result<&'a [u8], &'static str> {
+    if bytes.len() < n {
+        return Err("[DISTRIBUTED] Message is truncated");
+    }
+    let (head, tail) = bytes.split_at(n);
+    *bytes = tail;
+    Ok(head)
+}
+
+impl Wire for f64 {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.to_le_bytes());
+    }
+
+    fn decode(bytes: &mut &[u8]) -> Result<Self, &'static str> {
+        let mut raw = [0; 8];
+        raw.copy_from_slice(take(bytes, 8)?);
+        Ok(f64::from_le_bytes(raw))
+    }
+}
+
+impl Wire for u64 {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.to_le_bytes());
+    }
+
+    fn decode(bytes: &mut &[u8]) -> Result<Self, &'static str> {
+        let mut raw = [0; 8];
+        raw.copy_from_slice(take(bytes, 8)?);
+        Ok(u64::from_le_bytes(raw))
+    }
+}
+
+impl Wire for usize {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        (*self as u64).encode(buf);
+    }
+
+    fn decode(bytes: &mut &[u8]) -> Result<Self, &'static str> {
+        Ok(u64::decode(bytes)? as usize)
+    }
+}
+
+impl Wire for bool {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.push(*self as u8);
+    }
+
+    fn decode(bytes: &mut &[u8]) -> Result<Self, &'static str> {
+        Ok(take(bytes, 1)?[0] != 0)
+    }
+}
+
+impl<T: Wire> Wire for Vec<T> {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        self.len().encode(buf);
+        for val in self {
+            val.encode(buf);
+        }
+    }
+
+    fn decode(bytes: &mut &[u8]) -> Result<Self, &'static str> {
+        let len = usize::decode(bytes)?;
+        // every value takes at least a byte, which bounds corrupt lengths
+        if len > bytes.len() {
+            return Err("[DISTRIBUTED] Message is truncated");
+        }
+        (0..len).map(|_| T::decode(bytes)).collect()
+    }
+}
+
+impl<A: Wire, B: Wire> Wire for (A, B) {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        self.0.encode(buf);
+        self.1.encode(buf);
+    }
+
+    fn decode(bytes: &mut &[u8]) -> Result<Self, &'static str> {
+        Ok((A::decode(bytes)?, B::decode(bytes)?))
+    }
+}
+
+impl<N: Dim + DimName> Wire for OVector<f64, N>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    fn encode(&self, buf: &mut Vec<u8>) {
+        for val in self.iter() {
+            val.encode(buf);
+        }
+    }
+
+    fn decode(bytes: &mut &[u8]) -> Result<Self, &'static str> {
+        let mut vec = OVector::<f64, N>::zeros();
+        for val in vec.iter_mut() {
+            *val = f64::decode(bytes)?;
+        }
+        Ok(vec)
+    }
+}
+
+// Writes a frame: tag, payload length, payload
+fn write_frame(stream: &mut TcpStream, tag: u8, payload: &[u8]) -> std::io::Result<()> {
+    let mut frame = Vec::with_capacity(payload.len() + 9);
+    frame.push(tag);
+    frame.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    frame.extend_from_slice(payload);
+    stream.write_all(&frame)
+}
+
+// Reads a frame written by `write_frame`
+fn read_frame(stream: &mut TcpStream) -> std::io::Result<(u8, Vec<u8>)> {
+    let mut header = [0; 9];
+    stream.read_exact(&mut header)?;
+    let mut len = [0; 8];
+    len.copy_from_slice(&header[1..]);
+    let mut payload = vec![0; u64::from_le_bytes(len) as usize];
+    stream.read_exact(&mut payload)?;
+    Ok((header[0], payload))
+}
+
+// Shared state of a coordinator
+struct Queue<R> {
+    // Indices of the cases not yet handed out (or handed back by failed workers)
+    pending: Mutex<VecDeque<usize>>,
+    // Results received so far
+    results: Mutex<Vec<Option<R>>>,
+    // Number of cases without a result
+    remaining: AtomicUsize,
+}
+
+// Runs every case on the workers connecting to the listener and returns the results
+// in the order of the parameters. Blocks until every case has a result, so at least
+// one worker has to connect
+pub fn coordinate<P: Wire + Sync, R: Wire + Send>(
+    listener: TcpListener,
+    params: &[P],
+) -> Result<Vec<R>, &'static str> {
+    let queue = Queue {
+        pending: Mutex::new((0..params.len()).collect()),
+        results: Mutex::new(params.iter().map(|_| None).collect()),
+        remaining: AtomicUsize::new(params.len()),
+    };
+    listener
+        .set_nonblocking(true)
+        .map_err(|_| "[DISTRIBUTED] Could not configure the listener")?;
+
+    thread::scope(|scope| {
+        while queue.remaining.load(Ordering::SeqCst) > 0 {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    let queue = &queue;
+                    scope.spawn(move || serve(stream, params, queue));
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+                Err(_) => return Err("[DISTRIBUTED] Could not accept a worker"),
+            }
+        }
+        Ok(())
+    })?;
+
+    Ok(queue
+        .results
+        .into_inner()
+        .expect("[DISTRIBUTED] Result store poisoned")
+        .into_iter()
+        .map(|res| res.expect("[DISTRIBUTED] Case did not run"))
+        .collect())
+}
+
+// Hands cases to one worker connection until none are left. A case whose result
+// does not come back is queued again for the other connections
+fn serve<P: Wire, R: Wire>(mut stream: TcpStream, params: &[P], queue: &Queue<R>) {
+    if stream.set_nonblocking(false).is_err() {
+        return;
+    }
+    loop {
+        let idx = loop {
+            if let Some(idx) = queue.pending.lock().unwrap().pop_front() {
+                break idx;
+            }
+            if queue.remaining.load(Ordering::SeqCst) == 0 {
+                let _ = write_frame(&mut stream, DONE, &[]);
+                return;
+            }
+            thread::sleep(POLL_INTERVAL);
+        };
+
+        let mut task = Vec::new();
+        idx.encode(&mut task);
+        params[idx].encode(&mut task);
+        let result = write_frame(&mut stream, TASK, &task)
+            .and_then(|_| read_frame(&mut stream))
+            .ok()
+            .and_then(|(tag, payload)| {
+                let mut bytes = &payload[..];
+                match (tag, usize::decode(&mut bytes), R::decode(&mut bytes)) {
+                    (RESULT, Ok(res_idx), Ok(res)) if res_idx == idx => Some(res),
+                    _ => None,
+                }
+            });
+        match result {
+            Some(res) => {
+                let mut results = queue.results.lock().unwrap();
+                if results[idx].is_none() {
+                    results[idx] = Some(res);
+                    queue.remaining.fetch_sub(1, Ordering::SeqCst);
+                }
+            }
+            None => {
+                queue.pending.lock().unwrap().push_back(idx);
+                return;
+            }
+        }
+    }
+}
+
+// Connects `threads` times to the coordinator and runs the cases it hands out until
+// it has no more (or closes the connection). Returns the number of cases run
+pub fn work<P: Wire, R: Wire, F: Fn(&P) -> R + Sync, A: ToSocketAddrs + Sync>(
+    addr: A,
+    threads: usize,
+    case: F,
+) -> Result<usize, &'static str> {
+    let run = || -> Result<usize, &'static str> {
+        let mut stream =
+            TcpStream::connect(&addr).map_err(|_| "[DISTRIBUTED] Could not connect")?;
+        let mut count = 0;
+        loop {
+            // a coordinator which finished before this connection was served closes
+            // it without a word
+            let (tag, payload) = match read_frame(&mut stream) {
+                Ok(frame) => frame,
+                Err(_) => return Ok(count),
+            };
+            match tag {
+                TASK => {
+                    let mut bytes = &payload[..];
+                    let idx = usize::decode(&mut bytes)?;
+                    let param = P::decode(&mut bytes)?;
+                    let mut reply = Vec::new();
+                    idx.encode(&mut reply);
+                    case(&param).encode(&mut reply);
+                    write_frame(&mut stream, RESULT, &reply)
+                        .map_err(|_| "[DISTRIBUTED] Lost the connection to the coordinator")?;
+                    count += 1;
+                }
+                DONE => return Ok(count),
+                _ => return Err("[DISTRIBUTED] Unknown message from the coordinator"),
+            }
+        }
+    };
+    thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads.max(1)).map(|_| scope.spawn(run)).collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("[DISTRIBUTED] Worker thread panicked"))
+            .sum()
+    })
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runge_kutta::common::IntegOptions;
+    use crate::runge_kutta::fixed::FixedStep;
+    use crate::runge_kutta::rk_simp::RK4;
+    use na::{Vector1, Vector2};
+
+    #[test]
+    fn test_distributed_sweep() {
+        // (decay rate, forcing)
+        let params: Vec<(f64, f64)> = (1..=12).map(|i| (0.2 * i as f64, (i % 3) as f64)).collect();
+        let case = |p: &(f64, f64)| {
+            // slow enough that every worker thread connects before the cases run out
+            thread::sleep(Duration::from_millis(2));
+            let (rate, forcing) = *p;
+            let fxn = move |_t: f64, y: &Vector1<f64>| Vector1::new(forcing - rate * y[0]);
+            let ans = RK4
+                .integrate(
+                    fxn,
+                    0.0,
+                    Vector1::new(1.0),
+                    1.0,
+                    0.01,
+                    IntegOptions::default(),
+                )
+                .unwrap();
+            Vector2::new(ans.last_y()[0], ans.times.len() as f64)
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (results, counts) = thread::scope(|scope| {
+            let coordinator = scope.spawn(|| coordinate::<_, Vector2<f64>>(listener, &params));
+            // a worker which drops its first case is replaced by the others
+            let mut stream = TcpStream::connect(addr).unwrap();
+            read_frame(&mut stream).unwrap();
+            drop(stream);
+            let workers: Vec<_> = (0..2)
+                .map(|_| scope.spawn(|| work(addr, 2, case)))
+                .collect();
+            let counts: Vec<usize> = workers
+                .into_iter()
+                .map(|worker| worker.join().unwrap().unwrap())
+                .collect();
+            (coordinator.join().unwrap().unwrap(), counts)
+        });
+
+        assert_eq!(counts.iter().sum::<usize>(), params.len());
+        for (p, res) in params.iter().zip(results) {
+            let steady = p.1 / p.0;
+            let truth = steady + (1.0 - steady) * (-p.0).exp();
+            assert!((res[0] - truth).abs() < 1e-9);
+            assert_eq!(res[1], 101.0);
+        }
+
+        let mut buf = Vec::new();
+        vec![(1.5, true), (-2.0, false)].encode(&mut buf);
+        let decoded: Vec<(f64, bool)> = Wire::decode(&mut &buf[..]).unwrap();
+        assert_eq!(decoded, vec![(1.5, true), (-2.0, false)]);
+        assert!(<Vec<f64>>::decode(&mut &buf[..5]).is_err());
+    }
+}
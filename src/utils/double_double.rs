@@ -0,0 +1,116 @@
+/// Double-Double Accumulation (double_double)
+///
+/// Vectors carried as the unevaluated sum of two f64 vectors, hi + lo, with |lo| at
+/// most half an ulp of hi. That gives about 106 bits of significand for running
+/// sums while every value handed to the dynamics stays a plain f64 (the hi part).
+///
+/// Used by the RIDC correction levels when the `double_double` option is set: each
+/// level adds the increment of every step (its update formula plus the low order
+/// part of the quadrature) onto a double-double copy of its corrected solution, so
+/// the rounding error of one addition per step no longer builds up over long
+/// intervals. Only the running solution is carried this way; the dynamics, the
+/// newton solves and the messages between levels remain in f64.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DefaultAllocator, Dim, DimName, OVector};
+
+// local imports
+use super::kahan::{two_sum, weighted_sum_parts};
+
+// === End Imports ===
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DoubleDouble<N: Dim + DimName>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    // Value rounded to f64
+    pub hi: OVector<f64, N>,
+    // Remainder below the precision of hi
+    pub lo: OVector<f64, N>,
+}
+
+impl<N: Dim + DimName> DoubleDouble<N>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    // Renormalizes the pair so hi is the sum rounded to f64
+    pub fn new(hi: OVector<f64, N>, lo: OVector<f64, N>) -> Self {
+        let mut sum = DoubleDouble { hi, lo };
+        sum.renormalize();
+        sum
+    }
+
+    pub fn add(&mut self, x: &OVector<f64, N>) {
+        for i in 0..N::dim() {
+            let (s, err) = two_sum(self.hi[i], x[i]);
+            self.hi[i] = s;
+            self.lo[i] += err;
+        }
+        self.renormalize();
+    }
+
+    fn renormalize(&mut self) {
+        for i in 0..N::dim() {
+            let (s, err) = two_sum(self.hi[i], self.lo[i]);
+            self.hi[i] = s;
+            self.lo[i] = err;
+        }
+    }
+}
+
+// Sum of w_i v_i over the weights and vectors (zipped) in double-double
+pub fn weighted_sum<'a, N: Dim + DimName, I>(weights: &[f64], vals: I) -> DoubleDouble<N>
+where
+    I: IntoIterator<Item = &'a OVector<f64, N>>,
+    DefaultAllocator: Allocator<N>,
+{
+    let (sum, comp) = weighted_sum_parts(weights, vals);
+    DoubleDouble::new(sum, comp)
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ridc::base::RIDCIntegratorFixed;
+    use crate::ridc::common::IntegOptionsParallel;
+    use crate::ridc::corrector::ExplicitEuler;
+    use crate::runge_kutta::rk_simp::RK4;
+    use na::Vector1;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_double_double() {
+        let mut sum = DoubleDouble::new(Vector1::new(1.0), Vector1::zeros());
+        sum.add(&Vector1::new(1e-17));
+        sum.add(&Vector1::new(1e-17));
+        assert_eq!(sum.hi[0], 1.0);
+        assert!((sum.lo[0] - 2e-17).abs() < 1e-32);
+        let quad = weighted_sum(&[1.0, 1.0], [Vector1::new(1.0), Vector1::new(1e-20)].iter());
+        assert_eq!((quad.hi[0], quad.lo[0]), (1.0, 1e-20));
+
+        // constant dynamics: the predictor and the quadrature are exact, so all of
+        // the error is round-off collected over the 100k steps
+        let fxn = |_t: f64, _y: &Vector1<f64>| Vector1::new(0.1);
+        let run = |double_double| {
+            let options = IntegOptionsParallel {
+                corrector_order: Some(2),
+                corrector: Some(Arc::new(ExplicitEuler)),
+                double_double: Some(double_double),
+                ..IntegOptionsParallel::default()
+            };
+            let ans = RK4
+                .parallel_integrator(fxn, 0.0, &Vector1::new(1.0), 12500.0, 0.125, options)
+                .unwrap();
+            (ans.last_y()[0] - 1251.0).abs()
+        };
+        let (plain, dd) = (run(false), run(true));
+        println!("F64 {:e} | DOUBLE-DOUBLE {:e}", plain, dd);
+        assert!(dd <= 1.2e-13);
+        assert!(dd * 10.0 < plain);
+    }
+}
@@ -6,45 +6,64 @@
 // third party imports
 extern crate nalgebra as na;
 use na::allocator::Allocator;
-use na::{DefaultAllocator, Dim, DimMin, DimName, DimSub, VectorN, U1};
+use na::{DMatrix, DefaultAllocator, Dim, DimName, OVector};
 
 // local imports
+use super::kron::{solve_implicit_stages_lazy, LazyJacobian};
 use super::newton_raphson::newton_raphson_fdiff;
+use super::solver_dim::{SolverAllocator, SolverDim};
+use crate::systems::OdeSystem;
 
 // === End Imports ===
 
-pub fn fwd_euler<N: Dim + DimName>(
+pub fn fwd_euler<N: Dim + DimName, S: OdeSystem<N> + ?Sized>(
     t: f64,
-    y0: &VectorN<f64, N>,
-    fxn: fn(f64, &VectorN<f64, N>) -> VectorN<f64, N>,
+    y0: &OVector<f64, N>,
+    fxn: &S,
     tn: f64,
-) -> VectorN<f64, N>
+) -> OVector<f64, N>
 where
-    DefaultAllocator: Allocator<f64, N>,
+    DefaultAllocator: Allocator<N>,
 {
-    y0 + (tn - t) * fxn(t, y0)
+    y0 + (tn - t) * fxn.dynamics(t, y0)
 }
 
-pub fn bwd_euler<N: Dim + DimName + DimMin<N> + DimSub<U1>>(
+pub fn bwd_euler<N: SolverDim, S: OdeSystem<N> + ?Sized>(
     t: f64,
-    y0: &VectorN<f64, N>,
-    fxn: fn(f64, &VectorN<f64, N>) -> VectorN<f64, N>,
+    y0: &OVector<f64, N>,
+    fxn: &S,
     tn: f64,
-) -> VectorN<f64, N>
+) -> Result<OVector<f64, N>, &'static str>
 where
-    DefaultAllocator: Allocator<f64, N>
-        + Allocator<f64, N, N>
-        + Allocator<f64, <N as DimMin<N>>::Output, N>
-        + Allocator<f64, <N as DimMin<N>>::Output>
-        + Allocator<f64, N, <N as DimMin<N>>::Output>
-        + Allocator<f64, <<N as DimMin<N>>::Output as DimSub<U1>>::Output>,
-    <N as DimMin<N>>::Output: DimName,
-    <N as DimMin<N>>::Output: DimSub<U1>,
+    DefaultAllocator: SolverAllocator<N>,
 {
     const CONV_TOL: f64 = 1.0e-7_f64; // tolerance for convergence of newton iteration
-    let y1_hat = fwd_euler(t, y0, fxn, tn);
-    let root_problem = |yn: &VectorN<f64, N>| yn - y0 - (tn - t) * fxn(tn, yn);
-    newton_raphson_fdiff(root_problem, y1_hat, CONV_TOL).unwrap()
+
+    // The explicit euler predictor is far outside the newton basin of attraction
+    // for stiff problems, so start from the previous state instead
+    let y1_hat = y0.clone();
+    let root_problem = |yn: &OVector<f64, N>| yn - y0 - (tn - t) * fxn.dynamics(tn, yn);
+    newton_raphson_fdiff(root_problem, y1_hat, CONV_TOL)
+}
+
+// Backward euler step which keeps the jacobian in `lazy` between steps and only
+// re-evaluates it when the newton iterations stop converging quickly
+pub fn bwd_euler_lazy<N: Dim + DimName, S: OdeSystem<N> + ?Sized>(
+    t: f64,
+    y0: &OVector<f64, N>,
+    fxn: &S,
+    tn: f64,
+    lazy: &mut LazyJacobian<N>,
+) -> Result<OVector<f64, N>, &'static str>
+where
+    DefaultAllocator: Allocator<N> + Allocator<N, N>,
+{
+    const CONV_TOL: f64 = 1.0e-7_f64; // tolerance for convergence of newton iteration
+
+    // backward euler is the one stage implicit runge-kutta method with A = [1]
+    let a = DMatrix::<f64>::from_element(1, 1, 1.0);
+    let z = solve_implicit_stages_lazy(fxn, t, y0, tn - t, &a, &[1.0], CONV_TOL, lazy)?;
+    Ok(y0 + &z[0])
 }
 
 // Tests
@@ -59,13 +78,37 @@ mod tests {
     #[test]
     fn test_fwd_one_step() {
         let t1 = 1.00001;
-        let out = fwd_euler(ONE_D_INIT_TIME, &ONE_D_INIT_VAL, one_d_dynamics, t1);
+        let out = fwd_euler(ONE_D_INIT_TIME, &ONE_D_INIT_VAL, &one_d_dynamics, t1);
         // Calculated ans using scipy integrate
         const TOL: f64 = 1.0e-5_f64;
         assert!((out[0] - one_d_solution(t1)[0]).abs() < TOL);
         //println!("ESTIMATE | {:?}", out);
         //println!("TRUTH! {:?} | ", one_d_solution(t1));
     }
+    #[test]
+    fn test_bwd_stiff_start() {
+        // y' = -1e6 (e^y - 1). The explicit euler predictor lands at y ~ -1.7e4,
+        // where the residual is flat and newton overflows on its first step
+        let fxn = |_t: f64, y: &na::Vector1<f64>| na::Vector1::new(-1.0e6 * y[0].exp_m1());
+        let y0 = na::Vector1::new(1.0);
+        let h = 1.0e-2;
+        let out = bwd_euler(0.0, &y0, &fxn, h).unwrap();
+        let residual = out - y0 - h * fxn(h, &out);
+        assert!(residual[0].abs() < 1.0e-7);
+        assert!((out[0] - 9.998_500_405e-5).abs() < 1.0e-12);
+
+        let root_problem = |yn: &na::Vector1<f64>| yn - y0 - h * fxn(h, yn);
+        assert!(newton_raphson_fdiff(root_problem, fwd_euler(0.0, &y0, &fxn, h), 1.0e-7).is_err());
+    }
+
+    #[test]
+    fn test_bwd_no_root_is_err() {
+        // y' = 1 + y^2 from y = 0 with h = 1 gives y^2 - y + 1 = 0, which has no
+        // real root
+        let fxn = |_t: f64, y: &na::Vector1<f64>| na::Vector1::new(1.0 + y[0].powi(2));
+        assert!(bwd_euler(0.0, &na::Vector1::new(0.0), &fxn, 1.0).is_err());
+    }
+
     /*
         //#[test]
         fn test_fwd_iterative() {
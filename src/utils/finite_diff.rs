@@ -6,6 +6,17 @@
 /// h \approx \sqrt(e_f) * x_c where x_c is the curvature scale
 /// typically we assume x_c = x unless x is near 0 then we want to use
 /// another value
+///
+/// `fdiff_jacobian_richardson` combines center differences over the steps h and h / 2
+/// (Richardson extrapolation, which cancels their h^2 error term) for entries
+/// accurate to ~1e-12 rather than ~1e-10, at twice the evaluations. Its step is
+/// h \approx e_f^(1/5) * x_c, which balances the h^4 truncation against roundoff
+///
+/// `check_jacobian` compares a hand written jacobian of some dynamics against the
+/// richardson differences entry by entry. A wrong sign or index in an analytic
+/// jacobian is the most common bug in an implicit integration setup and only shows
+/// as slow or failing newton convergence, so the mismatching entries are reported
+/// worst first
 // === Begin Imports ===
 // std library
 use std::f64::EPSILON;
@@ -13,39 +24,44 @@ use std::f64::EPSILON;
 // third party imports
 extern crate nalgebra as na;
 use na::allocator::Allocator;
-use na::{DefaultAllocator, Dim, DimName, MatrixN, VectorN};
+use na::{DefaultAllocator, Dim, DimName, OMatrix, OVector};
+
+// local imports
+use super::banded::BandedMatrix;
+use crate::systems::OdeSystem;
 
 // === End Imports ===
 
 // Finds jacobian matrix via finite differencing
 pub fn fdiff_jacobian<F, N: Dim + DimName>(
     fxn: &F,
-    y: &VectorN<f64, N>,
-    x: &VectorN<f64, N>,
-) -> MatrixN<f64, N>
+    y: &OVector<f64, N>,
+    x: &OVector<f64, N>,
+) -> OMatrix<f64, N, N>
 where
-    F: Fn(&VectorN<f64, N>) -> VectorN<f64, N>,
-    DefaultAllocator: Allocator<f64, N> + Allocator<f64, N, N>,
+    F: Fn(&OVector<f64, N>) -> OVector<f64, N>,
+    DefaultAllocator: Allocator<N> + Allocator<N, N>,
 {
     // Approximately cube root of ULP precision
-    const H_FACTOR: f64 = EPSILON; // 1.4901161193847656e-8_f64;
+    const H_FACTOR: f64 = 6.055_454_452_393_343e-6_f64;
     const Z_LIM: f64 = 1e-16_f64;
 
-    // Initialize a vector for differences
-    let shift_vals = VectorN::<f64, N>::from_iterator(x.iter().map(|val| {
-        if val * H_FACTOR < Z_LIM {
+    // Initialize a vector for differences. Values near zero have no curvature
+    // scale so fall back to an absolute step
+    let shift_vals = OVector::<f64, N>::from_iterator(x.iter().map(|val| {
+        if val.abs() * H_FACTOR > Z_LIM {
             let temp = val + val.abs() * H_FACTOR;
             temp - val
         } else {
-            EPSILON
+            H_FACTOR
         }
     }));
 
     // Pre-initialize values
-    let mut diff: VectorN<f64, N> = VectorN::<f64, N>::zeros();
-    let mut columns: Vec<VectorN<f64, N>> = Vec::new();
-    let mut fxn_shift_p: VectorN<f64, N>;
-    let mut fxn_shift_m: VectorN<f64, N>;
+    let mut diff: OVector<f64, N> = OVector::<f64, N>::zeros();
+    let mut columns: Vec<OVector<f64, N>> = Vec::new();
+    let mut fxn_shift_p: OVector<f64, N>;
+    let mut fxn_shift_m: OVector<f64, N>;
 
     for m in 0..x.len() {
         diff.fill(0.0);
@@ -54,32 +70,36 @@ where
         fxn_shift_m = fxn(&(x - &diff));
         columns.push((&fxn_shift_p - &fxn_shift_m) / (2.0 * shift_vals[m]));
     }
-    MatrixN::<f64, N>::from_columns(&columns)
+    OMatrix::<f64, N, N>::from_columns(&columns)
 }
 
 // Finds jacobian matrix via finite differencing
 pub fn fdiff_jacobian_2<F, N: Dim + DimName>(
     fxn: &F,
-    y: &VectorN<f64, N>,
-    x: &VectorN<f64, N>,
-) -> MatrixN<f64, N>
+    y: &OVector<f64, N>,
+    x: &OVector<f64, N>,
+) -> OMatrix<f64, N, N>
 where
-    F: Fn(&VectorN<f64, N>) -> VectorN<f64, N>,
-    DefaultAllocator: Allocator<f64, N> + Allocator<f64, N, N>,
+    F: Fn(&OVector<f64, N>) -> OVector<f64, N>,
+    DefaultAllocator: Allocator<N> + Allocator<N, N>,
 {
     let dim = x.len();
-    let mut mat = MatrixN::<f64, N>::repeat(0.0);
+    let mut mat = OMatrix::<f64, N, N>::repeat(0.0);
     let mut temp: f64;
-    let mut xh_p: VectorN<f64, N> = x.clone();
-    let mut xh_m: VectorN<f64, N> = x.clone();
+    let mut xh_p: OVector<f64, N> = x.clone();
+    let mut xh_m: OVector<f64, N> = x.clone();
     let mut h: f64;
-    let mut f_p: VectorN<f64, N>;
-    let mut f_m: VectorN<f64, N>;
+    let mut f_p: OVector<f64, N>;
+    let mut f_m: OVector<f64, N>;
     for jdx in 0..dim {
         temp = x[jdx];
-        h = EPSILON * temp.abs();
+        // sqrt(e_f) * x_c step (see header). A step of e_f * x_c leaves the
+        // difference dominated by roundoff
+        h = EPSILON.sqrt() * temp.abs();
         if h == 0.0 {
-            h = EPSILON;
+            // no curvature scale at zero, so an absolute step of the same size (a
+            // step of e_f is lost to roundoff next to any sizeable residual)
+            h = f64::EPSILON.sqrt();
         }
         xh_p[jdx] = temp + h;
         xh_m[jdx] = temp - h;
@@ -95,10 +115,145 @@ where
     mat
 }
 
+// Finds jacobian matrix via richardson extrapolated center differencing
+pub fn fdiff_jacobian_richardson<F, N: Dim + DimName>(
+    fxn: &F,
+    _y: &OVector<f64, N>,
+    x: &OVector<f64, N>,
+) -> OMatrix<f64, N, N>
+where
+    F: Fn(&OVector<f64, N>) -> OVector<f64, N>,
+    DefaultAllocator: Allocator<N> + Allocator<N, N>,
+{
+    // Approximately fifth root of ULP precision
+    const H_FACTOR: f64 = 7.400_959_797_414_05e-4_f64;
+
+    let center_diff = |m: usize, h: f64| {
+        let mut diff = OVector::<f64, N>::zeros();
+        diff[m] = h;
+        (fxn(&(x + &diff)) - fxn(&(x - &diff))) / (2.0 * h)
+    };
+
+    let mut jac = OMatrix::<f64, N, N>::zeros();
+    for m in 0..x.len() {
+        // steps exactly representable around x (see fdiff_jacobian)
+        let h = (x[m] + x[m].abs().max(1.0) * H_FACTOR) - x[m];
+        let half = (x[m] + 0.5 * h) - x[m];
+        let coarse = center_diff(m, h);
+        let fine = center_diff(m, half);
+        // the h^2 error terms cancel for the ratio of the steps
+        let ratio = (h / half).powi(2);
+        jac.set_column(m, &((ratio * fine - coarse) / (ratio - 1.0)));
+    }
+    jac
+}
+
+// Finds a banded jacobian matrix via forward differencing
+//
+// Columns further apart than the bandwidth never touch the same rows, so they can
+// be perturbed together (Curtis, Powell & Reid 1974). This needs only
+// lower + upper + 1 evaluations of fxn on top of y = fxn(x), independent of the
+// dimension of x.
+pub fn fdiff_jacobian_banded<F, N: Dim>(
+    fxn: &F,
+    y: &OVector<f64, N>,
+    x: &OVector<f64, N>,
+    lower: usize,
+    upper: usize,
+) -> BandedMatrix
+where
+    F: Fn(&OVector<f64, N>) -> OVector<f64, N>,
+    DefaultAllocator: Allocator<N>,
+{
+    let dim = x.len();
+    let width = lower + upper + 1;
+    let mut jac = BandedMatrix::zeros(dim, lower, upper);
+
+    // step sizes (sqrt(e_f) * x_c with x_c = max(|x|, 1))
+    let steps: Vec<f64> = x
+        .iter()
+        .map(|val| {
+            let h = EPSILON.sqrt() * val.abs().max(1.0);
+            (val + h) - val
+        })
+        .collect();
+
+    for group in 0..width.min(dim) {
+        let mut x_p = x.clone();
+        for jdx in (group..dim).step_by(width) {
+            x_p[jdx] += steps[jdx];
+        }
+        let f_p = fxn(&x_p);
+        for jdx in (group..dim).step_by(width) {
+            for idx in jdx.saturating_sub(upper)..(jdx + lower + 1).min(dim) {
+                jac.set(idx, jdx, (f_p[idx] - y[idx]) / steps[jdx]);
+            }
+        }
+    }
+    jac
+}
+
+// Entry of a user jacobian which disagrees with finite differences
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JacobianMismatch {
+    // Row (component of the dynamics) of the entry
+    pub row: usize,
+    // Column (component of the state) of the entry
+    pub col: usize,
+    // Value of the user jacobian
+    pub analytic: f64,
+    // Value from finite differences
+    pub fdiff: f64,
+    // Error |analytic - fdiff| / (1 + |fdiff|), relative for large entries and
+    // absolute for small ones
+    pub error: f64,
+}
+
+// Checks a user jacobian of the dynamics at (t, y) against finite differences.
+// Returns the entries whose error exceeds `tol`, worst first (empty if the jacobian
+// agrees)
+pub fn check_jacobian<F, S, N: Dim + DimName>(
+    dynamics: &S,
+    jacobian: F,
+    t: f64,
+    y: &OVector<f64, N>,
+    tol: f64,
+) -> Vec<JacobianMismatch>
+where
+    F: Fn(f64, &OVector<f64, N>) -> OMatrix<f64, N, N>,
+    S: OdeSystem<N>,
+    DefaultAllocator: Allocator<N> + Allocator<N, N>,
+{
+    let analytic = jacobian(t, y);
+    let fxn = |y: &OVector<f64, N>| dynamics.dynamics(t, y);
+    let fdiff = fdiff_jacobian_richardson(&fxn, &fxn(y), y);
+
+    let mut mismatches = Vec::new();
+    for col in 0..N::dim() {
+        for row in 0..N::dim() {
+            let (analytic, fdiff) = (analytic[(row, col)], fdiff[(row, col)]);
+            let error = (analytic - fdiff).abs() / (1.0 + fdiff.abs());
+            // a NaN entry is always reported
+            if error > tol || error.is_nan() {
+                mismatches.push(JacobianMismatch {
+                    row,
+                    col,
+                    analytic,
+                    fdiff,
+                    error,
+                });
+            }
+        }
+    }
+    mismatches.sort_by(|a, b| b.error.total_cmp(&a.error));
+    mismatches
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use na::{Matrix2, Vector2};
+    use na::{DMatrix, DVector, Matrix2, Vector2};
+    use std::cell::Cell;
 
     #[test]
     fn test_jacobian() {
@@ -113,4 +268,97 @@ mod tests {
             assert!((jac[idx] - solution[idx]).abs() < TOL);
         }
     }
+
+    #[test]
+    fn test_jacobian_step_size() {
+        // A step relative to |x| in both directions. An absolute step of e_f vanishes
+        // next to a large positive x and leaves a zero jacobian
+        let fxn = |x: &Vector2<f64>| Vector2::new(x[0].powi(2), x[1].powi(3));
+        for &(a, b) in &[(1.0e3, -1.0e3), (-2.5, 4.0e2), (0.0, 1.0e-20)] {
+            let x = Vector2::new(a, b);
+            let jac = fdiff_jacobian(&fxn, &fxn(&x), &x);
+            let truth = Matrix2::new(2.0 * a, 0.0, 0.0, 3.0 * b * b);
+            let err = (jac - truth).component_div(&truth.abs().add_scalar(1.0));
+            assert!(err.amax() < 1.0e-9);
+        }
+    }
+
+    #[test]
+    fn test_jacobian_banded() {
+        // tri-diagonal nonlinear function
+        let n = 50;
+        let calls = Cell::new(0);
+        let fxn = |x: &DVector<f64>| {
+            calls.set(calls.get() + 1);
+            DVector::<f64>::from_fn(n, |i, _| {
+                let left = if i == 0 { 0.0 } else { x[i - 1] };
+                let right = if i == n - 1 { 0.0 } else { x[i + 1] };
+                left - 2.0 * x[i] + right + x[i].powi(2)
+            })
+        };
+        let x = DVector::<f64>::from_fn(n, |i, _| (i as f64 * 0.1).sin());
+        let y = fxn(&x);
+        calls.set(0);
+        let jac = fdiff_jacobian_banded(&fxn, &y, &x, 1, 1);
+        assert_eq!(calls.get(), 3);
+
+        let truth = DMatrix::<f64>::from_fn(n, n, |i, j| match j as i64 - i as i64 {
+            0 => -2.0 + 2.0 * x[i],
+            -1 | 1 => 1.0,
+            _ => 0.0,
+        });
+        assert!((jac.to_dense() - truth).amax() < 1.0e-6);
+    }
+
+    #[test]
+    fn test_jacobian_richardson() {
+        let fxn = |x: &Vector2<f64>| Vector2::new(x[0].exp() * x[1].sin(), x[0].powi(3) * x[1]);
+        let x = Vector2::new(0.7_f64, 1.3);
+        let truth = Matrix2::new(
+            x[0].exp() * x[1].sin(),
+            x[0].exp() * x[1].cos(),
+            3.0 * x[0].powi(2) * x[1],
+            x[0].powi(3),
+        );
+        let y = fxn(&x);
+        let center = (fdiff_jacobian(&fxn, &y, &x) - truth).amax();
+        let richardson = (fdiff_jacobian_richardson(&fxn, &y, &x) - truth).amax();
+        println!("CENTER {:e} | RICHARDSON {:e}", center, richardson);
+        assert!(richardson < 1e-11);
+        assert!(richardson < 0.1 * center);
+    }
+
+    #[test]
+    fn test_check_jacobian() {
+        use crate::systems::stiff::{Robertson, ROBERTSON_INIT};
+        use na::{Matrix3, Vector3};
+
+        let robertson_jac = |bug: f64| {
+            move |_t: f64, y: &Vector3<f64>| {
+                Matrix3::new(
+                    -0.04,
+                    1.0e4 * y[2],
+                    1.0e4 * y[1],
+                    0.04,
+                    -1.0e4 * y[2] - 6.0e7 * y[1],
+                    -1.0e4 * y[1],
+                    0.0,
+                    bug * 6.0e7 * y[1],
+                    0.0,
+                )
+            }
+        };
+        let y = Vector3::new(0.9, 3.0e-5, 0.1);
+        assert!(check_jacobian(&Robertson, robertson_jac(1.0), 0.0, &y, 1e-8).is_empty());
+        assert!(
+            check_jacobian(&Robertson, robertson_jac(1.0), 0.0, &ROBERTSON_INIT, 1e-8).is_empty()
+        );
+
+        // a sign error in one entry is reported, and only that entry
+        let bad = check_jacobian(&Robertson, robertson_jac(-1.0), 0.0, &y, 1e-8);
+        assert_eq!(bad.len(), 1);
+        assert_eq!((bad[0].row, bad[0].col), (2, 1));
+        assert!((bad[0].fdiff - 1800.0).abs() < 1e-6);
+        assert_eq!(bad[0].analytic, -1800.0);
+    }
 }
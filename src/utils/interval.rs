@@ -0,0 +1,289 @@
+/// Interval Arithmetic (interval)
+///
+/// Closed intervals [lo, hi] of f64 with outward rounding: every operation rounds
+/// its lower bound down and its upper bound up by one ulp, so the result always
+/// contains the exact result of the operation on any points of the operands. This
+/// is rigorous for +, -, *, / and sqrt, which IEEE 754 rounds to nearest. For exp,
+/// sin and cos the bounds are computed with the platform math library and widened
+/// by two ulps, which assumes the library is accurate to within one ulp (true of
+/// the common implementations).
+///
+/// Division by an interval containing zero gives the whole real line.
+///
+// === Begin Imports ===
+// Standard library imports
+use std::f64::consts::{FRAC_PI_2, PI};
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+// === End Imports ===
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Interval {
+    // Lower bound
+    pub lo: f64,
+    // Upper bound
+    pub hi: f64,
+}
+
+impl Interval {
+    // Interval between two bounds (in either order)
+    pub fn new(a: f64, b: f64) -> Self {
+        Interval {
+            lo: a.min(b),
+            hi: a.max(b),
+        }
+    }
+
+    // Degenerate interval holding one value
+    pub fn point(x: f64) -> Self {
+        Interval { lo: x, hi: x }
+    }
+
+    // Interval of the given half width around a value
+    pub fn around(x: f64, radius: f64) -> Self {
+        Interval::new(x - radius.abs(), x + radius.abs()).outward()
+    }
+
+    pub fn width(&self) -> f64 {
+        self.hi - self.lo
+    }
+
+    pub fn mid(&self) -> f64 {
+        0.5 * (self.lo + self.hi)
+    }
+
+    pub fn contains(&self, x: f64) -> bool {
+        self.lo <= x && x <= self.hi
+    }
+
+    // True if self lies within other
+    pub fn subset(&self, other: &Interval) -> bool {
+        other.lo <= self.lo && self.hi <= other.hi
+    }
+
+    // Smallest interval containing both
+    pub fn hull(&self, other: &Interval) -> Self {
+        Interval {
+            lo: self.lo.min(other.lo),
+            hi: self.hi.max(other.hi),
+        }
+    }
+
+    // Intersection, None if disjoint
+    pub fn intersect(&self, other: &Interval) -> Option<Self> {
+        let lo = self.lo.max(other.lo);
+        let hi = self.hi.min(other.hi);
+        if lo <= hi {
+            Some(Interval { lo, hi })
+        } else {
+            None
+        }
+    }
+
+    pub fn is_finite(&self) -> bool {
+        self.lo.is_finite() && self.hi.is_finite()
+    }
+
+    // Square, tighter than self * self when the interval straddles zero
+    pub fn sqr(&self) -> Self {
+        let (a, b) = (self.lo * self.lo, self.hi * self.hi);
+        if self.contains(0.0) {
+            Interval::new(0.0, a.max(b).next_up())
+        } else {
+            Interval::new(a.min(b), a.max(b)).outward()
+        }
+    }
+
+    // Square root of the non negative part (NaN bounds if entirely negative)
+    pub fn sqrt(&self) -> Self {
+        let lo = if self.lo <= 0.0 {
+            0.0
+        } else {
+            self.lo.sqrt().next_down().max(0.0)
+        };
+        Interval {
+            lo,
+            hi: self.hi.sqrt().next_up(),
+        }
+    }
+
+    pub fn exp(&self) -> Self {
+        Interval {
+            lo: self.lo.exp().next_down().next_down().max(0.0),
+            hi: self.hi.exp().next_up().next_up(),
+        }
+    }
+
+    pub fn sin(&self) -> Self {
+        if !self.is_finite() || self.width() >= 2.0 * PI {
+            return Interval::new(-1.0, 1.0);
+        }
+        let (a, b) = (self.lo.sin(), self.hi.sin());
+        let mut lo = a.min(b).next_down().next_down();
+        let mut hi = a.max(b).next_up().next_up();
+        // the extrema sit at pi / 2 + k pi, tested with some slack since pi is not
+        // exact: a false positive only widens the bound
+        let slack = 1e-12 * (1.0 + self.lo.abs().max(self.hi.abs()));
+        let k_min = ((self.lo - FRAC_PI_2 - slack) / PI).ceil() as i64;
+        let k_max = ((self.hi - FRAC_PI_2 + slack) / PI).floor() as i64;
+        for k in k_min..=k_max {
+            if k.rem_euclid(2) == 0 {
+                hi = 1.0;
+            } else {
+                lo = -1.0;
+            }
+        }
+        Interval {
+            lo: lo.max(-1.0),
+            hi: hi.min(1.0),
+        }
+    }
+
+    pub fn cos(&self) -> Self {
+        (*self + Interval::new(FRAC_PI_2.next_down(), FRAC_PI_2.next_up())).sin()
+    }
+
+    // Rounds both bounds outward by one ulp
+    fn outward(self) -> Self {
+        Interval {
+            lo: self.lo.next_down(),
+            hi: self.hi.next_up(),
+        }
+    }
+}
+
+impl Add for Interval {
+    type Output = Interval;
+    fn add(self, other: Interval) -> Interval {
+        Interval {
+            lo: self.lo + other.lo,
+            hi: self.hi + other.hi,
+        }
+        .outward()
+    }
+}
+
+impl Sub for Interval {
+    type Output = Interval;
+    fn sub(self, other: Interval) -> Interval {
+        Interval {
+            lo: self.lo - other.hi,
+            hi: self.hi - other.lo,
+        }
+        .outward()
+    }
+}
+
+impl Mul for Interval {
+    type Output = Interval;
+    fn mul(self, other: Interval) -> Interval {
+        let prods = [
+            self.lo * other.lo,
+            self.lo * other.hi,
+            self.hi * other.lo,
+            self.hi * other.hi,
+        ];
+        Interval {
+            lo: prods.iter().cloned().fold(f64::INFINITY, f64::min),
+            hi: prods.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
+        }
+        .outward()
+    }
+}
+
+impl Div for Interval {
+    type Output = Interval;
+    fn div(self, other: Interval) -> Interval {
+        if other.contains(0.0) {
+            return Interval::new(f64::NEG_INFINITY, f64::INFINITY);
+        }
+        let quots = [
+            self.lo / other.lo,
+            self.lo / other.hi,
+            self.hi / other.lo,
+            self.hi / other.hi,
+        ];
+        Interval {
+            lo: quots.iter().cloned().fold(f64::INFINITY, f64::min),
+            hi: quots.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
+        }
+        .outward()
+    }
+}
+
+impl Neg for Interval {
+    type Output = Interval;
+    fn neg(self) -> Interval {
+        Interval {
+            lo: -self.hi,
+            hi: -self.lo,
+        }
+    }
+}
+
+impl Add<f64> for Interval {
+    type Output = Interval;
+    fn add(self, other: f64) -> Interval {
+        self + Interval::point(other)
+    }
+}
+
+impl Sub<f64> for Interval {
+    type Output = Interval;
+    fn sub(self, other: f64) -> Interval {
+        self - Interval::point(other)
+    }
+}
+
+impl Mul<f64> for Interval {
+    type Output = Interval;
+    fn mul(self, other: f64) -> Interval {
+        self * Interval::point(other)
+    }
+}
+
+impl Mul<Interval> for f64 {
+    type Output = Interval;
+    fn mul(self, other: Interval) -> Interval {
+        Interval::point(self) * other
+    }
+}
+
+impl Div<f64> for Interval {
+    type Output = Interval;
+    fn div(self, other: f64) -> Interval {
+        self / Interval::point(other)
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interval_arithmetic() {
+        // 0.1 is not representable: the enclosure of 3 * 0.1 holds both roundings
+        let tenth = Interval::point(1.0) / 10.0;
+        let sum = tenth + tenth + tenth;
+        assert!(sum.contains(0.3) && sum.contains(0.1 + 0.1 + 0.1));
+        assert!(sum.width() < 1e-15);
+
+        let x = Interval::new(-1.0, 2.0);
+        assert_eq!(x.sqr().lo, 0.0);
+        assert!((x * x).lo < -2.0);
+        assert!((x - x).contains(0.0) && (x - x).width() >= 6.0);
+        assert!((Interval::point(1.0) / x).lo.is_infinite());
+
+        let s = Interval::new(1.0, 2.0).sin();
+        assert_eq!(s.hi, 1.0);
+        assert!(s.contains(1.0_f64.sin()) && s.lo <= 1.0_f64.sin());
+        let c = Interval::new(3.0, 3.5).cos();
+        assert_eq!(c.lo, -1.0);
+        assert!(c.contains(3.5_f64.cos()));
+        let e = Interval::new(0.0, 1.0).exp();
+        assert!(e.contains(1.0) && e.contains(std::f64::consts::E));
+        let r = Interval::new(2.0, 4.0).sqrt();
+        assert!(r.contains(2.0_f64.sqrt()) && r.contains(2.0));
+    }
+}
@@ -0,0 +1,131 @@
+/// Compensated Summation (kahan)
+///
+/// Long running sums in plain f64 lose a rounding error on every addition: after
+/// millions of steps t = t_0 + h + h + ... drifts away from t_0 + k h by many ulps,
+/// and the quadrature sums of the deferred correction integrals lose the low order
+/// bits of their small terms. `CompensatedSum` carries the rounding error of each
+/// addition along in a second float (Neumaier's improvement of Kahan summation,
+/// which also handles terms larger than the running sum), so the result is as
+/// accurate as if it were summed in twice the precision and rounded once.
+///
+/// `weighted_sum` computes sum_i w_i v_i for vectors the same way, with the
+/// rounding error of each product recovered exactly through a fused multiply add
+/// (the Dot2 algorithm of Ogita, Rump & Oishi, 2005).
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DefaultAllocator, Dim, DimName, OVector};
+
+// === End Imports ===
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CompensatedSum {
+    // Running sum
+    sum: f64,
+    // Accumulated rounding error of the running sum
+    comp: f64,
+}
+
+impl CompensatedSum {
+    pub fn new(start: f64) -> Self {
+        CompensatedSum {
+            sum: start,
+            comp: 0.0,
+        }
+    }
+
+    pub fn add(&mut self, x: f64) {
+        let (sum, err) = two_sum(self.sum, x);
+        self.sum = sum;
+        self.comp += err;
+    }
+
+    pub fn value(&self) -> f64 {
+        self.sum + self.comp
+    }
+}
+
+// Compensated sum of w_i v_i over the weights and vectors (zipped)
+pub fn weighted_sum<'a, N: Dim + DimName, I>(weights: &[f64], vals: I) -> OVector<f64, N>
+where
+    I: IntoIterator<Item = &'a OVector<f64, N>>,
+    DefaultAllocator: Allocator<N>,
+{
+    let (sum, comp) = weighted_sum_parts(weights, vals);
+    sum + comp
+}
+
+// Running sum of w_i v_i and its accumulated rounding error, kept apart
+pub(crate) fn weighted_sum_parts<'a, N: Dim + DimName, I>(
+    weights: &[f64],
+    vals: I,
+) -> (OVector<f64, N>, OVector<f64, N>)
+where
+    I: IntoIterator<Item = &'a OVector<f64, N>>,
+    DefaultAllocator: Allocator<N>,
+{
+    let mut sum = OVector::<f64, N>::zeros();
+    let mut comp = OVector::<f64, N>::zeros();
+    for (w, v) in weights.iter().zip(vals) {
+        for i in 0..N::dim() {
+            let prod = w * v[i];
+            // exact rounding error of the product
+            let prod_err = w.mul_add(v[i], -prod);
+            let (s, sum_err) = two_sum(sum[i], prod);
+            sum[i] = s;
+            comp[i] += prod_err + sum_err;
+        }
+    }
+    (sum, comp)
+}
+
+// Rounded sum of a and b and its exact rounding error
+pub(crate) fn two_sum(a: f64, b: f64) -> (f64, f64) {
+    let sum = a + b;
+    let err = if a.abs() >= b.abs() {
+        (a - sum) + b
+    } else {
+        (b - sum) + a
+    };
+    (sum, err)
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use na::Vector2;
+
+    #[test]
+    fn test_compensated_sum() {
+        // a million steps of 0.1: the plain sum drifts, the compensated one stays
+        // within an ulp of the exact value (1e5 + 5.6e-12 for the float nearest 0.1)
+        let (mut plain, mut comp) = (0.0_f64, CompensatedSum::new(0.0));
+        for _ in 0..1_000_000 {
+            plain += 0.1;
+            comp.add(0.1);
+        }
+        let exact = 100_000.0;
+        assert!((plain - exact).abs() > 1e-7);
+        assert!((comp.value() - exact).abs() <= 1.5e-11);
+
+        // terms cancelling around a large one are kept
+        let mut sum = CompensatedSum::new(1.0);
+        sum.add(1e100);
+        sum.add(1.0);
+        sum.add(-1e100);
+        assert_eq!(sum.value(), 2.0);
+
+        let vals = [
+            Vector2::new(1e16, 1.0),
+            Vector2::new(1.0, 1e-16),
+            Vector2::new(-1e16, 0.0),
+        ];
+        let ans = weighted_sum(&[1.0, 1.0, 1.0], vals.iter());
+        assert_eq!(ans, Vector2::new(1.0, 1.0 + 1e-16));
+        let ans = weighted_sum(&[0.1, 3.0, 0.1], vals.iter());
+        assert_eq!(ans[0], 3.0);
+    }
+}
@@ -0,0 +1,468 @@
+/// Kronecker Structured Stage Solver
+///
+/// The newton iterations of an s stage implicit runge-kutta (or RIDC correction)
+/// step solve linear systems with the stage coupled matrix I - h (A ⊗ J), where A
+/// is the s x s stage (butcher) matrix and J the n x n jacobian of the dynamics.
+/// Forming and factoring this sn x sn matrix costs O(s^3 n^3). Instead A is
+/// reduced once to real schur form A = Q T Q^T, which turns the system into a
+/// block upper triangular one with blocks I - h t_ii J. Each 1x1 block of T (real
+/// eigenvalue) needs a single n x n factorization and each 2x2 block (complex
+/// conjugate pair) a single 2n x 2n one. The factors are computed once per step
+/// and reused for every stage and newton iteration of that step.
+///
+/// `LazyJacobian` goes one step further and keeps the jacobian (and the factored
+/// stage system while the step size is unchanged) across steps, refreshing it only
+/// once newton convergence degrades.
+///
+/// See Hairer & Wanner "Solving Ordinary Differential Equations II" chpt IV.8
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::linalg::{Schur, LU};
+use na::{DMatrix, DVector, DefaultAllocator, Dim, DimName, Dyn, OMatrix, OVector, U1};
+
+// local imports
+use super::finite_diff::fdiff_jacobian;
+use crate::systems::OdeSystem;
+
+// === End Imports ===
+
+// Factored diagonal block of the transformed stage system
+#[derive(Debug, Clone)]
+struct StageBlock {
+    // First stage (in schur coordinates) covered by the block
+    start: usize,
+    // Number of stages covered (1 for a real eigenvalue, 2 for a complex pair)
+    size: usize,
+    // LU factors of the (size n) x (size n) block matrix
+    lu: LU<f64, Dyn, Dyn>,
+}
+
+#[derive(Debug, Clone)]
+pub struct KronSolver {
+    // Dimension of the state
+    n: usize,
+    // Step size
+    h: f64,
+    // Jacobian of the dynamics
+    jac: DMatrix<f64>,
+    // Orthogonal schur vectors of the stage matrix
+    q: DMatrix<f64>,
+    // Quasi upper triangular schur form of the stage matrix
+    t: DMatrix<f64>,
+    // Factored diagonal blocks of I - h (T ⊗ J)
+    blocks: Vec<StageBlock>,
+}
+
+impl KronSolver {
+    // Factors I - h (A ⊗ J) for the stage matrix `a` and jacobian `jac`
+    pub fn new<N: Dim + DimName>(
+        a: &DMatrix<f64>,
+        h: f64,
+        jac: &OMatrix<f64, N, N>,
+    ) -> Result<Self, &'static str>
+    where
+        DefaultAllocator: Allocator<N> + Allocator<N, N>,
+    {
+        if !a.is_square() || a.nrows() == 0 {
+            return Err("[KRON SOLVER] Stage matrix must be square and non-empty");
+        }
+        let n = jac.nrows();
+        let s = a.nrows();
+        let jac = DMatrix::<f64>::from_iterator(n, n, jac.iter().cloned());
+        let (q, t) = match Schur::<f64, Dyn>::try_new(a.clone(), 1.0e-15, 1000) {
+            Some(schur) => schur.unpack(),
+            None => return Err("[KRON SOLVER] Schur decomposition of stage matrix failed"),
+        };
+
+        // sub-diagonal entries of the schur form mark 2x2 (complex pair) blocks
+        let tiny = 1.0e-13 * t.norm();
+        let mut blocks = Vec::new();
+        let mut start = 0;
+        while start < s {
+            let size = if start + 1 < s && t[(start + 1, start)].abs() > tiny {
+                2
+            } else {
+                1
+            };
+            let mut mat = DMatrix::<f64>::zeros(size * n, size * n);
+            for bi in 0..size {
+                for bj in 0..size {
+                    let coeff = -h * t[(start + bi, start + bj)];
+                    let mut sub = mat.view_mut((bi * n, bj * n), (n, n));
+                    sub.copy_from(&(&jac * coeff));
+                    if bi == bj {
+                        for k in 0..n {
+                            sub[(k, k)] += 1.0;
+                        }
+                    }
+                }
+            }
+            let lu = mat.lu();
+            if !lu.is_invertible() {
+                return Err("[KRON SOLVER] Stage system is singular");
+            }
+            blocks.push(StageBlock { start, size, lu });
+            start += size;
+        }
+
+        Ok(KronSolver {
+            n,
+            h,
+            jac,
+            q,
+            t,
+            blocks,
+        })
+    }
+
+    // Number of stages
+    pub fn stages(&self) -> usize {
+        self.t.nrows()
+    }
+
+    // Number of factorizations held (one per real eigenvalue or complex pair of A)
+    pub fn factorizations(&self) -> usize {
+        self.blocks.len()
+    }
+
+    // Solves (I - h (A ⊗ J)) z = rhs where both z and rhs hold one vector per stage
+    pub fn solve<N: Dim + DimName>(
+        &self,
+        rhs: &[OVector<f64, N>],
+    ) -> Result<Vec<OVector<f64, N>>, &'static str>
+    where
+        DefaultAllocator: Allocator<N>,
+    {
+        let s = self.stages();
+        let n = self.n;
+        if rhs.len() != s {
+            return Err("[KRON SOLVER] Expected one right hand side per stage");
+        }
+
+        // transform to schur coordinates: w = (Q^T ⊗ I) rhs
+        let mut w: Vec<DVector<f64>> = (0..s)
+            .map(|i| {
+                let mut acc = DVector::<f64>::zeros(n);
+                for (k, rhs_k) in rhs.iter().enumerate() {
+                    for row in 0..n {
+                        acc[row] += self.q[(k, i)] * rhs_k[row];
+                    }
+                }
+                acc
+            })
+            .collect();
+
+        // block back substitution, coupling to solved stages only goes through J
+        for block in self.blocks.iter().rev() {
+            let end = block.start + block.size;
+            let mut block_rhs = DVector::<f64>::zeros(block.size * n);
+            for bi in 0..block.size {
+                let i = block.start + bi;
+                let mut coupling = DVector::<f64>::zeros(n);
+                for (j, w_j) in w.iter().enumerate().skip(end) {
+                    coupling += w_j * self.t[(i, j)];
+                }
+                let rows = w[i].clone() + (&self.jac * coupling) * self.h;
+                block_rhs.rows_mut(bi * n, n).copy_from(&rows);
+            }
+            let sol = match block.lu.solve::<Dyn, U1, _>(&block_rhs) {
+                Some(sol) => sol,
+                None => return Err("[KRON SOLVER] Stage system is singular"),
+            };
+            for bi in 0..block.size {
+                w[block.start + bi] = sol.rows(bi * n, n).into_owned();
+            }
+        }
+
+        // transform back: z = (Q ⊗ I) w
+        Ok((0..s)
+            .map(|i| {
+                let mut z_i = &rhs[0] * 0.0;
+                for (k, w_k) in w.iter().enumerate() {
+                    for row in 0..n {
+                        z_i[row] += self.q[(i, k)] * w_k[row];
+                    }
+                }
+                z_i
+            })
+            .collect())
+    }
+}
+
+// === Jacobian reuse ===
+// Lazy jacobian policy for implicit steps
+//
+// Evaluating a finite difference jacobian costs n dynamics evaluations and is
+// usually the dominant cost of a stiff integration. The jacobian is therefore kept
+// across steps and only re-evaluated once the simplified newton iterations stop
+// contracting fast enough (contraction rate |dz_k| / |dz_k-1| above `max_rate`).
+// Factorizations of the stage system are kept as long as the step size and stage
+// matrix are unchanged.
+#[derive(Debug, Clone)]
+pub struct LazyJacobian<N: Dim + DimName>
+where
+    DefaultAllocator: Allocator<N> + Allocator<N, N>,
+{
+    // Newton contraction rate above which the jacobian is refreshed
+    pub max_rate: f64,
+    // Jacobian kept from a previous step
+    jac: Option<OMatrix<f64, N, N>>,
+    // Factored stage system along with the step size and stage matrix it was built for
+    solver: Option<(f64, DMatrix<f64>, KronSolver)>,
+    // Number of jacobian evaluations so far
+    jac_evals: usize,
+    // Number of stage system factorizations so far
+    factorizations: usize,
+}
+
+impl<N: Dim + DimName> LazyJacobian<N>
+where
+    DefaultAllocator: Allocator<N> + Allocator<N, N>,
+{
+    pub fn new(max_rate: f64) -> Self {
+        LazyJacobian {
+            max_rate,
+            jac: None,
+            solver: None,
+            jac_evals: 0,
+            factorizations: 0,
+        }
+    }
+
+    pub fn default() -> Self {
+        LazyJacobian::new(0.5)
+    }
+
+    // Number of jacobian evaluations so far
+    pub fn jacobian_evals(&self) -> usize {
+        self.jac_evals
+    }
+
+    // Number of stage system factorizations so far
+    pub fn factorizations(&self) -> usize {
+        self.factorizations
+    }
+
+    // Drops the kept jacobian so it is re-evaluated on the next solve (e.g. after a
+    // discontinuity in the dynamics)
+    pub fn invalidate(&mut self) {
+        self.jac = None;
+        self.solver = None;
+    }
+
+    // Re-evaluates the jacobian of the dynamics at (t, y)
+    fn refresh<S: OdeSystem<N> + ?Sized>(&mut self, fxn: &S, t: f64, y: &OVector<f64, N>) {
+        self.jac = Some(fdiff_jacobian(
+            &|x: &OVector<f64, N>| fxn.dynamics(t, x),
+            &fxn.dynamics(t, y),
+            y,
+        ));
+        self.solver = None;
+        self.jac_evals += 1;
+    }
+
+    // Factored stage system for the step size h and stage matrix a
+    fn solver(&mut self, a: &DMatrix<f64>, h: f64) -> Result<&KronSolver, &'static str> {
+        let reuse = match &self.solver {
+            // step sizes differing only by round-off (tn - t) share a factorization
+            Some((h_old, a_old, _)) => (h_old - h).abs() <= 1.0e-12 * h.abs() && a_old == a,
+            None => false,
+        };
+        if !reuse {
+            let jac = match &self.jac {
+                Some(jac) => jac,
+                None => return Err("[LAZY JACOBIAN] No jacobian available"),
+            };
+            self.solver = Some((h, a.clone(), KronSolver::new(a, h, jac)?));
+            self.factorizations += 1;
+        }
+        match &self.solver {
+            Some((_, _, solver)) => Ok(solver),
+            None => Err("[LAZY JACOBIAN] No jacobian available"),
+        }
+    }
+}
+
+// Solves the stage equations of an implicit runge-kutta step
+//
+//     Z_i = h sum_j a_ij f(t + c_j h, y + Z_j)
+//
+// with simplified newton iterations. The jacobian is evaluated once at (t, y) and
+// factored once through a `KronSolver`, then reused by every iteration. Returns
+// the stage increments Z_i.
+pub fn solve_implicit_stages<N: Dim + DimName, S: OdeSystem<N> + ?Sized>(
+    fxn: &S,
+    t: f64,
+    y: &OVector<f64, N>,
+    h: f64,
+    a: &DMatrix<f64>,
+    c: &[f64],
+    acc: f64,
+) -> Result<Vec<OVector<f64, N>>, &'static str>
+where
+    DefaultAllocator: Allocator<N> + Allocator<N, N>,
+{
+    solve_implicit_stages_lazy(fxn, t, y, h, a, c, acc, &mut LazyJacobian::default())
+}
+
+// Same as `solve_implicit_stages` but takes the jacobian (and its factorization)
+// from `lazy`, which is kept between calls. The jacobian is only re-evaluated when
+// none is available or the newton iterations contract slower than `lazy.max_rate`.
+pub fn solve_implicit_stages_lazy<N: Dim + DimName, S: OdeSystem<N> + ?Sized>(
+    fxn: &S,
+    t: f64,
+    y: &OVector<f64, N>,
+    h: f64,
+    a: &DMatrix<f64>,
+    c: &[f64],
+    acc: f64,
+    lazy: &mut LazyJacobian<N>,
+) -> Result<Vec<OVector<f64, N>>, &'static str>
+where
+    DefaultAllocator: Allocator<N> + Allocator<N, N>,
+{
+    const MAX_ITER: i32 = 50;
+
+    let s = a.nrows();
+    if c.len() != s {
+        return Err("[IMPLICIT STAGES] Stage nodes do not match stage matrix");
+    }
+    if lazy.jac.is_none() {
+        lazy.refresh(fxn, t, y);
+    }
+
+    let mut z: Vec<OVector<f64, N>> = vec![y * 0.0; s];
+    let mut last_del: Option<f64> = None;
+    for _ in 0..MAX_ITER {
+        let f_z: Vec<OVector<f64, N>> = (0..s)
+            .map(|j| fxn.dynamics(t + c[j] * h, &(y + &z[j])))
+            .collect();
+
+        // negative residual -G(Z) = h (A ⊗ I) F(Z) - Z
+        let neg_res: Vec<OVector<f64, N>> = (0..s)
+            .map(|i| {
+                let mut r = -&z[i];
+                for (j, f_j) in f_z.iter().enumerate() {
+                    r += f_j * (h * a[(i, j)]);
+                }
+                r
+            })
+            .collect();
+
+        let del_z = lazy.solver(a, h)?.solve(&neg_res)?;
+        let max_del = del_z
+            .iter()
+            .flat_map(|del_i| del_i.iter())
+            .fold(0.0_f64, |m, val| m.max(val.abs()));
+
+        // a jacobian which no longer contracts well (or diverges to non-finite
+        // values) is re-evaluated at the latest end of step estimate and the
+        // update is discarded
+        let degraded = match last_del {
+            Some(last) => max_del.is_nan() || max_del > lazy.max_rate * last,
+            None => !max_del.is_finite(),
+        };
+        if degraded {
+            lazy.refresh(fxn, t + c[s - 1] * h, &(y + &z[s - 1]));
+            last_del = None;
+            continue;
+        }
+
+        for (z_i, del_i) in z.iter_mut().zip(&del_z) {
+            *z_i += del_i;
+        }
+        if max_del < acc {
+            return Ok(z);
+        }
+        last_del = Some(max_del);
+    }
+    Err("[IMPLICIT STAGES] Maximum Number of Iterations Reached")
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use na::{Matrix2, Vector2, Vector3, U3};
+
+    // Radau IIA (order 5) stage matrix and nodes
+    fn radau_iia() -> (DMatrix<f64>, Vec<f64>) {
+        let r6 = 6.0_f64.sqrt();
+        let a = DMatrix::<f64>::from_row_slice(
+            3,
+            3,
+            &[
+                (88.0 - 7.0 * r6) / 360.0,
+                (296.0 - 169.0 * r6) / 1800.0,
+                (-2.0 + 3.0 * r6) / 225.0,
+                (296.0 + 169.0 * r6) / 1800.0,
+                (88.0 + 7.0 * r6) / 360.0,
+                (-2.0 - 3.0 * r6) / 225.0,
+                (16.0 - r6) / 36.0,
+                (16.0 + r6) / 36.0,
+                1.0 / 9.0,
+            ],
+        );
+        (a, vec![(4.0 - r6) / 10.0, (4.0 + r6) / 10.0, 1.0])
+    }
+
+    #[test]
+    fn test_kron_solver_matches_full_system() {
+        let jac = na::Matrix3::new(-2.0, 1.0, 0.5, 0.3, -40.0, 2.0, 1.0, 0.0, -1.0e3);
+        let rhs = vec![
+            Vector3::new(1.0, 2.0, 3.0),
+            Vector3::new(-1.0, 0.5, 0.0),
+            Vector3::new(0.2, -3.0, 1.0),
+        ];
+        let h = 0.1;
+        let sdirk =
+            DMatrix::<f64>::from_row_slice(3, 3, &[0.4, 0.0, 0.0, 0.3, 0.4, 0.0, 0.1, 0.5, 0.4]);
+
+        // radau IIA has one real eigenvalue and a complex pair, sdirk a repeated one
+        for (a, n_fact) in &[(radau_iia().0, 2), (sdirk, 3)] {
+            let solver = KronSolver::new(a, h, &jac).unwrap();
+            assert_eq!(solver.factorizations(), *n_fact);
+            let z = solver.solve::<U3>(&rhs).unwrap();
+
+            // assemble the full stage system for comparison
+            let jac_d = DMatrix::<f64>::from_iterator(3, 3, jac.iter().cloned());
+            let full = DMatrix::<f64>::identity(9, 9) - a.kronecker(&jac_d) * h;
+            let stacked =
+                DVector::<f64>::from_iterator(9, rhs.iter().flat_map(|r| r.iter().cloned()));
+            let sol = full.lu().solve(&stacked).unwrap();
+            for (i, z_i) in z.iter().enumerate() {
+                for k in 0..3 {
+                    assert!((z_i[k] - sol[3 * i + k]).abs() < 1e-12 * sol.amax());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_radau_step_stability_function() {
+        // y' = M y with eigenvalues -1 and -1e4 in a non-diagonal basis
+        let p = Matrix2::new(1.0, 1.0, 0.0, 1.0);
+        let p_inv = p.try_inverse().unwrap();
+        let m = p * Matrix2::new(-1.0, 0.0, 0.0, -1.0e4) * p_inv;
+        let fxn = move |_t: f64, y: &Vector2<f64>| m * y;
+
+        // radau IIA is stiffly accurate so y_1 = y_0 + Z_3
+        let (a, c) = radau_iia();
+        let y0 = Vector2::new(1.0, 2.0);
+        let h = 0.1;
+        let z = solve_implicit_stages(&fxn, 0.0, &y0, h, &a, &c, 1e-12).unwrap();
+        let y1 = y0 + z[2];
+
+        // R(z) = (1 + 2z/5 + z^2/20) / (1 - 3z/5 + 3z^2/20 - z^3/60)
+        let stab = |x: f64| {
+            (1.0 + 0.4 * x + x.powi(2) / 20.0)
+                / (1.0 - 0.6 * x + 0.15 * x.powi(2) - x.powi(3) / 60.0)
+        };
+        let modal = p_inv * y0;
+        let expected = p * Vector2::new(modal[0] * stab(-h), modal[1] * stab(-1.0e4 * h));
+        assert!((y1 - expected).norm() < 1e-10);
+    }
+}
@@ -0,0 +1,150 @@
+/// Nonlinear Least Squares (least_squares)
+///
+/// Levenberg-Marquardt minimization of the sum of squared residuals |r(x)|^2.
+/// Each iteration solves the damped normal equations
+///
+/// (J^T J + mu diag(J^T J)) dx = -J^T r
+///
+/// which move from gradient descent (large mu, far from the minimum) towards
+/// gauss-newton steps (small mu, near the minimum). mu is decreased after a step
+/// that reduces the cost and increased (and the step retried) otherwise. Scaling
+/// the damping by diag(J^T J) makes the steps invariant to the units of each
+/// unknown (Marquardt 1963).
+///
+/// The residual function returns the residuals and their jacobian together so
+/// callers can compute both from one evaluation (e.g. sensitivities integrated
+/// alongside the state).
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::{DMatrix, DVector};
+
+// === End Imports ===
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LevenbergMarquardt {
+    // Maximum number of residual evaluations
+    pub max_iter: usize,
+    // Relative tolerance on the step and on the cost decrease
+    pub tol: f64,
+    // Initial damping
+    pub mu_0: f64,
+}
+
+impl LevenbergMarquardt {
+    pub fn default() -> Self {
+        LevenbergMarquardt {
+            max_iter: 200,
+            tol: 1e-10,
+            mu_0: 1e-3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LeastSquaresResult {
+    // Minimizer
+    pub x: DVector<f64>,
+    // Sum of squared residuals at the minimizer
+    pub cost: f64,
+    // Jacobian of the residuals at the minimizer
+    pub jacobian: DMatrix<f64>,
+    // Number of residual evaluations
+    pub iterations: usize,
+}
+
+// Minimizes the sum of squared residuals returned (with their jacobian) by fxn
+pub fn levenberg_marquardt<F>(
+    fxn: F,
+    x_0: DVector<f64>,
+    solver: &LevenbergMarquardt,
+) -> Result<LeastSquaresResult, &'static str>
+where
+    F: Fn(&DVector<f64>) -> Result<(DVector<f64>, DMatrix<f64>), &'static str>,
+{
+    const MU_MAX: f64 = 1e16;
+
+    let mut x = x_0;
+    let (mut res, mut jac) = fxn(&x)?;
+    let mut cost = res.norm_squared();
+    let mut mu = solver.mu_0;
+
+    for iteration in 1..solver.max_iter {
+        let jtj = jac.transpose() * &jac;
+        let grad = jac.transpose() * &res;
+        if grad.amax() <= solver.tol * cost.max(solver.tol) {
+            return Ok(LeastSquaresResult {
+                x,
+                cost,
+                jacobian: jac,
+                iterations: iteration,
+            });
+        }
+
+        // damped gauss-newton step
+        let mut damped = jtj.clone();
+        for i in 0..damped.nrows() {
+            damped[(i, i)] += mu * jtj[(i, i)].max(f64::EPSILON);
+        }
+        let del_x = match damped.cholesky() {
+            Some(chol) => -chol.solve(&grad),
+            None => return Err("[LEVENBERG MARQUARDT] Damped normal equations are singular"),
+        };
+        let x_new = &x + &del_x;
+
+        // the residuals may be undefined at the trial point (e.g. a failed
+        // integration), which is treated as an increase of the cost
+        let trial = fxn(&x_new).ok().map(|(r, j)| (r.norm_squared(), r, j));
+        match trial {
+            Some((cost_new, res_new, jac_new)) if cost_new < cost => {
+                let small_step = del_x.norm() <= solver.tol * (x.norm() + solver.tol);
+                let small_decrease = cost - cost_new <= solver.tol * cost;
+                x = x_new;
+                res = res_new;
+                jac = jac_new;
+                cost = cost_new;
+                mu = (mu / 10.0).max(1e-12);
+                if small_step || small_decrease {
+                    return Ok(LeastSquaresResult {
+                        x,
+                        cost,
+                        jacobian: jac,
+                        iterations: iteration + 1,
+                    });
+                }
+            }
+            _ => {
+                mu *= 10.0;
+                if mu > MU_MAX {
+                    return Err("[LEVENBERG MARQUARDT] Cannot decrease the cost any further");
+                }
+            }
+        }
+    }
+    Err("[LEVENBERG MARQUARDT] Maximum Number of Iterations Reached")
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levenberg_marquardt() {
+        // rosenbrock as a least squares problem, minimum at (1, 1)
+        let rosenbrock = |x: &DVector<f64>| {
+            let res = DVector::from_vec(vec![10.0 * (x[1] - x[0] * x[0]), 1.0 - x[0]]);
+            let jac = DMatrix::from_row_slice(2, 2, &[-20.0 * x[0], 10.0, -1.0, 0.0]);
+            Ok((res, jac))
+        };
+        let ans = levenberg_marquardt(
+            rosenbrock,
+            DVector::from_vec(vec![-1.2, 1.0]),
+            &LevenbergMarquardt::default(),
+        )
+        .unwrap();
+        assert!((ans.x[0] - 1.0).abs() < 1e-8 && (ans.x[1] - 1.0).abs() < 1e-8);
+        assert!(ans.cost < 1e-16);
+    }
+}
@@ -0,0 +1,800 @@
+/// Linear Algebra Routines
+///
+/// Iterative (Krylov subspace) linear solvers for systems A x = b where A is only
+/// available through its action on a vector. This allows solving the linear
+/// systems that show up in implicit methods without ever forming (or inverting)
+/// a dense matrix, which is required for large systems.
+///
+/// Works for both statically sized and dynamically sized (`DVector`) vectors.
+///
+/// Provides restarted GMRES and BiCGStab, both as free functions and behind the
+/// `KrylovSolver` trait so callers (e.g. the newton-krylov solver) can swap the
+/// backend. Every solve reports its convergence history (relative residual norm
+/// per iteration).
+///
+/// The GMRES implementation follows Saad "Iterative Methods for Sparse Linear
+/// Systems" (Algorithm 6.9) using givens rotations to solve the least squares
+/// problem as the Arnoldi process proceeds. BiCGStab follows van der Vorst (1992)
+/// with right preconditioning.
+///
+/// Also computes the (complex) eigenvalues of small dense matrices, and their
+/// exponential by pade approximation with scaling and squaring (Higham, "The
+/// Scaling and Squaring Method for the Matrix Exponential Revisited", 2005). The
+/// degree of the approximant (3 to 13) and the number of squarings are chosen from
+/// the 1-norm of the matrix so the result is accurate to double precision.
+///
+/// The phi functions phi_0(z) = exp(z), phi_(k+1)(z) = (phi_k(z) - 1 / k!) / z used by
+/// exponential integrators are taken from one exponential of a block matrix (Sidje,
+/// "Expokit", 1998), exp([[A, I, 0], [0, 0, I], [0, 0, 0]]) having exp(A), phi_1(A)
+/// and phi_2(A) along its first block row. Unlike their defining formulas this
+/// stays accurate for nearly singular A.
+///
+/// For large A, `expmv` approximates the action exp(t A) v from matrix vector
+/// products alone: the exponential of the small hessenberg matrix of an Arnoldi
+/// process started at v, taken over as many sub-steps of t as the a posteriori error
+/// estimate of Saad ("Analysis of some Krylov subspace approximations to the matrix
+/// exponential operator", 1992) requires (as Expokit does).
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::linalg::Schur;
+use na::{Complex, DMatrix, DVector, DefaultAllocator, Dim, Dyn, OVector, U1};
+
+// local imports
+use super::precond::Preconditioner;
+
+// === End Imports ===
+
+// Preconditioner M^-1 (any closure `Fn(&OVector<f64, N>) -> OVector<f64, N>`
+// or one of the implementations in `utils::precond`)
+pub type Precond<'a, N> = &'a dyn Preconditioner<N>;
+
+// Solution of an iterative linear solve
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinSolveResult<N: Dim>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    // Solution vector
+    pub x: OVector<f64, N>,
+    // Relative residual norm |b - A x| / |b| at the start and after each iteration
+    pub residuals: Vec<f64>,
+}
+
+impl<N: Dim> LinSolveResult<N>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    // Number of iterations performed
+    pub fn iterations(&self) -> usize {
+        self.residuals.len().saturating_sub(1)
+    }
+}
+
+// Common interface for the iterative linear solvers
+pub trait KrylovSolver {
+    // Solves op(x) = b to a relative residual tolerance `tol` starting from x_0
+    fn solve<A, N: Dim>(
+        &self,
+        op: A,
+        b: &OVector<f64, N>,
+        x_0: OVector<f64, N>,
+        tol: f64,
+        precond: Option<Precond<N>>,
+    ) -> Result<LinSolveResult<N>, &'static str>
+    where
+        A: Fn(&OVector<f64, N>) -> OVector<f64, N>,
+        DefaultAllocator: Allocator<N>;
+}
+
+// === GMRES ===
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gmres {
+    // Size of the krylov subspace before restarting
+    pub restart: usize,
+    // Maximum total number of iterations
+    pub max_iter: usize,
+}
+
+impl Gmres {
+    pub fn default() -> Self {
+        Gmres {
+            restart: 30,
+            max_iter: 300,
+        }
+    }
+}
+
+impl KrylovSolver for Gmres {
+    fn solve<A, N: Dim>(
+        &self,
+        op: A,
+        b: &OVector<f64, N>,
+        x_0: OVector<f64, N>,
+        tol: f64,
+        precond: Option<Precond<N>>,
+    ) -> Result<LinSolveResult<N>, &'static str>
+    where
+        A: Fn(&OVector<f64, N>) -> OVector<f64, N>,
+        DefaultAllocator: Allocator<N>,
+    {
+        gmres(op, b, x_0, tol, self.restart, self.max_iter, precond)
+    }
+}
+
+// Restarted, right preconditioned GMRES(m)
+//
+// Solves op(x) = b to a relative residual tolerance `tol` starting from x_0. The
+// krylov basis is rebuilt every `restart` iterations and the solver gives up after
+// `max_iter` total iterations. If a preconditioner M^-1 is provided the solver
+// works on op(M^-1 u) = b and returns x = M^-1 u, leaving the residual unchanged
+pub fn gmres<A, N: Dim>(
+    op: A,
+    b: &OVector<f64, N>,
+    x_0: OVector<f64, N>,
+    tol: f64,
+    restart: usize,
+    max_iter: usize,
+    precond: Option<Precond<N>>,
+) -> Result<LinSolveResult<N>, &'static str>
+where
+    A: Fn(&OVector<f64, N>) -> OVector<f64, N>,
+    DefaultAllocator: Allocator<N>,
+{
+    let apply_precond = |v: &OVector<f64, N>| apply(precond, v);
+
+    let b_norm = b.norm();
+    if b_norm == 0.0 {
+        return Ok(LinSolveResult {
+            x: OVector::<f64, N>::zeros_generic(N::from_usize(b.len()), U1),
+            residuals: vec![0.0],
+        });
+    }
+
+    let mut x = x_0;
+    let mut residuals = Vec::new();
+    let mut iters = 0;
+    while iters < max_iter {
+        let r = b - op(&x);
+        let beta = r.norm();
+        if residuals.is_empty() {
+            residuals.push(beta / b_norm);
+        }
+        if beta <= tol * b_norm {
+            return Ok(LinSolveResult { x, residuals });
+        }
+
+        // Arnoldi basis and hessenberg matrix for this cycle
+        let m = restart.min(max_iter - iters);
+        let mut basis: Vec<OVector<f64, N>> = Vec::with_capacity(m + 1);
+        basis.push(r / beta);
+        let mut hess = DMatrix::<f64>::zeros(m + 1, m);
+        let mut cs = DVector::<f64>::zeros(m);
+        let mut sn = DVector::<f64>::zeros(m);
+        let mut g = DVector::<f64>::zeros(m + 1);
+        g[0] = beta;
+
+        let mut k = 0;
+        while k < m {
+            // modified gram-schmidt
+            let mut w = op(&apply_precond(&basis[k]));
+            for (i, v) in basis.iter().enumerate() {
+                hess[(i, k)] = w.dot(v);
+                w -= hess[(i, k)] * v;
+            }
+            hess[(k + 1, k)] = w.norm();
+
+            // apply previous rotations to the new column
+            for i in 0..k {
+                let temp = cs[i] * hess[(i, k)] + sn[i] * hess[(i + 1, k)];
+                hess[(i + 1, k)] = -sn[i] * hess[(i, k)] + cs[i] * hess[(i + 1, k)];
+                hess[(i, k)] = temp;
+            }
+
+            // generate a rotation to zero out the sub-diagonal
+            let denom = hess[(k, k)].hypot(hess[(k + 1, k)]);
+            if denom == 0.0 {
+                return Err("[GMRES] Breakdown in Arnoldi process");
+            }
+            cs[k] = hess[(k, k)] / denom;
+            sn[k] = hess[(k + 1, k)] / denom;
+            let h_sub = hess[(k + 1, k)];
+            hess[(k, k)] = denom;
+            hess[(k + 1, k)] = 0.0;
+            g[k + 1] = -sn[k] * g[k];
+            g[k] *= cs[k];
+
+            iters += 1;
+            k += 1;
+            residuals.push(g[k].abs() / b_norm);
+            // happy breakdown (exact solution in the subspace) or converged
+            if h_sub == 0.0 || g[k].abs() <= tol * b_norm {
+                break;
+            }
+            basis.push(w / h_sub);
+        }
+
+        // back substitution for the least squares solution y of H y = g
+        let mut y = DVector::<f64>::zeros(k);
+        for i in (0..k).rev() {
+            let mut sum = g[i];
+            for j in i + 1..k {
+                sum -= hess[(i, j)] * y[j];
+            }
+            y[i] = sum / hess[(i, i)];
+        }
+        let mut update = basis[0].clone() * y[0];
+        for i in 1..k {
+            update += &basis[i] * y[i];
+        }
+        x += apply_precond(&update);
+    }
+
+    if (b - op(&x)).norm() <= tol * b_norm {
+        Ok(LinSolveResult { x, residuals })
+    } else {
+        Err("[GMRES] Maximum Number of Iterations Reached")
+    }
+}
+
+// === BiCGStab ===
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BiCgStab {
+    // Maximum number of iterations
+    pub max_iter: usize,
+}
+
+impl BiCgStab {
+    pub fn default() -> Self {
+        BiCgStab { max_iter: 300 }
+    }
+}
+
+impl KrylovSolver for BiCgStab {
+    fn solve<A, N: Dim>(
+        &self,
+        op: A,
+        b: &OVector<f64, N>,
+        x_0: OVector<f64, N>,
+        tol: f64,
+        precond: Option<Precond<N>>,
+    ) -> Result<LinSolveResult<N>, &'static str>
+    where
+        A: Fn(&OVector<f64, N>) -> OVector<f64, N>,
+        DefaultAllocator: Allocator<N>,
+    {
+        bicgstab(op, b, x_0, tol, self.max_iter, precond)
+    }
+}
+
+// Right preconditioned BiCGStab
+//
+// Uses short recurrences so memory use is fixed (unlike GMRES), at the cost of
+// an irregular convergence history and possible breakdown
+pub fn bicgstab<A, N: Dim>(
+    op: A,
+    b: &OVector<f64, N>,
+    x_0: OVector<f64, N>,
+    tol: f64,
+    max_iter: usize,
+    precond: Option<Precond<N>>,
+) -> Result<LinSolveResult<N>, &'static str>
+where
+    A: Fn(&OVector<f64, N>) -> OVector<f64, N>,
+    DefaultAllocator: Allocator<N>,
+{
+    let b_norm = b.norm();
+    if b_norm == 0.0 {
+        return Ok(LinSolveResult {
+            x: OVector::<f64, N>::zeros_generic(N::from_usize(b.len()), U1),
+            residuals: vec![0.0],
+        });
+    }
+
+    let mut x = x_0;
+    let mut r = b - op(&x);
+    let mut residuals = vec![r.norm() / b_norm];
+    if residuals[0] <= tol {
+        return Ok(LinSolveResult { x, residuals });
+    }
+
+    // shadow residual
+    let r_hat = r.clone();
+    let mut rho = 1.0;
+    let mut alpha = 1.0;
+    let mut omega = 1.0;
+    let mut v = &r * 0.0;
+    let mut p = &r * 0.0;
+
+    for _ in 0..max_iter {
+        let rho_new = r_hat.dot(&r);
+        if rho_new == 0.0 {
+            return Err("[BICGSTAB] Breakdown (rho = 0)");
+        }
+        let beta = (rho_new / rho) * (alpha / omega);
+        p = &r + beta * (&p - omega * &v);
+        let p_hat = apply(precond, &p);
+        v = op(&p_hat);
+        alpha = rho_new / r_hat.dot(&v);
+        let s = &r - alpha * &v;
+
+        // converged on the half step
+        if s.norm() <= tol * b_norm {
+            x += alpha * p_hat;
+            residuals.push(s.norm() / b_norm);
+            return Ok(LinSolveResult { x, residuals });
+        }
+
+        let s_hat = apply(precond, &s);
+        let t = op(&s_hat);
+        omega = t.dot(&s) / t.dot(&t);
+        x += alpha * p_hat + omega * s_hat;
+        r = s - omega * t;
+        residuals.push(r.norm() / b_norm);
+
+        if r.norm() <= tol * b_norm {
+            return Ok(LinSolveResult { x, residuals });
+        }
+        if omega == 0.0 {
+            return Err("[BICGSTAB] Breakdown (omega = 0)");
+        }
+        rho = rho_new;
+    }
+    Err("[BICGSTAB] Maximum Number of Iterations Reached")
+}
+
+// Applies the (optional) preconditioner to a vector
+fn apply<N: Dim>(precond: Option<Precond<N>>, v: &OVector<f64, N>) -> OVector<f64, N>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    match precond {
+        Some(m_inv) => m_inv.apply(v),
+        None => v.clone(),
+    }
+}
+
+// Eigenvalues of a square matrix from its real schur form
+//
+// Each 2x2 diagonal block of the schur form is solved directly since its
+// eigenvalues may be a real pair as well as a complex conjugate one
+pub fn eigenvalues(mat: DMatrix<f64>) -> Result<Vec<Complex<f64>>, &'static str> {
+    let n = mat.nrows();
+    let t = match Schur::<f64, Dyn>::try_new(mat, 1.0e-15, 1000) {
+        Some(schur) => schur.unpack().1,
+        None => return Err("[EIGENVALUES] Eigenvalue computation did not converge"),
+    };
+
+    // sub-diagonal entries of the schur form mark 2x2 blocks
+    let tiny = 1.0e-13 * t.norm();
+    let mut vals = Vec::with_capacity(n);
+    let mut m = 0;
+    while m < n {
+        if m + 1 < n && t[(m + 1, m)].abs() > tiny {
+            let half_tr = 0.5 * (t[(m, m)] + t[(m + 1, m + 1)]);
+            let det = t[(m, m)] * t[(m + 1, m + 1)] - t[(m, m + 1)] * t[(m + 1, m)];
+            let discr = half_tr * half_tr - det;
+            if discr >= 0.0 {
+                vals.push(Complex::new(half_tr + discr.sqrt(), 0.0));
+                vals.push(Complex::new(half_tr - discr.sqrt(), 0.0));
+            } else {
+                vals.push(Complex::new(half_tr, (-discr).sqrt()));
+                vals.push(Complex::new(half_tr, -(-discr).sqrt()));
+            }
+            m += 2;
+        } else {
+            vals.push(Complex::new(t[(m, m)], 0.0));
+            m += 1;
+        }
+    }
+    Ok(vals)
+}
+
+// Coefficients of the [m/m] pade approximants of exp used by `expm`, lowest order
+// first, and the largest 1-norm each is accurate to double precision for
+const PADE_3: [f64; 4] = [120.0, 60.0, 12.0, 1.0];
+const PADE_5: [f64; 6] = [30240.0, 15120.0, 3360.0, 420.0, 30.0, 1.0];
+const PADE_7: [f64; 8] = [
+    17297280.0, 8648640.0, 1995840.0, 277200.0, 25200.0, 1512.0, 56.0, 1.0,
+];
+const PADE_9: [f64; 10] = [
+    17643225600.0,
+    8821612800.0,
+    2075673600.0,
+    302702400.0,
+    30270240.0,
+    2162160.0,
+    110880.0,
+    3960.0,
+    90.0,
+    1.0,
+];
+const PADE_13: [f64; 14] = [
+    64764752532480000.0,
+    32382376266240000.0,
+    7771770303897600.0,
+    1187353796428800.0,
+    129060195264000.0,
+    10559470521600.0,
+    670442572800.0,
+    33522128640.0,
+    1323241920.0,
+    40840800.0,
+    960960.0,
+    16380.0,
+    182.0,
+    1.0,
+];
+const PADE_THETA: [f64; 5] = [
+    1.495_585_217_958_292e-2,
+    2.539_398_330_063_23e-1,
+    9.504_178_996_162_932e-1,
+    2.097_847_961_257_068,
+    5.371_920_351_148_152,
+];
+
+// Exponential of a square matrix
+pub fn expm(mat: &DMatrix<f64>) -> Result<DMatrix<f64>, &'static str> {
+    let n = mat.nrows();
+    if n != mat.ncols() {
+        return Err("[EXPM] Matrix must be square");
+    }
+    let norm = mat
+        .column_iter()
+        .map(|col| col.abs().sum())
+        .fold(0.0, f64::max);
+    if !norm.is_finite() {
+        return Err("[EXPM] Matrix has non-finite entries");
+    }
+    let eye = DMatrix::<f64>::identity(n, n);
+
+    // odd (u) and even (v) parts of the numerator of the approximant
+    let low_degree = |coeffs: &[f64]| {
+        let a2 = mat * mat;
+        let (mut u, mut v) = (&eye * coeffs[1], &eye * coeffs[0]);
+        let mut pow = eye.clone();
+        for k in 1..coeffs.len() / 2 {
+            pow = &pow * &a2;
+            u += &pow * coeffs[2 * k + 1];
+            v += &pow * coeffs[2 * k];
+        }
+        (mat * u, v)
+    };
+    let (u, v, squarings) = if norm <= PADE_THETA[0] {
+        let (u, v) = low_degree(&PADE_3);
+        (u, v, 0)
+    } else if norm <= PADE_THETA[1] {
+        let (u, v) = low_degree(&PADE_5);
+        (u, v, 0)
+    } else if norm <= PADE_THETA[2] {
+        let (u, v) = low_degree(&PADE_7);
+        (u, v, 0)
+    } else if norm <= PADE_THETA[3] {
+        let (u, v) = low_degree(&PADE_9);
+        (u, v, 0)
+    } else {
+        // scaled so the degree 13 approximant is accurate, then squared back
+        let squarings = (norm / PADE_THETA[4]).log2().ceil().max(0.0) as i32;
+        let a = mat / 2.0_f64.powi(squarings);
+        let b = &PADE_13;
+        let a2 = &a * &a;
+        let a4 = &a2 * &a2;
+        let a6 = &a4 * &a2;
+        let u = &a
+            * (&a6 * (&a6 * b[13] + &a4 * b[11] + &a2 * b[9])
+                + &a6 * b[7]
+                + &a4 * b[5]
+                + &a2 * b[3]
+                + &eye * b[1]);
+        let v = &a6 * (&a6 * b[12] + &a4 * b[10] + &a2 * b[8])
+            + &a6 * b[6]
+            + &a4 * b[4]
+            + &a2 * b[2]
+            + &eye * b[0];
+        (u, v, squarings)
+    };
+
+    let mut exp = match (&v - &u).lu().solve(&(v + u)) {
+        Some(exp) => exp,
+        None => return Err("[EXPM] Pade denominator is singular"),
+    };
+    for _ in 0..squarings {
+        exp = &exp * &exp;
+    }
+    Ok(exp)
+}
+
+// phi_0(A) = exp(A) through phi_p(A) for a square matrix
+pub fn phi_functions(mat: &DMatrix<f64>, p: usize) -> Result<Vec<DMatrix<f64>>, &'static str> {
+    let n = mat.nrows();
+    if n != mat.ncols() {
+        return Err("[PHI] Matrix must be square");
+    }
+    let mut aug = DMatrix::<f64>::zeros(n * (p + 1), n * (p + 1));
+    aug.view_mut((0, 0), (n, n)).copy_from(mat);
+    for k in 0..p {
+        aug.view_mut((k * n, (k + 1) * n), (n, n))
+            .fill_with_identity();
+    }
+    let exp = expm(&aug)?;
+    Ok((0..=p)
+        .map(|k| exp.view((0, k * n), (n, n)).into_owned())
+        .collect())
+}
+
+// phi_1(A) = A^-1 (exp(A) - I)
+pub fn phi_1(mat: &DMatrix<f64>) -> Result<DMatrix<f64>, &'static str> {
+    Ok(phi_functions(mat, 1)?.swap_remove(1))
+}
+
+// phi_2(A) = A^-2 (exp(A) - I - A)
+pub fn phi_2(mat: &DMatrix<f64>) -> Result<DMatrix<f64>, &'static str> {
+    Ok(phi_functions(mat, 2)?.swap_remove(2))
+}
+
+// Approximation of exp(t A) v using only products with A, from krylov subspaces
+// of dimension (at most) `m`. The error relative to |v| is kept under `tol`
+pub fn expmv<A, N: Dim>(
+    op: A,
+    v: &OVector<f64, N>,
+    t: f64,
+    m: usize,
+    tol: f64,
+) -> Result<OVector<f64, N>, &'static str>
+where
+    A: Fn(&OVector<f64, N>) -> OVector<f64, N>,
+    DefaultAllocator: Allocator<N>,
+{
+    const MAX_HALVINGS: usize = 50;
+
+    let m = m.min(v.len()).max(1);
+    let v_norm = v.norm();
+    let mut w = v.clone();
+    let mut t_done = 0.0;
+    let mut tau = t.abs();
+    while t_done < t.abs() {
+        let beta = w.norm();
+        if beta == 0.0 {
+            break;
+        }
+
+        // Arnoldi basis and hessenberg matrix of the krylov subspace of w
+        let mut basis: Vec<OVector<f64, N>> = Vec::with_capacity(m + 1);
+        basis.push(&w / beta);
+        let mut hess = DMatrix::<f64>::zeros(m + 1, m);
+        let mut k = m;
+        for j in 0..m {
+            let mut p = op(&basis[j]);
+            for (i, b_i) in basis.iter().enumerate() {
+                hess[(i, j)] = b_i.dot(&p);
+                p.axpy(-hess[(i, j)], b_i, 1.0);
+            }
+            hess[(j + 1, j)] = p.norm();
+            // the subspace is invariant (happy breakdown): the projection is exact
+            if hess[(j + 1, j)] <= 1e-12 * beta {
+                k = j + 1;
+                break;
+            }
+            basis.push(p / hess[(j + 1, j)]);
+        }
+        let h_next = if k < m { 0.0 } else { hess[(m, m - 1)] };
+        let h_k = hess.view((0, 0), (k, k)).into_owned() * t.signum();
+
+        // largest sub-step (halving from the trial) whose error estimate is in tolerance
+        tau = tau.min(t.abs() - t_done);
+        let mut halvings = 0;
+        let exp = loop {
+            let phis = phi_functions(&(&h_k * tau), 1)?;
+            let err = beta * tau * h_next * phis[1][(k - 1, 0)].abs();
+            if err <= tol * v_norm * tau / t.abs() {
+                break phis.into_iter().next().unwrap();
+            }
+            halvings += 1;
+            if halvings > MAX_HALVINGS {
+                return Err("[EXPMV] Could not meet the tolerance with the krylov subspace");
+            }
+            tau *= 0.5;
+        };
+
+        w = basis
+            .iter()
+            .take(k)
+            .enumerate()
+            .fold(&w * 0.0, |acc, (i, b_i)| acc + b_i * (beta * exp[(i, 0)]));
+        t_done += tau;
+        // the next sub-step tries twice the accepted one
+        tau *= 2.0;
+    }
+    Ok(w)
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use na::{Matrix3, Vector3};
+
+    // non-symmetric tri-diagonal (convection-diffusion like) system
+    fn tridiag_system(n: usize) -> (DMatrix<f64>, DVector<f64>) {
+        let a = DMatrix::<f64>::from_fn(n, n, |i, j| {
+            if i == j {
+                4.0
+            } else if j + 1 == i {
+                -1.5
+            } else if i + 1 == j {
+                -0.5
+            } else {
+                0.0
+            }
+        });
+        let b = DVector::<f64>::from_fn(n, |i, _| (i as f64).sin());
+        (a, b)
+    }
+
+    #[test]
+    fn test_gmres_3d() {
+        let a = Matrix3::new(4.0, 1.0, 0.0, 2.0, 5.0, 1.0, 0.0, 3.0, 6.0);
+        let b = Vector3::new(1.0, 2.0, 3.0);
+        let ans = gmres(|x| a * x, &b, Vector3::zeros(), 1e-12, 3, 10, None)
+            .expect("Couldn't converge to solution");
+        let sol = a.lu().solve(&b).unwrap();
+        assert!((ans.x - sol).norm() < 1e-10);
+        // exact in at most n iterations
+        assert!(ans.iterations() <= 3);
+    }
+
+    #[test]
+    fn test_gmres_restarted_preconditioned() {
+        let n = 50;
+        let (a, b) = tridiag_system(n);
+        let sol = a.clone().lu().solve(&b).unwrap();
+
+        let ans = gmres(|x| &a * x, &b, DVector::zeros(n), 1e-10, 5, 500, None)
+            .expect("Couldn't converge to solution");
+        assert!((&ans.x - &sol).norm() < 1e-8);
+        // minimal residual property: history never increases
+        for pair in ans.residuals.windows(2) {
+            assert!(pair[1] <= pair[0] * (1.0 + 1e-12));
+        }
+
+        // jacobi preconditioner
+        let jacobi = |v: &DVector<f64>| v / 4.0;
+        let ans = gmres(
+            |x| &a * x,
+            &b,
+            DVector::zeros(n),
+            1e-10,
+            5,
+            500,
+            Some(&jacobi),
+        )
+        .expect("Couldn't converge to solution");
+        assert!((&ans.x - &sol).norm() < 1e-8);
+    }
+
+    #[test]
+    fn test_bicgstab() {
+        let n = 50;
+        let (a, b) = tridiag_system(n);
+        let sol = a.clone().lu().solve(&b).unwrap();
+
+        let ans = bicgstab(|x| &a * x, &b, DVector::zeros(n), 1e-10, 500, None)
+            .expect("Couldn't converge to solution");
+        assert!((&ans.x - &sol).norm() < 1e-8);
+        assert!(*ans.residuals.last().unwrap() <= 1e-10);
+        assert_eq!(ans.residuals.len(), ans.iterations() + 1);
+    }
+
+    #[test]
+    fn test_krylov_solver_backends() {
+        let n = 50;
+        let (a, b) = tridiag_system(n);
+        let sol = a.clone().lu().solve(&b).unwrap();
+
+        fn solve_with<K: KrylovSolver>(
+            solver: &K,
+            a: &DMatrix<f64>,
+            b: &DVector<f64>,
+        ) -> LinSolveResult<na::Dyn> {
+            solver
+                .solve(|x| a * x, b, DVector::zeros(b.len()), 1e-10, None)
+                .expect("Couldn't converge to solution")
+        }
+
+        let gmres_ans = solve_with(&Gmres::default(), &a, &b);
+        let bicgstab_ans = solve_with(&BiCgStab::default(), &a, &b);
+        assert!((&gmres_ans.x - &sol).norm() < 1e-8);
+        assert!((&bicgstab_ans.x - &sol).norm() < 1e-8);
+
+        // too few iterations is reported as an error
+        let starved = Gmres {
+            restart: 2,
+            max_iter: 2,
+        };
+        assert!(starved
+            .solve(|x| &a * x, &b, DVector::zeros(n), 1e-10, None)
+            .is_err());
+    }
+
+    #[test]
+    fn test_expm_phi() {
+        // rotation generator: exp is the rotation by the angle
+        let theta = 2.5_f64;
+        let rot = DMatrix::from_row_slice(2, 2, &[0.0, -theta, theta, 0.0]);
+        let exp = expm(&rot).unwrap();
+        let truth =
+            DMatrix::from_row_slice(2, 2, &[theta.cos(), -theta.sin(), theta.sin(), theta.cos()]);
+        assert!((exp - truth).amax() < 1e-14);
+
+        // large norms go through scaling and squaring
+        let diag = DMatrix::from_diagonal(&DVector::from_vec(vec![-40.0, 0.5, 12.0]));
+        let exp = expm(&diag).unwrap();
+        for (i, d) in [-40.0_f64, 0.5, 12.0].iter().enumerate() {
+            assert!((exp[(i, i)] / d.exp() - 1.0).abs() < 1e-13);
+        }
+        // non-normal (nilpotent) part: exp([[1, 1], [0, 1]]) = e [[1, 1], [0, 1]]
+        let jordan = DMatrix::from_row_slice(2, 2, &[1.0, 1.0, 0.0, 1.0]);
+        let exp = expm(&jordan).unwrap();
+        assert!((exp - jordan * 1.0_f64.exp()).amax() < 1e-14);
+
+        // scalar phi functions, including where their formulas cancel
+        for z in [-3.0_f64, 0.7, 1e-9] {
+            let phis = phi_functions(&DMatrix::from_element(1, 1, z), 2).unwrap();
+            let phi_1 = z.exp_m1() / z;
+            let phi_2 = (phi_1 - 1.0) / z;
+            assert!((phis[0][0] - z.exp()).abs() < 1e-14);
+            assert!((phis[1][0] - phi_1).abs() < 1e-14);
+            if z.abs() > 1e-3 {
+                assert!((phis[2][0] - phi_2).abs() < 1e-14);
+            } else {
+                assert!((phis[2][0] - 0.5).abs() < 1e-9);
+            }
+        }
+        let a = DMatrix::from_row_slice(2, 2, &[-2.0, 1.0, 0.5, -1.0]);
+        let lhs = &a * phi_1(&a).unwrap();
+        assert!((lhs - (expm(&a).unwrap() - DMatrix::identity(2, 2))).amax() < 1e-14);
+        let lhs = &a * &a * phi_2(&a).unwrap();
+        assert!((lhs - (expm(&a).unwrap() - DMatrix::identity(2, 2) - &a)).amax() < 1e-14);
+    }
+
+    #[test]
+    fn test_expmv() {
+        // 1d diffusion with dirichlet boundaries (stiff, norm ~ 4 / dx^2)
+        let n = 200;
+        let dx = 1.0 / (n + 1) as f64;
+        let lap = DMatrix::<f64>::from_fn(n, n, |i, j| match i as i64 - j as i64 {
+            0 => -2.0 / dx.powi(2),
+            -1 | 1 => 1.0 / dx.powi(2),
+            _ => 0.0,
+        });
+        let v = DVector::<f64>::from_fn(n, |i, _| {
+            let x = (i + 1) as f64 * dx;
+            (std::f64::consts::PI * x).sin() + 0.3 * (7.0 * std::f64::consts::PI * x).sin()
+        });
+        let products = std::cell::Cell::new(0);
+        let op = |x: &DVector<f64>| {
+            products.set(products.get() + 1);
+            &lap * x
+        };
+
+        for t in [1e-4, 0.02] {
+            let truth = expm(&(&lap * t)).unwrap() * &v;
+            let ans = expmv(&op, &v, t, 30, 1e-10).unwrap();
+            assert!((ans - &truth).norm() < 1e-8 * v.norm());
+        }
+        // far fewer products than the dimension of a dense exponential
+        assert!(products.get() < 20 * n);
+
+        // exact in n products (happy breakdown) for a small matrix, backward too
+        let a = Matrix3::new(-1.0, 2.0, 0.0, 0.0, -3.0, 1.0, 0.5, 0.0, -2.0);
+        let v = Vector3::new(1.0, -1.0, 2.0);
+        let a_dyn = DMatrix::from_iterator(3, 3, a.iter().cloned());
+        for t in [0.8, -0.8] {
+            let truth = expm(&(&a_dyn * t)).unwrap() * DVector::from_column_slice(v.as_slice());
+            let ans = expmv(|x: &Vector3<f64>| a * x, &v, t, 3, 1e-12).unwrap();
+            assert!((DVector::from_column_slice(ans.as_slice()) - truth).amax() < 1e-12);
+        }
+    }
+}
@@ -3,7 +3,22 @@
 /// A multi-dimensional linear search routine from pg 479 of
 /// numerical recipes
 ///
+/// The line searches of the newton solvers minimize the merit function
+/// 0.5 F.F along the newton direction, behind the `LineSearch` trait so the
+/// solver can swap them:
 ///
+/// - `Armijo` backtracks with quadratic / cubic interpolation until the merit
+///   decreases sufficiently (the numerical recipes routine)
+/// - `StrongWolfe` also asks for the slope of the merit to flatten, bracketing a
+///   step and zooming in on it with cubic interpolation (Nocedal & Wright
+///   "Numerical Optimization", algorithms 3.5 and 3.6)
+/// - `MoreThuente` finds a strong Wolfe step with the safeguarded interval
+///   updates of More & Thuente "Line search algorithms with guaranteed
+///   sufficient decrease" (1994), as in MINPACK-2 `dcsrch`
+///
+/// The Wolfe searches take the slope of the merit at each trial step by a
+/// forward difference of the residual along the search direction, which costs a
+/// second residual evaluation per trial.
 ///
 // === Begin Imports ===
 // std library imports
@@ -12,27 +27,110 @@ use std::f64::EPSILON;
 // third party imports
 extern crate nalgebra as na;
 use na::allocator::Allocator;
-use na::{DefaultAllocator, Dim, DimName, VectorN};
+use na::{DefaultAllocator, Dim, DimName, OVector};
 
 // === End Imports ===
 
-// Newton raphson method using Broydens method
-// see: https://en.wikipedia.org/wiki/Broyden%27s_method
-//
+// Point found by a line search, with its residual and merit 0.5 F.F
+pub type SearchPoint<N> = (OVector<f64, N>, OVector<f64, N>, f64);
+
+// Common interface for the line searches
+pub trait LineSearch {
+    // Searches from x_old (with merit f_old and merit gradient grad) along the
+    // direction p, which is shortened to stepmax if longer, for a point with a
+    // sufficiently smaller merit. fxn gives the residual and merit of a point
+    fn search<F, N: Dim + DimName>(
+        &self,
+        x_old: &OVector<f64, N>,
+        f_old: f64,
+        grad: &OVector<f64, N>,
+        p: &OVector<f64, N>,
+        stepmax: f64,
+        fxn: F,
+    ) -> Result<SearchPoint<N>, &'static str>
+    where
+        F: Fn(&OVector<f64, N>) -> (OVector<f64, N>, f64),
+        DefaultAllocator: Allocator<N>;
+}
+
+// === Armijo ===
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Armijo {
+    // Sufficient decrease parameter
+    pub c1: f64,
+    // Maximum number of backtracking steps
+    pub max_backtracks: usize,
+}
+
+impl Armijo {
+    pub fn default() -> Self {
+        Armijo {
+            c1: 1e-4,
+            max_backtracks: 100,
+        }
+    }
+}
+
+impl LineSearch for Armijo {
+    fn search<F, N: Dim + DimName>(
+        &self,
+        x_old: &OVector<f64, N>,
+        f_old: f64,
+        grad: &OVector<f64, N>,
+        p: &OVector<f64, N>,
+        stepmax: f64,
+        fxn: F,
+    ) -> Result<SearchPoint<N>, &'static str>
+    where
+        F: Fn(&OVector<f64, N>) -> (OVector<f64, N>, f64),
+        DefaultAllocator: Allocator<N>,
+    {
+        backtrack(
+            x_old,
+            f_old,
+            grad,
+            p,
+            stepmax,
+            fxn,
+            self.c1,
+            self.max_backtracks,
+        )
+    }
+}
+
+// Backtracking line search with the default Armijo parameters
 pub fn linsrch_w_backtracking<F, N: Dim + DimName>(
-    x_old: &VectorN<f64, N>,
+    x_old: &OVector<f64, N>,
     f_old: f64,
-    grad: &VectorN<f64, N>,
-    p: &VectorN<f64, N>,
+    grad: &OVector<f64, N>,
+    p: &OVector<f64, N>,
     stepmax: f64,
     fxn: F,
-) -> Result<(VectorN<f64, N>, VectorN<f64, N>, f64), &'static str>
+) -> Result<SearchPoint<N>, &'static str>
 where
-    F: Fn(&VectorN<f64, N>) -> (VectorN<f64, N>, f64),
-    DefaultAllocator: Allocator<f64, N>,
+    F: Fn(&OVector<f64, N>) -> (OVector<f64, N>, f64),
+    DefaultAllocator: Allocator<N>,
+{
+    Armijo::default().search(x_old, f_old, grad, p, stepmax, fxn)
+}
+
+// Backtracking until the merit decreases by c1 times the decrease predicted by
+// its slope
+#[allow(clippy::too_many_arguments)]
+fn backtrack<F, N: Dim + DimName>(
+    x_old: &OVector<f64, N>,
+    f_old: f64,
+    grad: &OVector<f64, N>,
+    p: &OVector<f64, N>,
+    stepmax: f64,
+    fxn: F,
+    c1: f64,
+    max_steps: usize,
+) -> Result<SearchPoint<N>, &'static str>
+where
+    F: Fn(&OVector<f64, N>) -> (OVector<f64, N>, f64),
+    DefaultAllocator: Allocator<N>,
 {
-    const MAX_STEPS: usize = 100;
-    const ALPHA: f64 = 1e-4_f64;
     const TOLX: f64 = EPSILON;
 
     // pre-initialize variables
@@ -87,14 +185,14 @@ where
     let x_old = x_old.clone();
 
     // main loop!
-    for _ in 0..MAX_STEPS {
+    for _ in 0..max_steps {
         let x_new = &x_old + alam * &p;
         let (f_vec, f_new) = fxn(&x_new.clone());
         // convergence on del_x
         if alam < alamin {
             return Ok((x_new.clone(), f_vec, f_new));
         // sufficient function decrease
-        } else if f_new <= f_old + ALPHA * alam * slope {
+        } else if f_new <= f_old + c1 * alam * slope {
             return Ok((x_new.clone(), f_vec, f_new));
         //backtrack
         } else {
@@ -131,3 +229,540 @@ where
     }
     Err("Maximum number of steps reached in Linear search with backtracking")
 }
+
+// === Strong Wolfe ===
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StrongWolfe {
+    // Sufficient decrease parameter
+    pub c1: f64,
+    // Curvature parameter (c1 < c2 < 1)
+    pub c2: f64,
+    // Maximum number of trial steps, both while bracketing and zooming
+    pub max_steps: usize,
+}
+
+impl StrongWolfe {
+    pub fn default() -> Self {
+        StrongWolfe {
+            c1: 1e-4,
+            c2: 0.9,
+            max_steps: 20,
+        }
+    }
+
+    // Zooms in on a strong wolfe step between lo (which has sufficient decrease)
+    // and hi. Ends on lo when out of steps
+    fn zoom<F, N: Dim + DimName>(
+        &self,
+        mut lo: Trial<N>,
+        mut hi: Trial<N>,
+        at: F,
+        slope: f64,
+        f_old: f64,
+    ) -> Result<SearchPoint<N>, &'static str>
+    where
+        F: Fn(f64) -> Trial<N>,
+        DefaultAllocator: Allocator<N>,
+    {
+        for _ in 0..self.max_steps {
+            let trial = at(cubic_min(&lo, &hi));
+            // sufficient decrease, which a NaN value is not
+            let decrease = trial.f <= f_old + self.c1 * trial.alpha * slope;
+            if !decrease || trial.f >= lo.f {
+                hi = trial;
+            } else {
+                if trial.slope.abs() <= -self.c2 * slope {
+                    return Ok(trial.point());
+                }
+                if trial.slope * (hi.alpha - lo.alpha) >= 0.0 {
+                    hi = std::mem::replace(&mut lo, trial);
+                } else {
+                    lo = trial;
+                }
+            }
+        }
+        match lo.alpha > 0.0 {
+            true => Ok(lo.point()),
+            false => Err("[STRONG WOLFE] No step with sufficient decrease found"),
+        }
+    }
+}
+
+impl LineSearch for StrongWolfe {
+    fn search<F, N: Dim + DimName>(
+        &self,
+        x_old: &OVector<f64, N>,
+        f_old: f64,
+        grad: &OVector<f64, N>,
+        p: &OVector<f64, N>,
+        stepmax: f64,
+        fxn: F,
+    ) -> Result<SearchPoint<N>, &'static str>
+    where
+        F: Fn(&OVector<f64, N>) -> (OVector<f64, N>, f64),
+        DefaultAllocator: Allocator<N>,
+    {
+        let (p, slope) = descent(grad, p, stepmax)?;
+        let at = |alpha: f64| Trial::at(&fxn, x_old, &p, alpha);
+        let alpha_max = stepmax / p.norm();
+
+        // bracket a step, extrapolating from the newton step
+        let mut prev = Trial::start(x_old, f_old, slope);
+        let mut alpha = alpha_max.min(1.0);
+        for step in 0..self.max_steps {
+            let trial = at(alpha);
+            // sufficient decrease, which a NaN value is not
+            let decrease = trial.f <= f_old + self.c1 * alpha * slope;
+            if !decrease || (step > 0 && trial.f >= prev.f) {
+                return self.zoom(prev, trial, at, slope, f_old);
+            }
+            if trial.slope.abs() <= -self.c2 * slope || alpha >= alpha_max {
+                return Ok(trial.point());
+            }
+            if trial.slope >= 0.0 {
+                return self.zoom(trial, prev, at, slope, f_old);
+            }
+            prev = trial;
+            alpha = (2.0 * alpha).min(alpha_max);
+        }
+        Err("[STRONG WOLFE] Maximum number of steps reached")
+    }
+}
+
+// === More Thuente ===
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MoreThuente {
+    // Sufficient decrease parameter
+    pub c1: f64,
+    // Curvature parameter
+    pub c2: f64,
+    // Relative width of the interval of uncertainty to stop at
+    pub xtol: f64,
+    // Maximum number of trial steps
+    pub max_steps: usize,
+}
+
+impl MoreThuente {
+    pub fn default() -> Self {
+        MoreThuente {
+            c1: 1e-4,
+            c2: 0.9,
+            xtol: 1e-10,
+            max_steps: 20,
+        }
+    }
+}
+
+impl LineSearch for MoreThuente {
+    fn search<F, N: Dim + DimName>(
+        &self,
+        x_old: &OVector<f64, N>,
+        f_old: f64,
+        grad: &OVector<f64, N>,
+        p: &OVector<f64, N>,
+        stepmax: f64,
+        fxn: F,
+    ) -> Result<SearchPoint<N>, &'static str>
+    where
+        F: Fn(&OVector<f64, N>) -> (OVector<f64, N>, f64),
+        DefaultAllocator: Allocator<N>,
+    {
+        // bounds of the interval of uncertainty while nothing is bracketed
+        const XTRAPL: f64 = 1.1;
+        const XTRAPU: f64 = 4.0;
+
+        let (p, slope) = descent(grad, p, stepmax)?;
+        let mut stpmax = stepmax / p.norm();
+        let gtest = self.c1 * slope;
+
+        let mut best = Trial::start(x_old, f_old, slope);
+        let mut interval = Interval {
+            stx: 0.0,
+            fx: f_old,
+            dx: slope,
+            sty: 0.0,
+            fy: f_old,
+            dy: slope,
+            brackt: false,
+        };
+        let mut stp = stpmax.min(1.0);
+        let mut stmin = 0.0;
+        let mut stmax = stp + XTRAPU * stp;
+        let mut width = stpmax;
+        let mut width1 = 2.0 * width;
+        let mut stage1 = true;
+
+        for _ in 0..self.max_steps {
+            let trial = Trial::at(&fxn, x_old, &p, stp);
+            let (f, g) = (trial.f, trial.slope);
+
+            // step out of the domain of the residual, shorten it
+            if !f.is_finite() || !g.is_finite() {
+                stpmax = stp;
+                stmax = stmax.min(stp);
+                stp = 0.5 * (interval.stx + stp);
+                continue;
+            }
+
+            let ftest = f_old + stp * gtest;
+            if stage1 && f <= ftest && g >= 0.0 {
+                stage1 = false;
+            }
+            if f <= ftest && g.abs() <= -self.c2 * slope {
+                return Ok(trial.point());
+            }
+            // rounding errors, or the interval or the step bounds exhausted
+            if (interval.brackt
+                && (stp <= stmin || stp >= stmax || stmax - stmin <= self.xtol * stmax))
+                || (stp == stpmax && f <= ftest && g <= gtest)
+            {
+                return match f <= ftest {
+                    true => Ok(trial.point()),
+                    false => best.found(),
+                };
+            }
+
+            // before a step with sufficient decrease and a positive slope is found
+            // the step is taken for the merit less its sufficient decrease line
+            let (next, moved) = match stage1 && f <= interval.fx && f > ftest {
+                true => {
+                    let mut modified = Interval {
+                        fx: interval.fx - interval.stx * gtest,
+                        dx: interval.dx - gtest,
+                        fy: interval.fy - interval.sty * gtest,
+                        dy: interval.dy - gtest,
+                        ..interval
+                    };
+                    let step = modified.step(stp, f - stp * gtest, g - gtest, stmin, stmax);
+                    interval = Interval {
+                        fx: modified.fx + modified.stx * gtest,
+                        dx: modified.dx + gtest,
+                        fy: modified.fy + modified.sty * gtest,
+                        dy: modified.dy + gtest,
+                        ..modified
+                    };
+                    step
+                }
+                false => interval.step(stp, f, g, stmin, stmax),
+            };
+            if moved {
+                best = trial;
+            }
+            stp = next;
+
+            // bisect when the interval does not shrink fast enough
+            if interval.brackt {
+                if (interval.sty - interval.stx).abs() >= 0.66 * width1 {
+                    stp = interval.stx + 0.5 * (interval.sty - interval.stx);
+                }
+                width1 = width;
+                width = (interval.sty - interval.stx).abs();
+                stmin = interval.stx.min(interval.sty);
+                stmax = interval.stx.max(interval.sty);
+            } else {
+                stmin = stp + XTRAPL * (stp - interval.stx);
+                stmax = stp + XTRAPU * (stp - interval.stx);
+            }
+            stp = stp.max(0.0).min(stpmax);
+            if interval.brackt
+                && (stp <= stmin || stp >= stmax || stmax - stmin <= self.xtol * stmax)
+            {
+                return best.found();
+            }
+        }
+        best.found()
+    }
+}
+
+// Merit and its slope at a point along the search direction
+struct Trial<N: Dim>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    // Step length along the search direction
+    alpha: f64,
+    x: OVector<f64, N>,
+    f_vec: OVector<f64, N>,
+    f: f64,
+    slope: f64,
+}
+
+impl<N: Dim> Trial<N>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    // The starting point of the search, whose residual is not known (it is never
+    // returned)
+    fn start(x_old: &OVector<f64, N>, f_old: f64, slope: f64) -> Self {
+        Trial {
+            alpha: 0.0,
+            x: x_old.clone(),
+            f_vec: x_old.map(|_| f64::NAN),
+            f: f_old,
+            slope,
+        }
+    }
+
+    // Evaluates the merit at x_old + alpha p, and its slope along p by a forward
+    // difference of the residual
+    fn at<F>(fxn: &F, x_old: &OVector<f64, N>, p: &OVector<f64, N>, alpha: f64) -> Self
+    where
+        F: Fn(&OVector<f64, N>) -> (OVector<f64, N>, f64),
+    {
+        let x = x_old + p * alpha;
+        let (f_vec, f) = fxn(&x);
+        let h = EPSILON.sqrt() * (1.0 + x.norm()) / p.norm();
+        let (f_vec_h, _) = fxn(&(&x + p * h));
+        let slope = f_vec.dot(&((f_vec_h - &f_vec) / h));
+        Trial {
+            alpha,
+            x,
+            f_vec,
+            f,
+            slope,
+        }
+    }
+
+    fn point(self) -> SearchPoint<N> {
+        (self.x, self.f_vec, self.f)
+    }
+
+    // The best point of a search which ran out of steps, unless it never left the
+    // starting point
+    fn found(self) -> Result<SearchPoint<N>, &'static str> {
+        match self.alpha > 0.0 {
+            true => Ok(self.point()),
+            false => Err("[LINE SEARCH] No step with sufficient decrease found"),
+        }
+    }
+}
+
+// Search direction shortened to stepmax, with the slope of the merit along it
+fn descent<N: Dim>(
+    grad: &OVector<f64, N>,
+    p: &OVector<f64, N>,
+    stepmax: f64,
+) -> Result<(OVector<f64, N>, f64), &'static str>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    let p_norm = p.norm();
+    let p = match p_norm > stepmax {
+        true => p * (stepmax / p_norm),
+        false => p.clone(),
+    };
+    let slope = grad.dot(&p);
+    match slope < 0.0 {
+        true => Ok((p, slope)),
+        false => Err("[LINE SEARCH] Search direction is not a descent direction"),
+    }
+}
+
+// Step length minimizing the cubic through the merits and slopes of two trial
+// steps, safeguarded to the middle 80% of the interval between them (bisecting
+// otherwise)
+fn cubic_min<N: Dim>(a: &Trial<N>, b: &Trial<N>) -> f64
+where
+    DefaultAllocator: Allocator<N>,
+{
+    let d1 = a.slope + b.slope - 3.0 * (a.f - b.f) / (a.alpha - b.alpha);
+    let d2 = (b.alpha - a.alpha).signum() * (d1 * d1 - a.slope * b.slope).sqrt();
+    let alpha =
+        b.alpha - (b.alpha - a.alpha) * (b.slope + d2 - d1) / (b.slope - a.slope + 2.0 * d2);
+    let (lo, hi) = (a.alpha.min(b.alpha), a.alpha.max(b.alpha));
+    let margin = 0.1 * (hi - lo);
+    match alpha >= lo + margin && alpha <= hi - margin {
+        true => alpha,
+        false => 0.5 * (lo + hi),
+    }
+}
+
+// Interval of uncertainty of the More Thuente search, with the merit (f) and its
+// slope (d) at both ends. stx is the step with the least merit so far
+#[derive(Debug, Clone, Copy)]
+struct Interval {
+    stx: f64,
+    fx: f64,
+    dx: f64,
+    sty: f64,
+    fy: f64,
+    dy: f64,
+    // Whether the interval brackets a step satisfying the wolfe conditions
+    brackt: bool,
+}
+
+impl Interval {
+    // Updates the interval with the trial step stp (MINPACK-2 `dcstep`), returning
+    // the next trial step, kept within [stpmin, stpmax], and whether stx moved to
+    // stp
+    fn step(&mut self, stp: f64, fp: f64, dp: f64, stpmin: f64, stpmax: f64) -> (f64, bool) {
+        let (stx, fx, dx) = (self.stx, self.fx, self.dx);
+        let (sty, fy, dy) = (self.sty, self.fy, self.dy);
+        let sgnd = dp * dx.signum();
+        let cubic_theta = |fa: f64, fb: f64, da: f64, db: f64, a: f64, b: f64| {
+            let theta = 3.0 * (fa - fb) / (b - a) + da + db;
+            let s = theta.abs().max(da.abs()).max(db.abs());
+            (theta, s)
+        };
+
+        let stpf = if fp > fx {
+            // higher merit, the minimum is bracketed. Take the cubic step if closer
+            // to stx than the quadratic one, else their average
+            let (theta, s) = cubic_theta(fx, fp, dx, dp, stx, stp);
+            let mut gamma = s * ((theta / s).powi(2) - (dx / s) * (dp / s)).sqrt();
+            if stp < stx {
+                gamma = -gamma;
+            }
+            let r = ((gamma - dx) + theta) / (((gamma - dx) + gamma) + dp);
+            let stpc = stx + r * (stp - stx);
+            let stpq = stx + ((dx / ((fx - fp) / (stp - stx) + dx)) / 2.0) * (stp - stx);
+            self.brackt = true;
+            match (stpc - stx).abs() < (stpq - stx).abs() {
+                true => stpc,
+                false => stpc + (stpq - stpc) / 2.0,
+            }
+        } else if sgnd < 0.0 {
+            // lower merit and slopes of opposite sign, the minimum is bracketed.
+            // Take the cubic step if further from stp than the secant one
+            let (theta, s) = cubic_theta(fx, fp, dx, dp, stx, stp);
+            let mut gamma = s * ((theta / s).powi(2) - (dx / s) * (dp / s)).sqrt();
+            if stp > stx {
+                gamma = -gamma;
+            }
+            let r = ((gamma - dp) + theta) / (((gamma - dp) + gamma) + dx);
+            let stpc = stp + r * (stx - stp);
+            let stpq = stp + (dp / (dp - dx)) * (stx - stp);
+            self.brackt = true;
+            match (stpc - stp).abs() > (stpq - stp).abs() {
+                true => stpc,
+                false => stpq,
+            }
+        } else if dp.abs() < dx.abs() {
+            // lower merit, same sign of the slope and its magnitude decreasing
+            let (theta, s) = cubic_theta(fx, fp, dx, dp, stx, stp);
+            let mut gamma = s * ((theta / s).powi(2) - (dx / s) * (dp / s)).max(0.0).sqrt();
+            if stp > stx {
+                gamma = -gamma;
+            }
+            let r = ((gamma - dp) + theta) / ((gamma + (dx - dp)) + gamma);
+            let stpc = match (r < 0.0 && gamma != 0.0, stp > stx) {
+                (true, _) => stp + r * (stx - stp),
+                (false, true) => stpmax,
+                (false, false) => stpmin,
+            };
+            let stpq = stp + (dp / (dp - dx)) * (stx - stp);
+            if self.brackt {
+                let stpf = match (stpc - stp).abs() < (stpq - stp).abs() {
+                    true => stpc,
+                    false => stpq,
+                };
+                match stp > stx {
+                    true => stpf.min(stp + 0.66 * (sty - stp)),
+                    false => stpf.max(stp + 0.66 * (sty - stp)),
+                }
+            } else {
+                let stpf = match (stpc - stp).abs() > (stpq - stp).abs() {
+                    true => stpc,
+                    false => stpq,
+                };
+                stpf.min(stpmax).max(stpmin)
+            }
+        } else if self.brackt {
+            // lower merit, same sign of the slope and its magnitude not decreasing
+            let (theta, s) = cubic_theta(fp, fy, dp, dy, stp, sty);
+            let mut gamma = s * ((theta / s).powi(2) - (dy / s) * (dp / s)).sqrt();
+            if stp > sty {
+                gamma = -gamma;
+            }
+            let r = ((gamma - dp) + theta) / (((gamma - dp) + gamma) + dy);
+            stp + r * (sty - stp)
+        } else if stp > stx {
+            stpmax
+        } else {
+            stpmin
+        };
+
+        // update the interval
+        let moved = fp.is_nan() || fp <= fx;
+        if moved {
+            if sgnd < 0.0 {
+                self.sty = stx;
+                self.fy = fx;
+                self.dy = dx;
+            }
+            self.stx = stp;
+            self.fx = fp;
+            self.dx = dp;
+        } else {
+            self.sty = stp;
+            self.fy = fp;
+            self.dy = dp;
+        }
+        (stpf, moved)
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use na::{Matrix2, Vector2};
+
+    // rosenbrock residual, whose merit 0.5 F.F is the rosenbrock function
+    fn residual(x: &Vector2<f64>) -> (Vector2<f64>, f64) {
+        let f_vec = Vector2::new(10.0 * (x[1] - x[0].powi(2)), 1.0 - x[0]);
+        let f = 0.5 * f_vec.dot(&f_vec);
+        (f_vec, f)
+    }
+
+    // steepest descent on the merit from the classic starting point, along which
+    // the unit step overshoots
+    fn start() -> (Vector2<f64>, f64, Vector2<f64>) {
+        let x = Vector2::new(-1.2, 1.0);
+        let (f_vec, f) = residual(&x);
+        let jac = Matrix2::new(-20.0 * x[0], 10.0, -1.0, 0.0);
+        (x, f, jac.tr_mul(&f_vec))
+    }
+
+    fn check<S: LineSearch>(search: &S, wolfe: bool) {
+        let (x, f, grad) = start();
+        let p = -&grad;
+        let slope = grad.dot(&p);
+        let (x_new, f_vec, f_new) = search.search(&x, f, &grad, &p, 1e3, residual).unwrap();
+        assert_eq!((f_vec, f_new), residual(&x_new));
+
+        // sufficient decrease, and the slope flattened for the wolfe searches
+        let alpha = (x_new - x).norm() / p.norm();
+        assert!(alpha > 0.0);
+        assert!(f_new <= f + 1e-4 * alpha * slope);
+        if wolfe {
+            let h = 1e-7;
+            let slope_new = (residual(&(x_new + p * h)).1 - f_new) / h;
+            assert!(slope_new.abs() <= -0.9 * slope * (1.0 + 1e-4));
+        }
+    }
+
+    #[test]
+    fn test_armijo() {
+        check(&Armijo::default(), false);
+    }
+
+    #[test]
+    fn test_strong_wolfe() {
+        check(&StrongWolfe::default(), true);
+    }
+
+    #[test]
+    fn test_more_thuente() {
+        check(&MoreThuente::default(), true);
+    }
+
+    #[test]
+    fn test_not_descent() {
+        let (x, f, grad) = start();
+        let wolfe = StrongWolfe::default().search(&x, f, &grad, &grad, 1e3, residual);
+        assert!(wolfe.is_err());
+        let more_thuente = MoreThuente::default().search(&x, f, &grad, &grad, 1e3, residual);
+        assert!(more_thuente.is_err());
+    }
+}
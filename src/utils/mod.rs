@@ -1,4 +1,26 @@
+pub mod arena;
+pub mod banded;
+pub mod dense;
+#[cfg(feature = "distributed")]
+pub mod distributed;
+#[cfg(feature = "double_double")]
+pub mod double_double;
 pub mod euler;
 pub mod finite_diff;
+#[cfg(feature = "validated")]
+pub mod interval;
+pub mod kahan;
+pub mod kron;
+pub mod least_squares;
+pub mod linalg;
 pub mod linsearch;
 pub mod newton_raphson;
+pub mod norms;
+#[cfg(feature = "plotters")]
+pub mod plot;
+pub mod poly;
+pub mod precond;
+pub mod rng;
+pub mod solver_dim;
+pub mod sparse;
+pub mod sweep;
@@ -4,214 +4,677 @@
 ///
 /// This method uses an initial guess for the
 ///
+/// Each solver has a `try_` variant for residuals which can fail to evaluate
+/// (`Fn(&OVector<f64, N>) -> Result<OVector<f64, N>, E>`), e.g. through table
+/// lookups or interpolation out of range. The first failure ends the solve with
+/// `NewtonError::Residual` carrying the error of the residual, while failures of
+/// the solver itself are `NewtonError::Unconverged`. Those carry a
+/// `NewtonDiagnostics`, the iteration by iteration record of the solve (residual
+/// and step norms, jacobian condition numbers and line search step lengths), to
+/// tell a badly scaled problem from a singular one or a bad initial guess. A
+/// line search which stalls at a local minimum of 0.5 F.F that is not a root ends
+/// with `NewtonError::LocalMinimum` instead.
 ///
 // === Begin Imports ===
 // std library imports
+use std::cell::Cell;
+use std::convert::Infallible;
 use std::f64::EPSILON;
 
 // third party imports
 extern crate nalgebra as na;
 use na::allocator::Allocator;
-use na::{DefaultAllocator, Dim, DimMin, DimName, DimSub, MatrixN, VectorN, U1};
+use na::{DefaultAllocator, Dim, DimName, OMatrix, OVector, SVD};
 
 // local imports
-use super::finite_diff::{fdiff_jacobian, fdiff_jacobian_2};
-use super::linsearch::linsrch_w_backtracking;
+use super::finite_diff::{
+    fdiff_jacobian, fdiff_jacobian_2, fdiff_jacobian_banded, fdiff_jacobian_richardson,
+};
+use super::linalg::{KrylovSolver, Precond};
+use super::linsearch::{Armijo, LineSearch};
+use super::norms::weighted_rms_norm;
+use super::solver_dim::{SolverAllocator, SolverDim};
+use super::sweep::sweep;
 
 // === End Imports ===
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum NewtonError<E> {
+    // The residual failed to evaluate
+    Residual(E),
+    // The solver failed (singular jacobian, no convergence, ...)
+    Solver(&'static str),
+    // The solver failed, with the record of the solve up to the failure
+    Unconverged(&'static str, Box<NewtonDiagnostics>),
+    // The line search converged to a local minimum of 0.5 F.F at the point given,
+    // which is not a root. A restart from a different initial guess (or with the
+    // point deflated, see `newton_raphson_deflated`) may still find a root, while
+    // restarting from the point itself will not
+    LocalMinimum(Vec<f64>, Box<NewtonDiagnostics>),
+}
+
+// Message of a `NewtonError::LocalMinimum`
+const LOCAL_MINIMUM: &str =
+    "[NEWTON LINSRCH] Converged to a local minimum of 0.5 F.F which is not a root, restart from a different initial guess";
+
+impl<E> NewtonError<E> {
+    // Record of the solve, for failures of the solver
+    pub fn diagnostics(&self) -> Option<&NewtonDiagnostics> {
+        match self {
+            NewtonError::Unconverged(_, diagnostics)
+            | NewtonError::LocalMinimum(_, diagnostics) => Some(diagnostics),
+            NewtonError::Residual(_) | NewtonError::Solver(_) => None,
+        }
+    }
+
+    // Attaches the record of the solve to a failure of the solver
+    fn diagnosed(self, diagnostics: NewtonDiagnostics) -> Self {
+        match self {
+            NewtonError::Solver(err) => NewtonError::Unconverged(err, Box::new(diagnostics)),
+            NewtonError::LocalMinimum(x, _) => NewtonError::LocalMinimum(x, Box::new(diagnostics)),
+            err => err,
+        }
+    }
+}
+
+impl<E> From<&'static str> for NewtonError<E> {
+    fn from(err: &'static str) -> Self {
+        NewtonError::Solver(err)
+    }
+}
+
+// so that residuals failing with the repo's own errors can use `?` on the result
+impl From<NewtonError<&'static str>> for &'static str {
+    fn from(err: NewtonError<&'static str>) -> Self {
+        match err {
+            NewtonError::Residual(err)
+            | NewtonError::Solver(err)
+            | NewtonError::Unconverged(err, _) => err,
+            NewtonError::LocalMinimum(..) => LOCAL_MINIMUM,
+        }
+    }
+}
+
+// Solver error of an infallible residual
+fn solver_error(err: NewtonError<Infallible>) -> &'static str {
+    match err {
+        NewtonError::Residual(never) => match never {},
+        NewtonError::Solver(err) | NewtonError::Unconverged(err, _) => err,
+        NewtonError::LocalMinimum(..) => LOCAL_MINIMUM,
+    }
+}
+
+// Iteration by iteration record of a newton solve. A residual which stops
+// decreasing with full steps and well conditioned jacobians points to a bad
+// initial guess (or a local minimum of |f|), a growing condition number to a
+// singular problem, and condition numbers in the millions from the first
+// iteration to badly scaled unknowns or residuals
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct NewtonDiagnostics {
+    // Infinity norm of the residual at the initial guess and after each iteration
+    pub residual_norms: Vec<f64>,
+    // Euclidean norm of the step taken in each iteration
+    pub step_norms: Vec<f64>,
+    // Condition number (ratio of the extreme singular values) of the jacobian each
+    // step was solved with, infinite when singular. For Broyden that of the
+    // jacobian last inverted (before the secant updates of its inverse), NaN for
+    // the solvers which do not form a dense jacobian (newton krylov, banded)
+    pub conditions: Vec<f64>,
+    // Fraction of the newton step taken in each iteration (1 for full steps, less
+    // after a line search or backtracking)
+    pub alphas: Vec<f64>,
+}
+
+impl NewtonDiagnostics {
+    // Number of iterations taken
+    pub fn iterations(&self) -> usize {
+        self.step_norms.len()
+    }
+
+    // Records an iteration, with the residual norm after its step
+    fn record(&mut self, residual: f64, step: f64, condition: f64, alpha: f64) {
+        self.residual_norms.push(residual);
+        self.step_norms.push(step);
+        self.conditions.push(condition);
+        self.alphas.push(alpha);
+    }
+}
+
+// A fallible residual evaluated where an infallible one is expected (finite
+// difference jacobians, line searches and krylov products). The first error is
+// kept and NaNs are returned from then on, until the error is taken
+struct Trap<'a, F, E> {
+    // Fallible residual
+    fxn: &'a F,
+    // First error since the last take
+    err: Cell<Option<E>>,
+}
+
+impl<'a, F, E> Trap<'a, F, E> {
+    fn new(fxn: &'a F) -> Self {
+        Trap {
+            fxn,
+            err: Cell::new(None),
+        }
+    }
+
+    fn eval<N: Dim>(&self, x: &OVector<f64, N>) -> OVector<f64, N>
+    where
+        F: Fn(&OVector<f64, N>) -> Result<OVector<f64, N>, E>,
+        DefaultAllocator: Allocator<N>,
+    {
+        let err = self.err.take();
+        if err.is_none() {
+            match (self.fxn)(x) {
+                Ok(f_x) => return f_x,
+                Err(err) => self.err.set(Some(err)),
+            }
+        } else {
+            self.err.set(err);
+        }
+        x.map(|_| f64::NAN)
+    }
+
+    // Errors with the first failure of the residual since the last take
+    fn take(&self) -> Result<(), NewtonError<E>> {
+        match self.err.take() {
+            Some(err) => Err(NewtonError::Residual(err)),
+            None => Ok(()),
+        }
+    }
+}
+
+// Whether a newton update del_x (from x_last to x_new) is below tol, measured in the
+// weighted rms norm with tol as both the absolute and relative tolerance
+fn converged_x<N: Dim>(
+    del_x: &OVector<f64, N>,
+    x_last: &OVector<f64, N>,
+    x_new: &OVector<f64, N>,
+    tol: f64,
+) -> bool
+where
+    DefaultAllocator: Allocator<N>,
+{
+    let atol = x_new.map(|_| tol);
+    weighted_rms_norm(del_x, x_last, x_new, &atol, tol) < 1.0
+}
+
+// Largest magnitude of a residual, NaN if any component is NaN (a running max of
+// comparisons passes over NaNs, taking a residual which failed for a root)
+fn residual_size<N: Dim>(f: &OVector<f64, N>) -> f64
+where
+    DefaultAllocator: Allocator<N>,
+{
+    f.iter()
+        .fold(0.0, |size: f64, val| match size.is_nan() || val.is_nan() {
+            true => f64::NAN,
+            false => size.max(val.abs()),
+        })
+}
+
+// Whether x, where the step of a line search vanished, is a local minimum of the
+// merit f = 0.5 F.F rather than a root: the gradient test of numerical recipes
+// (pg 480), the largest component of the gradient relative to x and f
+fn local_minimum<N: Dim>(grad: &OVector<f64, N>, x: &OVector<f64, N>, f: f64) -> bool
+where
+    DefaultAllocator: Allocator<N>,
+{
+    const TOLMIN: f64 = 1e-6;
+
+    let den = f.max(0.5 * x.len() as f64);
+    let test = grad.iter().zip(x.iter()).fold(0.0_f64, |test, (g, x)| {
+        test.max(g.abs() * x.abs().max(1.0) / den)
+    });
+    test < TOLMIN
+}
+
+// Pseudo inverse of a jacobian, with its condition number from the same SVD
+// (infinite when the pseudo inverse drops a singular value below eps)
+fn pseudo_inverse_cond<N: SolverDim>(
+    jac: OMatrix<f64, N, N>,
+    eps: f64,
+) -> Result<(OMatrix<f64, N, N>, f64), &'static str>
+where
+    DefaultAllocator: SolverAllocator<N>,
+{
+    let svd = SVD::new_unordered(jac, true, true);
+    let (s_max, s_min) = (svd.singular_values.max(), svd.singular_values.min());
+    let cond = match s_min > eps {
+        true => s_max / s_min,
+        false => f64::INFINITY,
+    };
+    Ok((svd.pseudo_inverse(eps)?, cond))
+}
+
 // Newton raphson method using Broydens method
 // see: https://en.wikipedia.org/wiki/Broyden%27s_method
 //
-pub fn newton_raphson_broyden<F, N: Dim + DimName + DimMin<N> + DimSub<U1>>(
+// The jacobian is inverted once and its inverse then carried along by the
+// Sherman-Morrison form of the rank one update, so each iteration is O(N^2)
+// rather than the O(N^3) of a fresh inverse (which is only taken again when the
+// update breaks down)
+pub fn newton_raphson_broyden<F, N: SolverDim>(
+    fxn: F,
+    x_0: OVector<f64, N>,
+    acc: f64,
+) -> Result<OVector<f64, N>, &'static str>
+where
+    DefaultAllocator: SolverAllocator<N>,
+    F: Fn(&OVector<f64, N>) -> OVector<f64, N>,
+{
+    try_newton_raphson_broyden(|x: &OVector<f64, N>| Ok(fxn(x)), x_0, acc).map_err(solver_error)
+}
+
+// Broydens method for a fallible residual
+pub fn try_newton_raphson_broyden<F, E, N: SolverDim>(
+    fxn: F,
+    x_0: OVector<f64, N>,
+    acc: f64,
+) -> Result<OVector<f64, N>, NewtonError<E>>
+where
+    DefaultAllocator: SolverAllocator<N>,
+    F: Fn(&OVector<f64, N>) -> Result<OVector<f64, N>, E>,
+{
+    let mut diagnostics = NewtonDiagnostics::default();
+    broyden_from(&fxn, x_0, acc, None, &mut diagnostics)
+        .map(|sol| sol.x)
+        .map_err(|err| err.diagnosed(diagnostics))
+}
+
+// Root found by a solver, with the jacobian (estimate) it ended on
+#[derive(Debug, Clone, PartialEq)]
+pub struct WarmSolution<N: Dim + DimName>
+where
+    DefaultAllocator: Allocator<N> + Allocator<N, N>,
+{
+    // Root
+    pub x: OVector<f64, N>,
+    // Jacobian estimate at the root, to seed the solve of a similar problem or to
+    // linearize about the root
+    pub jacobian: OMatrix<f64, N, N>,
+}
+
+// Broydens method seeded with the jacobian of a previous solve (e.g. the previous
+// iteration of an optimization loop), which saves the finite difference jacobian
+// and most of the iterations Broyden needs to rebuild it. The jacobian the solve
+// ends on is returned to seed the next one. Falls back to a finite difference
+// jacobian when the solve from the seed fails
+pub fn newton_raphson_broyden_warm<F, N: SolverDim>(
+    fxn: F,
+    x_0: OVector<f64, N>,
+    acc: f64,
+    jacobian: Option<OMatrix<f64, N, N>>,
+) -> Result<WarmSolution<N>, &'static str>
+where
+    DefaultAllocator: SolverAllocator<N>,
+    F: Fn(&OVector<f64, N>) -> OVector<f64, N>,
+{
+    try_newton_raphson_broyden_warm(|x: &OVector<f64, N>| Ok(fxn(x)), x_0, acc, jacobian)
+        .map_err(solver_error)
+}
+
+// Warm started Broydens method for a fallible residual
+pub fn try_newton_raphson_broyden_warm<F, E, N: SolverDim>(
     fxn: F,
-    x_0: VectorN<f64, N>,
+    x_0: OVector<f64, N>,
     acc: f64,
-) -> Result<VectorN<f64, N>, &'static str>
+    jacobian: Option<OMatrix<f64, N, N>>,
+) -> Result<WarmSolution<N>, NewtonError<E>>
 where
-    F: Fn(&VectorN<f64, N>) -> VectorN<f64, N>,
-    DefaultAllocator: Allocator<f64, N>
-        + Allocator<f64, U1, N>
-        + Allocator<f64, N, N>
-        + Allocator<f64, <N as DimMin<N>>::Output, N>
-        + Allocator<f64, <N as DimMin<N>>::Output>
-        + Allocator<f64, N, <N as DimMin<N>>::Output>
-        + Allocator<f64, <<N as DimMin<N>>::Output as DimSub<U1>>::Output>,
-    <N as DimMin<N>>::Output: DimName,
-    <N as DimMin<N>>::Output: DimSub<U1>,
+    DefaultAllocator: SolverAllocator<N>,
+    F: Fn(&OVector<f64, N>) -> Result<OVector<f64, N>, E>,
+{
+    let warm = jacobian.is_some();
+    let mut diagnostics = NewtonDiagnostics::default();
+    match broyden_from(&fxn, x_0.clone(), acc, jacobian, &mut diagnostics) {
+        Err(NewtonError::Solver(_)) if warm => {
+            diagnostics = NewtonDiagnostics::default();
+            broyden_from(&fxn, x_0, acc, None, &mut diagnostics)
+        }
+        solution => solution,
+    }
+    .map_err(|err| err.diagnosed(diagnostics))
+}
+
+// Broydens method from the given jacobian estimate, or a finite difference one,
+// recording its iterations
+fn broyden_from<F, E, N: SolverDim>(
+    fxn: &F,
+    x_0: OVector<f64, N>,
+    acc: f64,
+    jacobian: Option<OMatrix<f64, N, N>>,
+    diagnostics: &mut NewtonDiagnostics,
+) -> Result<WarmSolution<N>, NewtonError<E>>
+where
+    DefaultAllocator: SolverAllocator<N>,
+    F: Fn(&OVector<f64, N>) -> Result<OVector<f64, N>, E>,
 {
     const MAX_ITER: i32 = 200;
     const INV_TOL: f64 = EPSILON;
-    const TOLX: f64 = 1.0_e-7_f64;
+    // Smallest denominator of the inverse update, relative to the vectors in it,
+    // below which the inverse is taken afresh
+    const SM_TOL: f64 = 1e-12;
+
+    let trap = Trap::new(fxn);
+    let eval = |x: &OVector<f64, N>| fxn(x).map_err(NewtonError::Residual);
 
     // pre-initialize variables
-    let dim = x_0.len();
-    let mut f_n = fxn(&x_0);
+    let mut f_n = eval(&x_0)?;
     let mut x_last = x_0.clone();
 
-    // check if first guess is root
-    let mut test = 0.0;
-    for idx in 0..dim {
-        if f_n[idx].abs() > test {
-            test = f_n[idx].abs()
+    // initialize the jacobian estimate
+    let mut jac: OMatrix<f64, N, N> = match jacobian {
+        Some(jac) => jac,
+        None => {
+            let jac = fdiff_jacobian_2(&|x: &OVector<f64, N>| trap.eval(x), &f_n, &x_0);
+            trap.take()?;
+            jac
         }
-    }
+    };
+
+    // check if first guess is root
+    let test = residual_size(&f_n);
+    diagnostics.residual_norms.push(test);
     if test < 0.01 * acc {
-        return Ok(x_last);
+        return Ok(WarmSolution {
+            x: x_last,
+            jacobian: jac,
+        });
     }
 
-    // if initial guess is not a root initialize values
-    let mut jac: MatrixN<f64, N> = fdiff_jacobian_2(&fxn, &f_n, &x_0);
+    // the inverse is taken once and then secant updated alongside the jacobian
+    let (mut jac_inv, mut cond) = pseudo_inverse_cond(jac.clone(), INV_TOL)?;
 
     // empty allocations
-    let mut x_new: VectorN<f64, N>;
-    let mut f_last: VectorN<f64, N>;
-    let mut del_x: VectorN<f64, N>;
+    let mut x_new: OVector<f64, N>;
+    let mut f_last: OVector<f64, N>;
+    let mut del_x: OVector<f64, N>;
     let mut del_x_norm: f64;
-    let mut del_f: VectorN<f64, N>;
+    let mut del_f: OVector<f64, N>;
     let mut test_f: f64;
-    let mut test_x: f64;
 
     // Iterate to victory!
     for _ in 0..MAX_ITER {
         // update x guess
-        x_new = &x_last - jac.clone().pseudo_inverse(INV_TOL)? * &f_n;
+        x_new = &x_last - &jac_inv * &f_n;
 
         del_x = &x_new - &x_last;
         del_x_norm = del_x.norm();
-
-        // check for convergence of x
-        test_x = 0.0;
-        for idx in 0..dim {
-            let temp = (del_x[idx]).abs() / (x_new[idx]).abs().max(1.0);
-            if temp > test_x {
-                test_x = temp;
-            }
-        }
-        if test_x < TOLX {
-            return Ok(x_last);
+        // the rank one updates only approximate the jacobian, so a small step is
+        // not yet a root to acc (the residual decides below), unless it stalls
+        if del_x_norm == 0.0 {
+            diagnostics.record(residual_size(&f_n), del_x_norm, cond, 1.0);
+            return Err(NewtonError::Solver(
+                "[NEWTON BROYDEN] Stalled short of a root",
+            ));
         }
         x_last = x_new.clone();
 
         // Function updates
         f_last = f_n.clone();
-        f_n = fxn(&x_new);
+        f_n = eval(&x_new)?;
         del_f = &f_n - &f_last;
+        // rank one update jac += (del_f - jac del_x) del_x^T / |del_x|^2
+        let update = (&del_f - &jac * &del_x) / del_x_norm.powf(2.0);
+        jac.ger(1.0, &update, &del_x, 1.0);
+        // and of its inverse by Sherman-Morrison
+        // jac_inv += (del_x - jac_inv del_f) del_x^T jac_inv / (del_x^T jac_inv del_f)
+        let inv_del_f = &jac_inv * &del_f;
+        let denom = del_x.dot(&inv_del_f);
+        if denom.abs() > SM_TOL * del_x_norm * inv_del_f.norm() {
+            let row = jac_inv.tr_mul(&del_x);
+            jac_inv.ger(1.0 / denom, &(&del_x - inv_del_f), &row, 1.0);
+        } else {
+            (jac_inv, cond) = pseudo_inverse_cond(jac.clone(), INV_TOL)?;
+        }
 
         // check for convergence of function
-        test_f = 0.0;
-        for idx in 0..dim {
-            if (f_n[idx]).abs() > test_f {
-                test_f = f_n[idx].abs();
-            }
-        }
+        test_f = residual_size(&f_n);
+        diagnostics.record(test_f, del_x_norm, cond, 1.0);
         if test_f < acc {
-            return Ok(x_new);
+            return Ok(WarmSolution {
+                x: x_new,
+                jacobian: jac,
+            });
         }
-        jac = &jac + (&del_f - &jac * &del_x) / del_x_norm.powf(2.0) * &del_x.transpose();
     }
-    return Err("[NEWTON BROYDEN] Maximum Number of Iterations Reached");
+    Err(NewtonError::Solver(
+        "[NEWTON BROYDEN] Maximum Number of Iterations Reached",
+    ))
 }
 
 // Basic newton-raphson method using finite differencing
-pub fn newton_raphson_fdiff<F, N: Dim + DimName + DimMin<N> + DimSub<U1>>(
+pub fn newton_raphson_fdiff<F, N: SolverDim>(
     fxn: F,
-    x_0: VectorN<f64, N>,
+    x_0: OVector<f64, N>,
     acc: f64,
-) -> Result<VectorN<f64, N>, &'static str>
+) -> Result<OVector<f64, N>, &'static str>
 where
-    F: Fn(&VectorN<f64, N>) -> VectorN<f64, N>,
-    DefaultAllocator: Allocator<f64, N>
-        + Allocator<f64, N, N>
-        + Allocator<f64, <N as DimMin<N>>::Output, N>
-        + Allocator<f64, <N as DimMin<N>>::Output>
-        + Allocator<f64, N, <N as DimMin<N>>::Output>
-        + Allocator<f64, <<N as DimMin<N>>::Output as DimSub<U1>>::Output>,
-    <N as DimMin<N>>::Output: DimName,
-    <N as DimMin<N>>::Output: DimSub<U1>,
+    DefaultAllocator: SolverAllocator<N>,
+    F: Fn(&OVector<f64, N>) -> OVector<f64, N>,
+{
+    try_newton_raphson_fdiff(|x: &OVector<f64, N>| Ok(fxn(x)), x_0, acc).map_err(solver_error)
+}
+
+// Finite differencing newton-raphson method for a fallible residual. Once an
+// iteration fails to reduce the residual (noise in the differenced jacobian can stall
+// the convergence) the jacobian is richardson extrapolated from then on
+pub fn try_newton_raphson_fdiff<F, E, N: SolverDim>(
+    fxn: F,
+    x_0: OVector<f64, N>,
+    acc: f64,
+) -> Result<OVector<f64, N>, NewtonError<E>>
+where
+    DefaultAllocator: SolverAllocator<N>,
+    F: Fn(&OVector<f64, N>) -> Result<OVector<f64, N>, E>,
+{
+    let mut diagnostics = NewtonDiagnostics::default();
+    fdiff_solve(&fxn, x_0, acc, &mut diagnostics)
+        .map(|(x, _)| x)
+        .map_err(|err| err.diagnosed(diagnostics))
+}
+
+// Finite differencing newton-raphson method returning the root with the jacobian
+// of the last iteration, so callers linearizing about the root (stability
+// analyses, the next step of an implicit integrator) need not difference the
+// residual again. That jacobian is taken one newton step (which has converged, so
+// is small) from the root, or at the root when the initial guess is one
+pub fn newton_raphson_fdiff_jac<F, N: SolverDim>(
+    fxn: F,
+    x_0: OVector<f64, N>,
+    acc: f64,
+) -> Result<WarmSolution<N>, &'static str>
+where
+    DefaultAllocator: SolverAllocator<N>,
+    F: Fn(&OVector<f64, N>) -> OVector<f64, N>,
+{
+    try_newton_raphson_fdiff_jac(|x: &OVector<f64, N>| Ok(fxn(x)), x_0, acc).map_err(solver_error)
+}
+
+// Finite differencing newton-raphson method returning the root with the jacobian
+// of the last iteration, for a fallible residual
+pub fn try_newton_raphson_fdiff_jac<F, E, N: SolverDim>(
+    fxn: F,
+    x_0: OVector<f64, N>,
+    acc: f64,
+) -> Result<WarmSolution<N>, NewtonError<E>>
+where
+    DefaultAllocator: SolverAllocator<N>,
+    F: Fn(&OVector<f64, N>) -> Result<OVector<f64, N>, E>,
+{
+    let mut diagnostics = NewtonDiagnostics::default();
+    let (x, jacobian) =
+        fdiff_solve(&fxn, x_0, acc, &mut diagnostics).map_err(|err| err.diagnosed(diagnostics))?;
+    let jacobian = match jacobian {
+        Some(jac) => jac,
+        None => {
+            let trap = Trap::new(&fxn);
+            let f_x = fxn(&x).map_err(NewtonError::Residual)?;
+            let jac = fdiff_jacobian(&|x: &OVector<f64, N>| trap.eval(x), &f_x, &x);
+            trap.take()?;
+            jac
+        }
+    };
+    Ok(WarmSolution { x, jacobian })
+}
+
+// Finite differencing newton-raphson method, recording its iterations. Returns the
+// jacobian of the last iteration with the root, none when the initial guess is one
+fn fdiff_solve<F, E, N: SolverDim>(
+    fxn: &F,
+    x_0: OVector<f64, N>,
+    acc: f64,
+    diagnostics: &mut NewtonDiagnostics,
+) -> Result<(OVector<f64, N>, Option<OMatrix<f64, N, N>>), NewtonError<E>>
+where
+    DefaultAllocator: SolverAllocator<N>,
+    F: Fn(&OVector<f64, N>) -> Result<OVector<f64, N>, E>,
 {
     const MAX_ITER: i32 = 200;
     const INV_TOL: f64 = EPSILON;
     const TOLX: f64 = 1.0_e-7_f64;
 
+    let trap = Trap::new(fxn);
+    let eval = |x: &OVector<f64, N>| fxn(x).map_err(NewtonError::Residual);
+    let jacobian = |f_x: &OVector<f64, N>, x: &OVector<f64, N>, richardson: bool| {
+        let trapped = |x: &OVector<f64, N>| trap.eval(x);
+        let jac = match richardson {
+            true => fdiff_jacobian_richardson(&trapped, f_x, x),
+            false => fdiff_jacobian(&trapped, f_x, x),
+        };
+        trap.take().map(|_| jac)
+    };
+
     // pre-initialize variables
-    let mut fk = fxn(&x_0);
-    let dim = x_0.len();
+    let mut fk = eval(&x_0)?;
 
     // check if first guess is root
-    let mut test = 0.0;
-    for idx in 0..dim {
-        if fk[idx].abs() > test {
-            test = fk[idx].abs()
-        }
-    }
+    let test = residual_size(&fk);
+    diagnostics.residual_norms.push(test);
     if test < 0.01 * acc {
-        return Ok(x_0);
+        return Ok((x_0, None));
     }
 
     // if not a root initialize other vals
-    let mut jac_inv: MatrixN<f64, N> = fdiff_jacobian(&fxn, &fk, &x_0).pseudo_inverse(INV_TOL)?;
-    let mut x_new: VectorN<f64, N>;
-    let mut del_x: VectorN<f64, N>;
+    let mut jac = jacobian(&fk, &x_0, false)?;
+    let (mut jac_inv, mut cond) = pseudo_inverse_cond(jac.clone(), INV_TOL)?;
+    let mut x_new: OVector<f64, N>;
+    let mut del_x: OVector<f64, N>;
     let mut x_last = x_0.clone();
-    let mut test_x: f64;
     let mut test_f: f64;
+    let mut test_last = test;
+    let mut richardson = false;
 
     // Iterate to victory!
     for _j in 0..MAX_ITER {
         // update x
-        x_new = &x_last - jac_inv * &fk;
+        x_new = &x_last - &jac_inv * &fk;
         del_x = &x_new - &x_last;
 
-        // check for convergence of x
-        test_x = 0.0;
-        for idx in 0..dim {
-            let temp = (del_x[idx]).abs() / x_new[idx].abs().max(1.0);
-            if temp > test_x {
-                test_x = temp;
+        // check for convergence of x, which is only a root if the residual there
+        // is (a residual without a root stalls the steps just the same)
+        if converged_x(&del_x, &x_last, &x_new, TOLX) {
+            fk = eval(&x_new)?;
+            test_f = residual_size(&fk);
+            diagnostics.record(test_f, del_x.norm(), cond, 1.0);
+            if test_f < acc {
+                return Ok((x_new, Some(jac)));
             }
-        }
-        if test_x < TOLX {
-            return Ok(x_last);
+            return Err(NewtonError::Solver(
+                "[NEWTON FDIFF] Stalled short of a root",
+            ));
         }
         x_last = x_new.clone();
 
         // update function
-        fk = fxn(&x_new);
+        fk = eval(&x_new)?;
 
         // check for convergence of function
-        test_f = 0.0;
-        for idx in 0..dim {
-            if fk[idx] > test_f {
-                test_f = fk[idx].abs();
-            }
-        }
+        test_f = residual_size(&fk);
+        diagnostics.record(test_f, del_x.norm(), cond, 1.0);
         if test_f < acc {
-            return Ok(x_new);
+            return Ok((x_new, Some(jac)));
         }
+        richardson |= test_f >= test_last;
+        test_last = test_f;
 
-        jac_inv = fdiff_jacobian(&fxn, &fk, &x_new).pseudo_inverse(INV_TOL)?;
+        jac = jacobian(&fk, &x_new, richardson)?;
+        (jac_inv, cond) = pseudo_inverse_cond(jac.clone(), INV_TOL)?;
     }
-    return Err("Maximum Number of Iterations Reached");
+    Err(NewtonError::Solver("Maximum Number of Iterations Reached"))
 }
 
 // Basic newton-raphson method using finite differencing and a linear search method
 // based off of glabally convergent method on pg 481 of Numerical Recipes
-pub fn newton_raphson_linsrch<F, N: Dim + DimName + DimMin<N> + DimSub<U1>>(
+pub fn newton_raphson_linsrch<F, N: SolverDim>(
+    fxn: F,
+    x_0: OVector<f64, N>,
+    acc: f64,
+) -> Result<OVector<f64, N>, &'static str>
+where
+    DefaultAllocator: SolverAllocator<N>,
+    F: Fn(&OVector<f64, N>) -> OVector<f64, N>,
+{
+    newton_raphson_linsrch_with(fxn, x_0, acc, &Armijo::default())
+}
+
+// Line searching newton-raphson method for a fallible residual
+pub fn try_newton_raphson_linsrch<F, E, N: SolverDim>(
+    fxn: F,
+    x_0: OVector<f64, N>,
+    acc: f64,
+) -> Result<OVector<f64, N>, NewtonError<E>>
+where
+    DefaultAllocator: SolverAllocator<N>,
+    F: Fn(&OVector<f64, N>) -> Result<OVector<f64, N>, E>,
+{
+    try_newton_raphson_linsrch_with(fxn, x_0, acc, &Armijo::default())
+}
+
+// Line searching newton-raphson method with the given line search (`Armijo`,
+// `StrongWolfe` or `MoreThuente` from `utils::linsearch`)
+pub fn newton_raphson_linsrch_with<F, S: LineSearch, N: SolverDim>(
+    fxn: F,
+    x_0: OVector<f64, N>,
+    acc: f64,
+    search: &S,
+) -> Result<OVector<f64, N>, &'static str>
+where
+    DefaultAllocator: SolverAllocator<N>,
+    F: Fn(&OVector<f64, N>) -> OVector<f64, N>,
+{
+    try_newton_raphson_linsrch_with(|x: &OVector<f64, N>| Ok(fxn(x)), x_0, acc, search)
+        .map_err(solver_error)
+}
+
+// Line searching newton-raphson method with the given line search for a fallible
+// residual
+pub fn try_newton_raphson_linsrch_with<F, E, S: LineSearch, N: SolverDim>(
     fxn: F,
-    x_0: VectorN<f64, N>,
+    x_0: OVector<f64, N>,
     acc: f64,
-) -> Result<VectorN<f64, N>, &'static str>
+    search: &S,
+) -> Result<OVector<f64, N>, NewtonError<E>>
 where
-    F: Fn(&VectorN<f64, N>) -> VectorN<f64, N>,
-    DefaultAllocator: Allocator<f64, N>
-        + Allocator<f64, N, N>
-        + Allocator<f64, <N as DimMin<N>>::Output, N>
-        + Allocator<f64, <N as DimMin<N>>::Output>
-        + Allocator<f64, N, <N as DimMin<N>>::Output>
-        + Allocator<f64, <<N as DimMin<N>>::Output as DimSub<U1>>::Output>,
-    <N as DimMin<N>>::Output: DimName,
-    <N as DimMin<N>>::Output: DimSub<U1>,
+    DefaultAllocator: SolverAllocator<N>,
+    F: Fn(&OVector<f64, N>) -> Result<OVector<f64, N>, E>,
+{
+    let mut diagnostics = NewtonDiagnostics::default();
+    linsrch_solve(&fxn, x_0, acc, search, &mut diagnostics)
+        .map_err(|err| err.diagnosed(diagnostics))
+}
+
+// Line searching newton-raphson method, recording its iterations
+fn linsrch_solve<F, E, S: LineSearch, N: SolverDim>(
+    fxn: &F,
+    x_0: OVector<f64, N>,
+    acc: f64,
+    search: &S,
+    diagnostics: &mut NewtonDiagnostics,
+) -> Result<OVector<f64, N>, NewtonError<E>>
+where
+    DefaultAllocator: SolverAllocator<N>,
+    F: Fn(&OVector<f64, N>) -> Result<OVector<f64, N>, E>,
 {
     // Constants
     const MAX_ITER: i32 = 200;
@@ -219,22 +682,20 @@ where
     const TOLX: f64 = EPSILON;
     const STEP_MAX: f64 = 100.0;
 
-    let fmin = |x: &VectorN<f64, N>| {
-        let big_f = fxn(x);
+    let trap = Trap::new(fxn);
+    let fmin = |x: &OVector<f64, N>| {
+        let big_f = trap.eval(x);
         (big_f.clone(), 0.5 * big_f.dot(&big_f))
     };
 
     // pre-initialize variables
     let (mut f_vec, mut f_new) = fmin(&x_0);
+    trap.take()?;
     let dim = x_0.len();
 
     // check if first guess is root
-    let mut test = 0.0;
-    for idx in 0..dim {
-        if f_vec[idx].abs() > test {
-            test = f_vec[idx].abs();
-        }
-    }
+    let test = residual_size(&f_vec);
+    diagnostics.residual_norms.push(test);
     if test < 0.01 * acc {
         return Ok(x_0);
     }
@@ -243,69 +704,402 @@ where
     let stepmax = STEP_MAX * x_0.norm().max(dim as f64);
 
     // initialize other vals
-    let mut jac: MatrixN<f64, N>;
+    let mut jac: OMatrix<f64, N, N>;
     let mut x_new = x_0.clone();
-    let mut x_old: VectorN<f64, N>;
-    let mut p: VectorN<f64, N>;
-    let mut test_x: f64;
+    let mut x_old: OVector<f64, N>;
+    let mut p: OVector<f64, N>;
     let mut test_f: f64;
-    let mut grad: VectorN<f64, N> = VectorN::<f64, N>::repeat(0.0);
+    let mut grad: OVector<f64, N> = OVector::<f64, N>::repeat(0.0);
     let mut f_old: f64;
     let mut g_sum: f64;
 
     // Iterate to victory!
     for _j in 0..MAX_ITER {
         // calculate jacobian
-        jac = fdiff_jacobian(&fxn, &f_vec, &x_new);
+        jac = fdiff_jacobian(&|x: &OVector<f64, N>| trap.eval(x), &f_vec, &x_new);
+        trap.take()?;
 
-        // calculate gradient
-        grad = &jac * &f_vec;
+        // calculate gradient of 0.5 F.F
+        grad = jac.tr_mul(&f_vec);
 
         // solve for p (newton step) using J * p = -F using pseudoinverse
-        p = -(jac.pseudo_inverse(INV_TOL)? * &f_vec);
+        let (jac_inv, cond) = pseudo_inverse_cond(jac, INV_TOL)?;
+        p = -(jac_inv * &f_vec);
 
         // store x and f
         x_old = x_new.clone();
         f_old = f_new.clone();
 
-        // linsearch
-        let (x_out, f_vec_out, f_new_out) =
-            linsrch_w_backtracking(&x_old, f_old, &grad, &mut p, stepmax, &fmin)?;
+        // linsearch (failures of the residual take priority over the search)
+        let found = search.search(&x_old, f_old, &grad, &p, stepmax, fmin);
+        trap.take()?;
+        let (x_out, f_vec_out, f_new_out) = found?;
 
         x_new = x_out;
         f_vec = f_vec_out;
         f_new = f_new_out;
 
         // check for convergence of function
-        test_f = 0.0;
-        for idx in 0..dim {
-            if f_vec[idx].abs() > test_f {
-                test_f = f_vec[idx].abs();
-            }
-        }
+        let step = (&x_new - &x_old).norm();
+        test_f = residual_size(&f_vec);
+        diagnostics.record(test_f, step, cond, step / p.norm());
         if test_f < acc {
             return Ok(x_new);
         }
 
-        // check for convergence of x
-        test_x = 0.0;
-        for idx in 0..dim {
-            let temp = (&x_new[idx] - &x_old[idx]).abs() / x_new[idx].abs().max(1.0);
-            if temp > test_x {
-                test_x = temp;
+        // check for convergence of x, which may be to a local minimum of 0.5 F.F
+        if converged_x(&(&x_new - &x_old), &x_old, &x_new, TOLX) {
+            jac = fdiff_jacobian(&|x: &OVector<f64, N>| trap.eval(x), &f_vec, &x_new);
+            trap.take()?;
+            if local_minimum(&jac.tr_mul(&f_vec), &x_new, f_new) {
+                let x = x_new.iter().cloned().collect();
+                return Err(NewtonError::LocalMinimum(x, Box::default()));
             }
-        }
-        if test_x < TOLX {
             return Ok(x_new);
         }
     }
-    return Err("Maximum Number of Iterations Reached");
+    Err(NewtonError::Solver("Maximum Number of Iterations Reached"))
+}
+
+// Jacobian-free Newton-Krylov method
+// see: Knoll & Keyes "Jacobian-free Newton-Krylov methods: a survey of approaches
+// and applications" (2004)
+//
+// Each newton step J dx = -F is solved with a krylov solver (e.g. `Gmres` or
+// `BiCgStab`) where the jacobian-vector products are approximated by directional
+// finite differences of fxn, so the jacobian is never formed. An optional right
+// preconditioner (approximating J^-1) can be supplied to speed up the inner solves.
+pub fn newton_krylov<F, K: KrylovSolver, N: Dim>(
+    fxn: F,
+    x_0: OVector<f64, N>,
+    acc: f64,
+    solver: &K,
+    precond: Option<Precond<N>>,
+) -> Result<OVector<f64, N>, &'static str>
+where
+    F: Fn(&OVector<f64, N>) -> OVector<f64, N>,
+    DefaultAllocator: Allocator<N>,
+{
+    try_newton_krylov(|x: &OVector<f64, N>| Ok(fxn(x)), x_0, acc, solver, precond)
+        .map_err(solver_error)
+}
+
+// Jacobian-free Newton-Krylov method for a fallible residual
+pub fn try_newton_krylov<F, E, K: KrylovSolver, N: Dim>(
+    fxn: F,
+    x_0: OVector<f64, N>,
+    acc: f64,
+    solver: &K,
+    precond: Option<Precond<N>>,
+) -> Result<OVector<f64, N>, NewtonError<E>>
+where
+    F: Fn(&OVector<f64, N>) -> Result<OVector<f64, N>, E>,
+    DefaultAllocator: Allocator<N>,
+{
+    let mut diagnostics = NewtonDiagnostics::default();
+    krylov_solve(&fxn, x_0, acc, solver, precond, &mut diagnostics)
+        .map_err(|err| err.diagnosed(diagnostics))
+}
+
+// Jacobian-free Newton-Krylov method, recording its iterations
+fn krylov_solve<F, E, K: KrylovSolver, N: Dim>(
+    fxn: &F,
+    x_0: OVector<f64, N>,
+    acc: f64,
+    solver: &K,
+    precond: Option<Precond<N>>,
+    diagnostics: &mut NewtonDiagnostics,
+) -> Result<OVector<f64, N>, NewtonError<E>>
+where
+    F: Fn(&OVector<f64, N>) -> Result<OVector<f64, N>, E>,
+    DefaultAllocator: Allocator<N>,
+{
+    const MAX_ITER: i32 = 200;
+    const MAX_BACKTRACK: i32 = 20;
+    const TOLX: f64 = 1.0_e-12_f64;
+    // Upper bound on the relative tolerance of the inner linear solves
+    const ETA_MAX: f64 = 0.1;
+
+    let trap = Trap::new(fxn);
+    let eval = |x: &OVector<f64, N>| fxn(x).map_err(NewtonError::Residual);
+
+    let mut x = x_0;
+    let mut f_x = eval(&x)?;
+    diagnostics.residual_norms.push(residual_size(&f_x));
+    if residual_size(&f_x) < acc {
+        return Ok(x);
+    }
+
+    for _ in 0..MAX_ITER {
+        // directional derivative approximation of J v
+        let jac_vec = |v: &OVector<f64, N>| {
+            let v_norm = v.norm();
+            if v_norm == 0.0 {
+                return v.clone();
+            }
+            let h = EPSILON.sqrt() * (1.0 + x.norm()) / v_norm;
+            (trap.eval(&(&x + v * h)) - &f_x) / h
+        };
+
+        // inexact newton step. Solve loosely far from the root and tighter near it
+        let eta = ETA_MAX.min(f_x.norm().sqrt());
+        let zeros = &f_x * 0.0;
+        let inner = solver.solve(jac_vec, &(-&f_x), zeros, eta, precond);
+        trap.take()?;
+        let del_x = inner?.x;
+
+        // backtrack along the newton direction until the residual decreases
+        let f_norm = f_x.norm();
+        let mut lambda = 1.0;
+        let mut x_new = &x + &del_x;
+        let mut f_new = eval(&x_new)?;
+        for _ in 0..MAX_BACKTRACK {
+            if f_new.norm() < f_norm {
+                break;
+            }
+            lambda *= 0.5;
+            x_new = &x + &del_x * lambda;
+            f_new = eval(&x_new)?;
+        }
+
+        // check for convergence of x
+        let stagnated = converged_x(&(&x_new - &x), &x, &x_new, TOLX);
+        let step = (&x_new - &x).norm();
+        diagnostics.record(residual_size(&f_new), step, f64::NAN, lambda);
+        x = x_new;
+        f_x = f_new;
+
+        // check for convergence of function
+        if residual_size(&f_x) < acc {
+            return Ok(x);
+        }
+        if stagnated {
+            return Err(NewtonError::Solver(
+                "[NEWTON KRYLOV] Stagnated before reaching tolerance",
+            ));
+        }
+    }
+    Err(NewtonError::Solver(
+        "[NEWTON KRYLOV] Maximum Number of Iterations Reached",
+    ))
+}
+
+// Newton raphson method for systems with a banded jacobian
+//
+// The jacobian is found with lower + upper + 1 function evaluations and factored
+// in banded form, so each iteration costs O(n b^2) rather than O(n^3). Suited to
+// method-of-lines discretizations where each state only couples to its neighbors.
+pub fn newton_raphson_banded<F, N: Dim>(
+    fxn: F,
+    x_0: OVector<f64, N>,
+    acc: f64,
+    lower: usize,
+    upper: usize,
+) -> Result<OVector<f64, N>, &'static str>
+where
+    F: Fn(&OVector<f64, N>) -> OVector<f64, N>,
+    DefaultAllocator: Allocator<N>,
+{
+    try_newton_raphson_banded(|x: &OVector<f64, N>| Ok(fxn(x)), x_0, acc, lower, upper)
+        .map_err(solver_error)
+}
+
+// Banded newton raphson method for a fallible residual
+pub fn try_newton_raphson_banded<F, E, N: Dim>(
+    fxn: F,
+    x_0: OVector<f64, N>,
+    acc: f64,
+    lower: usize,
+    upper: usize,
+) -> Result<OVector<f64, N>, NewtonError<E>>
+where
+    F: Fn(&OVector<f64, N>) -> Result<OVector<f64, N>, E>,
+    DefaultAllocator: Allocator<N>,
+{
+    let mut diagnostics = NewtonDiagnostics::default();
+    banded_solve(&fxn, x_0, acc, lower, upper, &mut diagnostics)
+        .map_err(|err| err.diagnosed(diagnostics))
+}
+
+// Banded newton raphson method, recording its iterations
+fn banded_solve<F, E, N: Dim>(
+    fxn: &F,
+    x_0: OVector<f64, N>,
+    acc: f64,
+    lower: usize,
+    upper: usize,
+    diagnostics: &mut NewtonDiagnostics,
+) -> Result<OVector<f64, N>, NewtonError<E>>
+where
+    F: Fn(&OVector<f64, N>) -> Result<OVector<f64, N>, E>,
+    DefaultAllocator: Allocator<N>,
+{
+    const MAX_ITER: i32 = 200;
+    const TOLX: f64 = 1.0_e-12_f64;
+
+    let trap = Trap::new(fxn);
+    let eval = |x: &OVector<f64, N>| fxn(x).map_err(NewtonError::Residual);
+
+    let mut x = x_0;
+    let mut f_x = eval(&x)?;
+    diagnostics.residual_norms.push(residual_size(&f_x));
+    if residual_size(&f_x) < acc {
+        return Ok(x);
+    }
+
+    for _ in 0..MAX_ITER {
+        let jac =
+            fdiff_jacobian_banded(&|x: &OVector<f64, N>| trap.eval(x), &f_x, &x, lower, upper);
+        trap.take()?;
+        let del_x = -jac.lu()?.solve(&f_x);
+        let x_last = x.clone();
+        x += &del_x;
+        f_x = eval(&x)?;
+        diagnostics.record(residual_size(&f_x), del_x.norm(), f64::NAN, 1.0);
+
+        // check for convergence of function
+        if residual_size(&f_x) < acc {
+            return Ok(x);
+        }
+
+        // check for convergence of x
+        if converged_x(&del_x, &x_last, &x, TOLX) {
+            return Err(NewtonError::Solver(
+                "[NEWTON BANDED] Stagnated before reaching tolerance",
+            ));
+        }
+    }
+    Err(NewtonError::Solver(
+        "[NEWTON BANDED] Maximum Number of Iterations Reached",
+    ))
+}
+
+// Distinct roots found from a batch of initial guesses
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchRoots<N: Dim + DimName>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    // Distinct roots, in the order of the first guess converging to each
+    pub roots: Vec<OVector<f64, N>>,
+    // Index into `roots` of the root each guess converged to, or the error of the
+    // solve from that guess
+    pub origins: Vec<Result<usize, &'static str>>,
+}
+
+// Solves the same residual from many initial guesses in parallel (on up to
+// `threads` worker threads, all available cores when None) with the line searching
+// newton-raphson method, e.g. to find all equilibria of a system from a grid of
+// guesses. Solves which stop at a point that is not a root (a local minimum of the
+// residual norm) count as failed. Roots within `dedup_tol` of each other (in the
+// euclidean norm) are merged into the first one found
+pub fn newton_raphson_batch<F, N: SolverDim>(
+    fxn: F,
+    guesses: &[OVector<f64, N>],
+    acc: f64,
+    dedup_tol: f64,
+    threads: Option<usize>,
+) -> BatchRoots<N>
+where
+    DefaultAllocator: SolverAllocator<N>,
+    F: Fn(&OVector<f64, N>) -> OVector<f64, N> + Sync,
+{
+    let solves = sweep(guesses, threads, |x_0| {
+        let x = newton_raphson_linsrch(&fxn, x_0.clone(), acc)?;
+        if residual_size(&fxn(&x)) < acc {
+            Ok(x)
+        } else {
+            Err("[NEWTON BATCH] Converged to a point which is not a root")
+        }
+    });
+
+    let mut roots: Vec<OVector<f64, N>> = Vec::new();
+    let origins = solves
+        .into_iter()
+        .map(|solve| {
+            let x = solve?;
+            match roots
+                .iter()
+                .position(|root| (root - &x).norm() <= dedup_tol)
+            {
+                Some(idx) => Ok(idx),
+                None => {
+                    roots.push(x);
+                    Ok(roots.len() - 1)
+                }
+            }
+        })
+        .collect();
+    BatchRoots { roots, origins }
+}
+
+// Deflated newton-raphson method
+// see: Farrell, Birkisson & Funke "Deflation techniques for finding distinct
+// solutions of nonlinear partial differential equations" (2015)
+//
+// Solves G(x) = M(x) F(x) = 0 with the finite differencing newton-raphson method
+// (full steps, as a line search on |G| stalls in the minima deflation leaves
+// between the known roots), where the deflation operator
+// M(x) = prod_r (1 / ||x - r||^power + shift) over the known roots r grows without
+// bound at each of them. The known roots are no longer roots of G, so the solve is
+// pushed away from them towards a new root of F (if it converges). With
+// shift >= 1, |F| <= |G| and a root of G to `acc` is a root of F to `acc`
+pub fn newton_raphson_deflated<F, N: SolverDim>(
+    fxn: F,
+    x_0: OVector<f64, N>,
+    acc: f64,
+    known: &[OVector<f64, N>],
+    power: f64,
+    shift: f64,
+) -> Result<OVector<f64, N>, &'static str>
+where
+    DefaultAllocator: SolverAllocator<N>,
+    F: Fn(&OVector<f64, N>) -> OVector<f64, N>,
+{
+    let deflated = |x: &OVector<f64, N>| {
+        let scale = known.iter().fold(1.0, |m, root| {
+            m * (1.0 / (x - root).norm().powf(power) + shift)
+        });
+        fxn(x) * scale
+    };
+    let x = newton_raphson_fdiff(deflated, x_0, acc)?;
+    if residual_size(&fxn(&x)) < acc {
+        Ok(x)
+    } else {
+        Err("[NEWTON DEFLATED] Converged to a point which is not a root")
+    }
+}
+
+// Finds up to `max_roots` distinct roots from a single initial guess by deflating
+// each root found from the following solves (with power 2 and shift 1). Stops at
+// the first solve which fails to find a new root
+pub fn deflated_roots<F, N: SolverDim>(
+    fxn: F,
+    x_0: OVector<f64, N>,
+    acc: f64,
+    max_roots: usize,
+) -> Vec<OVector<f64, N>>
+where
+    DefaultAllocator: SolverAllocator<N>,
+    F: Fn(&OVector<f64, N>) -> OVector<f64, N>,
+{
+    let mut roots: Vec<OVector<f64, N>> = Vec::new();
+    while roots.len() < max_roots {
+        match newton_raphson_deflated(&fxn, x_0.clone(), acc, &roots, 2.0, 1.0) {
+            Ok(root) => roots.push(root),
+            Err(_) => break,
+        }
+    }
+    roots
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use na::{Matrix2, Vector1, Vector2};
+    use crate::utils::linalg::{BiCgStab, Gmres};
+    use crate::utils::linsearch::{MoreThuente, StrongWolfe};
+    use na::{DVector, Matrix2, Vector1, Vector2};
+    use std::f64::consts::PI;
 
     #[test]
     fn test_newton_1d() {
@@ -377,6 +1171,135 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_broyden_50d() {
+        // weakly coupled nonlinear chain x_i + 0.1 (x_(i-1) + x_(i+1))^2 / 4 + 0.2 sin(x_i) = 1
+        type Vector50 = na::SVector<f64, 50>;
+        let fxn = |x: &Vector50| {
+            Vector50::from_fn(|i, _| {
+                let left = if i > 0 { x[i - 1] } else { 0.0 };
+                let right = if i < 49 { x[i + 1] } else { 0.0 };
+                x[i] + 0.025 * (left + right).powi(2) + 0.2 * x[i].sin() - 1.0
+            })
+        };
+        let sol = newton_raphson_broyden(fxn, Vector50::zeros(), 1e-12).unwrap();
+        assert!(fxn(&sol).amax() < 1e-12);
+    }
+
+    #[test]
+    fn test_newton_krylov_2d() {
+        let i_guess = Vector2::new(0.0, 0.0);
+        let fxn = |x: &Vector2<f64>| {
+            Vector2::new(
+                x[0] + 0.5 * (x[0] - x[1]).powf(3.0) - 1.0,
+                0.5 * (x[1] - x[0]).powf(3.0) + x[1],
+            )
+        };
+
+        // value found using scipy.optimize.root
+        let ans = newton_krylov(fxn, i_guess, 1.0e-10_f64, &Gmres::default(), None)
+            .expect("Couldn't converge to solution");
+
+        let python_sol = Vector2::new(0.8411639, 0.1588361);
+        const TOL: f64 = 1.0e-7_f64;
+        for idx in 0..2 {
+            assert!((ans[idx] - python_sol[idx]).abs() < TOL);
+        }
+    }
+
+    #[test]
+    fn test_newton_krylov_bratu() {
+        // 1D Bratu problem u'' + lambda e^u = 0, u(0) = u(1) = 0 discretized with
+        // central differences on a grid far too large for a dense jacobian solve
+        const N: usize = 400;
+        const LAMBDA: f64 = 1.0;
+        let dx = 1.0 / (N + 1) as f64;
+        let fxn = |u: &DVector<f64>| {
+            DVector::<f64>::from_fn(N, |i, _| {
+                let left = if i == 0 { 0.0 } else { u[i - 1] };
+                let right = if i == N - 1 { 0.0 } else { u[i + 1] };
+                (left - 2.0 * u[i] + right) / dx.powi(2) + LAMBDA * u[i].exp()
+            })
+        };
+
+        // inverse of the discrete laplacian (thomas algorithm) as a preconditioner
+        let precond = |v: &DVector<f64>| {
+            let (diag, off) = (-2.0 / dx.powi(2), 1.0 / dx.powi(2));
+            let mut c_prime = vec![0.0; N];
+            let mut sol = v.clone();
+            c_prime[0] = off / diag;
+            sol[0] /= diag;
+            for i in 1..N {
+                let denom = diag - off * c_prime[i - 1];
+                c_prime[i] = off / denom;
+                sol[i] = (sol[i] - off * sol[i - 1]) / denom;
+            }
+            for i in (0..N - 1).rev() {
+                sol[i] -= c_prime[i] * sol[i + 1];
+            }
+            sol
+        };
+        let gmres_ans = newton_krylov(
+            &fxn,
+            DVector::zeros(N),
+            1.0e-8_f64,
+            &Gmres::default(),
+            Some(&precond),
+        )
+        .expect("Couldn't converge to solution");
+        let bicgstab_ans = newton_krylov(
+            &fxn,
+            DVector::zeros(N),
+            1.0e-8_f64,
+            &BiCgStab::default(),
+            Some(&precond),
+        )
+        .expect("Couldn't converge to solution");
+
+        // max of the lower solution branch for lambda = 1
+        const U_MAX: f64 = 0.140_4;
+        assert!((gmres_ans.max() - U_MAX).abs() < 1e-3);
+        assert!((bicgstab_ans.max() - U_MAX).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_newton_banded_heat_step() {
+        // one backward euler step of the method-of-lines heat equation with a
+        // nonlinear source: u_t = u_xx + u^2 on a grid with a tri-diagonal jacobian
+        const N: usize = 500;
+        let dx = 1.0 / (N + 1) as f64;
+        let dt = 1.0e-2;
+        let u_0 = DVector::<f64>::from_fn(N, |i, _| ((i + 1) as f64 * dx * PI).sin());
+        let rhs = |u: &DVector<f64>| {
+            DVector::<f64>::from_fn(N, |i, _| {
+                let left = if i == 0 { 0.0 } else { u[i - 1] };
+                let right = if i == N - 1 { 0.0 } else { u[i + 1] };
+                (left - 2.0 * u[i] + right) / dx.powi(2) + u[i].powi(2)
+            })
+        };
+        let root_problem = |u: &DVector<f64>| u - &u_0 - dt * rhs(u);
+
+        let ans = newton_raphson_banded(&root_problem, u_0.clone(), 1.0e-10_f64, 1, 1)
+            .expect("Couldn't converge to solution");
+        assert!(root_problem(&ans).amax() < 1.0e-10);
+    }
+
+    #[test]
+    fn test_newton_fdiff_jac() {
+        // unit circle meeting the line x = y at (1, 1) / sqrt(2), with jacobian
+        // [[2 x, 2 y], [1, -1]]
+        let fxn = |x: &Vector2<f64>| Vector2::new(x[0].powi(2) + x[1].powi(2) - 1.0, x[0] - x[1]);
+        let root = 0.5_f64.sqrt();
+        let truth = Matrix2::new(2.0 * root, 2.0 * root, 1.0, -1.0);
+        let sol = newton_raphson_fdiff_jac(fxn, Vector2::new(1.0, 0.5), 1e-12).unwrap();
+        assert!((sol.x - Vector2::new(root, root)).amax() < 1e-10);
+        assert!((sol.jacobian - truth).amax() < 1e-5);
+
+        // from the root itself the jacobian is taken there
+        let sol = newton_raphson_fdiff_jac(fxn, Vector2::new(root, root), 1e-12).unwrap();
+        assert!((sol.jacobian - truth).amax() < 1e-5);
+    }
+
     #[test]
     fn test_newton_linsrch_1d() {
         let i_guess = Vector1::new(1.0);
@@ -411,4 +1334,206 @@ mod tests {
             assert!((ans[idx] - python_sol[idx]).abs() < TOL);
         }
     }
+
+    #[test]
+    fn test_newton_linsrch_local_minimum() {
+        // x^2 + 1 has no real root, and 0.5 F.F its minimum at x = 0
+        let fxn = |x: &Vector1<f64>| Vector1::new(x[0].powi(2) + 1.0);
+        let err = try_newton_raphson_linsrch(
+            |x: &Vector1<f64>| Ok::<_, ()>(fxn(x)),
+            Vector1::new(0.5),
+            1e-8,
+        )
+        .unwrap_err();
+        match err {
+            NewtonError::LocalMinimum(x, diagnostics) => {
+                assert!(x[0].abs() < 1e-6);
+                assert!(diagnostics.iterations() > 0);
+            }
+            err => panic!("{:?} is no local minimum", err),
+        }
+        let err = newton_raphson_linsrch(fxn, Vector1::new(0.5), 1e-8).unwrap_err();
+        assert_eq!(err, LOCAL_MINIMUM);
+    }
+
+    #[test]
+    fn test_newton_linsrch_with() {
+        // rosenbrock residual from the classic starting point, with each line search
+        let fxn = |x: &Vector2<f64>| Vector2::new(10.0 * (x[1] - x[0].powi(2)), 1.0 - x[0]);
+        let x_0 = Vector2::new(-1.2, 1.0);
+        let sols = [
+            newton_raphson_linsrch_with(fxn, x_0, 1e-10, &Armijo::default()),
+            newton_raphson_linsrch_with(fxn, x_0, 1e-10, &StrongWolfe::default()),
+            newton_raphson_linsrch_with(fxn, x_0, 1e-10, &MoreThuente::default()),
+        ];
+        for sol in sols.iter() {
+            let x = sol.unwrap();
+            assert!((x - Vector2::new(1.0, 1.0)).amax() < 1e-8);
+        }
+    }
+
+    #[test]
+    fn test_try_newton_fallible_residual() {
+        // residual through a table which only covers [0, 2]: the root 1.4063 is
+        // found from inside the table, and a guess outside fails with the lookup
+        let lookup = |x: f64| {
+            if (0.0..=2.0).contains(&x) {
+                Ok(x.powi(3) + 3.0 * x - 7.0)
+            } else {
+                Err("[TABLE] Lookup out of range")
+            }
+        };
+        let fxn = |x: &Vector1<f64>| lookup(x[0]).map(Vector1::new);
+        let sol = 1.406_287_579_960_535;
+        let ans = try_newton_raphson_fdiff(fxn, Vector1::new(1.0), 1.0e-8_f64).unwrap();
+        assert!((ans[0] - sol).abs() < 1e-7);
+        let ans = try_newton_raphson_linsrch(fxn, Vector1::new(1.0), 1.0e-8_f64).unwrap();
+        assert!((ans[0] - sol).abs() < 1e-7);
+
+        let err = try_newton_raphson_broyden(fxn, Vector1::new(3.0), 1.0e-8_f64).unwrap_err();
+        assert_eq!(err, NewtonError::Residual("[TABLE] Lookup out of range"));
+        let err = try_newton_krylov(fxn, Vector1::new(3.0), 1.0e-8_f64, &Gmres::default(), None)
+            .unwrap_err();
+        assert_eq!(err, NewtonError::Residual("[TABLE] Lookup out of range"));
+
+        // the lookup fails in the finite difference jacobian at the table edge
+        let err = try_newton_raphson_banded(fxn, Vector1::new(2.0), 1.0e-8_f64, 0, 0);
+        let err: &'static str = err.unwrap_err().into();
+        assert_eq!(err, "[TABLE] Lookup out of range");
+    }
+
+    #[test]
+    fn test_newton_nan_residual_is_no_root() {
+        // ln leaves its domain at the guess, so every residual is NaN
+        let fxn = |x: &Vector1<f64>| Vector1::new(x[0].ln() - 1.0);
+        let x_0 = Vector1::new(-1.0);
+        assert!(newton_raphson_fdiff(fxn, x_0, 1.0e-8_f64).is_err());
+        assert!(newton_raphson_linsrch(fxn, x_0, 1.0e-8_f64).is_err());
+        assert!(newton_raphson_broyden(fxn, x_0, 1.0e-8_f64).is_err());
+    }
+
+    #[test]
+    fn test_newton_negative_residual_is_no_root() {
+        // the residual is negative everywhere short of the root, which a signed
+        // comparison takes for converged
+        let fxn = |x: &Vector2<f64>| Vector2::new(x[0] - 3.0, 2.0 * x[1] - 1.0);
+        let x_0 = Vector2::new(0.0, 0.0);
+        let ans = newton_raphson_fdiff(fxn, x_0, 1.0e-8_f64).unwrap();
+        assert!((ans - Vector2::new(3.0, 0.5)).amax() < 1.0e-8);
+    }
+
+    #[test]
+    fn test_newton_fdiff_stalled_is_no_root() {
+        // a constant residual has no root, yet the steps it gives vanish at once
+        let constant = |_x: &Vector2<f64>| Vector2::new(1.0, 1.0);
+        let x_0 = Vector2::new(0.3, 0.2);
+        assert!(newton_raphson_fdiff(constant, x_0, 1.0e-8_f64).is_err());
+        assert!(newton_raphson_fdiff_jac(constant, x_0, 1.0e-8_f64).is_err());
+
+        // nor has an inconsistent linear system, x + y = 1 and x + y = 2
+        let inconsistent = |x: &Vector2<f64>| Vector2::new(x[0] + x[1] - 1.0, x[0] + x[1] - 2.0);
+        assert!(newton_raphson_fdiff(inconsistent, x_0, 1.0e-8_f64).is_err());
+        assert!(newton_raphson_fdiff_jac(inconsistent, x_0, 1.0e-8_f64).is_err());
+    }
+
+    #[test]
+    fn test_newton_diagnostics() {
+        // x^2 + 1 has no real root, so the residual never drops below 1
+        let fxn = |x: &Vector1<f64>| Vector1::new(x[0].powi(2) + 1.0);
+        let err = try_newton_raphson_fdiff(
+            |x: &Vector1<f64>| Ok::<_, ()>(fxn(x)),
+            Vector1::new(0.5),
+            1e-8,
+        )
+        .unwrap_err();
+        let diag = err.diagnostics().unwrap();
+        assert!(diag.iterations() > 0);
+        assert_eq!(diag.residual_norms.len(), diag.iterations() + 1);
+        assert!(diag.residual_norms.iter().all(|&f| f >= 1.0));
+        assert!(diag.alphas.iter().all(|&alpha| alpha == 1.0));
+
+        // the jacobian of an inconsistent linear system is singular (until the
+        // rank one updates perturb it)
+        let fxn =
+            |x: &Vector2<f64>| Ok::<_, ()>(Vector2::new(x[0] + x[1] - 1.0, x[0] + x[1] + 1.0));
+        let err = try_newton_raphson_broyden(fxn, Vector2::new(0.0, 0.0), 1e-8).unwrap_err();
+        let diag = err.diagnostics().unwrap();
+        assert!(diag.conditions[0].is_infinite());
+        assert!(matches!(err, NewtonError::Unconverged(..)));
+    }
+
+    #[test]
+    fn test_broyden_warm_start() {
+        // a sequence of slightly different problems, as in an optimization loop,
+        // solved cold and warm started from the previous jacobian
+        use std::cell::Cell;
+        let evals = Cell::new(0);
+        let residual = |p: f64| {
+            let evals = &evals;
+            move |x: &Vector2<f64>| {
+                evals.set(evals.get() + 1);
+                Vector2::new(
+                    x[0] + 0.5 * (x[0] - x[1]).powf(3.0) - p,
+                    0.5 * (x[1] - x[0]).powf(3.0) + x[1],
+                )
+            }
+        };
+        let params: Vec<f64> = (0..20).map(|k| 1.0 + 0.01 * k as f64).collect();
+
+        let mut cold = Vec::new();
+        let mut x = Vector2::new(0.0, 0.0);
+        for p in params.iter() {
+            x = newton_raphson_broyden(residual(*p), x, 1.0e-10_f64).unwrap();
+            cold.push(x);
+        }
+        let cold_evals = evals.replace(0);
+
+        let mut warm =
+            newton_raphson_broyden_warm(residual(1.0), Vector2::zeros(), 1e-10, None).unwrap();
+        for (p, x) in params.iter().zip(cold.iter()).skip(1) {
+            warm = newton_raphson_broyden_warm(residual(*p), warm.x, 1e-10, Some(warm.jacobian))
+                .unwrap();
+            assert!((warm.x - x).norm() < 1e-8);
+        }
+        assert!(evals.get() < cold_evals);
+    }
+
+    #[test]
+    fn test_newton_batch() {
+        // equilibria of the damped duffing oscillator x'' = -0.1 x' + x - x^3 at
+        // x = -1, 0, 1 (with x' = 0), found from a grid of guesses
+        let fxn = |y: &Vector2<f64>| Vector2::new(y[1], -0.1 * y[1] + y[0] - y[0].powi(3));
+        let guesses: Vec<Vector2<f64>> = (0..9)
+            .map(|k| Vector2::new(-2.0 + 0.5 * k as f64, 0.3))
+            .collect();
+        let ans = newton_raphson_batch(fxn, &guesses, 1e-10, 1e-6, Some(3));
+        assert_eq!(ans.origins.len(), guesses.len());
+        assert!(ans.origins.iter().all(|origin| origin.is_ok()));
+        let mut found: Vec<f64> = ans.roots.iter().map(|root| root[0]).collect();
+        found.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(found.len(), 3);
+        for (x, truth) in found.iter().zip([-1.0, 0.0, 1.0].iter()) {
+            assert!((x - truth).abs() < 1e-8);
+        }
+        // same answer serially
+        assert_eq!(
+            newton_raphson_batch(fxn, &guesses, 1e-10, 1e-6, Some(1)),
+            ans
+        );
+    }
+
+    #[test]
+    fn test_newton_deflated() {
+        // the three equilibria of the duffing oscillator from a single guess
+        let fxn = |y: &Vector2<f64>| Vector2::new(y[1], -0.1 * y[1] + y[0] - y[0].powi(3));
+        let roots = deflated_roots(fxn, Vector2::new(0.8, 0.3), 1e-10, 5);
+        let mut found: Vec<f64> = roots.iter().map(|root| root[0]).collect();
+        found.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(found.len(), 3);
+        for (x, truth) in found.iter().zip([-1.0, 0.0, 1.0].iter()) {
+            assert!((x - truth).abs() < 1e-8);
+        }
+        // the guess converges to x = 1 without deflation
+        assert!((roots[0][0] - 1.0).abs() < 1e-8);
+    }
 }
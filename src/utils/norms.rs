@@ -0,0 +1,72 @@
+/// Error Norms (norms)
+///
+/// Weighted root-mean-square norm following the convention of Hairer, Norsett and
+/// Wanner ("Solving Ordinary Differential Equations I", section II.4)
+///
+/// err = sqrt(1/N sum (e_i / sc_i)^2),  sc_i = atol_i + rtol * max(|y0_i|, |y1_i|)
+///
+/// A value of 1 means the error is exactly at the requested tolerance. This is the
+/// norm used by the step size controllers and the newton convergence checks, so
+/// atol / rtol behave as they do in other mainstream solvers (scipy, the Hairer
+/// codes) and do not get stricter as the dimension of the system grows.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DefaultAllocator, Dim, OVector};
+
+// === End Imports ===
+
+// Root-mean-square of err scaled component-wise by scale
+pub fn rms_norm<N: Dim>(err: &OVector<f64, N>, scale: &OVector<f64, N>) -> f64
+where
+    DefaultAllocator: Allocator<N>,
+{
+    let sum_sq: f64 = err
+        .iter()
+        .zip(scale.iter())
+        .map(|(e, sc)| (e / sc).powi(2))
+        .sum();
+    (sum_sq / err.len() as f64).sqrt()
+}
+
+// Weighted rms norm of err for a step from y_0 to y_1
+pub fn weighted_rms_norm<N: Dim>(
+    err: &OVector<f64, N>,
+    y_0: &OVector<f64, N>,
+    y_1: &OVector<f64, N>,
+    atol: &OVector<f64, N>,
+    rtol: f64,
+) -> f64
+where
+    DefaultAllocator: Allocator<N>,
+{
+    let scale = atol.zip_zip_map(y_0, y_1, |a, y_a, y_b| a + rtol * y_a.abs().max(y_b.abs()));
+    rms_norm(err, &scale)
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use na::{Vector2, Vector4};
+
+    #[test]
+    fn test_weighted_rms_norm() {
+        let err = Vector2::new(3.0e-6, 4.0e-6);
+        let y = Vector2::new(0.0, 2.0);
+        let y_new = Vector2::new(1.0, -1.0);
+        let atol = Vector2::repeat(1e-6);
+        // scales are 1e-6 + 1e-6 * 1 and 1e-6 + 1e-6 * 2
+        let expected = ((1.5_f64.powi(2) + (4.0_f64 / 3.0).powi(2)) / 2.0).sqrt();
+        assert!((weighted_rms_norm(&err, &y, &y_new, &atol, 1e-6) - expected).abs() < 1e-14);
+
+        // the same relative error gives the same norm in any dimension
+        let err_4 = Vector4::repeat(1e-3);
+        let ones_4 = Vector4::repeat(1.0);
+        let err_2 = Vector2::repeat(1e-3);
+        let ones_2 = Vector2::repeat(1.0);
+        assert!((rms_norm(&err_4, &ones_4) - rms_norm(&err_2, &ones_2)).abs() < 1e-18);
+    }
+}
@@ -0,0 +1,261 @@
+/// Trajectory Plots (plot)
+///
+/// Only available with the `plotters` feature.
+///
+/// Quick looks at a solution without exporting it first: time series of selected
+/// components against time, and phase plane plots of one component against
+/// another. The image format follows the extension of the output path, `.png` for
+/// a bitmap and `.svg` for a vector image.
+///
+/// The plots are meant for examples and debugging rather than publication. Text in
+/// PNG images is rendered with a sans-serif font of the system.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DefaultAllocator, Dim, DimName};
+use plotters::coord::Shift;
+use plotters::prelude::*;
+
+// local imports
+use crate::runge_kutta::common::IntegResult;
+
+// Standard library imports
+use std::ops::Range;
+use std::path::Path;
+
+// === End Imports ===
+
+// Colors of the plotted series, cycled through when there are more series
+const PALETTE: [RGBColor; 6] = [
+    RGBColor(31, 119, 180),
+    RGBColor(255, 127, 14),
+    RGBColor(44, 160, 44),
+    RGBColor(214, 39, 40),
+    RGBColor(148, 103, 189),
+    RGBColor(140, 86, 75),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlotOptions {
+    // Width and height of the image in pixels
+    pub size: (u32, u32),
+    // Title drawn above the plot
+    pub title: String,
+    // Names of the state components, "y<i>" for components without one
+    pub labels: Vec<String>,
+}
+
+impl PlotOptions {
+    pub fn default() -> Self {
+        PlotOptions {
+            size: (800, 600),
+            title: String::new(),
+            labels: Vec::new(),
+        }
+    }
+
+    fn label(&self, i: usize) -> String {
+        match self.labels.get(i) {
+            Some(label) => label.clone(),
+            None => format!("y{}", i),
+        }
+    }
+}
+
+// Plots the selected components of a solution against time
+pub fn plot_time_series<N: Dim + DimName, P: AsRef<Path>>(
+    results: &IntegResult<N>,
+    components: &[usize],
+    path: P,
+    options: &PlotOptions,
+) -> Result<(), &'static str>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    check_components(components, N::dim())?;
+    let series: Vec<(String, Vec<(f64, f64)>)> = components
+        .iter()
+        .map(|&i| {
+            let points = results
+                .times
+                .iter()
+                .zip(results.states.iter())
+                .map(|(t, y)| (*t, y[i]))
+                .collect();
+            (options.label(i), points)
+        })
+        .collect();
+    let y_label = match components {
+        [i] => options.label(*i),
+        _ => String::new(),
+    };
+    draw(path, options, ("t", &y_label), &series)
+}
+
+// Plots component j of a solution against component i
+pub fn plot_phase_plane<N: Dim + DimName, P: AsRef<Path>>(
+    results: &IntegResult<N>,
+    (i, j): (usize, usize),
+    path: P,
+    options: &PlotOptions,
+) -> Result<(), &'static str>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    check_components(&[i, j], N::dim())?;
+    let points = results.states.iter().map(|y| (y[i], y[j])).collect();
+    let series = [(String::new(), points)];
+    draw(
+        path,
+        options,
+        (&options.label(i), &options.label(j)),
+        &series,
+    )
+}
+
+fn check_components(components: &[usize], dim: usize) -> Result<(), &'static str> {
+    if components.is_empty() {
+        return Err("[PLOT] No components to plot");
+    }
+    if components.iter().any(|&i| i >= dim) {
+        return Err("[PLOT] Component is out of the state");
+    }
+    Ok(())
+}
+
+// Draws the series on the backend matching the extension of the path
+fn draw<P: AsRef<Path>>(
+    path: P,
+    options: &PlotOptions,
+    axes: (&str, &str),
+    series: &[(String, Vec<(f64, f64)>)],
+) -> Result<(), &'static str> {
+    let path = path.as_ref();
+    let extension = path.extension().and_then(|ext| ext.to_str());
+    match extension.map(|ext| ext.to_ascii_lowercase()).as_deref() {
+        Some("png") => {
+            let root = BitMapBackend::new(path, options.size).into_drawing_area();
+            chart(&root, options, axes, series)?;
+            root.present()
+                .map_err(|_| "[PLOT] Failed to write the image")
+        }
+        Some("svg") => {
+            let root = SVGBackend::new(path, options.size).into_drawing_area();
+            chart(&root, options, axes, series)?;
+            root.present()
+                .map_err(|_| "[PLOT] Failed to write the image")
+        }
+        _ => Err("[PLOT] Output must be a .png or .svg file"),
+    }
+}
+
+// Draws the axes and the series as lines, with a legend when the series are named
+fn chart<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    options: &PlotOptions,
+    (x_label, y_label): (&str, &str),
+    series: &[(String, Vec<(f64, f64)>)],
+) -> Result<(), &'static str> {
+    const FAILED: &str = "[PLOT] Failed to draw the plot";
+    let points = || series.iter().flat_map(|(_, pts)| pts.iter());
+    let x_range = span(points().map(|p| p.0));
+    let y_range = span(points().map(|p| p.1));
+
+    root.fill(&WHITE).map_err(|_| FAILED)?;
+    let mut chart = ChartBuilder::on(root)
+        .caption(&options.title, ("sans-serif", 24))
+        .margin(10)
+        .x_label_area_size(40)
+        .y_label_area_size(60)
+        .build_cartesian_2d(x_range, y_range)
+        .map_err(|_| FAILED)?;
+    chart
+        .configure_mesh()
+        .x_desc(x_label)
+        .y_desc(y_label)
+        .draw()
+        .map_err(|_| FAILED)?;
+
+    for (k, (name, pts)) in series.iter().enumerate() {
+        let color = PALETTE[k % PALETTE.len()];
+        let drawn = chart
+            .draw_series(LineSeries::new(pts.iter().copied(), &color))
+            .map_err(|_| FAILED)?;
+        if !name.is_empty() {
+            drawn
+                .label(name.as_str())
+                .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+        }
+    }
+    if series.iter().any(|(name, _)| !name.is_empty()) {
+        chart
+            .configure_series_labels()
+            .background_style(WHITE.mix(0.8))
+            .border_style(BLACK)
+            .draw()
+            .map_err(|_| FAILED)?;
+    }
+    Ok(())
+}
+
+// Range covering the values, padded by 5% (by 1 when they are all equal) so the
+// lines do not run along the frame. Non-finite values are left out
+fn span<I: Iterator<Item = f64>>(values: I) -> Range<f64> {
+    let (lo, hi) = values
+        .filter(|v| v.is_finite())
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| {
+            (lo.min(v), hi.max(v))
+        });
+    if lo > hi {
+        return -1.0..1.0;
+    }
+    let pad = if hi > lo { 0.05 * (hi - lo) } else { 1.0 };
+    (lo - pad)..(hi + pad)
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runge_kutta::common::IntegOptions;
+    use crate::runge_kutta::fixed::FixedStep;
+    use crate::runge_kutta::rk_simp::RK4;
+    use nalgebra::Vector2;
+    use std::fs;
+
+    #[test]
+    fn test_plots() {
+        let spring = |_t: f64, y: &Vector2<f64>| Vector2::new(y[1], -y[0]);
+        let ans = RK4
+            .integrate(
+                spring,
+                0.0,
+                Vector2::new(1.0, 0.0),
+                10.0,
+                0.1,
+                IntegOptions::default(),
+            )
+            .unwrap();
+        let mut options = PlotOptions::default();
+        options.title = String::from("Spring");
+        options.labels = vec![String::from("x")];
+
+        let dir = std::env::temp_dir();
+        let svg = dir.join("integration_station_test_plots.svg");
+        plot_time_series(&ans, &[0, 1], &svg, &options).unwrap();
+        let image = fs::read_to_string(&svg).unwrap();
+        assert!(image.contains("<svg") && image.contains("Spring"));
+        assert!(image.contains("\nx\n") && image.contains("\ny1\n"));
+        fs::remove_file(&svg).unwrap();
+
+        let png = dir.join("integration_station_test_plots.png");
+        plot_phase_plane(&ans, (0, 1), &png, &options).unwrap();
+        assert!(fs::read(&png).unwrap().starts_with(b"\x89PNG"));
+        fs::remove_file(&png).unwrap();
+
+        assert!(plot_time_series(&ans, &[2], &svg, &options).is_err());
+        assert!(plot_phase_plane(&ans, (0, 1), dir.join("plot.pdf"), &options).is_err());
+    }
+}
@@ -0,0 +1,186 @@
+/// Polynomial Roots (poly)
+///
+/// Roots of real polynomials p(x) = c_0 + c_1 x + ... + c_n x^n, given by their
+/// coefficients in increasing order of degree. The roots are the eigenvalues of the
+/// companion matrix of p (as numpy.roots and MATLAB roots compute them), which is
+/// balanced first (Parlett & Reinsch, 1969) since coefficients of very different
+/// magnitudes otherwise cost most of the accuracy. Each root is then polished with
+/// a few newton iterations on p itself.
+///
+/// Vanishing leading coefficients lower the degree and vanishing low order
+/// coefficients give roots at exactly 0. Used for the stability polynomials of the
+/// integrators and for building custom quadrature nodes.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::{Complex, DMatrix};
+
+// local imports
+use super::linalg::eigenvalues;
+
+// === End Imports ===
+
+// Value of the polynomial with coefficients c_0, ..., c_n at z (horner's scheme)
+pub fn poly_eval(coeffs: &[f64], z: Complex<f64>) -> Complex<f64> {
+    coeffs
+        .iter()
+        .rev()
+        .fold(Complex::new(0.0, 0.0), |acc, c| acc * z + c)
+}
+
+// Roots of the polynomial with coefficients c_0, ..., c_n (with multiplicity),
+// sorted by real then imaginary part
+pub fn poly_roots(coeffs: &[f64]) -> Result<Vec<Complex<f64>>, &'static str> {
+    if coeffs.iter().any(|c| !c.is_finite()) {
+        return Err("[POLY ROOTS] Coefficients must be finite");
+    }
+    let degree = match coeffs.iter().rposition(|c| *c != 0.0) {
+        Some(degree) => degree,
+        None => return Err("[POLY ROOTS] The zero polynomial has no isolated roots"),
+    };
+    let zeros = coeffs.iter().position(|c| *c != 0.0).unwrap();
+    let reduced = &coeffs[zeros..=degree];
+    let n = reduced.len() - 1;
+
+    let mut roots = vec![Complex::new(0.0, 0.0); zeros];
+    if n > 0 {
+        // companion matrix of the monic polynomial
+        let mut companion = DMatrix::<f64>::zeros(n, n);
+        for i in 1..n {
+            companion[(i, i - 1)] = 1.0;
+        }
+        for i in 0..n {
+            companion[(i, n - 1)] = -reduced[i] / reduced[n];
+        }
+        balance(&mut companion);
+        for root in eigenvalues(companion)? {
+            roots.push(polish(reduced, root));
+        }
+    }
+    roots.sort_by(|a, b| {
+        a.re.partial_cmp(&b.re)
+            .unwrap()
+            .then(a.im.partial_cmp(&b.im).unwrap())
+    });
+    Ok(roots)
+}
+
+// Newton iterations on the polynomial from an eigenvalue estimate of a root, kept
+// only while they reduce |p|
+fn polish(coeffs: &[f64], root: Complex<f64>) -> Complex<f64> {
+    let derivative: Vec<f64> = coeffs
+        .iter()
+        .enumerate()
+        .skip(1)
+        .map(|(k, c)| k as f64 * c)
+        .collect();
+    let mut z = root;
+    let mut p_z = poly_eval(coeffs, z);
+    for _ in 0..3 {
+        let dp_z = poly_eval(&derivative, z);
+        if dp_z.norm_sqr() == 0.0 {
+            break;
+        }
+        let z_new = z - p_z / dp_z;
+        let p_new = poly_eval(coeffs, z_new);
+        let (size, size_new) = (p_z.norm_sqr(), p_new.norm_sqr());
+        if size_new.is_nan() || size_new >= size {
+            break;
+        }
+        z = z_new;
+        p_z = p_new;
+    }
+    // roots of real polynomials come in conjugate pairs: snap round-off imaginary parts
+    if z.im.abs() <= 4.0 * f64::EPSILON * z.re.abs() {
+        z.im = 0.0;
+    }
+    z
+}
+
+// Scales rows and columns of a matrix by powers of 2 (a similarity transform, so
+// the eigenvalues are exact) until their norms are comparable
+fn balance(mat: &mut DMatrix<f64>) {
+    const RADIX: f64 = 2.0;
+    let n = mat.nrows();
+    let mut converged = false;
+    while !converged {
+        converged = true;
+        for i in 0..n {
+            let col: f64 = (0..n).filter(|j| *j != i).map(|j| mat[(j, i)].abs()).sum();
+            let row: f64 = (0..n).filter(|j| *j != i).map(|j| mat[(i, j)].abs()).sum();
+            if col == 0.0 || row == 0.0 {
+                continue;
+            }
+            let sum = col + row;
+            let mut f = 1.0;
+            let (mut c, mut r) = (col, row);
+            while c < r / RADIX {
+                f *= RADIX;
+                c *= RADIX;
+                r /= RADIX;
+            }
+            while c > r * RADIX {
+                f /= RADIX;
+                c /= RADIX;
+                r *= RADIX;
+            }
+            if c + r < 0.95 * sum {
+                converged = false;
+                for j in 0..n {
+                    mat[(i, j)] /= f;
+                    mat[(j, i)] *= f;
+                }
+            }
+        }
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_poly_roots() {
+        // (x - 1)(x + 2)(x^2 + 4) x^2 = x^6 + x^5 + 2 x^4 + 4 x^3 - 8 x^2, with a
+        // vanishing x^7 coefficient
+        let roots = poly_roots(&[0.0, 0.0, -8.0, 4.0, 2.0, 1.0, 1.0, 0.0]).unwrap();
+        let truth = [
+            Complex::new(-2.0, 0.0),
+            Complex::new(0.0, -2.0),
+            Complex::new(0.0, 2.0),
+            Complex::new(0.0, 0.0),
+            Complex::new(1.0, 0.0),
+        ];
+        assert_eq!(roots.len(), 6);
+        assert_eq!(
+            roots.iter().filter(|root| root.norm_sqr() == 0.0).count(),
+            2
+        );
+        for truth in truth.iter() {
+            assert!(roots
+                .iter()
+                .any(|root| (root - truth).norm_sqr().sqrt() < 1e-12));
+        }
+
+        // wilkinson's polynomial prod (x - k) for k = 1..10 with coefficients over
+        // seven orders of magnitude
+        let mut coeffs = vec![1.0];
+        for k in 1..=10 {
+            let mut next = vec![0.0; coeffs.len() + 1];
+            for (i, c) in coeffs.iter().enumerate() {
+                next[i] -= k as f64 * c;
+                next[i + 1] += c;
+            }
+            coeffs = next;
+        }
+        let roots = poly_roots(&coeffs).unwrap();
+        for (k, root) in roots.iter().enumerate() {
+            assert!((root - Complex::new(k as f64 + 1.0, 0.0)).norm_sqr().sqrt() < 1e-8);
+        }
+
+        assert!(poly_roots(&[0.0, 0.0]).is_err());
+        assert!(poly_roots(&[3.0]).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,251 @@
+/// Preconditioners
+///
+/// Defines the `Preconditioner` trait used by the krylov solvers in `utils::linalg`
+/// to apply an approximate inverse M^-1 of the system matrix. Any closure of the
+/// form `Fn(&OVector<f64, N>) -> OVector<f64, N>` is a preconditioner so users can
+/// supply their own (e.g. a physics based or multigrid approximate solve).
+///
+/// Provided implementations built from a sparse (CSR) jacobian:
+/// - `Jacobi`: inverse of the diagonal. Cheap but only helps badly scaled systems
+/// - `Ilu0`: incomplete LU factorization with zero fill-in (Saad "Iterative Methods
+///   for Sparse Linear Systems", Algorithm 10.4). Much more effective on
+///   convection dominated problems where GMRES otherwise stalls
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DefaultAllocator, Dim, OVector};
+
+// local imports
+use super::sparse::CsrMatrix;
+
+// === End Imports ===
+
+pub trait Preconditioner<N: Dim>
+where
+    DefaultAllocator: Allocator<N>,
+{
+    // Applies the approximate inverse M^-1 v
+    fn apply(&self, v: &OVector<f64, N>) -> OVector<f64, N>;
+}
+
+impl<N: Dim, F> Preconditioner<N> for F
+where
+    F: Fn(&OVector<f64, N>) -> OVector<f64, N>,
+    DefaultAllocator: Allocator<N>,
+{
+    fn apply(&self, v: &OVector<f64, N>) -> OVector<f64, N> {
+        self(v)
+    }
+}
+
+// === Jacobi ===
+#[derive(Debug, Clone, PartialEq)]
+pub struct Jacobi {
+    // Reciprocal of the matrix diagonal
+    inv_diag: Vec<f64>,
+}
+
+impl Jacobi {
+    pub fn new(mat: &CsrMatrix) -> Result<Self, &'static str> {
+        let mut inv_diag = Vec::with_capacity(mat.nrows);
+        for i in 0..mat.nrows {
+            let diag = mat.get(i, i);
+            if diag == 0.0 {
+                return Err("[JACOBI] Zero on the matrix diagonal");
+            }
+            inv_diag.push(1.0 / diag);
+        }
+        Ok(Jacobi { inv_diag })
+    }
+}
+
+impl<N: Dim> Preconditioner<N> for Jacobi
+where
+    DefaultAllocator: Allocator<N>,
+{
+    fn apply(&self, v: &OVector<f64, N>) -> OVector<f64, N> {
+        let mut out = v.clone();
+        for (i, inv) in self.inv_diag.iter().enumerate() {
+            out[i] *= inv;
+        }
+        out
+    }
+}
+
+// === ILU(0) ===
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ilu0 {
+    // L (unit diagonal, not stored) and U factors sharing the sparsity of the input
+    factors: CsrMatrix,
+    // Position of the diagonal entry of each row in `factors`
+    diag_pos: Vec<usize>,
+}
+
+impl Ilu0 {
+    pub fn new(mat: &CsrMatrix) -> Result<Self, &'static str> {
+        let n = mat.nrows;
+        let mut factors = mat.clone();
+
+        let mut diag_pos = Vec::with_capacity(n);
+        for i in 0..n {
+            let (cols, _) = factors.row(i);
+            match cols.binary_search(&i) {
+                Ok(pos) => diag_pos.push(factors.row_ptr[i] + pos),
+                Err(_) => return Err("[ILU0] Missing diagonal entry in sparsity pattern"),
+            }
+        }
+
+        // position of each column of the current row (IKJ variant)
+        let mut col_pos: Vec<Option<usize>> = vec![None; n];
+        for i in 0..n {
+            let start = factors.row_ptr[i];
+            let end = factors.row_ptr[i + 1];
+            for pos in start..end {
+                col_pos[factors.col_idx[pos]] = Some(pos);
+            }
+
+            for pos in start..end {
+                let k = factors.col_idx[pos];
+                if k >= i {
+                    break;
+                }
+                let pivot = factors.values[diag_pos[k]];
+                if pivot == 0.0 {
+                    return Err("[ILU0] Zero pivot");
+                }
+                factors.values[pos] /= pivot;
+                let l_ik = factors.values[pos];
+
+                // update the rest of row i, only where row i already has entries
+                for k_pos in diag_pos[k] + 1..factors.row_ptr[k + 1] {
+                    if let Some(i_pos) = col_pos[factors.col_idx[k_pos]] {
+                        factors.values[i_pos] -= l_ik * factors.values[k_pos];
+                    }
+                }
+            }
+
+            for pos in start..end {
+                col_pos[factors.col_idx[pos]] = None;
+            }
+            if factors.values[diag_pos[i]] == 0.0 {
+                return Err("[ILU0] Zero pivot");
+            }
+        }
+
+        Ok(Ilu0 { factors, diag_pos })
+    }
+}
+
+impl<N: Dim> Preconditioner<N> for Ilu0
+where
+    DefaultAllocator: Allocator<N>,
+{
+    fn apply(&self, v: &OVector<f64, N>) -> OVector<f64, N> {
+        let lu = &self.factors;
+        let mut out = v.clone();
+
+        // forward solve L y = v
+        for i in 0..lu.nrows {
+            let mut sum = out[i];
+            for pos in lu.row_ptr[i]..self.diag_pos[i] {
+                sum -= lu.values[pos] * out[lu.col_idx[pos]];
+            }
+            out[i] = sum;
+        }
+
+        // backward solve U x = y
+        for i in (0..lu.nrows).rev() {
+            let mut sum = out[i];
+            for pos in self.diag_pos[i] + 1..lu.row_ptr[i + 1] {
+                sum -= lu.values[pos] * out[lu.col_idx[pos]];
+            }
+            out[i] = sum / lu.values[self.diag_pos[i]];
+        }
+        out
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::linalg::{gmres, KrylovSolver};
+    use na::{DMatrix, DVector};
+
+    // Upwinded convection-diffusion on an m x m grid (5 point stencil) with a
+    // strong convective velocity in x and y
+    fn convection_diffusion(m: usize) -> CsrMatrix {
+        const EPS: f64 = 1.0e-3;
+        const VEL: f64 = 1.0;
+        let h = 1.0 / (m + 1) as f64;
+        let idx = |i: usize, j: usize| i * m + j;
+        let mut triplets = Vec::new();
+        for i in 0..m {
+            for j in 0..m {
+                let row = idx(i, j);
+                triplets.push((row, row, 4.0 * EPS / h.powi(2) + 2.0 * VEL / h));
+                if i > 0 {
+                    triplets.push((row, idx(i - 1, j), -EPS / h.powi(2) - VEL / h));
+                }
+                if j > 0 {
+                    triplets.push((row, idx(i, j - 1), -EPS / h.powi(2) - VEL / h));
+                }
+                if i + 1 < m {
+                    triplets.push((row, idx(i + 1, j), -EPS / h.powi(2)));
+                }
+                if j + 1 < m {
+                    triplets.push((row, idx(i, j + 1), -EPS / h.powi(2)));
+                }
+            }
+        }
+        CsrMatrix::from_triplets(m * m, m * m, &triplets).unwrap()
+    }
+
+    #[test]
+    fn test_ilu0_exact_for_tridiagonal() {
+        // no fill-in happens for a tri-diagonal matrix so ILU(0) is the exact LU
+        let dense = DMatrix::<f64>::from_fn(6, 6, |i, j| match (i as i32 - j as i32).abs() {
+            0 => 3.0,
+            1 => -1.0,
+            _ => 0.0,
+        });
+        let ilu = Ilu0::new(&CsrMatrix::from_dense(&dense)).unwrap();
+        let b = DVector::<f64>::from_fn(6, |i, _| i as f64 + 1.0);
+        let sol = dense.lu().solve(&b).unwrap();
+        assert!((ilu.apply(&b) - sol).norm() < 1e-12);
+    }
+
+    #[test]
+    fn test_preconditioned_convection_diffusion() {
+        let mat = convection_diffusion(20);
+        let n = mat.nrows;
+        let b = DVector::<f64>::repeat(n, 1.0);
+        let sol = mat.to_dense().lu().solve(&b).unwrap();
+        let op = |x: &DVector<f64>| mat.mul_vec(x);
+
+        let solve = |precond: Option<&dyn Preconditioner<na::Dyn>>| {
+            gmres(op, &b, DVector::zeros(n), 1e-10, 20, 2000, precond)
+                .expect("Couldn't converge to solution")
+        };
+        let jacobi = Jacobi::new(&mat).unwrap();
+        let ilu = Ilu0::new(&mat).unwrap();
+        let plain = solve(None);
+        let with_jacobi = solve(Some(&jacobi));
+        let with_ilu = solve(Some(&ilu));
+
+        for ans in &[&plain, &with_jacobi, &with_ilu] {
+            assert!((&ans.x - &sol).norm() / sol.norm() < 1e-8);
+        }
+        assert!(with_ilu.iterations() < with_jacobi.iterations());
+        assert!(with_ilu.iterations() < plain.iterations());
+
+        // user provided preconditioners are plain closures
+        let user = |v: &DVector<f64>| ilu.apply(v);
+        let with_user = crate::utils::linalg::Gmres::default()
+            .solve(op, &b, DVector::zeros(n), 1e-10, Some(&user))
+            .unwrap();
+        assert_eq!(with_user.iterations(), with_ilu.iterations());
+    }
+}
@@ -0,0 +1,159 @@
+/// Reproducible Random Numbers (rng)
+///
+/// Random number plumbing for stochastic dynamics and Monte Carlo ensembles. The
+/// results of an ensemble run on a thread pool must not depend on which thread
+/// picked up which trajectory, or in what order, so the draws of a trajectory may
+/// not come from a generator shared between the trajectories. `SplitRng` is a
+/// seedable generator which splits into independent substreams instead: the
+/// substream of trajectory i is a pure function of the root seed and i, so every
+/// trajectory sees the same numbers whatever the scheduling (see
+/// `sweep::monte_carlo`). Substreams can be split again (e.g. per trajectory, then
+/// per restart or per noise source).
+///
+/// The generator is xoshiro256++ (Blackman & Vigna, "Scrambled linear pseudorandom
+/// number generators", 2021), with its state expanded from the 64 bit stream key
+/// by SplitMix64 as its authors recommend. Stream keys are derived by mixing the
+/// parent key with the substream index through the SplitMix64 finalizer. It is
+/// fast and of high statistical quality, but not cryptographic.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DefaultAllocator, Dim, DimName, OVector};
+
+// Standard library imports
+use std::f64::consts::PI;
+
+// === End Imports ===
+
+// Increment of the SplitMix64 sequence (golden ratio)
+const GOLDEN_GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+
+// SplitMix64 finalizer: a bijective mixing of 64 bits
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SplitRng {
+    // Key of the stream, from which substreams are derived
+    key: u64,
+    // xoshiro256++ state
+    state: [u64; 4],
+}
+
+impl SplitRng {
+    pub fn new(seed: u64) -> Self {
+        let mut sm = seed;
+        let mut state = [0; 4];
+        for s in state.iter_mut() {
+            sm = sm.wrapping_add(GOLDEN_GAMMA);
+            *s = mix(sm);
+        }
+        SplitRng { key: seed, state }
+    }
+
+    // Independent stream number `index` of this one. Depends only on the key of
+    // this stream and the index, not on the numbers drawn from it so far
+    pub fn substream(&self, index: u64) -> SplitRng {
+        SplitRng::new(mix(self.key ^ mix(index.wrapping_add(GOLDEN_GAMMA))))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let s = &mut self.state;
+        let out = (s[0].wrapping_add(s[3])).rotate_left(23).wrapping_add(s[0]);
+        let t = s[1] << 17;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+        out
+    }
+
+    // Uniform on [0, 1), with the 53 bits of an f64 significand
+    pub fn uniform(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+    }
+
+    // Standard normal (Box-Muller)
+    pub fn normal(&mut self) -> f64 {
+        // 1 - u is in (0, 1], keeping the logarithm finite
+        let radius = (-2.0 * (1.0 - self.uniform()).ln()).sqrt();
+        radius * (2.0 * PI * self.uniform()).cos()
+    }
+
+    // Vector of independent standard normals
+    pub fn normal_vector<N: Dim + DimName>(&mut self) -> OVector<f64, N>
+    where
+        DefaultAllocator: Allocator<N>,
+    {
+        OVector::<f64, N>::from_fn(|_, _| self.normal())
+    }
+
+    // Increment of an N dimensional wiener process over a step dt
+    pub fn wiener_increment<N: Dim + DimName>(&mut self, dt: f64) -> OVector<f64, N>
+    where
+        DefaultAllocator: Allocator<N>,
+    {
+        self.normal_vector::<N>() * dt.abs().sqrt()
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::sweep::monte_carlo;
+    use na::{Vector1, Vector3};
+
+    #[test]
+    fn test_split_rng() {
+        // reference values of xoshiro256++ seeded with SplitMix64 from 0
+        let mut rng = SplitRng::new(0);
+        let first: Vec<u64> = (0..2).map(|_| rng.next_u64()).collect();
+        assert_eq!(first, vec![0x53175d61490b23df, 0x61da6f3dc380d507]);
+
+        // substreams ignore the draws of their parent and differ from each other
+        let root = SplitRng::new(42);
+        let mut used = root.clone();
+        used.next_u64();
+        assert_eq!(used.substream(3), root.substream(3));
+        assert_ne!(root.substream(3), root.substream(4));
+        assert_ne!(
+            root.substream(3).substream(0),
+            root.substream(0).substream(3)
+        );
+
+        // moments of the normals
+        let mut rng = root.substream(0);
+        let draws: Vec<f64> = (0..200_000).map(|_| rng.normal()).collect();
+        let mean = draws.iter().sum::<f64>() / draws.len() as f64;
+        let var = draws.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / draws.len() as f64;
+        assert!(mean.abs() < 0.01 && (var - 1.0).abs() < 0.01);
+        let dw: Vector3<f64> = rng.wiener_increment(0.0);
+        assert_eq!(dw, Vector3::zeros());
+
+        // geometric brownian motion dX = mu X dt + sigma X dW by euler-maruyama,
+        // E[X(1)] = exp(mu). The ensemble is the same for any number of threads
+        let (mu, sigma, dt) = (0.5, 0.3, 0.01);
+        let gbm = |_: usize, rng: &mut SplitRng| {
+            let mut x = Vector1::new(1.0);
+            for _ in 0..100 {
+                let dw: Vector1<f64> = rng.wiener_increment(dt);
+                x += x * (mu * dt) + (x * sigma).component_mul(&dw);
+            }
+            x[0]
+        };
+        let finals = monte_carlo(7, 4000, Some(4), gbm);
+        assert_eq!(finals, monte_carlo(7, 4000, Some(1), gbm));
+        assert_ne!(finals, monte_carlo(8, 4000, Some(4), gbm));
+        let mean = finals.iter().sum::<f64>() / finals.len() as f64;
+        println!("GBM MEAN {} | EXACT {}", mean, mu.exp());
+        assert!((mean - mu.exp()).abs() < 0.02);
+    }
+}
@@ -0,0 +1,132 @@
+/// Solver Dimensions (solver_dim)
+///
+/// Bounds for the dimension of a problem solved with the dense newton solvers
+/// (and everything built on them: the implicit correctors, backward euler, RIDC).
+/// Those need nalgebra to allocate vectors, square matrices and the intermediates
+/// of the SVD for the dimension, which otherwise takes a where clause of some ten
+/// lines that every generic caller has to repeat. With these a generic caller
+/// needs two:
+///
+/// - `N: SolverDim` for the dimension arithmetic (square matrices whose SVD is
+///   taken)
+/// - `DefaultAllocator: SolverAllocator<N>` for the storage, including states
+///   which can be sent to the RIDC corrector threads
+///
+/// nalgebra only allocates storage for dimensions it knows to be compile time
+/// constants, which a trait on the dimension cannot promise, so the storage bound
+/// is stated on the allocator (as a supertrait, which the compiler carries into
+/// every function bounded by it). Both traits are sealed and implemented for every
+/// compile time dimension (U1, U2, ...), which lets the bounds grow with the
+/// internals without breaking downstream signatures.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DefaultAllocator, DimDiff, DimMin, DimName, DimSub, U1};
+
+// === End Imports ===
+
+mod sealed {
+    pub trait Sealed {}
+
+    impl<N: super::DimName> Sealed for N {}
+
+    pub trait SealedAllocator {}
+
+    impl SealedAllocator for super::DefaultAllocator {}
+}
+
+pub trait SolverDim: sealed::Sealed + DimName + DimMin<Self, Output = Self> + DimSub<U1> {}
+
+impl<N: DimName + DimMin<N, Output = N> + DimSub<U1>> SolverDim for N {}
+
+pub trait SolverAllocator<N: SolverDim>:
+    sealed::SealedAllocator
+    + Allocator<N, Buffer<f64>: Send + Sync>
+    + Allocator<N, N>
+    + Allocator<DimDiff<N, U1>>
+{
+}
+
+impl<N: SolverDim> SolverAllocator<N> for DefaultAllocator where
+    DefaultAllocator:
+        Allocator<N, Buffer<f64>: Send + Sync> + Allocator<N, N> + Allocator<DimDiff<N, U1>>
+{
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ridc::base::RIDCIntegratorFixed;
+    use crate::ridc::common::IntegOptionsParallel;
+    use crate::runge_kutta::rk_simp::RK4;
+    use crate::utils::euler::bwd_euler;
+    use crate::utils::linalg::Gmres;
+    use crate::utils::newton_raphson::{newton_krylov, newton_raphson_broyden};
+    use na::{DVector, Dim, OVector, SVector, Vector1, Vector3};
+    use std::thread;
+
+    // Downstream generic code, bounded by SolverDim and SolverAllocator alone
+    fn decay_to<N: SolverDim>(y_0: &OVector<f64, N>, t_end: f64) -> [OVector<f64, N>; 3]
+    where
+        DefaultAllocator: SolverAllocator<N>,
+    {
+        let decay = |_t: f64, y: &OVector<f64, N>| -y;
+        let implicit = bwd_euler(0.0, y_0, &decay, t_end).unwrap();
+        let root = newton_raphson_broyden(|y: &OVector<f64, N>| y - &implicit, y_0.clone(), 1e-12)
+            .unwrap();
+        let ridc = RK4
+            .parallel_integrator(decay, 0.0, y_0, t_end, 0.1, IntegOptionsParallel::default())
+            .unwrap();
+        [implicit, root, ridc.last_y().clone()]
+    }
+
+    #[test]
+    fn test_solver_dim_bound() {
+        let [implicit, root, ridc] = decay_to(&Vector3::new(1.0, 2.0, 3.0), 1.0);
+        assert!((implicit - Vector3::new(0.5, 1.0, 1.5)).amax() < 1e-9);
+        assert!((root - implicit).amax() < 1e-9);
+        assert!((ridc - Vector3::new(1.0, 2.0, 3.0) * (-1.0_f64).exp()).amax() < 1e-5);
+
+        let [implicit, _, _] = decay_to(&Vector1::new(2.0), 1.0);
+        assert!((implicit[0] - 1.0).abs() < 1e-9);
+    }
+
+    // Downstream generic code over any dimension, dynamic or compile time, bounded
+    // by the storage of the state alone
+    fn cube_root<N: Dim>(target: &OVector<f64, N>) -> OVector<f64, N>
+    where
+        DefaultAllocator: Allocator<N>,
+    {
+        let cube = |y: &OVector<f64, N>| y.map(|val| val.powi(3)) - target;
+        newton_krylov(cube, target.map(|_| 1.0), 1e-12, &Gmres::default(), None).unwrap()
+    }
+
+    // State moved to another thread under the solver bounds alone
+    fn doubled_elsewhere<N: SolverDim>(y: OVector<f64, N>) -> OVector<f64, N>
+    where
+        DefaultAllocator: SolverAllocator<N>,
+    {
+        thread::spawn(move || y * 2.0).join().unwrap()
+    }
+
+    #[test]
+    fn test_dyn_and_dim_name_bounds() {
+        // the same root of a dynamic and a compile time dimension
+        let dynamic = cube_root(&DVector::from_column_slice(&[1.0, 8.0, 27.0]));
+        let fixed = cube_root(&Vector3::new(1.0, 8.0, 27.0));
+        assert_eq!(dynamic.nrows(), 3);
+        assert!((fixed - Vector3::new(1.0, 2.0, 3.0)).amax() < 1e-9);
+        assert!((dynamic - DVector::from_column_slice(fixed.as_slice())).amax() < 1e-12);
+
+        // the smallest dimension (whose DimDiff is U0) and the largest of the CLI
+        assert_eq!(doubled_elsewhere(Vector1::new(1.5))[0], 3.0);
+        let y_0 = SVector::<f64, 12>::from_fn(|i, _| i as f64);
+        assert_eq!(doubled_elsewhere(y_0), y_0 * 2.0);
+        let [implicit, _, ridc] = decay_to(&y_0, 1.0);
+        assert!((implicit - y_0 * 0.5).amax() < 1e-9);
+        assert!((ridc - y_0 * (-1.0_f64).exp()).amax() < 1e-4);
+    }
+}
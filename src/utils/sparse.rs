@@ -0,0 +1,161 @@
+/// Sparse Matrices
+///
+/// A minimal compressed sparse row (CSR) matrix used to store large sparse
+/// jacobians (e.g. from discretized PDEs) for preconditioning and matrix-vector
+/// products inside the krylov solvers.
+///
+/// Column indices in each row are kept sorted and duplicate entries are summed
+/// on construction.
+///
+// === Begin Imports ===
+// third party imports
+extern crate nalgebra as na;
+use na::allocator::Allocator;
+use na::{DMatrix, DefaultAllocator, Dim, OVector};
+
+// === End Imports ===
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsrMatrix {
+    // Number of rows
+    pub nrows: usize,
+    // Number of columns
+    pub ncols: usize,
+    // Offsets into `col_idx` and `values` for the start of each row (len nrows + 1)
+    pub row_ptr: Vec<usize>,
+    // Column index of each stored entry
+    pub col_idx: Vec<usize>,
+    // Value of each stored entry
+    pub values: Vec<f64>,
+}
+
+impl CsrMatrix {
+    // Builds a matrix from (row, col, value) triplets
+    pub fn from_triplets(
+        nrows: usize,
+        ncols: usize,
+        triplets: &[(usize, usize, f64)],
+    ) -> Result<Self, &'static str> {
+        let mut sorted = triplets.to_vec();
+        for (row, col, _) in &sorted {
+            if *row >= nrows || *col >= ncols {
+                return Err("[CSR] Triplet index out of bounds");
+            }
+        }
+        sorted.sort_by_key(|triplet| (triplet.0, triplet.1));
+
+        let mut row_ptr = vec![0; nrows + 1];
+        let mut col_idx: Vec<usize> = Vec::with_capacity(sorted.len());
+        let mut values: Vec<f64> = Vec::with_capacity(sorted.len());
+        let mut last: Option<(usize, usize)> = None;
+        for (row, col, val) in sorted {
+            if last == Some((row, col)) {
+                // duplicate entry
+                *values.last_mut().unwrap() += val;
+                continue;
+            }
+            col_idx.push(col);
+            values.push(val);
+            row_ptr[row + 1] += 1;
+            last = Some((row, col));
+        }
+        for i in 0..nrows {
+            row_ptr[i + 1] += row_ptr[i];
+        }
+
+        Ok(CsrMatrix {
+            nrows,
+            ncols,
+            row_ptr,
+            col_idx,
+            values,
+        })
+    }
+
+    // Builds a matrix from the non-zero entries of a dense matrix
+    pub fn from_dense(mat: &DMatrix<f64>) -> Self {
+        let mut triplets = Vec::new();
+        for i in 0..mat.nrows() {
+            for j in 0..mat.ncols() {
+                if mat[(i, j)] != 0.0 {
+                    triplets.push((i, j, mat[(i, j)]));
+                }
+            }
+        }
+        CsrMatrix::from_triplets(mat.nrows(), mat.ncols(), &triplets).unwrap()
+    }
+
+    // Number of stored entries
+    pub fn nnz(&self) -> usize {
+        self.values.len()
+    }
+
+    // Column indices and values stored in row i
+    pub fn row(&self, i: usize) -> (&[usize], &[f64]) {
+        let range = self.row_ptr[i]..self.row_ptr[i + 1];
+        (&self.col_idx[range.clone()], &self.values[range])
+    }
+
+    // Stored value at (i, j) or zero if the entry is not in the sparsity pattern
+    pub fn get(&self, i: usize, j: usize) -> f64 {
+        let (cols, vals) = self.row(i);
+        match cols.binary_search(&j) {
+            Ok(pos) => vals[pos],
+            Err(_) => 0.0,
+        }
+    }
+
+    // Matrix-vector product A v (square matrices only, the result shares the
+    // dimension of v)
+    pub fn mul_vec<N: Dim>(&self, v: &OVector<f64, N>) -> OVector<f64, N>
+    where
+        DefaultAllocator: Allocator<N>,
+    {
+        let mut out = v.clone();
+        for i in 0..self.nrows {
+            let (cols, vals) = self.row(i);
+            out[i] = cols.iter().zip(vals).map(|(j, a)| a * v[*j]).sum();
+        }
+        out
+    }
+
+    pub fn to_dense(&self) -> DMatrix<f64> {
+        let mut mat = DMatrix::<f64>::zeros(self.nrows, self.ncols);
+        for i in 0..self.nrows {
+            let (cols, vals) = self.row(i);
+            for (j, val) in cols.iter().zip(vals) {
+                mat[(i, *j)] = *val;
+            }
+        }
+        mat
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use na::DVector;
+
+    #[test]
+    fn test_csr_round_trip() {
+        let triplets = vec![
+            (0, 0, 2.0),
+            (1, 2, -1.0),
+            (0, 1, 1.0),
+            (1, 2, 0.5),
+            (2, 0, 3.0),
+        ];
+        let mat = CsrMatrix::from_triplets(3, 3, &triplets).unwrap();
+        assert_eq!(mat.nnz(), 4);
+        assert_eq!(mat.get(1, 2), -0.5);
+        assert_eq!(mat.get(1, 1), 0.0);
+
+        let dense = mat.to_dense();
+        assert_eq!(CsrMatrix::from_dense(&dense), mat);
+
+        let v = DVector::from_vec(vec![1.0, 2.0, 3.0]);
+        assert!((mat.mul_vec(&v) - &dense * &v).norm() < 1e-14);
+        assert!(CsrMatrix::from_triplets(3, 3, &[(3, 0, 1.0)]).is_err());
+    }
+}
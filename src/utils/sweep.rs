@@ -0,0 +1,136 @@
+/// Parameter Sweeps (sweep)
+///
+/// Propagates one case per parameter value in parallel. The caller supplies the
+/// parameter values (a list, or a grid built with `grid`) and a closure which builds
+/// and integrates the system for one value. The closure can return the full
+/// `IntegResult` or reduce it to whatever summary is needed (final state, an event
+/// time, ...) so large sweeps do not have to hold every trajectory in memory.
+///
+/// Cases are handed out to a pool of worker threads one at a time, so cases with
+/// very different run times still balance across the threads. Results are returned
+/// in the order of the parameter values.
+///
+/// Monte Carlo ensembles (`monte_carlo`) hand each trial its own substream of a
+/// seeded random number generator (see `rng`), so the results are reproducible
+/// whatever the thread count or the order the trials run in.
+///
+// === Begin Imports ===
+// local imports
+use super::rng::SplitRng;
+
+// Standard library imports
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+// === End Imports ===
+
+// Runs `case` for every parameter value on up to `threads` worker threads (all
+// available cores when None)
+pub fn sweep<P, R, F>(params: &[P], threads: Option<usize>, case: F) -> Vec<R>
+where
+    P: Sync,
+    R: Send,
+    F: Fn(&P) -> R + Sync,
+{
+    let threads = threads
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()))
+        .clamp(1, params.len().max(1));
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<R>>> = Mutex::new(params.iter().map(|_| None).collect());
+
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| loop {
+                let idx = next.fetch_add(1, Ordering::Relaxed);
+                if idx >= params.len() {
+                    break;
+                }
+                let res = case(&params[idx]);
+                results.lock().expect("[SWEEP] Result store poisoned")[idx] = Some(res);
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .expect("[SWEEP] Result store poisoned")
+        .into_iter()
+        .map(|res| res.expect("[SWEEP] Case did not run"))
+        .collect()
+}
+
+// Runs `case` for trials 0..trials, each with substream i of the generator seeded
+// with `seed`
+pub fn monte_carlo<R, F>(seed: u64, trials: usize, threads: Option<usize>, case: F) -> Vec<R>
+where
+    R: Send,
+    F: Fn(usize, &mut SplitRng) -> R + Sync,
+{
+    let root = SplitRng::new(seed);
+    let indices: Vec<usize> = (0..trials).collect();
+    sweep(&indices, threads, |i| {
+        case(*i, &mut root.substream(*i as u64))
+    })
+}
+
+// Every combination of two lists of parameter values (the first varying slowest)
+pub fn grid<A: Clone, B: Clone>(first: &[A], second: &[B]) -> Vec<(A, B)> {
+    first
+        .iter()
+        .flat_map(|a| second.iter().map(move |b| (a.clone(), b.clone())))
+        .collect()
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runge_kutta::common::IntegOptions;
+    use crate::runge_kutta::fixed::FixedStep;
+    use crate::runge_kutta::rk_simp::RK4;
+    use nalgebra::Vector1;
+
+    #[derive(Debug, Clone, Copy)]
+    struct Decay {
+        rate: f64,
+        forcing: f64,
+    }
+
+    #[test]
+    fn test_sweep() {
+        let params: Vec<Decay> = grid(&[0.5, 1.0, 2.0], &[0.0, 1.0])
+            .into_iter()
+            .map(|(rate, forcing)| Decay { rate, forcing })
+            .collect();
+        assert_eq!(params.len(), 6);
+
+        // each case is reduced to its final value
+        let finals = sweep(&params, Some(4), |p| {
+            let p = *p;
+            let fxn = move |_t: f64, y: &Vector1<f64>| Vector1::new(p.forcing - p.rate * y[0]);
+            RK4.integrate(
+                fxn,
+                0.0,
+                Vector1::new(1.0),
+                1.0,
+                0.01,
+                IntegOptions::default(),
+            )
+            .map(|ans| ans.last_y()[0])
+        });
+        for (p, y) in params.iter().zip(finals) {
+            let steady = p.forcing / p.rate;
+            let truth = steady + (1.0 - steady) * (-p.rate).exp();
+            assert!((y.unwrap() - truth).abs() < 1e-9);
+        }
+
+        // the result order follows the parameters whatever the thread count
+        let values: Vec<usize> = (0..20).collect();
+        assert_eq!(
+            sweep(&values, None, |v| 2 * v),
+            sweep(&values, Some(1), |v| 2 * v)
+        );
+        assert!(sweep(&Vec::<usize>::new(), None, |v| *v).is_empty());
+    }
+}